@@ -0,0 +1,41 @@
+from typing import Any, Callable, Optional
+
+class ConversionError(Exception): ...
+
+class Converter:
+    def __init__(
+        self,
+        input_format: str,
+        output_format: str,
+        *,
+        csv: Optional[dict[str, Any]] = None,
+        xml: Optional[dict[str, Any]] = None,
+        transform: Optional[dict[str, Any]] = None,
+        functions: Optional[dict[str, Callable[..., Any]]] = None,
+        options: Optional[dict[str, Any]] = None,
+        profile: bool = False,
+    ) -> None: ...
+    def push(self, chunk: bytes) -> bytes: ...
+    def finish(self) -> bytes: ...
+    def stats(self) -> dict[str, float]: ...
+    def warnings(self) -> list[dict[str, Any]]: ...
+    def errors(self) -> list[dict[str, Any]]: ...
+    def take_rejects(self) -> bytes: ...
+    def detection(self) -> Optional[dict[str, Any]]: ...
+    def csv_schema(self) -> Optional[dict[str, Any]]: ...
+    def is_stopped(self) -> bool: ...
+    def matched_record(self) -> Optional[dict[str, Any]]: ...
+
+def convert(
+    data: bytes,
+    input_format: str,
+    output_format: str,
+    *,
+    csv: Optional[dict[str, Any]] = None,
+    xml: Optional[dict[str, Any]] = None,
+    transform: Optional[dict[str, Any]] = None,
+    functions: Optional[dict[str, Callable[..., Any]]] = None,
+    options: Optional[dict[str, Any]] = None,
+) -> bytes: ...
+def detect_format(sample: bytes) -> Optional[str]: ...
+def detect_structure(sample: bytes, format_hint: Optional[str] = None) -> Optional[dict[str, Any]]: ...
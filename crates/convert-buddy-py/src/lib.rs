@@ -0,0 +1,367 @@
+//! Python bindings for the convert-buddy streaming converters.
+//!
+//! Dictionaries passed in or returned use the same camelCase keys as the JS
+//! package, so its option docs apply as is.
+
+use convert_buddy::{
+    detect, AutoDetect, ColumnType, ConvertError, CsvColumn, Converter as Engine, ConverterConfig, ConverterOptionsInput,
+    CsvConfig, ExpressionFunction, Format, FunctionRegistry, NamespacePolicy, TransformConfigInput,
+    TransformPlan, XmlConfig,
+};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyTuple};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+create_exception!(convert_buddy, ConversionError, PyException, "A conversion failed.");
+
+fn conversion_error(error: ConvertError) -> PyErr {
+    ConversionError::new_err(error.to_string())
+}
+
+/// Read a Python value through `json.dumps`.
+fn from_python<T: DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json: String = value
+        .py()
+        .import("json")?
+        .call_method1("dumps", (value,))?
+        .extract()?;
+    serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+/// Build a Python value through `json.loads`.
+fn to_python<T: Serialize + ?Sized>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CsvOptions {
+    delimiter: Option<String>,
+    quote: Option<String>,
+    has_headers: Option<bool>,
+    trim_whitespace: Option<bool>,
+    infer_types: Option<bool>,
+    column_types: Option<HashMap<String, ColumnType>>,
+    columns: Option<Vec<CsvColumn>>,
+    unflatten: Option<bool>,
+    skip_rows: Option<usize>,
+    header_row: Option<usize>,
+    max_rows: Option<usize>,
+    comment_char: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct XmlOptions {
+    record_element: Option<RecordElement>,
+    record_path: Option<String>,
+    tag_element: Option<bool>,
+    namespaces: Option<NamespacePolicy>,
+    trim_text: Option<bool>,
+    include_attributes: Option<bool>,
+    expand_entities: Option<bool>,
+}
+
+/// `recordElement`: one element or path, or a list of them.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RecordElement {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// A Python callable used as a custom compute expression function.
+struct PythonFunction(PyObject);
+
+impl ExpressionFunction for PythonFunction {
+    fn call(&self, args: &[Value]) -> convert_buddy::Result<Value> {
+        Python::with_gil(|py| {
+            let args = args
+                .iter()
+                .map(|arg| to_python(py, arg))
+                .collect::<PyResult<Vec<_>>>()?;
+            let result = self.0.call1(py, PyTuple::new(py, args)?)?;
+            from_python(result.bind(py))
+        })
+        .map_err(|e| ConvertError::Transform(format!("Python function failed: {e}")))
+    }
+}
+
+fn parse_format(name: &str) -> PyResult<Format> {
+    Format::from_string(name).ok_or_else(|| PyValueError::new_err(format!("Invalid format: {name}")))
+}
+
+/// Build a config the way the JS `withConfig` does: whatever isn't given is
+/// detected from the first chunk.
+#[allow(clippy::too_many_arguments)]
+fn build_config(
+    input_format: &str,
+    output_format: &str,
+    csv: Option<&Bound<'_, PyAny>>,
+    xml: Option<&Bound<'_, PyAny>>,
+    transform: Option<&Bound<'_, PyAny>>,
+    functions: Option<&Bound<'_, PyDict>>,
+    options: Option<&Bound<'_, PyAny>>,
+    profile: bool,
+) -> PyResult<ConverterConfig> {
+    let detect_format = input_format.eq_ignore_ascii_case("auto");
+    let input = if detect_format { Format::Csv } else { parse_format(input_format)? };
+    let mut config = ConverterConfig::new(input, parse_format(output_format)?).with_stats(profile);
+
+    let csv: CsvOptions = csv.map(from_python).transpose()?.unwrap_or_default();
+    let mut csv_config = CsvConfig::default();
+    if let Some(&byte) = csv.delimiter.as_deref().and_then(|d| d.as_bytes().first()) {
+        csv_config.delimiter = byte;
+    }
+    if let Some(&byte) = csv.quote.as_deref().and_then(|q| q.as_bytes().first()) {
+        csv_config.quote = byte;
+        csv_config.escape = Some(byte);
+    }
+    csv_config.has_headers = csv.has_headers.unwrap_or(csv_config.has_headers);
+    csv_config.trim_whitespace = csv.trim_whitespace.unwrap_or(csv_config.trim_whitespace);
+    csv_config.infer_types = csv.infer_types.unwrap_or(csv_config.infer_types);
+    csv_config.column_types = csv.column_types.unwrap_or_default();
+    csv_config.columns = csv.columns.unwrap_or_default();
+    csv_config.unflatten = csv.unflatten.unwrap_or(csv_config.unflatten);
+    csv_config.skip_rows = csv.skip_rows.unwrap_or_default();
+    csv_config.header_row = csv.header_row.unwrap_or_default();
+    csv_config.max_rows = csv.max_rows;
+    csv_config.comment_char = csv.comment_char.as_deref().and_then(|c| c.as_bytes().first().copied());
+
+    let xml: XmlOptions = xml.map(from_python).transpose()?.unwrap_or_default();
+    let mut xml_config = XmlConfig::default();
+    match xml.record_element.as_ref() {
+        Some(RecordElement::One(element)) if !element.is_empty() => {
+            xml_config.record_element = element.clone();
+        }
+        Some(RecordElement::Many(elements)) if !elements.is_empty() => {
+            xml_config.record_element = elements[0].clone();
+            xml_config.record_elements = elements[1..].to_vec();
+        }
+        _ => {}
+    }
+    xml_config.record_path = xml.record_path.filter(|path| !path.is_empty());
+    xml_config.tag_element = xml.tag_element.unwrap_or(xml_config.tag_element);
+    xml_config.namespaces = xml.namespaces.unwrap_or(xml_config.namespaces);
+    xml_config.trim_text = xml.trim_text.unwrap_or(xml_config.trim_text);
+    xml_config.include_attributes = xml.include_attributes.unwrap_or(xml_config.include_attributes);
+    xml_config.expand_entities = xml.expand_entities.unwrap_or(xml_config.expand_entities);
+
+    config = config
+        .with_csv_config(csv_config)
+        .with_xml_config(xml_config)
+        .with_auto_detect(AutoDetect {
+            input_format: detect_format,
+            csv_delimiter: csv.delimiter.is_none(),
+            csv_quote: csv.quote.is_none(),
+            csv_has_headers: csv.has_headers.is_none(),
+            xml_record_element: xml.record_element.is_none(),
+        });
+
+    if let Some(transform) = transform {
+        let input: TransformConfigInput = from_python(transform)?;
+        let mut registry = FunctionRegistry::new();
+        for (name, function) in functions.into_iter().flat_map(|f| f.iter()) {
+            registry
+                .register(name.extract::<String>()?, PythonFunction(function.unbind()))
+                .map_err(conversion_error)?;
+        }
+        let plan = TransformPlan::compile(input).map_err(conversion_error)?;
+        config = config.with_transform(plan.with_functions(registry));
+    }
+
+    if let Some(options) = options {
+        let options: ConverterOptionsInput = from_python(options)?;
+        config = config.with_options(options).map_err(conversion_error)?;
+    }
+
+    Ok(config)
+}
+
+/// Streaming converter: `push` chunks of input, then `finish`.
+#[pyclass(unsendable, module = "convert_buddy")]
+struct Converter {
+    inner: Engine,
+}
+
+#[pymethods]
+impl Converter {
+    #[new]
+    #[pyo3(signature = (input_format, output_format, *, csv=None, xml=None, transform=None, functions=None, options=None, profile=false))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        input_format: &str,
+        output_format: &str,
+        csv: Option<&Bound<'_, PyAny>>,
+        xml: Option<&Bound<'_, PyAny>>,
+        transform: Option<&Bound<'_, PyAny>>,
+        functions: Option<&Bound<'_, PyDict>>,
+        options: Option<&Bound<'_, PyAny>>,
+        profile: bool,
+    ) -> PyResult<Self> {
+        let config = build_config(
+            input_format,
+            output_format,
+            csv,
+            xml,
+            transform,
+            functions,
+            options,
+            profile,
+        )?;
+        Ok(Self {
+            inner: Engine::from_config(config),
+        })
+    }
+
+    /// Convert a chunk of input, returning the output it completes.
+    fn push<'py>(&mut self, py: Python<'py>, chunk: &[u8]) -> PyResult<Bound<'py, PyBytes>> {
+        let output = self.inner.push(chunk).map_err(conversion_error)?;
+        Ok(PyBytes::new(py, &output))
+    }
+
+    /// Flush the rest of the output once all input is pushed.
+    fn finish<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let output = self.inner.finish().map_err(conversion_error)?;
+        Ok(PyBytes::new(py, &output))
+    }
+
+    /// Counters and timings; the timings stay 0 unless `profile=True`.
+    fn stats<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let stats = self.inner.get_stats();
+        let dict = PyDict::new(py);
+        dict.set_item("bytesIn", stats.bytes_in())?;
+        dict.set_item("bytesOut", stats.bytes_out())?;
+        dict.set_item("chunksIn", stats.chunks_in())?;
+        dict.set_item("recordsProcessed", stats.records_processed())?;
+        dict.set_item("recordsSkipped", stats.records_skipped())?;
+        dict.set_item("parseTimeMs", stats.parse_time_ms())?;
+        dict.set_item("transformTimeMs", stats.transform_time_ms())?;
+        dict.set_item("writeTimeMs", stats.write_time_ms())?;
+        dict.set_item("maxBufferSize", stats.max_buffer_size())?;
+        dict.set_item("currentPartialSize", stats.current_partial_size())?;
+        dict.set_item("throughputMbPerSec", stats.throughput_mb_per_sec())?;
+        Ok(dict)
+    }
+
+    /// Problems the conversion worked around, as `{code, message, count}` dicts.
+    fn warnings(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.inner.warnings())
+    }
+
+    /// Records dropped under `onRecordError: "collect"`, as `{line, message}` dicts.
+    fn errors(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.inner.record_errors())
+    }
+
+    /// Records dropped since the last call, as NDJSON `{line, error, raw}` objects.
+    fn take_rejects<'py>(&mut self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.take_rejects())
+    }
+
+    /// Settings detected from the start of the input, or `None` before detection ran.
+    fn detection(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self.inner.detection() {
+            Some(detection) => to_python(py, detection),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// `{columns, unflatten}` of the CSV written so far, to pass back as the
+    /// `csv` options that read it; `None` unless `csvOutput.typeSidecar` is set.
+    fn csv_schema(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self.inner.csv_schema() {
+            Some(schema) => to_python(py, &schema),
+            None => Ok(py.None()),
+        }
+    }
+
+    /// Whether a record matched `transform["stopWhen"]`.
+    fn is_stopped(&self) -> bool {
+        self.inner.is_stopped()
+    }
+
+    /// The input record that matched `transform["stopWhen"]`, or `None`.
+    fn matched_record(&self, py: Python<'_>) -> PyResult<PyObject> {
+        match self.inner.matched_record() {
+            Some(record) => to_python(py, record),
+            None => Ok(py.None()),
+        }
+    }
+}
+
+/// Convert a whole input in one call.
+#[pyfunction]
+#[pyo3(signature = (data, input_format, output_format, *, csv=None, xml=None, transform=None, functions=None, options=None))]
+#[allow(clippy::too_many_arguments)]
+fn convert<'py>(
+    py: Python<'py>,
+    data: &[u8],
+    input_format: &str,
+    output_format: &str,
+    csv: Option<&Bound<'_, PyAny>>,
+    xml: Option<&Bound<'_, PyAny>>,
+    transform: Option<&Bound<'_, PyAny>>,
+    functions: Option<&Bound<'_, PyDict>>,
+    options: Option<&Bound<'_, PyAny>>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let config = build_config(
+        input_format,
+        output_format,
+        csv,
+        xml,
+        transform,
+        functions,
+        options,
+        false,
+    )?;
+    let output = convert_buddy::convert_bytes(data, config).map_err(conversion_error)?;
+    Ok(PyBytes::new(py, &output))
+}
+
+/// Detect the input format of a sample, or `None`.
+#[pyfunction]
+fn detect_format(sample: &[u8]) -> Option<String> {
+    convert_buddy::detect_format(sample)
+}
+
+/// Detect the fields (and CSV delimiter or XML record element) of a sample.
+#[pyfunction]
+#[pyo3(signature = (sample, format_hint=None))]
+fn detect_structure<'py>(
+    py: Python<'py>,
+    sample: &[u8],
+    format_hint: Option<&str>,
+) -> PyResult<Option<Bound<'py, PyDict>>> {
+    let format = format_hint.map(parse_format).transpose()?;
+    let Some(detection) = detect::detect_structure(sample, format) else {
+        return Ok(None);
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("format", detection.format.to_string_js())?;
+    dict.set_item("fields", detection.fields)?;
+    if let Some(delimiter) = detection.delimiter {
+        dict.set_item("delimiter", delimiter)?;
+    }
+    if let Some(record_element) = detection.record_element {
+        dict.set_item("recordElement", record_element)?;
+    }
+    Ok(Some(dict))
+}
+
+#[pymodule]
+#[pyo3(name = "convert_buddy")]
+fn convert_buddy_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Converter>()?;
+    m.add_function(wrap_pyfunction!(convert, m)?)?;
+    m.add_function(wrap_pyfunction!(detect_format, m)?)?;
+    m.add_function(wrap_pyfunction!(detect_structure, m)?)?;
+    m.add("ConversionError", m.py().get_type::<ConversionError>())?;
+    Ok(())
+}
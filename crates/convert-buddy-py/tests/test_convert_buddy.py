@@ -0,0 +1,69 @@
+import json
+
+import pytest
+
+import convert_buddy
+
+
+def test_convert_csv_to_ndjson():
+    output = convert_buddy.convert(b"name,age\nAda,36\n", "csv", "ndjson")
+    assert output == b'{"name":"Ada","age":"36"}\n'
+
+
+def test_streaming_with_options():
+    converter = convert_buddy.Converter(
+        "ndjson", "ndjson", options={"onRecordError": "collect", "keepRejects": True}
+    )
+    output = converter.push(b'{"a":1}\n{"a":}\n') + converter.finish()
+    assert output == b'{"a":1}\n'
+    assert converter.errors()[0]["line"] == 2
+    assert json.loads(converter.take_rejects())["raw"] == '{"a":}'
+    assert converter.stats()["recordsSkipped"] == 1
+
+
+def test_transform_with_python_function():
+    converter = convert_buddy.Converter(
+        "csv",
+        "ndjson",
+        transform={"mode": "augment", "fields": [{"targetFieldName": "loud", "compute": "shout(name)"}]},
+        functions={"shout": lambda value: value.upper()},
+    )
+    output = converter.push(b"name\nada\n") + converter.finish()
+    assert json.loads(output) == {"name": "ada", "loud": "ADA"}
+
+
+def test_detection():
+    assert convert_buddy.detect_format(b"<rows><row/></rows>") == "xml"
+    structure = convert_buddy.detect_structure(b"a;b\n1;2\n")
+    assert structure["delimiter"] == ";"
+    assert structure["fields"] == ["a", "b"]
+
+
+def test_errors():
+    with pytest.raises(ValueError):
+        convert_buddy.Converter("csv", "nope")
+    with pytest.raises(convert_buddy.ConversionError):
+        convert_buddy.convert(b'{"a":}\n', "ndjson", "csv")
+
+
+def test_xml_record_elements():
+    output = convert_buddy.convert(
+        b"<feed><item><n>1</n></item><entry><n>2</n></entry></feed>",
+        "xml",
+        "ndjson",
+        xml={"recordElement": ["item", "entry"], "tagElement": True},
+    )
+    assert [json.loads(line) for line in output.splitlines()] == [
+        {"_element": "item", "n": "1"},
+        {"_element": "entry", "n": "2"},
+    ]
+
+
+def test_csv_schema_round_trip():
+    writer = convert_buddy.Converter("ndjson", "csv", options={"csvOutput": {"typeSidecar": True}})
+    csv = writer.push(b'{"a":{"b":1},"c":"x"}\n') + writer.finish()
+    assert csv == b"a.b,c\n1,x\n"
+    schema = writer.csv_schema()
+    assert schema["unflatten"] is True
+    output = convert_buddy.convert(csv, "csv", "ndjson", csv=schema)
+    assert json.loads(output) == {"a": {"b": 1}, "c": "x"}
@@ -0,0 +1,48 @@
+//! Throughput of NDJSON validation, i.e. an NDJSON to NDJSON pass-through.
+//!
+//! cargo run --release --example ndjson_validation --target x86_64-unknown-linux-gnu --no-default-features
+//!
+//! Add `--features simd` to compare with the SIMD string scanner.
+
+use convert_buddy::{Converter, ConverterConfig, Format};
+use std::time::Instant;
+
+const RECORDS: usize = 200_000;
+const ROUNDS: usize = 5;
+
+fn main() {
+    let mut input = Vec::new();
+    for id in 0..RECORDS {
+        input.extend_from_slice(
+            format!(
+                r#"{{"id":{id},"name":"Customer {id}","email":"customer.{id}@example.com","note":"Ordered the usual, asked for delivery after six in the evening","total":{}.{:02},"tags":["retail","newsletter"],"active":true}}"#,
+                id % 997,
+                id % 100
+            )
+            .as_bytes(),
+        );
+        input.push(b'\n');
+    }
+
+    let mut best = f64::MAX;
+    for _ in 0..ROUNDS {
+        let mut converter = Converter::from_config(ConverterConfig::new(Format::Ndjson, Format::Ndjson));
+        let start = Instant::now();
+        let mut bytes = 0;
+        for chunk in input.chunks(1 << 20) {
+            bytes += converter.push(chunk).expect("valid input").len();
+        }
+        bytes += converter.finish().expect("valid input").len();
+        best = best.min(start.elapsed().as_secs_f64());
+        assert_eq!(bytes, input.len());
+    }
+
+    let megabytes = input.len() as f64 / (1024.0 * 1024.0);
+    println!(
+        "simd: {}, {:.1} MB in {:.1} ms, {:.0} MB/s",
+        cfg!(feature = "simd"),
+        megabytes,
+        best * 1000.0,
+        megabytes / best
+    );
+}
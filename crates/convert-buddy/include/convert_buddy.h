@@ -0,0 +1,57 @@
+/*
+ * C API of the convert-buddy engine.
+ *
+ * Build the shared library with:
+ *   cargo build --release --target <host triple> --no-default-features --features ffi
+ *
+ * Functions that fail return NULL or -1; convert_buddy_last_error() then
+ * describes the failure for the calling thread.
+ */
+#ifndef CONVERT_BUDDY_H
+#define CONVERT_BUDDY_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Converter ConvertBuddyConverter;
+
+/* Bytes owned by the library; release with convert_buddy_buffer_free(). */
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} ConvertBuddyBuffer;
+
+/*
+ * input_format: "csv", "tsv", "ndjson", "json", "xml" or "auto".
+ * options_json: NULL or a JSON object of converter options, with the same
+ * camelCase keys as the JS package, e.g. {"onRecordError":"skip"}.
+ */
+ConvertBuddyConverter *convert_buddy_new(const char *input_format,
+                                         const char *output_format,
+                                         const char *options_json);
+
+/* Convert len bytes; out receives the output they complete. */
+int convert_buddy_push(ConvertBuddyConverter *converter,
+                       const uint8_t *data,
+                       size_t len,
+                       ConvertBuddyBuffer *out);
+
+/* Flush the rest of the output once all input is pushed. */
+int convert_buddy_finish(ConvertBuddyConverter *converter, ConvertBuddyBuffer *out);
+
+void convert_buddy_free(ConvertBuddyConverter *converter);
+
+void convert_buddy_buffer_free(ConvertBuddyBuffer buffer);
+
+/* Valid until the next failing call on the same thread. */
+const char *convert_buddy_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* CONVERT_BUDDY_H */
@@ -1,6 +1,6 @@
 #![cfg(not(test))]
 
-use convert_buddy::xml_parser::{XmlConfig, XmlParser};
+use convert_buddy::{XmlConfig, XmlParser};
 
 fn main() {
     let config = XmlConfig {
@@ -78,6 +78,22 @@ impl Default for BufferPool {
     }
 }
 
+thread_local! {
+    /// Pool the parsers take their output buffers from, and that
+    /// `Converter::push_into` returns them to once their bytes are copied out.
+    static SHARED: BufferPool = BufferPool::new(4, 1024 * 1024);
+}
+
+/// Acquire a buffer of at least `capacity` from the thread's shared pool
+pub(crate) fn acquire_shared(capacity: usize) -> Vec<u8> {
+    SHARED.with(|pool| pool.acquire_with_capacity(capacity))
+}
+
+/// Release a buffer back to the thread's shared pool
+pub(crate) fn release_shared(buffer: Vec<u8>) {
+    SHARED.with(|pool| pool.release(buffer));
+}
+
 /// RAII wrapper for pooled buffers
 pub struct PooledBuffer<'a> {
     buffer: Option<Vec<u8>>,
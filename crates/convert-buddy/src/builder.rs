@@ -0,0 +1,291 @@
+use crate::csv_parser::CsvConfig;
+use crate::csv_writer::CsvWriterConfig;
+use crate::dedupe::DedupeConfig;
+use crate::error::{ConvertError, Result};
+use crate::format::{ConverterConfig, Format};
+use crate::json_parser::JsonInputConfig;
+use crate::ndjson_parser::JsonOutputConfig;
+use crate::ndjson_writer::NdjsonOutputConfig;
+use crate::projection::{FieldProjection, OutputColumn};
+use crate::sort::SortConfig;
+use crate::transform::TransformPlan;
+use crate::xml_parser::{XmlConfig, XmlOutputConfig};
+use crate::Converter;
+
+/// Type parameter of a [`ConverterBuilder`] whose input or output format
+/// hasn't been chosen yet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Unset;
+
+/// Typed alternative to assembling a [`ConverterConfig`] by hand, from
+/// [`Converter::builder`].
+///
+/// `build()` only exists once both formats are chosen, and rejects settings
+/// that don't apply to them, such as a CSV config for JSON input, instead of
+/// silently ignoring them.
+#[derive(Debug, Clone)]
+pub struct ConverterBuilder<I = Unset, O = Unset> {
+    input: I,
+    output: O,
+    config: ConverterConfig,
+    formats: FormatSettings,
+}
+
+/// Settings for one input or output format, kept apart from `config` until
+/// `build()` has checked them against the chosen formats.
+#[derive(Debug, Clone, Default)]
+struct FormatSettings {
+    csv: Option<CsvConfig>,
+    csv_writer: Option<CsvWriterConfig>,
+    xml: Option<XmlConfig>,
+    xml_output: Option<XmlOutputConfig>,
+    json_input: Option<JsonInputConfig>,
+    json_output: Option<JsonOutputConfig>,
+    ndjson_output: Option<NdjsonOutputConfig>,
+}
+
+impl ConverterBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            input: Unset,
+            output: Unset,
+            config: ConverterConfig::default(),
+            formats: FormatSettings::default(),
+        }
+    }
+}
+
+impl<O> ConverterBuilder<Unset, O> {
+    pub fn input(self, format: Format) -> ConverterBuilder<Format, O> {
+        self.with_input(format)
+    }
+
+    /// Detect the input format from the first chunk. Format-specific input
+    /// settings are then accepted for any format and used if they apply.
+    pub fn detect_input(mut self) -> ConverterBuilder<Format, O> {
+        self.config.auto_detect.input_format = true;
+        let placeholder = self.config.input_format;
+        self.with_input(placeholder)
+    }
+
+    fn with_input(self, input: Format) -> ConverterBuilder<Format, O> {
+        ConverterBuilder {
+            input,
+            output: self.output,
+            config: self.config,
+            formats: self.formats,
+        }
+    }
+}
+
+impl<I> ConverterBuilder<I, Unset> {
+    pub fn output(self, output: Format) -> ConverterBuilder<I, Format> {
+        ConverterBuilder {
+            input: self.input,
+            output,
+            config: self.config,
+            formats: self.formats,
+        }
+    }
+}
+
+impl<I, O> ConverterBuilder<I, O> {
+    /// How CSV or TSV input is read.
+    pub fn csv(mut self, config: CsvConfig) -> Self {
+        self.formats.csv = Some(config);
+        self
+    }
+
+    /// How CSV or TSV output is written.
+    pub fn csv_writer(mut self, config: CsvWriterConfig) -> Self {
+        self.formats.csv_writer = Some(config);
+        self
+    }
+
+    /// How XML input is read.
+    pub fn xml(mut self, config: XmlConfig) -> Self {
+        self.formats.xml = Some(config);
+        self
+    }
+
+    /// How XML output is written.
+    pub fn xml_output(mut self, config: XmlOutputConfig) -> Self {
+        self.formats.xml_output = Some(config);
+        self
+    }
+
+    /// How JSON input is read.
+    pub fn json_input(mut self, config: JsonInputConfig) -> Self {
+        self.formats.json_input = Some(config);
+        self
+    }
+
+    /// Indentation of JSON output.
+    pub fn json_output(mut self, config: JsonOutputConfig) -> Self {
+        self.formats.json_output = Some(config);
+        self
+    }
+
+    /// Record framing of NDJSON output.
+    pub fn ndjson_output(mut self, config: NdjsonOutputConfig) -> Self {
+        self.formats.ndjson_output = Some(config);
+        self
+    }
+
+    pub fn transform(mut self, plan: TransformPlan) -> Self {
+        self.config.transform = Some(plan);
+        self
+    }
+
+    pub fn projection(mut self, projection: FieldProjection) -> Self {
+        self.config.projection = Some(projection);
+        self
+    }
+
+    pub fn output_columns(mut self, columns: Vec<OutputColumn>) -> Self {
+        self.config.output_columns = Some(columns);
+        self
+    }
+
+    pub fn dedupe(mut self, dedupe: DedupeConfig) -> Self {
+        self.config.dedupe = Some(dedupe);
+        self
+    }
+
+    pub fn sort(mut self, sort: SortConfig) -> Self {
+        self.config.sort = Some(sort);
+        self
+    }
+
+    /// Change settings without a method of their own through the
+    /// [`ConverterConfig`] `with_*` methods. They are checked by `build()`
+    /// like the rest.
+    pub fn configure(mut self, configure: impl FnOnce(ConverterConfig) -> ConverterConfig) -> Self {
+        self.config = configure(self.config);
+        self
+    }
+}
+
+impl ConverterBuilder<Format, Format> {
+    pub fn build(self) -> Result<Converter> {
+        self.into_config().map(Converter::from_config)
+    }
+
+    /// The checked config `build()` makes its converter from.
+    pub fn into_config(self) -> Result<ConverterConfig> {
+        let mut config = self.config;
+        config.input_format = self.input;
+        config.output_format = self.output;
+
+        #[cfg(feature = "parquet")]
+        if config.input_format == Format::Parquet {
+            return Err(ConvertError::Unsupported(
+                "Parquet is only supported as an output format".to_string(),
+            ));
+        }
+        #[cfg(feature = "xlsx")]
+        if config.output_format == Format::Xlsx {
+            return Err(ConvertError::Unsupported(
+                "XLSX is only supported as an input format".to_string(),
+            ));
+        }
+
+        let formats = self.formats;
+        let input = |accepted: &[Format]| config.auto_detect.input_format || accepted.contains(&config.input_format);
+        let output = |accepted: &[Format]| accepted.contains(&config.output_format);
+        let misplaced = [
+            (formats.csv.is_some() && !input(&[Format::Csv, Format::Tsv]), "CSV config needs CSV or TSV input"),
+            (formats.csv_writer.is_some() && !output(&[Format::Csv, Format::Tsv]), "CSV writer config needs CSV or TSV output"),
+            (formats.xml.is_some() && !input(&[Format::Xml]), "XML config needs XML input"),
+            (formats.xml_output.is_some() && !output(&[Format::Xml]), "XML output config needs XML output"),
+            (formats.json_input.is_some() && !input(&[Format::Json]), "JSON input config needs JSON input"),
+            (formats.json_output.is_some() && !output(&[Format::Json]), "JSON output config needs JSON output"),
+            (formats.ndjson_output.is_some() && !output(&[Format::Ndjson]), "NDJSON output config needs NDJSON output"),
+        ];
+        if let Some((_, message)) = misplaced.iter().find(|(misplaced, _)| *misplaced) {
+            return Err(ConvertError::InvalidConfig(message.to_string()));
+        }
+
+        if let Some(csv) = formats.csv {
+            config.csv_config = Some(csv);
+        }
+        if let Some(csv_writer) = formats.csv_writer {
+            config.csv_writer = csv_writer;
+        }
+        if let Some(xml) = formats.xml {
+            config.xml_config = Some(xml);
+        }
+        if let Some(xml_output) = formats.xml_output {
+            config.xml_output = xml_output;
+        }
+        if let Some(json_input) = formats.json_input {
+            config.json_input = json_input;
+        }
+        if let Some(json_output) = formats.json_output {
+            config.json_output = json_output;
+        }
+        if let Some(ndjson_output) = formats.ndjson_output {
+            config.ndjson_output = Some(ndjson_output);
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sort::SortOrder;
+
+    #[test]
+    fn rejects_settings_for_other_formats() {
+        let config = Converter::builder()
+            .output(Format::Json)
+            .input(Format::Csv)
+            .csv(CsvConfig { delimiter: b';', ..Default::default() })
+            .json_output(JsonOutputConfig { pretty: true, indent: 2 })
+            .into_config()
+            .unwrap();
+        assert_eq!((config.input_format, config.output_format), (Format::Csv, Format::Json));
+        assert_eq!(config.csv_config.unwrap().delimiter, b';');
+        assert!(config.json_output.pretty);
+
+        let error = Converter::builder()
+            .input(Format::Json)
+            .output(Format::Ndjson)
+            .csv(CsvConfig::default())
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "Invalid format configuration: CSV config needs CSV or TSV input");
+
+        assert!(Converter::builder()
+            .detect_input()
+            .output(Format::Ndjson)
+            .csv(CsvConfig::default())
+            .build()
+            .is_ok());
+
+        let sorted = |output| {
+            Converter::builder()
+                .input(Format::Csv)
+                .output(output)
+                .sort(SortConfig { index: true, ..SortConfig::new(vec!["a".to_string()], SortOrder::Asc) })
+                .build()
+        };
+        assert!(sorted(Format::Ndjson).is_ok());
+        assert!(sorted(Format::Csv).is_err());
+        assert!(Converter::builder()
+            .input(Format::Csv)
+            .output(Format::Ndjson)
+            .configure(|config| config.with_threads(0))
+            .build()
+            .is_err());
+        assert!(Converter::builder()
+            .input(Format::Csv)
+            .output(Format::Ndjson)
+            .dedupe(DedupeConfig::default())
+            .build()
+            .is_err());
+    }
+}
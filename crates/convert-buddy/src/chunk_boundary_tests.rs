@@ -0,0 +1,112 @@
+//! Property tests: converting input split at arbitrary chunk boundaries must
+//! produce exactly the bytes of a single-push conversion.
+
+use crate::{Converter, ConverterConfig, Format, XmlConfig};
+use proptest::prelude::*;
+
+const FORMATS: [Format; 4] = [Format::Csv, Format::Ndjson, Format::Json, Format::Xml];
+
+const CSV_INPUT: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/products.csv"));
+const NDJSON_INPUT: &[u8] =
+    include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/products.ndjson"));
+const JSON_INPUT: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/products.json"));
+const XML_INPUT: &[u8] = include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/products.xml"));
+
+fn convert_chunks(input_format: Format, output_format: Format, chunks: &[&[u8]]) -> Vec<u8> {
+    let mut config = ConverterConfig::new(input_format, output_format);
+    if input_format == Format::Xml {
+        config = config.with_xml_config(XmlConfig {
+            record_element: "product".to_string(),
+            ..XmlConfig::default()
+        });
+    }
+
+    let mut converter = Converter::from_config(config);
+    let mut output = Vec::new();
+    for chunk in chunks {
+        output.extend(converter.push(chunk).expect("push failed"));
+    }
+    output.extend(converter.finish().expect("finish failed"));
+    output
+}
+
+/// Split `input` at the given positions (taken modulo the input length).
+fn split_at_points<'a>(input: &'a [u8], points: &[usize]) -> Vec<&'a [u8]> {
+    let mut points: Vec<usize> = points.iter().map(|p| p % (input.len() + 1)).collect();
+    points.sort_unstable();
+    points.dedup();
+
+    let mut chunks = Vec::with_capacity(points.len() + 1);
+    let mut start = 0;
+    for point in points {
+        chunks.push(&input[start..point]);
+        start = point;
+    }
+    chunks.push(&input[start..]);
+    chunks
+}
+
+fn assert_split_invariant(input_format: Format, input: &[u8], points: &[usize]) {
+    let chunks = split_at_points(input, points);
+    for output_format in FORMATS {
+        let whole = convert_chunks(input_format, output_format, &[input]);
+        let split = convert_chunks(input_format, output_format, &chunks);
+        assert_eq!(
+            String::from_utf8_lossy(&split),
+            String::from_utf8_lossy(&whole),
+            "{:?} -> {:?} split into {:?}",
+            input_format,
+            output_format,
+            chunks.iter().map(|c| String::from_utf8_lossy(c)).collect::<Vec<_>>()
+        );
+    }
+}
+
+/// CSV field text that exercises quoting: delimiters, quotes and line breaks.
+fn csv_field() -> impl Strategy<Value = String> {
+    proptest::string::string_regex("[a-zé ,\"\n]{0,8}").unwrap()
+}
+
+fn quote_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) || field.starts_with(' ') || field.ends_with(' ') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+proptest! {
+    #[test]
+    fn csv_splits_anywhere(points in prop::collection::vec(any::<usize>(), 1..8)) {
+        assert_split_invariant(Format::Csv, CSV_INPUT, &points);
+    }
+
+    #[test]
+    fn ndjson_splits_anywhere(points in prop::collection::vec(any::<usize>(), 1..8)) {
+        assert_split_invariant(Format::Ndjson, NDJSON_INPUT, &points);
+    }
+
+    #[test]
+    fn json_splits_anywhere(points in prop::collection::vec(any::<usize>(), 1..8)) {
+        assert_split_invariant(Format::Json, JSON_INPUT, &points);
+    }
+
+    #[test]
+    fn xml_splits_anywhere(points in prop::collection::vec(any::<usize>(), 1..8)) {
+        assert_split_invariant(Format::Xml, XML_INPUT, &points);
+    }
+
+    #[test]
+    fn generated_csv_with_quoted_fields_splits_anywhere(
+        rows in prop::collection::vec(prop::collection::vec(csv_field(), 3), 1..6),
+        points in prop::collection::vec(any::<usize>(), 1..8),
+    ) {
+        let mut input = String::from("a,b,c\n");
+        for row in &rows {
+            let fields: Vec<String> = row.iter().map(|f| quote_csv(f)).collect();
+            input.push_str(&fields.join(","));
+            input.push('\n');
+        }
+        assert_split_invariant(Format::Csv, input.as_bytes(), &points);
+    }
+}
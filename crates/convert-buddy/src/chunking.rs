@@ -0,0 +1,223 @@
+use crate::error::{ConvertError, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt::Write as _;
+
+/// Random values mixed into the rolling hash for each byte value.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    // splitmix64 from a fixed seed, so cut points never change between builds
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x6a09_e667_f3bc_c908;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+/// Splits the output into content-defined chunks and lists them in a
+/// manifest, so a re-run over a mostly unchanged feed can upload only the
+/// chunks that changed.
+///
+/// Cut points depend on the last 64 bytes of output only, so an edit moves
+/// the boundaries of the chunks around it but not of the rest.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChunkingConfig {
+    /// Smallest chunk; only the last one can be shorter.
+    pub min_bytes: usize,
+    /// Typical distance between cut points past `min_bytes`, rounded down
+    /// to a power of two.
+    pub avg_bytes: usize,
+    /// Largest chunk; a chunk is cut here without a cut point.
+    pub max_bytes: usize,
+    /// Hex SHA-256 hashes of chunks the consumer already has, e.g. from the
+    /// manifest of the previous run.
+    pub known: Vec<String>,
+}
+
+impl Default for ChunkingConfig {
+    fn default() -> Self {
+        Self {
+            min_bytes: 16 * 1024,
+            avg_bytes: 64 * 1024,
+            max_bytes: 256 * 1024,
+            known: Vec::new(),
+        }
+    }
+}
+
+impl ChunkingConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.min_bytes == 0 || self.min_bytes > self.max_bytes || self.avg_bytes == 0 {
+            return Err(ConvertError::InvalidConfig(
+                "chunking needs 0 < minBytes <= maxBytes and avgBytes > 0".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Applies a [`ChunkingConfig`] to the bytes a converter returns.
+#[derive(Debug)]
+pub(crate) struct Chunker {
+    min_bytes: usize,
+    max_bytes: usize,
+    /// Bits of the rolling hash that must be zero at a cut point.
+    mask: u64,
+    /// Hashes of the chunks known to the consumer or written before.
+    seen: HashSet<String>,
+    hash: u64,
+    digest: Sha256,
+    /// Bytes of output before the current chunk, and in it.
+    offset: u64,
+    length: usize,
+    manifest: Vec<u8>,
+}
+
+impl Chunker {
+    pub(crate) fn new(config: &ChunkingConfig) -> Self {
+        let bits = usize::BITS - 1 - config.avg_bytes.max(1).leading_zeros();
+        Self {
+            min_bytes: config.min_bytes,
+            max_bytes: config.max_bytes,
+            mask: !(u64::MAX >> bits.min(63)),
+            seen: config.known.iter().map(|hash| hash.to_ascii_lowercase()).collect(),
+            hash: 0,
+            digest: Sha256::new(),
+            offset: 0,
+            length: 0,
+            manifest: Vec::new(),
+        }
+    }
+
+    /// Take the next bytes of output, as returned to the caller.
+    pub(crate) fn write(&mut self, output: &[u8]) {
+        let mut rest = output;
+        while let Some(end) = self.cut_point(rest) {
+            self.digest.update(&rest[..end]);
+            self.length += end;
+            self.end_chunk();
+            rest = &rest[end..];
+        }
+        self.digest.update(rest);
+        self.length += rest.len();
+    }
+
+    /// End the last chunk, whatever its size.
+    pub(crate) fn finish(&mut self) {
+        if self.length > 0 {
+            self.end_chunk();
+        }
+    }
+
+    /// Manifest lines of the chunks ended since the last call.
+    pub(crate) fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.manifest)
+    }
+
+    /// Length of the prefix of `data` that ends the current chunk, if any.
+    fn cut_point(&mut self, data: &[u8]) -> Option<usize> {
+        for (i, &byte) in data.iter().enumerate() {
+            self.hash = (self.hash << 1).wrapping_add(GEAR[byte as usize]);
+            let length = self.length + i + 1;
+            if length >= self.max_bytes || (length >= self.min_bytes && self.hash & self.mask == 0) {
+                return Some(i + 1);
+            }
+        }
+        None
+    }
+
+    fn end_chunk(&mut self) {
+        let sha256 = sha256_hex(std::mem::take(&mut self.digest));
+        let known = !self.seen.insert(sha256.clone());
+        let line = serde_json::json!({
+            "offset": self.offset,
+            "length": self.length,
+            "sha256": sha256,
+            "known": known,
+        });
+        serde_json::to_writer(&mut self.manifest, &line).ok();
+        self.manifest.push(b'\n');
+
+        self.offset += self.length as u64;
+        self.length = 0;
+        self.hash = 0;
+    }
+}
+
+/// Lowercase hex of the SHA-256 of the bytes fed to `digest`.
+pub(crate) fn sha256_hex(digest: Sha256) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in digest.finalize() {
+        write!(hex, "{byte:02x}").ok();
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn manifest(config: &ChunkingConfig, output: &[u8], piece: usize) -> Vec<Value> {
+        let mut chunker = Chunker::new(config);
+        for part in output.chunks(piece) {
+            chunker.write(part);
+        }
+        chunker.finish();
+        chunker
+            .take()
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    fn feed(edited: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        for n in 0..2000 {
+            let price = if n == edited { 1 } else { n % 97 };
+            output.extend(format!("{{\"sku\":\"S-{n}\",\"price\":{price}}}\n").as_bytes());
+        }
+        output
+    }
+
+    #[test]
+    fn cuts_at_content_defined_points() {
+        let config = ChunkingConfig {
+            min_bytes: 256,
+            avg_bytes: 1024,
+            max_bytes: 4096,
+            known: Vec::new(),
+        };
+        let output = feed(usize::MAX);
+        let chunks = manifest(&config, &output, 1000);
+        assert_eq!(chunks, manifest(&config, &output, 7));
+        assert!(chunks.len() > 10);
+        let mut offset = 0;
+        for chunk in &chunks {
+            assert_eq!(chunk["offset"], offset);
+            let length = chunk["length"].as_u64().unwrap();
+            assert!((256..=4096).contains(&length) || offset + length == output.len() as u64);
+            offset += length;
+        }
+        assert_eq!(offset, output.len() as u64);
+
+        // One edited record leaves most chunks known
+        let known: Vec<String> = chunks.iter().map(|chunk| chunk["sha256"].as_str().unwrap().to_string()).collect();
+        let edited = manifest(&ChunkingConfig { known, ..config.clone() }, &feed(1000), 1000);
+        let changed = edited.iter().filter(|chunk| chunk["known"] == false).count();
+        assert!((1..=2).contains(&changed), "{changed} chunks changed");
+
+        assert!(ChunkingConfig { min_bytes: 0, ..config }.validate().is_err());
+    }
+}
@@ -0,0 +1,264 @@
+use crate::detect::detect_compression;
+use crate::error::{ConvertError, Result};
+use flate2::write::{DeflateDecoder, DeflateEncoder, GzEncoder, MultiGzDecoder, ZlibDecoder, ZlibEncoder};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::{Read, Write};
+
+/// Compression wrapped around an input or output byte stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    #[default]
+    None,
+    /// gzip (RFC 1952); concatenated members are read as one stream.
+    Gzip,
+    /// zlib-wrapped deflate (RFC 1950).
+    Zlib,
+    /// Raw deflate (RFC 1951), without a header.
+    Deflate,
+}
+
+/// Inflates compressed input chunk by chunk.
+///
+/// Without a configured compression, the first bytes are sniffed with
+/// [`detect_compression`] and the stream is either inflated or passed through
+/// unchanged.
+pub(crate) enum Decompressor {
+    /// Holding the first byte until there are enough to sniff.
+    Sniffing(Vec<u8>),
+    Passthrough,
+    Gzip(MultiGzDecoder<Vec<u8>>),
+    Zlib(ZlibDecoder<Vec<u8>>),
+    Deflate(DeflateDecoder<Vec<u8>>),
+}
+
+/// Bytes needed to recognise gzip and zlib headers.
+const SNIFF_BYTES: usize = 2;
+
+impl Decompressor {
+    /// `None` sniffs the stream; `Some` forces a compression, including none.
+    pub(crate) fn new(compression: Option<Compression>) -> Self {
+        match compression {
+            None => Decompressor::Sniffing(Vec::new()),
+            Some(compression) => Self::for_compression(compression),
+        }
+    }
+
+    fn for_compression(compression: Compression) -> Self {
+        match compression {
+            Compression::None => Decompressor::Passthrough,
+            Compression::Gzip => Decompressor::Gzip(MultiGzDecoder::new(Vec::new())),
+            Compression::Zlib => Decompressor::Zlib(ZlibDecoder::new(Vec::new())),
+            Compression::Deflate => Decompressor::Deflate(DeflateDecoder::new(Vec::new())),
+        }
+    }
+
+    /// Inflate the next chunk. Uncompressed input is returned as-is.
+    pub(crate) fn push<'a>(&mut self, chunk: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        if let Decompressor::Sniffing(head) = self {
+            if head.len() + chunk.len() < SNIFF_BYTES {
+                head.extend_from_slice(chunk);
+                return Ok(Cow::Owned(Vec::new()));
+            }
+            let mut sample = std::mem::take(head);
+            sample.extend_from_slice(chunk);
+            *self = Self::for_compression(detect_compression(&sample).unwrap_or_default());
+            return self.push(&sample).map(|data| Cow::Owned(data.into_owned()));
+        }
+
+        let output = match self {
+            Decompressor::Sniffing(_) => unreachable!("handled above"),
+            Decompressor::Passthrough => return Ok(Cow::Borrowed(chunk)),
+            Decompressor::Gzip(decoder) => write_and_take(decoder, chunk, |d| d.get_mut()),
+            Decompressor::Zlib(decoder) => write_and_take(decoder, chunk, |d| d.get_mut()),
+            Decompressor::Deflate(decoder) => write_and_take(decoder, chunk, |d| d.get_mut()),
+        };
+        output.map(Cow::Owned).map_err(decode_error)
+    }
+
+    /// Flush the rest of the stream, including input too short to sniff.
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>> {
+        let output = match self {
+            Decompressor::Sniffing(head) => Ok(std::mem::take(head)),
+            Decompressor::Passthrough => Ok(Vec::new()),
+            Decompressor::Gzip(decoder) => decoder
+                .try_finish()
+                .map(|_| std::mem::take(decoder.get_mut())),
+            Decompressor::Zlib(decoder) => decoder
+                .try_finish()
+                .map(|_| std::mem::take(decoder.get_mut())),
+            Decompressor::Deflate(decoder) => decoder
+                .try_finish()
+                .map(|_| std::mem::take(decoder.get_mut())),
+        };
+        *self = Decompressor::Passthrough;
+        output.map_err(decode_error)
+    }
+
+    /// Compressed bytes held back, waiting for more input.
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        match self {
+            Decompressor::Sniffing(head) => head.len(),
+            _ => 0,
+        }
+    }
+}
+
+/// Compresses output chunk by chunk.
+///
+/// The encoder holds data back until it has enough to emit a block, so a
+/// small `push` may return nothing; `finish` flushes the rest and the trailer.
+pub(crate) enum Compressor {
+    Gzip(GzEncoder<Vec<u8>>),
+    Zlib(ZlibEncoder<Vec<u8>>),
+    Deflate(DeflateEncoder<Vec<u8>>),
+}
+
+impl Compressor {
+    /// `None` when output is left uncompressed.
+    pub(crate) fn new(compression: Compression) -> Option<Self> {
+        let level = flate2::Compression::default();
+        match compression {
+            Compression::None => None,
+            Compression::Gzip => Some(Compressor::Gzip(GzEncoder::new(Vec::new(), level))),
+            Compression::Zlib => Some(Compressor::Zlib(ZlibEncoder::new(Vec::new(), level))),
+            Compression::Deflate => Some(Compressor::Deflate(DeflateEncoder::new(Vec::new(), level))),
+        }
+    }
+
+    pub(crate) fn push(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let output = match self {
+            Compressor::Gzip(encoder) => write_and_take(encoder, data, |e| e.get_mut()),
+            Compressor::Zlib(encoder) => write_and_take(encoder, data, |e| e.get_mut()),
+            Compressor::Deflate(encoder) => write_and_take(encoder, data, |e| e.get_mut()),
+        };
+        output.map_err(encode_error)
+    }
+
+    /// Write out everything buffered so far with a sync flush, so a reader can
+    /// decode it before the stream ends.
+    pub(crate) fn flush(&mut self) -> Result<Vec<u8>> {
+        let output = match self {
+            Compressor::Gzip(encoder) => encoder.flush().map(|_| std::mem::take(encoder.get_mut())),
+            Compressor::Zlib(encoder) => encoder.flush().map(|_| std::mem::take(encoder.get_mut())),
+            Compressor::Deflate(encoder) => encoder.flush().map(|_| std::mem::take(encoder.get_mut())),
+        };
+        output.map_err(encode_error)
+    }
+
+    /// Write out everything still buffered, followed by the stream trailer.
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>> {
+        let output = match self {
+            Compressor::Gzip(encoder) => encoder
+                .try_finish()
+                .map(|_| std::mem::take(encoder.get_mut())),
+            Compressor::Zlib(encoder) => encoder
+                .try_finish()
+                .map(|_| std::mem::take(encoder.get_mut())),
+            Compressor::Deflate(encoder) => encoder
+                .try_finish()
+                .map(|_| std::mem::take(encoder.get_mut())),
+        };
+        output.map_err(encode_error)
+    }
+}
+
+/// Feed `data` through a flate2 writer and take what it has produced so far.
+/// Inflate as much of `sample` as decodes, for detection on a sample cut off
+/// mid-stream.
+pub(crate) fn inflate_prefix(sample: &[u8], compression: Compression) -> Vec<u8> {
+    let mut output = Vec::new();
+    // What was read before a truncation error is kept in `output`
+    let _ = match compression {
+        Compression::None => {
+            output.extend_from_slice(sample);
+            Ok(0)
+        }
+        Compression::Gzip => flate2::read::MultiGzDecoder::new(sample).read_to_end(&mut output),
+        Compression::Zlib => flate2::read::ZlibDecoder::new(sample).read_to_end(&mut output),
+        Compression::Deflate => flate2::read::DeflateDecoder::new(sample).read_to_end(&mut output),
+    };
+    output
+}
+
+fn write_and_take<W: Write>(
+    coder: &mut W,
+    data: &[u8],
+    output: impl Fn(&mut W) -> &mut Vec<u8>,
+) -> std::io::Result<Vec<u8>> {
+    coder.write_all(data)?;
+    Ok(std::mem::take(output(coder)))
+}
+
+fn decode_error(error: std::io::Error) -> ConvertError {
+    ConvertError::Io(format!("Decompression error: {error}"))
+}
+
+fn encode_error(error: std::io::Error) -> ConvertError {
+    ConvertError::Io(format!("Compression error: {error}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inflate_in_chunks(mut decompressor: Decompressor, data: &[u8], size: usize) -> Vec<u8> {
+        let mut output = Vec::new();
+        for chunk in data.chunks(size) {
+            output.extend_from_slice(&decompressor.push(chunk).unwrap());
+        }
+        output.extend(decompressor.finish().unwrap());
+        output
+    }
+
+    #[test]
+    fn sniffs_and_inflates_split_streams() {
+        let text = b"id,name\n1,Ada\n2,Grace\n".repeat(50);
+
+        let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&text).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut zlib = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(&text).unwrap();
+        let zlib = zlib.finish().unwrap();
+
+        for size in [1, 7, 4096] {
+            assert_eq!(inflate_in_chunks(Decompressor::new(None), &gzip, size), text);
+            assert_eq!(inflate_in_chunks(Decompressor::new(None), &zlib, size), text);
+            assert_eq!(inflate_in_chunks(Decompressor::new(None), &text, size), text);
+        }
+        // Two gzip members read back as one stream
+        let both = [gzip.clone(), gzip].concat();
+        assert_eq!(
+            inflate_in_chunks(Decompressor::new(Some(Compression::Gzip)), &both, 100),
+            [text.clone(), text].concat()
+        );
+    }
+
+    #[test]
+    fn compressed_output_round_trips() {
+        let text = b"{\"id\":1}\n".repeat(500);
+        for compression in [Compression::Gzip, Compression::Zlib, Compression::Deflate] {
+            let mut compressor = Compressor::new(compression).unwrap();
+            let mut packed = Vec::new();
+            for chunk in text.chunks(100) {
+                packed.extend(compressor.push(chunk).unwrap());
+            }
+            packed.extend(compressor.finish().unwrap());
+            assert!(packed.len() < text.len());
+
+            let unpacked = inflate_in_chunks(Decompressor::new(Some(compression)), &packed, 64);
+            assert_eq!(unpacked, text, "{compression:?}");
+        }
+        assert!(Compressor::new(Compression::None).is_none());
+    }
+
+    #[test]
+    fn forced_none_passes_compressed_bytes_through() {
+        let data = [0x1f, 0x8b, 0x08];
+        let mut decompressor = Decompressor::new(Some(Compression::None));
+        assert_eq!(&*decompressor.push(&data).unwrap(), &data);
+        assert!(Decompressor::new(Some(Compression::Gzip)).push(b"plain text").is_err());
+    }
+}
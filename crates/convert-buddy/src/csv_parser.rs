@@ -1,14 +1,106 @@
-use crate::error::Result;
-use crate::buffer_pool::BufferPool;
+use crate::error::{ConvertError, Result};
+use crate::buffer_pool;
+use crate::limits::ResourceLimits;
+use crate::record_errors::RecordErrors;
+use crate::utf8::{keep_line, InvalidUtf8Policy};
+use crate::warnings::{WarningCode, Warnings};
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use memchr::memchr;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 use std::io::Write;
 
-#[cfg(feature = "threads")]
-use rayon::prelude::*;
+/// How the values of one column are written to JSON.
+///
+/// The forced types write empty fields as `null` and fail on values they
+/// can't parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// Always a JSON string.
+    String,
+    /// Numbers, booleans and `null` for empty fields; strings otherwise.
+    Infer,
+    /// A 64-bit integer.
+    Integer,
+    /// A finite number.
+    Number,
+    /// `true`/`false`, `1`/`0` or `yes`/`no`, in any case.
+    Boolean,
+    /// A date, written as `YYYY-MM-DD`.
+    Date,
+    /// A date and time, written as RFC 3339 (without an offset if the input has none).
+    Datetime,
+    /// JSON text, written as the value it holds.
+    Json,
+}
+
+/// What an empty field becomes instead of the column type's default of `""`
+/// or `null`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyField {
+    /// `null`, in string columns too.
+    Null,
+    /// No key, as for a record that didn't have one.
+    Omit,
+}
+
+/// Schema entry for the CSV column at the same position.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvColumn {
+    /// Key of the column in the output, replacing its header.
+    pub name: String,
+    /// Type of the values; `None` falls back to `column_types` and `infer_types`.
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub column_type: Option<ColumnType>,
+    /// Whether empty fields are allowed; they fail the record otherwise.
+    #[serde(default = "default_nullable", skip_serializing_if = "is_true")]
+    pub nullable: bool,
+    /// chrono format of `date` and `datetime` values, e.g. `%d/%m/%Y`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// What empty fields become; `None` keeps the column type's default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub empty: Option<EmptyField>,
+    /// Keys down to the nested value the CSV writer flattened into this
+    /// column, e.g. `["tags", "0"]` for `tags.0`; empty for a top-level key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<String>,
+}
 
-// Thread-local buffer pool for reduced allocations
-thread_local! {
-    static BUFFER_POOL: BufferPool = BufferPool::default();
+fn default_nullable() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl CsvColumn {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            column_type: None,
+            nullable: true,
+            format: None,
+            empty: None,
+            path: Vec::new(),
+        }
+    }
+}
+
+/// Column types of a CSV written with `typeSidecar` set, to restore the
+/// records it came from when reading it back; see [`CsvConfig::with_schema`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CsvSchema {
+    /// One entry per column, in order.
+    pub columns: Vec<CsvColumn>,
+    /// Whether nested objects or arrays were flattened into dotted keys.
+    pub unflatten: bool,
 }
 
 /// CSV parser configuration
@@ -19,6 +111,26 @@ pub struct CsvConfig {
     pub escape: Option<u8>,
     pub has_headers: bool,
     pub trim_whitespace: bool,
+    /// Write numbers, booleans and empty fields as JSON numbers, booleans and `null`.
+    pub infer_types: bool,
+    /// Per-column overrides of `infer_types`, keyed by header (or `field_N`).
+    pub column_types: HashMap<String, ColumnType>,
+    /// Schema of the leading columns, by position: output names, types and dates.
+    pub columns: Vec<CsvColumn>,
+    /// Nest `a.b` and `a.0` keys back into objects and arrays, undoing the
+    /// CSV writer's flattening. When `columns` give a `path`, only those
+    /// columns are nested, along it.
+    pub unflatten: bool,
+    /// Lines ignored at the start of the input, such as a title above the header.
+    pub skip_rows: usize,
+    /// Index of the header row among the lines left after `skip_rows`; the
+    /// lines before it are ignored too.
+    pub header_row: usize,
+    /// Data rows to convert; the rest of the input is ignored.
+    pub max_rows: Option<usize>,
+    /// Lines starting with this byte, like `#`, are skipped wherever they
+    /// appear, quotes and all.
+    pub comment_char: Option<u8>,
 }
 
 impl Default for CsvConfig {
@@ -29,10 +141,36 @@ impl Default for CsvConfig {
             escape: Some(b'"'), // RFC 4180: double quote escapes quote
             has_headers: true,
             trim_whitespace: false,
+            infer_types: false,
+            column_types: HashMap::new(),
+            columns: Vec::new(),
+            unflatten: false,
+            skip_rows: 0,
+            header_row: 0,
+            max_rows: None,
+            comment_char: None,
         }
     }
 }
 
+impl CsvConfig {
+    /// Read the columns as `schema` describes them, nesting dotted keys back
+    /// when the writer flattened any.
+    pub fn with_schema(mut self, schema: CsvSchema) -> Self {
+        self.columns = schema.columns;
+        self.unflatten = schema.unflatten;
+        self
+    }
+}
+
+/// A header column as written into each record.
+struct ColumnKey {
+    /// `"name":`, escaped, copied into each record as it is
+    prefix: Vec<u8>,
+    name: String,
+    column_type: ColumnType,
+}
+
 /// High-performance CSV parser with:
 /// - Fast path for unquoted fields
 /// - Quoted path for fields with quotes/escapes
@@ -42,28 +180,160 @@ pub struct CsvParser {
     config: CsvConfig,
     partial_line: Vec<u8>,
     headers: Option<Vec<String>>,
+    /// JSON keys of the header columns, serialized once the header is known
+    keys: Vec<ColumnKey>,
     output_buffer: Vec<u8>,
     chunk_target_bytes: usize,
     record_count: usize,
     // Speculative parsing: assume no quotes initially
     speculative_mode: bool,
+    /// Column names the consumer needs; other columns are not materialized
+    projection: Option<Vec<String>>,
+    /// Per-column flags resolved from `projection` once headers are known
+    needed: Option<Vec<bool>>,
+    /// Keep parallel output in input order
+    ordered: bool,
+    /// Most threads the parallel path may use; `None` defers to the pool
+    threads: Option<usize>,
+    /// Field size cap; the converter enforces the other limits
+    limits: ResourceLimits,
+    warnings: Warnings,
+    /// Whether rows with invalid UTF-8 are skipped instead of failing
+    invalid_utf8: InvalidUtf8Policy,
+    /// The last line ended at a `\r`, so an empty line next is the rest of a CRLF
+    after_cr: bool,
+    /// What happens to rows that fail to convert
+    errors: RecordErrors,
+    /// `\n` line endings consumed so far, for the line numbers of failed rows
+    lines: u64,
+    /// Lines still to drop before the header, from `skip_rows` and `header_row`
+    preamble_left: usize,
+    /// `path` of the schema columns that have one, by output key
+    paths: HashMap<String, Vec<String>>,
 }
 
 impl CsvParser {
     pub fn new(config: CsvConfig, chunk_target_bytes: usize) -> Self {
-        Self {
+        // Without a header row, the schema names the columns
+        let headers = (!config.has_headers && !config.columns.is_empty())
+            .then(|| config.columns.iter().map(|column| column.name.clone()).collect());
+        let preamble_left = config.skip_rows + config.header_row;
+        let paths = config
+            .columns
+            .iter()
+            .filter(|column| !column.path.is_empty())
+            .map(|column| (column.name.clone(), column.path.clone()))
+            .collect();
+        let mut parser = Self {
             config,
             partial_line: Vec::new(),
             speculative_mode: true, // Start with optimistic assumption
-            headers: None,
+            headers,
+            keys: Vec::new(),
             output_buffer: Vec::with_capacity(chunk_target_bytes),
             chunk_target_bytes,
             record_count: 0,
+            projection: None,
+            needed: None,
+            ordered: true,
+            threads: None,
+            limits: ResourceLimits::default(),
+            warnings: Warnings::default(),
+            invalid_utf8: InvalidUtf8Policy::Error,
+            after_cr: false,
+            errors: RecordErrors::default(),
+            lines: 0,
+            preamble_left,
+            paths,
+        };
+        parser.intern_keys();
+        parser
+    }
+
+    /// Allow parallel batches to be emitted as they finish instead of in input order.
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Cap the threads used by the parallel path.
+    pub fn with_threads(mut self, threads: Option<usize>) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Reject fields larger than `limits.max_field_bytes`.
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Report skipped blank lines and rows that don't match the header.
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Skip data rows with invalid UTF-8 under `InvalidUtf8Policy::Skip`.
+    /// Replacement happens before the parser, so other policies fail here.
+    pub fn with_invalid_utf8(mut self, policy: InvalidUtf8Policy) -> Self {
+        self.invalid_utf8 = policy;
+        self
+    }
+
+    /// Skip or collect data rows that fail to convert instead of failing.
+    /// The header row always fails.
+    pub fn with_record_errors(mut self, errors: RecordErrors) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Only emit the named columns. Headerless columns are named `field_N`.
+    pub fn with_projection(mut self, columns: Vec<String>) -> Self {
+        self.projection = Some(columns);
+        if !self.config.has_headers {
+            self.resolve_projection();
+        }
+        self
+    }
+
+    /// Map projected column names to header indices
+    fn resolve_projection(&mut self) {
+        let Some(columns) = &self.projection else {
+            return;
+        };
+        let headers = self.headers.as_deref().unwrap_or(&[]);
+        let mut needed = vec![false; headers.len()];
+        for column in columns {
+            let mut found = false;
+            for (i, header) in headers.iter().enumerate() {
+                if header == column {
+                    needed[i] = true;
+                    found = true;
+                }
+            }
+            if found {
+                continue;
+            }
+            // Columns past the header row are keyed as field_N
+            if let Some(i) = column.strip_prefix("field_").and_then(|n| n.parse::<usize>().ok()) {
+                if i >= headers.len() {
+                    if needed.len() <= i {
+                        needed.resize(i + 1, false);
+                    }
+                    needed[i] = true;
+                }
+            }
         }
+        self.needed = Some(needed);
     }
 
     /// Uses buffer pooling and speculative parsing for optimal performance
     pub fn push_to_ndjson(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        if self.rows_done() {
+            return Ok(Vec::new());
+        }
+
         // Use pooled buffer for output
         let estimated_size = if self.partial_line.is_empty() {
             (chunk.len() as f64 * 1.3) as usize
@@ -71,7 +341,7 @@ impl CsvParser {
             ((self.partial_line.len() + chunk.len()) as f64 * 1.3) as usize
         };
         
-        let mut output = BUFFER_POOL.with(|pool: &BufferPool| pool.acquire_with_capacity(estimated_size));
+        let mut output = buffer_pool::acquire_shared(estimated_size);
 
         // Handle partial line by creating a temporary buffer
         let mut temp_buffer = Vec::new();
@@ -83,18 +353,24 @@ impl CsvParser {
             chunk
         };
 
-        let mut start = 0;
+        let mut start = self.skip_preamble(input_data);
         
         // Process line by line
-        while let Some(line_end) = self.find_line_end(&input_data[start..]) {
+        while let Some(line_end) = self.next_line_end(input_data, &mut start) {
             let line = &input_data[start..start + line_end];
             
             // Skip empty lines and whitespace-only lines
-            if !line.is_empty() && !line.iter().all(|&b| b.is_ascii_whitespace()) {
-                self.process_csv_line(line, &mut output)?;
+            let ends_cr = input_data[start + line_end] == b'\r';
+            if self.is_record_line(line, ends_cr) {
+                self.process_row(line, &mut output)?;
             }
+            self.count_lines(line, !ends_cr);
             
             start += line_end + 1; // +1 for newline
+            if self.rows_done() {
+                start = input_data.len();
+                break;
+            }
         }
 
         // Store remaining partial line
@@ -106,12 +382,16 @@ impl CsvParser {
         Ok(output)
     }
 
-    /// Process a chunk of CSV data and convert to NDJSON using parallel processing
-    /// This method splits large inputs into chunks and processes them in parallel
+    /// Process a chunk of CSV data and convert to NDJSON using parallel processing.
+    ///
+    /// Lines are split exactly as in [`push_to_ndjson`](Self::push_to_ndjson)
+    /// and converted in batches across the rayon pool. Unless `ordered` is
+    /// turned off, the output matches the sequential path byte for byte.
     #[cfg(feature = "threads")]
     pub fn push_to_ndjson_parallel(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
-        // For small chunks, use sequential processing
-        if chunk.len() < 64 * 1024 { // 64KB threshold
+        // For small chunks, use sequential processing. Skipped rows are
+        // reported with their line, which only the sequential path tracks.
+        if chunk.len() < 64 * 1024 || !self.errors.aborts() || self.rows_done() { // 64KB threshold
             return self.push_to_ndjson(chunk);
         }
 
@@ -132,123 +412,43 @@ impl CsvParser {
             chunk
         };
 
-        // Find all line boundaries first
-        let mut line_starts = Vec::new();
-        let mut line_ends = Vec::new();
-        let mut start = 0;
-        
-        while let Some(line_end) = self.find_line_end(&input_data[start..]) {
-            let absolute_end = start + line_end;
-            if absolute_end > start {
-                line_starts.push(start);
-                line_ends.push(absolute_end);
+        // Find line boundaries up front, skipping empty and whitespace-only lines
+        let mut lines = Vec::new();
+        let mut start = self.skip_preamble(input_data);
+        while let Some(line_end) = self.next_line_end(input_data, &mut start) {
+            let line = &input_data[start..start + line_end];
+            if self.is_record_line(line, input_data[start + line_end] == b'\r') {
+                lines.push(line);
             }
-            start = absolute_end + 1;
+            start += line_end + 1;
         }
 
-        // Process headers sequentially if needed
         let mut output = Vec::with_capacity(estimated_size);
-        let mut process_start = 0;
-
-        if self.config.has_headers && self.headers.is_none() && !line_starts.is_empty() {
-            // Process first line as headers
-            let header_line = &input_data[line_starts[0]..line_ends[0]];
-            std::str::from_utf8(header_line)?;
-            let fields = self.parse_fields(header_line)?;
-            let headers = fields
-                .iter()
-                .map(|field| std::str::from_utf8(field).map(str::to_string))
-                .collect::<std::result::Result<Vec<String>, _>>()?;
-            self.headers = Some(headers);
-            process_start = 1;
-        }
+        let mut records = lines.as_slice();
 
-        if line_starts.len() > process_start {
-            // Process remaining lines in parallel
-            let lines: Vec<_> = line_starts[process_start..]
-                .iter()
-                .zip(line_ends[process_start..].iter())
-                .filter(|(start, end)| {
-                    **end > **start &&
-                    // Skip whitespace-only lines
-                    !input_data[**start..**end].iter().all(|&b| b.is_ascii_whitespace())
-                })
-                .map(|(start, end)| &input_data[*start..*end])
-                .collect();
-
-            if lines.len() > 1 {
-                // Parallel processing using contiguous per-thread ranges to reduce synchronization.
-                let num_threads = rayon::current_num_threads();
-                let mut ranges: Vec<(usize, usize)> = Vec::with_capacity(num_threads);
-                let total = input_data.len();
-
-                // Compute approximate chunk size and then adjust to line boundaries
-                let mut start_idx = 0usize;
-                for t in 0..num_threads {
-                    let mut end_idx = if t + 1 == num_threads {
-                        total
-                    } else {
-                        ((total as f64) * ((t + 1) as f64) / (num_threads as f64)) as usize
-                    };
-                    // Move end_idx forward until a newline or EOF so each range ends at a safe boundary
-                    while end_idx < total && input_data[end_idx] != b'\n' {
-                        end_idx += 1;
-                    }
-                    if end_idx > total { end_idx = total; }
-                    ranges.push((start_idx, end_idx));
-                    start_idx = end_idx + 1; // next range starts after newline
-                    if start_idx >= total { break; }
-                }
+        // Headers must be known before records are converted
+        if self.config.has_headers && self.headers.is_none() {
+            if let Some((header, rest)) = records.split_first() {
+                self.process_csv_line(header, &mut output)?;
+                records = rest;
+            }
+        }
+        if let Some(max_rows) = self.config.max_rows {
+            let left = max_rows.saturating_sub(self.record_count);
+            if records.len() >= left {
+                records = &records[..left];
+                start = input_data.len();
+            }
+        }
 
-                if ranges.is_empty() {
-                    // Fallback to sequential processing
-                    for line in lines {
-                        // Skip whitespace-only lines
-                        if !line.iter().all(|&b| b.is_ascii_whitespace()) {
-                            self.process_csv_line(line, &mut output)?;
-                        }
-                    }
-                } else {
-                    // Prepare config and headers for workers
-                    let config_clone = self.config.clone();
-                    let headers_clone = self.headers.clone();
-
-                    let parallel_results: Result<Vec<Vec<u8>>> = ranges
-                        .into_par_iter()
-                        .map(|(s, e)| {
-                            if s >= e || s >= input_data.len() { return Ok(Vec::new()); }
-                            let slice = &input_data[s..e];
-                            // Split into lines within the slice
-                            let mut local_output = Vec::new();
-                            let mut local_start = 0usize;
-                            while let Some(pos) = memchr(b'\n', &slice[local_start..]) {
-                                let line_end = local_start + pos;
-                                let line = &slice[local_start..line_end];
-                                if !line.is_empty() && !line.iter().all(|&b| b.is_ascii_whitespace()) {
-                                    std::str::from_utf8(line)?;
-                                    // Parse fields (fast or quoted) using local config
-                                    let fields = CsvParser::parse_fields_static(&config_clone, line);
-                                    // Convert fields to JSON into local_output
-                                    CsvParser::fields_to_json_static(&headers_clone, &fields, &mut local_output);
-                                    local_output.push(b'\n');
-                                }
-                                local_start = line_end + 1;
-                            }
-                            Ok(local_output)
-                        })
-                        .collect();
-
-                    for part in parallel_results? {
-                        if !part.is_empty() {
-                            output.extend_from_slice(&part);
-                            // estimate record count from newlines
-                            self.record_count += bytecount::count(&part, b'\n');
-                        }
-                    }
-                }
-            } else if lines.len() == 1 {
-                // Single line, process sequentially
-                let line = lines[0];
+        if records.len() > 1 {
+            let this = &*self;
+            crate::parallel::convert_lines(records, self.threads, self.ordered, &mut output, |line, out| {
+                this.record_to_ndjson(line, out)
+            })?;
+            self.record_count += records.len();
+        } else {
+            for line in records {
                 self.process_csv_line(line, &mut output)?;
             }
         }
@@ -262,6 +462,45 @@ impl CsvParser {
         Ok(output)
     }
 
+    /// Drop the lines still to skip before the header from the start of
+    /// `data`, quotes and all. Returns where the rest begins.
+    fn skip_preamble(&mut self, data: &[u8]) -> usize {
+        let mut start = 0;
+        while self.preamble_left > 0 {
+            let Some(end) = memchr(b'\n', &data[start..]) else {
+                break;
+            };
+            start += end + 1;
+            self.preamble_left -= 1;
+            self.lines += 1;
+        }
+        start
+    }
+
+    /// The end of the line starting at `*start`, relative to it. Comment
+    /// lines are dropped first, moving `start` past them. `None` while there
+    /// is no complete line, or preamble lines are still to come.
+    fn next_line_end(&mut self, data: &[u8], start: &mut usize) -> Option<usize> {
+        if self.preamble_left > 0 {
+            return None;
+        }
+        while self.is_comment(&data[*start..]) {
+            let end = memchr(b'\n', &data[*start..])?;
+            *start += end + 1;
+            self.lines += 1;
+        }
+        self.find_line_end(&data[*start..])
+    }
+
+    fn is_comment(&self, line: &[u8]) -> bool {
+        self.config.comment_char.is_some_and(|comment| line.first() == Some(&comment))
+    }
+
+    /// Whether `max_rows` data rows have been converted.
+    fn rows_done(&self) -> bool {
+        self.config.max_rows.is_some_and(|max_rows| self.record_count >= max_rows)
+    }
+
     /// Find the end of a CSV line (handles quoted newlines and backslash escaping)
     fn find_line_end(&self, data: &[u8]) -> Option<usize> {
         let mut pos = 0;
@@ -299,19 +538,62 @@ impl CsvParser {
         None
     }
 
+    /// Whether `line` holds a row. Blank lines are reported and skipped,
+    /// except the empty remainder of a CRLF ending; `ends_cr` says whether
+    /// this line ended at a `\r`.
+    fn is_record_line(&mut self, line: &[u8], ends_cr: bool) -> bool {
+        let after_cr = std::mem::replace(&mut self.after_cr, ends_cr);
+        if !line.is_empty() && !line.iter().all(|&b| b.is_ascii_whitespace()) {
+            return true;
+        }
+        if !(line.is_empty() && after_cr) {
+            self.warnings.warn(WarningCode::BlankLineSkipped, || {
+                format!("blank line skipped after record {}", self.record_count)
+            });
+        }
+        false
+    }
+
+    /// Convert a line, applying the record error policy to data rows.
+    fn process_row(&mut self, line: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        if self.config.has_headers && self.headers.is_none() {
+            return self.process_csv_line(line, output);
+        }
+        let start = output.len();
+        self.process_csv_line(line, output).or_else(|error| {
+            output.truncate(start);
+            self.errors.handle(self.lines + 1, line, error)
+        })
+    }
+
+    /// Count the line endings of a consumed line, including quoted ones.
+    fn count_lines(&mut self, line: &[u8], ends_lf: bool) {
+        self.lines += bytecount::count(line, b'\n') as u64 + u64::from(ends_lf);
+    }
+
     /// Process a single CSV line and convert to NDJSON
     fn process_csv_line(&mut self, line: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        let is_header = self.config.has_headers && self.headers.is_none();
+        if !is_header && self.skips_invalid_row(line) {
+            return Ok(());
+        }
         std::str::from_utf8(line)?;
         // Parse fields using fast or quoted path
         let fields = self.parse_fields(line)?;
+        self.check_field_sizes(&fields)?;
 
         // Handle headers
         if self.config.has_headers && self.headers.is_none() {
-            let headers = fields
+            let mut headers = fields
                 .iter()
                 .map(|field| std::str::from_utf8(field).map(str::to_string))
                 .collect::<std::result::Result<Vec<String>, _>>()?;
+            for (header, column) in headers.iter_mut().zip(&self.config.columns) {
+                header.clone_from(&column.name);
+            }
             self.headers = Some(headers);
+            self.intern_keys();
+            self.resolve_projection();
             return Ok(());
         }
 
@@ -323,26 +605,50 @@ impl CsvParser {
         Ok(())
     }
 
+    /// Convert a data line to an NDJSON record once headers are known
+    #[cfg(feature = "threads")]
+    fn record_to_ndjson(&self, line: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        if self.skips_invalid_row(line) {
+            return Ok(());
+        }
+        std::str::from_utf8(line)?;
+        let fields = self.split_fields(line);
+        self.check_field_sizes(&fields)?;
+        self.fields_to_json(&fields, output)?;
+        output.push(b'\n');
+        Ok(())
+    }
+
+    /// Whether a data row is dropped for holding invalid UTF-8.
+    fn skips_invalid_row(&self, line: &[u8]) -> bool {
+        self.invalid_utf8 == InvalidUtf8Policy::Skip && !keep_line(line, &self.warnings)
+    }
+
+    fn check_field_sizes(&self, fields: &[Vec<u8>]) -> Result<()> {
+        if self.limits.max_field_bytes.is_some() {
+            for field in fields {
+                self.limits.check_field(field.len())?;
+            }
+        }
+        Ok(())
+    }
+
     /// Parse CSV fields with speculative fast path optimization
     /// Assumes no quotes initially, falls back to full parser if needed
     fn parse_fields(&mut self, line: &[u8]) -> Result<Vec<Vec<u8>>> {
-        // Speculative parsing: try fast path first if in speculative mode
-        if self.speculative_mode {
-            let has_quotes = memchr(self.config.quote, line).is_some();
-            
-            if !has_quotes {
-                // Fast path: no quotes, simple delimiter splitting
-                return Ok(self.parse_fields_fast(line));
-            } else {
-                // Detected quotes, switch to conservative mode for future lines
-                self.speculative_mode = false;
-            }
-        } else {
-            // Check if we can switch back to speculative mode
-            let has_quotes = memchr(self.config.quote, line).is_some();
-            if !has_quotes {
-                return Ok(self.parse_fields_fast(line));
-            }
+        // Speculative parsing: track whether recent lines needed the quoted path
+        let has_quotes = memchr(self.config.quote, line).is_some();
+        if self.speculative_mode && has_quotes {
+            // Detected quotes, switch to conservative mode for future lines
+            self.speculative_mode = false;
+        }
+        Ok(self.split_fields(line))
+    }
+
+    /// Split a line into fields, taking the fast path when it has no quotes
+    fn split_fields(&self, line: &[u8]) -> Vec<Vec<u8>> {
+        if memchr(self.config.quote, line).is_none() {
+            return self.parse_fields_fast(line);
         }
 
         // Quoted path: state machine for complex CSV
@@ -405,8 +711,8 @@ impl CsvParser {
                     in_quotes = true;
                 }
             } else if byte == self.config.delimiter && !in_quotes {
-                fields.push(self.finalize_field(&field));
-                field = Vec::new();
+                fields.push(self.project_field(fields.len(), &field));
+                field.clear();
             } else {
                 field.push(byte);
             }
@@ -415,133 +721,11 @@ impl CsvParser {
         }
 
         // Add last field
-        fields.push(self.finalize_field(&field));
-
-        Ok(fields)
-    }
-
-    /// Static variant of parse_fields that doesn't require &mut self, used by parallel workers
-    fn parse_fields_static(config: &CsvConfig, line: &[u8]) -> Vec<Vec<u8>> {
-        // Fast check for quotes
-        let has_quotes = memchr(config.quote, line).is_some();
-        if !has_quotes {
-            // Fast path
-            let mut fields = Vec::new();
-            let mut start = 0usize;
-            while let Some(pos) = memchr(config.delimiter, &line[start..]) {
-                let field = &line[start..start + pos];
-                fields.push(field.to_vec());
-                start += pos + 1;
-            }
-            if start <= line.len() {
-                fields.push(line[start..].to_vec());
-            }
-            return fields;
-        }
-
-        // Quoted path
-        let mut fields: Vec<Vec<u8>> = Vec::new();
-        let mut field: Vec<u8> = Vec::new();
-        let mut pos = 0usize;
-        let mut in_quotes = false;
+        fields.push(self.project_field(fields.len(), &field));
 
-        while pos < line.len() {
-            let byte = line[pos];
-            if byte == b'\\' && in_quotes && pos + 1 < line.len() {
-                // Handle backslash escaping (supports \", \\, \n, \r, \t, etc.)
-                let next_byte = line[pos + 1];
-                match next_byte {
-                    b'"' => {
-                        field.push(b'"');  // Keep the quote
-                        pos += 2;
-                        continue;
-                    }
-                    b'\\' => {
-                        field.push(b'\\');  // Keep the backslash
-                        pos += 2;
-                        continue;
-                    }
-                    b'n' => {
-                        field.push(b'\n');
-                        pos += 2;
-                        continue;
-                    }
-                    b'r' => {
-                        field.push(b'\r');
-                        pos += 2;
-                        continue;
-                    }
-                    b't' => {
-                        field.push(b'\t');
-                        pos += 2;
-                        continue;
-                    }
-                    _ => {
-                        // Unknown escape sequence - keep the backslash
-                        field.push(byte);
-                    }
-                }
-            } else if byte == config.quote {
-                if in_quotes {
-                    if let Some(escape) = config.escape {
-                        if pos + 1 < line.len() && line[pos + 1] == escape {
-                            field.push(escape);
-                            pos += 2;
-                            continue;
-                        }
-                    }
-                    in_quotes = false;
-                } else {
-                    in_quotes = true;
-                }
-            } else if byte == config.delimiter && !in_quotes {
-                fields.push(field);
-                field = Vec::new();
-            } else {
-                field.push(byte);
-            }
-            pos += 1;
-        }
-
-        fields.push(field);
         fields
     }
 
-    /// Static fields_to_json used by parallel workers. Writes JSON object bytes into output.
-    fn fields_to_json_static(headers: &Option<Vec<String>>, fields: &[Vec<u8>], output: &mut Vec<u8>) {
-        output.push(b'{');
-        for (i, field) in fields.iter().enumerate() {
-            if i > 0 { output.push(b','); }
-            output.push(b'"');
-            if let Some(hdrs) = headers {
-                if i < hdrs.len() {
-                    output.extend_from_slice(hdrs[i].as_bytes());
-                } else {
-                    write!(output, "field_{}", i).ok();
-                }
-            } else {
-                write!(output, "field_{}", i).ok();
-            }
-            output.extend_from_slice(b"\":\"");
-
-            // Escape field bytes
-            for &byte in field.iter() {
-                match byte {
-                    b'"' => output.extend_from_slice(b"\\\""),
-                    b'\\' => output.extend_from_slice(b"\\\\"),
-                    b'\n' => output.extend_from_slice(b"\\n"),
-                    b'\r' => output.extend_from_slice(b"\\r"),
-                    b'\t' => output.extend_from_slice(b"\\t"),
-                    b'\x08' => output.extend_from_slice(b"\\b"),
-                    b'\x0C' => output.extend_from_slice(b"\\f"),
-                    _ => output.push(byte),
-                }
-            }
-            output.push(b'"');
-        }
-        output.push(b'}');
-    }
-
     /// Fast path: parse unquoted CSV fields
     fn parse_fields_fast(&self, line: &[u8]) -> Vec<Vec<u8>> {
         let mut fields = Vec::new();
@@ -549,18 +733,27 @@ impl CsvParser {
 
         while let Some(pos) = memchr(self.config.delimiter, &line[start..]) {
             let field = &line[start..start + pos];
-            fields.push(self.finalize_field(field));
+            fields.push(self.project_field(fields.len(), field));
             start += pos + 1;
         }
 
         // Add last field
         if start <= line.len() {
-            fields.push(self.finalize_field(&line[start..]));
+            fields.push(self.project_field(fields.len(), &line[start..]));
         }
 
         fields
     }
 
+    /// Finalize a field, or skip materializing it when projected out
+    fn project_field(&self, index: usize, field: &[u8]) -> Vec<u8> {
+        if column_needed(&self.needed, index) {
+            self.finalize_field(field)
+        } else {
+            Vec::new()
+        }
+    }
+
     /// Finalize a field (trim if configured)
     fn finalize_field(&self, field: &[u8]) -> Vec<u8> {
         if self.config.trim_whitespace {
@@ -580,41 +773,144 @@ impl CsvParser {
         }
     }
 
+    /// Serialize each header's key and resolve its type, so records only
+    /// copy them.
+    fn intern_keys(&mut self) {
+        let headers = self.headers.as_deref().unwrap_or(&[]);
+        let keys = headers
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let mut prefix = Vec::with_capacity(name.len() + 3);
+                prefix.push(b'"');
+                self.escape_json_string(name.as_bytes(), &mut prefix);
+                prefix.extend_from_slice(b"\":");
+                let column_type = self
+                    .config
+                    .columns
+                    .get(i)
+                    .and_then(|column| column.column_type)
+                    .unwrap_or_else(|| self.column_type(name.as_bytes()));
+                ColumnKey { prefix, name: name.clone(), column_type }
+            })
+            .collect();
+        self.keys = keys;
+    }
+
     /// Convert fields to JSON object
     fn fields_to_json(&self, fields: &[Vec<u8>], output: &mut Vec<u8>) -> Result<()> {
+        let start = output.len();
         output.push(b'{');
 
         let headers = self.headers.as_ref();
-        let _field_count = fields.len();
+        if let Some(headers) = headers {
+            self.check_row_width(fields.len(), headers.len());
+        }
+        let mut first = true;
 
         for (i, field) in fields.iter().enumerate() {
-            if i > 0 {
+            if !column_needed(&self.needed, i) {
+                continue;
+            }
+            let column = self.config.columns.get(i);
+            let empty = column.and_then(|column| column.empty).filter(|_| field.is_empty());
+            if empty == Some(EmptyField::Omit) {
+                continue;
+            }
+            if !first {
                 output.push(b',');
             }
+            first = false;
 
             // Write key
-            output.push(b'"');
-            if let Some(headers) = headers {
-                if i < headers.len() {
-                    output.extend_from_slice(headers[i].as_bytes());
-                } else {
+            let key = self.keys.get(i);
+            let column_type = match key {
+                Some(key) => {
+                    output.extend_from_slice(&key.prefix);
+                    key.column_type
+                }
+                // Columns past the header row are keyed as field_N
+                None => {
+                    output.push(b'"');
+                    let key_start = output.len();
                     write!(output, "field_{}", i).unwrap();
+                    let column_type = match column.and_then(|column| column.column_type) {
+                        Some(column_type) => column_type,
+                        None => self.column_type(&output[key_start..]),
+                    };
+                    output.extend_from_slice(b"\":");
+                    column_type
                 }
-            } else {
-                write!(output, "field_{}", i).unwrap();
+            };
+            let column_error = |message: String| {
+                let key = key.map_or_else(|| format!("field_{i}"), |key| key.name.clone());
+                ConvertError::CsvParse(format!("column '{key}': {message}"))
+            };
+            if field.is_empty() && column.is_some_and(|column| !column.nullable) {
+                return Err(column_error("empty value in a column that isn't nullable".to_string()));
+            }
+
+            if empty == Some(EmptyField::Null) {
+                output.extend_from_slice(b"null");
+                continue;
             }
-            output.extend_from_slice(b"\":");
 
-            // Write value (always as string for safety)
+            // Write value: a string unless the column has another type
+            match column_type {
+                ColumnType::String => {}
+                ColumnType::Infer => {
+                    if let Some(literal) = infer_json_literal(field) {
+                        output.extend_from_slice(literal);
+                        continue;
+                    }
+                }
+                _ if field.is_empty() => {
+                    output.extend_from_slice(b"null");
+                    continue;
+                }
+                _ => {
+                    let format = column.and_then(|column| column.format.as_deref());
+                    let value = typed_json_value(field, column_type, format).map_err(column_error)?;
+                    output.extend_from_slice(&value);
+                    continue;
+                }
+            }
             output.push(b'"');
             self.escape_json_string(field, output);
             output.push(b'"');
         }
 
         output.push(b'}');
+        if self.config.unflatten {
+            unflatten_record(output, start, &self.paths)?;
+        }
         Ok(())
     }
 
+    /// Type of the column named `key` when the schema doesn't set one.
+    fn column_type(&self, key: &[u8]) -> ColumnType {
+        let default = if self.config.infer_types { ColumnType::Infer } else { ColumnType::String };
+        if self.config.column_types.is_empty() {
+            return default;
+        }
+        std::str::from_utf8(key)
+            .ok()
+            .and_then(|key| self.config.column_types.get(key))
+            .copied()
+            .unwrap_or(default)
+    }
+
+    fn check_row_width(&self, fields: usize, columns: usize) {
+        let code = match fields.cmp(&columns) {
+            std::cmp::Ordering::Less => WarningCode::MissingFields,
+            std::cmp::Ordering::Greater => WarningCode::ExtraFields,
+            std::cmp::Ordering::Equal => return,
+        };
+        self.warnings.warn(code, || {
+            format!("row with {fields} fields under a header of {columns} columns")
+        });
+    }
+
     /// Escape a string for JSON using optimized approach
     fn escape_json_string(&self, input: &[u8], output: &mut Vec<u8>) {
         // Fast path: check if any escaping is needed
@@ -646,9 +942,9 @@ impl CsvParser {
         let mut output = Vec::new();
 
         // Process any remaining partial line
-        if !self.partial_line.is_empty() {
-            let line = std::mem::take(&mut self.partial_line);
-            self.process_csv_line(&line, &mut output)?;
+        let line = std::mem::take(&mut self.partial_line);
+        if !line.is_empty() && self.preamble_left == 0 && !self.rows_done() && !self.is_comment(&line) {
+            self.process_row(&line, &mut output)?;
         }
 
         Ok(output)
@@ -658,7 +954,354 @@ impl CsvParser {
         self.partial_line.len()
     }
 
+    /// Drop the row cut off at the end of the last chunk.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn discard_partial(&mut self) {
+        self.partial_line.clear();
+        self.after_cr = false;
+    }
+
     pub fn record_count(&self) -> usize {
         self.record_count
     }
 }
+
+/// Whether column `index` survives projection (all columns do without one)
+fn column_needed(needed: &Option<Vec<bool>>, index: usize) -> bool {
+    match needed {
+        Some(flags) => flags.get(index).copied().unwrap_or(false),
+        None => true,
+    }
+}
+
+/// Rebuild the objects and arrays that a CSV writer flattened into `a.b` and
+/// `a.0` keys, in the record at `output[start..]`. Without `paths`, every
+/// dotted key is taken as flattened.
+fn unflatten_record(output: &mut Vec<u8>, start: usize, paths: &HashMap<String, Vec<String>>) -> Result<()> {
+    let flat: Map<String, Value> =
+        serde_json::from_slice(&output[start..]).map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+    let mut record = Map::new();
+    for (key, value) in flat {
+        if paths.is_empty() {
+            let path: Vec<&str> = key.split('.').collect();
+            insert_path(&mut record, &path, value);
+        } else if let Some(path) = paths.get(&key) {
+            let path: Vec<&str> = path.iter().map(String::as_str).collect();
+            insert_path(&mut record, &path, value);
+        } else {
+            record.insert(key, value);
+        }
+    }
+    let record: Map<String, Value> = record.into_iter().map(|(key, value)| (key, nest_arrays(value))).collect();
+    output.truncate(start);
+    serde_json::to_writer(&mut *output, &record).map_err(|e| ConvertError::JsonParse(e.to_string()))
+}
+
+/// Insert `value` under `path`, creating the objects on its way.
+fn insert_path(node: &mut Map<String, Value>, path: &[&str], value: Value) {
+    let [head, rest @ ..] = path else {
+        return;
+    };
+    if rest.is_empty() {
+        node.insert(head.to_string(), value);
+        return;
+    }
+    match node.entry(*head).or_insert_with(|| Value::Object(Map::new())) {
+        Value::Object(child) => insert_path(child, rest, value),
+        // A value already sits at `head`, so the rest stays flat beside it
+        _ => {
+            node.insert(path.join("."), value);
+        }
+    }
+}
+
+/// Turn the objects keyed `0` to `n` in `value` into arrays.
+fn nest_arrays(value: Value) -> Value {
+    let Value::Object(object) = value else {
+        return value;
+    };
+    let mut object: Map<String, Value> = object.into_iter().map(|(key, value)| (key, nest_arrays(value))).collect();
+    let len = object.len();
+    if len == 0 || !(0..len).all(|index| object.contains_key(&index.to_string())) {
+        return Value::Object(object);
+    }
+    Value::Array((0..len).map(|index| object.remove(&index.to_string()).unwrap_or_default()).collect())
+}
+
+/// The JSON value of a non-empty field in a column of a forced type, or why
+/// it doesn't parse as one.
+fn typed_json_value(
+    field: &[u8],
+    column_type: ColumnType,
+    format: Option<&str>,
+) -> std::result::Result<Vec<u8>, String> {
+    let text = std::str::from_utf8(field).map_err(|e| e.to_string())?;
+    let invalid = |kind: &str| match format {
+        Some(format) => format!("{text:?} is not {kind} in the format {format:?}"),
+        None => format!("{text:?} is not {kind}"),
+    };
+    let value = match column_type {
+        ColumnType::String | ColumnType::Infer => unreachable!("not a forced type"),
+        ColumnType::Integer => {
+            let value: i64 = text.parse().map_err(|_| invalid("an integer"))?;
+            value.to_string()
+        }
+        ColumnType::Number => {
+            let value = text
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| invalid("a number"))?;
+            // Keep the digits as written when they already form a JSON number
+            if infer_json_literal(field) == Some(field) {
+                return Ok(field.to_vec());
+            }
+            serde_json::Number::from_f64(value).ok_or_else(|| invalid("a number"))?.to_string()
+        }
+        ColumnType::Boolean => match text.to_ascii_lowercase().as_str() {
+            "true" | "1" | "yes" => "true".to_string(),
+            "false" | "0" | "no" => "false".to_string(),
+            _ => return Err(invalid("a boolean")),
+        },
+        ColumnType::Date => {
+            let date = NaiveDate::parse_from_str(text, format.unwrap_or("%Y-%m-%d"))
+                .map_err(|_| invalid("a date"))?;
+            format!("\"{}\"", date.format("%Y-%m-%d"))
+        }
+        ColumnType::Datetime => format!("\"{}\"", parse_datetime(text, format).ok_or_else(|| invalid("a datetime"))?),
+        ColumnType::Json => serde_json::from_str::<Value>(text).map_err(|_| invalid("JSON"))?.to_string(),
+    };
+    Ok(value.into_bytes())
+}
+
+/// Parse a date and time with `format`, or as RFC 3339 / `YYYY-MM-DD HH:MM:SS`
+/// without one, and render it as RFC 3339.
+fn parse_datetime(text: &str, format: Option<&str>) -> Option<String> {
+    const NAIVE: &str = "%Y-%m-%dT%H:%M:%S%.f";
+    let with_offset = match format {
+        Some(format) => DateTime::parse_from_str(text, format),
+        None => DateTime::parse_from_rfc3339(text),
+    };
+    if let Ok(datetime) = with_offset {
+        return Some(datetime.to_rfc3339());
+    }
+    let naive = match format {
+        Some(format) => NaiveDateTime::parse_from_str(text, format),
+        None => NaiveDateTime::parse_from_str(text, NAIVE)
+            .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f")),
+    };
+    naive.ok().map(|datetime| datetime.format(NAIVE).to_string())
+}
+
+/// The JSON literal for a field under type inference, or `None` to keep it a
+/// string. Empty fields become `null`, `true`/`false` in any case booleans,
+/// and numbers are kept as written when they are valid JSON numbers. Numbers
+/// with leading zeros (zip codes, IDs) and integers beyond `i64` stay strings.
+pub(crate) fn infer_json_literal(field: &[u8]) -> Option<&[u8]> {
+    if field.is_empty() {
+        return Some(b"null");
+    }
+    if field.eq_ignore_ascii_case(b"true") {
+        return Some(b"true");
+    }
+    if field.eq_ignore_ascii_case(b"false") {
+        return Some(b"false");
+    }
+    let digits = field.strip_prefix(b"-").unwrap_or(field);
+    let int_len = digits.iter().take_while(|b| b.is_ascii_digit()).count();
+    if int_len == 0 || (int_len > 1 && digits[0] == b'0') {
+        return None;
+    }
+    let mut rest = &digits[int_len..];
+    if rest.is_empty() {
+        // Only text the caller checked as UTF-8 gets here
+        let text = std::str::from_utf8(field).ok()?;
+        return text.parse::<i64>().is_ok().then_some(field);
+    }
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let len = fraction.iter().take_while(|b| b.is_ascii_digit()).count();
+        if len == 0 {
+            return None;
+        }
+        rest = &fraction[len..];
+    }
+    if let Some(exponent) = rest.strip_prefix(b"e").or_else(|| rest.strip_prefix(b"E")) {
+        let exponent = exponent
+            .strip_prefix(b"+")
+            .or_else(|| exponent.strip_prefix(b"-"))
+            .unwrap_or(exponent);
+        if exponent.is_empty() || !exponent.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        rest = &[];
+    }
+    if !rest.is_empty() {
+        return None;
+    }
+    // Overflowing exponents would turn into infinity in JSON readers
+    let value: f64 = std::str::from_utf8(field).ok()?.parse().ok()?;
+    value.is_finite().then_some(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projected(config: CsvConfig, columns: &[&str]) -> CsvParser {
+        CsvParser::new(config, 1024)
+            .with_projection(columns.iter().map(|c| c.to_string()).collect())
+    }
+
+    #[test]
+    fn projection_skips_unneeded_columns() {
+        let mut parser = projected(CsvConfig::default(), &["c", "a"]);
+        let mut output = parser
+            .push_to_ndjson(b"a,b,c\n1,2,3\n\"x,y\",\"skip\",z\n")
+            .unwrap();
+        output.extend(parser.finish().unwrap());
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"a\":\"1\",\"c\":\"3\"}\n{\"a\":\"x,y\",\"c\":\"z\"}\n"
+        );
+    }
+
+    #[test]
+    fn warns_on_blank_lines_and_ragged_rows() {
+        let warnings = Warnings::default();
+        let mut parser = CsvParser::new(CsvConfig::default(), 1024).with_warnings(warnings.clone());
+        parser.push_to_ndjson(b"a,b\r\n1,2\r").unwrap();
+        parser.push_to_ndjson(b"\n3,4\r\n").unwrap();
+        assert!(warnings.list().is_empty());
+
+        let output = parser.push_to_ndjson(b"\n5\n\n6,7,8\n").unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"a\":\"5\"}\n{\"a\":\"6\",\"b\":\"7\",\"field_2\":\"8\"}\n"
+        );
+        let codes: Vec<(WarningCode, u64)> = warnings.list().iter().map(|w| (w.code, w.count)).collect();
+        assert_eq!(
+            codes,
+            vec![
+                (WarningCode::BlankLineSkipped, 2),
+                (WarningCode::MissingFields, 1),
+                (WarningCode::ExtraFields, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_rows_with_invalid_utf8() {
+        let warnings = Warnings::default();
+        let mut parser = CsvParser::new(CsvConfig::default(), 1024)
+            .with_invalid_utf8(InvalidUtf8Policy::Skip)
+            .with_warnings(warnings.clone());
+        let mut output = parser.push_to_ndjson(b"name\ncaf\xe9\nok\n").unwrap();
+        output.extend(parser.finish().unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"name\":\"ok\"}\n");
+        assert_eq!(warnings.list()[0].code, WarningCode::InvalidUtf8Skipped);
+
+        let mut strict = CsvParser::new(CsvConfig::default(), 1024);
+        assert!(strict.push_to_ndjson(b"name\ncaf\xe9\n").is_err());
+    }
+
+    #[test]
+    fn projection_without_headers_uses_field_names() {
+        let config = CsvConfig {
+            has_headers: false,
+            ..CsvConfig::default()
+        };
+        let mut parser = projected(config, &["field_1"]);
+        let output = parser.push_to_ndjson(b"1,2,3\n").unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"field_1\":\"2\"}\n");
+    }
+
+    #[test]
+    fn infers_types() {
+        let config = CsvConfig {
+            infer_types: true,
+            column_types: HashMap::from([("zip".to_string(), ColumnType::String)]),
+            ..CsvConfig::default()
+        };
+        let mut parser = CsvParser::new(config, 1024);
+        let output = parser
+            .push_to_ndjson(b"id,price,ok,zip,note,code,big\n7,-1.5e3,TRUE,12345,,007,9223372036854775808\n")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":7,\"price\":-1.5e3,\"ok\":true,\"zip\":\"12345\",\"note\":null,\"code\":\"007\",\"big\":\"9223372036854775808\"}\n"
+        );
+
+        for text in ["1.", ".5", "+1", "1e", "NaN", "1e999", "0x1F", "1 "] {
+            assert_eq!(infer_json_literal(text.as_bytes()), None, "{text}");
+        }
+        assert_eq!(infer_json_literal(b"-0.25E+2"), Some(&b"-0.25E+2"[..]));
+    }
+
+    #[test]
+    fn column_types_opt_single_columns_in() {
+        let config = CsvConfig {
+            has_headers: false,
+            column_types: HashMap::from([("field_1".to_string(), ColumnType::Infer)]),
+            ..CsvConfig::default()
+        };
+        let mut parser = CsvParser::new(config, 1024);
+        let output = parser.push_to_ndjson(b"1,2\n").unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"field_0\":\"1\",\"field_1\":2}\n");
+    }
+
+    #[test]
+    fn applies_column_schema() {
+        let column = |name: &str, column_type, format: Option<&str>| CsvColumn {
+            column_type,
+            format: format.map(str::to_string),
+            ..CsvColumn::new(name)
+        };
+        let config = CsvConfig {
+            columns: vec![
+                column("id", Some(ColumnType::Integer), None),
+                column("price", Some(ColumnType::Number), None),
+                column("active", Some(ColumnType::Boolean), None),
+                column("born", Some(ColumnType::Date), Some("%d/%m/%Y")),
+                column("seen", Some(ColumnType::Datetime), None),
+                CsvColumn { nullable: false, ..CsvColumn::new("name") },
+            ],
+            ..CsvConfig::default()
+        };
+        let mut parser = CsvParser::new(config.clone(), 1024);
+        let output = parser
+            .push_to_ndjson(
+                b"ID,Price,Active,Born,Seen,Name,Note\n\
+                  007,+2.50,Yes,31/01/1990,2024-05-01 10:00:00,Ada,x\n\
+                  8,,0,,2024-05-01T10:00:00+02:00,Lin,\n",
+            )
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":7,\"price\":2.5,\"active\":true,\"born\":\"1990-01-31\",\"seen\":\"2024-05-01T10:00:00\",\"name\":\"Ada\",\"Note\":\"x\"}\n\
+             {\"id\":8,\"price\":null,\"active\":false,\"born\":null,\"seen\":\"2024-05-01T10:00:00+02:00\",\"name\":\"Lin\",\"Note\":\"\"}\n"
+        );
+
+        let mut parser = CsvParser::new(config.clone(), 1024);
+        let error = parser.push_to_ndjson(b"a,b,c,d,e,f\nx,1,1,01/01/2000,,Ada\n").unwrap_err();
+        assert_eq!(error.to_string(), "CSV parse error: column 'id': \"x\" is not an integer");
+        let mut parser = CsvParser::new(config.clone(), 1024);
+        let error = parser.push_to_ndjson(b"a,b,c,d,e,f\n1,1,1,2000-01-01,,Ada\n").unwrap_err();
+        assert!(error.to_string().contains("not a date in the format \"%d/%m/%Y\""));
+        let mut parser = CsvParser::new(config, 1024);
+        let error = parser.push_to_ndjson(b"a,b,c,d,e,f\n1,1,1,,,\n").unwrap_err();
+        assert!(error.to_string().contains("column 'name': empty value"));
+    }
+
+    #[test]
+    fn schema_names_headerless_columns() {
+        let config = CsvConfig {
+            has_headers: false,
+            columns: vec![CsvColumn::new("a")],
+            ..CsvConfig::default()
+        };
+        let mut parser = projected(config, &["a"]);
+        let output = parser.push_to_ndjson(b"1,2\n").unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "{\"a\":\"1\"}\n");
+    }
+}
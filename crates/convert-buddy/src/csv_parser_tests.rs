@@ -109,4 +109,132 @@ mod csv_parser_tests {
         
         assert!(output.contains("Alice"));
     }
+
+    #[test]
+    fn test_header_keys_are_escaped_and_extra_columns_numbered() {
+        let mut config = CsvConfig::default();
+        config.infer_types = true;
+        let mut parser = CsvParser::new(config, 1024);
+
+        let input = b"\"say \"\"hi\"\"\",n\nx,1,extra\ny,2\n";
+        let result = parser.push_to_ndjson(input).unwrap();
+        assert_eq!(
+            String::from_utf8(result).unwrap(),
+            "{\"say \\\"hi\\\"\":\"x\",\"n\":1,\"field_2\":\"extra\"}\n{\"say \\\"hi\\\"\":\"y\",\"n\":2}\n"
+        );
+    }
+
+    #[test]
+    fn test_skip_rows_header_row_and_max_rows() {
+        let config = CsvConfig {
+            skip_rows: 1,
+            header_row: 1,
+            max_rows: Some(2),
+            ..CsvConfig::default()
+        };
+        let mut parser = CsvParser::new(config, 1024);
+
+        let input = b"Report \"Q3\nexported 2024-01-01, by ops\nid,name\n1,a\n2,b\n3,c\n4";
+        let mut output = Vec::new();
+        for chunk in input.chunks(7) {
+            output.extend(parser.push_to_ndjson(chunk).unwrap());
+        }
+        output.extend(parser.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":\"1\",\"name\":\"a\"}\n{\"id\":\"2\",\"name\":\"b\"}\n"
+        );
+        assert_eq!(parser.partial_size(), 0);
+    }
+
+    #[test]
+    fn test_comment_lines_are_skipped() {
+        let config = CsvConfig {
+            comment_char: Some(b'#'),
+            ..CsvConfig::default()
+        };
+        let mut parser = CsvParser::new(config, 1024);
+
+        let input = b"# sensor \"A\nid,temp\r\n1,20.5\r\n# recalibrated, see notes\r\n2,#3\r\n# end";
+        let mut output = Vec::new();
+        for chunk in input.chunks(5) {
+            output.extend(parser.push_to_ndjson(chunk).unwrap());
+        }
+        output.extend(parser.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":\"1\",\"temp\":\"20.5\"}\n{\"id\":\"2\",\"temp\":\"#3\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_field_size_limit() {
+        let limits = crate::ResourceLimits {
+            max_field_bytes: Some(5),
+            ..Default::default()
+        };
+        let mut parser = CsvParser::new(CsvConfig::default(), 1024).with_limits(limits);
+        assert!(parser.push_to_ndjson(b"name,age\n\"Alice\",30\n").is_ok());
+        let err = parser.push_to_ndjson(b"Bartholomew,41\n").unwrap_err();
+        assert!(matches!(err, crate::ConvertError::LimitExceeded(_)));
+    }
+
+    #[cfg(feature = "threads")]
+    fn parallel_sample() -> Vec<u8> {
+        let mut input = b"id, name ,note\r\n".to_vec();
+        for i in 0..4000 {
+            input.extend_from_slice(
+                format!("{i}, item {i} ,\"line one\nline \"\"{i}\"\"\"\r\n\n").as_bytes(),
+            );
+        }
+        input
+    }
+
+    #[cfg(feature = "threads")]
+    fn convert(parallel: bool, ordered: bool, input: &[u8], chunk_size: usize) -> Vec<u8> {
+        let config = CsvConfig {
+            trim_whitespace: true,
+            ..CsvConfig::default()
+        };
+        let mut parser = CsvParser::new(config, 1024).with_ordered(ordered);
+        let mut output = Vec::new();
+        for chunk in input.chunks(chunk_size) {
+            let part = if parallel {
+                parser.push_to_ndjson_parallel(chunk)
+            } else {
+                parser.push_to_ndjson(chunk)
+            };
+            output.extend(part.unwrap());
+        }
+        output.extend(parser.finish().unwrap());
+        output
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_parallel_output_matches_sequential() {
+        let input = parallel_sample();
+        let sequential = convert(false, true, &input, input.len());
+        assert_eq!(sequential.iter().filter(|&&b| b == b'\n').count(), 4000);
+
+        for chunk_size in [input.len(), 100_003, 70_001] {
+            let parallel = convert(true, true, &input, chunk_size);
+            assert!(parallel == sequential, "parallel output differs at chunk size {chunk_size}");
+        }
+    }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_unordered_parallel_output_has_the_same_records() {
+        let input = parallel_sample();
+        let sorted_lines = |output: &[u8]| {
+            let mut lines: Vec<Vec<u8>> = output.split(|&b| b == b'\n').map(<[u8]>::to_vec).collect();
+            lines.sort();
+            lines
+        };
+
+        let sequential = convert(false, true, &input, input.len());
+        let unordered = convert(true, false, &input, input.len());
+        assert_eq!(sorted_lines(&unordered), sorted_lines(&sequential));
+    }
 }
@@ -1,57 +1,311 @@
-use crate::error::Result;
+use crate::csv_parser::{ColumnType, CsvColumn, CsvSchema, EmptyField};
+use crate::error::{ConvertError, Result};
+use crate::number_format::FloatFormat;
+use crate::projection::OutputColumn;
+use crate::record_errors::RecordErrors;
+use crate::ndjson_writer::LineEnding;
+use crate::transform::record_snippet;
+use crate::warnings::{WarningCode, Warnings};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// What to do when flattening produces the same dotted key twice
+/// (e.g. a record with both a literal `"a.b"` key and a nested `a: {b: ...}`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlattenCollisionPolicy {
+    /// Fail the record with an error naming the colliding key
+    Error,
+    /// Keep both values, renaming the later one to `key_1`, `key_2`, ...
+    Suffix,
+    /// The value flattened last overwrites earlier ones
+    #[default]
+    LastWins,
+}
+
+impl FlattenCollisionPolicy {
+    pub fn from_string(s: &str) -> Option<FlattenCollisionPolicy> {
+        match s.to_lowercase().as_str() {
+            "error" => Some(FlattenCollisionPolicy::Error),
+            "suffix" => Some(FlattenCollisionPolicy::Suffix),
+            "lastwins" | "last_wins" | "last-wins" => Some(FlattenCollisionPolicy::LastWins),
+            _ => None,
+        }
+    }
+}
+
+/// Which CSV output fields are wrapped in quotes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    /// Only fields holding the delimiter, the quote itself or a line break
+    #[default]
+    Minimal,
+    /// Every field, including the header row
+    Always,
+}
+
+/// CSV writer output settings
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CsvWriterConfig {
+    #[serde(deserialize_with = "single_byte")]
+    pub delimiter: u8,
+    /// Quotes inside a quoted field are doubled.
+    #[serde(deserialize_with = "single_byte")]
+    pub quote: u8,
+    pub quote_style: QuoteStyle,
+    pub line_ending: LineEnding,
+    /// Whether the first row holds the column names.
+    pub include_header: bool,
+    /// Columns to write, in this order. Without it the columns are the sorted
+    /// keys of the first record. Keys outside the columns are dropped.
+    pub columns: Option<Vec<String>>,
+    /// Note the JSON types written into each column, for a [`CsvSchema`] that
+    /// lets a later CSV→JSON conversion restore them.
+    pub type_sidecar: bool,
+}
+
+impl CsvWriterConfig {
+    /// Tab-separated output.
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: b'\t',
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for CsvWriterConfig {
+    fn default() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            quote_style: QuoteStyle::Minimal,
+            line_ending: LineEnding::Lf,
+            include_header: true,
+            columns: None,
+            type_sidecar: false,
+        }
+    }
+}
+
+/// A one-character string such as `";"`, read as its ASCII byte.
+fn single_byte<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<u8, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    match value.as_bytes() {
+        [byte] => Ok(*byte),
+        _ => Err(serde::de::Error::custom(format!(
+            "expected a single ASCII character, got {value:?}"
+        ))),
+    }
+}
+
+/// Kinds of JSON value written into a column, as bits.
+mod kind {
+    pub const STRING: u16 = 1;
+    pub const EMPTY_STRING: u16 = 1 << 1;
+    pub const INTEGER: u16 = 1 << 2;
+    pub const NUMBER: u16 = 1 << 3;
+    pub const BOOLEAN: u16 = 1 << 4;
+    /// Arrays in arrays, written as JSON text.
+    pub const JSON: u16 = 1 << 5;
+    pub const NULL: u16 = 1 << 6;
+    /// Records without the key.
+    pub const MISSING: u16 = 1 << 7;
+    /// Strings written as JSON text, into a column of other values.
+    pub const JSON_STRING: u16 = 1 << 8;
+    /// Values other than strings, which are also valid JSON text.
+    pub const VALUES: u16 = INTEGER | NUMBER | BOOLEAN | JSON;
+}
+
+/// A flattened value: the text of its field and its [`kind`].
+struct FlatValue {
+    text: String,
+    kind: u16,
+    /// Keys down to the value when it was nested, noted for `type_sidecar`.
+    path: Vec<String>,
+}
+
+/// What was written into a column so far.
+#[derive(Debug)]
+struct ColumnSeen {
+    name: String,
+    kinds: u16,
+    /// `path` of the first value written, if any was.
+    path: Option<Vec<String>>,
+}
+
+/// The kinds of value written into each column, shared between the CSV
+/// writers of a converter and the converter that builds a [`CsvSchema`] from them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ColumnKinds(Arc<Mutex<Vec<ColumnSeen>>>);
+
+impl ColumnKinds {
+    /// Note the kinds of a row about to be written. A column that so far
+    /// holds only numbers, booleans or JSON text gets its strings as JSON
+    /// text too, so the whole column reads back as [`ColumnType::Json`].
+    fn record(&self, headers: &[String], row: &mut [FlatValue]) {
+        let mut columns = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if columns.is_empty() {
+            columns.extend(headers.iter().map(|header| ColumnSeen {
+                name: header.clone(),
+                kinds: 0,
+                path: None,
+            }));
+        }
+        for (column, value) in columns.iter_mut().zip(row) {
+            if value.kind & kind::STRING != 0 && column.kinds & kind::VALUES != 0 && column.kinds & kind::STRING == 0 {
+                value.text = Value::String(std::mem::take(&mut value.text)).to_string();
+                value.kind = kind::JSON_STRING;
+            }
+            column.kinds |= value.kind;
+            if column.path.is_none() && value.kind != kind::MISSING {
+                column.path = Some(std::mem::take(&mut value.path));
+            }
+        }
+    }
+
+    /// `None` until a record is written.
+    pub(crate) fn schema(&self) -> Option<CsvSchema> {
+        let columns = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if columns.is_empty() {
+            return None;
+        }
+        let columns: Vec<CsvColumn> = columns.iter().map(column_schema).collect();
+        Some(CsvSchema {
+            unflatten: columns.iter().any(|column| !column.path.is_empty()),
+            columns,
+        })
+    }
+}
+
+/// The type that reads back the values of a column. Strings that come
+/// before other kinds of value in a column are written as they are, so such
+/// a column is inferred, which turns strings like `"12"` into numbers.
+fn column_schema(column: &ColumnSeen) -> CsvColumn {
+    let kinds = column.kinds;
+    let values = kinds & !(kind::NULL | kind::MISSING | kind::EMPTY_STRING);
+    let column_type = match values {
+        0 | kind::STRING => ColumnType::String,
+        kind::INTEGER => ColumnType::Integer,
+        kind::BOOLEAN => ColumnType::Boolean,
+        kind::JSON => ColumnType::Json,
+        _ if values & !(kind::INTEGER | kind::NUMBER) == 0 => ColumnType::Number,
+        _ if values & kind::STRING == 0 => ColumnType::Json,
+        _ => ColumnType::Infer,
+    };
+    // An empty field stands for an empty string first, then null, then no key
+    let empty = if kinds & kind::EMPTY_STRING != 0 {
+        None
+    } else if kinds & kind::NULL != 0 {
+        (column_type == ColumnType::String).then_some(EmptyField::Null)
+    } else if kinds & kind::MISSING != 0 {
+        Some(EmptyField::Omit)
+    } else {
+        None
+    };
+    CsvColumn {
+        column_type: Some(column_type),
+        empty,
+        path: column.path.clone().unwrap_or_default(),
+        ..CsvColumn::new(&column.name)
+    }
+}
 
 /// CSV writer that converts JSON objects to CSV format
 pub struct CsvWriter {
+    config: CsvWriterConfig,
     headers: Vec<String>,
+    /// The flattened key written into each of `headers`.
+    sources: Vec<String>,
+    /// `sources` as a set, to find keys outside them.
+    header_set: HashSet<String>,
     headers_written: bool,
+    collision_policy: FlattenCollisionPolicy,
+    warnings: Warnings,
+    float_format: Option<FloatFormat>,
+    column_kinds: ColumnKinds,
+    output_columns: Option<Vec<OutputColumn>>,
+    errors: RecordErrors,
+    /// Objects received so far; the `line` of a record that fails
+    records: u64,
 }
 
 impl CsvWriter {
     pub fn new() -> Self {
         Self {
+            config: CsvWriterConfig::default(),
             headers: Vec::new(),
+            sources: Vec::new(),
+            header_set: HashSet::new(),
             headers_written: false,
+            collision_policy: FlattenCollisionPolicy::default(),
+            warnings: Warnings::default(),
+            float_format: None,
+            column_kinds: ColumnKinds::default(),
+            output_columns: None,
+            errors: RecordErrors::default(),
+            records: 0,
         }
     }
 
+    pub fn with_config(mut self, config: CsvWriterConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_collision_policy(mut self, policy: FlattenCollisionPolicy) -> Self {
+        self.collision_policy = policy;
+        self
+    }
+
+    /// Report skipped records, dropped keys and last-wins collisions.
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Apply `onRecordError` to records whose flattened keys collide under
+    /// [`FlattenCollisionPolicy::Error`].
+    pub fn with_record_errors(mut self, errors: RecordErrors) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Write non-integer numbers with `format` instead of as they come.
+    pub fn with_float_format(mut self, format: Option<FloatFormat>) -> Self {
+        self.float_format = format;
+        self
+    }
+
+    /// Where to note the kinds of value written when `type_sidecar` is set.
+    pub(crate) fn with_column_kinds(mut self, column_kinds: ColumnKinds) -> Self {
+        self.column_kinds = column_kinds;
+        self
+    }
+
+    /// Write these columns, renamed, in place of `config.columns`. `from`
+    /// names a flattened key, so nested fields like `address.city` can be
+    /// picked too.
+    pub fn with_output_columns(mut self, columns: Option<Vec<OutputColumn>>) -> Self {
+        self.output_columns = columns;
+        self
+    }
+
     /// Process a JSON line (NDJSON format) and convert to CSV
     pub fn process_json_line(&mut self, json_line: &str) -> Result<Vec<u8>> {
-        let mut output = Vec::new();
-        
-        // Parse the JSON to extract fields
-        if let Ok(value) = serde_json::from_str::<serde_json::Value>(json_line) {
-            if let Some(obj) = value.as_object() {
-                // Extract all keys (flattened)
-                let mut fields = HashMap::new();
-                self.flatten_object("", obj, &mut fields);
-                
-                // Update headers if this is the first row or we found new fields
-                let mut all_keys: HashSet<String> = fields.keys().cloned().collect();
-                for header in &self.headers {
-                    all_keys.insert(header.clone());
-                }
-                let mut sorted_keys: Vec<String> = all_keys.into_iter().collect();
-                sorted_keys.sort();
-                
-                // Write headers if not written yet
-                if !self.headers_written {
-                    self.headers = sorted_keys.clone();
-                    self.write_csv_row(&self.headers, &mut output);
-                    self.headers_written = true;
-                }
-                
-                // Write data row
-                let mut row_values = Vec::new();
-                for header in &self.headers {
-                    let value = fields.get(header).cloned().unwrap_or_default();
-                    row_values.push(value);
-                }
-                self.write_csv_row(&row_values, &mut output);
+        match serde_json::from_str::<serde_json::Value>(json_line) {
+            Ok(value) => self.process_json_value(&value),
+            Err(_) => {
+                self.warnings.warn(WarningCode::RecordSkipped, || {
+                    format!("skipped a line that isn't JSON: {}", record_snippet(json_line.as_bytes()))
+                });
+                Ok(Vec::new())
             }
         }
-        
-        Ok(output)
     }
 
     /// Process a JSON value (borrowed) and convert to CSV without reparsing
@@ -59,39 +313,84 @@ impl CsvWriter {
         let mut output = Vec::new();
 
         if let Some(obj) = value.as_object() {
+            self.records += 1;
             // Extract all keys (flattened)
             let mut fields = HashMap::new();
-            self.flatten_object("", obj, &mut fields);
-
-            // Update headers if this is the first row or we found new fields
-            let mut all_keys: HashSet<String> = fields.keys().cloned().collect();
-            for header in &self.headers {
-                all_keys.insert(header.clone());
+            if let Err(key) = self.flatten_object("", &mut Vec::new(), obj, &mut fields) {
+                let raw = value.to_string();
+                let error = ConvertError::Transform(format!(
+                    "flattened key '{key}' collides with an existing field in {}",
+                    record_snippet(raw.as_bytes())
+                ));
+                self.errors.handle(self.records, raw.as_bytes(), error)?;
+                return Ok(output);
             }
-            let mut sorted_keys: Vec<String> = all_keys.into_iter().collect();
-            sorted_keys.sort();
 
-            // Write headers if not written yet
+            // Headers are fixed by the config or by the first record
             if !self.headers_written {
-                self.headers = sorted_keys.clone();
-                self.write_csv_row(&self.headers, &mut output);
-                self.headers_written = true;
+                let (headers, sources) = self.configured_columns().unwrap_or_else(|| {
+                    let mut sorted_keys: Vec<String> = fields.keys().cloned().collect();
+                    sorted_keys.sort();
+                    (sorted_keys.clone(), sorted_keys)
+                });
+                self.set_headers(headers, sources, &mut output);
             }
 
             // Write data row
-            let mut row_values = Vec::new();
-            for header in &self.headers {
-                let value = fields.get(header).cloned().unwrap_or_default();
-                row_values.push(value);
+            if self.config.type_sidecar {
+                let mut row: Vec<FlatValue> = self
+                    .headers
+                    .iter()
+                    .zip(&self.sources)
+                    .map(|(header, source)| match fields.get(source) {
+                        Some(value) => FlatValue {
+                            text: value.text.clone(),
+                            kind: value.kind,
+                            // A renamed column isn't nested back
+                            path: if header == source { value.path.clone() } else { Vec::new() },
+                        },
+                        None => FlatValue { text: String::new(), kind: kind::MISSING, path: Vec::new() },
+                    })
+                    .collect();
+                self.column_kinds.record(&self.headers, &mut row);
+                let row_values: Vec<String> = row.into_iter().map(|value| value.text).collect();
+                self.write_csv_row(&row_values, &mut output);
+            } else {
+                let mut row_values = Vec::new();
+                for source in &self.sources {
+                    let value = fields.get(source).map(|value| value.text.clone()).unwrap_or_default();
+                    row_values.push(value);
+                }
+                self.write_csv_row(&row_values, &mut output);
+            }
+
+            if let Some(key) = fields.keys().find(|key| !self.header_set.contains(*key)) {
+                self.warnings.warn(WarningCode::FieldDropped, || {
+                    format!("key '{key}' is not in the CSV header and was dropped")
+                });
             }
-            self.write_csv_row(&row_values, &mut output);
+        } else {
+            self.warnings.warn(WarningCode::RecordSkipped, || {
+                format!("skipped a record that isn't an object: {}", record_snippet(value.to_string().as_bytes()))
+            });
         }
 
         Ok(output)
     }
 
-    /// Flatten a JSON object into dot-notation keys with indexed arrays
-    fn flatten_object(&self, prefix: &str, obj: &serde_json::Map<String, serde_json::Value>, result: &mut HashMap<String, String>) {
+    /// Flatten a JSON object into dot-notation keys with indexed arrays.
+    /// `dots` holds the positions in `prefix` of the dots it added. Fails with
+    /// the colliding key under [`FlattenCollisionPolicy::Error`].
+    fn flatten_object(
+        &self,
+        prefix: &str,
+        dots: &mut Vec<usize>,
+        obj: &serde_json::Map<String, Value>,
+        result: &mut HashMap<String, FlatValue>,
+    ) -> std::result::Result<(), String> {
+        if !prefix.is_empty() {
+            dots.push(prefix.len());
+        }
         for (key, value) in obj {
             let new_key = if prefix.is_empty() {
                 key.clone()
@@ -100,81 +399,162 @@ impl CsvWriter {
             };
             
             match value {
-                serde_json::Value::Object(nested) => {
-                    self.flatten_object(&new_key, nested, result);
+                Value::Object(nested) => {
+                    self.flatten_object(&new_key, dots, nested, result)?;
                 }
-                serde_json::Value::Array(arr) => {
+                Value::Array(arr) => {
                     // Flatten array with indexed keys: field.0, field.1, etc.
+                    dots.push(new_key.len());
                     for (idx, item) in arr.iter().enumerate() {
                         let indexed_key = format!("{}.{}", new_key, idx);
                         match item {
-                            serde_json::Value::Object(nested) => {
-                                self.flatten_object(&indexed_key, nested, result);
+                            Value::Object(nested) => {
+                                self.flatten_object(&indexed_key, dots, nested, result)?;
                             }
-                            serde_json::Value::String(s) => {
-                                result.insert(indexed_key, s.clone());
-                            }
-                            serde_json::Value::Number(n) => {
-                                result.insert(indexed_key, n.to_string());
-                            }
-                            serde_json::Value::Bool(b) => {
-                                result.insert(indexed_key, b.to_string());
-                            }
-                            serde_json::Value::Null => {
-                                result.insert(indexed_key, String::new());
-                            }
-                            serde_json::Value::Array(nested_arr) => {
-                                // Nested arrays: serialize as JSON string
-                                result.insert(indexed_key, serde_json::to_string(nested_arr).unwrap_or_default());
+                            _ => {
+                                let value = self.flat_value(item, &indexed_key, dots);
+                                self.insert_flat(result, indexed_key, value)?
                             }
                         }
                     }
+                    dots.pop();
                 }
-                serde_json::Value::String(s) => {
-                    result.insert(new_key, s.clone());
+                _ => {
+                    let value = self.flat_value(value, &new_key, dots);
+                    self.insert_flat(result, new_key, value)?
                 }
-                serde_json::Value::Number(n) => {
-                    result.insert(new_key, n.to_string());
-                }
-                serde_json::Value::Bool(b) => {
-                    result.insert(new_key, b.to_string());
-                }
-                serde_json::Value::Null => {
-                    result.insert(new_key, String::new());
+            }
+        }
+        if !prefix.is_empty() {
+            dots.pop();
+        }
+        Ok(())
+    }
+
+    /// The field text of a value that isn't flattened further, at `key`.
+    fn flat_value(&self, value: &Value, key: &str, dots: &[usize]) -> FlatValue {
+        let mut path = Vec::new();
+        if self.config.type_sidecar && !dots.is_empty() {
+            let mut start = 0;
+            for &dot in dots {
+                path.push(key[start..dot].to_string());
+                start = dot + 1;
+            }
+            path.push(key[start..].to_string());
+        }
+        let (text, kind) = match value {
+            Value::String(s) if s.is_empty() => (String::new(), kind::STRING | kind::EMPTY_STRING),
+            Value::String(s) => (s.clone(), kind::STRING),
+            Value::Number(n) => {
+                let kind = if n.is_i64() { kind::INTEGER } else { kind::NUMBER };
+                (FloatFormat::number_text(self.float_format.as_ref(), n), kind)
+            }
+            Value::Bool(b) => (b.to_string(), kind::BOOLEAN),
+            Value::Null => (String::new(), kind::NULL),
+            // Nested arrays: serialize as JSON string
+            Value::Array(_) | Value::Object(_) => (value.to_string(), kind::JSON),
+        };
+        FlatValue { text, kind, path }
+    }
+
+    /// Insert a flattened key, applying the collision policy if it already exists
+    fn insert_flat(
+        &self,
+        result: &mut HashMap<String, FlatValue>,
+        key: String,
+        value: FlatValue,
+    ) -> std::result::Result<(), String> {
+        let key = match result.entry(key) {
+            Entry::Vacant(slot) => {
+                slot.insert(value);
+                return Ok(());
+            }
+            Entry::Occupied(slot) => slot.key().clone(),
+        };
+
+        match self.collision_policy {
+            FlattenCollisionPolicy::Error => Err(key),
+            FlattenCollisionPolicy::Suffix => {
+                let mut n = 1;
+                let mut candidate = format!("{}_{}", key, n);
+                while result.contains_key(&candidate) {
+                    n += 1;
+                    candidate = format!("{}_{}", key, n);
                 }
+                // The renamed key isn't nested back where it came from
+                result.insert(candidate, FlatValue { path: Vec::new(), ..value });
+                Ok(())
             }
+            FlattenCollisionPolicy::LastWins => {
+                self.warnings.warn(WarningCode::FlattenCollision, || {
+                    format!("flattened key '{key}' collided; the last value was kept")
+                });
+                result.insert(key, value);
+                Ok(())
+            }
+        }
+    }
+
+    /// The column names and the keys written into them, when they don't
+    /// depend on the first record.
+    fn configured_columns(&self) -> Option<(Vec<String>, Vec<String>)> {
+        if let Some(columns) = &self.output_columns {
+            let names = columns.iter().map(|column| column.name().to_string()).collect();
+            let sources = columns.iter().map(|column| column.from.clone()).collect();
+            return Some((names, sources));
         }
+        self.config.columns.clone().map(|columns| (columns.clone(), columns))
+    }
+
+    /// Fix the columns, writing the header row unless it's turned off
+    fn set_headers(&mut self, headers: Vec<String>, sources: Vec<String>, output: &mut Vec<u8>) {
+        if self.config.include_header {
+            self.write_csv_row(&headers, output);
+        }
+        self.header_set = sources.iter().cloned().collect();
+        self.sources = sources;
+        self.headers = headers;
+        self.headers_written = true;
     }
 
     /// Write a CSV row
     fn write_csv_row(&self, values: &[String], output: &mut Vec<u8>) {
+        let (delimiter, quote) = (self.config.delimiter, self.config.quote);
         for (i, value) in values.iter().enumerate() {
             if i > 0 {
-                output.push(b',');
+                output.push(delimiter);
             }
             
-            // Quote and escape if necessary
-            if value.contains(',') || value.contains('"') || value.contains('\n') {
-                output.push(b'"');
-                for ch in value.chars() {
-                    if ch == '"' {
-                        output.extend_from_slice(b"\"\"");
-                    } else {
-                        let mut buf = [0u8; 4];
-                        let s = ch.encode_utf8(&mut buf);
-                        output.extend_from_slice(s.as_bytes());
+            // Quote and escape if necessary; a lone empty field is quoted so
+            // that the row isn't read as a blank line
+            let needs_quotes = self.config.quote_style == QuoteStyle::Always
+                || (values.len() == 1 && value.is_empty())
+                || value
+                    .bytes()
+                    .any(|b| b == delimiter || b == quote || b == b'\n' || b == b'\r');
+            if needs_quotes {
+                output.push(quote);
+                for &b in value.as_bytes() {
+                    if b == quote {
+                        output.push(quote);
                     }
+                    output.push(b);
                 }
-                output.push(b'"');
+                output.push(quote);
             } else {
                 output.extend_from_slice(value.as_bytes());
             }
         }
-        output.push(b'\n');
+        output.extend_from_slice(self.config.line_ending.as_bytes());
     }
 
+    /// With configured columns, input without records still gets a header row.
     pub fn finish(&mut self) -> Result<Vec<u8>> {
-        Ok(Vec::new())
+        let mut output = Vec::new();
+        if let (false, Some((headers, sources))) = (self.headers_written, self.configured_columns()) {
+            self.set_headers(headers, sources, &mut output);
+        }
+        Ok(output)
     }
 }
 
@@ -206,10 +586,133 @@ mod tests {
         assert!(output_str.contains("tags.1"));
     }
 
+    #[test]
+    fn flatten_collision_policies() {
+        let json_line = r#"{"a":{"b":"nested"},"a.b":"literal"}"#;
+
+        let mut last_wins = CsvWriter::new();
+        let output = last_wins.process_json_line(json_line).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output), "a.b\nliteral\n");
+
+        let mut suffix = CsvWriter::new().with_collision_policy(FlattenCollisionPolicy::Suffix);
+        let output = suffix.process_json_line(json_line).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output), "a.b,a.b_1\nnested,literal\n");
+
+        let mut error = CsvWriter::new().with_collision_policy(FlattenCollisionPolicy::Error);
+        assert!(matches!(error.process_json_line(json_line), Err(ConvertError::Transform(_))));
+
+        let errors = RecordErrors::new(crate::RecordErrorPolicy::Collect);
+        let mut skip = CsvWriter::new()
+            .with_collision_policy(FlattenCollisionPolicy::Error)
+            .with_record_errors(errors.clone());
+        let mut output = skip.process_json_line(json_line).unwrap();
+        output.extend(skip.process_json_line(r#"{"a":{"b":"ok"}}"#).unwrap());
+        assert_eq!(String::from_utf8_lossy(&output), "a.b\nok\n");
+        assert_eq!(errors.skipped(), 1);
+        assert_eq!(errors.list()[0].line, 1);
+        assert!(errors.list()[0].message.contains("'a.b' collides"));
+
+        assert_eq!(FlattenCollisionPolicy::from_string("last_wins"), Some(FlattenCollisionPolicy::LastWins));
+        assert_eq!(FlattenCollisionPolicy::from_string("bogus"), None);
+    }
+
+    #[test]
+    fn writes_with_configured_delimiter_quote_and_terminator() {
+        let json_line = r#"{"a":"x\ty","b":"it's","c":"1,2"}"#;
+
+        let mut tsv = CsvWriter::new().with_config(CsvWriterConfig::tsv());
+        let output = tsv.process_json_line(json_line).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output), "a\tb\tc\n\"x\ty\"\tit's\t1,2\n");
+
+        let mut custom = CsvWriter::new().with_config(CsvWriterConfig {
+            delimiter: b';',
+            quote: b'\'',
+            line_ending: LineEnding::Crlf,
+            ..CsvWriterConfig::default()
+        });
+        let output = custom.process_json_line(json_line).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output), "a;b;c\r\nx\ty;'it''s';1,2\r\n");
+    }
+
+    #[test]
+    fn writes_configured_columns_quoting_and_header() {
+        let config = CsvWriterConfig {
+            quote_style: QuoteStyle::Always,
+            columns: Some(vec!["name".to_string(), "id".to_string(), "missing".to_string()]),
+            ..CsvWriterConfig::default()
+        };
+        let mut writer = CsvWriter::new().with_config(config.clone());
+        let output = writer.process_json_line(r#"{"id":1,"name":"Ada","extra":true}"#).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output),
+            "\"name\",\"id\",\"missing\"\n\"Ada\",\"1\",\"\"\n"
+        );
+        assert_eq!(writer.warnings.list()[0].code, WarningCode::FieldDropped);
+
+        let mut no_header = CsvWriter::new().with_config(CsvWriterConfig {
+            include_header: false,
+            ..CsvWriterConfig::default()
+        });
+        let output = no_header.process_json_line(r#"{"b":2,"a":1}"#).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output), "1,2\n");
+
+        // Known columns give a header row even without records
+        let mut empty = CsvWriter::new().with_config(config);
+        assert_eq!(empty.finish().unwrap(), b"\"name\",\"id\",\"missing\"\n");
+
+        let config: CsvWriterConfig =
+            serde_json::from_str(r#"{"delimiter":";","quoteStyle":"always","lineEnding":"crlf"}"#).unwrap();
+        assert_eq!((config.delimiter, config.quote), (b';', b'"'));
+        assert_eq!(config.line_ending, LineEnding::Crlf);
+        assert!(serde_json::from_str::<CsvWriterConfig>(r#"{"delimiter":"ab"}"#).is_err());
+    }
+
+    #[test]
+    fn writes_output_columns_renamed_in_order() {
+        let columns = vec![
+            OutputColumn::new("address.city", "City"),
+            OutputColumn::new("name", "Name"),
+            OutputColumn::new("id", "ID"),
+        ];
+        let mut writer = CsvWriter::new()
+            .with_config(CsvWriterConfig {
+                columns: Some(vec!["id".to_string()]),
+                ..CsvWriterConfig::default()
+            })
+            .with_output_columns(Some(columns.clone()));
+        let mut output = writer.process_json_line(r#"{"id":1,"name":"Ada","address":{"city":"Paris"}}"#).unwrap();
+        output.extend(writer.process_json_line(r#"{"id":2,"name":"Bo"}"#).unwrap());
+        assert_eq!(String::from_utf8_lossy(&output), "City,Name,ID
+Paris,Ada,1
+,Bo,2
+");
+
+        let mut empty = CsvWriter::new().with_output_columns(Some(columns));
+        assert_eq!(empty.finish().unwrap(), b"City,Name,ID
+");
+    }
+
     #[test]
     fn finish_returns_empty() {
         let mut writer = CsvWriter::new();
         let output = writer.finish().unwrap();
         assert!(output.is_empty());
     }
+
+    #[test]
+    fn notes_column_kinds_when_asked() {
+        let kinds = ColumnKinds::default();
+        let config = CsvWriterConfig { type_sidecar: true, ..CsvWriterConfig::default() };
+        let mut writer = CsvWriter::new().with_config(config).with_column_kinds(kinds.clone());
+        assert!(kinds.schema().is_none());
+        for line in [r#"{"n":1,"v":1}"#, r#"{"n":2.5,"v":"x"}"#, r#"{"n":3}"#] {
+            writer.process_json_line(line).unwrap();
+        }
+
+        let schema = kinds.schema().unwrap();
+        assert!(!schema.unflatten);
+        assert_eq!(schema.columns[0].column_type, Some(ColumnType::Number));
+        assert_eq!(schema.columns[1].column_type, Some(ColumnType::Json));
+        assert_eq!(schema.columns[1].empty, Some(EmptyField::Omit));
+    }
 }
@@ -0,0 +1,325 @@
+use crate::error::{ConvertError, Result};
+use crate::warnings::{WarningCode, Warnings};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+
+/// Estimated bookkeeping of one remembered record beyond its key and line:
+/// the map entry, the queue slot and their allocations.
+const ENTRY_OVERHEAD_BYTES: usize = 64;
+
+/// Which record of a set of duplicates is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupeKeep {
+    /// The first record with a key; later ones are dropped as they arrive.
+    #[default]
+    First,
+    /// The last record with a key, written where it appears in the input.
+    /// Records are held back until the end of the input, since a later one
+    /// may replace them.
+    Last,
+}
+
+/// Drops records whose key fields repeat those of an earlier record.
+///
+/// Keys are read from the records as written, i.e. after `transform`.
+/// Records that have none of the key fields (or only nulls) are always kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DedupeConfig {
+    /// Top-level fields that together identify a record.
+    pub fields: Vec<String>,
+    pub keep: DedupeKeep,
+    /// Most memory to spend on seen keys and, with `keep: last`, held-back
+    /// records. Exceeding it fails the conversion with
+    /// [`ConvertError::LimitExceeded`] unless `approximate` is set.
+    pub max_memory_bytes: Option<usize>,
+    /// Remember 64-bit hashes of keys instead of the keys, and once over
+    /// `max_memory_bytes`, forget the oldest keys (writing their held-back
+    /// records) instead of failing. A hash collision can drop a distinct
+    /// record, and a duplicate of a forgotten key is written again.
+    pub approximate: bool,
+}
+
+impl DedupeConfig {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self {
+            fields,
+            ..Self::default()
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.fields.is_empty() || self.fields.iter().any(String::is_empty) {
+            return Err(ConvertError::InvalidConfig(
+                "dedupe.fields must list at least one non-empty field".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Exact(String),
+    Hash(u64),
+}
+
+impl Key {
+    fn len(&self) -> usize {
+        match self {
+            Key::Exact(text) => text.len(),
+            Key::Hash(_) => std::mem::size_of::<u64>(),
+        }
+    }
+}
+
+/// A remembered key, in input order. With `keep: last` it also holds the
+/// record's NDJSON line until it is written or replaced by a duplicate.
+#[derive(Debug)]
+struct Slot {
+    key: Option<Key>,
+    line: Option<Vec<u8>>,
+}
+
+impl Slot {
+    fn bytes(&self) -> usize {
+        // The key is stored both here and in the map
+        ENTRY_OVERHEAD_BYTES
+            + 2 * self.key.as_ref().map_or(0, Key::len)
+            + self.line.as_ref().map_or(0, Vec::len)
+    }
+}
+
+/// Applies a [`DedupeConfig`] to the NDJSON lines a transform engine writes.
+#[derive(Debug)]
+pub(crate) struct Deduper {
+    config: DedupeConfig,
+    warnings: Warnings,
+    /// Sequence number of the slot holding each key's kept record.
+    seen: HashMap<Key, u64>,
+    slots: VecDeque<Slot>,
+    /// Sequence number of `slots[0]`.
+    first_seq: u64,
+    memory: usize,
+}
+
+impl Deduper {
+    pub(crate) fn new(config: DedupeConfig, warnings: Warnings) -> Self {
+        Self {
+            config,
+            warnings,
+            seen: HashMap::new(),
+            slots: VecDeque::new(),
+            first_seq: 0,
+            memory: 0,
+        }
+    }
+
+    /// Decide on `record`, whose NDJSON line is `output[start..]`: leave it,
+    /// remove it as a duplicate, or hold it back. Returns the number of
+    /// records this call leaves written in `output`, which may include
+    /// held-back ones the memory cap forced out.
+    pub(crate) fn admit(&mut self, record: &Value, output: &mut Vec<u8>, start: usize) -> Result<usize> {
+        let key = self.key_of(record);
+        match self.config.keep {
+            DedupeKeep::First => {
+                let Some(key) = key else {
+                    return Ok(1);
+                };
+                if self.seen.contains_key(&key) {
+                    output.truncate(start);
+                    self.warn_dropped();
+                    return Ok(0);
+                }
+                self.remember(Slot { key: Some(key), line: None });
+                self.enforce_cap(output).map(|written| written + 1)
+            }
+            DedupeKeep::Last => {
+                let line = output.split_off(start);
+                if let Some(seq) = key.as_ref().and_then(|key| self.seen.get(key)) {
+                    let slot = &mut self.slots[(seq - self.first_seq) as usize];
+                    self.memory -= slot.line.take().map_or(0, |line| line.len());
+                    self.warn_dropped();
+                }
+                self.remember(Slot { key, line: Some(line) });
+                self.enforce_cap(output)
+            }
+        }
+    }
+
+    /// Write the records still held back. Returns how many there were.
+    pub(crate) fn finish(&mut self, output: &mut Vec<u8>) -> usize {
+        let mut written = 0;
+        for slot in self.slots.drain(..) {
+            if let Some(line) = slot.line {
+                output.extend_from_slice(&line);
+                written += 1;
+            }
+        }
+        self.seen.clear();
+        self.memory = 0;
+        written
+    }
+
+    fn key_of(&self, record: &Value) -> Option<Key> {
+        let values: Vec<&Value> = self
+            .config
+            .fields
+            .iter()
+            .map(|field| record.get(field).unwrap_or(&Value::Null))
+            .collect();
+        if values.iter().all(|value| value.is_null()) {
+            return None;
+        }
+        // Written as a JSON array, so "1" and 1 or ["a,b"] and ["a","b"] differ
+        let text = serde_json::to_string(&values).ok()?;
+        Some(if self.config.approximate {
+            let mut hasher = DefaultHasher::new();
+            text.hash(&mut hasher);
+            Key::Hash(hasher.finish())
+        } else {
+            Key::Exact(text)
+        })
+    }
+
+    fn remember(&mut self, slot: Slot) {
+        if let Some(key) = &slot.key {
+            let seq = self.first_seq + self.slots.len() as u64;
+            self.seen.insert(key.clone(), seq);
+        }
+        self.memory += slot.bytes();
+        self.slots.push_back(slot);
+    }
+
+    fn enforce_cap(&mut self, output: &mut Vec<u8>) -> Result<usize> {
+        let Some(max) = self.config.max_memory_bytes else {
+            return Ok(0);
+        };
+        if self.memory <= max {
+            return Ok(0);
+        }
+        if !self.config.approximate {
+            return Err(ConvertError::LimitExceeded(format!(
+                "dedupe state of more than {max} bytes (dedupe.maxMemoryBytes)"
+            )));
+        }
+
+        let mut written = 0;
+        while self.memory > max {
+            let Some(slot) = self.slots.pop_front() else {
+                break;
+            };
+            let seq = self.first_seq;
+            self.first_seq += 1;
+            self.memory -= slot.bytes();
+            if let Some(key) = slot.key {
+                if self.seen.get(&key) == Some(&seq) {
+                    self.seen.remove(&key);
+                }
+            }
+            if let Some(line) = slot.line {
+                output.extend_from_slice(&line);
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
+
+    fn warn_dropped(&self) {
+        self.warnings.warn(WarningCode::DuplicateDropped, || {
+            format!("Dropped a record with a duplicate {}", self.config.fields.join(", "))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(config: DedupeConfig, records: &[Value]) -> Result<Vec<Value>> {
+        let mut deduper = Deduper::new(config, Warnings::default());
+        let mut output = Vec::new();
+        let mut count = 0;
+        for record in records {
+            let start = output.len();
+            serde_json::to_writer(&mut output, record).unwrap();
+            output.push(b'\n');
+            count += deduper.admit(record, &mut output, start)?;
+        }
+        count += deduper.finish(&mut output);
+
+        let written: Vec<Value> = output
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(written.len(), count);
+        Ok(written)
+    }
+
+    fn feed() -> Vec<Value> {
+        vec![
+            json!({"id": 1, "v": "a"}),
+            json!({"id": 2, "v": "b"}),
+            json!({"id": 1, "v": "c"}),
+            json!({"v": "no id"}),
+            json!({"id": "1", "v": "d"}),
+            json!({"id": 2, "v": "e"}),
+        ]
+    }
+
+    #[test]
+    fn keeps_first_or_last_record_per_key() {
+        let first = run(DedupeConfig::new(vec!["id".to_string()]), &feed()).unwrap();
+        let values: Vec<&Value> = first.iter().map(|record| &record["v"]).collect();
+        assert_eq!(values, ["a", "b", "no id", "d"]);
+
+        let config = DedupeConfig {
+            keep: DedupeKeep::Last,
+            ..DedupeConfig::new(vec!["id".to_string()])
+        };
+        let last = run(config, &feed()).unwrap();
+        let values: Vec<&Value> = last.iter().map(|record| &record["v"]).collect();
+        assert_eq!(values, ["c", "no id", "d", "e"]);
+    }
+
+    #[test]
+    fn combines_key_fields() {
+        let records = [
+            json!({"sku": "a", "store": 1}),
+            json!({"sku": "a", "store": 2}),
+            json!({"sku": "a", "store": 1}),
+        ];
+        let config = DedupeConfig::new(vec!["sku".to_string(), "store".to_string()]);
+        assert_eq!(run(config, &records).unwrap().len(), 2);
+        assert!(DedupeConfig::new(Vec::new()).validate().is_err());
+    }
+
+    #[test]
+    fn memory_cap_fails_or_forgets_the_oldest_keys() {
+        let records: Vec<Value> = (0..100).map(|n| json!({"id": n % 50})).collect();
+        let config = DedupeConfig {
+            max_memory_bytes: Some(10 * ENTRY_OVERHEAD_BYTES),
+            ..DedupeConfig::new(vec!["id".to_string()])
+        };
+        assert!(matches!(run(config.clone(), &records), Err(ConvertError::LimitExceeded(_))));
+
+        // Each key is forgotten before it comes back
+        let approximate = DedupeConfig { approximate: true, ..config.clone() };
+        assert_eq!(run(approximate.clone(), &records).unwrap().len(), 100);
+
+        let last = DedupeConfig { keep: DedupeKeep::Last, ..approximate };
+        let written = run(last, &records).unwrap();
+        assert_eq!(written.len(), 100);
+        assert_eq!(written[0], json!({"id": 0}));
+
+        let nearby: Vec<Value> = (0..100).map(|n| json!({"id": n / 2})).collect();
+        let approximate = DedupeConfig { approximate: true, ..config };
+        assert_eq!(run(approximate, &nearby).unwrap().len(), 50);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::error::{ConvertError, Result};
+use crate::transform::{record_snippet, FunctionRegistry, TransformConfigInput, TransformPlan};
+use memchr::memchr;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+/// What to do with records whose type value has no configured route.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnmatchedPolicy {
+    /// Pass the record through unchanged to the unmatched route.
+    #[default]
+    Keep,
+    Drop,
+    Error,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DemuxConfigInput {
+    /// Field whose value selects the route, e.g. `type`.
+    pub field: String,
+    /// Route name to transform plan; `null` passes matching records through unchanged.
+    pub routes: HashMap<String, Option<TransformConfigInput>>,
+    pub on_unmatched: Option<UnmatchedPolicy>,
+    /// Output name for unmatched records kept by [`UnmatchedPolicy::Keep`].
+    pub unmatched_route: Option<String>,
+}
+
+/// NDJSON output grouped by route name.
+#[derive(Debug, Default)]
+pub struct DemuxResult {
+    pub outputs: BTreeMap<String, Vec<u8>>,
+    pub records: usize,
+}
+
+/// Splits a mixed NDJSON stream by the value of one field, applying a
+/// different transform plan to each record type.
+pub struct DemuxEngine {
+    field: String,
+    routes: HashMap<String, Option<TransformPlan>>,
+    on_unmatched: UnmatchedPolicy,
+    unmatched_route: String,
+    partial_line: Vec<u8>,
+    record_index: usize,
+}
+
+impl DemuxEngine {
+    pub fn compile(input: DemuxConfigInput) -> Result<Self> {
+        if input.routes.is_empty() {
+            return Err(ConvertError::InvalidConfig(
+                "demux.routes must contain at least one route".to_string(),
+            ));
+        }
+
+        let mut routes = HashMap::with_capacity(input.routes.len());
+        for (name, config) in input.routes {
+            let plan = config
+                .map(TransformPlan::compile)
+                .transpose()
+                .map_err(|e| ConvertError::InvalidConfig(format!("route '{name}': {e}")))?;
+            routes.insert(name, plan);
+        }
+
+        Ok(Self {
+            field: input.field,
+            routes,
+            on_unmatched: input.on_unmatched.unwrap_or_default(),
+            unmatched_route: input.unmatched_route.unwrap_or_else(|| "unmatched".to_string()),
+            partial_line: Vec::new(),
+            record_index: 0,
+        })
+    }
+
+    /// Make the custom functions in `functions` callable from every route's plan.
+    pub fn with_functions(mut self, functions: FunctionRegistry) -> Self {
+        self.routes = std::mem::take(&mut self.routes)
+            .into_iter()
+            .map(|(name, plan)| (name, plan.map(|plan| plan.with_functions(functions.clone()))))
+            .collect();
+        self
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<DemuxResult> {
+        let mut result = DemuxResult::default();
+
+        let mut temp_buffer = Vec::new();
+        let input_data: &[u8] = if !self.partial_line.is_empty() {
+            temp_buffer.extend_from_slice(&self.partial_line);
+            temp_buffer.extend_from_slice(chunk);
+            &temp_buffer
+        } else {
+            chunk
+        };
+
+        let mut start = 0;
+        while let Some(pos) = memchr(b'\n', &input_data[start..]) {
+            let line_end = start + pos;
+            self.route_line(&input_data[start..line_end], &mut result)?;
+            start = line_end + 1;
+        }
+
+        self.partial_line.clear();
+        if start < input_data.len() {
+            self.partial_line.extend_from_slice(&input_data[start..]);
+        }
+
+        Ok(result)
+    }
+
+    pub fn finish(&mut self) -> Result<DemuxResult> {
+        let mut result = DemuxResult::default();
+        let line = std::mem::take(&mut self.partial_line);
+        self.route_line(&line, &mut result)?;
+        Ok(result)
+    }
+
+    fn route_line(&mut self, line: &[u8], result: &mut DemuxResult) -> Result<()> {
+        if line.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(());
+        }
+
+        // Numbered from 1, like the records of transform errors
+        self.record_index += 1;
+        let index = self.record_index;
+
+        let value: Value = serde_json::from_slice(line)
+            .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+        let route = match value.get(&self.field) {
+            Some(Value::String(text)) => Some(text.clone()),
+            Some(Value::Null) | None => None,
+            Some(other) => Some(other.to_string()),
+        };
+
+        let matched = route
+            .as_ref()
+            .and_then(|name| self.routes.get(name).map(|plan| (name, plan)));
+        let (name, output) = match matched {
+            Some((name, Some(plan))) => {
+                let transformed = plan.apply_all(&value).map_err(|err| {
+                    ConvertError::Transform(format!(
+                        "record {index}: {err} (record: {})",
+                        record_snippet(line)
+                    ))
+                })?;
+                if transformed.is_empty() {
+                    return Ok(());
+                }
+                let buffer = result.outputs.entry(name.clone()).or_default();
+                for record in &transformed {
+                    serde_json::to_writer(&mut *buffer, record)
+                        .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+                    buffer.push(b'\n');
+                }
+                result.records += transformed.len();
+                return Ok(());
+            }
+            Some((name, None)) => (name.clone(), line.to_vec()),
+            None => match self.on_unmatched {
+                UnmatchedPolicy::Keep => (self.unmatched_route.clone(), line.to_vec()),
+                UnmatchedPolicy::Drop => return Ok(()),
+                UnmatchedPolicy::Error => {
+                    return Err(ConvertError::Transform(format!(
+                        "record {index}: no route for {} = {} (record: {})",
+                        self.field,
+                        route.as_deref().unwrap_or("null"),
+                        record_snippet(line)
+                    )))
+                }
+            },
+        };
+
+        let buffer = result.outputs.entry(name).or_default();
+        buffer.extend_from_slice(&output);
+        buffer.push(b'\n');
+        result.records += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn engine(config: Value) -> DemuxEngine {
+        DemuxEngine::compile(serde_json::from_value(config).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn routes_records_by_type_with_per_route_plans() {
+        let mut engine = engine(json!({
+            "field": "type",
+            "routes": {
+                "click": { "fields": [{ "targetFieldName": "x" }] },
+                "view": null
+            }
+        }));
+
+        let input = b"{\"type\":\"click\",\"x\":1,\"y\":2}\n{\"type\":\"view\",\"page\":\"/\"}\n{\"type\":\"other\"}\n{\"type\":\"click\",\"x\":3}";
+        let mut result = engine.push(input).unwrap();
+        assert_eq!(result.records, 3);
+        let tail = engine.finish().unwrap();
+        result.outputs.get_mut("click").unwrap().extend(&tail.outputs["click"]);
+
+        assert_eq!(result.outputs["click"], b"{\"x\":1}\n{\"x\":3}\n");
+        assert_eq!(result.outputs["view"], b"{\"type\":\"view\",\"page\":\"/\"}\n");
+        assert_eq!(result.outputs["unmatched"], b"{\"type\":\"other\"}\n");
+    }
+
+    #[test]
+    fn unmatched_policy_drop_and_error() {
+        let config = |policy: &str| {
+            json!({ "field": "kind", "routes": { "a": null }, "onUnmatched": policy })
+        };
+
+        let result = engine(config("drop")).push(b"{\"kind\":\"b\"}\n{\"kind\":\"a\"}\n").unwrap();
+        assert_eq!(result.outputs.keys().collect::<Vec<_>>(), vec!["a"]);
+
+        let err = engine(config("error")).push(b"{\"kind\":\"a\"}\n{}\n").unwrap_err();
+        assert!(err.to_string().contains("record 2: no route for kind = null"));
+    }
+}
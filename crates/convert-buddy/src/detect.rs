@@ -1,16 +1,25 @@
+use crate::compression::{inflate_prefix, Compression};
 use crate::format::Format;
 use crate::json_parser::JsonParser;
+use serde::Serialize;
+use std::borrow::Cow;
+#[cfg(feature = "yaml")]
+use crate::yaml_parser::YamlParser;
 
 const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
 const CSV_DELIMITERS: &[u8] = &[b',', b'\t', b';', b'|'];
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CsvDetection {
     pub delimiter: u8,
+    /// `"`, or `'` when fields are wrapped in single quotes instead.
+    pub quote: u8,
+    /// Whether the first row looks like column names rather than data.
+    pub has_headers: bool,
     pub fields: Vec<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct XmlDetection {
     pub elements: Vec<String>,
     pub record_element: Option<String>,
@@ -26,6 +35,26 @@ pub struct NdjsonDetection {
     pub fields: Vec<String>,
 }
 
+/// What a converter detected from its first chunk. Settings that were
+/// configured, or that detection fell back on a default for, are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DetectionResult {
+    /// Set for `inputFormat: "auto"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_headers: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_element: Option<String>,
+    /// Fields of the first CSV row, or XML element names seen in the sample.
+    pub fields: Vec<String>,
+}
+
 #[derive(Debug)]
 pub struct StructureDetection {
     pub format: Format,
@@ -34,6 +63,62 @@ pub struct StructureDetection {
     pub record_element: Option<String>, // For XML
 }
 
+/// Everything detected from one sample, for setting up any number of
+/// converters with [`ConverterConfig::from_detection`](crate::ConverterConfig::from_detection).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    pub format: Format,
+    /// Compression of the sample; the rest was detected from what it inflates to.
+    pub compression: Option<Compression>,
+    /// Set for CSV input.
+    pub csv: Option<CsvDetection>,
+    /// Set for XML input.
+    pub xml: Option<XmlDetection>,
+    /// CSV columns, XML element names, or the fields of JSON and NDJSON records.
+    pub fields: Vec<String>,
+}
+
+impl Detection {
+    /// Detect the format of `sample` and its settings; `None` when the format
+    /// can't be told. A compressed sample may be cut off anywhere.
+    pub fn analyze(sample: &[u8]) -> Option<Self> {
+        let compression = detect_compression(sample);
+        let inflated = match compression {
+            Some(compression) => Cow::Owned(inflate_prefix(sample, compression)),
+            None => Cow::Borrowed(sample),
+        };
+        let sample = strip_bom(trim_ascii(&inflated));
+        let format = detect_format(sample)?;
+        let csv = if format == Format::Csv { detect_csv(sample) } else { None };
+        let xml = if format == Format::Xml { detect_xml(sample) } else { None };
+        let fields = match (&csv, &xml) {
+            (Some(csv), _) => csv.fields.clone(),
+            (_, Some(xml)) => xml.elements.clone(),
+            _ => detect_structure(sample, Some(format)).map(|structure| structure.fields).unwrap_or_default(),
+        };
+        Some(Self {
+            format,
+            compression,
+            csv,
+            xml,
+            fields,
+        })
+    }
+}
+
+/// Recognise a gzip or zlib header at the start of a sample.
+///
+/// Only zlib headers that can't start UTF-8 text are recognised: a
+/// low-compression stream (`78 5E`, which reads as "x^") has to be configured
+/// explicitly, and raw deflate has no header at all.
+pub fn detect_compression(sample: &[u8]) -> Option<Compression> {
+    match sample {
+        [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+        [0x78, 0x01 | 0x9c | 0xda, ..] => Some(Compression::Zlib),
+        _ => None,
+    }
+}
+
 fn utf8_prefix(bytes: &[u8]) -> Option<&str> {
     match std::str::from_utf8(bytes) {
         Ok(value) => Some(value),
@@ -51,6 +136,12 @@ fn utf8_prefix(bytes: &[u8]) -> Option<&str> {
 }
 
 pub fn detect_format(sample: &[u8]) -> Option<Format> {
+    // A zip local file header; .xlsx is the only zip-based format read
+    #[cfg(feature = "xlsx")]
+    if sample.starts_with(b"PK\x03\x04") {
+        return Some(Format::Xlsx);
+    }
+
     let sample = trim_ascii(sample);
     if sample.is_empty() {
         return None;
@@ -61,6 +152,14 @@ pub fn detect_format(sample: &[u8]) -> Option<Format> {
         return None;
     }
 
+    // Only recognised by an explicit document start or directive
+    #[cfg(feature = "yaml")]
+    if sample.starts_with(b"%YAML") || sample == b"---" || sample.starts_with(b"---\n")
+        || sample.starts_with(b"---\r") || sample.starts_with(b"--- ")
+    {
+        return Some(Format::Yaml);
+    }
+
     let first = sample[0];
     if first == b'<' && looks_like_xml(sample) {
         return Some(Format::Xml);
@@ -103,14 +202,372 @@ pub fn detect_csv(sample: &[u8]) -> Option<CsvDetection> {
 
     let sample = strip_bom(sample);
     let line = first_non_empty_line(sample)?;
-    let delimiter = detect_delimiter(sample);
+    let quote = detect_quote(sample);
+    let delimiter = detect_delimiter(sample, quote);
     let mut fields = Vec::new();
-    for field in split_csv_fields(line, delimiter) {
+    for field in split_csv_fields(line, delimiter, quote) {
         let decoded = std::str::from_utf8(&field).ok()?;
         fields.push(decoded.to_string());
     }
+    let has_headers = detect_has_headers(sample, delimiter, quote);
+
+    Some(CsvDetection {
+        delimiter,
+        quote,
+        has_headers,
+        fields,
+    })
+}
+
+/// The header row of a CSV or TSV sample, from [`extract_header`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvHeader {
+    pub delimiter: u8,
+    pub fields: Vec<HeaderField>,
+}
+
+/// One column of a [`CsvHeader`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeaderField {
+    pub name: String,
+    /// Position of the column, from 0.
+    pub index: usize,
+    /// Byte offset of the field in the sample, opening quote included.
+    pub start: usize,
+    /// Byte offset just past the field, closing quote included.
+    pub end: usize,
+}
+
+/// Read the first row of a CSV or TSV sample, without looking at the rows
+/// after it. A `None` delimiter is detected from that row alone. Without
+/// `has_headers` the columns are named `field_0`, `field_1`, ... as the
+/// parser names them. `None` when the sample has no row or ends inside a
+/// quoted field of it.
+pub fn extract_header(sample: &[u8], delimiter: Option<u8>, quote: u8, has_headers: bool) -> Option<CsvHeader> {
+    let mut start = if sample.starts_with(UTF8_BOM) { UTF8_BOM.len() } else { 0 };
+    while matches!(sample.get(start), Some(b'\r' | b'\n')) {
+        start += 1;
+    }
+    if start == sample.len() {
+        return None;
+    }
+
+    // The row ends at the first line break outside quotes
+    let mut in_quotes = false;
+    let mut end = start;
+    while end < sample.len() {
+        match sample[end] {
+            byte if byte == quote => in_quotes = !in_quotes,
+            b'\n' if !in_quotes => break,
+            _ => {}
+        }
+        end += 1;
+    }
+    if in_quotes {
+        return None;
+    }
+    if end > start && sample[end - 1] == b'\r' {
+        end -= 1;
+    }
+    let row = &sample[start..end];
+    let delimiter = delimiter.unwrap_or_else(|| detect_delimiter(row, quote));
+
+    let mut fields = Vec::new();
+    let mut field_start = 0;
+    loop {
+        let mut field_end = field_start;
+        let mut in_quotes = false;
+        while field_end < row.len() && (in_quotes || row[field_end] != delimiter) {
+            if row[field_end] == quote {
+                in_quotes = !in_quotes;
+            }
+            field_end += 1;
+        }
+        let index = fields.len();
+        let name = if has_headers {
+            unquote(&row[field_start..field_end], quote)
+        } else {
+            format!("field_{index}")
+        };
+        fields.push(HeaderField {
+            name,
+            index,
+            start: start + field_start,
+            end: start + field_end,
+        });
+        if field_end == row.len() {
+            break;
+        }
+        field_start = field_end + 1;
+    }
+    Some(CsvHeader { delimiter, fields })
+}
+
+/// The text of a raw CSV field: outer quotes removed and doubled quotes undone.
+fn unquote(field: &[u8], quote: u8) -> String {
+    let text = match field {
+        [first, inner @ .., last] if *first == quote && *last == quote => {
+            let mut text = Vec::with_capacity(inner.len());
+            let mut bytes = inner.iter();
+            while let Some(&byte) = bytes.next() {
+                text.push(byte);
+                if byte == quote {
+                    bytes.next();
+                }
+            }
+            text
+        }
+        _ => field.to_vec(),
+    };
+    String::from_utf8_lossy(&text).into_owned()
+}
+
+/// Size of a whole input in records, extrapolated from a leading sample by
+/// [`estimate_records`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordEstimate {
+    pub format: Format,
+    /// Estimated records in the whole input.
+    pub records: u64,
+    /// Complete records found in the sample.
+    pub sample_records: u64,
+    /// Mean size of the sampled records in bytes, separators included.
+    pub average_record_bytes: f64,
+    /// Whether the sample was the whole input, so `records` is a count.
+    pub exact: bool,
+}
+
+/// Where the complete records of a sample start and end, and how many
+/// there are between.
+struct RecordSpan {
+    records: u64,
+    start: usize,
+    end: usize,
+}
+
+/// Estimate how many records an input of `total_bytes` holds from its first
+/// bytes, without converting them. A `None` format is detected from the
+/// sample. CSV and TSV rows are counted after the header row, JSON records
+/// are the items of a top-level array, and XML records are the detected
+/// record elements. `None` for compressed samples, formats that can't be
+/// sampled, and samples without a complete record.
+pub fn estimate_records(sample: &[u8], total_bytes: u64, format: Option<Format>) -> Option<RecordEstimate> {
+    if detect_compression(sample).is_some() {
+        return None;
+    }
+    let format = match format {
+        Some(format) => format,
+        None => detect_format(sample)?,
+    };
+    let whole = sample.len() as u64 >= total_bytes;
+    let span = match format {
+        Format::Csv | Format::Tsv => csv_record_span(sample, format, whole),
+        Format::Ndjson => ndjson_record_span(sample, whole),
+        Format::Json => json_record_span(sample, whole),
+        Format::Xml => xml_record_span(sample),
+        #[allow(unreachable_patterns)]
+        _ => None,
+    }?;
+    if span.records == 0 && !whole {
+        return None;
+    }
+
+    let average_record_bytes = if span.records == 0 {
+        0.0
+    } else {
+        (span.end - span.start) as f64 / span.records as f64
+    };
+    let records = if whole {
+        span.records
+    } else {
+        let remaining = total_bytes.saturating_sub(span.start as u64) as f64;
+        ((remaining / average_record_bytes).round() as u64).max(span.records)
+    };
+    Some(RecordEstimate {
+        format,
+        records,
+        sample_records: span.records,
+        average_record_bytes,
+        exact: whole,
+    })
+}
+
+/// Rows end at line breaks outside quotes; blank rows aren't records.
+fn csv_record_span(sample: &[u8], format: Format, whole: bool) -> Option<RecordSpan> {
+    let body = strip_bom(sample);
+    let offset = sample.len() - body.len();
+    let quote = detect_quote(body);
+    let delimiter = if format == Format::Tsv { b'\t' } else { detect_delimiter(body, quote) };
+    let mut header = detect_has_headers(body, delimiter, quote);
+
+    let mut span = RecordSpan { records: 0, start: offset, end: offset };
+    let mut row_start = 0;
+    let mut in_quotes = false;
+    let mut row_end = |end: usize, span: &mut RecordSpan| {
+        if trim_line(&body[row_start..end]).is_empty() {
+            if span.records == 0 {
+                span.start = offset + end;
+            }
+        } else if header {
+            header = false;
+            span.start = offset + end;
+        } else {
+            span.records += 1;
+        }
+        span.end = offset + end;
+        row_start = end;
+    };
+    for (pos, &byte) in body.iter().enumerate() {
+        if byte == quote {
+            in_quotes = !in_quotes;
+        } else if byte == b'\n' && !in_quotes {
+            row_end(pos + 1, &mut span);
+        }
+    }
+    if whole && !in_quotes {
+        row_end(body.len(), &mut span);
+    }
+    Some(span)
+}
+
+fn ndjson_record_span(sample: &[u8], whole: bool) -> Option<RecordSpan> {
+    let mut span = RecordSpan { records: 0, start: 0, end: 0 };
+    let mut line_start = 0;
+    let complete = memchr::memchr_iter(b'\n', sample).map(|pos| pos + 1);
+    let last = (whole && sample.last() != Some(&b'\n')).then_some(sample.len());
+    for end in complete.chain(last) {
+        if trim_ascii(&sample[line_start..end]).is_empty() {
+            if span.records == 0 {
+                span.start = end;
+            }
+        } else {
+            span.records += 1;
+        }
+        span.end = end;
+        line_start = end;
+    }
+    Some(span)
+}
+
+/// Items of a top-level array, each ending at the comma after it; any other
+/// document is one record.
+fn json_record_span(sample: &[u8], whole: bool) -> Option<RecordSpan> {
+    let body = strip_bom(sample);
+    let offset = sample.len() - body.len();
+    let open = body.iter().position(|b| !matches!(b, b' ' | b'\t' | b'\r' | b'\n'))?;
+    if body[open] != b'[' {
+        return whole.then_some(RecordSpan { records: 1, start: 0, end: sample.len() });
+    }
+
+    let start = offset + open + 1;
+    let mut span = RecordSpan { records: 0, start, end: start };
+    // Depth within the array
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut item = false;
+    for (pos, &byte) in body.iter().enumerate().skip(open + 1) {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match byte {
+            b' ' | b'\t' | b'\r' | b'\n' => {}
+            b',' | b']' if depth == 0 => {
+                if item {
+                    span.records += 1;
+                    span.end = offset + pos + 1;
+                }
+                if byte == b']' {
+                    break;
+                }
+                item = false;
+            }
+            b'"' => {
+                in_string = true;
+                item = true;
+            }
+            b'[' | b'{' => {
+                depth += 1;
+                item = true;
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => item = true,
+        }
+    }
+    Some(span)
+}
 
-    Some(CsvDetection { delimiter, fields })
+/// Record elements closed within the sample, self-closing ones included.
+fn xml_record_span(sample: &[u8]) -> Option<RecordSpan> {
+    let element = detect_xml(sample)?.record_element?;
+    let open = format!("<{element}");
+    let close = format!("</{element}>");
+    let starts: Vec<usize> = memchr::memmem::find_iter(sample, open.as_bytes())
+        .filter(|&pos| {
+            matches!(
+                sample.get(pos + open.len()),
+                Some(b'>' | b'/' | b' ' | b'\t' | b'\r' | b'\n')
+            )
+        })
+        .collect();
+    let start = *starts.first()?;
+
+    let mut end = memchr::memmem::rfind(sample, close.as_bytes()).map_or(start, |pos| pos + close.len());
+    for &pos in starts.iter().rev() {
+        let tag_end = memchr::memchr(b'>', &sample[pos..]).map(|offset| pos + offset);
+        if let Some(tag_end) = tag_end.filter(|&tag_end| sample[tag_end - 1] == b'/') {
+            end = end.max(tag_end + 1);
+            break;
+        }
+    }
+    let records = starts.iter().filter(|&&pos| pos < end).count() as u64;
+    Some(RecordSpan { records, start, end })
+}
+
+/// `sample` without the lines starting with `comment`, so CSV detection
+/// only looks at rows. A comment line cut off at the end is dropped too.
+pub fn strip_comment_lines(sample: &[u8], comment: Option<u8>) -> Cow<'_, [u8]> {
+    let Some(comment) = comment else {
+        return Cow::Borrowed(sample);
+    };
+    let sample = strip_bom(sample);
+    let mut rows = Vec::with_capacity(sample.len());
+    for line in sample.split_inclusive(|&b| b == b'\n') {
+        if line.first() != Some(&comment) {
+            rows.extend_from_slice(line);
+        }
+    }
+    Cow::Owned(rows)
+}
+
+/// A delimiter that splits the first line of `sample` into columns when
+/// `delimiter` leaves it whole.
+pub fn better_csv_delimiter(sample: &[u8], delimiter: u8, quote: u8) -> Option<u8> {
+    let sample = strip_bom(sample);
+    let line = first_non_empty_line(sample)?;
+    if count_fields(line, delimiter, quote) > 1 {
+        return None;
+    }
+    let candidate = detect_delimiter(sample, quote);
+    (candidate != delimiter && count_fields(line, candidate, quote) > 1).then_some(candidate)
+}
+
+/// Whether `sample` holds a start tag of `element`. A name right at the end
+/// of the sample counts, as the byte after it isn't known yet.
+pub fn contains_start_tag(sample: &[u8], element: &str) -> bool {
+    let tag = format!("<{element}");
+    memchr::memmem::find_iter(sample, tag.as_bytes()).any(|pos| {
+        matches!(
+            sample.get(pos + tag.len()),
+            None | Some(b'>' | b'/' | b' ' | b'\t' | b'\r' | b'\n')
+        )
+    })
 }
 
 pub fn detect_xml(sample: &[u8]) -> Option<XmlDetection> {
@@ -421,6 +878,20 @@ pub fn detect_structure(sample: &[u8], format: Option<Format>) -> Option<Structu
                 None
             }
         }
+        // The delimiter is known, so only the header row is read
+        Format::Tsv => {
+            let line = first_non_empty_line(sample)?;
+            let fields = split_csv_fields(line, b'\t', b'"')
+                .into_iter()
+                .map(|field| String::from_utf8(field).ok())
+                .collect::<Option<Vec<_>>>()?;
+            Some(StructureDetection {
+                format: Format::Tsv,
+                fields,
+                delimiter: Some("\t".to_string()),
+                record_element: None,
+            })
+        }
         Format::Xml => {
             if let Some(xml_detection) = detect_xml(sample) {
                 Some(StructureDetection {
@@ -457,6 +928,23 @@ pub fn detect_structure(sample: &[u8], format: Option<Format>) -> Option<Structu
                 None
             }
         }
+        // Output-only formats have no readable structure
+        #[cfg(feature = "parquet")]
+        Format::Parquet => None,
+        // Workbooks can't be read from a leading sample
+        #[cfg(feature = "xlsx")]
+        Format::Xlsx => None,
+        // Fields of the items complete within the sample
+        #[cfg(feature = "yaml")]
+        Format::Yaml => {
+            let ndjson = YamlParser::new().push(sample).ok()?;
+            detect_ndjson(&ndjson).map(|detection| StructureDetection {
+                format: Format::Yaml,
+                fields: detection.fields,
+                delimiter: None,
+                record_element: None,
+            })
+        }
     }
 }
 
@@ -592,8 +1080,9 @@ fn looks_like_csv(sample: &[u8]) -> bool {
         None => return false,
     };
 
-    let delimiter = detect_delimiter(sample);
-    let field_count = count_fields(line, delimiter);
+    let quote = detect_quote(sample);
+    let delimiter = detect_delimiter(sample, quote);
+    let field_count = count_fields(line, delimiter, quote);
     field_count >= 2
 }
 
@@ -616,7 +1105,72 @@ fn trim_line(line: &[u8]) -> &[u8] {
     trim_ascii(line)
 }
 
-fn detect_delimiter(sample: &[u8]) -> u8 {
+/// Pick `'` as the quote character when more fields open with it than with `"`.
+///
+/// A quote only counts at the start of a field: at the start of a line or
+/// right after a candidate delimiter, so apostrophes inside words don't.
+fn detect_quote(sample: &[u8]) -> u8 {
+    let (mut double, mut single) = (0usize, 0usize);
+    for line in sample.split(|&b| b == b'\n').take(10) {
+        let line = trim_line(line);
+        let field_starts = line
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i == 0 || CSV_DELIMITERS.contains(&line[i - 1]));
+        for (_, &byte) in field_starts {
+            match byte {
+                b'"' => double += 1,
+                b'\'' => single += 1,
+                _ => {}
+            }
+        }
+    }
+    if single > double {
+        b'\''
+    } else {
+        b'"'
+    }
+}
+
+/// Whether the first row is a header, in the manner of Python's
+/// `csv.Sniffer.has_header`: each column whose later values are all numeric
+/// votes for a header when its first value isn't numeric, and against one
+/// when it is. Without numeric columns to go by, a header is assumed.
+fn detect_has_headers(sample: &[u8], delimiter: u8, quote: u8) -> bool {
+    let mut rows = sample
+        .split(|&b| b == b'\n')
+        .map(trim_line)
+        .filter(|line| !line.is_empty())
+        .take(10)
+        .map(|line| split_csv_fields(line, delimiter, quote));
+    let Some(first) = rows.next() else {
+        return true;
+    };
+    let rows: Vec<Vec<Vec<u8>>> = rows.collect();
+    if rows.is_empty() {
+        return true;
+    }
+
+    let is_number = |field: &[u8]| {
+        std::str::from_utf8(field)
+            .ok()
+            .and_then(|text| text.trim().parse::<f64>().ok())
+            .is_some()
+    };
+    let mut votes = 0i32;
+    for (column, header) in first.iter().enumerate() {
+        let numeric_column = rows.iter().all(|row| {
+            row.get(column)
+                .is_some_and(|field| is_number(field))
+        });
+        if numeric_column {
+            votes += if is_number(header) { -1 } else { 1 };
+        }
+    }
+    votes >= 0
+}
+
+fn detect_delimiter(sample: &[u8], quote: u8) -> u8 {
     // Analyze multiple lines to detect the most likely delimiter
     let mut delimiter_scores: std::collections::HashMap<u8, (usize, usize, usize)> = 
         std::collections::HashMap::new();
@@ -637,7 +1191,7 @@ fn detect_delimiter(sample: &[u8]) -> u8 {
         line_count += 1;
         
         for &candidate in CSV_DELIMITERS {
-            let count = count_delimiters(line, candidate);
+            let count = count_delimiters(line, candidate, quote);
             if let Some(entry) = delimiter_scores.get_mut(&candidate) {
                 entry.0 += count;
                 if count > 0 {
@@ -679,15 +1233,15 @@ fn detect_delimiter(sample: &[u8]) -> u8 {
     }
 }
 
-fn count_fields(line: &[u8], delimiter: u8) -> usize {
+fn count_fields(line: &[u8], delimiter: u8, quote: u8) -> usize {
     let mut count = 1usize;
     let mut in_quotes = false;
     let mut pos = 0;
 
     while pos < line.len() {
         let byte = line[pos];
-        if byte == b'"' {
-            if in_quotes && pos + 1 < line.len() && line[pos + 1] == b'"' {
+        if byte == quote {
+            if in_quotes && pos + 1 < line.len() && line[pos + 1] == quote {
                 pos += 2;
                 continue;
             }
@@ -701,15 +1255,15 @@ fn count_fields(line: &[u8], delimiter: u8) -> usize {
     count
 }
 
-fn count_delimiters(line: &[u8], delimiter: u8) -> usize {
+fn count_delimiters(line: &[u8], delimiter: u8, quote: u8) -> usize {
     let mut count = 0usize;
     let mut in_quotes = false;
     let mut pos = 0;
 
     while pos < line.len() {
         let byte = line[pos];
-        if byte == b'"' {
-            if in_quotes && pos + 1 < line.len() && line[pos + 1] == b'"' {
+        if byte == quote {
+            if in_quotes && pos + 1 < line.len() && line[pos + 1] == quote {
                 pos += 2;
                 continue;
             }
@@ -723,7 +1277,7 @@ fn count_delimiters(line: &[u8], delimiter: u8) -> usize {
     count
 }
 
-fn split_csv_fields(line: &[u8], delimiter: u8) -> Vec<Vec<u8>> {
+fn split_csv_fields(line: &[u8], delimiter: u8, quote: u8) -> Vec<Vec<u8>> {
     let mut fields = Vec::new();
     let mut field = Vec::new();
     let mut pos = 0;
@@ -731,9 +1285,9 @@ fn split_csv_fields(line: &[u8], delimiter: u8) -> Vec<Vec<u8>> {
 
     while pos < line.len() {
         let byte = line[pos];
-        if byte == b'"' {
-            if in_quotes && pos + 1 < line.len() && line[pos + 1] == b'"' {
-                field.push(b'"');
+        if byte == quote {
+            if in_quotes && pos + 1 < line.len() && line[pos + 1] == quote {
+                field.push(quote);
                 pos += 2;
                 continue;
             }
@@ -755,6 +1309,67 @@ fn split_csv_fields(line: &[u8], delimiter: u8) -> Vec<Vec<u8>> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn detect_compression_magic_bytes() {
+        assert_eq!(detect_compression(&[0x1f, 0x8b, 0x08, 0x00]), Some(Compression::Gzip));
+        assert_eq!(detect_compression(&[0x78, 0x9c, 0x4b]), Some(Compression::Zlib));
+        assert_eq!(detect_compression(b"x^2,y\n"), None);
+        assert_eq!(detect_compression(b"id,name\n"), None);
+        assert_eq!(detect_compression(&[0x1f]), None);
+    }
+
+    #[test]
+    fn extract_header_reads_only_the_first_row() {
+        let sample = b"\xEF\xBB\xBFid;\"full \"\"name\"\"\";\"a\nb\"\r\n1;Ada;x\n2;\"Gr";
+        let header = extract_header(sample, None, b'"', true).unwrap();
+        assert_eq!(header.delimiter, b';');
+        let names: Vec<&str> = header.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["id", "full \"name\"", "a\nb"]);
+        let field = &header.fields[1];
+        assert_eq!((field.index, &sample[field.start..field.end]), (1, &b"\"full \"\"name\"\"\""[..]));
+
+        let header = extract_header(b"1\t2\n", Some(b'\t'), b'"', false).unwrap();
+        assert_eq!(header.fields[1].name, "field_1");
+        assert_eq!(extract_header(b"\"id,name\n1", None, b'"', true), None);
+    }
+
+    #[test]
+    fn comment_lines_are_left_out_of_csv_detection() {
+        let sample = b"# exported by lab tool, v2; do not edit\nid|temp\n1|20.5\n# note, a|b\n2|21.0\n#";
+        let rows = strip_comment_lines(sample, Some(b'#'));
+        assert_eq!(&rows[..], b"id|temp\n1|20.5\n2|21.0\n");
+        let detection = detect_csv(&rows).unwrap();
+        assert_eq!((detection.delimiter, detection.fields), (b'|', vec!["id".to_string(), "temp".to_string()]));
+        assert!(matches!(strip_comment_lines(sample, None), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn estimate_records_extrapolates_from_complete_records() {
+        // Header row of 8 bytes, then rows of 10 bytes and a cut-off one
+        let sample = b"id,name\n1,\"a\nbcd\"\n2,abcdefg\n3,ab";
+        let estimate = estimate_records(sample, 1_008, None).unwrap();
+        assert_eq!(estimate.format, Format::Csv);
+        assert_eq!((estimate.sample_records, estimate.records), (2, 100));
+        assert_eq!(estimate.average_record_bytes, 10.0);
+        assert!(!estimate.exact);
+
+        let sample = b"{\"a\":1}\n{\"a\":2}\n{\"a\":3}";
+        let estimate = estimate_records(sample, sample.len() as u64, Some(Format::Ndjson)).unwrap();
+        assert_eq!((estimate.records, estimate.exact), (3, true));
+
+        let sample = br#"[{"a":"x,]"}, {"a":[1,2]}, {"a""#;
+        let estimate = estimate_records(sample, 1_000, None).unwrap();
+        assert_eq!((estimate.format, estimate.sample_records), (Format::Json, 2));
+        assert_eq!(estimate_records(b"[]", 2, None).unwrap().records, 0);
+
+        let sample = b"<rows><row id=\"1\"><v>a</v></row><row id=\"2\"/><row><v>";
+        let estimate = estimate_records(sample, 10_000, Some(Format::Xml)).unwrap();
+        assert_eq!(estimate.sample_records, 2);
+
+        assert_eq!(estimate_records(b"id,name\n1,a", 1_000, None), None);
+        assert_eq!(estimate_records(&[0x1f, 0x8b, 0x08, 0x00], 1_000, None), None);
+    }
+
     #[test]
     fn detect_format_json() {
         let sample = br#"{ "name": "Ada" }"#;
@@ -900,6 +1515,27 @@ mod tests {
         assert_eq!(detection.fields, vec!["1", "2", "3", "4", "5"]);
     }
 
+    #[test]
+    fn detect_csv_single_quotes() {
+        let sample = b"'id','note'\n1,'it''s, fine'\n2,'don''t'\n";
+        let detection = detect_csv(sample).unwrap();
+        assert_eq!((detection.quote, detection.delimiter), (b'\'', b','));
+        assert_eq!(detection.fields, vec!["id", "note"]);
+
+        // Apostrophes inside words don't make a quote character
+        let detection = detect_csv(b"name,quote\nAda,\"it's\"\nO'Brien,x\n").unwrap();
+        assert_eq!(detection.quote, b'"');
+    }
+
+    #[test]
+    fn detect_csv_header_row() {
+        assert!(detect_csv(b"id,price\n1,9.5\n2,3\n").unwrap().has_headers);
+        assert!(!detect_csv(b"1,9.5\n2,3\n").unwrap().has_headers);
+        // All-text columns give no evidence, so a header is assumed
+        assert!(detect_csv(b"a,b\nc,d\n").unwrap().has_headers);
+        assert!(detect_csv(b"id,name\n").unwrap().has_headers);
+    }
+
     #[test]
     fn detect_csv_special_characters_in_fields() {
         let sample = b"id,description,notes\n1,Item@Product,Note-123\n";
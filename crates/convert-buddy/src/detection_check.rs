@@ -0,0 +1,231 @@
+use crate::detect;
+use crate::format::{ConverterConfig, Format};
+use serde::Deserialize;
+
+/// Checks the start of CSV and XML input for signs of a wrong delimiter or
+/// record element.
+///
+/// CSV input is suspect when its first line is a single column that another
+/// delimiter would split. XML input is suspect when the record element never
+/// opens within `after_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct DetectionCheck {
+    pub enabled: bool,
+    /// How much input to look at before giving up on a record start tag or
+    /// the end of the first CSV line.
+    pub after_bytes: usize,
+    /// Hold input until the check is done, and re-run detection when it fails
+    /// instead of only warning.
+    pub redetect: bool,
+}
+
+impl Default for DetectionCheck {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            after_bytes: 1024 * 1024,
+            redetect: false,
+        }
+    }
+}
+
+/// A setting detection found in place of a suspect one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Correction {
+    CsvDelimiter(u8),
+    XmlRecordElement(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    Pending,
+    Fine,
+    /// A warning message, and what detection found instead if anything.
+    Suspect(String, Option<Correction>),
+}
+
+enum Expected {
+    Csv { delimiter: u8, quote: u8, comment: Option<u8> },
+    XmlRecordElement(String),
+}
+
+/// Runs a [`DetectionCheck`] over the first input of a converter.
+pub(crate) struct DetectionWatch {
+    check: DetectionCheck,
+    expected: Expected,
+    sample: Vec<u8>,
+    /// Where the next search for an XML start tag begins.
+    scanned: usize,
+}
+
+impl DetectionWatch {
+    /// The watch for a converter, if its input format has anything to check.
+    pub(crate) fn for_config(config: &ConverterConfig) -> Option<Self> {
+        if !config.detection_check.enabled || config.auto_detect.input_format {
+            return None;
+        }
+        let expected = match config.input_format {
+            Format::Csv | Format::Tsv => {
+                let csv_config = config.csv_config.clone().unwrap_or_default();
+                let delimiter = match config.input_format {
+                    Format::Tsv => b'\t',
+                    _ => csv_config.delimiter,
+                };
+                Expected::Csv {
+                    delimiter,
+                    quote: csv_config.quote,
+                    comment: csv_config.comment_char,
+                }
+            }
+            Format::Xml => Expected::XmlRecordElement(
+                config.xml_config.clone().unwrap_or_default().record_element_name().to_string(),
+            ),
+            _ => return None,
+        };
+        Some(Self {
+            check: config.detection_check,
+            expected,
+            sample: Vec::new(),
+            scanned: 0,
+        })
+    }
+
+    /// Whether input waits here until the check is done.
+    pub(crate) fn holds_input(&self) -> bool {
+        self.check.redetect
+    }
+
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        if self.holds_input() {
+            self.sample.len()
+        } else {
+            0
+        }
+    }
+
+    pub(crate) fn observe(&mut self, chunk: &[u8]) -> Verdict {
+        self.sample.extend_from_slice(chunk);
+        if self.sample.len() >= self.check.after_bytes {
+            return self.finish();
+        }
+        match &self.expected {
+            Expected::Csv { comment, .. } => {
+                let rows = detect::strip_comment_lines(&self.sample, *comment);
+                let content = rows.iter().position(|b| !b.is_ascii_whitespace());
+                match content {
+                    Some(start) if rows[start..].contains(&b'\n') => self.finish(),
+                    _ => Verdict::Pending,
+                }
+            }
+            Expected::XmlRecordElement(element) => {
+                let found = detect::contains_start_tag(&self.sample[self.scanned..], element);
+                // A tag cut off at the end is searched again with the next chunk
+                self.scanned = self.sample.len().saturating_sub(element.len() + 1);
+                if found {
+                    Verdict::Fine
+                } else {
+                    Verdict::Pending
+                }
+            }
+        }
+    }
+
+    /// Judge the input seen so far.
+    pub(crate) fn finish(&self) -> Verdict {
+        if self.sample.iter().all(u8::is_ascii_whitespace) {
+            return Verdict::Fine;
+        }
+        match &self.expected {
+            Expected::Csv { delimiter, quote, comment } => {
+                let rows = detect::strip_comment_lines(&self.sample, *comment);
+                match detect::better_csv_delimiter(&rows, *delimiter, *quote) {
+                    Some(better) => Verdict::Suspect(
+                        format!(
+                            "CSV input is a single column with delimiter '{}', but '{}' splits it",
+                            char::from(*delimiter),
+                            char::from(better)
+                        ),
+                        Some(Correction::CsvDelimiter(better)),
+                    ),
+                    None => Verdict::Fine,
+                }
+            }
+            Expected::XmlRecordElement(element) => {
+                if detect::contains_start_tag(&self.sample, element) {
+                    return Verdict::Fine;
+                }
+                let correction = detect::detect_xml(&self.sample)
+                    .and_then(|detection| detection.record_element)
+                    .filter(|found| found != element)
+                    .map(Correction::XmlRecordElement);
+                Verdict::Suspect(
+                    format!(
+                        "no <{}> records in the first {} bytes of XML input",
+                        element,
+                        self.sample.len()
+                    ),
+                    correction,
+                )
+            }
+        }
+    }
+
+    pub(crate) fn into_sample(self) -> Vec<u8> {
+        self.sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_parser::CsvConfig;
+    use crate::xml_parser::XmlConfig;
+
+    fn watch(config: ConverterConfig) -> DetectionWatch {
+        DetectionWatch::for_config(&config).unwrap()
+    }
+
+    #[test]
+    fn flags_single_column_csv() {
+        let mut csv = watch(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        assert_eq!(csv.observe(b"id;na"), Verdict::Pending);
+        assert!(matches!(
+            csv.observe(b"me\n1;Ada\n"),
+            Verdict::Suspect(_, Some(Correction::CsvDelimiter(b';')))
+        ));
+
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_csv_config(CsvConfig {
+            delimiter: b';',
+            ..CsvConfig::default()
+        });
+        assert_eq!(watch(config).observe(b"id;name\n"), Verdict::Fine);
+
+        // A real single column has nothing better to offer
+        let mut single = watch(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        assert_eq!(single.observe(b"name\nAda\n"), Verdict::Fine);
+    }
+
+    #[test]
+    fn flags_missing_xml_record_element() {
+        let config = ConverterConfig::new(Format::Xml, Format::Ndjson).with_detection_check(DetectionCheck {
+            after_bytes: 64,
+            ..DetectionCheck::default()
+        });
+        let mut xml = watch(config.clone());
+        assert_eq!(xml.observe(b"<items><item><a>1</a></item>"), Verdict::Pending);
+        assert!(matches!(
+            xml.observe(b"<item><a>2</a></item><item><a>3</a></item>"),
+            Verdict::Suspect(_, Some(Correction::XmlRecordElement(ref element))) if element == "item"
+        ));
+
+        // A start tag split across chunks is found
+        let config = config.with_xml_config(XmlConfig {
+            record_element: "item".to_string(),
+            ..XmlConfig::default()
+        });
+        let mut xml = watch(config);
+        assert_eq!(xml.observe(b"<items><it"), Verdict::Pending);
+        assert_eq!(xml.observe(b"em><a>1</a></item>"), Verdict::Fine);
+    }
+}
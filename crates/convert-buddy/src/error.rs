@@ -1,4 +1,5 @@
 use thiserror::Error;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
 #[derive(Error, Debug)]
@@ -11,6 +12,12 @@ pub enum ConvertError {
     
     #[error("XML parse error: {0}")]
     XmlParse(String),
+
+    #[error("XLSX parse error: {0}")]
+    XlsxParse(String),
+
+    #[error("YAML parse error: {0}")]
+    YamlParse(String),
     
     #[error("UTF-8 decode error: {0}")]
     Utf8Error(#[from] std::str::Utf8Error),
@@ -29,11 +36,19 @@ pub enum ConvertError {
     
     #[error("Unsupported operation: {0}")]
     Unsupported(String),
+
+    #[error("Resource limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// `push`, `flush` or `finish` called after `finish` succeeded.
+    #[error("Converter already finished")]
+    AlreadyFinished,
 }
 
 pub type Result<T> = std::result::Result<T, ConvertError>;
 
 // Implement conversion to JsValue for WASM
+#[cfg(feature = "wasm")]
 impl From<ConvertError> for JsValue {
     fn from(error: ConvertError) -> Self {
         #[cfg(target_arch = "wasm32")]
@@ -52,6 +67,7 @@ impl From<ConvertError> for JsValue {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn converts_errors_to_js_value_string() {
         let error = ConvertError::JsonParse("bad json".to_string());
@@ -72,6 +88,8 @@ mod tests {
             ConvertError::BufferOverflow("overflow".to_string()),
             ConvertError::Io("io".to_string()),
             ConvertError::Unsupported("unsupported".to_string()),
+            ConvertError::LimitExceeded("limit".to_string()),
+            ConvertError::AlreadyFinished,
         ];
 
         for error in errors {
@@ -0,0 +1,262 @@
+//! C API for embedding the engine from Go, Java, Swift and other languages
+//! with a C FFI. The declarations are in `include/convert_buddy.h`.
+//!
+//! Functions that can fail return `NULL` or a non-zero status and leave the
+//! message for `convert_buddy_last_error` on the calling thread.
+
+use crate::error::{ConvertError, Result};
+use crate::format::{AutoDetect, ConverterConfig, Format};
+use crate::{Converter, ConverterOptionsInput};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Bytes returned to the caller; release with `convert_buddy_buffer_free`.
+#[repr(C)]
+pub struct ConvertBuddyBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl ConvertBuddyBuffer {
+    fn empty() -> Self {
+        Self {
+            data: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        if bytes.is_empty() {
+            return Self::empty();
+        }
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        Self { data, len }
+    }
+}
+
+fn set_last_error(error: &ConvertError) {
+    // Messages quote input, which may hold NUL bytes
+    let message = error.to_string().replace('\0', "\\0");
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+}
+
+/// Store `result` in `out`, returning 0, or record the error and return -1.
+unsafe fn write_result(result: Result<Vec<u8>>, out: *mut ConvertBuddyBuffer) -> c_int {
+    match result {
+        Ok(bytes) => {
+            if !out.is_null() {
+                *out = ConvertBuddyBuffer::from_vec(bytes);
+            }
+            0
+        }
+        Err(error) => {
+            set_last_error(&error);
+            if !out.is_null() {
+                *out = ConvertBuddyBuffer::empty();
+            }
+            -1
+        }
+    }
+}
+
+unsafe fn read_str<'a>(value: *const c_char, name: &str) -> Result<Option<&'a str>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(Some)
+        .map_err(|_| ConvertError::InvalidConfig(format!("{name} is not valid UTF-8")))
+}
+
+fn build_config(
+    input_format: Option<&str>,
+    output_format: Option<&str>,
+    options_json: Option<&str>,
+) -> Result<ConverterConfig> {
+    let parse = |name: Option<&str>| {
+        let name = name.unwrap_or_default();
+        Format::from_string(name)
+            .ok_or_else(|| ConvertError::InvalidConfig(format!("Invalid format: {name}")))
+    };
+    let detect_format = input_format.is_some_and(|name| name.eq_ignore_ascii_case("auto"));
+    let input = if detect_format { Format::Csv } else { parse(input_format)? };
+    let mut config = ConverterConfig::new(input, parse(output_format)?).with_auto_detect(AutoDetect {
+        input_format: detect_format,
+        csv_delimiter: true,
+        csv_quote: true,
+        csv_has_headers: true,
+        xml_record_element: true,
+    });
+    if let Some(json) = options_json {
+        let options: ConverterOptionsInput = serde_json::from_str(json)
+            .map_err(|e| ConvertError::InvalidConfig(format!("Invalid options: {e}")))?;
+        config = config.with_options(options)?;
+    }
+    Ok(config)
+}
+
+/// Create a converter, or return `NULL` on an invalid configuration.
+///
+/// `input_format` may be `"auto"`; CSV and XML settings are detected from
+/// the input. `options_json` is `NULL` or a JSON object with the JS
+/// package's converter options (e.g. `{"onRecordError":"skip"}`).
+///
+/// # Safety
+///
+/// Each argument must be `NULL` or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn convert_buddy_new(
+    input_format: *const c_char,
+    output_format: *const c_char,
+    options_json: *const c_char,
+) -> *mut Converter {
+    let config = read_str(input_format, "input_format").and_then(|input| {
+        build_config(
+            input,
+            read_str(output_format, "output_format")?,
+            read_str(options_json, "options_json")?,
+        )
+    });
+    match config {
+        Ok(config) => Box::into_raw(Box::new(Converter::from_config(config))),
+        Err(error) => {
+            set_last_error(&error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Convert `len` bytes of input, storing the output they complete in `out`.
+/// Returns 0 on success.
+///
+/// # Safety
+///
+/// `converter` must come from `convert_buddy_new`, `data` must point to
+/// `len` readable bytes (or be `NULL` with `len` 0), and `out` must be
+/// `NULL` or writable.
+#[no_mangle]
+pub unsafe extern "C" fn convert_buddy_push(
+    converter: *mut Converter,
+    data: *const u8,
+    len: usize,
+    out: *mut ConvertBuddyBuffer,
+) -> c_int {
+    let Some(converter) = converter.as_mut() else {
+        return write_result(Err(ConvertError::InvalidConfig("converter is NULL".to_string())), out);
+    };
+    let chunk = if data.is_null() { &[][..] } else { std::slice::from_raw_parts(data, len) };
+    write_result(converter.push_bytes(chunk), out)
+}
+
+/// Flush the rest of the output into `out` once all input is pushed.
+/// Returns 0 on success.
+///
+/// # Safety
+///
+/// `converter` must come from `convert_buddy_new` and `out` must be `NULL`
+/// or writable.
+#[no_mangle]
+pub unsafe extern "C" fn convert_buddy_finish(
+    converter: *mut Converter,
+    out: *mut ConvertBuddyBuffer,
+) -> c_int {
+    let Some(converter) = converter.as_mut() else {
+        return write_result(Err(ConvertError::InvalidConfig("converter is NULL".to_string())), out);
+    };
+    write_result(converter.finish_stream(), out)
+}
+
+/// Release a converter. `NULL` is ignored.
+///
+/// # Safety
+///
+/// `converter` must come from `convert_buddy_new` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn convert_buddy_free(converter: *mut Converter) {
+    if !converter.is_null() {
+        drop(Box::from_raw(converter));
+    }
+}
+
+/// Release a buffer filled by `convert_buddy_push` or `convert_buddy_finish`.
+///
+/// # Safety
+///
+/// `buffer` must have been filled by this library and not freed before.
+#[no_mangle]
+pub unsafe extern "C" fn convert_buddy_buffer_free(buffer: ConvertBuddyBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Message of the last error on this thread, or `NULL`. The string stays
+/// valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn convert_buddy_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn take(buffer: ConvertBuddyBuffer) -> Vec<u8> {
+        let bytes = if buffer.data.is_null() {
+            Vec::new()
+        } else {
+            std::slice::from_raw_parts(buffer.data, buffer.len).to_vec()
+        };
+        convert_buddy_buffer_free(buffer);
+        bytes
+    }
+
+    #[test]
+    fn converts_through_the_c_api() {
+        unsafe {
+            let converter = convert_buddy_new(c"csv".as_ptr(), c"ndjson".as_ptr(), ptr::null());
+            assert!(!converter.is_null());
+
+            let input = b"name;age\nAda;36\n";
+            let mut out = ConvertBuddyBuffer::empty();
+            assert_eq!(convert_buddy_push(converter, input.as_ptr(), input.len(), &mut out), 0);
+            let mut output = take(out);
+            let mut out = ConvertBuddyBuffer::empty();
+            assert_eq!(convert_buddy_finish(converter, &mut out), 0);
+            output.extend(take(out));
+            convert_buddy_free(converter);
+
+            assert_eq!(output, b"{\"name\":\"Ada\",\"age\":\"36\"}\n");
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        unsafe {
+            let converter = convert_buddy_new(c"csv".as_ptr(), c"nope".as_ptr(), ptr::null());
+            assert!(converter.is_null());
+            let message = CStr::from_ptr(convert_buddy_last_error()).to_str().unwrap();
+            assert_eq!(message, "Invalid format configuration: Invalid format: nope");
+
+            let converter = convert_buddy_new(c"ndjson".as_ptr(), c"csv".as_ptr(), c"{\"threads\":0}".as_ptr());
+            assert!(converter.is_null());
+
+            let converter = convert_buddy_new(c"csv".as_ptr(), c"ndjson".as_ptr(), ptr::null());
+            let input = b"a\n\xff\n";
+            let mut out = ConvertBuddyBuffer::empty();
+            // Detection holds short input back until finish
+            assert_eq!(convert_buddy_push(converter, input.as_ptr(), input.len(), &mut out), 0);
+            assert_eq!(convert_buddy_finish(converter, &mut out), -1);
+            assert!(out.data.is_null());
+            assert!(!convert_buddy_last_error().is_null());
+            convert_buddy_free(converter);
+        }
+    }
+}
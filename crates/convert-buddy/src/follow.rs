@@ -0,0 +1,251 @@
+use crate::error::{ConvertError, Result};
+use crate::format::ConverterConfig;
+use crate::Converter;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const READ_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Bytes kept from just before the read position, to tell a file that was
+/// truncated and rewritten past it from one that grew.
+const TAIL_BYTES: usize = 64;
+
+/// Converts a file as it grows, like `tail -f file | convert`.
+///
+/// Appended bytes are pushed as they appear. A record cut off at the current
+/// end of the file waits in the converter until the rest of it is written.
+/// When the file is rotated, whether truncated in place or replaced by a new
+/// file at the same path, reading starts again from its beginning and the
+/// cut-off record is dropped. A truncated file is recognised by its size, or
+/// by the bytes before the read position changing; a replaced one, on Unix,
+/// by its inode.
+pub struct Follow {
+    path: PathBuf,
+    file: File,
+    position: u64,
+    /// The last bytes read, up to [`TAIL_BYTES`].
+    tail: Vec<u8>,
+    converter: Converter,
+    poll_interval: Duration,
+    buffer: Vec<u8>,
+}
+
+impl Follow {
+    /// Follow `path` from its start. Set `idle_flush` on `config` to have
+    /// held-back output flushed once the file has been quiet that long.
+    pub fn open(path: impl AsRef<Path>, config: ConverterConfig) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|e| ConvertError::Io(format!("Unable to open {}: {e}", path.display())))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            position: 0,
+            tail: Vec::new(),
+            converter: Converter::from_config(config),
+            poll_interval: Duration::from_millis(250),
+            buffer: vec![0; READ_CHUNK_BYTES],
+        })
+    }
+
+    /// Skip what the file holds now and convert only what is appended, like
+    /// `tail -n 0 -f`. A CSV header already written is skipped too.
+    pub fn from_end(mut self) -> Result<Self> {
+        self.position = self.file.seek(SeekFrom::End(0)).map_err(io_error)?;
+        self.tail = self.bytes_before_position()?;
+        Ok(self)
+    }
+
+    /// How long [`run`](Self::run) waits when the file has no new data;
+    /// 250ms by default.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Convert what was appended since the last call. When nothing was,
+    /// returns what [`Converter::poll_idle`] flushes, if anything.
+    pub fn poll(&mut self) -> Result<Vec<u8>> {
+        if self.rotated()? {
+            self.position = self.file.seek(SeekFrom::Start(0)).map_err(io_error)?;
+            self.tail.clear();
+            self.converter.discard_partial_input();
+        }
+
+        let mut output = Vec::new();
+        let mut appended = false;
+        loop {
+            let read = self.file.read(&mut self.buffer).map_err(io_error)?;
+            if read == 0 {
+                break;
+            }
+            appended = true;
+            self.position += read as u64;
+            self.tail.extend_from_slice(&self.buffer[..read]);
+            self.tail.drain(..self.tail.len().saturating_sub(TAIL_BYTES));
+            output.extend(self.converter.push_bytes(&self.buffer[..read])?);
+        }
+        if !appended {
+            output.extend(self.converter.poll_idle()?);
+        }
+        Ok(output)
+    }
+
+    /// Convert the rest of the file and finish the conversion.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut output = self.poll()?;
+        output.extend(self.converter.finish_stream()?);
+        Ok(output)
+    }
+
+    /// Follow the file until `stop` returns true, writing output to `out` as
+    /// it is produced, then finish the conversion.
+    pub fn run<W: Write>(&mut self, out: &mut W, mut stop: impl FnMut() -> bool) -> Result<()> {
+        while !stop() {
+            let position = self.position;
+            let output = self.poll()?;
+            write_out(out, &output)?;
+            if self.position == position {
+                std::thread::sleep(self.poll_interval);
+            }
+        }
+        let output = self.finish()?;
+        write_out(out, &output)
+    }
+
+    /// The converter, for its stats, warnings and errors.
+    pub fn converter(&self) -> &Converter {
+        &self.converter
+    }
+
+    /// Whether the file was rotated since the last read. A file replaced at
+    /// the path is opened in place of the old one.
+    fn rotated(&mut self) -> Result<bool> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            // Until the new file is created, the old one is read to its end
+            if let Ok(current) = std::fs::metadata(&self.path) {
+                let open = self.file.metadata().map_err(io_error)?;
+                if (current.dev(), current.ino()) != (open.dev(), open.ino()) {
+                    self.file = File::open(&self.path).map_err(|e| {
+                        ConvertError::Io(format!("Unable to open {}: {e}", self.path.display()))
+                    })?;
+                    return Ok(true);
+                }
+            }
+        }
+        let len = self.file.metadata().map_err(io_error)?.len();
+        if len < self.position {
+            return Ok(true);
+        }
+        Ok(self.bytes_before_position()? != self.tail)
+    }
+
+    /// Up to [`TAIL_BYTES`] of the file before the read position, leaving the
+    /// file at that position.
+    fn bytes_before_position(&mut self) -> Result<Vec<u8>> {
+        let start = self.position.saturating_sub(TAIL_BYTES as u64);
+        let mut bytes = vec![0; (self.position - start) as usize];
+        self.file.seek(SeekFrom::Start(start)).map_err(io_error)?;
+        self.file.read_exact(&mut bytes).map_err(io_error)?;
+        Ok(bytes)
+    }
+}
+
+fn write_out<W: Write>(out: &mut W, output: &[u8]) -> Result<()> {
+    if output.is_empty() {
+        return Ok(());
+    }
+    // Readers downstream of a tail expect output as soon as it exists
+    out.write_all(output).and_then(|_| out.flush()).map_err(io_error)
+}
+
+fn io_error(error: std::io::Error) -> ConvertError {
+    ConvertError::Io(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use std::fs::OpenOptions;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("convert-buddy-follow-{}-{name}", std::process::id()))
+    }
+
+    fn append(path: &Path, data: &[u8]) {
+        let mut file = OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(data).unwrap();
+    }
+
+    #[test]
+    fn converts_appended_lines_and_waits_for_partial_ones() {
+        let path = temp_path("append.ndjson");
+        std::fs::write(&path, b"{\"n\":1}\n{\"n\":").unwrap();
+
+        let mut follow = Follow::open(&path, ConverterConfig::new(Format::Ndjson, Format::Csv)).unwrap();
+        assert_eq!(follow.poll().unwrap(), b"n\n1\n");
+        assert!(follow.poll().unwrap().is_empty());
+
+        append(&path, b"2}\n{\"n\":3}\n{\"n\":");
+        assert_eq!(follow.poll().unwrap(), b"2\n3\n");
+
+        // Truncated and rewritten, as by copytruncate rotation; the line cut
+        // off before is dropped
+        std::fs::write(&path, b"{\"n\":4}\n").unwrap();
+        assert_eq!(follow.poll().unwrap(), b"4\n");
+
+        // Truncated and rewritten past the old position between two polls
+        append(&path, b"{\"n\":");
+        assert!(follow.poll().unwrap().is_empty());
+        std::fs::write(&path, b"{\"n\":50}\n{\"n\":60}\n").unwrap();
+        assert_eq!(follow.poll().unwrap(), b"50\n60\n");
+
+        // Replaced by a new file, as by create rotation
+        #[cfg(unix)]
+        {
+            let rotated = temp_path("append.ndjson.1");
+            std::fs::rename(&path, &rotated).unwrap();
+            std::fs::write(&path, b"{\"n\":70}\n{\"n\":80}\n").unwrap();
+            assert_eq!(follow.poll().unwrap(), b"70\n80\n");
+            std::fs::remove_file(&rotated).unwrap();
+        }
+
+        append(&path, b"{\"n\":5}");
+        assert_eq!(follow.finish().unwrap(), b"5\n");
+        assert_eq!(follow.converter().warnings(), Vec::new());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn runs_until_stopped() {
+        let path = temp_path("run.ndjson");
+        std::fs::write(&path, b"{\"n\":1}\n").unwrap();
+
+        let config = ConverterConfig::new(Format::Ndjson, Format::Json);
+        let mut follow = Follow::open(&path, config)
+            .unwrap()
+            .from_end()
+            .unwrap()
+            .with_poll_interval(Duration::ZERO);
+        let mut out = Vec::new();
+        let mut polls = 0;
+        follow
+            .run(&mut out, || {
+                polls += 1;
+                if polls == 2 {
+                    append(&path, b"{\"n\":2}\n{\"n\":3}\n");
+                }
+                polls > 3
+            })
+            .unwrap();
+        assert_eq!(out, b"[{\"n\":2},{\"n\":3}]");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,23 +1,62 @@
 use crate::csv_parser::CsvConfig;
-use crate::xml_parser::XmlConfig;
+use crate::chunking::ChunkingConfig;
+use crate::dedupe::DedupeConfig;
+use crate::error::{ConvertError, Result};
+use crate::projection::{FieldProjection, OutputColumn};
+use crate::compression::Compression;
+use crate::csv_writer::{CsvWriterConfig, FlattenCollisionPolicy};
+use crate::number_format::{FloatFormat, IntegerStrings};
+use crate::limits::ResourceLimits;
+use crate::json_parser::JsonInputConfig;
+use crate::ndjson_parser::JsonOutputConfig;
+use crate::ndjson_writer::NdjsonOutputConfig;
+use crate::record_errors::RecordErrorPolicy;
+use crate::sort::{SortConfig, SortOrder};
+#[cfg(feature = "parquet")]
+use crate::parquet_writer::ParquetConfig;
+#[cfg(feature = "xlsx")]
+use crate::xlsx_parser::XlsxConfig;
+use crate::xml_parser::{XmlConfig, XmlOutputConfig};
 use crate::transform::TransformPlan;
+use crate::utf8::InvalidUtf8Policy;
+use crate::detection_check::DetectionCheck;
+use crate::detect::Detection;
+use std::time::Duration;
 
 /// Supported input/output formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Format {
     Csv,
+    /// Tab-separated values, read and written as CSV with a tab delimiter.
+    Tsv,
     Ndjson,
     Json,
     Xml,
+    /// Apache Parquet; output only.
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// Excel workbook; input only.
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+    /// A YAML sequence of mappings, one record per item.
+    #[cfg(feature = "yaml")]
+    Yaml,
 }
 
 impl Format {
     pub fn from_string(s: &str) -> Option<Format> {
         match s.to_lowercase().as_str() {
             "csv" => Some(Format::Csv),
+            "tsv" | "tab" => Some(Format::Tsv),
             "ndjson" | "jsonl" => Some(Format::Ndjson),
             "json" => Some(Format::Json),
             "xml" => Some(Format::Xml),
+            #[cfg(feature = "parquet")]
+            "parquet" => Some(Format::Parquet),
+            #[cfg(feature = "xlsx")]
+            "xlsx" => Some(Format::Xlsx),
+            #[cfg(feature = "yaml")]
+            "yaml" | "yml" => Some(Format::Yaml),
             _ => None,
         }
     }
@@ -25,9 +64,43 @@ impl Format {
     pub fn to_string_js(&self) -> String {
         match self {
             Format::Csv => "csv".to_string(),
+            Format::Tsv => "tsv".to_string(),
             Format::Ndjson => "ndjson".to_string(),
             Format::Json => "json".to_string(),
             Format::Xml => "xml".to_string(),
+            #[cfg(feature = "parquet")]
+            Format::Parquet => "parquet".to_string(),
+            #[cfg(feature = "xlsx")]
+            Format::Xlsx => "xlsx".to_string(),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => "yaml".to_string(),
+        }
+    }
+}
+
+/// Settings the converter works out from the first chunk of input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AutoDetect {
+    /// Pick the input format with `detect_format`, replacing `input_format`.
+    pub input_format: bool,
+    /// Detect the delimiter of CSV input.
+    pub csv_delimiter: bool,
+    /// Detect whether CSV fields are quoted with `"` or `'`.
+    pub csv_quote: bool,
+    /// Detect whether CSV input starts with a header row.
+    pub csv_has_headers: bool,
+    /// Detect the record element of XML input.
+    pub xml_record_element: bool,
+}
+
+impl AutoDetect {
+    /// Whether input in `format` has anything left to detect.
+    pub fn applies_to(&self, format: Format) -> bool {
+        match format {
+            _ if self.input_format => true,
+            Format::Csv => self.csv_delimiter || self.csv_quote || self.csv_has_headers,
+            Format::Xml => self.xml_record_element,
+            _ => false,
         }
     }
 }
@@ -35,13 +108,80 @@ impl Format {
 /// Converter configuration
 #[derive(Debug, Clone)]
 pub struct ConverterConfig {
+    /// With `auto_detect.input_format` set, only a placeholder until detected.
     pub input_format: Format,
     pub output_format: Format,
     pub chunk_target_bytes: usize,
     pub enable_stats: bool,
     pub csv_config: Option<CsvConfig>,
+    /// How CSV output is written; `Tsv` output uses a tab delimiter regardless.
+    pub csv_writer: CsvWriterConfig,
     pub xml_config: Option<XmlConfig>,
+    /// How XML output is written.
+    pub xml_output: XmlOutputConfig,
     pub transform: Option<TransformPlan>,
+    pub flatten_collision: FlattenCollisionPolicy,
+    /// Record framing for NDJSON output; `None` keeps plain `\n` lines.
+    pub ndjson_output: Option<NdjsonOutputConfig>,
+    /// How JSON input is read.
+    pub json_input: JsonInputConfig,
+    /// Indentation of JSON output.
+    pub json_output: JsonOutputConfig,
+    /// How non-integer numbers are written in JSON, NDJSON, CSV and XML output;
+    /// `None` writes them as they come.
+    pub float_format: Option<FloatFormat>,
+    /// Integers written as strings in JSON and NDJSON output.
+    pub integer_strings: Option<IntegerStrings>,
+    /// Largest output returned by one `push`/`finish` call; the rest waits for `drain()`.
+    pub max_output_bytes: Option<usize>,
+    /// Output `push` collects before returning it, so many small records
+    /// come back in a few larger buffers; `flush` and `finish` return the rest.
+    pub min_output_bytes: Option<usize>,
+    /// Time without input after which `poll_idle()` flushes held-back output.
+    pub idle_flush: Option<Duration>,
+    /// Keep the last N trace events for `getTrace()`; `None` disables tracing.
+    pub trace_capacity: Option<usize>,
+    /// Keep multi-threaded output in input order; `false` trades order for throughput.
+    pub ordered: bool,
+    /// Most threads the parallel paths may use; `None` uses the process-wide setting.
+    pub threads: Option<usize>,
+    /// Hard caps on input size, record count, field size and nesting depth.
+    pub limits: ResourceLimits,
+    /// Compression of pushed input; `None` recognises gzip and zlib by their headers.
+    pub input_compression: Option<Compression>,
+    /// Compression applied to the bytes returned by `push`/`finish`.
+    pub output_compression: Compression,
+    /// How input that isn't valid UTF-8 is handled.
+    pub invalid_utf8: InvalidUtf8Policy,
+    /// Whether a CSV row, NDJSON line or transformed record that fails stops the conversion.
+    pub on_record_error: RecordErrorPolicy,
+    /// Keep the raw bytes of records dropped by `on_record_error` for `take_rejects()`.
+    pub keep_rejects: bool,
+    /// Keep only some fields of each input record, before `transform`.
+    pub projection: Option<FieldProjection>,
+    /// Drop records repeating the key fields of an earlier one, after `transform`.
+    pub dedupe: Option<DedupeConfig>,
+    /// Write records ordered by key fields once all input is in, after `dedupe`.
+    pub sort: Option<SortConfig>,
+    /// Stop cleanly once this many records are written, after `dedupe`.
+    pub max_records: Option<usize>,
+    /// Write only every nth record, starting with the first, before `max_records`.
+    pub sample_every_n: Option<usize>,
+    /// Fields written by the CSV, XML and JSON writers, renamed and in this
+    /// order, after everything else.
+    pub output_columns: Option<Vec<OutputColumn>>,
+    /// Cut the returned output into content-defined chunks for `take_chunks()`.
+    pub chunking: Option<ChunkingConfig>,
+    /// Build a [`ConversionManifest`](crate::ConversionManifest) at `finish()`.
+    pub manifest: bool,
+    /// Settings to detect from the first chunk instead of taking them from this config.
+    pub auto_detect: AutoDetect,
+    /// Sanity check of the CSV delimiter or XML record element against the input.
+    pub detection_check: DetectionCheck,
+    #[cfg(feature = "parquet")]
+    pub parquet_config: ParquetConfig,
+    #[cfg(feature = "xlsx")]
+    pub xlsx_config: XlsxConfig,
 }
 
 impl Default for ConverterConfig {
@@ -52,8 +192,42 @@ impl Default for ConverterConfig {
             chunk_target_bytes: 1024 * 1024, // 1MB
             enable_stats: false,
             csv_config: Some(CsvConfig::default()),
+            csv_writer: CsvWriterConfig::default(),
             xml_config: Some(XmlConfig::default()),
+            xml_output: XmlOutputConfig::default(),
             transform: None,
+            flatten_collision: FlattenCollisionPolicy::default(),
+            ndjson_output: None,
+            json_input: JsonInputConfig::default(),
+            json_output: JsonOutputConfig::default(),
+            float_format: None,
+            integer_strings: None,
+            max_output_bytes: None,
+            min_output_bytes: None,
+            idle_flush: None,
+            trace_capacity: None,
+            ordered: true,
+            threads: None,
+            limits: ResourceLimits::default(),
+            input_compression: None,
+            output_compression: Compression::None,
+            invalid_utf8: InvalidUtf8Policy::Error,
+            on_record_error: RecordErrorPolicy::Abort,
+            keep_rejects: false,
+            projection: None,
+            dedupe: None,
+            sort: None,
+            max_records: None,
+            sample_every_n: None,
+            output_columns: None,
+            chunking: None,
+            manifest: false,
+            auto_detect: AutoDetect::default(),
+            detection_check: DetectionCheck::default(),
+            #[cfg(feature = "parquet")]
+            parquet_config: ParquetConfig::default(),
+            #[cfg(feature = "xlsx")]
+            xlsx_config: XlsxConfig::default(),
         }
     }
 }
@@ -67,6 +241,29 @@ impl ConverterConfig {
         }
     }
 
+    /// A config reading input like the sample `detection` was made from,
+    /// with its compression, CSV dialect and XML record element.
+    pub fn from_detection(detection: &Detection, output_format: Format) -> Self {
+        let mut config = Self::new(detection.format, output_format);
+        config.input_compression = detection.compression;
+        if let Some(csv) = &detection.csv {
+            config.csv_config = Some(CsvConfig {
+                delimiter: csv.delimiter,
+                quote: csv.quote,
+                escape: Some(csv.quote),
+                has_headers: csv.has_headers,
+                ..CsvConfig::default()
+            });
+        }
+        if let Some(record_element) = detection.xml.as_ref().and_then(|xml| xml.record_element.clone()) {
+            config.xml_config = Some(XmlConfig {
+                record_element,
+                ..XmlConfig::default()
+            });
+        }
+        config
+    }
+
     pub fn with_chunk_size(mut self, bytes: usize) -> Self {
         self.chunk_target_bytes = bytes;
         self
@@ -82,15 +279,260 @@ impl ConverterConfig {
         self
     }
 
+    pub fn with_csv_writer_config(mut self, config: CsvWriterConfig) -> Self {
+        self.csv_writer = config;
+        self
+    }
+
     pub fn with_xml_config(mut self, config: XmlConfig) -> Self {
         self.xml_config = Some(config);
         self
     }
 
+    pub fn with_xml_output_config(mut self, config: XmlOutputConfig) -> Self {
+        self.xml_output = config;
+        self
+    }
+
     pub fn with_transform(mut self, transform: TransformPlan) -> Self {
         self.transform = Some(transform);
         self
     }
+
+    pub fn with_flatten_collision(mut self, policy: FlattenCollisionPolicy) -> Self {
+        self.flatten_collision = policy;
+        self
+    }
+
+    pub fn with_ndjson_output(mut self, config: NdjsonOutputConfig) -> Self {
+        self.ndjson_output = Some(config);
+        self
+    }
+
+    pub fn with_json_input_config(mut self, config: JsonInputConfig) -> Self {
+        self.json_input = config;
+        self
+    }
+
+    pub fn with_json_output_config(mut self, config: JsonOutputConfig) -> Self {
+        self.json_output = config;
+        self
+    }
+
+    pub fn with_float_format(mut self, format: FloatFormat) -> Self {
+        self.float_format = Some(format);
+        self
+    }
+
+    pub fn with_integer_strings(mut self, strings: IntegerStrings) -> Self {
+        self.integer_strings = Some(strings);
+        self
+    }
+
+    /// Cap what one `push` or `finish` returns at `bytes`, queueing the rest
+    /// for `drain`. Zero is rejected by [`validate`](Self::validate).
+    pub fn with_max_output_bytes(mut self, bytes: usize) -> Self {
+        self.max_output_bytes = Some(bytes);
+        self
+    }
+
+    /// Have `push` hold output back until about `bytes` of it are ready,
+    /// typically `chunk_target_bytes`, to cut the number of small buffers
+    /// handed across the WASM boundary.
+    pub fn with_min_output_bytes(mut self, bytes: usize) -> Self {
+        self.min_output_bytes = Some(bytes);
+        self
+    }
+
+    /// Let [`Converter::poll_idle`](crate::Converter::poll_idle) flush output
+    /// once `idle` passes without input, for long-running streams such as a
+    /// tailed log.
+    pub fn with_idle_flush(mut self, idle: Duration) -> Self {
+        self.idle_flush = Some(idle);
+        self
+    }
+
+    pub fn with_trace(mut self, capacity: usize) -> Self {
+        self.trace_capacity = Some(capacity);
+        self
+    }
+
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Inflate input with this compression; `Compression::None` turns off sniffing.
+    pub fn with_input_compression(mut self, compression: Compression) -> Self {
+        self.input_compression = Some(compression);
+        self
+    }
+
+    pub fn with_output_compression(mut self, compression: Compression) -> Self {
+        self.output_compression = compression;
+        self
+    }
+
+    pub fn with_invalid_utf8(mut self, policy: InvalidUtf8Policy) -> Self {
+        self.invalid_utf8 = policy;
+        self
+    }
+
+    pub fn with_record_error_policy(mut self, policy: RecordErrorPolicy) -> Self {
+        self.on_record_error = policy;
+        self
+    }
+
+    pub fn with_keep_rejects(mut self, keep: bool) -> Self {
+        self.keep_rejects = keep;
+        self
+    }
+
+    pub fn with_projection(mut self, projection: FieldProjection) -> Self {
+        self.projection = Some(projection);
+        self
+    }
+
+    pub fn with_dedupe(mut self, dedupe: DedupeConfig) -> Self {
+        self.dedupe = Some(dedupe);
+        self
+    }
+
+    /// Sort records by `keys` with the default memory budget; see
+    /// [`with_sort_config`](Self::with_sort_config) to change it. `keys`
+    /// must not be empty.
+    pub fn with_sort(self, keys: Vec<String>, order: SortOrder) -> Self {
+        self.with_sort_config(SortConfig::new(keys, order))
+    }
+
+    pub fn with_sort_config(mut self, sort: SortConfig) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    /// Write at most `records` records and then stop as `stopWhen` does, with
+    /// the output closed; unlike `limits.max_records` this isn't an error.
+    pub fn with_max_records(mut self, records: usize) -> Self {
+        self.max_records = Some(records);
+        self
+    }
+
+    /// Write only the 1st, (n+1)th, (2n+1)th... record, for previews of large inputs.
+    pub fn with_sample_every(mut self, n: usize) -> Self {
+        self.sample_every_n = Some(n);
+        self
+    }
+
+    pub fn with_output_columns(mut self, columns: Vec<OutputColumn>) -> Self {
+        self.output_columns = Some(columns);
+        self
+    }
+
+    pub fn with_chunking(mut self, chunking: ChunkingConfig) -> Self {
+        self.chunking = Some(chunking);
+        self
+    }
+
+    pub fn with_manifest(mut self, manifest: bool) -> Self {
+        self.manifest = manifest;
+        self
+    }
+
+    pub fn with_auto_detect(mut self, auto_detect: AutoDetect) -> Self {
+        self.auto_detect = auto_detect;
+        self
+    }
+
+    pub fn with_detection_check(mut self, check: DetectionCheck) -> Self {
+        self.detection_check = check;
+        self
+    }
+
+    #[cfg(feature = "parquet")]
+    pub fn with_parquet_config(mut self, config: ParquetConfig) -> Self {
+        self.parquet_config = config;
+        self
+    }
+
+    #[cfg(feature = "xlsx")]
+    pub fn with_xlsx_config(mut self, config: XlsxConfig) -> Self {
+        self.xlsx_config = config;
+        self
+    }
+
+    /// Check the settings that don't depend on the formats, which the
+    /// `with_*` methods take as they are. Converters are only built from
+    /// configs that pass.
+    pub fn validate(&self) -> Result<()> {
+        let zero = [
+            (self.max_output_bytes, "maxOutputBytes"),
+            (self.min_output_bytes, "minOutputBytes"),
+            (self.threads, "threads"),
+            (self.max_records, "maxRecords"),
+            (self.sample_every_n, "sampleEveryN"),
+        ];
+        if let Some((_, option)) = zero.iter().find(|(value, _)| *value == Some(0)) {
+            return Err(ConvertError::InvalidConfig(format!("{option} must be greater than 0")));
+        }
+        self.json_input.validate()?;
+        if let Some(float_format) = &self.float_format {
+            float_format.validate()?;
+        }
+        if let Some(projection) = &self.projection {
+            projection.validate()?;
+        }
+        if let Some(dedupe) = &self.dedupe {
+            dedupe.validate()?;
+        }
+        if let Some(sort) = &self.sort {
+            sort.validate()?;
+            if sort.index && !self.supports_sort_index() {
+                return Err(ConvertError::InvalidConfig(
+                    "sort.index needs uncompressed NDJSON output".to_string(),
+                ));
+            }
+        }
+        if let Some(columns) = &self.output_columns {
+            OutputColumn::validate(columns)?;
+        }
+        if let Some(chunking) = &self.chunking {
+            chunking.validate()?;
+        }
+        Ok(())
+    }
+
+    /// Whether `sort.index` can be built: it reads record offsets from the
+    /// uncompressed NDJSON output.
+    pub(crate) fn supports_sort_index(&self) -> bool {
+        self.output_format == Format::Ndjson && self.output_compression == Compression::None
+    }
+
+    /// The same conversion with TSV input and output turned into CSV with a
+    /// tab delimiter.
+    pub(crate) fn tsv_as_csv(&self) -> ConverterConfig {
+        let mut config = self.clone();
+        if config.input_format == Format::Tsv {
+            config.input_format = Format::Csv;
+            let mut csv_config = config.csv_config.unwrap_or_default();
+            csv_config.delimiter = b'\t';
+            config.csv_config = Some(csv_config);
+        }
+        if config.output_format == Format::Tsv {
+            config.output_format = Format::Csv;
+            config.csv_writer.delimiter = b'\t';
+        }
+        config
+    }
 }
 
 #[cfg(test)]
@@ -100,6 +542,7 @@ mod tests {
     #[test]
     fn format_string_round_trip() {
         assert_eq!(Format::from_string("csv"), Some(Format::Csv));
+        assert_eq!(Format::from_string("TSV"), Some(Format::Tsv));
         assert_eq!(Format::from_string("ndjson"), Some(Format::Ndjson));
         assert_eq!(Format::from_string("jsonl"), Some(Format::Ndjson));
         assert_eq!(Format::from_string("json"), Some(Format::Json));
@@ -107,6 +550,7 @@ mod tests {
         assert_eq!(Format::from_string("unknown"), None);
 
         assert_eq!(Format::Csv.to_string_js(), "csv");
+        assert_eq!(Format::Tsv.to_string_js(), "tsv");
         assert_eq!(Format::Ndjson.to_string_js(), "ndjson");
         assert_eq!(Format::Json.to_string_js(), "json");
         assert_eq!(Format::Xml.to_string_js(), "xml");
@@ -133,4 +577,27 @@ mod tests {
         assert_eq!(config_csv.quote, csv_config.quote);
         assert_eq!(config_xml.record_element, xml_config.record_element);
     }
+
+    #[test]
+    fn validate_rejects_what_the_builders_take_as_is() {
+        let base = || ConverterConfig::new(Format::Csv, Format::Ndjson);
+        assert!(base().validate().is_ok());
+
+        let invalid = [
+            base().with_max_output_bytes(0),
+            base().with_min_output_bytes(0),
+            base().with_threads(0),
+            base().with_max_records(0),
+            base().with_sample_every(0),
+            base().with_dedupe(DedupeConfig::default()),
+            base().with_sort(vec![], SortOrder::Asc),
+            base().with_sort(vec![String::new()], SortOrder::Desc),
+        ];
+        for config in invalid {
+            assert!(
+                matches!(config.validate(), Err(ConvertError::InvalidConfig(_))),
+                "{config:?} passed validation"
+            );
+        }
+    }
 }
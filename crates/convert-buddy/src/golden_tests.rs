@@ -0,0 +1,110 @@
+//! Golden-file round trips over realistic feeds.
+//!
+//! Each fixture in `tests/golden/` is converted to every output format and
+//! compared with `tests/golden/expected/<fixture>.<output>`. Fixtures are
+//! embedded with `include_bytes!` so the same cases run natively and under
+//! wasm-bindgen-test. To add a fixture, drop the input file in place, create
+//! empty expected files, register it in `CASES`, then run the native tests
+//! with `GOLDEN_BLESS=1` to write the actual output and review the diff.
+
+use crate::{Converter, ConverterConfig, Format, XmlConfig};
+
+macro_rules! golden_file {
+    ($($part:literal),+) => {
+        include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/", $($part),+))
+    };
+}
+
+macro_rules! golden_case {
+    ($input:literal, $format:expr) => {
+        GoldenCase {
+            input: $input,
+            format: $format,
+            input_bytes: golden_file!($input),
+            expected: [
+                (Format::Csv, golden_file!("expected/", $input, ".csv")),
+                (Format::Ndjson, golden_file!("expected/", $input, ".ndjson")),
+                (Format::Json, golden_file!("expected/", $input, ".json")),
+                (Format::Xml, golden_file!("expected/", $input, ".xml")),
+            ],
+        }
+    };
+}
+
+struct GoldenCase {
+    input: &'static str,
+    format: Format,
+    input_bytes: &'static [u8],
+    expected: [(Format, &'static [u8]); 4],
+}
+
+const CASES: &[GoldenCase] = &[
+    golden_case!("products.csv", Format::Csv),
+    golden_case!("products.ndjson", Format::Ndjson),
+    golden_case!("products.json", Format::Json),
+    golden_case!("products.xml", Format::Xml),
+];
+
+fn convert(case: &GoldenCase, output: Format) -> Vec<u8> {
+    let mut config = ConverterConfig::new(case.format, output);
+    if case.format == Format::Xml {
+        config = config.with_xml_config(XmlConfig {
+            record_element: "product".to_string(),
+            ..XmlConfig::default()
+        });
+    }
+
+    let mut converter = Converter::from_config(config);
+    let mut result = converter.push(case.input_bytes).expect("push failed");
+    result.extend(converter.finish().expect("finish failed"));
+    result
+}
+
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+fn golden_conversions_match_expected_output() {
+    let mut failures = Vec::new();
+    for case in CASES {
+        for (output, expected) in &case.expected {
+            let actual = convert(case, *output);
+            #[cfg(not(target_arch = "wasm32"))]
+            if std::env::var_os("GOLDEN_BLESS").is_some() {
+                let path = format!(
+                    "{}/tests/golden/expected/{}.{}",
+                    env!("CARGO_MANIFEST_DIR"),
+                    case.input,
+                    output.to_string_js()
+                );
+                std::fs::write(path, &actual).unwrap();
+                continue;
+            }
+            if actual != *expected {
+                failures.push(format!(
+                    "{} -> {}:\n--- expected\n{}\n--- actual\n{}",
+                    case.input,
+                    output.to_string_js(),
+                    String::from_utf8_lossy(expected),
+                    String::from_utf8_lossy(&actual)
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+/// Catch fixtures that were added to the directory but never registered.
+#[cfg(not(target_arch = "wasm32"))]
+#[test]
+fn every_golden_fixture_is_registered() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let entry = entry.unwrap();
+        if entry.file_type().unwrap().is_file() {
+            let name = entry.file_name().into_string().unwrap();
+            assert!(
+                CASES.iter().any(|case| case.input == name),
+                "tests/golden/{name} is not listed in golden_tests::CASES"
+            );
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::compression::Compression;
+use crate::error::{ConvertError, Result};
+use crate::format::{ConverterConfig, Format};
+use crate::warnings::Warning;
+use crate::Converter;
+use memchr::memchr;
+use serde_json::Value;
+
+/// Output of one group, or the part of it produced by one call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupBatch {
+    /// Value of the group field: strings as they are, other values as JSON,
+    /// `None` when the field is missing or null.
+    pub key: Option<String>,
+    pub output: Vec<u8>,
+    /// Whether this is the last part of the group.
+    pub complete: bool,
+}
+
+/// Converts input that is grouped by one field, writing each run of records
+/// with the same value as a separate output, e.g. one JSON array or one CSV
+/// file (with its own header) per customer.
+///
+/// Groups end where the value changes, so only the current group's writer is
+/// kept; a value that comes back later starts a new group.
+pub struct GroupBatcher {
+    field: String,
+    records: Converter,
+    output_config: ConverterConfig,
+    current: Option<(Option<String>, Converter)>,
+    partial_line: Vec<u8>,
+}
+
+impl GroupBatcher {
+    /// Group the records `config` reads and transforms by `field`, writing
+    /// each group with the output settings of `config`.
+    pub fn new(config: ConverterConfig, field: impl Into<String>) -> Result<Self> {
+        let field = field.into();
+        if field.is_empty() {
+            return Err(ConvertError::InvalidConfig("groupBy field must not be empty".to_string()));
+        }
+
+        let mut output_config = ConverterConfig::new(Format::Ndjson, config.output_format)
+            .with_chunk_size(config.chunk_target_bytes)
+            .with_csv_writer_config(config.csv_writer.clone())
+            .with_xml_output_config(config.xml_output.clone())
+            .with_json_output_config(config.json_output)
+            .with_flatten_collision(config.flatten_collision)
+            .with_input_compression(Compression::None)
+            .with_output_compression(config.output_compression);
+        output_config.ndjson_output = config.ndjson_output;
+        output_config.float_format = config.float_format;
+        output_config.integer_strings = config.integer_strings.clone();
+        #[cfg(feature = "parquet")]
+        {
+            output_config.parquet_config = config.parquet_config;
+        }
+
+        let mut records_config = config;
+        records_config.output_format = Format::Ndjson;
+        records_config.ndjson_output = None;
+        records_config.output_compression = Compression::None;
+        records_config.float_format = None;
+        records_config.integer_strings = None;
+        records_config.max_output_bytes = None;
+        records_config.min_output_bytes = None;
+
+        Ok(Self {
+            field,
+            records: Converter::from_config(records_config),
+            output_config,
+            current: None,
+            partial_line: Vec::new(),
+        })
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<GroupBatch>> {
+        let ndjson = self.records.push_bytes(chunk)?;
+        let mut batches = Vec::new();
+        self.split(&ndjson, &mut batches)?;
+        Ok(batches)
+    }
+
+    /// Convert the rest of the input and close the last group.
+    pub fn finish(&mut self) -> Result<Vec<GroupBatch>> {
+        let mut ndjson = self.records.finish_stream()?;
+        let mut batches = Vec::new();
+        if !ndjson.ends_with(b"\n") {
+            ndjson.push(b'\n');
+        }
+        self.split(&ndjson, &mut batches)?;
+        self.close_group(&mut batches)?;
+        Ok(batches)
+    }
+
+    /// Warnings from reading the input.
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.records.warnings()
+    }
+
+    fn split(&mut self, ndjson: &[u8], batches: &mut Vec<GroupBatch>) -> Result<()> {
+        let mut temp_buffer = Vec::new();
+        let input_data: &[u8] = if !self.partial_line.is_empty() {
+            temp_buffer.append(&mut self.partial_line);
+            temp_buffer.extend_from_slice(ndjson);
+            &temp_buffer
+        } else {
+            ndjson
+        };
+
+        // Lines of the current group are pushed together
+        let mut run_start = 0;
+        let mut start = 0;
+        while let Some(pos) = memchr(b'\n', &input_data[start..]) {
+            let line_end = start + pos;
+            let line = &input_data[start..line_end];
+            if !line.iter().all(|b| b.is_ascii_whitespace()) {
+                let key = self.key_of(line)?;
+                let same_group = matches!(&self.current, Some((current, _)) if *current == key);
+                if !same_group {
+                    self.write_run(&input_data[run_start..start], batches)?;
+                    run_start = start;
+                    self.close_group(batches)?;
+                    let converter = Converter::from_config(self.output_config.clone());
+                    self.current = Some((key, converter));
+                }
+            }
+            start = line_end + 1;
+        }
+        self.write_run(&input_data[run_start..start], batches)?;
+
+        if start < input_data.len() {
+            self.partial_line.extend_from_slice(&input_data[start..]);
+        }
+        Ok(())
+    }
+
+    fn key_of(&self, line: &[u8]) -> Result<Option<String>> {
+        let value: Value = serde_json::from_slice(line)
+            .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+        Ok(match value.get(&self.field) {
+            Some(Value::String(text)) => Some(text.clone()),
+            Some(Value::Null) | None => None,
+            Some(other) => Some(other.to_string()),
+        })
+    }
+
+    fn write_run(&mut self, lines: &[u8], batches: &mut Vec<GroupBatch>) -> Result<()> {
+        let Some((key, converter)) = self.current.as_mut() else {
+            return Ok(());
+        };
+        if lines.is_empty() {
+            return Ok(());
+        }
+        let output = converter.push_bytes(lines)?;
+        if output.is_empty() {
+            return Ok(());
+        }
+        match batches.last_mut() {
+            Some(last) if !last.complete && last.key == *key => last.output.extend(output),
+            _ => batches.push(GroupBatch {
+                key: key.clone(),
+                output,
+                complete: false,
+            }),
+        }
+        Ok(())
+    }
+
+    fn close_group(&mut self, batches: &mut Vec<GroupBatch>) -> Result<()> {
+        let Some((key, mut converter)) = self.current.take() else {
+            return Ok(());
+        };
+        let output = converter.finish_stream()?;
+        match batches.last_mut() {
+            Some(last) if !last.complete && last.key == key => {
+                last.output.extend(output);
+                last.complete = true;
+            }
+            _ => batches.push(GroupBatch {
+                key,
+                output,
+                complete: true,
+            }),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(config: ConverterConfig, chunks: &[&[u8]]) -> Vec<GroupBatch> {
+        let mut batcher = GroupBatcher::new(config, "customer").unwrap();
+        let mut batches = Vec::new();
+        for chunk in chunks {
+            batches.extend(batcher.push(chunk).unwrap());
+        }
+        batches.extend(batcher.finish().unwrap());
+        batches
+    }
+
+    /// Merge the parts of each group.
+    fn groups(batches: Vec<GroupBatch>) -> Vec<(Option<String>, String)> {
+        let mut groups: Vec<(Option<String>, String)> = Vec::new();
+        let mut open = false;
+        for batch in batches {
+            let text = String::from_utf8(batch.output).unwrap();
+            match groups.last_mut() {
+                Some((key, output)) if open && *key == batch.key => output.push_str(&text),
+                _ => groups.push((batch.key, text)),
+            }
+            open = !batch.complete;
+        }
+        groups
+    }
+
+    #[test]
+    fn writes_each_run_as_its_own_output() {
+        let config = ConverterConfig::new(Format::Csv, Format::Json);
+        let batches = run(config, &[b"customer,n\na,1\na,2\nb,", b"3\n,4\na,5\n"]);
+        assert!(batches.last().unwrap().complete);
+        assert_eq!(
+            groups(batches),
+            vec![
+                (Some("a".to_string()), "[{\"customer\":\"a\",\"n\":\"1\"},{\"customer\":\"a\",\"n\":\"2\"}]".to_string()),
+                (Some("b".to_string()), "[{\"customer\":\"b\",\"n\":\"3\"}]".to_string()),
+                (Some(String::new()), "[{\"customer\":\"\",\"n\":\"4\"}]".to_string()),
+                (Some("a".to_string()), "[{\"customer\":\"a\",\"n\":\"5\"}]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn streams_the_open_group() {
+        let config = ConverterConfig::new(Format::Ndjson, Format::Csv);
+        let mut batcher = GroupBatcher::new(config, "customer").unwrap();
+
+        let batches = batcher.push(b"{\"customer\":1,\"n\":1}\n{\"customer\":1,\"n\":2}\n").unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].key.as_deref(), Some("1"));
+        assert!(!batches[0].complete);
+
+        let batches = batcher.push(b"{\"n\":3}\n").unwrap();
+        assert!(batches[0].complete);
+        assert_eq!(batches[0].key.as_deref(), Some("1"));
+        assert_eq!(batches[1].key, None);
+
+        let batches = batcher.finish().unwrap();
+        assert_eq!(
+            batches,
+            vec![GroupBatch {
+                key: None,
+                output: Vec::new(),
+                complete: true
+            }]
+        );
+    }
+}
@@ -1,5 +1,8 @@
 use crate::error::{ConvertError, Result};
+use crate::json_validate;
 use log::debug;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 
 /// JSON parser that uses high-performance parsing when available
 pub struct JsonParser {
@@ -19,19 +22,12 @@ impl JsonParser {
         Self { use_simd }
     }
 
-    /// Parse JSON bytes and validate structure
-    /// Returns the parsed JSON as bytes (for zero-copy streaming)
-    #[cfg(feature = "simd")]
-    pub fn parse_and_validate(&self, data: &mut [u8]) -> Result<()> {
-        // Mode B: High-performance simd-json
-        simd_json::to_borrowed_value(data)
-            .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
-        Ok(())
-    }
-
-    #[cfg(not(feature = "simd"))]
+    /// Check that `data` is one JSON value. Valid input is scanned without
+    /// building a value; the rest is parsed by serde_json for its error.
     pub fn parse_and_validate(&self, data: &[u8]) -> Result<()> {
-        // Mode A: Portable serde_json
+        if json_validate::is_valid(data) {
+            return Ok(());
+        }
         serde_json::from_slice::<serde_json::Value>(data)
             .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
         Ok(())
@@ -119,6 +115,543 @@ impl Default for JsonParser {
     }
 }
 
+/// How JSON input is read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct JsonInputConfig {
+    /// Dot-separated object keys leading to the array of records, such as
+    /// `data.items` or `$.data.items`; `None` takes the top-level value.
+    /// Keys are compared as written in the document, escapes included.
+    pub records_path: Option<String>,
+}
+
+impl JsonInputConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(path) = &self.records_path {
+            if records_path_keys(path).iter().any(|key| key.is_empty()) {
+                return Err(ConvertError::InvalidConfig(format!(
+                    "jsonInput.recordsPath has an empty key: {path:?}"
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split a records path into its object keys.
+fn records_path_keys(path: &str) -> Vec<Vec<u8>> {
+    let path = path.strip_prefix('$').map_or(path, |rest| rest.strip_prefix('.').unwrap_or(rest));
+    if path.is_empty() {
+        return Vec::new();
+    }
+    path.split('.').map(|key| key.as_bytes().to_vec()).collect()
+}
+
+/// Incremental JSON syntax checker for passthrough conversions.
+///
+/// Bytes are validated as they arrive, so a document split across any number
+/// of chunks is checked without buffering it. Errors report the line, column
+/// and byte offset of the offending byte. Records are counted as the elements
+/// of a top-level array, or 1 for any other top-level value; with a records
+/// path, the same applies to the value at that path instead.
+#[derive(Debug)]
+pub struct JsonValidator {
+    stack: Vec<Container>,
+    /// Object keys leading from the top-level value to the records.
+    records_path: Vec<Vec<u8>>,
+    /// Per open container, whether the current member's key is the next key
+    /// of `records_path`.
+    on_path: Vec<bool>,
+    /// The object key being read, while it may still be on the path.
+    key: Vec<u8>,
+    /// Whether the value at the records path is an array, once seen.
+    records_in_array: Option<bool>,
+    state: State,
+    offset: usize,
+    line: usize,
+    column: usize,
+    records: usize,
+    /// Byte offset where the record currently being read began.
+    record_start: Option<usize>,
+    /// Nesting depth of records.
+    record_depth: usize,
+    /// Completed record byte ranges, collected only when requested.
+    record_spans: Option<Vec<Range<usize>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Container {
+    Object,
+    Array,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// Expecting a value; `allow_close` permits `]` right after `[`.
+    Value { allow_close: bool },
+    /// Expecting an object key; `allow_close` permits `}` right after `{`.
+    Key { allow_close: bool },
+    Colon,
+    AfterValue,
+    String { key: bool, escape: Escape },
+    Number(NumberState),
+    Literal { word: &'static [u8], index: usize },
+    Done,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Escape {
+    None,
+    Backslash,
+    /// Hex digits still expected after `\u`.
+    Unicode(u8),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NumberState {
+    Minus,
+    Zero,
+    Integer,
+    Dot,
+    Fraction,
+    Exponent,
+    ExponentSign,
+    ExponentDigits,
+}
+
+impl NumberState {
+    fn next(self, b: u8) -> Option<Self> {
+        use NumberState::*;
+        match (self, b) {
+            (Minus, b'0') => Some(Zero),
+            (Minus, b'1'..=b'9') => Some(Integer),
+            (Integer, b'0'..=b'9') => Some(Integer),
+            (Zero | Integer, b'.') => Some(Dot),
+            (Dot | Fraction, b'0'..=b'9') => Some(Fraction),
+            (Zero | Integer | Fraction, b'e' | b'E') => Some(Exponent),
+            (Exponent, b'+' | b'-') => Some(ExponentSign),
+            (Exponent | ExponentSign | ExponentDigits, b'0'..=b'9') => Some(ExponentDigits),
+            _ => None,
+        }
+    }
+
+    fn is_complete(self) -> bool {
+        matches!(
+            self,
+            NumberState::Zero | NumberState::Integer | NumberState::Fraction | NumberState::ExponentDigits
+        )
+    }
+}
+
+impl JsonValidator {
+    pub fn new() -> Self {
+        Self {
+            stack: Vec::new(),
+            records_path: Vec::new(),
+            on_path: Vec::new(),
+            key: Vec::new(),
+            records_in_array: None,
+            state: State::Value { allow_close: false },
+            offset: 0,
+            line: 1,
+            column: 1,
+            records: 0,
+            record_start: None,
+            record_depth: 0,
+            record_spans: None,
+        }
+    }
+
+    /// Take records from the value at `path` (see
+    /// [`JsonInputConfig::records_path`]) instead of the top-level value.
+    pub fn with_records_path(mut self, path: &str) -> Self {
+        self.records_path = records_path_keys(path);
+        self
+    }
+
+    /// Also collect the byte range of every completed record, for
+    /// [`JsonValidator::take_record_spans`].
+    pub fn with_record_spans(mut self) -> Self {
+        self.record_spans = Some(Vec::new());
+        self
+    }
+
+    /// Start over on a new document, keeping the records path.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn restart(&mut self) {
+        *self = Self {
+            records_path: std::mem::take(&mut self.records_path),
+            record_spans: self.record_spans.as_ref().map(|_| Vec::new()),
+            ..Self::new()
+        };
+    }
+
+    /// Validate the next chunk of the document.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        for &b in chunk {
+            self.step(b)?;
+            self.offset += 1;
+            if b == b'\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the input ended on a complete document. Empty input is accepted.
+    pub fn finish(&mut self) -> Result<()> {
+        if let State::Number(number) = self.state {
+            if self.stack.is_empty() && number.is_complete() {
+                self.end_value(self.offset);
+            }
+        }
+        match self.state {
+            State::Done => Ok(()),
+            State::Value { .. } if self.stack.is_empty() => Ok(()),
+            _ => Err(self.error("unexpected end of JSON input")),
+        }
+    }
+
+    /// Records seen so far.
+    pub fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Byte ranges (in total input offsets) of the records completed since the
+    /// last call. Empty unless built [`with_record_spans`](Self::with_record_spans).
+    pub fn take_record_spans(&mut self) -> Vec<Range<usize>> {
+        self.record_spans.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Offset where the record still being read began, if any.
+    pub fn open_record_start(&self) -> Option<usize> {
+        self.record_start
+    }
+
+    /// Whether records are the elements of an array, once the value holding
+    /// them has started.
+    pub fn records_in_array(&self) -> Option<bool> {
+        self.records_in_array
+    }
+
+    fn step(&mut self, b: u8) -> Result<()> {
+        loop {
+            match self.state {
+                State::Value { allow_close } => {
+                    if b.is_ascii_whitespace() {
+                        return Ok(());
+                    }
+                    self.state = match b {
+                        b']' if allow_close => return self.close(Container::Array),
+                        b'{' => {
+                            self.begin_value(false);
+                            self.open(Container::Object);
+                            State::Key { allow_close: true }
+                        }
+                        b'[' => {
+                            self.begin_value(true);
+                            self.open(Container::Array);
+                            State::Value { allow_close: true }
+                        }
+                        b'"' => {
+                            self.begin_value(false);
+                            State::String { key: false, escape: Escape::None }
+                        }
+                        b'-' | b'0'..=b'9' => {
+                            self.begin_value(false);
+                            State::Number(match b {
+                                b'-' => NumberState::Minus,
+                                b'0' => NumberState::Zero,
+                                _ => NumberState::Integer,
+                            })
+                        }
+                        b't' | b'f' | b'n' => {
+                            self.begin_value(false);
+                            let word: &'static [u8] = match b {
+                                b't' => b"true",
+                                b'f' => b"false",
+                                _ => b"null",
+                            };
+                            State::Literal { word, index: 1 }
+                        }
+                        _ => return Err(self.error("expected value")),
+                    };
+                    return Ok(());
+                }
+                State::Key { allow_close } => {
+                    return match b {
+                        _ if b.is_ascii_whitespace() => Ok(()),
+                        b'"' => {
+                            self.key.clear();
+                            self.state = State::String { key: true, escape: Escape::None };
+                            Ok(())
+                        }
+                        b'}' if allow_close => self.close(Container::Object),
+                        _ => Err(self.error("expected object key")),
+                    };
+                }
+                State::Colon => {
+                    return match b {
+                        _ if b.is_ascii_whitespace() => Ok(()),
+                        b':' => {
+                            self.state = State::Value { allow_close: false };
+                            Ok(())
+                        }
+                        _ => Err(self.error("expected ':'")),
+                    };
+                }
+                State::AfterValue => {
+                    return match b {
+                        _ if b.is_ascii_whitespace() => Ok(()),
+                        b',' => {
+                            self.state = match self.stack.last() {
+                                Some(Container::Object) => State::Key { allow_close: false },
+                                _ => State::Value { allow_close: false },
+                            };
+                            Ok(())
+                        }
+                        b'}' => self.close(Container::Object),
+                        b']' => self.close(Container::Array),
+                        _ => Err(self.error("expected ',' or closing bracket")),
+                    };
+                }
+                State::String { key, escape } => {
+                    let escape = match (escape, b) {
+                        (Escape::None, b'"') => {
+                            if key {
+                                self.end_key();
+                                self.state = State::Colon;
+                            } else {
+                                self.end_value(self.offset + 1);
+                            }
+                            return Ok(());
+                        }
+                        (Escape::None, b'\\') => Escape::Backslash,
+                        (Escape::None, 0x00..=0x1f) => {
+                            return Err(self.error("control character in string"))
+                        }
+                        (Escape::None, _) => Escape::None,
+                        (Escape::Backslash, b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => {
+                            Escape::None
+                        }
+                        (Escape::Backslash, b'u') => Escape::Unicode(4),
+                        (Escape::Backslash, _) => return Err(self.error("invalid escape")),
+                        (Escape::Unicode(remaining), _) if b.is_ascii_hexdigit() => {
+                            if remaining == 1 {
+                                Escape::None
+                            } else {
+                                Escape::Unicode(remaining - 1)
+                            }
+                        }
+                        (Escape::Unicode(_), _) => {
+                            return Err(self.error("invalid unicode escape"))
+                        }
+                    };
+                    if key && self.stack.len() <= self.records_path.len() {
+                        self.key.push(b);
+                    }
+                    self.state = State::String { key, escape };
+                    return Ok(());
+                }
+                State::Number(number) => match number.next(b) {
+                    Some(next) => {
+                        self.state = State::Number(next);
+                        return Ok(());
+                    }
+                    None if number.is_complete() => {
+                        // The byte ending a number belongs to what follows it.
+                        self.end_value(self.offset);
+                    }
+                    None => return Err(self.error("invalid number")),
+                },
+                State::Literal { word, index } => {
+                    if word[index] != b {
+                        return Err(self.error("invalid literal"));
+                    }
+                    if index + 1 == word.len() {
+                        self.end_value(self.offset + 1);
+                    } else {
+                        self.state = State::Literal { word, index: index + 1 };
+                    }
+                    return Ok(());
+                }
+                State::Done => {
+                    return if b.is_ascii_whitespace() {
+                        Ok(())
+                    } else {
+                        Err(self.error("trailing characters after JSON document"))
+                    };
+                }
+            }
+        }
+    }
+
+    fn begin_value(&mut self, is_array: bool) {
+        let depth = self.stack.len();
+        let path_len = self.records_path.len();
+        if depth > path_len + 1 || self.record_start.is_some() {
+            return;
+        }
+        let is_record = if depth == path_len {
+            let on_path = self.on_path.iter().all(|&on_path| on_path);
+            if on_path {
+                self.records_in_array = Some(is_array);
+            }
+            on_path && !is_array
+        } else {
+            depth == path_len + 1
+                && self.stack[path_len] == Container::Array
+                && self.on_path[..path_len].iter().all(|&on_path| on_path)
+        };
+        if is_record {
+            self.records += 1;
+            self.record_start = Some(self.offset);
+            self.record_depth = depth;
+        }
+    }
+
+    /// Note whether the key just read leads further along the records path.
+    fn end_key(&mut self) {
+        let level = self.stack.len() - 1;
+        if let Some(on_path) = self.on_path.get_mut(level) {
+            *on_path = self.records_path.get(level) == Some(&self.key);
+        }
+    }
+
+    fn open(&mut self, container: Container) {
+        self.stack.push(container);
+        if self.stack.len() <= self.records_path.len() {
+            self.on_path.push(false);
+        }
+    }
+
+    /// Finish the value ending just before byte offset `end`.
+    fn end_value(&mut self, end: usize) {
+        if self.stack.len() == self.record_depth {
+            if let Some(start) = self.record_start.take() {
+                if let Some(spans) = self.record_spans.as_mut() {
+                    spans.push(start..end);
+                }
+            }
+        }
+        self.state = if self.stack.is_empty() {
+            State::Done
+        } else {
+            State::AfterValue
+        };
+    }
+
+    fn close(&mut self, container: Container) -> Result<()> {
+        if self.stack.len() <= self.records_path.len() {
+            self.on_path.pop();
+        }
+        if self.stack.pop() != Some(container) {
+            return Err(self.error("mismatched closing bracket"));
+        }
+        self.end_value(self.offset + 1);
+        Ok(())
+    }
+
+    fn error(&self, message: &str) -> ConvertError {
+        ConvertError::JsonParse(format!(
+            "{message} at line {} column {} (byte {})",
+            self.line, self.column, self.offset
+        ))
+    }
+}
+
+impl Default for JsonValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Streams the records of a JSON document out as NDJSON lines.
+///
+/// Records are the elements of a top-level array, or a top-level object, or
+/// the same at the records path when one is set. Input may be split anywhere: only the bytes of the record still being read
+/// are buffered, and each record is re-serialized minified once complete.
+#[derive(Debug)]
+pub struct JsonRecordReader {
+    validator: JsonValidator,
+    /// Input bytes from `buffer_offset` onwards that are not yet emitted.
+    buffer: Vec<u8>,
+    buffer_offset: usize,
+}
+
+impl JsonRecordReader {
+    pub fn new() -> Self {
+        Self {
+            validator: JsonValidator::new().with_record_spans(),
+            buffer: Vec::new(),
+            buffer_offset: 0,
+        }
+    }
+
+    /// Take records from the value at `path` instead of the top-level value.
+    pub fn with_records_path(mut self, path: &str) -> Self {
+        self.validator = self.validator.with_records_path(path);
+        self
+    }
+
+    /// Feed the next chunk, returning the NDJSON lines of records it completed.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        self.buffer.extend_from_slice(chunk);
+        self.validator.push(chunk)?;
+        self.drain_records()
+    }
+
+    /// Check the document is complete and emit a trailing top-level value.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        self.validator.finish()?;
+        self.drain_records()
+    }
+
+    /// Bytes held for the record still being read.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Drop the document read so far, so the next chunk starts a new one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn discard_partial(&mut self) {
+        self.validator.restart();
+        self.buffer.clear();
+        self.buffer_offset = 0;
+    }
+
+    fn drain_records(&mut self) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        for span in self.validator.take_record_spans() {
+            let bytes = &self.buffer[span.start - self.buffer_offset..span.end - self.buffer_offset];
+            if self.validator.records_in_array() == Some(true) || bytes.first() == Some(&b'{') {
+                let value: serde_json::Value = serde_json::from_slice(bytes)
+                    .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+                serde_json::to_writer(&mut output, &value)
+                    .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+                output.push(b'\n');
+            }
+        }
+
+        let keep_from = self
+            .validator
+            .open_record_start()
+            .unwrap_or(self.buffer_offset + self.buffer.len());
+        self.buffer.drain(..keep_from - self.buffer_offset);
+        self.buffer_offset = keep_from;
+        Ok(output)
+    }
+}
+
+impl Default for JsonRecordReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,6 +689,89 @@ mod tests {
         assert!(pretty_str.contains("\"a\""));
     }
 
+    fn validate_in_chunks(data: &[u8], chunk_size: usize) -> Result<usize> {
+        let mut validator = JsonValidator::new();
+        for chunk in data.chunks(chunk_size) {
+            validator.push(chunk)?;
+        }
+        validator.finish()?;
+        Ok(validator.records())
+    }
+
+    #[test]
+    fn test_validator_accepts_documents_split_anywhere() {
+        let data = br#" [ {"a": "x\"]\u00e9", "b": [1, -0.5e+3, true, null]}, {}, [], 7 ]
+"#;
+        for chunk_size in 1..data.len() {
+            assert_eq!(validate_in_chunks(data, chunk_size).unwrap(), 4);
+        }
+        assert_eq!(validate_in_chunks(br#"{"a":1}"#, 3).unwrap(), 1);
+        assert_eq!(validate_in_chunks(b"42", 1).unwrap(), 1);
+        assert_eq!(validate_in_chunks(b"  ", 1).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_validator_reports_error_positions() {
+        let err = validate_in_chunks(b"[1,\n {\"a\" 2}]", 4).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "JSON parse error: expected ':' at line 2 column 7 (byte 10)"
+        );
+
+        for invalid in [&b"[1,]"[..], b"{\"a\":1", b"[01]", b"tru", b"{} {}", b"[}", b"\"\\x\""] {
+            assert!(validate_in_chunks(invalid, 2).is_err(), "{:?}", String::from_utf8_lossy(invalid));
+        }
+    }
+
+    #[test]
+    fn test_record_reader_streams_records_split_anywhere() {
+        let data = br#" [ {"a": "x\"]", "b": [1, -0.5e+3]}, 7, {} ] "#;
+        for chunk_size in 1..data.len() {
+            let mut reader = JsonRecordReader::new();
+            let mut output = Vec::new();
+            for chunk in data.chunks(chunk_size) {
+                output.extend(reader.push(chunk).unwrap());
+                assert!(reader.buffered_bytes() <= 40);
+            }
+            output.extend(reader.finish().unwrap());
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                "{\"a\":\"x\\\"]\",\"b\":[1,-500.0]}\n7\n{}\n"
+            );
+        }
+
+        let mut reader = JsonRecordReader::new();
+        assert_eq!(reader.push(b"{\"a\":1}").unwrap(), b"{\"a\":1}\n".to_vec());
+        let mut reader = JsonRecordReader::new();
+        assert!(reader.push(b"42").unwrap().is_empty());
+        assert!(reader.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_record_reader_takes_records_at_path() {
+        let data = br#"{"meta": {"items": [0]}, "data": {"count": 2, "items": [{"id": 1, "items": [9]}, {"id": 2}]}, "items": [3]}"#;
+        for chunk_size in [1, 5, data.len()] {
+            let mut reader = JsonRecordReader::new().with_records_path("$.data.items");
+            let mut output = Vec::new();
+            for chunk in data.chunks(chunk_size) {
+                output.extend(reader.push(chunk).unwrap());
+            }
+            output.extend(reader.finish().unwrap());
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                "{\"id\":1,\"items\":[9]}\n{\"id\":2}\n"
+            );
+        }
+
+        // An object at the path is one record; a missing path gives none
+        let mut reader = JsonRecordReader::new().with_records_path("data");
+        assert_eq!(reader.push(br#"{"data": {"id": 1}}"#).unwrap(), b"{\"id\":1}\n".to_vec());
+        let mut reader = JsonRecordReader::new().with_records_path("data.items");
+        assert!(reader.push(br#"[{"data": {"items": [1]}}]"#).unwrap().is_empty());
+
+        assert!(JsonInputConfig { records_path: Some("data..items".to_string()) }.validate().is_err());
+    }
+
     #[test]
     fn test_parse_and_validate_errors() {
         let parser = JsonParser::new();
@@ -0,0 +1,321 @@
+//! Allocation-free validation of JSON documents such as NDJSON lines.
+//!
+//! [`is_valid`] only accepts documents `serde_json` accepts. Anything else,
+//! including valid documents it can't vouch for (numbers with an exponent,
+//! escaped surrogates, deep nesting), is left to a full parse, which also
+//! words the error. With the `simd` feature, string contents are skipped 16
+//! bytes at a time using SSE2 on x86_64 and simd128 on wasm32.
+
+/// Levels of nesting checked here; deeper documents are left to `serde_json`,
+/// which allows 128.
+const MAX_DEPTH: u32 = 127;
+
+/// Integer digits a number may have before it could overflow an `f64`.
+const MAX_INTEGER_DIGITS: usize = 300;
+
+/// Whether `data` is a single JSON value, optionally surrounded by whitespace.
+pub(crate) fn is_valid(data: &[u8]) -> bool {
+    std::str::from_utf8(data).is_ok() && Scanner { data, pos: 0 }.document().is_some()
+}
+
+struct Scanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn document(&mut self) -> Option<()> {
+        // Bit `n` is set when level `n` is an object
+        let mut objects = 0u128;
+        let mut depth = 0u32;
+        'value: loop {
+            self.skip_whitespace();
+            match *self.data.get(self.pos)? {
+                open @ (b'{' | b'[') => {
+                    if depth == MAX_DEPTH {
+                        return None;
+                    }
+                    let object = open == b'{';
+                    objects = (objects & !(1 << depth)) | (u128::from(object) << depth);
+                    depth += 1;
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    if self.data.get(self.pos) == Some(&if object { b'}' } else { b']' }) {
+                        self.pos += 1;
+                        depth -= 1;
+                    } else {
+                        if object {
+                            self.key()?;
+                        }
+                        continue 'value;
+                    }
+                }
+                b'"' => self.string()?,
+                b't' => self.literal(b"true")?,
+                b'f' => self.literal(b"false")?,
+                b'n' => self.literal(b"null")?,
+                b'-' | b'0'..=b'9' => self.number()?,
+                _ => return None,
+            }
+
+            // After a value: close its containers or move on to the next one
+            loop {
+                self.skip_whitespace();
+                if depth == 0 {
+                    return (self.pos == self.data.len()).then_some(());
+                }
+                let object = objects & (1 << (depth - 1)) != 0;
+                match *self.data.get(self.pos)? {
+                    b',' => {
+                        self.pos += 1;
+                        if object {
+                            self.skip_whitespace();
+                            self.key()?;
+                        }
+                        continue 'value;
+                    }
+                    b'}' if object => depth -= 1,
+                    b']' if !object => depth -= 1,
+                    _ => return None,
+                }
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// An object key and the colon after it.
+    fn key(&mut self) -> Option<()> {
+        if self.data.get(self.pos) != Some(&b'"') {
+            return None;
+        }
+        self.string()?;
+        self.skip_whitespace();
+        if self.data.get(self.pos) != Some(&b':') {
+            return None;
+        }
+        self.pos += 1;
+        Some(())
+    }
+
+    fn string(&mut self) -> Option<()> {
+        self.pos += 1;
+        loop {
+            self.pos = skip_plain(self.data, self.pos);
+            match *self.data.get(self.pos)? {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(());
+                }
+                b'\\' => self.escape()?,
+                // Control characters have to be escaped
+                _ => return None,
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Option<()> {
+        match *self.data.get(self.pos + 1)? {
+            b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => self.pos += 2,
+            b'u' => {
+                let hex = self.data.get(self.pos + 2..self.pos + 6)?;
+                let code = u16::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?;
+                // Whether a surrogate is paired is left to serde_json
+                if (0xD800..=0xDFFF).contains(&code) {
+                    return None;
+                }
+                self.pos += 6;
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Option<()> {
+        if !self.data[self.pos..].starts_with(literal) {
+            return None;
+        }
+        self.pos += literal.len();
+        Some(())
+    }
+
+    fn number(&mut self) -> Option<()> {
+        if self.data[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        let start = self.pos;
+        let integer = self.digits();
+        // At least one integer digit, without leading zeros
+        if integer == 0 || integer > MAX_INTEGER_DIGITS || (integer > 1 && self.data[start] == b'0') {
+            return None;
+        }
+        if self.data.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if self.digits() == 0 {
+                return None;
+            }
+        }
+        // Exponents may overflow, which serde_json reports as an error
+        if matches!(self.data.get(self.pos), Some(b'e' | b'E')) {
+            return None;
+        }
+        Some(())
+    }
+
+    /// Skip a run of digits, returning its length.
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.data.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.data.get(self.pos), Some(b' ' | b'\n' | b'\t' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+}
+
+/// Index of the first byte from `pos` that ends a run of plain string
+/// content: a quote, a backslash or a control character.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+fn skip_plain(data: &[u8], pos: usize) -> usize {
+    sse2::skip_plain(data, pos)
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+fn skip_plain(data: &[u8], pos: usize) -> usize {
+    simd128::skip_plain(data, pos)
+}
+
+#[cfg(not(any(
+    all(feature = "simd", target_arch = "x86_64"),
+    all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")
+)))]
+fn skip_plain(data: &[u8], pos: usize) -> usize {
+    skip_plain_scalar(data, pos)
+}
+
+fn skip_plain_scalar(data: &[u8], pos: usize) -> usize {
+    data[pos..]
+        .iter()
+        .position(|&b| b == b'"' || b == b'\\' || b < 0x20)
+        .map_or(data.len(), |offset| pos + offset)
+}
+
+#[cfg(all(target_arch = "x86_64", any(feature = "simd", test)))]
+mod sse2 {
+    use std::arch::x86_64::{
+        __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_min_epu8, _mm_movemask_epi8, _mm_or_si128,
+        _mm_set1_epi8,
+    };
+
+    pub(super) fn skip_plain(data: &[u8], mut pos: usize) -> usize {
+        // SAFETY: SSE2 is part of the x86_64 baseline, and every load reads
+        // 16 bytes within `data`.
+        unsafe {
+            let quote = _mm_set1_epi8(b'"' as i8);
+            let backslash = _mm_set1_epi8(b'\\' as i8);
+            let control = _mm_set1_epi8(0x1F);
+            while pos + 16 <= data.len() {
+                let chunk = _mm_loadu_si128(data.as_ptr().add(pos) as *const __m128i);
+                let stops = _mm_or_si128(
+                    _mm_or_si128(_mm_cmpeq_epi8(chunk, quote), _mm_cmpeq_epi8(chunk, backslash)),
+                    // Bytes up to 0x1F are left unchanged by the unsigned minimum
+                    _mm_cmpeq_epi8(_mm_min_epu8(chunk, control), chunk),
+                );
+                let mask = _mm_movemask_epi8(stops);
+                if mask != 0 {
+                    return pos + mask.trailing_zeros() as usize;
+                }
+                pos += 16;
+            }
+        }
+        super::skip_plain_scalar(data, pos)
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod simd128 {
+    use std::arch::wasm32::{u8x16_bitmask, u8x16_eq, u8x16_lt, u8x16_splat, v128, v128_load, v128_or};
+
+    pub(super) fn skip_plain(data: &[u8], mut pos: usize) -> usize {
+        let quote = u8x16_splat(b'"');
+        let backslash = u8x16_splat(b'\\');
+        let control_end = u8x16_splat(0x20);
+        while pos + 16 <= data.len() {
+            // SAFETY: the load reads 16 bytes within `data`; wasm loads may be unaligned
+            let chunk = unsafe { v128_load(data.as_ptr().add(pos) as *const v128) };
+            let stops = v128_or(
+                v128_or(u8x16_eq(chunk, quote), u8x16_eq(chunk, backslash)),
+                u8x16_lt(chunk, control_end),
+            );
+            let mask = u8x16_bitmask(stops);
+            if mask != 0 {
+                return pos + mask.trailing_zeros() as usize;
+            }
+            pos += 16;
+        }
+        super::skip_plain_scalar(data, pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_only_what_serde_json_parses() {
+        let documents: &[&[u8]] = &[
+            br#" {"a": [1, -2.5, 0, true, null, {"b": "x\"\\\/\n\u00e9"}], "c": {}} "#,
+            b"[]",
+            b"\"caf\xC3\xA9\"",
+            b"01",
+            b"1.",
+            b"-",
+            b"[1,]",
+            b"{\"a\":1,}",
+            b"{\"a\" 1}",
+            b"{1:2}",
+            b"[1] [2]",
+            b"\"tab\there\"",
+            b"\"\\x\"",
+            b"\"\xFF\"",
+            b"tru",
+            b"\"unterminated",
+            b"1e999",
+            b"\"\\ud800\"",
+        ];
+        for document in documents {
+            let serde = serde_json::from_slice::<serde_json::Value>(document).is_ok();
+            if is_valid(document) {
+                assert!(serde, "{}", String::from_utf8_lossy(document));
+            }
+        }
+        assert!(is_valid(documents[0]));
+        assert!(!is_valid(b"1e999"));
+
+        let deep = [vec![b'['; 127], vec![b']'; 127]].concat();
+        assert!(is_valid(&deep));
+        let deeper = [vec![b'['; 128], vec![b']'; 128]].concat();
+        assert!(!is_valid(&deeper));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn sse2_scan_matches_scalar_scan() {
+        let mut data = b"plain text that runs past sixteen bytes ".repeat(3);
+        for stop in [b'"', b'\\', b'\n', 0x1F] {
+            for at in [0, 15, 16, 40, data.len() - 1] {
+                let mut data = data.clone();
+                data[at] = stop;
+                for pos in [0, 1, 17] {
+                    assert_eq!(sse2::skip_plain(&data, pos), skip_plain_scalar(&data, pos));
+                }
+            }
+        }
+        data.extend_from_slice("é€".as_bytes());
+        assert_eq!(sse2::skip_plain(&data, 0), data.len());
+    }
+}
@@ -1,18 +1,63 @@
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
-use log::{debug, info};
+use log::debug;
 
 mod error;
 mod stats;
 mod json_parser;
+mod json_validate;
 mod ndjson_parser;
+mod ndjson_writer;
+mod number_format;
 mod csv_parser;
 mod buffer_pool;
 mod csv_writer;
 mod xml_parser;
 mod format;
+mod builder;
 mod timing;
-mod detect;
+#[macro_use]
+mod spans;
+#[cfg(feature = "wasm")]
+mod logging;
+// Most sample detectors only back the JS `detect*` exports
+pub mod detect;
 mod transform;
+mod dedupe;
+mod sampling;
+mod projection;
+mod sort;
+mod sort_index;
+mod chunking;
+mod manifest;
+mod demux;
+mod group_batch;
+#[cfg(not(target_arch = "wasm32"))]
+mod follow;
+mod query;
+mod search;
+mod trace;
+mod limits;
+mod compression;
+mod utf8;
+mod record_errors;
+mod detection_check;
+mod warnings;
+mod sink;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod web_stream;
+#[cfg(feature = "threads")]
+mod parallel;
+#[cfg(feature = "parquet")]
+mod parquet_writer;
+#[cfg(feature = "xlsx")]
+mod xlsx_parser;
+#[cfg(feature = "yaml")]
+mod yaml_parser;
+#[cfg(feature = "yaml")]
+mod yaml_writer;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 // WASM roundtrip tests moved into integration_tests below
 
@@ -32,34 +77,95 @@ mod timing_tests;
 mod stats_tests;
 #[cfg(test)]
 mod converter_tests;
+#[cfg(test)]
+mod golden_tests;
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod chunk_boundary_tests;
 
 pub use error::{ConvertError, Result};
-pub use stats::Stats;
-pub use format::{Format, ConverterConfig};
-pub use csv_parser::CsvConfig;
-pub use xml_parser::XmlConfig;
+pub use stats::{PushInfo, Stats};
+pub use format::{AutoDetect, Format, ConverterConfig};
+pub use builder::{ConverterBuilder, Unset};
+pub use detect::{Detection, DetectionResult};
+pub use csv_parser::{ColumnType, CsvColumn, CsvConfig, CsvSchema, EmptyField};
+pub use csv_writer::{CsvWriterConfig, FlattenCollisionPolicy, QuoteStyle};
+pub use json_parser::JsonInputConfig;
+pub use ndjson_parser::JsonOutputConfig;
+pub use ndjson_writer::{LineEnding, NdjsonOutputConfig};
+pub use number_format::{FloatFormat, FloatStyle, IntegerStrings};
+pub use xml_parser::{NamespacePolicy, XmlConfig, XmlOutputConfig};
 pub use xml_parser::XmlParser;
-pub use transform::{TransformConfigInput, TransformPlan};
-
-use ndjson_parser::NdjsonParser;
+pub use query::RecordQuery;
+pub use search::RecordFinder;
+pub use limits::ResourceLimits;
+pub use compression::Compression;
+pub use utf8::InvalidUtf8Policy;
+pub use record_errors::{RecordError, RecordErrorPolicy, RecordErrors, MAX_COLLECTED_ERRORS};
+pub use detection_check::DetectionCheck;
+pub use warnings::{Warning, WarningCode, Warnings};
+pub use sink::OutputSink;
+pub use trace::{TraceEntry, TraceEvent, TraceLog, DEFAULT_TRACE_CAPACITY};
+#[cfg(feature = "parquet")]
+pub use parquet_writer::{ParquetCompression, ParquetConfig, ParquetWriter, DEFAULT_ROW_GROUP_SIZE};
+#[cfg(feature = "xlsx")]
+pub use xlsx_parser::{XlsxConfig, XlsxParser};
+#[cfg(feature = "yaml")]
+pub use yaml_parser::YamlParser;
+#[cfg(feature = "yaml")]
+pub use yaml_writer::YamlWriter;
+pub use demux::{DemuxConfigInput, DemuxEngine, DemuxResult, UnmatchedPolicy};
+pub use group_batch::{GroupBatch, GroupBatcher};
+pub use dedupe::{DedupeConfig, DedupeKeep};
+pub use projection::{FieldProjection, OutputColumn};
+pub use sort::{SortConfig, SortOrder, DEFAULT_SORT_MEMORY_BYTES};
+pub use chunking::ChunkingConfig;
+pub use manifest::{Checksum, ConversionManifest};
+#[cfg(not(target_arch = "wasm32"))]
+pub use follow::Follow;
+pub use transform::{
+    BinaryOutput, ExpressionFunction, FilterInput, FunctionRegistry, TransformConfigInput,
+    TransformPlan, TransformPlanConfig, TRANSFORM_PLAN_VERSION,
+};
+
+use ndjson_parser::{JsonPrettifier, NdjsonParser};
 use csv_parser::CsvParser;
-use json_parser::JsonParser;
+use json_parser::{JsonRecordReader, JsonValidator};
+#[cfg(feature = "wasm")]
 use js_sys::{Array, Object, Reflect};
 use transform::TransformEngine;
+use limits::LimitGuard;
+use compression::{Compressor, Decompressor};
+use utf8::Utf8Filter;
+use number_format::NumberRewriter;
+use projection::NdjsonColumns;
+use csv_writer::ColumnKinds;
+use detection_check::{Correction, DetectionWatch, Verdict};
+use sort_index::SortIndex;
+use chunking::Chunker;
+use manifest::{ManifestCounts, ManifestRecorder};
+use std::collections::VecDeque;
 
 // WASM threading support for Node.js only
 #[cfg(all(target_arch = "wasm32", feature = "threads-nodejs"))]
 use wasm_bindgen_rayon::init_thread_pool;
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 use serde::de::DeserializeOwned;
-#[cfg(target_arch = "wasm32")]
 use serde::Deserialize;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+use serde::Serialize;
 
+/// Set up panic messages and logging. `log_sink`, when given, receives every
+/// log record as `(level, message, target)` instead of the console.
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
-pub fn init(debug_enabled: bool) {
+pub fn init(debug_enabled: bool, log_sink: Option<js_sys::Function>) {
     console_error_panic_hook::set_once();
 
+    if log_sink.is_some() {
+        logging::set_sink(log_sink);
+    }
+
     // Only initialize logging on first call
     static INIT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
     
@@ -71,40 +177,61 @@ pub fn init(debug_enabled: bool) {
     ).is_ok() {
         // First call - actually initialize
         if debug_enabled {
-            let _ = console_log::init_with_level(log::Level::Debug);
+            logging::install(log::LevelFilter::Debug);
             debug!("convert-buddy: debug logging enabled");
         } else {
-            let _ = console_log::init_with_level(log::Level::Info);
-            info!("convert-buddy: logging initialized");
+            logging::install(log::LevelFilter::Info);
+            log::info!("convert-buddy: logging initialized");
         }
         
         // Initialize rayon for native/Node.js environments
         #[cfg(all(feature = "threads", not(target_arch = "wasm32")))]
         {
-            info!("Rayon thread pool available for native processing");
+            log::info!("Rayon thread pool available for native processing");
         }
     }
 }
 
+/// Send log records to `sink` as `(level, message, target)`, or back to the
+/// console when `sink` is null.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = setLogSink)]
+pub fn set_log_sink(sink: Option<js_sys::Function>) {
+    logging::set_sink(sink);
+}
+
 /// Check if SIMD is enabled in this build.
-#[wasm_bindgen(js_name = getSimdEnabled)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = getSimdEnabled))]
 pub fn get_simd_enabled() -> bool {
     cfg!(feature = "simd")
 }
 
 /// Check if threading is enabled in this build.
-#[wasm_bindgen(js_name = getThreadingEnabled)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = getThreadingEnabled))]
 pub fn get_threading_enabled() -> bool {
     cfg!(feature = "threads")
 }
 
+/// Limit the threads every converter may use in its parallel paths, unless it
+/// sets its own `threads` option; 0 removes the limit. Returns whether this
+/// build is multi-threaded, i.e. whether the limit has any effect.
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = setThreadPoolSize))]
+pub fn set_thread_pool_size(threads: usize) -> bool {
+    #[cfg(feature = "threads")]
+    parallel::set_default_threads(threads);
+    #[cfg(not(feature = "threads"))]
+    let _ = threads;
+    cfg!(feature = "threads")
+}
+
 /// Detect the input format from a sample of bytes.
-#[wasm_bindgen(js_name = detectFormat)]
+#[cfg_attr(feature = "wasm", wasm_bindgen(js_name = detectFormat))]
 pub fn detect_format(sample: &[u8]) -> Option<String> {
     detect::detect_format(sample).map(|format| format.to_string_js())
 }
 
 /// Detect CSV fields and delimiter from a sample of bytes.
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(js_name = detectCsvFields)]
 pub fn detect_csv_fields(sample: &[u8]) -> JsValue {
     let Some(detection) = detect::detect_csv(sample) else {
@@ -120,11 +247,66 @@ pub fn detect_csv_fields(sample: &[u8]) -> JsValue {
 
     let _ = Reflect::set(&result, &JsValue::from("delimiter"), &JsValue::from(delimiter));
     let _ = Reflect::set(&result, &JsValue::from("fields"), &fields);
+    let quote = char::from(detection.quote).to_string();
+    let _ = Reflect::set(&result, &JsValue::from("quote"), &JsValue::from(quote));
+    let _ = Reflect::set(&result, &JsValue::from("hasHeaders"), &JsValue::from(detection.has_headers));
+
+    result.into()
+}
+
+/// Read only the header row of a CSV or TSV sample, taking `delimiter`,
+/// `quote` and `hasHeaders` from `config` (a delimiter left out is detected
+/// from the row). Returns `{ delimiter, fields: [{ name, index, start, end }] }`
+/// with byte offsets into the sample, or `null`.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen(js_name = extractHeader)]
+pub fn extract_header(sample: &[u8], config: JsValue) -> JsValue {
+    let config: Option<CsvConfigInput> = deserialize_optional(config);
+    let first_byte = |value: Option<&String>| value.and_then(|value| value.as_bytes().first().copied());
+    let delimiter = first_byte(config.as_ref().and_then(|c| c.delimiter.as_ref()));
+    let quote = first_byte(config.as_ref().and_then(|c| c.quote.as_ref())).unwrap_or(b'"');
+    let has_headers = config.as_ref().and_then(|c| c.has_headers).unwrap_or(true);
+    let Some(header) = detect::extract_header(sample, delimiter, quote, has_headers) else {
+        return JsValue::NULL;
+    };
+
+    let result = Object::new();
+    let delimiter = char::from(header.delimiter).to_string();
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+    let fields = serde::Serialize::serialize(&header.fields, &serializer).unwrap_or(JsValue::NULL);
+    let _ = Reflect::set(&result, &JsValue::from("delimiter"), &JsValue::from(delimiter));
+    let _ = Reflect::set(&result, &JsValue::from("fields"), &fields);
+
+    result.into()
+}
+
+/// Extrapolate how many records an input of `totalBytes` holds from its
+/// first bytes, without converting them. Returns `{ format, records,
+/// sampleRecords, averageRecordBytes, exact }`, or `null`.
+#[cfg(feature = "wasm")]
+#[wasm_bindgen(js_name = estimateRecords)]
+pub fn estimate_records(sample: &[u8], total_bytes: f64, format_hint: Option<String>) -> JsValue {
+    let format = format_hint.as_deref().and_then(Format::from_string);
+    let Some(estimate) = detect::estimate_records(sample, total_bytes as u64, format) else {
+        return JsValue::NULL;
+    };
+
+    let result = Object::new();
+    let _ = Reflect::set(&result, &JsValue::from("format"), &JsValue::from(estimate.format.to_string_js()));
+    let _ = Reflect::set(&result, &JsValue::from("records"), &JsValue::from(estimate.records as f64));
+    let _ = Reflect::set(&result, &JsValue::from("sampleRecords"), &JsValue::from(estimate.sample_records as f64));
+    let _ = Reflect::set(
+        &result,
+        &JsValue::from("averageRecordBytes"),
+        &JsValue::from(estimate.average_record_bytes),
+    );
+    let _ = Reflect::set(&result, &JsValue::from("exact"), &JsValue::from(estimate.exact));
 
     result.into()
 }
 
 /// Detect XML elements from a sample of bytes.
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(js_name = detectXmlElements)]
 pub fn detect_xml_elements(sample: &[u8]) -> JsValue {
     let Some(detection) = detect::detect_xml(sample) else {
@@ -146,6 +328,7 @@ pub fn detect_xml_elements(sample: &[u8]) -> JsValue {
 }
 
 /// Detect JSON fields from a sample of bytes.
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(js_name = detectJsonFields)]
 pub fn detect_json_fields(sample: &[u8]) -> JsValue {
     let Some(detection) = detect::detect_json(sample) else {
@@ -164,6 +347,7 @@ pub fn detect_json_fields(sample: &[u8]) -> JsValue {
 }
 
 /// Detect NDJSON fields from a sample of bytes.
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(js_name = detectNdjsonFields)]
 pub fn detect_ndjson_fields(sample: &[u8]) -> JsValue {
     let Some(detection) = detect::detect_ndjson(sample) else {
@@ -182,10 +366,12 @@ pub fn detect_ndjson_fields(sample: &[u8]) -> JsValue {
 }
 
 /// Detect structure (fields/elements) for any format
+#[cfg(feature = "wasm")]
 #[wasm_bindgen(js_name = detectStructure)]
 pub fn detect_structure(sample: &[u8], format_hint: Option<String>) -> JsValue {
     let format = format_hint.and_then(|f| match f.as_str() {
         "csv" => Some(Format::Csv),
+        "tsv" => Some(Format::Tsv),
         "xml" => Some(Format::Xml),
         "json" => Some(Format::Json),
         "ndjson" => Some(Format::Ndjson),
@@ -247,19 +433,51 @@ enum ConverterState {
     XmlToCsvTransform(XmlParser, TransformEngine, csv_writer::CsvWriter),
     XmlPassthrough(XmlParser),
     XmlToXmlTransform(XmlParser, TransformEngine, xml_parser::XmlWriter),
-    JsonPassthrough(JsonParser),
-    JsonToJsonTransform(JsonParser, TransformEngine, NdjsonParser, bool),
-    JsonToNdjson(JsonParser), // JSON array to NDJSON
-    JsonToNdjsonTransform(JsonParser, TransformEngine),
-    JsonToCsv(JsonParser, csv_writer::CsvWriter),
-    JsonToCsvTransform(JsonParser, TransformEngine, csv_writer::CsvWriter),
-    JsonToXml(JsonParser, xml_parser::XmlWriter),
-    JsonToXmlTransform(JsonParser, TransformEngine, xml_parser::XmlWriter),
+    JsonPassthrough(JsonValidator, Option<JsonPrettifier>),
+    JsonToJson(JsonRecordReader, NdjsonParser, bool), // records taken from `recordsPath`
+    JsonToJsonTransform(JsonRecordReader, TransformEngine, NdjsonParser, bool),
+    JsonToNdjson(JsonRecordReader), // JSON array to NDJSON
+    JsonToNdjsonTransform(JsonRecordReader, TransformEngine),
+    JsonToCsv(JsonRecordReader, csv_writer::CsvWriter),
+    JsonToCsvTransform(JsonRecordReader, TransformEngine, csv_writer::CsvWriter),
+    JsonToXml(JsonRecordReader, xml_parser::XmlWriter),
+    JsonToXmlTransform(JsonRecordReader, TransformEngine, xml_parser::XmlWriter),
     NeedsDetection(Vec<u8>), // Buffered first chunk for auto-detection
+    /// Any input converted to NDJSON by the inner state, then encoded as Parquet
+    #[cfg(feature = "parquet")]
+    ToParquet(Box<ConverterState>, parquet_writer::ParquetWriter),
+    /// Workbook buffered until `finish`, then its rows fed as NDJSON to the inner state
+    #[cfg(feature = "xlsx")]
+    FromXlsx(XlsxParser, Box<ConverterState>),
+    /// YAML records parsed to NDJSON and fed to the inner state as they complete
+    #[cfg(feature = "yaml")]
+    FromYaml(YamlParser, Box<ConverterState>),
+    /// Any input converted to NDJSON by the inner state, then written as YAML
+    #[cfg(feature = "yaml")]
+    ToYaml(Box<ConverterState>, YamlWriter),
+    /// A format pairing that cannot be converted; every call reports the reason
+    #[cfg(any(feature = "parquet", feature = "xlsx"))]
+    Unsupported(String),
+    /// Built from a config that failed [`ConverterConfig::validate`]; every
+    /// call reports the reason
+    Invalid(String),
+    /// `finish` ran, but output held back by `max_output_bytes` is still
+    /// waiting; each further `finish` returns the next part of it
+    Draining,
+    /// `finish` returned the last output; further calls fail with
+    /// [`ConvertError::AlreadyFinished`]
+    Finished,
+}
+
+/// Error for a converter whose state was dropped by an earlier error.
+fn failed_converter() -> ConvertError {
+    ConvertError::InvalidConfig("Converter stopped after an earlier error".to_string())
 }
 
 fn converter_state_name(state: &ConverterState) -> &'static str {
     match state {
+        ConverterState::Draining => "Draining",
+        ConverterState::Finished => "Finished",
         ConverterState::CsvPassthrough(_, _) => "CsvPassthrough",
         ConverterState::CsvToNdjson(_) => "CsvToNdjson",
         ConverterState::CsvToNdjsonTransform(_, _) => "CsvToNdjsonTransform",
@@ -284,7 +502,8 @@ fn converter_state_name(state: &ConverterState) -> &'static str {
         ConverterState::XmlToCsvTransform(_, _, _) => "XmlToCsvTransform",
         ConverterState::XmlPassthrough(_) => "XmlPassthrough",
         ConverterState::XmlToXmlTransform(_, _, _) => "XmlToXmlTransform",
-        ConverterState::JsonPassthrough(_) => "JsonPassthrough",
+        ConverterState::JsonPassthrough(_, _) => "JsonPassthrough",
+        ConverterState::JsonToJson(_, _, _) => "JsonToJson",
         ConverterState::JsonToJsonTransform(_, _, _, _) => "JsonToJsonTransform",
         ConverterState::JsonToNdjson(_) => "JsonToNdjson",
         ConverterState::JsonToNdjsonTransform(_, _) => "JsonToNdjsonTransform",
@@ -293,20 +512,90 @@ fn converter_state_name(state: &ConverterState) -> &'static str {
         ConverterState::JsonToXml(_, _) => "JsonToXml",
         ConverterState::JsonToXmlTransform(_, _, _) => "JsonToXmlTransform",
         ConverterState::NeedsDetection(_) => "NeedsDetection",
+        #[cfg(feature = "parquet")]
+        ConverterState::ToParquet(_, _) => "ToParquet",
+        #[cfg(feature = "xlsx")]
+        ConverterState::FromXlsx(_, _) => "FromXlsx",
+        #[cfg(feature = "yaml")]
+        ConverterState::FromYaml(_, _) => "FromYaml",
+        #[cfg(feature = "yaml")]
+        ConverterState::ToYaml(_, _) => "ToYaml",
+        #[cfg(any(feature = "parquet", feature = "xlsx"))]
+        ConverterState::Unsupported(_) => "Unsupported",
+        ConverterState::Invalid(_) => "Invalid",
     }
 }
 
+/// Error returned by [`Converter::push`] and [`Converter::finish`]: a `JsValue`
+/// holding the message with the `wasm` feature, the [`ConvertError`] without it.
+#[cfg(feature = "wasm")]
+pub type BindingError = JsValue;
+#[cfg(not(feature = "wasm"))]
+pub type BindingError = ConvertError;
+
 /// A streaming converter state machine.
 /// Converts between CSV, NDJSON, JSON, and XML formats with high performance.
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub struct Converter {
     debug: bool,
     config: ConverterConfig,
     state: Option<ConverterState>,
     stats: Stats,
+    /// Input record that matched `transform.stopWhen`; once set, further input is ignored.
+    stop_match: Option<serde_json::Value>,
+    /// Set once `max_records` records are written; further input is ignored too.
+    records_done: bool,
+    /// Records parsed, whether or not `stats` are enabled.
+    records_parsed: u64,
+    /// Records the transform stage wrote, once there is one; these are the
+    /// records that reach the writer instead of `records_parsed`.
+    records_transformed: Option<u64>,
+    /// Created on first output when `ndjson_output` framing is configured.
+    ndjson_writer: Option<ndjson_writer::NdjsonWriter>,
+    /// Created on first NDJSON output when `output_columns` is set.
+    ndjson_columns: Option<NdjsonColumns>,
+    /// Created on first JSON or NDJSON output when `float_format` or
+    /// `integer_strings` is set.
+    number_rewriter: Option<NumberRewriter>,
+    /// Output held back by `max_output_bytes`, returned by `drain()`.
+    pending_output: VecDeque<u8>,
+    /// Output `push` holds until `min_output_bytes` of it are ready; always
+    /// older than `pending_output`.
+    batched_output: Vec<u8>,
+    /// When input last arrived, if output may be held back since; used by
+    /// `poll_idle()` when `config.idle_flush` is set.
+    idle_since_ms: Option<f64>,
+    /// Created on the first event when `trace_capacity` is set.
+    trace_log: Option<TraceLog>,
+    /// Running totals checked against `config.limits`.
+    limit_guard: LimitGuard,
+    /// Inflates compressed input before it reaches the pipeline.
+    decompressor: Decompressor,
+    /// Applies `config.invalid_utf8` to the inflated input.
+    utf8_filter: Utf8Filter,
+    /// Runs `config.detection_check` until the start of the input is judged.
+    detection_watch: Option<DetectionWatch>,
+    /// Compresses output when `output_compression` is set; dropped once finished.
+    compressor: Option<Compressor>,
+    /// Indexes the sorted output when `sort.index` is set, for `take_index()`.
+    sort_index: Option<SortIndex>,
+    /// Cuts the returned output into chunks when `chunking` is set, for `take_chunks()`.
+    chunker: Option<Chunker>,
+    /// Checksums the input and output when `manifest` is set, for `manifest()`.
+    manifest: Option<ManifestRecorder>,
+    /// Settings detected from the first chunk, returned by `getDetectionResult()`.
+    detection: Option<DetectionResult>,
+    /// Non-fatal problems reported by the pipeline, returned by `getWarnings()`.
+    warnings: Warnings,
+    /// Records dropped under `config.on_record_error`, returned by `getErrors()`.
+    record_errors: RecordErrors,
+    /// Kinds of value the CSV writer wrote per column, for `csv_schema()`.
+    column_kinds: ColumnKinds,
+    /// Takes the output in place of the caller when set with `with_sink()`.
+    sink: Option<Box<dyn OutputSink>>,
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct CsvConfigInput {
@@ -314,38 +603,91 @@ struct CsvConfigInput {
     quote: Option<String>,
     has_headers: Option<bool>,
     trim_whitespace: Option<bool>,
+    infer_types: Option<bool>,
+    column_types: Option<std::collections::HashMap<String, ColumnType>>,
+    columns: Option<Vec<CsvColumn>>,
+    unflatten: Option<bool>,
+    skip_rows: Option<usize>,
+    header_row: Option<usize>,
+    max_rows: Option<usize>,
+    comment_char: Option<String>,
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct XmlConfigInput {
-    record_element: Option<String>,
+    record_element: Option<RecordElementInput>,
+    record_path: Option<String>,
+    tag_element: Option<bool>,
+    namespaces: Option<NamespacePolicy>,
     trim_text: Option<bool>,
     include_attributes: Option<bool>,
     expand_entities: Option<bool>,
 }
 
-#[wasm_bindgen]
+/// `xmlConfig.recordElement`: one element or path, or several.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RecordElementInput {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// The `options` object of `withConfig`, applied by [`ConverterConfig::with_options`].
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConverterOptionsInput {
+    flatten_collision: Option<String>,
+    ndjson_output: Option<NdjsonOutputConfig>,
+    csv_output: Option<CsvWriterConfig>,
+    xml_output: Option<XmlOutputConfig>,
+    json_input: Option<JsonInputConfig>,
+    json_output: Option<JsonOutputConfig>,
+    float_format: Option<FloatFormat>,
+    integer_strings: Option<IntegerStrings>,
+    max_output_bytes: Option<usize>,
+    min_output_bytes: Option<usize>,
+    trace: Option<bool>,
+    trace_capacity: Option<usize>,
+    ordered: Option<bool>,
+    threads: Option<usize>,
+    limits: Option<ResourceLimits>,
+    input_compression: Option<Compression>,
+    output_compression: Option<Compression>,
+    invalid_utf8: Option<InvalidUtf8Policy>,
+    on_record_error: Option<RecordErrorPolicy>,
+    keep_rejects: Option<bool>,
+    select_fields: Option<Vec<String>>,
+    drop_fields: Option<Vec<String>>,
+    dedupe: Option<DedupeConfig>,
+    sort: Option<SortConfig>,
+    max_records: Option<usize>,
+    sample_every_n: Option<usize>,
+    output_columns: Option<Vec<OutputColumn>>,
+    chunking: Option<ChunkingConfig>,
+    manifest: Option<bool>,
+    detection_check: Option<DetectionCheck>,
+    #[cfg(feature = "parquet")]
+    parquet: Option<ParquetConfig>,
+    #[cfg(feature = "xlsx")]
+    xlsx: Option<XlsxConfig>,
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Converter {
-    #[wasm_bindgen(constructor)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(constructor))]
     pub fn new(debug: bool) -> Converter {
         if debug {
             debug!("Converter::new(debug=true)");
         }
         
-        let config = ConverterConfig::default();
-        let state = Self::create_state(&config);
-        
-        Converter {
-            debug,
-            config,
-            state: Some(state),
-            stats: Stats::default(),
-        }
+        Self::build(debug, ConverterConfig::default())
     }
 
     /// Create a new converter with specific configuration
+    #[cfg(feature = "wasm")]
     #[wasm_bindgen(js_name = withConfig)]
     pub fn with_config(
         debug: bool,
@@ -356,10 +698,11 @@ impl Converter {
         csv_config: JsValue,
         xml_config: JsValue,
         transform_config: JsValue,
+        options: JsValue,
     ) -> std::result::Result<Converter, JsValue> {
         #[cfg(not(target_arch = "wasm32"))]
         {
-            let _ = (csv_config, xml_config, transform_config);
+            let _ = (csv_config, xml_config, transform_config, options);
             let input = Format::from_string(input_format)
                 .ok_or_else(|| ConvertError::InvalidConfig(format!("Invalid input format: {}", input_format)))?;
             let output = Format::from_string(output_format)
@@ -369,87 +712,144 @@ impl Converter {
                 .with_chunk_size(chunk_target_bytes)
                 .with_stats(enable_stats);
 
-            let state = Self::create_state(&config);
-
-            return Ok(Converter {
-                debug,
-                config,
-                state: Some(state),
-                stats: Stats::default(),
-            });
+            return Ok(Self::build(debug, config));
         }
 
         #[cfg(target_arch = "wasm32")]
         {
-        let input = Format::from_string(input_format)
-            .ok_or_else(|| ConvertError::InvalidConfig(format!("Invalid input format: {}", input_format)))?;
-        
-        let output = Format::from_string(output_format)
-            .ok_or_else(|| ConvertError::InvalidConfig(format!("Invalid output format: {}", output_format)))?;
+        let config = wasm_converter_config(
+            input_format,
+            output_format,
+            chunk_target_bytes,
+            enable_stats,
+            csv_config,
+            xml_config,
+            transform_config,
+            options,
+        )?;
+        let (input, output) = (config.input_format, config.output_format);
+
+        if debug && config.auto_detect.applies_to(input) {
+            debug!("Converter will auto-detect config on first chunk");
+        }
+        if debug {
+            debug!("Converter::with_config({:?} -> {:?})", input, output);
+        }
+
+        Ok(Self::build(debug, config))
+        }
+    }
 
-        let mut config = ConverterConfig::new(input, output)
-            .with_chunk_size(chunk_target_bytes)
-            .with_stats(enable_stats);
+    /// Push a chunk of bytes. Returns converted output bytes for that chunk.
+    // `BindingError` is `ConvertError` itself without the `wasm` feature
+    #[cfg_attr(not(feature = "wasm"), allow(clippy::useless_conversion))]
+    pub fn push(&mut self, chunk: &[u8]) -> std::result::Result<Vec<u8>, BindingError> {
+        self.push_bytes(chunk).map_err(BindingError::from)
+    }
+
+    /// Finish the stream and return any remaining buffered output.
+    // `BindingError` is `ConvertError` itself without the `wasm` feature
+    #[cfg_attr(not(feature = "wasm"), allow(clippy::useless_conversion))]
+    pub fn finish(&mut self) -> std::result::Result<Vec<u8>, BindingError> {
+        self.finish_stream().map_err(BindingError::from)
+    }
+
+    /// Push a chunk of bytes and copy the output into `output` instead of
+    /// returning a new buffer. Returns the number of bytes written; output
+    /// that doesn't fit waits for `drainInto()` or `drain()`.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = pushInto))]
+    #[cfg_attr(not(feature = "wasm"), allow(clippy::useless_conversion))]
+    pub fn push_into_slice(&mut self, chunk: &[u8], output: &mut [u8]) -> std::result::Result<usize, BindingError> {
+        let result = self.push_bytes(chunk).map_err(BindingError::from)?;
+        Ok(self.write_output(result, output))
+    }
 
-        let csv_provided = parse_csv_config(csv_config.clone());
-        let xml_provided = parse_xml_config(xml_config.clone());
-        let transform_provided = parse_transform_config(transform_config.clone())?;
+    /// Finish the stream, copying the remaining output into `output` like
+    /// `pushInto()`.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = finishInto))]
+    #[cfg_attr(not(feature = "wasm"), allow(clippy::useless_conversion))]
+    pub fn finish_into_slice(&mut self, output: &mut [u8]) -> std::result::Result<usize, BindingError> {
+        let result = self.finish_stream().map_err(BindingError::from)?;
+        let written = self.write_output(result, output);
+        self.settle_finish();
+        Ok(written)
+    }
 
-        if let Some(csv) = csv_provided.clone() {
-            config = config.with_csv_config(csv);
+    /// Copy output waiting for `drain()` into `output`, returning the number
+    /// of bytes written. 0 once everything has been returned.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = drainInto))]
+    pub fn drain_into_slice(&mut self, output: &mut [u8]) -> usize {
+        let written = output.len().min(self.pending_output.len());
+        for (slot, byte) in output.iter_mut().zip(self.pending_output.drain(..written)) {
+            *slot = byte;
         }
+        self.settle_finish();
+        written
+    }
 
-        if let Some(xml) = xml_provided.clone() {
-            config = config.with_xml_config(xml);
+    pub(crate) fn push_bytes(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        let _span = stage_span!(INFO, "push", state = self.state_name(), input_bytes = chunk.len());
+        if self.debug {
+            debug!("Converter::push chunk_len={}", chunk.len());
+        }
+        if self.finish_called() {
+            return Err(ConvertError::AlreadyFinished);
         }
 
-        if let Some(transform) = transform_provided.clone() {
-            config = config.with_transform(transform);
+        if let Some(manifest) = self.manifest.as_mut() {
+            manifest.input(chunk);
+        }
+        if self.is_stopped() {
+            return Ok(Vec::new());
+        }
+        if self.config.idle_flush.is_some() {
+            self.idle_since_ms = Some(crate::timing::now_ms());
         }
 
-        // Determine if we need auto-detection
-        let needs_detection = match input {
-            Format::Csv => csv_provided.is_none() || csv_provided.as_ref().and_then(|_c| {
-                // Check if delimiter was explicitly provided via the input
-                let input_obj: Option<CsvConfigInput> = deserialize_optional(csv_config);
-                input_obj.and_then(|i| i.delimiter)
-            }).is_none(),
-            Format::Xml => xml_provided.is_none() || xml_provided.as_ref().and_then(|_x| {
-                // Check if recordElement was explicitly provided
-                let input_obj: Option<XmlConfigInput> = deserialize_optional(xml_config);
-                input_obj.and_then(|i| i.record_element)
-            }).is_none(),
-            _ => false,
+        let data = {
+            let _span = stage_span!(DEBUG, "decompress");
+            match self.decompressor.push(chunk) {
+                Ok(data) => data,
+                Err(err) => return Err(self.fail(err)),
+            }
         };
+        if data.is_empty() && !chunk.is_empty() {
+            // Still collecting enough bytes to recognise a compressed stream
+            return Ok(Vec::new());
+        }
+        if let Err(err) = self.limit_guard.add_input(data.len()) {
+            return Err(self.fail(err));
+        }
+        let data = self.utf8_filter.push(&data, &self.warnings);
 
-        let state = if needs_detection {
-            if debug {
-                debug!("Converter will auto-detect config on first chunk");
-            }
-            ConverterState::NeedsDetection(Vec::new())
+        let output = self.push_chunk(&data)?;
+        let output = self.batch_output(output);
+        self.deliver(output)
+    }
+
+    /// Detect from the input buffered in `NeedsDetection` and convert it.
+    fn settle_detection(&mut self) -> Result<Vec<u8>> {
+        let detection_sample = if let Some(ConverterState::NeedsDetection(buffer)) = self.state.take() {
+            buffer
         } else {
-            Self::create_state(&config)
+            Vec::new()
         };
 
-        if debug {
-            debug!("Converter::with_config({:?} -> {:?})", input, output);
-        }
+        let detection_sample = self.resolve_input_format(detection_sample)?;
+        self.auto_detect_and_initialize(&detection_sample)?;
 
-        Ok(Converter {
-            debug,
-            config,
-            state: Some(state),
-            stats: Stats::default(),
-        })
-        }
+        // Now process the buffered chunk with the newly initialized state
+        self.push_chunk(&detection_sample)
     }
 
-    /// Push a chunk of bytes. Returns converted output bytes for that chunk.
-    pub fn push(&mut self, chunk: &[u8]) -> std::result::Result<Vec<u8>, JsValue> {
-        if self.debug {
-            debug!("Converter::push chunk_len={}", chunk.len());
-        }
+    /// Drop the pipeline after an error, as errors inside it already do.
+    fn fail(&mut self, err: ConvertError) -> ConvertError {
+        self.state = None;
+        err
+    }
 
+    /// Convert a chunk already counted against `maxInputBytes`.
+    fn push_chunk(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
         // Record input stats
         if self.config.enable_stats {
             self.stats.record_chunk(chunk.len());
@@ -462,106 +862,101 @@ impl Converter {
             if let Some(ConverterState::NeedsDetection(ref mut buffer)) = self.state {
                 buffer.extend_from_slice(chunk);
                 
-                // Wait for enough data to detect (at least 256 bytes or until we have some data)
-                if buffer.len() < 256 && !chunk.is_empty() {
+                // Wait for 256 bytes to detect from, or for `finish` with less;
+                // an empty chunk never starts detection
+                let buffered_bytes = buffer.len();
+                if buffered_bytes < 256 {
                     // Need more data for reliable detection
+                    self.trace(|| TraceEvent::Push {
+                        state: "NeedsDetection",
+                        input_bytes: chunk.len(),
+                        output_bytes: 0,
+                        buffered_bytes,
+                        elapsed_ms: 0.0,
+                    });
                     return Ok(Vec::new());
                 }
             }
             
-            // Take the buffer and do detection
-            let detection_sample = if let Some(ConverterState::NeedsDetection(buffer)) = self.state.take() {
-                buffer
-            } else {
-                Vec::new()
-            };
-            
-            self.auto_detect_and_initialize(&detection_sample)?;
-            
-            // Now process the buffered chunk with the newly initialized state
-            return self.push(&detection_sample);
+            return self.settle_detection();
+        }
+
+        if let Some(mut watch) = self.detection_watch.take() {
+            match watch.observe(chunk) {
+                Verdict::Pending => {
+                    let holds_input = watch.holds_input();
+                    self.detection_watch = Some(watch);
+                    if holds_input {
+                        let buffered_bytes = self.buffered_bytes();
+                        self.trace(|| TraceEvent::Push {
+                            state: "DetectionCheck",
+                            input_bytes: chunk.len(),
+                            output_bytes: 0,
+                            buffered_bytes,
+                            elapsed_ms: 0.0,
+                        });
+                        return Ok(Vec::new());
+                    }
+                }
+                verdict => {
+                    if let Some(held) = self.settle_detection_check(watch, verdict) {
+                        return self.push_chunk(&held);
+                    }
+                }
+            }
+        }
+
+        if matches!(self.config.input_format, Format::Json | Format::Ndjson) {
+            if let Err(err) = self.limit_guard.scan_json(chunk) {
+                return Err(self.fail(err));
+            }
         }
 
         let start = crate::timing::Timer::new();
 
         // Handle transformations separately to avoid borrow checker issues
-        let result = self.push_internal(chunk)?;
+        let result = {
+            let _span = stage_span!(DEBUG, "convert", state = self.state_name());
+            self.push_internal(chunk)?
+        };
+        let result = self.map_output_columns(result, false);
+        let result = self.format_numbers(result, false);
+        let result = self.frame_ndjson_output(result);
+        if let Some(index) = self.sort_index.as_mut() {
+            index.write(&result);
+        }
+        let result = self.compress_output(result, false)?;
+        self.note_output(&result, false);
+        if self.config.trace_capacity.is_some() {
+            let (state, buffered_bytes) = (self.state_name(), self.buffered_bytes());
+            let output_bytes = result.len();
+            let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+            self.trace(|| TraceEvent::Push {
+                state,
+                input_bytes: chunk.len(),
+                output_bytes,
+                buffered_bytes,
+                elapsed_ms,
+            });
+        }
         // Record output stats
         if self.config.enable_stats {
             self.stats.record_output(result.len());
             self.stats.record_parse_time(start.elapsed());
             
             // Update buffer sizes
-            let partial_size = match self.state.as_ref() {
-                Some(ConverterState::CsvPassthrough(p, _)) => p.partial_size(),
-                Some(ConverterState::CsvToNdjson(p)) => p.partial_size(),
-                Some(ConverterState::CsvToNdjsonTransform(p, engine)) => {
-                    p.partial_size() + engine.partial_size()
-                }
-                Some(ConverterState::CsvToJson(csv_p, ndjson_p, _)) => {
-                    csv_p.partial_size() + ndjson_p.partial_size()
-                }
-                Some(ConverterState::CsvToJsonTransform(csv_p, engine, ndjson_p, _)) => {
-                    csv_p.partial_size() + engine.partial_size() + ndjson_p.partial_size()
-                }
-                Some(ConverterState::CsvToXml(csv_p, _)) => csv_p.partial_size(),
-                Some(ConverterState::CsvToXmlTransform(csv_p, engine, _)) => {
-                    csv_p.partial_size() + engine.partial_size()
-                }
-                Some(ConverterState::CsvToCsvTransform(csv_p, engine, _)) => {
-                    csv_p.partial_size() + engine.partial_size()
-                }
-                Some(ConverterState::NdjsonPassthrough(p)) => p.partial_size(),
-                Some(ConverterState::NdjsonTransform(engine)) => engine.partial_size(),
-                Some(ConverterState::NdjsonToJson(p, _)) => p.partial_size(),
-                Some(ConverterState::NdjsonToJsonTransform(engine, p, _)) => {
-                    engine.partial_size() + p.partial_size()
-                }
-                Some(ConverterState::NdjsonToCsv(ndjson_p, _)) => ndjson_p.partial_size(),
-                Some(ConverterState::NdjsonToCsvTransform(engine, _)) => engine.partial_size(),
-                Some(ConverterState::NdjsonToXml(ndjson_p, _)) => ndjson_p.partial_size(),
-                Some(ConverterState::NdjsonToXmlTransform(engine, _)) => engine.partial_size(),
-                Some(ConverterState::XmlToNdjson(p)) => p.partial_size(),
-                Some(ConverterState::XmlToNdjsonTransform(p, engine)) => {
-                    p.partial_size() + engine.partial_size()
-                }
-                Some(ConverterState::XmlToJson(xml_p, ndjson_p, _)) => {
-                    xml_p.partial_size() + ndjson_p.partial_size()
-                }
-                Some(ConverterState::XmlToJsonTransform(xml_p, engine, ndjson_p, _)) => {
-                    xml_p.partial_size() + engine.partial_size() + ndjson_p.partial_size()
-                }
-                Some(ConverterState::XmlToCsv(xml_p, _)) => xml_p.partial_size(),
-                Some(ConverterState::XmlToCsvTransform(xml_p, engine, _)) => {
-                    xml_p.partial_size() + engine.partial_size()
-                }
-                Some(ConverterState::XmlPassthrough(p)) => p.partial_size(),
-                Some(ConverterState::XmlToXmlTransform(p, engine, _)) => {
-                    p.partial_size() + engine.partial_size()
-                }
-                Some(ConverterState::JsonToNdjson(_)) => 0,
-                Some(ConverterState::JsonToNdjsonTransform(_, engine)) => engine.partial_size(),
-                Some(ConverterState::JsonToCsv(_, _)) => 0,
-                Some(ConverterState::JsonToCsvTransform(_, engine, _)) => engine.partial_size(),
-                Some(ConverterState::JsonToXml(_, _)) => 0,
-                Some(ConverterState::JsonToXmlTransform(_, engine, _)) => engine.partial_size(),
-                Some(ConverterState::JsonToJsonTransform(_, engine, _, _)) => engine.partial_size(),
-                Some(ConverterState::NeedsDetection(buffer)) => buffer.len(),
-                _ => 0,
-            };
+            let partial_size = self.buffered_bytes();
             self.stats.update_buffer_size(partial_size);
         }
 
-        Ok(result)
+        Ok(self.cap_output(result))
     }
 
-    fn push_internal(&mut self, chunk: &[u8]) -> std::result::Result<Vec<u8>, JsValue> {
+    fn push_internal(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
         // Handle transformations to avoid borrow checker issues
         // We need to take ownership of intermediate data to avoid conflicts
         
-        let state = self.state.take().ok_or_else(|| 
-            JsValue::from(ConvertError::InvalidConfig("Converter already finished".to_string()))
-        )?;
+        let state = self.state.take().ok_or_else(failed_converter)?;
         
         let (result, new_state) = match state {
             ConverterState::CsvPassthrough(mut parser, mut csv_writer) => {
@@ -569,26 +964,26 @@ impl Converter {
                 let ndjson = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON output)
                 let record_count = ndjson.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 // Process each line of NDJSON
                 let ndjson_str = std::str::from_utf8(&ndjson)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut result = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed = line.trim();
                     if !trimmed.is_empty() {
-                        result.extend(csv_writer.process_json_line(line).map_err(JsValue::from)?);
+                        result.extend(csv_writer.process_json_line(line)?);
                     }
                 }
                 (result, ConverterState::CsvPassthrough(parser, csv_writer))
@@ -598,29 +993,29 @@ impl Converter {
                 let ndjson = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 // Apply transform
                 let transformed = self.apply_transform_push(&mut engine, &ndjson)?;
                 
                 // Convert transformed NDJSON to CSV
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut result = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed = line.trim();
                     if !trimmed.is_empty() {
-                        result.extend(csv_writer.process_json_line(line).map_err(JsValue::from)?);
+                        result.extend(csv_writer.process_json_line(line)?);
                     }
                 }
                 
@@ -630,17 +1025,17 @@ impl Converter {
                 let result = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON output)
                 let record_count = result.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 (result, ConverterState::CsvToNdjson(parser))
             }
@@ -648,13 +1043,16 @@ impl Converter {
                 let ndjson_chunk = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
+                let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
+
                 let result = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 (result, ConverterState::CsvToNdjsonTransform(parser, engine))
             }
@@ -662,64 +1060,64 @@ impl Converter {
                 let ndjson_chunk = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 let is_first_chunk = is_first;
                 is_first = false;
-                let result = ndjson_parser.to_json_array(&ndjson_chunk, is_first_chunk, false).map_err(JsValue::from)?;
+                let result = ndjson_parser.to_json_array(&ndjson_chunk, is_first_chunk, false)?;
                 (result, ConverterState::CsvToJson(parser, ndjson_parser, is_first))
             }
             ConverterState::CsvToJsonTransform(mut parser, mut engine, mut ndjson_parser, mut is_first) => {
                 let ndjson_chunk = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 let transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let is_first_chunk = is_first;
                 is_first = false;
-                let result = ndjson_parser.to_json_array(&transformed, is_first_chunk, false).map_err(JsValue::from)?;
+                let result = ndjson_parser.to_json_array(&transformed, is_first_chunk, false)?;
                 (result, ConverterState::CsvToJsonTransform(parser, engine, ndjson_parser, is_first))
             }
             ConverterState::CsvToXml(mut parser, mut xml_writer) => {
                 let ndjson_chunk = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 // Convert NDJSON to XML
                 let ndjson_str = std::str::from_utf8(&ndjson_chunk)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -734,24 +1132,24 @@ impl Converter {
                 let ndjson_chunk = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_to_ndjson_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push_to_ndjson(chunk).map_err(JsValue::from)?
+                        parser.push_to_ndjson(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 // Apply transform
                 let transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 
                 // Convert transformed NDJSON to XML
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -766,88 +1164,94 @@ impl Converter {
                 let result = {
                     #[cfg(feature = "threads")]
                     {
-                        parser.push_parallel(chunk).map_err(JsValue::from)?
+                        parser.push_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        parser.push(chunk).map_err(JsValue::from)?
+                        parser.push(chunk)?
                     }
                 };
                 
                 // Count records for passthrough (count newlines as records)
                 let record_count = chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 (result, ConverterState::NdjsonPassthrough(parser))
             }
             ConverterState::NdjsonTransform(mut engine) => {
+                let record_count = chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
                 let result = self.apply_transform_push(&mut engine, chunk)?;
                 (result, ConverterState::NdjsonTransform(engine))
             }
             ConverterState::NdjsonToJson(mut parser, mut is_first) => {
                 // Count records (newlines in input NDJSON)
                 let record_count = chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 let is_first_chunk = is_first;
                 is_first = false;
-                let result = parser.to_json_array(chunk, is_first_chunk, false).map_err(JsValue::from)?;
+                let result = parser.to_json_array(chunk, is_first_chunk, false)?;
                 (result, ConverterState::NdjsonToJson(parser, is_first))
             }
             ConverterState::NdjsonToJsonTransform(mut engine, mut parser, mut is_first) => {
                 // Count records (newlines in input NDJSON)
                 let record_count = chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 let transformed = self.apply_transform_push(&mut engine, chunk)?;
                 let is_first_chunk = is_first;
                 is_first = false;
-                let result = parser.to_json_array(&transformed, is_first_chunk, false).map_err(JsValue::from)?;
+                let result = parser.to_json_array(&transformed, is_first_chunk, false)?;
                 (result, ConverterState::NdjsonToJsonTransform(engine, parser, is_first))
             }
             ConverterState::XmlToNdjson(mut parser) => {
-                let result = parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let result = parser.push_to_ndjson(chunk)?;
                 
                 // Count records (newlines in NDJSON output)
                 let record_count = result.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 (result, ConverterState::XmlToNdjson(parser))
             }
             ConverterState::XmlToNdjsonTransform(mut parser, mut engine) => {
-                let ndjson_chunk = parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let ndjson_chunk = parser.push_to_ndjson(chunk)?;
+                let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
                 let result = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 (result, ConverterState::XmlToNdjsonTransform(parser, engine))
             }
             ConverterState::XmlToJson(mut xml_parser, mut ndjson_parser, mut is_first) => {
-                let ndjson_chunk = xml_parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let ndjson_chunk = xml_parser.push_to_ndjson(chunk)?;
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 let is_first_chunk = is_first;
                 is_first = false;
-                let result = ndjson_parser.to_json_array(&ndjson_chunk, is_first_chunk, false).map_err(JsValue::from)?;
+                let result = ndjson_parser.to_json_array(&ndjson_chunk, is_first_chunk, false)?;
                 (result, ConverterState::XmlToJson(xml_parser, ndjson_parser, is_first))
             }
             ConverterState::XmlToJsonTransform(mut xml_parser, mut engine, mut ndjson_parser, mut is_first) => {
-                let ndjson_chunk = xml_parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let ndjson_chunk = xml_parser.push_to_ndjson(chunk)?;
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 let transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let is_first_chunk = is_first;
                 is_first = false;
-                let result = ndjson_parser.to_json_array(&transformed, is_first_chunk, false).map_err(JsValue::from)?;
+                let result = ndjson_parser.to_json_array(&transformed, is_first_chunk, false)?;
                 (result, ConverterState::XmlToJsonTransform(xml_parser, engine, ndjson_parser, is_first))
             }
             ConverterState::XmlToCsv(mut xml_parser, mut csv_writer) => {
-                let ndjson_chunk = xml_parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let ndjson_chunk = xml_parser.push_to_ndjson(chunk)?;
+                let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
                 let ndjson_str = std::str::from_utf8(&ndjson_chunk)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -858,13 +1262,15 @@ impl Converter {
                 (output, ConverterState::XmlToCsv(xml_parser, csv_writer))
             }
             ConverterState::XmlToCsvTransform(mut xml_parser, mut engine, mut csv_writer) => {
-                let ndjson_chunk = xml_parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let ndjson_chunk = xml_parser.push_to_ndjson(chunk)?;
+                let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
                 let mut transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 transformed.extend_from_slice(&remaining);
 
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -876,29 +1282,29 @@ impl Converter {
                 (output, ConverterState::XmlToCsvTransform(xml_parser, engine, csv_writer))
             }
             ConverterState::XmlPassthrough(mut parser) => {
-                let result = parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let result = parser.push_to_ndjson(chunk)?;
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = result.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 // For passthrough, we don't actually convert - just validate
                 // Return the original chunk
                 (chunk.to_vec(), ConverterState::XmlPassthrough(parser))
             }
             ConverterState::XmlToXmlTransform(mut xml_parser, mut engine, mut xml_writer) => {
-                let ndjson_chunk = xml_parser.push_to_ndjson(chunk).map_err(JsValue::from)?;
+                let ndjson_chunk = xml_parser.push_to_ndjson(chunk)?;
                 
                 // Count records (newlines in NDJSON intermediate)
                 let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
                 // Apply transform
                 let transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 
                 // Convert transformed NDJSON to XML
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -909,230 +1315,92 @@ impl Converter {
                 
                 (output, ConverterState::XmlToXmlTransform(xml_parser, engine, xml_writer))
             }
-            ConverterState::JsonPassthrough(_parser) => {
-                let result = chunk.to_vec();
-                
-                // Count records for JSON passthrough
-                // Parse to count top-level array elements or single object
-                if let Ok(s) = std::str::from_utf8(chunk) {
-                    if let Ok(value) = serde_json::from_str::<serde_json::Value>(s) {
-                        let count = match value {
-                            serde_json::Value::Array(ref arr) => arr.len(),
-                            _ => 1, // Single object counts as 1 record
-                        };
-                        self.stats.record_records(count);
+            ConverterState::JsonPassthrough(mut validator, mut pretty) => {
+                let before = validator.records();
+                validator.push(chunk)?;
+                self.count_records(validator.records() - before)?;
+
+                let output = match &mut pretty {
+                    Some(pretty) => {
+                        let mut output = Vec::with_capacity(chunk.len() * 2);
+                        pretty.push(chunk, &mut output);
+                        output
                     }
-                }
-                
-                (result, ConverterState::JsonPassthrough(_parser))
-            }
-            ConverterState::JsonToNdjson(mut parser) => {
-                let s = std::str::from_utf8(chunk).map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let value: serde_json::Value = serde_json::from_str(s).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                let mut output = Vec::new();
-                
-                // Count records
-                let count = match &value {
-                    serde_json::Value::Array(arr) => arr.len(),
-                    serde_json::Value::Object(_) => 1,
-                    _ => 0,
+                    None => chunk.to_vec(),
                 };
-                self.stats.record_records(count);
-                
-                match value {
-                    serde_json::Value::Array(arr) => {
-                        for v in arr.iter() {
-                            let mut buf = Vec::new();
-                            serde_json::to_writer(&mut buf, v).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                            buf.push(b'\n');
-                            output.extend(buf);
-                        }
-                    }
-                    serde_json::Value::Object(_) => {
-                        serde_json::to_writer(&mut output, &value).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                        output.push(b'\n');
-                    }
-                    _ => {}
-                }
-                (output, ConverterState::JsonToNdjson(parser))
+                (output, ConverterState::JsonPassthrough(validator, pretty))
             }
-            ConverterState::JsonToCsv(mut parser, mut csv_writer) => {
-                let s = std::str::from_utf8(chunk).map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let value: serde_json::Value = serde_json::from_str(s).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                
-                // Count records
-                let count = match &value {
-                    serde_json::Value::Array(arr) => arr.len(),
-                    serde_json::Value::Object(_) => 1,
-                    _ => 0,
-                };
-                self.stats.record_records(count);
-                
-                // Convert to NDJSON lines then to CSV
-                let mut output = Vec::new();
-                match value {
-                    serde_json::Value::Array(arr) => {
-                        for v in arr.iter() {
-                            let mut buf = Vec::new();
-                            serde_json::to_writer(&mut buf, v).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                            let line = String::from_utf8_lossy(&buf);
-                            output.extend(csv_writer.process_json_line(&line)?);
-                        }
-                    }
-                    serde_json::Value::Object(_) => {
-                        let mut buf = Vec::new();
-                        serde_json::to_writer(&mut buf, &value).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                        let line = String::from_utf8_lossy(&buf);
-                        output.extend(csv_writer.process_json_line(&line)?);
-                    }
-                    _ => {}
-                }
-                (output, ConverterState::JsonToCsv(parser, csv_writer))
+            ConverterState::JsonToJson(mut reader, mut ndjson_parser, mut is_first) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let is_first_chunk = is_first;
+                is_first = false;
+                let output = ndjson_parser.to_json_array(&ndjson, is_first_chunk, false)?;
+                (output, ConverterState::JsonToJson(reader, ndjson_parser, is_first))
             }
-            ConverterState::JsonToXml(mut parser, mut xml_writer) => {
-                let s = std::str::from_utf8(chunk).map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let value: serde_json::Value = serde_json::from_str(s).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                
-                // Count records
-                let count = match &value {
-                    serde_json::Value::Array(arr) => arr.len(),
-                    serde_json::Value::Object(_) => 1,
-                    _ => 0,
-                };
-                self.stats.record_records(count);
-                
-                // Convert to NDJSON lines then to XML
-                let mut output = Vec::new();
-                match value {
-                    serde_json::Value::Array(arr) => {
-                        for v in arr.iter() {
-                            let mut buf = Vec::new();
-                            serde_json::to_writer(&mut buf, v).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                            let line = String::from_utf8_lossy(&buf);
-                            output.extend(xml_writer.process_json_line(&line)?);
-                        }
-                    }
-                    serde_json::Value::Object(_) => {
-                        let mut buf = Vec::new();
-                        serde_json::to_writer(&mut buf, &value).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                        let line = String::from_utf8_lossy(&buf);
-                        output.extend(xml_writer.process_json_line(&line)?);
-                    }
-                    _ => {}
-                }
-                (output, ConverterState::JsonToXml(parser, xml_writer))
+            ConverterState::JsonToNdjson(mut reader) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                (ndjson, ConverterState::JsonToNdjson(reader))
             }
-            ConverterState::JsonToXmlTransform(mut parser, mut engine, mut xml_writer) => {
-                let s = std::str::from_utf8(chunk).map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let value: serde_json::Value = serde_json::from_str(s).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                
-                // Count records
-                let count = match &value {
-                    serde_json::Value::Array(arr) => arr.len(),
-                    serde_json::Value::Object(_) => 1,
-                    _ => 0,
-                };
-                self.stats.record_records(count);
-                
-                // Convert to NDJSON, apply transform, then to XML
-                let mut ndjson_lines = Vec::new();
-                match value {
-                    serde_json::Value::Array(arr) => {
-                        for v in arr.iter() {
-                            let mut buf = Vec::new();
-                            serde_json::to_writer(&mut buf, v).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                            buf.push(b'\n');
-                            ndjson_lines.extend(buf);
-                        }
-                    }
-                    serde_json::Value::Object(_) => {
-                        let mut buf = Vec::new();
-                        serde_json::to_writer(&mut buf, &value).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                        buf.push(b'\n');
-                        ndjson_lines.extend(buf);
-                    }
-                    _ => {}
-                }
-                
-                // Apply transform
-                let transformed = self.apply_transform_push(&mut engine, &ndjson_lines)?;
-                
-                // Convert transformed NDJSON to XML
-                let ndjson_str = std::str::from_utf8(&transformed).map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let mut output = Vec::new();
-                for line in ndjson_str.lines() {
-                    let trimmed: &str = line.trim();
-                    if !trimmed.is_empty() {
-                        output.extend(xml_writer.process_json_line(line)?);
-                    }
-                }
-                
-                (output, ConverterState::JsonToXmlTransform(parser, engine, xml_writer))
+            ConverterState::JsonToNdjsonTransform(mut reader, mut engine) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let output = self.apply_transform_push(&mut engine, &ndjson)?;
+                (output, ConverterState::JsonToNdjsonTransform(reader, engine))
             }
-            ConverterState::JsonToCsvTransform(mut parser, mut engine, mut csv_writer) => {
-                let s = std::str::from_utf8(chunk).map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let value: serde_json::Value = serde_json::from_str(s).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                
-                // Count records
-                let count = match &value {
-                    serde_json::Value::Array(arr) => arr.len(),
-                    serde_json::Value::Object(_) => 1,
-                    _ => 0,
-                };
-                self.stats.record_records(count);
-                
-                // Convert to NDJSON, apply transform, then to CSV
-                let mut ndjson_lines = Vec::new();
-                match value {
-                    serde_json::Value::Array(arr) => {
-                        for v in arr.iter() {
-                            let mut buf = Vec::new();
-                            serde_json::to_writer(&mut buf, v).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                            buf.push(b'\n');
-                            ndjson_lines.extend(buf);
-                        }
-                    }
-                    serde_json::Value::Object(_) => {
-                        let mut buf = Vec::new();
-                        serde_json::to_writer(&mut buf, &value).map_err(|e| JsValue::from(ConvertError::JsonParse(e.to_string())))?;
-                        buf.push(b'\n');
-                        ndjson_lines.extend(buf);
-                    }
-                    _ => {}
-                }
-                
-                // Apply transform
-                let transformed = self.apply_transform_push(&mut engine, &ndjson_lines)?;
-                
-                // Convert transformed NDJSON to CSV
-                let ndjson_str = std::str::from_utf8(&transformed).map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let mut output = Vec::new();
-                for line in ndjson_str.lines() {
-                    let trimmed: &str = line.trim();
-                    if !trimmed.is_empty() {
-                        output.extend(csv_writer.process_json_line(line)?);
-                    }
-                }
-                
-                (output, ConverterState::JsonToCsvTransform(parser, engine, csv_writer))
+            ConverterState::JsonToJsonTransform(mut reader, mut engine, mut ndjson_parser, mut is_first) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let transformed = self.apply_transform_push(&mut engine, &ndjson)?;
+                let is_first_chunk = is_first;
+                is_first = false;
+                let output = ndjson_parser.to_json_array(&transformed, is_first_chunk, false)?;
+                (output, ConverterState::JsonToJsonTransform(reader, engine, ndjson_parser, is_first))
+            }
+            ConverterState::JsonToCsv(mut reader, mut csv_writer) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let output = Self::write_ndjson_lines(&ndjson, |line| csv_writer.process_json_line(line))?;
+                (output, ConverterState::JsonToCsv(reader, csv_writer))
+            }
+            ConverterState::JsonToXml(mut reader, mut xml_writer) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let output = Self::write_ndjson_lines(&ndjson, |line| xml_writer.process_json_line(line))?;
+                (output, ConverterState::JsonToXml(reader, xml_writer))
+            }
+            ConverterState::JsonToXmlTransform(mut reader, mut engine, mut xml_writer) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let transformed = self.apply_transform_push(&mut engine, &ndjson)?;
+                let output = Self::write_ndjson_lines(&transformed, |line| xml_writer.process_json_line(line))?;
+                (output, ConverterState::JsonToXmlTransform(reader, engine, xml_writer))
+            }
+            ConverterState::JsonToCsvTransform(mut reader, mut engine, mut csv_writer) => {
+                let ndjson = reader.push(chunk)?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let transformed = self.apply_transform_push(&mut engine, &ndjson)?;
+                let output = Self::write_ndjson_lines(&transformed, |line| csv_writer.process_json_line(line))?;
+                (output, ConverterState::JsonToCsvTransform(reader, engine, csv_writer))
             }
             ConverterState::NdjsonToCsv(mut ndjson_parser, mut csv_writer) => {
                 let ndjson_chunk = {
                     #[cfg(feature = "threads")]
                     {
-                        ndjson_parser.push_parallel(chunk).map_err(JsValue::from)?
+                        ndjson_parser.push_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        ndjson_parser.push(chunk).map_err(JsValue::from)?
+                        ndjson_parser.push(chunk)?
                     }
                 };
                 
                 // Count records (newlines in NDJSON)
                 let record_count = chunk.iter().filter(|&&b| b == b'\n').count();
-                self.stats.record_records(record_count);
+                self.count_records(record_count)?;
                 
-                let ndjson_str = std::str::from_utf8(&ndjson_chunk).map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                let ndjson_str = std::str::from_utf8(&ndjson_chunk)?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1143,8 +1411,10 @@ impl Converter {
                 (output, ConverterState::NdjsonToCsv(ndjson_parser, csv_writer))
             }
             ConverterState::NdjsonToCsvTransform(mut engine, mut csv_writer) => {
+                let record_count = chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
                 let transformed = self.apply_transform_push(&mut engine, chunk)?;
-                let ndjson_str = std::str::from_utf8(&transformed).map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                let ndjson_str = std::str::from_utf8(&transformed)?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1158,14 +1428,17 @@ impl Converter {
                 let ndjson_chunk = {
                     #[cfg(feature = "threads")]
                     {
-                        ndjson_parser.push_parallel(chunk).map_err(JsValue::from)?
+                        ndjson_parser.push_parallel(chunk)?
                     }
                     #[cfg(not(feature = "threads"))]
                     {
-                        ndjson_parser.push(chunk).map_err(JsValue::from)?
+                        ndjson_parser.push(chunk)?
                     }
                 };
-                let ndjson_str = std::str::from_utf8(&ndjson_chunk).map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                let record_count = ndjson_chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
+
+                let ndjson_str = std::str::from_utf8(&ndjson_chunk)?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1176,8 +1449,10 @@ impl Converter {
                 (output, ConverterState::NdjsonToXml(ndjson_parser, xml_writer))
             }
             ConverterState::NdjsonToXmlTransform(mut engine, mut xml_writer) => {
+                let record_count = chunk.iter().filter(|&&b| b == b'\n').count();
+                self.count_records(record_count)?;
                 let transformed = self.apply_transform_push(&mut engine, chunk)?;
-                let ndjson_str = std::str::from_utf8(&transformed).map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                let ndjson_str = std::str::from_utf8(&transformed)?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1187,6 +1462,69 @@ impl Converter {
                 }
                 (output, ConverterState::NdjsonToXmlTransform(engine, xml_writer))
             }
+            #[cfg(feature = "parquet")]
+            ConverterState::ToParquet(inner, writer) => {
+                self.state = Some(*inner);
+                let ndjson = self.push_internal(chunk);
+                // Re-wrap the inner state even on error, so later calls never bypass the writer
+                self.state = self
+                    .state
+                    .take()
+                    .map(|inner| ConverterState::ToParquet(Box::new(inner), writer));
+                let ndjson = ndjson?;
+                let Some(ConverterState::ToParquet(inner, mut writer)) = self.state.take() else {
+                    return Err(failed_converter());
+                };
+                let output = writer.process_ndjson(&ndjson)?;
+                (output, ConverterState::ToParquet(inner, writer))
+            }
+            #[cfg(feature = "xlsx")]
+            ConverterState::FromXlsx(mut parser, inner) => {
+                parser.push(chunk);
+                (Vec::new(), ConverterState::FromXlsx(parser, inner))
+            }
+            #[cfg(feature = "yaml")]
+            ConverterState::FromYaml(mut parser, inner) => {
+                let ndjson = parser.push(chunk)?;
+                self.state = Some(*inner);
+                let output = self.push_internal(&ndjson);
+                self.state = self
+                    .state
+                    .take()
+                    .map(|inner| ConverterState::FromYaml(parser, Box::new(inner)));
+                let output = output?;
+                let Some(state) = self.state.take() else {
+                    return Err(failed_converter());
+                };
+                (output, state)
+            }
+            #[cfg(feature = "yaml")]
+            ConverterState::ToYaml(inner, writer) => {
+                self.state = Some(*inner);
+                let ndjson = self.push_internal(chunk);
+                // Re-wrap the inner state even on error, so later calls never bypass the writer
+                self.state = self
+                    .state
+                    .take()
+                    .map(|inner| ConverterState::ToYaml(Box::new(inner), writer));
+                let ndjson = ndjson?;
+                let Some(ConverterState::ToYaml(inner, mut writer)) = self.state.take() else {
+                    return Err(failed_converter());
+                };
+                let output = writer.process_ndjson(&ndjson)?;
+                (output, ConverterState::ToYaml(inner, writer))
+            }
+            #[cfg(any(feature = "parquet", feature = "xlsx"))]
+            ConverterState::Unsupported(reason) => {
+                let error = ConvertError::Unsupported(reason.clone());
+                self.state = Some(ConverterState::Unsupported(reason));
+                return Err(error);
+            }
+            ConverterState::Invalid(reason) => {
+                let error = ConvertError::InvalidConfig(reason.clone());
+                self.state = Some(ConverterState::Invalid(reason));
+                return Err(error);
+            }
             // For other complex cases, we'll handle them similarly
             state => {
                 // Return an error for unhandled cases for now
@@ -1195,7 +1533,7 @@ impl Converter {
                     debug!("Unhandled converter state in push_internal: {}", name);
                 }
                 self.state = Some(state);
-                return Err(JsValue::from(ConvertError::InvalidConfig(format!("Unhandled converter state in push_internal: {}", name))));
+                return Err(ConvertError::InvalidConfig(format!("Unhandled converter state in push_internal: {}", name)));
             }
         };
         
@@ -1203,54 +1541,164 @@ impl Converter {
         Ok(result)
     }
 
-    /// Finish the stream and return any remaining buffered output.
-    pub fn finish(&mut self) -> std::result::Result<Vec<u8>, JsValue> {
+    pub(crate) fn finish_stream(&mut self) -> Result<Vec<u8>> {
+        match self.state {
+            Some(ConverterState::Finished) => return Err(ConvertError::AlreadyFinished),
+            // Later calls hand out what `max_output_bytes` held back
+            Some(ConverterState::Draining) => return Ok(self.drain()),
+            _ => {}
+        }
+        self.release_batch();
+        let output = self.finish_pipeline()?;
+        self.state = Some(ConverterState::Draining);
+        let output = self.deliver(output)?;
+        if let Some(sink) = self.sink.as_mut() {
+            sink.finish()?;
+        }
+        self.settle_finish();
+        Ok(output)
+    }
+
+    /// Whether `finish` has run, even if output is still waiting to be drained.
+    fn finish_called(&self) -> bool {
+        matches!(self.state, Some(ConverterState::Draining | ConverterState::Finished))
+    }
+
+    /// Once `finish` has run, move between `Draining` and `Finished` as
+    /// output waits for or has been taken by the caller.
+    fn settle_finish(&mut self) {
+        if self.finish_called() {
+            self.state = Some(if self.pending_output.is_empty() {
+                ConverterState::Finished
+            } else {
+                ConverterState::Draining
+            });
+        }
+    }
+
+    fn finish_pipeline(&mut self) -> Result<Vec<u8>> {
+        let _span = stage_span!(INFO, "finish", state = self.state_name());
         if self.debug {
             debug!("Converter::finish");
         }
+        let start = crate::timing::Timer::new();
+        let state = self.state_name();
+
+        // Input still held by the decompressor or UTF-8 filter goes through
+        // the pipeline first
+        let tail = match self.decompressor.finish() {
+            Ok(tail) => tail,
+            Err(err) => return Err(self.fail(err)),
+        };
+        if !self.is_stopped() {
+            if let Err(err) = self.limit_guard.add_input(tail.len()) {
+                return Err(self.fail(err));
+            }
+            let mut tail = self.utf8_filter.push(&tail, &self.warnings).into_owned();
+            tail.extend(self.utf8_filter.finish(&self.warnings));
+            if !tail.is_empty() {
+                return self.push_and_finish(&tail);
+            }
+        }
 
         // If still in detection state, initialize with buffered data
         if let Some(ConverterState::NeedsDetection(ref buffer)) = self.state {
             if !buffer.is_empty() {
-                let detection_sample = buffer.clone();
+                let detection_sample = self.resolve_input_format(buffer.clone())?;
                 self.auto_detect_and_initialize(&detection_sample)?;
                 
                 // Process the buffered data and then finish
-                let buffered = detection_sample;
-                let mut output = self.push(&buffered)?;
-                
-                // Now call finish to get any remaining data
-                let remaining = self.finish()?;
-                output.extend_from_slice(&remaining);
-                
-                return Ok(output);
+                return self.push_and_finish(&detection_sample);
             }
         }
 
-        let result = match self.state.take() {
-            Some(ConverterState::CsvPassthrough(mut parser, mut csv_writer)) => {
-                // Finish CSV parsing
-                let ndjson = parser.finish()?;
-                // Convert final NDJSON to CSV
-                let ndjson_str = std::str::from_utf8(&ndjson)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let mut output = Vec::new();
-                for line in ndjson_str.lines() {
+        if let Some(watch) = self.detection_watch.take() {
+            let verdict = watch.finish();
+            if let Some(held) = self.settle_detection_check(watch, verdict) {
+                if !held.is_empty() {
+                    return self.push_and_finish(&held);
+                }
+            }
+        }
+
+        let result = {
+            let _span = stage_span!(DEBUG, "convert", state = self.state_name());
+            self.finish_internal()?
+        };
+        let result = self.map_output_columns(result, true);
+        let result = self.format_numbers(result, true);
+        let mut result = self.frame_ndjson_output(result);
+        if let Some(writer) = self.ndjson_writer.as_mut() {
+            result.extend(writer.finish());
+        }
+        if let Some(index) = self.sort_index.as_mut() {
+            index.write(&result);
+            index.finish();
+        }
+        let result = self.compress_output(result, true)?;
+        self.note_output(&result, true);
+        let output_bytes = result.len();
+        self.trace(|| TraceEvent::Finish {
+            state,
+            output_bytes,
+            elapsed_ms: start.elapsed().as_secs_f64() * 1000.0,
+        });
+
+        if self.config.enable_stats {
+            self.stats.record_output(result.len());
+        }
+
+        Ok(self.cap_output(result))
+    }
+
+    /// Push input that only became available at `finish`, then finish.
+    fn push_and_finish(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+        let mut output = self.push_chunk(input)?;
+        
+        // Now call finish to get any remaining data
+        let remaining = self.finish_pipeline()?;
+        if self.config.max_output_bytes.is_some() {
+            // Keep the per-call cap: finish output waits behind the push output.
+            let mut queued = VecDeque::from(remaining);
+            queued.append(&mut self.pending_output);
+            self.pending_output = queued;
+        } else {
+            output.extend_from_slice(&remaining);
+        }
+        
+        Ok(output)
+    }
+
+    /// Flush the current state, without the output framing and bookkeeping of `finish`.
+    fn finish_internal(&mut self) -> Result<Vec<u8>> {
+        let result = match self.state.take() {
+            Some(ConverterState::CsvPassthrough(mut parser, mut csv_writer)) => {
+                // Finish CSV parsing
+                let ndjson = parser.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                // Convert final NDJSON to CSV
+                let ndjson_str = std::str::from_utf8(&ndjson)
+                    ?;
+                let mut output = Vec::new();
+                for line in ndjson_str.lines() {
                     let trimmed = line.trim();
                     if !trimmed.is_empty() {
-                        output.extend(csv_writer.process_json_line(line).map_err(JsValue::from)?);
+                        output.extend(csv_writer.process_json_line(line)?);
                     }
                 }
                 // Finalize CSV writer
-                let final_output = csv_writer.finish().map_err(JsValue::from)?;
+                let final_output = csv_writer.finish()?;
                 output.extend_from_slice(&final_output);
                 output
             }
             Some(ConverterState::CsvToNdjson(mut parser)) => {
-                parser.finish()?
+                let ndjson = parser.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                ndjson
             }
             Some(ConverterState::CsvToNdjsonTransform(mut parser, mut engine)) => {
                 let ndjson_chunk = parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut output = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 output.extend_from_slice(&remaining);
@@ -1259,6 +1707,7 @@ impl Converter {
             Some(ConverterState::CsvToJson(mut csv_parser, mut ndjson_parser, is_first_flag)) => {
                 // Finish CSV parsing
                 let ndjson_chunk = csv_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 
                 // Process remaining NDJSON through JSON converter
                 // Use the is_first flag to determine if we need opening bracket
@@ -1278,6 +1727,7 @@ impl Converter {
             }
             Some(ConverterState::CsvToJsonTransform(mut csv_parser, mut engine, mut ndjson_parser, is_first_flag)) => {
                 let ndjson_chunk = csv_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 transformed.extend_from_slice(&remaining);
@@ -1295,10 +1745,11 @@ impl Converter {
             Some(ConverterState::CsvToXml(mut csv_parser, mut xml_writer)) => {
                 // Finish CSV parsing
                 let ndjson_chunk = csv_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 
                 // Process remaining NDJSON through XML writer
                 let ndjson_str = std::str::from_utf8(&ndjson_chunk)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1315,12 +1766,13 @@ impl Converter {
             }
             Some(ConverterState::CsvToXmlTransform(mut csv_parser, mut engine, mut xml_writer)) => {
                 let ndjson_chunk = csv_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 transformed.extend_from_slice(&remaining);
 
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1335,12 +1787,13 @@ impl Converter {
             }
             Some(ConverterState::CsvToCsvTransform(mut csv_parser, mut engine, mut csv_writer)) => {
                 let ndjson_chunk = csv_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 transformed.extend_from_slice(&remaining);
 
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1354,7 +1807,9 @@ impl Converter {
                 output
             }
             Some(ConverterState::NdjsonPassthrough(mut parser)) => {
-                parser.finish()?
+                let ndjson = parser.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                ndjson
             }
             Some(ConverterState::NdjsonTransform(mut engine)) => {
                 self.apply_transform_finish(&mut engine)?
@@ -1384,10 +1839,11 @@ impl Converter {
             Some(ConverterState::NdjsonToCsv(mut ndjson_parser, mut csv_writer)) => {
                 // Finish NDJSON parsing
                 let ndjson_chunk = ndjson_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 
                 // Process remaining NDJSON through CSV writer
                 let ndjson_str = std::str::from_utf8(&ndjson_chunk)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1405,7 +1861,7 @@ impl Converter {
             Some(ConverterState::NdjsonToCsvTransform(mut engine, mut csv_writer)) => {
                 let transformed = self.apply_transform_finish(&mut engine)?;
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1421,10 +1877,11 @@ impl Converter {
             Some(ConverterState::NdjsonToXml(mut ndjson_parser, mut xml_writer)) => {
                 // Finish NDJSON parsing
                 let ndjson_chunk = ndjson_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 
                 // Process remaining NDJSON through XML writer
                 let ndjson_str = std::str::from_utf8(&ndjson_chunk)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1442,7 +1899,7 @@ impl Converter {
             Some(ConverterState::NdjsonToXmlTransform(mut engine, mut xml_writer)) => {
                 let transformed = self.apply_transform_finish(&mut engine)?;
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1456,10 +1913,13 @@ impl Converter {
                 output
             }
             Some(ConverterState::XmlToNdjson(mut parser)) => {
-                parser.finish()?
+                let ndjson = parser.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                ndjson
             }
             Some(ConverterState::XmlToNdjsonTransform(mut parser, mut engine)) => {
                 let ndjson_chunk = parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut output = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 output.extend_from_slice(&remaining);
@@ -1468,6 +1928,7 @@ impl Converter {
             Some(ConverterState::XmlToJson(mut xml_parser, mut ndjson_parser, _)) => {
                 // Finish XML parsing
                 let ndjson_chunk = xml_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 
                 // Process remaining NDJSON through JSON converter
                 let mut output = ndjson_parser.to_json_array(&ndjson_chunk, false, false)?;
@@ -1486,6 +1947,7 @@ impl Converter {
             }
             Some(ConverterState::XmlToJsonTransform(mut xml_parser, mut engine, mut ndjson_parser, is_first_flag)) => {
                 let ndjson_chunk = xml_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 transformed.extend_from_slice(&remaining);
@@ -1503,10 +1965,11 @@ impl Converter {
             Some(ConverterState::XmlToCsv(mut xml_parser, mut csv_writer)) => {
                 // Finish XML parsing
                 let ndjson_chunk = xml_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 
                 // Process remaining NDJSON through CSV writer
                 let ndjson_str = std::str::from_utf8(&ndjson_chunk)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1523,12 +1986,13 @@ impl Converter {
             }
             Some(ConverterState::XmlToCsvTransform(mut xml_parser, mut engine, mut csv_writer)) => {
                 let ndjson_chunk = xml_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 transformed.extend_from_slice(&remaining);
 
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1547,12 +2011,13 @@ impl Converter {
             }
             Some(ConverterState::XmlToXmlTransform(mut xml_parser, mut engine, mut xml_writer)) => {
                 let ndjson_chunk = xml_parser.finish()?;
+                self.count_records(ndjson_chunk.iter().filter(|&&b| b == b'\n').count())?;
                 let mut transformed = self.apply_transform_push(&mut engine, &ndjson_chunk)?;
                 let remaining = self.apply_transform_finish(&mut engine)?;
                 transformed.extend_from_slice(&remaining);
 
                 let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
+                    ?;
                 let mut output = Vec::new();
                 for line in ndjson_str.lines() {
                     let trimmed: &str = line.trim();
@@ -1565,11 +2030,23 @@ impl Converter {
                 output.extend_from_slice(&final_output);
                 output
             }
-            Some(ConverterState::JsonPassthrough(_)) => {
-                Vec::new()
+            Some(ConverterState::JsonPassthrough(mut validator, pretty)) => {
+                validator.finish()?;
+                pretty.map_or_else(Vec::new, |mut pretty| pretty.finish())
             }
-            Some(ConverterState::JsonToJsonTransform(_, mut engine, mut ndjson_parser, is_first_flag)) => {
-                let transformed = self.apply_transform_finish(&mut engine)?;
+            Some(ConverterState::JsonToJson(mut reader, mut ndjson_parser, is_first_flag)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let mut output = ndjson_parser.to_json_array(&ndjson, is_first_flag, false)?;
+                output.extend(ndjson_parser.to_json_array(&[], false, true)?);
+                output.extend(ndjson_parser.finish()?);
+                output
+            }
+            Some(ConverterState::JsonToJsonTransform(mut reader, mut engine, mut ndjson_parser, is_first_flag)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let mut transformed = self.apply_transform_push(&mut engine, &ndjson)?;
+                transformed.extend(self.apply_transform_finish(&mut engine)?);
                 let mut output = ndjson_parser.to_json_array(&transformed, is_first_flag, false)?;
                 let closing = ndjson_parser.to_json_array(&[], false, true)?;
                 output.extend_from_slice(&closing);
@@ -1579,81 +2056,523 @@ impl Converter {
                 }
                 output
             }
-            Some(ConverterState::JsonToNdjson(_)) => {
-                Vec::new()
+            Some(ConverterState::JsonToNdjson(mut reader)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                ndjson
             }
-            Some(ConverterState::JsonToNdjsonTransform(_, mut engine)) => {
-                self.apply_transform_finish(&mut engine)?
+            Some(ConverterState::JsonToNdjsonTransform(mut reader, mut engine)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let mut output = self.apply_transform_push(&mut engine, &ndjson)?;
+                output.extend(self.apply_transform_finish(&mut engine)?);
+                output
             }
-            Some(ConverterState::JsonToCsv(_, mut csv_writer)) => {
-                // Finalize CSV writer
-                csv_writer.finish()?
+            Some(ConverterState::JsonToCsv(mut reader, mut csv_writer)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let mut output = Self::write_ndjson_lines(&ndjson, |line| csv_writer.process_json_line(line))?;
+                output.extend(csv_writer.finish()?);
+                output
             }
-            Some(ConverterState::JsonToCsvTransform(_, mut engine, mut csv_writer)) => {
-                let transformed = self.apply_transform_finish(&mut engine)?;
-                let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let mut output = Vec::new();
-                for line in ndjson_str.lines() {
-                    let trimmed: &str = line.trim();
-                    if !trimmed.is_empty() {
-                        output.extend(csv_writer.process_json_line(line)?);
-                    }
-                }
-                let final_output = csv_writer.finish()?;
-                output.extend_from_slice(&final_output);
+            Some(ConverterState::JsonToCsvTransform(mut reader, mut engine, mut csv_writer)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let mut transformed = self.apply_transform_push(&mut engine, &ndjson)?;
+                transformed.extend(self.apply_transform_finish(&mut engine)?);
+                let mut output = Self::write_ndjson_lines(&transformed, |line| csv_writer.process_json_line(line))?;
+                output.extend(csv_writer.finish()?);
                 output
             }
-            Some(ConverterState::JsonToXml(_, xml_writer)) => {
-                // Finalize XML writer
-                xml_writer.finish()?
+            Some(ConverterState::JsonToXml(mut reader, mut xml_writer)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let mut output = Self::write_ndjson_lines(&ndjson, |line| xml_writer.process_json_line(line))?;
+                output.extend(xml_writer.finish()?);
+                output
             }
-            Some(ConverterState::JsonToXmlTransform(_, mut engine, mut xml_writer)) => {
-                let transformed = self.apply_transform_finish(&mut engine)?;
-                let ndjson_str = std::str::from_utf8(&transformed)
-                    .map_err(|e| JsValue::from(ConvertError::from(e)))?;
-                let mut output = Vec::new();
-                for line in ndjson_str.lines() {
-                    let trimmed: &str = line.trim();
-                    if !trimmed.is_empty() {
-                        output.extend(xml_writer.process_json_line(line)?);
-                    }
-                }
-                let final_output = xml_writer.finish()?;
-                output.extend_from_slice(&final_output);
+            Some(ConverterState::JsonToXmlTransform(mut reader, mut engine, mut xml_writer)) => {
+                let ndjson = reader.finish()?;
+                self.count_records(ndjson.iter().filter(|&&b| b == b'\n').count())?;
+                let mut transformed = self.apply_transform_push(&mut engine, &ndjson)?;
+                transformed.extend(self.apply_transform_finish(&mut engine)?);
+                let mut output = Self::write_ndjson_lines(&transformed, |line| xml_writer.process_json_line(line))?;
+                output.extend(xml_writer.finish()?);
                 output
             }
             Some(ConverterState::NeedsDetection(_)) => {
                 // Already handled above, should not reach here
                 Vec::new()
             }
+            #[cfg(feature = "parquet")]
+            Some(ConverterState::ToParquet(inner, mut writer)) => {
+                self.state = Some(*inner);
+                let ndjson = self.finish_internal()?;
+                let mut output = writer.process_ndjson(&ndjson)?;
+                output.extend(writer.finish()?);
+                output
+            }
+            #[cfg(feature = "xlsx")]
+            Some(ConverterState::FromXlsx(mut parser, inner)) => {
+                let ndjson = parser.finish()?;
+                self.state = Some(*inner);
+                let mut output = self.push_internal(&ndjson)?;
+                output.extend(self.finish_internal()?);
+                output
+            }
+            #[cfg(feature = "yaml")]
+            Some(ConverterState::FromYaml(mut parser, inner)) => {
+                let ndjson = parser.finish()?;
+                self.state = Some(*inner);
+                let mut output = self.push_internal(&ndjson)?;
+                output.extend(self.finish_internal()?);
+                output
+            }
+            #[cfg(feature = "yaml")]
+            Some(ConverterState::ToYaml(inner, mut writer)) => {
+                self.state = Some(*inner);
+                let ndjson = self.finish_internal()?;
+                let mut output = writer.process_ndjson(&ndjson)?;
+                output.extend(writer.finish());
+                output
+            }
+            #[cfg(any(feature = "parquet", feature = "xlsx"))]
+            Some(ConverterState::Unsupported(reason)) => {
+                return Err(ConvertError::Unsupported(reason));
+            }
+            Some(ConverterState::Invalid(reason)) => {
+                self.state = Some(ConverterState::Invalid(reason.clone()));
+                return Err(ConvertError::InvalidConfig(reason));
+            }
+            Some(state @ (ConverterState::Draining | ConverterState::Finished)) => {
+                self.state = Some(state);
+                return Err(ConvertError::AlreadyFinished);
+            }
             None => {
-                return Err(ConvertError::InvalidConfig("Converter already finished".to_string()).into());
+                return Err(failed_converter());
             }
         };
 
-        if self.config.enable_stats {
-            self.stats.record_output(result.len());
-        }
-
         Ok(result)
     }
 
     /// Get performance statistics
-    #[wasm_bindgen(js_name = getStats)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = getStats))]
     pub fn get_stats(&self) -> Stats {
-        self.stats.clone()
+        let mut stats = self.stats.clone();
+        stats.records_skipped = self.record_errors.skipped();
+        stats
+    }
+
+    /// Events recorded in trace mode, oldest first, as an array of objects.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = getTrace)]
+    pub fn get_trace(&self) -> JsValue {
+        let entries = self.trace_entries();
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        serde::Serialize::serialize(&entries, &serializer).unwrap_or(JsValue::NULL)
+    }
+
+    /// Discard recorded trace events.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = clearTrace))]
+    pub fn clear_trace(&mut self) {
+        if let Some(log) = self.trace_log.as_mut() {
+            log.clear();
+        }
+    }
+
+    /// Non-fatal problems seen so far, one object per kind with the first
+    /// occurrence's message and a count.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = getWarnings)]
+    pub fn get_warnings(&self) -> JsValue {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        serde::Serialize::serialize(&self.warnings.list(), &serializer).unwrap_or(JsValue::NULL)
+    }
+
+    /// `push` returning `{output, recordsEmitted, bytesBuffered, warnings}`,
+    /// with the records, buffered input and warnings of this chunk rather
+    /// than the totals of `getStats()` and `getWarnings()`.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = pushWithInfo)]
+    pub fn push_with_info_js(&mut self, chunk: &[u8]) -> std::result::Result<JsValue, JsValue> {
+        let info = self.push_with_info(chunk)?;
+        let result = Object::new();
+        Reflect::set(&result, &JsValue::from("output"), &js_sys::Uint8Array::from(info.output.as_slice()))?;
+        Reflect::set(&result, &JsValue::from("recordsEmitted"), &JsValue::from(info.records_emitted as f64))?;
+        Reflect::set(&result, &JsValue::from("bytesBuffered"), &JsValue::from(info.bytes_buffered as f64))?;
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        let warnings = serde::Serialize::serialize(&info.warnings, &serializer)?;
+        Reflect::set(&result, &JsValue::from("warnings"), &warnings)?;
+        Ok(result.into())
+    }
+
+    /// Types of the CSV columns written with `csvOutput.typeSidecar` set, as
+    /// `{columns, unflatten}` to spread into the `csvConfig` that reads the
+    /// CSV back. `null` until a record is written.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = getCsvSchema)]
+    pub fn get_csv_schema(&self) -> JsValue {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        serde::Serialize::serialize(&self.csv_schema(), &serializer).unwrap_or(JsValue::NULL)
+    }
+
+    /// Records dropped under the `collect` record error policy, as objects
+    /// with the `line` they start on and the error `message`.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = getErrors)]
+    pub fn get_errors(&self) -> JsValue {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        serde::Serialize::serialize(&self.record_errors.list(), &serializer).unwrap_or(JsValue::NULL)
+    }
+
+    /// Raw bytes of records dropped since the last call, as an NDJSON stream
+    /// of `{line, error, raw}` objects. Empty unless `keepRejects` is set.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = takeRejects))]
+    pub fn take_rejects(&mut self) -> Vec<u8> {
+        self.record_errors.take_rejects()
+    }
+
+    /// The sidecar index of the output returned since the last call, as NDJSON
+    /// `[key, offset, length]` arrays, one per distinct sort key. Empty unless
+    /// `sort.index` is set; entries are complete once the converter finishes.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = takeIndex))]
+    pub fn take_index(&mut self) -> Vec<u8> {
+        self.sort_index.as_mut().map(SortIndex::take).unwrap_or_default()
+    }
+
+    /// The chunk manifest of the output returned since the last call, as
+    /// NDJSON `{offset, length, sha256, known}` objects. Empty unless
+    /// `chunking` is set; the last chunk is listed once the converter finishes.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = takeChunks))]
+    pub fn take_chunks(&mut self) -> Vec<u8> {
+        self.chunker.as_mut().map(Chunker::take).unwrap_or_default()
+    }
+
+    /// Discard collected warnings.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = clearWarnings))]
+    pub fn clear_warnings(&mut self) {
+        self.warnings.clear();
+    }
+
+    /// The conversion manifest, or `null` before `finish()` or without
+    /// `manifest` set.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = getManifest)]
+    pub fn get_manifest(&self) -> JsValue {
+        let Some(manifest) = self.manifest() else {
+            return JsValue::NULL;
+        };
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        serde::Serialize::serialize(manifest, &serializer).unwrap_or(JsValue::NULL)
+    }
+
+    /// Settings detected from the first chunk, or `null` before detection
+    /// has run or when nothing was detected.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = getDetectionResult)]
+    pub fn get_detection_result(&self) -> JsValue {
+        let Some(detection) = &self.detection else {
+            return JsValue::NULL;
+        };
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        serde::Serialize::serialize(detection, &serializer).unwrap_or(JsValue::NULL)
+    }
+
+    /// Next part of the output held back by `maxOutputBytes`, at most that many
+    /// bytes. Empty once everything has been returned.
+    pub fn drain(&mut self) -> Vec<u8> {
+        let limit = self.config.max_output_bytes.unwrap_or(usize::MAX);
+        let output = self.take_pending(limit);
+        self.settle_finish();
+        output
+    }
+
+    /// Bytes of converted output waiting to be returned by `drain()`.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = pendingOutputBytes))]
+    pub fn pending_output_bytes(&self) -> usize {
+        self.pending_output.len()
+    }
+
+    /// Whether a record matched `transform.stopWhen` or `maxRecords` records
+    /// were written. Later pushes are ignored, so callers can stop reading
+    /// input and call `finish()`.
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = isStopped))]
+    pub fn is_stopped(&self) -> bool {
+        self.stop_match.is_some() || self.records_done
+    }
+
+    /// Whether `finish` has returned the last output. Until then, with
+    /// `maxOutputBytes` set, each further `finish` returns the next part of
+    /// the output held back; once finished, pushing or finishing again fails
+    /// with "Converter already finished".
+    #[cfg_attr(feature = "wasm", wasm_bindgen(js_name = isFinished))]
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, Some(ConverterState::Finished))
+    }
+
+    /// The input record that matched `transform.stopWhen`, or `null`.
+    #[cfg(feature = "wasm")]
+    #[wasm_bindgen(js_name = getMatch)]
+    pub fn get_match(&self) -> JsValue {
+        let Some(record) = self.matched_record() else {
+            return JsValue::NULL;
+        };
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        serde::Serialize::serialize(record, &serializer).unwrap_or(JsValue::NULL)
     }
+
+    /// Pass output to `callback` as a `Uint8Array` as it is produced, in
+    /// place of returning it from `push()`, `flush()` and `finish()`. `null`
+    /// has it returned again.
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    #[wasm_bindgen(js_name = setOutputCallback)]
+    pub fn set_output_callback(&mut self, callback: Option<js_sys::Function>) {
+        self.sink = callback.map(|callback| Box::new(sink::JsOutputSink(callback)) as Box<dyn OutputSink>);
+    }
+}
+
+/// Convert a whole input in one call: push it, finish, and collect all the
+/// output, including any held back by `max_output_bytes`.
+pub fn convert_bytes(input: &[u8], config: ConverterConfig) -> Result<Vec<u8>> {
+    config.validate()?;
+    let mut converter = Converter::from_config(config);
+    let mut output = converter.push_bytes(input)?;
+    while !converter.is_finished() {
+        output.extend(converter.finish_stream()?);
+    }
+    Ok(output)
 }
 
 impl Converter {
+    /// Start a [`ConverterBuilder`], which checks the settings against the
+    /// chosen formats before building.
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder::new()
+    }
+
+    /// Build a converter from a config. Only the settings flagged in
+    /// `config.auto_detect` are detected from the input. When the config
+    /// fails [`ConverterConfig::validate`], every `push` and `finish`
+    /// returns that error.
+    pub fn from_config(config: ConverterConfig) -> Converter {
+        Self::build(false, config)
+    }
+
+    /// A converter in the first state of `config`'s pipeline.
+    fn build(debug: bool, config: ConverterConfig) -> Converter {
+        let warnings = Warnings::default();
+        let record_errors = RecordErrors::for_config(&config);
+        let column_kinds = ColumnKinds::default();
+        let state = match config.validate() {
+            Ok(()) => Self::initial_state(&config, &warnings, &record_errors, &column_kinds),
+            Err(ConvertError::InvalidConfig(reason)) => ConverterState::Invalid(reason),
+            Err(err) => ConverterState::Invalid(err.to_string()),
+        };
+        Converter {
+            debug,
+            state: Some(state),
+            limit_guard: LimitGuard::new(config.limits),
+            decompressor: Decompressor::new(config.input_compression),
+            utf8_filter: Utf8Filter::for_config(&config),
+            detection_watch: DetectionWatch::for_config(&config),
+            compressor: Compressor::new(config.output_compression),
+            sort_index: SortIndex::for_config(&config),
+            chunker: config.chunking.as_ref().map(Chunker::new),
+            manifest: ManifestRecorder::for_config(&config),
+            config,
+            stats: Stats::default(),
+            stop_match: None,
+            records_done: false,
+            records_parsed: 0,
+            records_transformed: None,
+            ndjson_writer: None,
+            ndjson_columns: None,
+            number_rewriter: None,
+            pending_output: VecDeque::new(),
+            batched_output: Vec::new(),
+            idle_since_ms: None,
+            trace_log: None,
+            detection: None,
+            record_errors,
+            warnings,
+            column_kinds,
+            sink: None,
+        }
+    }
+
+    /// [`push`](Self::push) with a [`ConvertError`], whether or not the
+    /// `wasm` feature makes `push` return a `JsValue`, for native callers
+    /// that depend on the crate with its default features.
+    pub fn push_native(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        self.push_bytes(chunk)
+    }
+
+    /// [`finish`](Self::finish) with a [`ConvertError`], like
+    /// [`push_native`](Self::push_native).
+    pub fn finish_native(&mut self) -> Result<Vec<u8>> {
+        self.finish_stream()
+    }
+
+    /// Like [`push_native`](Self::push_native), with the records, buffered
+    /// input and warnings of this chunk alone.
+    pub fn push_with_info(&mut self, chunk: &[u8]) -> Result<PushInfo> {
+        let records_before = self.records_emitted();
+        let warnings_before = self.warnings.list();
+        let output = self.push_bytes(chunk)?;
+        Ok(PushInfo {
+            output,
+            records_emitted: self.records_emitted() - records_before,
+            bytes_buffered: self.buffered_bytes(),
+            warnings: self.warnings.since(&warnings_before),
+        })
+    }
+
+    /// Like [`push`](Self::push), but appends the output to `out`. Reusing
+    /// one buffer across pushes saves allocating one per chunk, as the
+    /// converter's own buffers go back to a pool once copied.
+    pub fn push_into(&mut self, chunk: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        let output = self.push_bytes(chunk)?;
+        out.extend_from_slice(&output);
+        buffer_pool::release_shared(output);
+        Ok(())
+    }
+
+    /// Like [`finish`](Self::finish), but appends the output to `out`.
+    pub fn finish_into(&mut self, out: &mut Vec<u8>) -> Result<()> {
+        let output = self.finish_stream()?;
+        out.extend_from_slice(&output);
+        buffer_pool::release_shared(output);
+        Ok(())
+    }
+
+    /// Write output to `sink` as it is produced: `push`, `flush` and `finish`
+    /// then return empty buffers, and output held back by `max_output_bytes`
+    /// is written in parts of at most that size instead of waiting for
+    /// `drain()`.
+    pub fn with_sink(mut self, sink: impl OutputSink + 'static) -> Self {
+        self.sink = Some(Box::new(sink));
+        self
+    }
+
+    /// Return output held back for more input without ending the stream:
+    /// input still waiting for format detection is converted with what has
+    /// arrived, and compressed output is sync-flushed so it can be decoded.
+    /// Records whose line hasn't ended are still held.
+    pub fn flush(&mut self) -> Result<Vec<u8>> {
+        if self.finish_called() {
+            return Err(ConvertError::AlreadyFinished);
+        }
+        if self.state.is_none() {
+            return Err(failed_converter());
+        }
+        self.idle_since_ms = None;
+        self.release_batch();
+
+        let mut output = Vec::new();
+        if matches!(&self.state, Some(ConverterState::NeedsDetection(buffer)) if !buffer.is_empty()) {
+            // Settle detection on the input so far
+            output = self.settle_detection()?;
+        }
+        if let Some(compressor) = self.compressor.as_mut() {
+            let flushed = match compressor.flush() {
+                Ok(flushed) => flushed,
+                Err(err) => return Err(self.fail(err)),
+            };
+            if self.config.enable_stats {
+                self.stats.record_output(flushed.len());
+            }
+            self.note_output(&flushed, false);
+            if self.config.max_output_bytes.is_some() && !output.is_empty() {
+                // Keep the per-call cap: the flushed bytes wait behind the push output
+                self.pending_output.extend(flushed);
+            } else {
+                output.extend(self.cap_output(flushed));
+            }
+        }
+        if output.is_empty() {
+            // Output batched for `min_output_bytes`
+            output = self.cap_output(output);
+        }
+        self.deliver(output)
+    }
+
+    /// [`flush`](Self::flush) once `config.idle_flush` has passed without
+    /// input, and return nothing otherwise. Call it periodically while
+    /// waiting for input, e.g. between reads of a tailed log; each quiet
+    /// period flushes once.
+    pub fn poll_idle(&mut self) -> Result<Vec<u8>> {
+        let (Some(idle), Some(since)) = (self.config.idle_flush, self.idle_since_ms) else {
+            return Ok(Vec::new());
+        };
+        if self.state.is_none() || self.finish_called() || crate::timing::now_ms() - since < idle.as_secs_f64() * 1000.0 {
+            return Ok(Vec::new());
+        }
+        self.flush()
+    }
+
+    /// Non-fatal problems seen so far; see [`WarningCode`].
+    pub fn warnings(&self) -> Vec<Warning> {
+        self.warnings.list()
+    }
+
+    /// Types of the CSV columns written so far, when
+    /// [`CsvWriterConfig::type_sidecar`] is set. Reading the CSV back with
+    /// [`CsvConfig::with_schema`] restores the records it was written from.
+    pub fn csv_schema(&self) -> Option<CsvSchema> {
+        self.column_kinds.schema()
+    }
+
+    /// Records dropped under [`RecordErrorPolicy::Collect`], at most
+    /// [`MAX_COLLECTED_ERRORS`] of them.
+    pub fn record_errors(&self) -> Vec<RecordError> {
+        self.record_errors.list()
+    }
+
+    /// Settings detected from the first chunk; see [`DetectionResult`].
+    pub fn detection(&self) -> Option<&DetectionResult> {
+        self.detection.as_ref()
+    }
+
+    /// The conversion manifest, once finished with `manifest` set.
+    pub fn manifest(&self) -> Option<&ConversionManifest> {
+        self.manifest.as_ref().and_then(ManifestRecorder::manifest)
+    }
+
+    /// Events recorded in trace mode, oldest first.
+    pub fn trace_entries(&self) -> Vec<&TraceEntry> {
+        self.trace_log
+            .as_ref()
+            .map(|log| log.entries().collect())
+            .unwrap_or_default()
+    }
+
+    /// The input record that matched `transform.stopWhen`.
+    pub fn matched_record(&self) -> Option<&serde_json::Value> {
+        self.stop_match.as_ref()
+    }
+
+    /// Records handed to the output writer so far.
+    fn records_emitted(&self) -> u64 {
+        self.records_transformed.unwrap_or(self.records_parsed)
+    }
+
+    /// Count parsed input records for stats and the `maxRecords` limit.
+    fn count_records(&mut self, count: usize) -> Result<()> {
+        self.records_parsed += count as u64;
+        self.stats.record_records(count);
+        self.limit_guard.add_records(count)
+    }
+
     fn apply_transform_push(
         &mut self,
         engine: &mut TransformEngine,
         chunk: &[u8],
-    ) -> std::result::Result<Vec<u8>, JsValue> {
+    ) -> Result<Vec<u8>> {
         let timer = crate::timing::Timer::new();
-        let result = engine.push(chunk).map_err(JsValue::from)?;
+        let result = engine.push(chunk)?;
+        self.capture_stop_match(engine);
+        *self.records_transformed.get_or_insert(0) += result.records as u64;
         if self.config.enable_stats {
             self.stats.record_transform_time(timer.elapsed());
             self.stats.record_records(result.records);
@@ -1664,9 +2583,11 @@ impl Converter {
     fn apply_transform_finish(
         &mut self,
         engine: &mut TransformEngine,
-    ) -> std::result::Result<Vec<u8>, JsValue> {
+    ) -> Result<Vec<u8>> {
         let timer = crate::timing::Timer::new();
-        let result = engine.finish().map_err(JsValue::from)?;
+        let result = engine.finish()?;
+        self.capture_stop_match(engine);
+        *self.records_transformed.get_or_insert(0) += result.records as u64;
         if self.config.enable_stats {
             self.stats.record_transform_time(timer.elapsed());
             self.stats.record_records(result.records);
@@ -1674,74 +2595,762 @@ impl Converter {
         Ok(result.output)
     }
 
-    /// Auto-detect configuration from a sample and initialize the converter state
-    fn auto_detect_and_initialize(&mut self, sample: &[u8]) -> std::result::Result<(), JsValue> {
-        if self.debug {
-            debug!("Auto-detecting configuration from {} byte sample", sample.len());
+    /// Feed each non-blank NDJSON line to a CSV or XML writer.
+    fn write_ndjson_lines(
+        ndjson: &[u8],
+        mut write: impl FnMut(&str) -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let ndjson_str = std::str::from_utf8(ndjson)?;
+        let mut output = Vec::new();
+        for line in ndjson_str.lines() {
+            if !line.trim().is_empty() {
+                output.extend(write(line)?);
+            }
         }
+        Ok(output)
+    }
 
-        match self.config.input_format {
-            Format::Csv => {
-                if let Some(detection) = detect::detect_csv(sample) {
-                    let mut csv_config = self.config.csv_config.clone().unwrap_or_default();
-                    csv_config.delimiter = detection.delimiter;
-                    self.config.csv_config = Some(csv_config.clone());
-                    
-                    if self.debug {
-                        let delim_bytes = [detection.delimiter];
-                        let delim_char = char::from(delim_bytes[0]);
-                        debug!("Auto-detected CSV delimiter: '{}' ({} fields)", delim_char, detection.fields.len());
-                    }
-                } else if self.debug {
-                    debug!("CSV auto-detection failed, using default config");
-                }
+    /// Bytes held in parser, engine and detection buffers for the current state.
+    fn buffered_bytes(&self) -> usize {
+        Self::state_buffered_bytes(self.state.as_ref())
+            + self.decompressor.buffered_bytes()
+            + self.utf8_filter.buffered_bytes()
+            + self.detection_watch.as_ref().map_or(0, DetectionWatch::buffered_bytes)
+            + self.number_rewriter.as_ref().map_or(0, NumberRewriter::buffered_bytes)
+    }
+
+    fn state_buffered_bytes(state: Option<&ConverterState>) -> usize {
+        match state {
+            Some(ConverterState::CsvPassthrough(p, _)) => p.partial_size(),
+            Some(ConverterState::CsvToNdjson(p)) => p.partial_size(),
+            Some(ConverterState::CsvToNdjsonTransform(p, engine)) => {
+                p.partial_size() + engine.partial_size()
             }
-            Format::Xml => {
-                if let Some(detection) = detect::detect_xml(sample) {
-                    if let Some(record_element) = detection.record_element {
-                        let mut xml_config = self.config.xml_config.clone().unwrap_or_default();
-                        xml_config.record_element = record_element.clone();
-                        self.config.xml_config = Some(xml_config.clone());
-                        
-                        if self.debug {
-                            debug!("Auto-detected XML record element: '{}'", record_element);
-                        }
-                    }
-                } else if self.debug {
-                    debug!("XML auto-detection failed, using default config");
-                }
+            Some(ConverterState::CsvToJson(csv_p, ndjson_p, _)) => {
+                csv_p.partial_size() + ndjson_p.partial_size()
             }
-            _ => {
-                // No auto-detection needed for other formats
+            Some(ConverterState::CsvToJsonTransform(csv_p, engine, ndjson_p, _)) => {
+                csv_p.partial_size() + engine.partial_size() + ndjson_p.partial_size()
+            }
+            Some(ConverterState::CsvToXml(csv_p, _)) => csv_p.partial_size(),
+            Some(ConverterState::CsvToXmlTransform(csv_p, engine, _)) => {
+                csv_p.partial_size() + engine.partial_size()
+            }
+            Some(ConverterState::CsvToCsvTransform(csv_p, engine, _)) => {
+                csv_p.partial_size() + engine.partial_size()
+            }
+            Some(ConverterState::NdjsonPassthrough(p)) => p.partial_size(),
+            Some(ConverterState::NdjsonTransform(engine)) => engine.partial_size(),
+            Some(ConverterState::NdjsonToJson(p, _)) => p.partial_size(),
+            Some(ConverterState::NdjsonToJsonTransform(engine, p, _)) => {
+                engine.partial_size() + p.partial_size()
+            }
+            Some(ConverterState::NdjsonToCsv(ndjson_p, _)) => ndjson_p.partial_size(),
+            Some(ConverterState::NdjsonToCsvTransform(engine, _)) => engine.partial_size(),
+            Some(ConverterState::NdjsonToXml(ndjson_p, _)) => ndjson_p.partial_size(),
+            Some(ConverterState::NdjsonToXmlTransform(engine, _)) => engine.partial_size(),
+            Some(ConverterState::XmlToNdjson(p)) => p.partial_size(),
+            Some(ConverterState::XmlToNdjsonTransform(p, engine)) => {
+                p.partial_size() + engine.partial_size()
             }
+            Some(ConverterState::XmlToJson(xml_p, ndjson_p, _)) => {
+                xml_p.partial_size() + ndjson_p.partial_size()
+            }
+            Some(ConverterState::XmlToJsonTransform(xml_p, engine, ndjson_p, _)) => {
+                xml_p.partial_size() + engine.partial_size() + ndjson_p.partial_size()
+            }
+            Some(ConverterState::XmlToCsv(xml_p, _)) => xml_p.partial_size(),
+            Some(ConverterState::XmlToCsvTransform(xml_p, engine, _)) => {
+                xml_p.partial_size() + engine.partial_size()
+            }
+            Some(ConverterState::XmlPassthrough(p)) => p.partial_size(),
+            Some(ConverterState::XmlToXmlTransform(p, engine, _)) => {
+                p.partial_size() + engine.partial_size()
+            }
+            Some(ConverterState::JsonToNdjson(reader)) => reader.buffered_bytes(),
+            Some(ConverterState::JsonToNdjsonTransform(reader, engine)) => {
+                reader.buffered_bytes() + engine.partial_size()
+            }
+            Some(ConverterState::JsonToCsv(reader, _)) => reader.buffered_bytes(),
+            Some(ConverterState::JsonToCsvTransform(reader, engine, _)) => {
+                reader.buffered_bytes() + engine.partial_size()
+            }
+            Some(ConverterState::JsonToXml(reader, _)) => reader.buffered_bytes(),
+            Some(ConverterState::JsonToXmlTransform(reader, engine, _)) => {
+                reader.buffered_bytes() + engine.partial_size()
+            }
+            Some(ConverterState::JsonToJsonTransform(reader, engine, p, _)) => {
+                reader.buffered_bytes() + engine.partial_size() + p.partial_size()
+            }
+            Some(ConverterState::JsonToJson(reader, p, _)) => reader.buffered_bytes() + p.partial_size(),
+            Some(ConverterState::NeedsDetection(buffer)) => buffer.len(),
+            #[cfg(feature = "parquet")]
+            Some(ConverterState::ToParquet(inner, _)) => {
+                Self::state_buffered_bytes(Some(inner))
+            }
+            #[cfg(feature = "xlsx")]
+            Some(ConverterState::FromXlsx(parser, inner)) => {
+                parser.buffered_bytes() + Self::state_buffered_bytes(Some(inner))
+            }
+            #[cfg(feature = "yaml")]
+            Some(ConverterState::FromYaml(parser, inner)) => {
+                parser.buffered_bytes() + Self::state_buffered_bytes(Some(inner))
+            }
+            #[cfg(feature = "yaml")]
+            Some(ConverterState::ToYaml(inner, _)) => Self::state_buffered_bytes(Some(inner)),
+            _ => 0,
         }
+    }
 
-        // Create the proper state with detected/default config
-        let new_state = Self::create_state(&self.config);
-        self.state = Some(new_state);
+    /// Drop the input held for a record cut off at the end of what was pushed,
+    /// so the next chunk is read from the start of a record, as when a followed
+    /// file is truncated. Output written so far, and the writer's header or
+    /// array, are kept.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn discard_partial_input(&mut self) {
+        self.utf8_filter.discard_partial();
+        Self::discard_state_partial(self.state.as_mut());
+    }
 
-        Ok(())
+    #[cfg(not(target_arch = "wasm32"))]
+    fn discard_state_partial(state: Option<&mut ConverterState>) {
+        match state {
+            Some(
+                ConverterState::CsvPassthrough(p, _)
+                | ConverterState::CsvToNdjson(p)
+                | ConverterState::CsvToNdjsonTransform(p, _)
+                | ConverterState::CsvToJson(p, _, _)
+                | ConverterState::CsvToJsonTransform(p, _, _, _)
+                | ConverterState::CsvToXml(p, _)
+                | ConverterState::CsvToXmlTransform(p, _, _)
+                | ConverterState::CsvToCsvTransform(p, _, _),
+            ) => p.discard_partial(),
+            Some(
+                ConverterState::NdjsonPassthrough(p)
+                | ConverterState::NdjsonToJson(p, _)
+                | ConverterState::NdjsonToCsv(p, _)
+                | ConverterState::NdjsonToXml(p, _),
+            ) => p.discard_partial(),
+            Some(
+                ConverterState::NdjsonTransform(engine)
+                | ConverterState::NdjsonToJsonTransform(engine, _, _)
+                | ConverterState::NdjsonToCsvTransform(engine, _)
+                | ConverterState::NdjsonToXmlTransform(engine, _),
+            ) => engine.discard_partial(),
+            Some(
+                ConverterState::XmlToNdjson(p)
+                | ConverterState::XmlToNdjsonTransform(p, _)
+                | ConverterState::XmlToJson(p, _, _)
+                | ConverterState::XmlToJsonTransform(p, _, _, _)
+                | ConverterState::XmlToCsv(p, _)
+                | ConverterState::XmlToCsvTransform(p, _, _)
+                | ConverterState::XmlPassthrough(p)
+                | ConverterState::XmlToXmlTransform(p, _, _),
+            ) => p.discard_partial(),
+            Some(
+                ConverterState::JsonToJson(reader, _, _)
+                | ConverterState::JsonToJsonTransform(reader, _, _, _)
+                | ConverterState::JsonToNdjson(reader)
+                | ConverterState::JsonToNdjsonTransform(reader, _)
+                | ConverterState::JsonToCsv(reader, _)
+                | ConverterState::JsonToCsvTransform(reader, _, _)
+                | ConverterState::JsonToXml(reader, _)
+                | ConverterState::JsonToXmlTransform(reader, _, _),
+            ) => reader.discard_partial(),
+            Some(ConverterState::NeedsDetection(buffer)) => buffer.clear(),
+            #[cfg(feature = "parquet")]
+            Some(ConverterState::ToParquet(inner, _)) => Self::discard_state_partial(Some(inner)),
+            #[cfg(feature = "xlsx")]
+            Some(ConverterState::FromXlsx(parser, _)) => parser.discard_partial(),
+            #[cfg(feature = "yaml")]
+            Some(ConverterState::FromYaml(parser, _)) => parser.discard_partial(),
+            #[cfg(feature = "yaml")]
+            Some(ConverterState::ToYaml(inner, _)) => Self::discard_state_partial(Some(inner)),
+            // A JSON document passed through can't be cut short and resumed
+            _ => {}
+        }
     }
 
-    fn create_state(config: &ConverterConfig) -> ConverterState {
-        let transform_plan = config.transform.clone();
-        match (config.input_format, config.output_format) {
-            (Format::Csv, Format::Ndjson) => {
-                let csv_config = config.csv_config.clone().unwrap_or_default();
-                if let Some(plan) = transform_plan {
-                    ConverterState::CsvToNdjsonTransform(
-                        CsvParser::new(csv_config, config.chunk_target_bytes),
-                        TransformEngine::new(plan),
-                    )
-                } else {
-                    ConverterState::CsvToNdjson(CsvParser::new(csv_config, config.chunk_target_bytes))
-                }
+    /// Record a trace event when trace mode is enabled.
+    fn trace(&mut self, event: impl FnOnce() -> TraceEvent) {
+        let Some(capacity) = self.config.trace_capacity else {
+            return;
+        };
+        self.trace_log
+            .get_or_insert_with(|| TraceLog::new(capacity))
+            .record(event());
+    }
+
+    fn state_name(&self) -> &'static str {
+        self.state.as_ref().map(converter_state_name).unwrap_or("Failed")
+    }
+
+    /// Enforce `max_output_bytes`: output beyond the limit is queued for `drain()`.
+    fn cap_output(&mut self, output: Vec<u8>) -> Vec<u8> {
+        let Some(limit) = self.config.max_output_bytes else {
+            // Output that didn't fit a caller's buffer goes first
+            if self.pending_output.is_empty() {
+                return output;
             }
-            (Format::Csv, Format::Json) => {
-                // CSV -> NDJSON -> JSON pipeline
-                let csv_config = config.csv_config.clone().unwrap_or_default();
-                let csv_parser = CsvParser::new(csv_config, config.chunk_target_bytes);
-                let ndjson_parser = NdjsonParser::new(config.chunk_target_bytes);
+            self.pending_output.extend(output);
+            return self.take_pending(usize::MAX);
+        };
+        if self.pending_output.is_empty() && output.len() <= limit {
+            return output;
+        }
+        self.pending_output.extend(output);
+        self.take_pending(limit)
+    }
+
+    /// Hold push output back until `min_output_bytes` of it are ready. Output
+    /// that `max_output_bytes` split sends the batch on right away, ahead of
+    /// the parts waiting for `drain()`.
+    fn batch_output(&mut self, output: Vec<u8>) -> Vec<u8> {
+        let Some(min) = self.config.min_output_bytes else {
+            return output;
+        };
+        if self.batched_output.is_empty() && output.len() >= min {
+            return output;
+        }
+        self.batched_output.extend_from_slice(&output);
+        buffer_pool::release_shared(output);
+        if self.batched_output.len() < min && self.pending_output.is_empty() {
+            return Vec::new();
+        }
+        self.release_batch();
+        self.take_pending(self.config.max_output_bytes.unwrap_or(usize::MAX))
+    }
+
+    /// Queue the output held by `batch_output` ahead of any waiting for
+    /// `drain()`, for `flush` and `finish` to return.
+    fn release_batch(&mut self) {
+        if self.batched_output.is_empty() {
+            return;
+        }
+        let mut queued = VecDeque::from(std::mem::take(&mut self.batched_output));
+        queued.append(&mut self.pending_output);
+        self.pending_output = queued;
+    }
+
+    /// Write `output` and any output waiting for `drain()` to the sink, if
+    /// one is set, and return what's left for the caller.
+    fn deliver(&mut self, output: Vec<u8>) -> Result<Vec<u8>> {
+        let Some(mut sink) = self.sink.take() else {
+            return Ok(output);
+        };
+        let mut written = if output.is_empty() { Ok(()) } else { sink.write_output(&output) };
+        while written.is_ok() && !self.pending_output.is_empty() {
+            written = sink.write_output(&self.drain());
+        }
+        self.sink = Some(sink);
+        match written {
+            Ok(()) => Ok(Vec::new()),
+            Err(err) => Err(self.fail(err)),
+        }
+    }
+
+    /// Copy as much of `result` as fits into `output`, queueing the rest
+    /// ahead of any output already waiting for `drain()`.
+    fn write_output(&mut self, result: Vec<u8>, output: &mut [u8]) -> usize {
+        let written = result.len().min(output.len());
+        output[..written].copy_from_slice(&result[..written]);
+        for &byte in result[written..].iter().rev() {
+            self.pending_output.push_front(byte);
+        }
+        buffer_pool::release_shared(result);
+        written
+    }
+
+    fn take_pending(&mut self, limit: usize) -> Vec<u8> {
+        let mut end = limit.min(self.pending_output.len());
+        if end < self.pending_output.len() {
+            // Prefer splitting after a newline so parts end between records.
+            if let Some(pos) = self.pending_output.range(..end).rposition(|&b| b == b'\n') {
+                end = pos + 1;
+            }
+        }
+        self.pending_output.drain(..end).collect()
+    }
+
+    /// Pass output as returned to the caller to the chunker and the
+    /// manifest; `last` also ends the last chunk and builds the manifest.
+    fn note_output(&mut self, output: &[u8], last: bool) {
+        if let Some(chunker) = self.chunker.as_mut() {
+            chunker.write(output);
+            if last {
+                chunker.finish();
+            }
+        }
+        if let Some(manifest) = self.manifest.as_mut() {
+            manifest.output(output);
+            if last {
+                let counts = ManifestCounts {
+                    records: self.stats.records_processed,
+                    records_skipped: self.record_errors.skipped(),
+                    warnings: self.warnings.list().len(),
+                };
+                manifest.finish(&self.config, self.detection.as_ref(), counts);
+            }
+        }
+    }
+
+    /// Compress output when `output_compression` is set; `last` also writes
+    /// the stream trailer.
+    fn compress_output(&mut self, output: Vec<u8>, last: bool) -> Result<Vec<u8>> {
+        let Some(compressor) = self.compressor.as_mut() else {
+            return Ok(output);
+        };
+        let _span = stage_span!(DEBUG, "compress", bytes = output.len());
+        let mut compressed = compressor.push(&output);
+        if last {
+            compressed = compressed.and_then(|mut data| {
+                data.extend(compressor.finish()?);
+                Ok(data)
+            });
+            self.compressor = None;
+        }
+        compressed.map_err(|err| self.fail(err))
+    }
+
+    /// Apply `output_columns` to NDJSON output; the other writers apply it
+    /// themselves.
+    fn map_output_columns(&mut self, output: Vec<u8>, last: bool) -> Vec<u8> {
+        let Some(columns) = &self.config.output_columns else {
+            return output;
+        };
+        if self.config.output_format != Format::Ndjson {
+            return output;
+        }
+        let _span = stage_span!(DEBUG, "output_columns", bytes = output.len());
+        let mapper = self.ndjson_columns.get_or_insert_with(|| NdjsonColumns::new(columns));
+        let mut result = mapper.write(&output);
+        if last {
+            result.extend(mapper.finish());
+        }
+        result
+    }
+
+    /// Apply the configured `float_format` and `integer_strings` to JSON and
+    /// NDJSON output; CSV and XML writers format numbers themselves.
+    fn format_numbers(&mut self, output: Vec<u8>, last: bool) -> Vec<u8> {
+        if self.config.float_format.is_none() && self.config.integer_strings.is_none() {
+            return output;
+        }
+        if !matches!(self.config.output_format, Format::Json | Format::Ndjson) {
+            return output;
+        }
+        let _span = stage_span!(DEBUG, "format_numbers", bytes = output.len());
+        let config = &self.config;
+        let rewriter = self.number_rewriter.get_or_insert_with(|| {
+            NumberRewriter::new(config.float_format, config.integer_strings.clone())
+        });
+        let mut result = rewriter.write(&output);
+        if last {
+            result.extend(rewriter.finish());
+        }
+        result
+    }
+
+    /// Apply the configured `ndjson_output` framing to NDJSON output.
+    fn frame_ndjson_output(&mut self, output: Vec<u8>) -> Vec<u8> {
+        let Some(config) = self.config.ndjson_output else {
+            return output;
+        };
+        if self.config.output_format != Format::Ndjson {
+            return output;
+        }
+        let _span = stage_span!(DEBUG, "frame_output", bytes = output.len());
+        self.ndjson_writer
+            .get_or_insert_with(|| ndjson_writer::NdjsonWriter::new(config))
+            .write(&output)
+    }
+
+    fn capture_stop_match(&mut self, engine: &TransformEngine) {
+        if self.stop_match.is_none() {
+            self.stop_match = engine.matched_record().cloned();
+        }
+        self.records_done |= engine.reached_max_records();
+    }
+
+    /// Pick the input format from the first sample when `auto_detect.input_format`
+    /// is set. Input passed the UTF-8 filter untouched until now, so the sample
+    /// is filtered for the detected format on its way out.
+    fn resolve_input_format(&mut self, sample: Vec<u8>) -> Result<Vec<u8>> {
+        if !self.config.auto_detect.input_format {
+            return Ok(sample);
+        }
+        let Some(format) = detect::detect_format(&sample) else {
+            let err = ConvertError::InvalidConfig("Could not detect the input format".to_string());
+            return Err(self.fail(err));
+        };
+        if self.debug {
+            debug!("Auto-detected input format: {}", format.to_string_js());
+        }
+        self.trace(|| TraceEvent::Detection {
+            sample_bytes: sample.len(),
+            decision: format!("input format {}", format.to_string_js()),
+        });
+        self.config.input_format = format;
+        self.config.auto_detect.input_format = false;
+        self.detection.get_or_insert_with(DetectionResult::default).input_format =
+            Some(format.to_string_js());
+        self.utf8_filter = Utf8Filter::for_config(&self.config);
+        Ok(self.utf8_filter.push(&sample, &self.warnings).into_owned())
+    }
+
+    /// Warn about input the detection check found suspect and, when input was
+    /// held for the check, apply what re-detection found. Returns the held
+    /// input, which the pipeline hasn't seen yet.
+    fn settle_detection_check(&mut self, watch: DetectionWatch, verdict: Verdict) -> Option<Vec<u8>> {
+        if let Verdict::Suspect(message, correction) = verdict {
+            if self.debug {
+                debug!("Detection check: {}", message);
+            }
+            self.warnings.warn(WarningCode::DetectionSuspect, || message);
+            if let (true, Some(correction)) = (watch.holds_input(), correction) {
+                self.apply_correction(correction, watch.buffered_bytes());
+            }
+        }
+        watch.holds_input().then(|| watch.into_sample())
+    }
+
+    /// Rebuild the pipeline, which hasn't seen any input yet, with a re-detected setting.
+    fn apply_correction(&mut self, correction: Correction, sample_bytes: usize) {
+        let result = self.detection.get_or_insert_with(DetectionResult::default);
+        let decision = match correction {
+            Correction::CsvDelimiter(delimiter) => {
+                // TSV input with another delimiter is plain CSV
+                if self.config.input_format == Format::Tsv {
+                    self.config.input_format = Format::Csv;
+                }
+                let mut csv_config = self.config.csv_config.clone().unwrap_or_default();
+                csv_config.delimiter = delimiter;
+                self.config.csv_config = Some(csv_config);
+                result.delimiter = Some(char::from(delimiter).to_string());
+                format!("redetected csv delimiter '{}'", char::from(delimiter))
+            }
+            Correction::XmlRecordElement(record_element) => {
+                let mut xml_config = self.config.xml_config.clone().unwrap_or_default();
+                xml_config.record_element = record_element.clone();
+                self.config.xml_config = Some(xml_config);
+                let decision = format!("redetected xml record element '{}'", record_element);
+                result.record_element = Some(record_element);
+                decision
+            }
+        };
+        self.trace(|| TraceEvent::Detection {
+            sample_bytes,
+            decision,
+        });
+        let new_state = Self::create_state(&self.config, &self.warnings, &self.record_errors, &self.column_kinds);
+        let (from, to) = (self.state_name(), converter_state_name(&new_state));
+        self.trace(|| TraceEvent::StateChange { from, to });
+        self.state = Some(new_state);
+    }
+
+    /// Auto-detect configuration from a sample and initialize the converter state
+    fn auto_detect_and_initialize(&mut self, sample: &[u8]) -> Result<()> {
+        let _span = stage_span!(DEBUG, "detect", sample_bytes = sample.len());
+        if self.debug {
+            debug!("Auto-detecting configuration from {} byte sample", sample.len());
+        }
+
+        let auto_detect = self.config.auto_detect;
+        let decision = match self.config.input_format {
+            Format::Csv if auto_detect.applies_to(Format::Csv) => {
+                let mut csv_config = self.config.csv_config.clone().unwrap_or_default();
+                let rows = detect::strip_comment_lines(sample, csv_config.comment_char);
+                if let Some(detection) = detect::detect_csv(&rows) {
+                    let result = self.detection.get_or_insert_with(DetectionResult::default);
+                    if auto_detect.csv_delimiter {
+                        csv_config.delimiter = detection.delimiter;
+                        result.delimiter = Some(char::from(detection.delimiter).to_string());
+                    }
+                    if auto_detect.csv_quote {
+                        csv_config.quote = detection.quote;
+                        csv_config.escape = Some(detection.quote);
+                        result.quote = Some(char::from(detection.quote).to_string());
+                    }
+                    if auto_detect.csv_has_headers {
+                        csv_config.has_headers = detection.has_headers;
+                        result.has_headers = Some(detection.has_headers);
+                    }
+                    result.fields = detection.fields.clone();
+                    
+                    let delim_char = char::from(csv_config.delimiter);
+                    if self.debug {
+                        debug!("Auto-detected CSV delimiter: '{}' ({} fields)", delim_char, detection.fields.len());
+                    }
+                    let mut decision = format!("csv delimiter '{}' ({} fields)", delim_char, detection.fields.len());
+                    if csv_config.quote != b'"' {
+                        decision.push_str(&format!(", quote '{}'", char::from(csv_config.quote)));
+                    }
+                    if !csv_config.has_headers {
+                        decision.push_str(", no header row");
+                    }
+                    self.config.csv_config = Some(csv_config);
+                    decision
+                } else {
+                    if self.debug {
+                        debug!("CSV auto-detection failed, using default config");
+                    }
+                    self.warnings.warn(WarningCode::DetectionFallback, || {
+                        "CSV delimiter not detected, using ','".to_string()
+                    });
+                    "csv detection failed, using default config".to_string()
+                }
+            }
+            Format::Xml if auto_detect.xml_record_element => {
+                if let Some(detection) = detect::detect_xml(sample) {
+                    if let Some(record_element) = detection.record_element {
+                        let mut xml_config = self.config.xml_config.clone().unwrap_or_default();
+                        xml_config.record_element = record_element.clone();
+                        self.config.xml_config = Some(xml_config.clone());
+                        let result = self.detection.get_or_insert_with(DetectionResult::default);
+                        result.record_element = Some(record_element.clone());
+                        result.fields = detection.elements.clone();
+                        
+                        if self.debug {
+                            debug!("Auto-detected XML record element: '{}'", record_element);
+                        }
+                        format!("xml record element '{}'", record_element)
+                    } else {
+                        self.warnings.warn(WarningCode::DetectionFallback, || {
+                            "XML record element not detected, using the default".to_string()
+                        });
+                        "xml record element not found, using default config".to_string()
+                    }
+                } else {
+                    if self.debug {
+                        debug!("XML auto-detection failed, using default config");
+                    }
+                    self.warnings.warn(WarningCode::DetectionFallback, || {
+                        "XML structure not detected, using the default record element".to_string()
+                    });
+                    "xml detection failed, using default config".to_string()
+                }
+            }
+            _ => {
+                // No auto-detection needed for other formats
+                "no detection needed".to_string()
+            }
+        };
+        self.trace(|| TraceEvent::Detection {
+            sample_bytes: sample.len(),
+            decision,
+        });
+
+        // Create the proper state with detected/default config
+        self.detection_watch = DetectionWatch::for_config(&self.config);
+        let new_state = Self::create_state(&self.config, &self.warnings, &self.record_errors, &self.column_kinds);
+        let (from, to) = (self.state_name(), converter_state_name(&new_state));
+        self.trace(|| TraceEvent::StateChange { from, to });
+        self.state = Some(new_state);
+
+        Ok(())
+    }
+
+    fn create_csv_parser(
+        config: &ConverterConfig,
+        warnings: &Warnings,
+        errors: &RecordErrors,
+    ) -> CsvParser {
+        let csv_config = config.csv_config.clone().unwrap_or_default();
+        let parser = CsvParser::new(csv_config, config.chunk_target_bytes)
+            .with_ordered(config.ordered)
+            .with_threads(config.threads)
+            .with_limits(config.limits)
+            .with_invalid_utf8(config.invalid_utf8)
+            .with_warnings(warnings.clone())
+            .with_record_errors(errors.clone());
+        // Skip materializing columns the transform never reads
+        let mut columns = config.transform.as_ref().and_then(TransformPlan::required_columns);
+        match &config.projection {
+            Some(FieldProjection::Select(fields)) => {
+                columns = Some(match columns {
+                    Some(columns) => columns.into_iter().filter(|column| fields.contains(column)).collect(),
+                    None => fields.clone(),
+                });
+            }
+            Some(FieldProjection::Drop(fields)) => {
+                if let Some(columns) = columns.as_mut() {
+                    columns.retain(|column| !fields.contains(column));
+                }
+            }
+            None => {}
+        }
+        match columns {
+            Some(columns) => parser.with_projection(columns),
+            None => parser,
+        }
+    }
+
+    fn create_ndjson_parser(
+        config: &ConverterConfig,
+        warnings: &Warnings,
+        errors: &RecordErrors,
+    ) -> NdjsonParser {
+        NdjsonParser::new(config.chunk_target_bytes)
+            .with_ordered(config.ordered)
+            .with_threads(config.threads)
+            .with_warnings(warnings.clone())
+            .with_record_errors(errors.clone())
+            .with_json_output(config.json_output)
+            .with_output_columns(config.output_columns.as_deref())
+    }
+
+    fn create_xml_parser(config: &ConverterConfig) -> XmlParser {
+        let xml_config = config.xml_config.clone().unwrap_or_default();
+        XmlParser::new(xml_config, config.chunk_target_bytes).with_limits(config.limits)
+    }
+
+    fn create_json_reader(config: &ConverterConfig) -> JsonRecordReader {
+        match &config.json_input.records_path {
+            Some(path) => JsonRecordReader::new().with_records_path(path),
+            None => JsonRecordReader::new(),
+        }
+    }
+
+    fn create_csv_writer(
+        config: &ConverterConfig,
+        warnings: &Warnings,
+        errors: &RecordErrors,
+        column_kinds: &ColumnKinds,
+    ) -> csv_writer::CsvWriter {
+        csv_writer::CsvWriter::new()
+            .with_config(config.csv_writer.clone())
+            .with_collision_policy(config.flatten_collision)
+            .with_warnings(warnings.clone())
+            .with_record_errors(errors.clone())
+            .with_column_kinds(column_kinds.clone())
+            .with_float_format(config.float_format)
+            .with_output_columns(config.output_columns.clone())
+    }
+
+    fn create_xml_writer(config: &ConverterConfig) -> xml_parser::XmlWriter {
+        xml_parser::XmlWriter::new()
+            .with_config(config.xml_output.clone())
+            .with_float_format(config.float_format)
+            .with_output_columns(config.output_columns.clone())
+    }
+
+    /// The state a new converter starts in: waiting for a sample when there is
+    /// something to auto-detect, otherwise the pipeline itself.
+    fn initial_state(
+        config: &ConverterConfig,
+        warnings: &Warnings,
+        errors: &RecordErrors,
+        column_kinds: &ColumnKinds,
+    ) -> ConverterState {
+        if config.auto_detect.applies_to(config.input_format) {
+            ConverterState::NeedsDetection(Vec::new())
+        } else {
+            Self::create_state(config, warnings, errors, column_kinds)
+        }
+    }
+
+    fn create_state(
+        config: &ConverterConfig,
+        warnings: &Warnings,
+        errors: &RecordErrors,
+        column_kinds: &ColumnKinds,
+    ) -> ConverterState {
+        // The CSV parser applies `selectFields` itself
+        let projection = config.projection.clone().filter(|projection| {
+            !(matches!(config.input_format, Format::Csv | Format::Tsv)
+                && matches!(projection, FieldProjection::Select(_)))
+        });
+        let transform_plan = match &config.transform {
+            Some(plan) => Some(plan.clone()),
+            // Dedupe, sampling, sort and projection run in the transform stage
+            None if config.dedupe.is_some()
+                || config.sort.is_some()
+                || projection.is_some()
+                || config.max_records.is_some()
+                || config.sample_every_n.is_some() =>
+            {
+                Some(TransformPlan::passthrough())
+            }
+            None => None,
+        }
+        .map(|plan| {
+            plan.with_warnings(warnings.clone())
+                .with_record_errors(errors.clone())
+                .with_projection(projection)
+                .with_dedupe(config.dedupe.clone())
+                .with_sampling(config.max_records, config.sample_every_n)
+                .with_sort(config.sort.clone())
+        });
+        match (config.input_format, config.output_format) {
+            (Format::Tsv, _) | (_, Format::Tsv) => {
+                Self::create_state(&config.tsv_as_csv(), warnings, errors, column_kinds)
+            }
+            #[cfg(feature = "parquet")]
+            (Format::Parquet, _) => ConverterState::Unsupported(
+                "Parquet is only supported as an output format".to_string(),
+            ),
+            #[cfg(feature = "parquet")]
+            (_, Format::Parquet) => {
+                let ndjson_config = ConverterConfig {
+                    output_format: Format::Ndjson,
+                    ..config.clone()
+                };
+                ConverterState::ToParquet(
+                    Box::new(Self::create_state(&ndjson_config, warnings, errors, column_kinds)),
+                    parquet_writer::ParquetWriter::new(config.parquet_config),
+                )
+            }
+            #[cfg(feature = "xlsx")]
+            (_, Format::Xlsx) => ConverterState::Unsupported(
+                "XLSX is only supported as an input format".to_string(),
+            ),
+            #[cfg(feature = "xlsx")]
+            (Format::Xlsx, _) => {
+                let ndjson_config = ConverterConfig {
+                    input_format: Format::Ndjson,
+                    ..config.clone()
+                };
+                ConverterState::FromXlsx(
+                    XlsxParser::new(config.xlsx_config.clone()),
+                    Box::new(Self::create_state(&ndjson_config, warnings, errors, column_kinds)),
+                )
+            }
+            #[cfg(feature = "yaml")]
+            (_, Format::Yaml) => {
+                let ndjson_config = ConverterConfig {
+                    output_format: Format::Ndjson,
+                    ..config.clone()
+                };
+                ConverterState::ToYaml(
+                    Box::new(Self::create_state(&ndjson_config, warnings, errors, column_kinds)),
+                    YamlWriter::new(),
+                )
+            }
+            #[cfg(feature = "yaml")]
+            (Format::Yaml, _) => {
+                let ndjson_config = ConverterConfig {
+                    input_format: Format::Ndjson,
+                    ..config.clone()
+                };
+                ConverterState::FromYaml(
+                    YamlParser::new(),
+                    Box::new(Self::create_state(&ndjson_config, warnings, errors, column_kinds)),
+                )
+            }
+            (Format::Csv, Format::Ndjson) => {
+                if let Some(plan) = transform_plan {
+                    ConverterState::CsvToNdjsonTransform(
+                        Self::create_csv_parser(config, warnings, errors),
+                        TransformEngine::new(plan),
+                    )
+                } else {
+                    ConverterState::CsvToNdjson(Self::create_csv_parser(config, warnings, errors))
+                }
+            }
+            (Format::Csv, Format::Json) => {
+                // CSV -> NDJSON -> JSON pipeline
+                let csv_parser = Self::create_csv_parser(config, warnings, errors);
+                let ndjson_parser = Self::create_ndjson_parser(config, warnings, errors);
                 if let Some(plan) = transform_plan {
                     ConverterState::CsvToJsonTransform(
                         csv_parser,
@@ -1755,26 +3364,24 @@ impl Converter {
             }
             (Format::Csv, Format::Csv) => {
                 // CSV to CSV
-                let csv_config = config.csv_config.clone().unwrap_or_default();
                 if let Some(plan) = transform_plan {
                     ConverterState::CsvToCsvTransform(
-                        CsvParser::new(csv_config, config.chunk_target_bytes),
+                        Self::create_csv_parser(config, warnings, errors),
                         TransformEngine::new(plan),
-                        csv_writer::CsvWriter::new(),
+                        Self::create_csv_writer(config, warnings, errors, column_kinds),
                     )
                 } else {
                     // For CSV to CSV without transform, use passthrough via CSV parser + writer
                     ConverterState::CsvPassthrough(
-                        CsvParser::new(csv_config.clone(), config.chunk_target_bytes),
-                        csv_writer::CsvWriter::new()
+                        Self::create_csv_parser(config, warnings, errors),
+                        Self::create_csv_writer(config, warnings, errors, column_kinds)
                     )
                 }
             }
             (Format::Csv, Format::Xml) => {
                 // CSV -> NDJSON -> XML pipeline
-                let csv_config = config.csv_config.clone().unwrap_or_default();
-                let csv_parser = CsvParser::new(csv_config, config.chunk_target_bytes);
-                let xml_writer = xml_parser::XmlWriter::new();
+                let csv_parser = Self::create_csv_parser(config, warnings, errors);
+                let xml_writer = Self::create_xml_writer(config);
                 if let Some(plan) = transform_plan {
                     ConverterState::CsvToXmlTransform(
                         csv_parser,
@@ -1789,23 +3396,23 @@ impl Converter {
                 if let Some(plan) = transform_plan {
                     ConverterState::NdjsonTransform(TransformEngine::new(plan))
                 } else {
-                    ConverterState::NdjsonPassthrough(NdjsonParser::new(config.chunk_target_bytes))
+                    ConverterState::NdjsonPassthrough(Self::create_ndjson_parser(config, warnings, errors))
                 }
             }
             (Format::Ndjson, Format::Json) => {
                 if let Some(plan) = transform_plan {
                     ConverterState::NdjsonToJsonTransform(
                         TransformEngine::new(plan),
-                        NdjsonParser::new(config.chunk_target_bytes),
+                        Self::create_ndjson_parser(config, warnings, errors),
                         true,
                     )
                 } else {
-                    ConverterState::NdjsonToJson(NdjsonParser::new(config.chunk_target_bytes), true)
+                    ConverterState::NdjsonToJson(Self::create_ndjson_parser(config, warnings, errors), true)
                 }
             }
             (Format::Ndjson, Format::Csv) => {
-                let ndjson_parser = NdjsonParser::new(config.chunk_target_bytes);
-                let csv_writer = csv_writer::CsvWriter::new();
+                let ndjson_parser = Self::create_ndjson_parser(config, warnings, errors);
+                let csv_writer = Self::create_csv_writer(config, warnings, errors, column_kinds);
                 if let Some(plan) = transform_plan {
                     ConverterState::NdjsonToCsvTransform(TransformEngine::new(plan), csv_writer)
                 } else {
@@ -1813,8 +3420,8 @@ impl Converter {
                 }
             }
             (Format::Ndjson, Format::Xml) => {
-                let ndjson_parser = NdjsonParser::new(config.chunk_target_bytes);
-                let xml_writer = xml_parser::XmlWriter::new();
+                let ndjson_parser = Self::create_ndjson_parser(config, warnings, errors);
+                let xml_writer = Self::create_xml_writer(config);
                 if let Some(plan) = transform_plan {
                     ConverterState::NdjsonToXmlTransform(TransformEngine::new(plan), xml_writer)
                 } else {
@@ -1822,20 +3429,18 @@ impl Converter {
                 }
             }
             (Format::Xml, Format::Ndjson) => {
-                let xml_config = config.xml_config.clone().unwrap_or_default();
                 if let Some(plan) = transform_plan {
                     ConverterState::XmlToNdjsonTransform(
-                        XmlParser::new(xml_config, config.chunk_target_bytes),
+                        Self::create_xml_parser(config),
                         TransformEngine::new(plan),
                     )
                 } else {
-                    ConverterState::XmlToNdjson(XmlParser::new(xml_config, config.chunk_target_bytes))
+                    ConverterState::XmlToNdjson(Self::create_xml_parser(config))
                 }
             }
             (Format::Xml, Format::Json) => {
-                let xml_config = config.xml_config.clone().unwrap_or_default();
-                let xml_parser = XmlParser::new(xml_config, config.chunk_target_bytes);
-                let ndjson_parser = NdjsonParser::new(config.chunk_target_bytes);
+                let xml_parser = Self::create_xml_parser(config);
+                let ndjson_parser = Self::create_ndjson_parser(config, warnings, errors);
                 if let Some(plan) = transform_plan {
                     ConverterState::XmlToJsonTransform(
                         xml_parser,
@@ -1848,9 +3453,8 @@ impl Converter {
                 }
             }
             (Format::Xml, Format::Csv) => {
-                let xml_config = config.xml_config.clone().unwrap_or_default();
-                let xml_parser = XmlParser::new(xml_config, config.chunk_target_bytes);
-                let csv_writer = csv_writer::CsvWriter::new();
+                let xml_parser = Self::create_xml_parser(config);
+                let csv_writer = Self::create_csv_writer(config, warnings, errors, column_kinds);
                 if let Some(plan) = transform_plan {
                     ConverterState::XmlToCsvTransform(
                         xml_parser,
@@ -1862,60 +3466,71 @@ impl Converter {
                 }
             }
             (Format::Xml, Format::Xml) => {
-                // XML passthrough
-                let xml_config = config.xml_config.clone().unwrap_or_default();
-                if let Some(plan) = transform_plan {
+                // Passthrough copies the input, so `outputColumns` needs the writer
+                let plan = transform_plan
+                    .or_else(|| config.output_columns.is_some().then(TransformPlan::passthrough));
+                if let Some(plan) = plan {
                     ConverterState::XmlToXmlTransform(
-                        XmlParser::new(xml_config, config.chunk_target_bytes),
+                        Self::create_xml_parser(config),
                         TransformEngine::new(plan),
-                        xml_parser::XmlWriter::new(),
+                        Self::create_xml_writer(config),
                     )
                 } else {
-                    ConverterState::XmlPassthrough(XmlParser::new(xml_config, config.chunk_target_bytes))
+                    ConverterState::XmlPassthrough(Self::create_xml_parser(config))
                 }
             }
             (Format::Json, Format::Json) => {
                 if let Some(plan) = transform_plan {
                     ConverterState::JsonToJsonTransform(
-                        JsonParser::new(),
+                        Self::create_json_reader(config),
                         TransformEngine::new(plan),
-                        NdjsonParser::new(config.chunk_target_bytes),
+                        Self::create_ndjson_parser(config, warnings, errors),
+                        true,
+                    )
+                } else if config.json_input.records_path.is_some() || config.output_columns.is_some() {
+                    ConverterState::JsonToJson(
+                        Self::create_json_reader(config),
+                        Self::create_ndjson_parser(config, warnings, errors),
                         true,
                     )
                 } else {
-                    ConverterState::JsonPassthrough(JsonParser::new())
+                    let json_output = config.json_output;
+                    ConverterState::JsonPassthrough(
+                        JsonValidator::new(),
+                        json_output.pretty.then(|| JsonPrettifier::new(json_output.indent)),
+                    )
                 }
             }
             (Format::Json, Format::Ndjson) => {
                 if let Some(plan) = transform_plan {
-                    ConverterState::JsonToNdjsonTransform(JsonParser::new(), TransformEngine::new(plan))
+                    ConverterState::JsonToNdjsonTransform(Self::create_json_reader(config), TransformEngine::new(plan))
                 } else {
-                    ConverterState::JsonToNdjson(JsonParser::new())
+                    ConverterState::JsonToNdjson(Self::create_json_reader(config))
                 }
             }
             (Format::Json, Format::Csv) => {
-                let json_parser = JsonParser::new();
-                let csv_writer = csv_writer::CsvWriter::new();
+                let reader = Self::create_json_reader(config);
+                let csv_writer = Self::create_csv_writer(config, warnings, errors, column_kinds);
                 if let Some(plan) = transform_plan {
-                    ConverterState::JsonToCsvTransform(json_parser, TransformEngine::new(plan), csv_writer)
+                    ConverterState::JsonToCsvTransform(reader, TransformEngine::new(plan), csv_writer)
                 } else {
-                    ConverterState::JsonToCsv(json_parser, csv_writer)
+                    ConverterState::JsonToCsv(reader, csv_writer)
                 }
             }
             (Format::Json, Format::Xml) => {
-                let json_parser = JsonParser::new();
-                let xml_writer = xml_parser::XmlWriter::new();
+                let reader = Self::create_json_reader(config);
+                let xml_writer = Self::create_xml_writer(config);
                 if let Some(plan) = transform_plan {
-                    ConverterState::JsonToXmlTransform(json_parser, TransformEngine::new(plan), xml_writer)
+                    ConverterState::JsonToXmlTransform(reader, TransformEngine::new(plan), xml_writer)
                 } else {
-                    ConverterState::JsonToXml(json_parser, xml_writer)
+                    ConverterState::JsonToXml(reader, xml_writer)
                 }
             }
         }
     }
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 fn parse_csv_config(value: JsValue) -> Option<CsvConfig> {
     let input: CsvConfigInput = deserialize_optional(value)?;
     let mut config = CsvConfig::default();
@@ -1941,18 +3556,63 @@ fn parse_csv_config(value: JsValue) -> Option<CsvConfig> {
         config.trim_whitespace = trim_whitespace;
     }
 
+    if let Some(infer_types) = input.infer_types {
+        config.infer_types = infer_types;
+    }
+
+    if let Some(column_types) = input.column_types {
+        config.column_types = column_types;
+    }
+
+    if let Some(columns) = input.columns {
+        config.columns = columns;
+    }
+
+    if let Some(unflatten) = input.unflatten {
+        config.unflatten = unflatten;
+    }
+
+    if let Some(skip_rows) = input.skip_rows {
+        config.skip_rows = skip_rows;
+    }
+
+    if let Some(header_row) = input.header_row {
+        config.header_row = header_row;
+    }
+
+    config.max_rows = input.max_rows.or(config.max_rows);
+
+    if let Some(value) = input.comment_char {
+        config.comment_char = value.as_bytes().first().copied();
+    }
+
     Some(config)
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 fn parse_xml_config(value: JsValue) -> Option<XmlConfig> {
     let input: XmlConfigInput = deserialize_optional(value)?;
     let mut config = XmlConfig::default();
 
-    if let Some(value) = input.record_element {
-        if !value.is_empty() {
+    match input.record_element {
+        Some(RecordElementInput::One(value)) if !value.is_empty() => {
             config.record_element = value;
         }
+        Some(RecordElementInput::Many(mut values)) if !values.is_empty() => {
+            config.record_element = values.remove(0);
+            config.record_elements = values;
+        }
+        _ => {}
+    }
+
+    config.record_path = input.record_path.filter(|path| !path.is_empty());
+
+    if let Some(tag_element) = input.tag_element {
+        config.tag_element = tag_element;
+    }
+
+    if let Some(namespaces) = input.namespaces {
+        config.namespaces = namespaces;
     }
 
     if let Some(trim_text) = input.trim_text {
@@ -1970,18 +3630,498 @@ fn parse_xml_config(value: JsValue) -> Option<XmlConfig> {
     Some(config)
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+thread_local! {
+    static EXPRESSION_FUNCTIONS: std::cell::RefCell<FunctionRegistry> =
+        std::cell::RefCell::new(FunctionRegistry::new());
+}
+
+/// Adapter that exposes a JS function as a custom expression function.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+struct JsExpressionFunction {
+    name: String,
+    function: js_sys::Function,
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl ExpressionFunction for JsExpressionFunction {
+    fn call(&self, args: &[serde_json::Value]) -> Result<serde_json::Value> {
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        let js_args = Array::new();
+        for arg in args {
+            let value = arg.serialize(&serializer).map_err(|e| {
+                ConvertError::Transform(format!("{}(): {}", self.name, e))
+            })?;
+            js_args.push(&value);
+        }
+
+        let result = self.function.apply(&JsValue::NULL, &js_args).map_err(|e| {
+            ConvertError::Transform(format!("{}() threw: {:?}", self.name, e))
+        })?;
+        if result.is_undefined() {
+            return Ok(serde_json::Value::Null);
+        }
+
+        serde_wasm_bindgen::from_value(result).map_err(|e| {
+            ConvertError::Transform(format!("{}() returned an unsupported value: {}", self.name, e))
+        })
+    }
+}
+
+/// Register a JS function callable from transform `compute` expressions.
+/// Only converters created after registration see the function.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen(js_name = registerExpressionFunction)]
+pub fn register_expression_function(
+    name: String,
+    function: js_sys::Function,
+) -> std::result::Result<(), JsValue> {
+    EXPRESSION_FUNCTIONS
+        .with(|registry| {
+            registry
+                .borrow_mut()
+                .register(name.clone(), JsExpressionFunction { name, function })
+        })
+        .map_err(JsValue::from)
+}
+
+/// Convert a whole input in one call, detecting whatever isn't configured.
+/// `config` takes the same fields as `createTransformStream`.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen]
+pub fn convert(input: &[u8], config: JsValue) -> std::result::Result<Vec<u8>, JsValue> {
+    let config = wasm_config_from_object(&config, "convert")?;
+    convert_bytes(input, config).map_err(JsValue::from)
+}
+
+/// `chunkTargetBytes` for converters configured from a JS object, where it
+/// also sizes the slices `createTransformStream` pushes and enqueues.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+const DEFAULT_CHUNK_TARGET_BYTES: usize = 512 * 1024;
+
+/// The configuration for a JS object of `Converter.withConfig` arguments:
+/// `inputFormat` ("auto" when left out), `outputFormat`, `chunkTargetBytes`,
+/// `profile`, `csvConfig`, `xmlConfig`, `transform` and `options`.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn wasm_config_from_object(config: &JsValue, caller: &str) -> std::result::Result<ConverterConfig, JsValue> {
+    let field = |name: &str| Reflect::get(config, &JsValue::from(name)).unwrap_or(JsValue::UNDEFINED);
+    let input_format = field("inputFormat").as_string().unwrap_or_else(|| "auto".to_string());
+    let output_format = field("outputFormat")
+        .as_string()
+        .ok_or_else(|| JsValue::from_str(&format!("{caller} needs an outputFormat")))?;
+    let chunk_target_bytes = field("chunkTargetBytes")
+        .as_f64()
+        .map_or(DEFAULT_CHUNK_TARGET_BYTES, |bytes| bytes as usize)
+        .max(1);
+    wasm_converter_config(
+        &input_format,
+        &output_format,
+        chunk_target_bytes,
+        field("profile").is_truthy(),
+        field("csvConfig"),
+        field("xmlConfig"),
+        field("transform"),
+        field("options"),
+    )
+}
+
+/// The configuration `Converter.withConfig` builds from its JS arguments.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[allow(clippy::too_many_arguments)]
+fn wasm_converter_config(
+    input_format: &str,
+    output_format: &str,
+    chunk_target_bytes: usize,
+    enable_stats: bool,
+    csv_config: JsValue,
+    xml_config: JsValue,
+    transform_config: JsValue,
+    options: JsValue,
+) -> std::result::Result<ConverterConfig, JsValue> {
+    // "auto" starts as CSV and is replaced by the format detected in the first chunk
+    let detect_format = input_format.eq_ignore_ascii_case("auto");
+    let input = if detect_format {
+        Format::Csv
+    } else {
+        Format::from_string(input_format)
+            .ok_or_else(|| ConvertError::InvalidConfig(format!("Invalid input format: {}", input_format)))?
+    };
+    
+    let output = Format::from_string(output_format)
+        .ok_or_else(|| ConvertError::InvalidConfig(format!("Invalid output format: {}", output_format)))?;
+
+    let mut config = ConverterConfig::new(input, output)
+        .with_chunk_size(chunk_target_bytes)
+        .with_stats(enable_stats);
+
+    let csv_provided = parse_csv_config(csv_config.clone());
+    let xml_provided = parse_xml_config(xml_config.clone());
+    let transform_provided = parse_transform_config(transform_config.clone())?;
+
+    if let Some(csv) = csv_provided {
+        config = config.with_csv_config(csv);
+    }
+
+    if let Some(xml) = xml_provided.clone() {
+        config = config.with_xml_config(xml);
+    }
+
+    if let Some(transform) = transform_provided.clone() {
+        config = config.with_transform(transform);
+    }
+
+    config = apply_converter_options(config, options)?;
+
+    // Detect whatever wasn't explicitly provided
+    let csv_input: Option<CsvConfigInput> = deserialize_optional(csv_config);
+    let csv_input = csv_input.as_ref();
+    config = config.with_auto_detect(AutoDetect {
+        input_format: detect_format,
+        csv_delimiter: csv_input.and_then(|i| i.delimiter.as_ref()).is_none(),
+        csv_quote: csv_input.and_then(|i| i.quote.as_ref()).is_none(),
+        csv_has_headers: csv_input.and_then(|i| i.has_headers).is_none(),
+        xml_record_element: xml_provided.is_none() || xml_provided.as_ref().and_then(|_x| {
+            // Check if recordElement or recordPath was explicitly provided
+            let input_obj: Option<XmlConfigInput> = deserialize_optional(xml_config);
+            input_obj.filter(|i| i.record_element.is_some() || i.record_path.is_some())
+        }).is_none(),
+    });
+    Ok(config)
+}
+
+/// Splits a mixed NDJSON stream into one NDJSON output per record type.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen]
+pub struct Demuxer {
+    engine: DemuxEngine,
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen]
+impl Demuxer {
+    #[wasm_bindgen(constructor)]
+    pub fn new(config: JsValue) -> std::result::Result<Demuxer, JsValue> {
+        let input: DemuxConfigInput = serde_wasm_bindgen::from_value(config)
+            .map_err(|e| ConvertError::InvalidConfig(format!("Invalid demux config: {}", e)))?;
+        let functions = EXPRESSION_FUNCTIONS.with(|registry| registry.borrow().clone());
+        let engine = DemuxEngine::compile(input)?.with_functions(functions);
+        Ok(Demuxer { engine })
+    }
+
+    /// Push NDJSON bytes; returns an object mapping route names to NDJSON bytes.
+    pub fn push(&mut self, chunk: &[u8]) -> std::result::Result<JsValue, JsValue> {
+        let result = self.engine.push(chunk)?;
+        demux_result_to_js(result)
+    }
+
+    /// Flush the trailing partial line; returns the same shape as `push`.
+    pub fn finish(&mut self) -> std::result::Result<JsValue, JsValue> {
+        let result = self.engine.finish()?;
+        demux_result_to_js(result)
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn demux_result_to_js(result: DemuxResult) -> std::result::Result<JsValue, JsValue> {
+    let outputs = Object::new();
+    for (name, bytes) in result.outputs {
+        let bytes = js_sys::Uint8Array::from(bytes.as_slice());
+        Reflect::set(&outputs, &JsValue::from_str(&name), &bytes)?;
+    }
+    Ok(outputs.into())
+}
+
+/// Converts input grouped by one field into a separate output per group.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen(js_name = GroupBatcher)]
+pub struct JsGroupBatcher {
+    batcher: GroupBatcher,
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen(js_class = GroupBatcher)]
+impl JsGroupBatcher {
+    /// Takes the arguments of `Converter.withConfig`, after the group field.
+    #[wasm_bindgen(constructor)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        group_by: &str,
+        input_format: &str,
+        output_format: &str,
+        chunk_target_bytes: usize,
+        csv_config: JsValue,
+        xml_config: JsValue,
+        transform_config: JsValue,
+        options: JsValue,
+    ) -> std::result::Result<JsGroupBatcher, JsValue> {
+        let config = wasm_converter_config(
+            input_format,
+            output_format,
+            chunk_target_bytes,
+            false,
+            csv_config,
+            xml_config,
+            transform_config,
+            options,
+        )?;
+        let batcher = GroupBatcher::new(config, group_by)?;
+        Ok(JsGroupBatcher { batcher })
+    }
+
+    /// Push input; returns an array of `{ key, data, complete }` group parts.
+    pub fn push(&mut self, chunk: &[u8]) -> std::result::Result<JsValue, JsValue> {
+        let batches = self.batcher.push(chunk)?;
+        group_batches_to_js(batches)
+    }
+
+    /// Convert the rest of the input; the last part returned completes the last group.
+    pub fn finish(&mut self) -> std::result::Result<JsValue, JsValue> {
+        let batches = self.batcher.finish()?;
+        group_batches_to_js(batches)
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn group_batches_to_js(batches: Vec<GroupBatch>) -> std::result::Result<JsValue, JsValue> {
+    let parts = Array::new();
+    for batch in batches {
+        let part = Object::new();
+        let key = batch.key.as_deref().map_or(JsValue::NULL, JsValue::from_str);
+        Reflect::set(&part, &JsValue::from_str("key"), &key)?;
+        let data = js_sys::Uint8Array::from(batch.output.as_slice());
+        Reflect::set(&part, &JsValue::from_str("data"), &data)?;
+        Reflect::set(&part, &JsValue::from_str("complete"), &JsValue::from_bool(batch.complete))?;
+        parts.push(&part);
+    }
+    Ok(parts.into())
+}
+
+/// Streams records from any input format and collects the ones matching a query.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen(js_name = RecordFinder)]
+pub struct JsRecordFinder {
+    finder: RecordFinder,
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+#[wasm_bindgen(js_class = RecordFinder)]
+impl JsRecordFinder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        input_format: &str,
+        query: JsValue,
+        csv_config: JsValue,
+        xml_config: JsValue,
+        limit: Option<usize>,
+    ) -> std::result::Result<JsRecordFinder, JsValue> {
+        let format = Format::from_string(input_format)
+            .ok_or_else(|| ConvertError::InvalidConfig(format!("Invalid input format: {}", input_format)))?;
+        let query: RecordQuery = serde_wasm_bindgen::from_value(query)
+            .map_err(|e| ConvertError::InvalidConfig(format!("Invalid query: {}", e)))?;
+
+        let mut finder = RecordFinder::new(
+            format,
+            query,
+            parse_csv_config(csv_config),
+            parse_xml_config(xml_config),
+        );
+        if let Some(limit) = limit {
+            finder = finder.with_limit(limit);
+        }
+        Ok(JsRecordFinder { finder })
+    }
+
+    /// Scan a chunk. Returns `true` once the match limit is reached.
+    pub fn push(&mut self, chunk: &[u8]) -> std::result::Result<bool, JsValue> {
+        self.finder.push(chunk)?;
+        Ok(self.finder.is_done())
+    }
+
+    /// Flush buffered input and return the matching records.
+    pub fn finish(&mut self) -> std::result::Result<JsValue, JsValue> {
+        self.finder.finish()?;
+        let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+        self.finder
+            .take_matches()
+            .serialize(&serializer)
+            .map_err(JsValue::from)
+    }
+
+    /// Number of records examined so far.
+    pub fn scanned(&self) -> usize {
+        self.finder.scanned()
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 fn parse_transform_config(value: JsValue) -> std::result::Result<Option<TransformPlan>, JsValue> {
     let input: Option<TransformConfigInput> = deserialize_optional(value);
     if let Some(input) = input {
         let plan = TransformPlan::compile(input).map_err(JsValue::from)?;
-        Ok(Some(plan))
+        let functions = EXPRESSION_FUNCTIONS.with(|registry| registry.borrow().clone());
+        Ok(Some(plan.with_functions(functions)))
     } else {
         Ok(None)
     }
 }
 
-#[cfg(target_arch = "wasm32")]
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+fn apply_converter_options(
+    config: ConverterConfig,
+    value: JsValue,
+) -> std::result::Result<ConverterConfig, JsValue> {
+    match deserialize_optional::<ConverterOptionsInput>(value) {
+        Some(input) => Ok(config.with_options(input)?),
+        None => Ok(config),
+    }
+}
+
+impl ConverterConfig {
+    /// Apply the options a JS caller passes to `withConfig`, so other
+    /// bindings can take the same option names.
+    pub fn with_options(self, input: ConverterOptionsInput) -> Result<Self> {
+        let mut config = self;
+
+        if let Some(policy) = input.flatten_collision {
+            let policy = FlattenCollisionPolicy::from_string(&policy).ok_or_else(|| {
+                ConvertError::InvalidConfig(format!("Invalid flattenCollision policy: {}", policy))
+            })?;
+            config = config.with_flatten_collision(policy);
+        }
+
+        if let Some(ndjson_output) = input.ndjson_output {
+            config = config.with_ndjson_output(ndjson_output);
+        }
+
+        if let Some(csv_output) = input.csv_output {
+            config = config.with_csv_writer_config(csv_output);
+        }
+
+        if let Some(xml_output) = input.xml_output {
+            config = config.with_xml_output_config(xml_output);
+        }
+
+        if let Some(json_input) = input.json_input {
+            config = config.with_json_input_config(json_input);
+        }
+
+        if let Some(json_output) = input.json_output {
+            config = config.with_json_output_config(json_output);
+        }
+
+        if let Some(float_format) = input.float_format {
+            config = config.with_float_format(float_format);
+        }
+
+        if let Some(integer_strings) = input.integer_strings {
+            config = config.with_integer_strings(integer_strings);
+        }
+
+        if let Some(max_output_bytes) = input.max_output_bytes {
+            config = config.with_max_output_bytes(max_output_bytes);
+        }
+
+        if let Some(min_output_bytes) = input.min_output_bytes {
+            config = config.with_min_output_bytes(min_output_bytes);
+        }
+
+        if input.trace.unwrap_or(input.trace_capacity.is_some()) {
+            config = config.with_trace(input.trace_capacity.unwrap_or(DEFAULT_TRACE_CAPACITY));
+        }
+
+        if let Some(ordered) = input.ordered {
+            config = config.with_ordered(ordered);
+        }
+
+        if let Some(threads) = input.threads {
+            config = config.with_threads(threads);
+        }
+
+        if let Some(limits) = input.limits {
+            config = config.with_limits(limits);
+        }
+
+        if let Some(compression) = input.input_compression {
+            config = config.with_input_compression(compression);
+        }
+
+        if let Some(compression) = input.output_compression {
+            config = config.with_output_compression(compression);
+        }
+
+        if let Some(policy) = input.invalid_utf8 {
+            config = config.with_invalid_utf8(policy);
+        }
+
+        if let Some(policy) = input.on_record_error {
+            config = config.with_record_error_policy(policy);
+        }
+
+        if let Some(keep) = input.keep_rejects {
+            config = config.with_keep_rejects(keep);
+        }
+
+        let projection = match (input.select_fields, input.drop_fields) {
+            (Some(_), Some(_)) => {
+                return Err(ConvertError::InvalidConfig(
+                    "selectFields and dropFields can't both be set".to_string(),
+                ))
+            }
+            (Some(fields), None) => Some(FieldProjection::Select(fields)),
+            (None, fields) => fields.map(FieldProjection::Drop),
+        };
+        if let Some(projection) = projection {
+            config = config.with_projection(projection);
+        }
+
+        if let Some(dedupe) = input.dedupe {
+            config = config.with_dedupe(dedupe);
+        }
+
+        if let Some(sort) = input.sort {
+            config = config.with_sort_config(sort);
+        }
+
+        if let Some(max_records) = input.max_records {
+            config = config.with_max_records(max_records);
+        }
+
+        if let Some(sample_every_n) = input.sample_every_n {
+            config = config.with_sample_every(sample_every_n);
+        }
+
+        if let Some(columns) = input.output_columns {
+            config = config.with_output_columns(columns);
+        }
+
+        if let Some(chunking) = input.chunking {
+            config = config.with_chunking(chunking);
+        }
+
+        if let Some(manifest) = input.manifest {
+            config = config.with_manifest(manifest);
+        }
+
+        if let Some(check) = input.detection_check {
+            config = config.with_detection_check(check);
+        }
+
+        #[cfg(feature = "parquet")]
+        if let Some(parquet) = input.parquet {
+            config = config.with_parquet_config(parquet);
+        }
+
+        #[cfg(feature = "xlsx")]
+        if let Some(xlsx) = input.xlsx {
+            config = config.with_xlsx_config(xlsx);
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
 fn deserialize_optional<T: DeserializeOwned>(value: JsValue) -> Option<T> {
     if value.is_null() || value.is_undefined() {
         return None;
@@ -1995,7 +4135,7 @@ fn deserialize_optional<T: DeserializeOwned>(value: JsValue) -> Option<T> {
 #[cfg(test)]
 mod integration_tests {
     use super::*;
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
     use js_sys::{Object, Reflect};
 
     /// Helper to create a converter without WASM bindings for testing
@@ -2007,24 +4147,15 @@ mod integration_tests {
         // Don't provide configs - force auto-detection
         config.csv_config = None;
         config.xml_config = None;
-
-        let needs_detection = match input_format {
-            Format::Csv | Format::Xml => true,
-            _ => false,
+        config.auto_detect = AutoDetect {
+            input_format: false,
+            csv_delimiter: true,
+            csv_quote: true,
+            csv_has_headers: true,
+            xml_record_element: true,
         };
 
-        let state = if needs_detection {
-            ConverterState::NeedsDetection(Vec::new())
-        } else {
-            Converter::create_state(&config)
-        };
-
-        Ok(Converter {
-            debug: false,
-            config,
-            state: Some(state),
-            stats: Stats::default(),
-        })
+        Ok(Converter::from_config(config))
     }
 
     #[test]
@@ -2168,6 +4299,74 @@ mod integration_tests {
         Ok(())
     }
 
+    #[test]
+    fn test_csv_auto_detect_single_quotes_and_missing_header() -> Result<()> {
+        let convert = |csv: &[u8]| -> Result<String> {
+            let mut converter = create_test_converter(Format::Csv, Format::Ndjson)?;
+            let mut output = converter.push(csv).map_err(|_| ConvertError::InvalidConfig("push failed".to_string()))?;
+            output.extend(converter.finish().map_err(|_| ConvertError::InvalidConfig("finish failed".to_string()))?);
+            Ok(String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(
+            convert(b"'name';'city'\n'Ada';'London; UK'\n'O''Brien';'Cork'\n")?,
+            "{\"name\":\"Ada\",\"city\":\"London; UK\"}\n{\"name\":\"O'Brien\",\"city\":\"Cork\"}\n"
+        );
+        assert_eq!(
+            convert(b"1,Ada,36\n2,Grace,85\n")?,
+            "{\"field_0\":\"1\",\"field_1\":\"Ada\",\"field_2\":\"36\"}\n{\"field_0\":\"2\",\"field_1\":\"Grace\",\"field_2\":\"85\"}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_detection_result_reports_detected_settings() {
+        let mut converter = create_test_converter(Format::Csv, Format::Ndjson).unwrap();
+        assert!(converter.detection().is_none());
+        // Detection waits for 256 bytes or the end of input
+        converter.push(b"id;name\n1;Ada\n").unwrap();
+        assert!(converter.detection().is_none());
+        converter.finish().unwrap();
+        assert_eq!(
+            converter.detection(),
+            Some(&DetectionResult {
+                delimiter: Some(";".to_string()),
+                quote: Some("\"".to_string()),
+                has_headers: Some(true),
+                fields: vec!["id".to_string(), "name".to_string()],
+                ..Default::default()
+            })
+        );
+
+        // Configured settings aren't reported
+        let config = ConverterConfig::new(Format::Xml, Format::Ndjson).with_auto_detect(AutoDetect {
+            input_format: true,
+            ..Default::default()
+        });
+        let mut converter = converter_from_config(config);
+        converter.push(b"<items><item><a>1</a></item></items>").unwrap();
+        converter.finish().unwrap();
+        let detection = converter.detection().unwrap();
+        assert_eq!(detection.input_format.as_deref(), Some("xml"));
+        assert_eq!(detection.record_element, None);
+    }
+
+    #[test]
+    fn test_empty_chunks_wait_for_detection() {
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_auto_detect(AutoDetect {
+            input_format: true,
+            ..Default::default()
+        });
+        let mut converter = converter_from_config(config);
+        assert!(converter.push(b"").unwrap().is_empty());
+        assert!(converter.detection().is_none());
+        let mut output = converter.push(b"id,name\n1,Ada\n").unwrap();
+        output.extend(converter.push(b"").unwrap());
+        output.extend(converter.finish().unwrap());
+        assert_eq!(output, b"{\"id\":\"1\",\"name\":\"Ada\"}\n");
+        assert_eq!(converter.detection().unwrap().input_format.as_deref(), Some("csv"));
+    }
+
     #[test]
     fn test_xml_nested_arrays_to_json() -> Result<()> {
         let xml = b"<movies><movie><title>The Matrix</title><cast><actor><name>Keanu</name></actor><actor><name>Laurence</name></actor></cast></movie></movies>";
@@ -2266,8 +4465,9 @@ mod integration_tests {
         Ok(())
     }
 
+    #[cfg(feature = "wasm")]
     fn build_csv_config(delimiter: Option<&str>, quote: Option<&str>) -> JsValue {
-        #[cfg(target_arch = "wasm32")]
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
         {
             if delimiter.is_none() && quote.is_none() {
                 return JsValue::NULL;
@@ -2288,8 +4488,9 @@ mod integration_tests {
         }
     }
 
+    #[cfg(feature = "wasm")]
     fn build_xml_config(record_element: Option<&str>) -> JsValue {
-        #[cfg(target_arch = "wasm32")]
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
         {
             if let Some(record_element) = record_element {
                 let config = Object::new();
@@ -2305,6 +4506,1274 @@ mod integration_tests {
         }
     }
 
+    /// Build a converter from a native config, skipping auto-detection.
+    fn converter_from_config(config: ConverterConfig) -> Converter {
+        Converter::from_config(config)
+    }
+
+    #[test]
+    fn test_sink_takes_output_in_place_of_returns() {
+        struct Parts(std::rc::Rc<std::cell::RefCell<Vec<Vec<u8>>>>);
+        impl OutputSink for Parts {
+            fn write_output(&mut self, output: &[u8]) -> Result<()> {
+                self.0.borrow_mut().push(output.to_vec());
+                Ok(())
+            }
+        }
+
+        let parts = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_max_output_bytes(15);
+        let mut converter = converter_from_config(config).with_sink(Parts(parts.clone()));
+        assert!(converter.push(b"a\n1\n2\n").unwrap().is_empty());
+        assert!(converter.push(b"3\n4").unwrap().is_empty());
+        assert!(converter.finish().unwrap().is_empty());
+        assert_eq!(converter.pending_output_bytes(), 0);
+        let parts = parts.borrow();
+        assert!(parts.iter().all(|part| part.len() <= 15));
+        assert_eq!(parts.concat(), b"{\"a\":\"1\"}\n{\"a\":\"2\"}\n{\"a\":\"3\"}\n{\"a\":\"4\"}\n".to_vec());
+
+        // A sink that fails fails the converter
+        let full = std::io::Cursor::new([0u8; 4]);
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Csv, Format::Ndjson)).with_sink(full);
+        assert!(matches!(converter.push_bytes(b"a\n1\n"), Err(ConvertError::Io(_))));
+        assert!(converter.push(b"2\n").is_err());
+    }
+
+    #[test]
+    fn test_calls_after_finish_report_already_finished() {
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        let mut output = converter.push_bytes(b"a\n1\n").unwrap();
+        assert!(!converter.is_finished());
+        output.extend(converter.finish_stream().unwrap());
+        assert_eq!(output, b"{\"a\":\"1\"}\n".to_vec());
+        assert!(converter.is_finished());
+        assert!(matches!(converter.push_bytes(b"2\n"), Err(ConvertError::AlreadyFinished)));
+        assert!(matches!(converter.finish_stream(), Err(ConvertError::AlreadyFinished)));
+        assert!(matches!(converter.flush(), Err(ConvertError::AlreadyFinished)));
+        assert!(converter.is_finished());
+
+        // A converter stopped by an error is not finished
+        let full = std::io::Cursor::new([0u8; 4]);
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Csv, Format::Ndjson)).with_sink(full);
+        assert!(converter.push_bytes(b"a\n1\n").is_err());
+        assert!(!converter.is_finished());
+        assert!(matches!(converter.finish_stream(), Err(ConvertError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_native_calls_return_convert_errors() {
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Ndjson, Format::Csv));
+        let mut output = converter.push_native(b"{\"a\":1}\n").unwrap();
+        output.extend(converter.finish_native().unwrap());
+        assert_eq!(output, b"a\n1\n".to_vec());
+        assert!(matches!(converter.push_native(b"{}\n"), Err(ConvertError::AlreadyFinished)));
+        assert!(matches!(converter.finish_native(), Err(ConvertError::AlreadyFinished)));
+    }
+
+    #[test]
+    fn test_convert_bytes_matches_push_and_finish() {
+        let input = b"id;name\n1;Ann\n2;Bo\n";
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_auto_detect(AutoDetect {
+            input_format: true,
+            csv_delimiter: true,
+            ..AutoDetect::default()
+        });
+        let mut converter = converter_from_config(config.clone());
+        let mut expected = converter.push_bytes(input).unwrap();
+        expected.extend(converter.finish_stream().unwrap());
+        assert_eq!(expected, b"{\"id\":\"1\",\"name\":\"Ann\"}\n{\"id\":\"2\",\"name\":\"Bo\"}\n".to_vec());
+
+        assert_eq!(convert_bytes(input, config.clone()).unwrap(), expected);
+        // Output held back by the cap is collected too
+        assert_eq!(convert_bytes(input, config.with_max_output_bytes(10)).unwrap(), expected);
+        assert!(convert_bytes(b"\x00\x01", ConverterConfig::new(Format::Csv, Format::Json).with_auto_detect(AutoDetect {
+            input_format: true,
+            ..AutoDetect::default()
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn test_min_output_bytes_batches_push_output() {
+        let rows: Vec<&[u8]> = vec![b"a\n", b"1\n", b"2\n", b"3\n", b"4\n", b"5\n"];
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        let mut expected = Vec::new();
+        for row in &rows {
+            expected.extend(converter.push_bytes(row).unwrap());
+        }
+        expected.extend(converter.finish_stream().unwrap());
+
+        // Each record is 10 bytes, so output comes back two records at a time
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_min_output_bytes(20);
+        let mut converter = converter_from_config(config);
+        let mut parts = Vec::new();
+        for row in &rows[..4] {
+            parts.push(converter.push_bytes(row).unwrap());
+        }
+        assert_eq!(parts.iter().filter(|part| !part.is_empty()).count(), 1);
+        assert_eq!(parts[2].len(), 20);
+        // `flush` returns what is batched
+        parts.push(converter.flush().unwrap());
+        assert_eq!(parts[4].len(), 10);
+        for row in &rows[4..] {
+            parts.push(converter.push_bytes(row).unwrap());
+        }
+        parts.push(converter.finish_stream().unwrap());
+        assert_eq!(parts.concat(), expected);
+
+        // A cap below the batch size still applies
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+            .with_min_output_bytes(30)
+            .with_max_output_bytes(10);
+        let mut converter = converter_from_config(config);
+        let mut output = Vec::new();
+        for row in &rows {
+            let part = converter.push_bytes(row).unwrap();
+            assert!(part.len() <= 10);
+            output.extend(part);
+            output.extend(converter.drain());
+        }
+        while !converter.is_finished() {
+            output.extend(converter.finish_stream().unwrap());
+        }
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_repeated_finish_returns_held_back_output() {
+        let input = b"a\n1\n2\n3\n4\n5\n6\n";
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        let mut expected = converter.push_bytes(input).unwrap();
+        expected.extend(converter.finish_stream().unwrap());
+
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_max_output_bytes(20);
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push_bytes(input).unwrap();
+        let mut calls = 0;
+        while !converter.is_finished() {
+            let part = converter.finish_stream().unwrap();
+            assert!(part.len() <= 20);
+            output.extend(part);
+            calls += 1;
+            // Input is complete once `finish` has run
+            assert!(matches!(converter.push_bytes(b"7\n"), Err(ConvertError::AlreadyFinished)));
+        }
+        assert!(calls > 1);
+        assert_eq!(output, expected);
+        assert!(matches!(converter.finish_stream(), Err(ConvertError::AlreadyFinished)));
+    }
+
+    #[test]
+    fn test_push_into_writes_to_caller_buffers() {
+        let input: &[&[u8]] = &[b"a,b\n1,x\n2,", b"y\n3,z\n", b"4,w"];
+        let new_converter = || converter_from_config(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        let mut converter = new_converter();
+        let mut expected = Vec::new();
+        for chunk in input {
+            expected.extend(converter.push(chunk).unwrap());
+        }
+        expected.extend(converter.finish().unwrap());
+
+        let mut converter = new_converter();
+        let mut output = Vec::new();
+        for chunk in input {
+            converter.push_into(chunk, &mut output).unwrap();
+        }
+        converter.finish_into(&mut output).unwrap();
+        assert_eq!(output, expected);
+
+        // A buffer too small for a push leaves the rest to drain, ahead of later output
+        let mut converter = new_converter();
+        let mut buffer = [0u8; 8];
+        let mut output = Vec::new();
+        for chunk in input {
+            let written = converter.push_into_slice(chunk, &mut buffer).unwrap();
+            output.extend_from_slice(&buffer[..written]);
+        }
+        let written = converter.finish_into_slice(&mut buffer).unwrap();
+        output.extend_from_slice(&buffer[..written]);
+        assert!(converter.pending_output_bytes() > 0);
+        loop {
+            let written = converter.drain_into_slice(&mut buffer);
+            if written == 0 {
+                break;
+            }
+            output.extend_from_slice(&buffer[..written]);
+        }
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_max_output_bytes_queues_surplus_for_drain() {
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_max_output_bytes(15);
+        let mut converter = converter_from_config(config);
+
+        let first = converter.push(b"a\n1\n2\n3\n").unwrap();
+        assert_eq!(first, b"{\"a\":\"1\"}\n".to_vec());
+        assert_eq!(converter.pending_output_bytes(), 20);
+
+        let mut output = first;
+        output.extend(converter.finish().unwrap());
+        while converter.pending_output_bytes() > 0 {
+            let part = converter.drain();
+            assert!(part.len() <= 15);
+            output.extend(part);
+        }
+        assert_eq!(output, b"{\"a\":\"1\"}\n{\"a\":\"2\"}\n{\"a\":\"3\"}\n".to_vec());
+        assert!(converter.drain().is_empty());
+    }
+
+    #[test]
+    fn test_zero_max_output_bytes_is_rejected_before_converting() {
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_max_output_bytes(0);
+        assert!(matches!(
+            convert_bytes(b"a\n1\n", config.clone()),
+            Err(ConvertError::InvalidConfig(message)) if message.contains("maxOutputBytes")
+        ));
+
+        let mut converter = converter_from_config(config);
+        assert!(matches!(converter.push_bytes(b"a\n1\n"), Err(ConvertError::InvalidConfig(_))));
+        assert!(matches!(converter.finish_stream(), Err(ConvertError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_trace_records_detection_and_pushes() {
+        let mut config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_trace(16);
+        config.csv_config = None;
+        config.auto_detect.csv_delimiter = true;
+        let mut converter = converter_from_config(config);
+
+        converter.push(b"a;b\n").unwrap();
+        converter.finish().unwrap();
+
+        let events: Vec<serde_json::Value> = converter
+            .trace_log
+            .as_ref()
+            .unwrap()
+            .entries()
+            .map(|entry| serde_json::to_value(entry).unwrap())
+            .collect();
+        let kinds: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+        assert_eq!(kinds, vec!["push", "detection", "stateChange", "push", "finish"]);
+        assert_eq!(events[0]["bufferedBytes"], 4);
+        assert_eq!(events[1]["decision"], "csv delimiter ';' (2 fields)");
+        assert_eq!(events[2]["to"], "CsvToNdjson");
+        assert_eq!(events[4]["state"], "CsvToNdjson");
+    }
+
+    #[test]
+    fn test_ndjson_output_framing() {
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_ndjson_output(
+            NdjsonOutputConfig {
+                line_ending: LineEnding::Crlf,
+                trailing_newline: false,
+                record_separator: false,
+                nul_terminated: false,
+            },
+        );
+        let mut converter = converter_from_config(config);
+
+        let mut output = converter.push(b"a\n1\n2\n").unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(output, b"{\"a\":\"1\"}\r\n{\"a\":\"2\"}".to_vec());
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn test_csv_to_parquet_streams_row_groups() {
+        use parquet::file::reader::{FileReader, SerializedFileReader};
+
+        let config = ConverterConfig::new(Format::Csv, Format::Parquet).with_parquet_config(ParquetConfig {
+            row_group_size: 2,
+            compression: ParquetCompression::Snappy,
+        });
+        let mut converter = converter_from_config(config);
+
+        let mut file = converter.push(b"id,name\n1,a\n2,b\n3,").unwrap();
+        assert!(file.starts_with(b"PAR1"));
+        file.extend(converter.push(b"c\n").unwrap());
+        file.extend(converter.finish().unwrap());
+
+        let reader = SerializedFileReader::new(bytes::Bytes::from(file)).unwrap();
+        assert_eq!(reader.metadata().num_row_groups(), 2);
+        assert_eq!(reader.metadata().file_metadata().num_rows(), 3);
+    }
+
+    #[test]
+    fn test_config_from_detection_reads_the_sampled_input() {
+        use std::io::Write;
+
+        let csv = b"id;name\n1;'Ada; Countess'\n2;Grace\n";
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(csv).unwrap();
+        let gzip = encoder.finish().unwrap();
+
+        let detection = Detection::analyze(&gzip).unwrap();
+        assert_eq!(detection.format, Format::Csv);
+        assert_eq!(detection.compression, Some(Compression::Gzip));
+        assert_eq!(detection.fields, vec!["id".to_string(), "name".to_string()]);
+        // A sample cut off before the gzip trailer
+        assert_eq!(Detection::analyze(&gzip[..gzip.len() - 8]).as_ref(), Some(&detection));
+
+        for (output_format, expected) in [
+            (Format::Ndjson, "{\"id\":\"1\",\"name\":\"Ada; Countess\"}\n{\"id\":\"2\",\"name\":\"Grace\"}\n"),
+            (Format::Csv, "id,name\n1,Ada; Countess\n2,Grace\n"),
+        ] {
+            let mut converter = converter_from_config(ConverterConfig::from_detection(&detection, output_format));
+            let mut output = converter.push(&gzip).unwrap();
+            output.extend(converter.finish().unwrap());
+            assert_eq!(String::from_utf8(output).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_gzip_input_is_inflated_across_chunks() {
+        use std::io::Write;
+
+        let csv = [b"id,name\n".to_vec(), b"1,Ada\n2,Grace\n".repeat(20)].concat();
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&csv).unwrap();
+        let gzip = encoder.finish().unwrap();
+
+        let convert = |config: ConverterConfig, input: &[u8]| {
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input.chunks(5) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            output
+        };
+        let plain = convert(ConverterConfig::new(Format::Csv, Format::Ndjson), &csv);
+        assert_eq!(plain.iter().filter(|&&b| b == b'\n').count(), 40);
+
+        // Sniffed from the gzip header, or configured explicitly
+        assert_eq!(convert(ConverterConfig::new(Format::Csv, Format::Ndjson), &gzip), plain);
+        let config =
+            ConverterConfig::new(Format::Csv, Format::Ndjson).with_input_compression(Compression::Gzip);
+        assert_eq!(convert(config, &gzip), plain);
+    }
+
+    #[test]
+    fn test_gzip_output_decompresses_to_plain_output() {
+        use std::io::Read;
+
+        let csv = [b"id,name\n".to_vec(), b"1,Ada\n2,Grace\n".repeat(200)].concat();
+        let convert = |config: ConverterConfig| {
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in csv.chunks(64) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            output
+        };
+        let plain = convert(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        let gzip = convert(
+            ConverterConfig::new(Format::Csv, Format::Ndjson).with_output_compression(Compression::Gzip),
+        );
+        assert!(gzip.starts_with(&[0x1f, 0x8b]));
+        assert!(gzip.len() < plain.len());
+
+        let mut inflated = Vec::new();
+        flate2::read::GzDecoder::new(gzip.as_slice())
+            .read_to_end(&mut inflated)
+            .unwrap();
+        assert_eq!(inflated, plain);
+    }
+
+    #[test]
+    fn test_idle_flush_emits_held_back_output() {
+        use std::io::Write;
+        use std::time::Duration;
+
+        let inflate = |deflate: &[u8]| {
+            let mut decoder = flate2::write::DeflateDecoder::new(Vec::new());
+            decoder.write_all(deflate).unwrap();
+            decoder.flush().unwrap();
+            String::from_utf8(decoder.get_ref().clone()).unwrap()
+        };
+
+        let config = ConverterConfig::new(Format::Ndjson, Format::Ndjson)
+            .with_output_compression(Compression::Deflate)
+            .with_idle_flush(Duration::ZERO);
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"{\"n\":1}\n{\"n\":2}\n").unwrap();
+        assert!(output.is_empty());
+        output.extend(converter.poll_idle().unwrap());
+        assert_eq!(inflate(&output), "{\"n\":1}\n{\"n\":2}\n");
+        // Once per quiet period
+        assert!(converter.poll_idle().unwrap().is_empty());
+
+        output.extend(converter.push(b"{\"n\":3}\n").unwrap());
+        output.extend(converter.poll_idle().unwrap());
+        assert_eq!(inflate(&output), "{\"n\":1}\n{\"n\":2}\n{\"n\":3}\n");
+        output.extend(converter.finish().unwrap());
+        assert!(converter.poll_idle().unwrap().is_empty());
+
+        let config = ConverterConfig::new(Format::Ndjson, Format::Ndjson)
+            .with_output_compression(Compression::Deflate)
+            .with_idle_flush(Duration::from_secs(3600));
+        let mut converter = converter_from_config(config);
+        assert!(converter.push(b"{\"n\":1}\n").unwrap().is_empty());
+        assert!(converter.poll_idle().unwrap().is_empty());
+
+        // Input held for format detection is converted with what has arrived
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_auto_detect(AutoDetect {
+            input_format: true,
+            ..AutoDetect::default()
+        });
+        let mut converter = converter_from_config(config);
+        assert!(converter.push(b"{\"n\":1}\n{\"n\":2}\n").unwrap().is_empty());
+        assert_eq!(converter.flush().unwrap(), b"{\"n\":1}\n{\"n\":2}\n");
+        assert_eq!(converter.push(b"{\"n\":3}\n").unwrap(), b"{\"n\":3}\n");
+    }
+
+    #[test]
+    fn test_dedupe_drops_repeated_keys() {
+        let input = b"sku,price\nb,2\na,1\nb,3\n";
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+            .with_dedupe(DedupeConfig::new(vec!["sku".to_string()]));
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(input).unwrap();
+        output.extend(converter.finish().unwrap());
+        // Lines are written as parsed, in their original key order
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"sku\":\"b\",\"price\":\"2\"}\n{\"sku\":\"a\",\"price\":\"1\"}\n"
+        );
+        let warnings = converter.warnings();
+        assert_eq!((warnings[0].code, warnings[0].count), (WarningCode::DuplicateDropped, 1));
+
+        // Keys are read after the transform
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({
+            "dedupe": { "fields": ["id"], "keep": "last" }
+        }))
+        .unwrap();
+        let transform: TransformConfigInput = serde_json::from_value(serde_json::json!({
+            "fields": [
+                { "targetFieldName": "id", "originFieldName": "sku" },
+                { "targetFieldName": "price", "coerce": { "type": "i64" } }
+            ]
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Csv, Format::Csv)
+            .with_transform(TransformPlan::compile(transform).unwrap())
+            .with_options(options)
+            .unwrap();
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(input).unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "id,price\na,1\nb,3\n");
+
+        let options: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "dedupe": { "fields": [] } })).unwrap();
+        assert!(ConverterConfig::default().with_options(options).is_err());
+    }
+
+    #[test]
+    fn test_sort_writes_records_in_key_order_at_finish() {
+        let config = ConverterConfig::new(Format::Ndjson, Format::Csv)
+            .with_sort(vec!["price".to_string()], SortOrder::Desc);
+        let mut converter = converter_from_config(config);
+        let mut output = converter
+            .push(b"{\"sku\":\"a\",\"price\":9}\n{\"sku\":\"b\",\"price\":10}\n")
+            .unwrap();
+        assert!(output.is_empty());
+        output.extend(converter.push(b"{\"sku\":\"c\",\"price\":9.5}").unwrap());
+        output.extend(converter.finish().unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "price,sku\n10,b\n9.5,c\n9,a\n");
+
+        // Spilled runs, after dedupe
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({
+            "dedupe": { "fields": ["id"] },
+            "sort": { "keys": ["id"], "maxMemoryBytes": 256 }
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Ndjson, Format::Ndjson).with_options(options).unwrap();
+        let mut converter = converter_from_config(config);
+        let input: String = (0..40).map(|i| format!("{{\"id\":{}}}\n", (i * 7) % 20)).collect();
+        let mut output = converter.push(input.as_bytes()).unwrap();
+        output.extend(converter.finish().unwrap());
+        let expected: String = (0..20).map(|i| format!("{{\"id\":{i}}}\n")).collect();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_sort_index_locates_records_by_key() {
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({
+            "sort": { "keys": ["sku"], "index": true },
+            "ndjsonOutput": { "recordSeparator": true }
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_options(options).unwrap();
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"sku,n\nb,1\na,2\nb,3\n").unwrap();
+        assert!(converter.take_index().is_empty());
+        output.extend(converter.finish().unwrap());
+
+        let index = String::from_utf8(converter.take_index()).unwrap();
+        let entries: Vec<serde_json::Value> = index.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0][0], serde_json::json!(["a"]));
+        let (offset, length) = (entries[1][1].as_u64().unwrap() as usize, entries[1][2].as_u64().unwrap() as usize);
+        assert_eq!(
+            &output[offset..offset + length],
+            b"\x1e{\"sku\":\"b\",\"n\":\"1\"}\n\x1e{\"sku\":\"b\",\"n\":\"3\"}\n"
+        );
+
+        let options: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "sort": { "keys": ["sku"], "index": true } })).unwrap();
+        assert!(ConverterConfig::new(Format::Csv, Format::Csv).with_options(options).is_err());
+    }
+
+    #[test]
+    fn test_select_and_drop_fields() {
+        let convert = |input_format: Format, output_format: Format, options: serde_json::Value, input: &[u8]| {
+            let options: ConverterOptionsInput = serde_json::from_value(options).unwrap();
+            let config = ConverterConfig::new(input_format, output_format).with_options(options).unwrap();
+            let mut converter = converter_from_config(config);
+            let mut output = converter.push(input).unwrap();
+            output.extend(converter.finish().unwrap());
+            String::from_utf8(output).unwrap()
+        };
+        let select = serde_json::json!({ "selectFields": ["price", "id"] });
+        let drop = serde_json::json!({ "dropFields": ["name"] });
+
+        let csv = b"id,name,price\n1,Ada,9.5\n2,Grace,3\n";
+        assert_eq!(convert(Format::Csv, Format::Csv, select.clone(), csv), "id,price\n1,9.5\n2,3\n");
+        assert_eq!(
+            convert(Format::Csv, Format::Ndjson, drop.clone(), csv),
+            "{\"id\":\"1\",\"price\":\"9.5\"}\n{\"id\":\"2\",\"price\":\"3\"}\n"
+        );
+
+        let ndjson = b"{\"id\":1,\"name\":\"Ada\",\"price\":9.5}\n{\"name\":\"Grace\"}\n";
+        assert_eq!(
+            convert(Format::Ndjson, Format::Ndjson, select.clone(), ndjson),
+            "{\"id\":1,\"price\":9.5}\n{}\n"
+        );
+        assert_eq!(convert(Format::Ndjson, Format::Csv, drop, ndjson), "id,price\n1,9.5\n,\n");
+        let json = br#"[{"id": 1, "name": "Ada", "price": 9.5}]"#;
+        assert_eq!(convert(Format::Json, Format::Json, select, json), "[{\"id\":1,\"price\":9.5}]");
+
+        let both: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "selectFields": ["id"], "dropFields": ["name"] })).unwrap();
+        assert!(ConverterConfig::default().with_options(both).is_err());
+    }
+
+    #[test]
+    fn test_json_records_path_selects_record_array() {
+        let input = br#"{"meta": {"total": 2}, "data": {"items": [{"id": 1}, {"id": 2}]}}"#;
+        for output_format in [Format::Json, Format::Csv] {
+            let options: ConverterOptionsInput =
+                serde_json::from_value(serde_json::json!({ "jsonInput": { "recordsPath": "data.items" } })).unwrap();
+            let config = ConverterConfig::new(Format::Json, output_format).with_options(options).unwrap();
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input.chunks(7) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            let output = String::from_utf8(output).unwrap();
+            match output_format {
+                Format::Json => {
+                    let records: serde_json::Value = serde_json::from_str(&output).unwrap();
+                    assert_eq!(records, serde_json::json!([{ "id": 1 }, { "id": 2 }]));
+                }
+                _ => assert_eq!(output, "id\n1\n2\n"),
+            }
+        }
+
+        let options: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "jsonInput": { "recordsPath": "data." } })).unwrap();
+        assert!(ConverterConfig::new(Format::Json, Format::Ndjson).with_options(options).is_err());
+    }
+
+    #[test]
+    fn test_chunk_manifest_covers_compressed_output() {
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({
+            "chunking": { "minBytes": 64, "avgBytes": 128, "maxBytes": 512 },
+            "outputCompression": "gzip"
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_options(options).unwrap();
+        let mut converter = converter_from_config(config);
+        let mut input = b"id,name\n".to_vec();
+        for n in 0..500 {
+            input.extend(format!("{n},name {n}\n").as_bytes());
+        }
+        let mut output = converter.push(&input).unwrap();
+        output.extend(converter.finish().unwrap());
+
+        let manifest = String::from_utf8(converter.take_chunks()).unwrap();
+        let chunks: Vec<serde_json::Value> = manifest.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert!(chunks.len() > 1);
+        let last = chunks.last().unwrap();
+        assert_eq!(
+            last["offset"].as_u64().unwrap() + last["length"].as_u64().unwrap(),
+            output.len() as u64
+        );
+        assert!(chunks.iter().all(|chunk| chunk["known"] == false));
+        assert!(converter.take_chunks().is_empty());
+
+        let options: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "chunking": { "minBytes": 0 } })).unwrap();
+        assert!(ConverterConfig::new(Format::Csv, Format::Csv).with_options(options).is_err());
+    }
+
+    #[test]
+    fn test_manifest_records_counts_and_checksums() {
+        use sha2::{Digest, Sha256};
+
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({
+            "manifest": true,
+            "dedupe": { "fields": ["id"] }
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+            .with_auto_detect(AutoDetect {
+                csv_delimiter: true,
+                ..AutoDetect::default()
+            })
+            .with_options(options)
+            .unwrap();
+        let mut converter = converter_from_config(config);
+        let input = b"id;name\n1;Ada\n2;Grace\n1;Ada";
+        let mut output = converter.push(&input[..10]).unwrap();
+        output.extend(converter.push(&input[10..]).unwrap());
+        assert!(converter.manifest().is_none());
+        output.extend(converter.finish().unwrap());
+
+        let manifest = converter.manifest().unwrap();
+        assert_eq!((manifest.input_format.as_str(), manifest.output_format.as_str()), ("csv", "ndjson"));
+        assert_eq!(manifest.records, 3);
+        assert_eq!(manifest.warnings, 1);
+        assert_eq!(manifest.input.bytes, input.len() as u64);
+        assert_eq!(manifest.input.sha256, format!("{:x}", Sha256::digest(input)));
+        assert_eq!(manifest.output.bytes, output.len() as u64);
+        assert_eq!(manifest.output.sha256, format!("{:x}", Sha256::digest(&output)));
+
+        let json = serde_json::to_value(manifest).unwrap();
+        assert_eq!(json["config"]["csvConfig"]["delimiter"], ";");
+        assert_eq!(json["config"]["options"]["dedupe"]["fields"], serde_json::json!(["id"]));
+        assert_eq!(json["detection"]["delimiter"], ";");
+    }
+
+    #[test]
+    fn test_stop_when_ends_conversion_after_match() {
+        let transform: TransformConfigInput = serde_json::from_value(serde_json::json!({
+            "mode": "augment",
+            "stopWhen": { "field": "id", "equals": "2" }
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Ndjson, Format::Json)
+            .with_transform(TransformPlan::compile(transform).unwrap());
+        let mut converter = converter_from_config(config);
+
+        let mut output = converter.push(b"{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n").unwrap();
+        assert!(converter.is_stopped());
+        assert!(converter.push(b"{\"id\":4}\n").unwrap().is_empty());
+        output.extend(converter.finish().unwrap());
+
+        let parsed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(parsed, serde_json::json!([{ "id": 1 }, { "id": 2 }]));
+        assert_eq!(converter.stop_match, Some(serde_json::json!({ "id": 2 })));
+    }
+
+    #[test]
+    fn test_type_sidecar_restores_json_from_csv() {
+        let records = serde_json::json!([
+            { "id": 1, "name": "Ann", "price": 9.5, "active": true, "note": null, "zip": "01234",
+              "tags": ["a", "b"], "address": { "city": "Oslo" }, "grid": [[1, 2]] },
+            { "id": 2, "name": "", "price": 10, "active": false, "note": "x", "zip": "99",
+              "tags": ["c"], "address": { "city": "Rome" }, "grid": [[3]] }
+        ]);
+        let writer = CsvWriterConfig { type_sidecar: true, ..CsvWriterConfig::default() };
+        let config = ConverterConfig::new(Format::Json, Format::Csv).with_csv_writer_config(writer);
+        let mut converter = converter_from_config(config);
+        let mut csv = converter.push_native(records.to_string().as_bytes()).unwrap();
+        csv.extend(converter.finish_native().unwrap());
+        assert!(csv.starts_with(b"active,address.city,grid.0,id,name,note,price,tags.0,tags.1,zip\n"));
+
+        let schema = converter.csv_schema().unwrap();
+        assert!(schema.unflatten);
+        let column = |name: &str| schema.columns.iter().find(|column| column.name == name).unwrap();
+        assert_eq!(column("zip").column_type, Some(ColumnType::String));
+        assert_eq!(column("note").empty, Some(EmptyField::Null));
+        assert_eq!(column("tags.1").empty, Some(EmptyField::Omit));
+        assert_eq!(column("grid.0").column_type, Some(ColumnType::Json));
+
+        let config = ConverterConfig::new(Format::Csv, Format::Json)
+            .with_csv_config(CsvConfig::default().with_schema(schema));
+        let json = convert_bytes(&csv, config).unwrap();
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&json).unwrap(), records);
+
+        let plain = converter_from_config(ConverterConfig::new(Format::Json, Format::Csv));
+        assert!(plain.csv_schema().is_none());
+    }
+
+    #[test]
+    fn test_type_sidecar_round_trips_dotted_keys_mixed_columns_and_blank_rows() {
+        let round_trip = |records: serde_json::Value| {
+            let writer = CsvWriterConfig { type_sidecar: true, ..CsvWriterConfig::default() };
+            let config = ConverterConfig::new(Format::Json, Format::Csv).with_csv_writer_config(writer);
+            let mut converter = converter_from_config(config);
+            let mut csv = converter.push_native(records.to_string().as_bytes()).unwrap();
+            csv.extend(converter.finish_native().unwrap());
+            let schema = converter.csv_schema().unwrap();
+            let config = ConverterConfig::new(Format::Csv, Format::Json)
+                .with_csv_config(CsvConfig::default().with_schema(schema.clone()));
+            let json = convert_bytes(&csv, config).unwrap();
+            assert_eq!(serde_json::from_slice::<serde_json::Value>(&json).unwrap(), records);
+            (String::from_utf8(csv).unwrap(), schema)
+        };
+
+        let (_, schema) = round_trip(serde_json::json!([{ "a.b": 1, "c": 2 }]));
+        assert!(!schema.unflatten);
+        let (_, schema) = round_trip(serde_json::json!([{ "a.b": 1, "x": { "y.z": [2] } }]));
+        assert!(schema.unflatten);
+        assert!(schema.columns[0].path.is_empty());
+        assert_eq!(schema.columns[1].path, ["x", "y.z", "0"]);
+
+        let (csv, schema) = round_trip(serde_json::json!([{ "v": 3 }, { "v": "12" }, { "v": "" }, { "v": null }]));
+        assert_eq!(csv, "v\n3\n\"\"\"12\"\"\"\n\"\"\"\"\"\"\n\"\"\n");
+        assert_eq!(schema.columns[0].column_type, Some(ColumnType::Json));
+
+        let (csv, _) = round_trip(serde_json::json!([{ "v": "" }, { "v": "x" }]));
+        assert_eq!(csv, "v\n\"\"\nx\n");
+
+        // A column holding both "" and null reads back as "", but keeps its rows
+        let config = ConverterConfig::new(Format::Json, Format::Csv);
+        let csv = convert_bytes(br#"[{"v":""},{"v":null}]"#, config).unwrap();
+        assert_eq!(csv, b"v\n\"\"\n\"\"\n");
+        let json = convert_bytes(&csv, ConverterConfig::new(Format::Csv, Format::Json)).unwrap();
+        assert_eq!(json, br#"[{"v":""},{"v":""}]"#);
+    }
+
+    #[test]
+    fn test_push_with_info_reports_each_chunk() {
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Csv, Format::Ndjson));
+        let info = converter.push_with_info(b"a,b\n1,2\n3").unwrap();
+        assert_eq!(info.output, b"{\"a\":\"1\",\"b\":\"2\"}\n");
+        assert_eq!(info.records_emitted, 1);
+        assert_eq!(info.bytes_buffered, 1);
+        assert!(info.warnings.is_empty());
+
+        let info = converter.push_with_info(b"\n4\n5,6\n").unwrap();
+        assert_eq!(info.records_emitted, 3);
+        assert_eq!(info.bytes_buffered, 0);
+        assert_eq!(info.warnings.len(), 1);
+        assert_eq!((info.warnings[0].code, info.warnings[0].count), (WarningCode::MissingFields, 2));
+
+        let info = converter.push_with_info(b"7\n").unwrap();
+        assert_eq!(info.warnings[0].count, 1);
+        assert_eq!(converter.warnings()[0].count, 3);
+    }
+
+    #[test]
+    fn test_push_with_info_counts_records_after_the_transform() {
+        let transform: TransformConfigInput = serde_json::from_value(serde_json::json!({
+            "mode": "augment",
+            "filter": "n > 1"
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+            .with_csv_config(CsvConfig { infer_types: true, ..CsvConfig::default() })
+            .with_transform(TransformPlan::compile(transform).unwrap());
+        assert!(!config.enable_stats);
+        let mut converter = converter_from_config(config);
+
+        let info = converter.push_with_info(b"n\n1\n2\n3\n").unwrap();
+        assert_eq!(info.output, b"{\"n\":2}\n{\"n\":3}\n");
+        assert_eq!(info.records_emitted, 2);
+
+        let info = converter.push_with_info(b"0\n").unwrap();
+        assert!(info.output.is_empty());
+        assert_eq!(info.records_emitted, 0);
+    }
+
+    #[test]
+    fn test_max_records_and_sampling_cut_output() {
+        let config = ConverterConfig::new(Format::Csv, Format::Json).with_max_records(2);
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"id\n1\n2\n3\n").unwrap();
+        assert!(converter.is_stopped());
+        assert!(converter.push(b"4\n").unwrap().is_empty());
+        output.extend(converter.finish().unwrap());
+        let parsed: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(parsed, serde_json::json!([{ "id": "1" }, { "id": "2" }]));
+
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_sample_every(2);
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"id\n1\n2\n3\n4\n").unwrap();
+        output.extend(converter.push(b"5").unwrap());
+        output.extend(converter.finish().unwrap());
+        assert!(!converter.is_stopped());
+        assert_eq!(output, b"{\"id\":\"1\"}\n{\"id\":\"3\"}\n{\"id\":\"5\"}\n");
+
+        let options: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "sampleEveryN": 0 })).unwrap();
+        assert!(matches!(
+            ConverterConfig::default().with_options(options),
+            Err(ConvertError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_output_columns_rename_and_reorder() {
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({
+            "outputColumns": [{ "from": "name", "to": "Name" }, { "from": "id" }],
+            "sort": { "keys": ["name"] },
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Json, Format::Json).with_options(options).unwrap();
+        let mut converter = converter_from_config(config.clone());
+        let mut output = converter.push(br#"[{"id":1,"name":"Bo","x":0},{"id":2,"name":"Ada"}]"#).unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(output, br#"[{"Name":"Ada","id":2},{"Name":"Bo","id":1}]"#);
+
+        let mut converter = converter_from_config(ConverterConfig { output_format: Format::Csv, ..config });
+        let mut output = converter.push(br#"[{"id":1,"name":"Bo"},{"name":"Ada"}]"#).unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(output, b"Name,id\nAda,\nBo,1\n");
+
+        let options: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "outputColumns": [{ "from": "a", "to": "" }] })).unwrap();
+        assert!(matches!(
+            ConverterConfig::default().with_options(options),
+            Err(ConvertError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_output_columns_apply_to_ndjson_and_xml_output() {
+        let columns = vec![OutputColumn::new("name", "Name"), OutputColumn::new("id", "ID")];
+        let inputs: [(Format, &[u8]); 4] = [
+            (Format::Ndjson, b"{\"id\":\"1\",\"name\":\"Bo\",\"x\":\"0\"}\n{\"id\":\"2\",\"name\":\"Ada\"}\n"),
+            (Format::Csv, b"id,name,x\n1,Bo,0\n2,Ada,\n"),
+            (Format::Xml, b"<rows><row><id>1</id><name>Bo</name><x>0</x></row><row><id>2</id><name>Ada</name></row></rows>"),
+            (Format::Json, br#"[{"id":"1","name":"Bo","x":"0"},{"id":"2","name":"Ada"}]"#),
+        ];
+        for (input_format, input) in inputs {
+            let mut config = ConverterConfig::new(input_format, Format::Ndjson).with_output_columns(columns.clone());
+            if input_format == Format::Xml {
+                config = config.with_xml_config(XmlConfig { record_element: "row".to_string(), ..XmlConfig::default() });
+            }
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input.chunks(5) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                "{\"Name\":\"Bo\",\"ID\":\"1\"}\n{\"Name\":\"Ada\",\"ID\":\"2\"}\n",
+                "{input_format:?} to NDJSON"
+            );
+        }
+
+        let config = ConverterConfig::new(Format::Xml, Format::Xml)
+            .with_xml_config(XmlConfig { record_element: "row".to_string(), ..XmlConfig::default() })
+            .with_output_columns(columns);
+        let output = convert_bytes(inputs[2].1, config).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let (name, id) = (output.find("<Name>Bo</Name>").unwrap(), output.find("<ID>1</ID>").unwrap());
+        assert!(name < id, "{output}");
+        assert!(!output.contains("<x>"), "{output}");
+    }
+
+    #[test]
+    fn test_csv_comment_lines_are_left_out_of_detection() {
+        let csv_config = CsvConfig {
+            comment_char: Some(b'#'),
+            ..CsvConfig::default()
+        };
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+            .with_csv_config(csv_config)
+            .with_auto_detect(AutoDetect {
+                csv_delimiter: true,
+                ..Default::default()
+            });
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"# station;4;north\n# sensor;t1;c\n# rev;2;ok\nday\ttemp\nmon\t20\n").unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(output, b"{\"day\":\"mon\",\"temp\":\"20\"}\n");
+        assert_eq!(converter.detection().unwrap().delimiter.as_deref(), Some("\t"));
+    }
+
+    #[cfg(feature = "xlsx")]
+    #[test]
+    fn test_xlsx_sheet_converts_to_csv() {
+        let rows = r#"<row><c t="s"><v>0</v></c><c t="s"><v>1</v></c></row><row><c t="s"><v>2</v></c><c><v>36</v></c></row>"#;
+        let data = xlsx_parser::tests::workbook(&[("People", rows)], &["name", "age", "Ada"]);
+        let mut converter = converter_from_config(ConverterConfig::new(Format::Xlsx, Format::Csv));
+
+        for chunk in data.chunks(64) {
+            assert!(converter.push(chunk).unwrap().is_empty());
+        }
+        let output = converter.finish().unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "age,name\n36,Ada\n");
+        assert_eq!(detect::detect_format(&data), Some(Format::Xlsx));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_round_trips_through_csv() {
+        let convert = |input: &[u8], from, to| {
+            let mut converter = converter_from_config(ConverterConfig::new(from, to));
+            let mut output = Vec::new();
+            for chunk in input.chunks(5) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            String::from_utf8(output).unwrap()
+        };
+
+        let yaml = b"---\n- id: 1\n  name: Ada\n- id: 2\n  name: Grace Hopper\n";
+        let csv = convert(yaml, Format::Yaml, Format::Csv);
+        assert_eq!(csv, "id,name\n1,Ada\n2,Grace Hopper\n");
+        assert_eq!(
+            convert(csv.as_bytes(), Format::Csv, Format::Yaml),
+            "- id: '1'\n  name: Ada\n- id: '2'\n  name: Grace Hopper\n"
+        );
+        assert_eq!(convert(yaml, Format::Yaml, Format::Json), r#"[{"id":1,"name":"Ada"},{"id":2,"name":"Grace Hopper"}]"#);
+        assert_eq!(convert(b"", Format::Csv, Format::Yaml), "[]\n");
+        assert_eq!(detect::detect_format(yaml), Some(Format::Yaml));
+    }
+
+    #[test]
+    fn test_detection_check_warns_and_redetects() {
+        let convert = |config: ConverterConfig, input: &[u8]| {
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input.chunks(6) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            (String::from_utf8(output).unwrap(), converter.warnings())
+        };
+        let redetect = DetectionCheck {
+            redetect: true,
+            ..DetectionCheck::default()
+        };
+
+        // Only a warning by default
+        let csv = b"id;name\n1;Ada\n";
+        let (output, warnings) = convert(ConverterConfig::new(Format::Csv, Format::Ndjson), csv);
+        assert_eq!(output, "{\"id;name\":\"1;Ada\"}\n");
+        assert_eq!(warnings[0].code, WarningCode::DetectionSuspect);
+
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_detection_check(redetect);
+        let (output, _) = convert(config, csv);
+        assert_eq!(output, "{\"id\":\"1\",\"name\":\"Ada\"}\n");
+
+        let xml = b"<items><item><a>1</a></item><item><a>2</a></item></items>";
+        let config = ConverterConfig::new(Format::Xml, Format::Ndjson).with_detection_check(DetectionCheck {
+            after_bytes: 32,
+            ..redetect
+        });
+        let (output, warnings) = convert(config, xml);
+        assert_eq!(output, "{\"a\":\"1\"}\n{\"a\":\"2\"}\n");
+        assert!(warnings[0].message.contains("no <row> records"));
+    }
+
+    #[test]
+    fn test_json_output_pretty() {
+        let pretty = JsonOutputConfig {
+            pretty: true,
+            indent: 2,
+        };
+        let convert = |config: ConverterConfig, input: &[u8]| {
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input.chunks(3) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            String::from_utf8(output).unwrap()
+        };
+
+        let config = ConverterConfig::new(Format::Csv, Format::Json).with_json_output_config(pretty);
+        assert_eq!(
+            convert(config, b"id,name\n1,Ada\n2,\"a, b\"\n"),
+            "[\n  {\n    \"id\": \"1\",\n    \"name\": \"Ada\"\n  },\n  {\n    \"id\": \"2\",\n    \"name\": \"a, b\"\n  }\n]\n"
+        );
+
+        // Passthrough keeps key order and reindents existing whitespace
+        let config = ConverterConfig::new(Format::Json, Format::Json).with_json_output_config(JsonOutputConfig {
+            indent: 4,
+            ..pretty
+        });
+        assert_eq!(
+            convert(config, b"{\"b\": [1,  2], \"a\": {}}\n"),
+            "{\n    \"b\": [\n        1,\n        2\n    ],\n    \"a\": {}\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_xml_output_config() {
+        let config = ConverterConfig::new(Format::Csv, Format::Xml).with_xml_output_config(XmlOutputConfig {
+            root_element: "items".to_string(),
+            record_element: "item".to_string(),
+            indent: 1,
+            ..XmlOutputConfig::default()
+        });
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"id,name\n1,Ada\n").unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<items>\n <item>\n  <id>1</id>\n  <name>Ada</name>\n </item>\n</items>\n"
+        );
+    }
+
+    #[test]
+    fn test_tsv_input_and_output() {
+        let convert = |input: &[u8], config: ConverterConfig| {
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input.chunks(4) {
+                output.extend(converter.push(chunk).unwrap());
+            }
+            output.extend(converter.finish().unwrap());
+            String::from_utf8(output).unwrap()
+        };
+
+        let csv = b"name,note\nAda,\"tab\there\"\nGrace,\"x, y\"\n";
+        let tsv = convert(csv, ConverterConfig::new(Format::Csv, Format::Tsv));
+        assert_eq!(tsv, "name\tnote\nAda\t\"tab\there\"\nGrace\tx, y\n");
+        assert_eq!(
+            convert(tsv.as_bytes(), ConverterConfig::new(Format::Tsv, Format::Ndjson)),
+            "{\"name\":\"Ada\",\"note\":\"tab\\there\"}\n{\"name\":\"Grace\",\"note\":\"x, y\"}\n"
+        );
+
+        let writer = CsvWriterConfig {
+            delimiter: b'|',
+            quote: b'\'',
+            line_ending: LineEnding::Crlf,
+            ..CsvWriterConfig::default()
+        };
+        assert_eq!(
+            convert(csv, ConverterConfig::new(Format::Csv, Format::Csv).with_csv_writer_config(writer)),
+            "name|note\r\nAda|tab\there\r\nGrace|x, y\r\n"
+        );
+    }
+
+    #[test]
+    fn test_warnings_report_skipped_and_coerced_values() {
+        let transform: TransformConfigInput = serde_json::from_value(serde_json::json!({
+            "mode": "augment",
+            "fields": [{ "targetFieldName": "n", "coerce": { "type": "i64" }, "onCoerceError": "null" }]
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Ndjson, Format::Csv)
+            .with_transform(TransformPlan::compile(transform).unwrap());
+        let mut converter = converter_from_config(config);
+
+        let mut output = converter
+            .push(b"{\"n\":\"1\"}\n{\"n\":\"x\",\"extra\":true}\n{\"n\":\"y\"}\n")
+            .unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), "n\n1\n\"\"\n\"\"\n");
+
+        let warnings = converter.warnings();
+        let codes: Vec<(WarningCode, u64)> = warnings.iter().map(|w| (w.code, w.count)).collect();
+        assert_eq!(codes, vec![(WarningCode::CoercionFailed, 2), (WarningCode::FieldDropped, 1)]);
+        assert!(warnings[1].message.contains("'extra'"));
+
+        converter.clear_warnings();
+        assert!(converter.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_auto_input_format_detected_from_first_chunk() {
+        let auto = AutoDetect {
+            input_format: true,
+            csv_delimiter: true,
+            csv_quote: true,
+            csv_has_headers: true,
+            xml_record_element: true,
+        };
+        let convert = |input: &[u8]| {
+            let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+                .with_auto_detect(auto)
+                .with_invalid_utf8(InvalidUtf8Policy::Skip);
+            let mut converter = converter_from_config(config);
+            let mut output = converter.push(input).ok()?;
+            output.extend(converter.finish().ok()?);
+            Some(String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(
+            convert(b"a;b\n1;2\n").unwrap(),
+            "{\"a\":\"1\",\"b\":\"2\"}\n"
+        );
+        assert_eq!(
+            convert(b"<items><item><id>1</id></item><item><id>2</id></item></items>").unwrap(),
+            "{\"id\":\"1\"}\n{\"id\":\"2\"}\n"
+        );
+        // The UTF-8 policy follows the detected format: NDJSON lines are skipped
+        assert_eq!(
+            convert(b"{\"id\":1}\n{\"id\":\"\xff\"}\n{\"id\":3}\n").unwrap(),
+            "{\"id\":1}\n{\"id\":3}\n"
+        );
+        assert_eq!(convert(b"   \n"), None);
+    }
+
+    #[test]
+    fn test_invalid_utf8_policies() {
+        // Latin-1 "é", split across pushes
+        let input: [&[u8]; 2] = [b"name\nJos\xe9\nAda", b"\n"];
+        let run = |policy| {
+            let config = ConverterConfig::new(Format::Csv, Format::Ndjson).with_invalid_utf8(policy);
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input {
+                output.extend(converter.push(chunk).ok()?);
+            }
+            output.extend(converter.finish().ok()?);
+            Some(String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(run(InvalidUtf8Policy::Error), None);
+        assert_eq!(
+            run(InvalidUtf8Policy::Replace).unwrap(),
+            "{\"name\":\"Jos\u{FFFD}\"}\n{\"name\":\"Ada\"}\n"
+        );
+        assert_eq!(run(InvalidUtf8Policy::Skip).unwrap(), "{\"name\":\"Ada\"}\n");
+    }
+
+    #[test]
+    fn test_record_error_policies() {
+        let run = |input_format, input: &[u8], policy| {
+            let config = ConverterConfig::new(input_format, Format::Ndjson).with_record_error_policy(policy);
+            let mut converter = converter_from_config(config);
+            let mut output = Vec::new();
+            for chunk in input.chunks(5) {
+                output.extend(converter.push(chunk).ok()?);
+            }
+            output.extend(converter.finish().ok()?);
+            let skipped = converter.get_stats().records_skipped();
+            Some((String::from_utf8(output).unwrap(), skipped, converter.record_errors()))
+        };
+
+        // A quoted field spans lines 2-3, so the bad row is on line 4
+        let csv = b"name\n\"Ada\nL\"\nJos\xe9\r\nGrace";
+        assert!(run(Format::Csv, csv, RecordErrorPolicy::Abort).is_none());
+        let (output, skipped, errors) = run(Format::Csv, csv, RecordErrorPolicy::Skip).unwrap();
+        assert_eq!(output, "{\"name\":\"Ada\\nL\"}\n{\"name\":\"Grace\"}\n");
+        assert_eq!((skipped, errors.len()), (1.0, 0));
+        let (_, _, errors) = run(Format::Csv, csv, RecordErrorPolicy::Collect).unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+        assert!(errors[0].message.contains("UTF-8"));
+
+        let ndjson = b"{\"a\":1}\n\n{\"a\":}\nnope\n{\"a\":2}";
+        assert!(run(Format::Ndjson, ndjson, RecordErrorPolicy::Abort).is_none());
+        let (output, skipped, errors) = run(Format::Ndjson, ndjson, RecordErrorPolicy::Collect).unwrap();
+        assert_eq!(output, "{\"a\":1}\n{\"a\":2}\n");
+        assert_eq!(skipped, 2.0);
+        let lines: Vec<u64> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![3, 4]);
+    }
+
+    #[test]
+    fn test_with_options() {
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({
+            "onRecordError": "skip",
+            "ndjsonOutput": { "lineEnding": "crlf" }
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Ndjson, Format::Ndjson).with_options(options).unwrap();
+        assert_eq!(config.on_record_error, RecordErrorPolicy::Skip);
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"{\"a\":1}\nnope\n").unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(output, b"{\"a\":1}\r\n");
+
+        let options: ConverterOptionsInput = serde_json::from_value(serde_json::json!({ "threads": 0 })).unwrap();
+        assert!(ConverterConfig::default().with_options(options).is_err());
+    }
+
+    #[test]
+    fn test_take_rejects() {
+        let transform: TransformConfigInput = serde_json::from_value(serde_json::json!({
+            "mode": "augment",
+            "fields": [{ "targetFieldName": "n", "coerce": { "type": "i64" } }]
+        }))
+        .unwrap();
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+            .with_transform(TransformPlan::compile(transform).unwrap())
+            .with_record_error_policy(RecordErrorPolicy::Skip)
+            .with_keep_rejects(true);
+        let mut converter = converter_from_config(config);
+
+        let mut output = converter.push(b"id,n\n1,x\n2,3\n3,\xff\n").unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(output, b"{\"id\":\"2\",\"n\":3}\n");
+        assert_eq!(converter.get_stats().records_skipped(), 2.0);
+
+        let rejects = converter.take_rejects();
+        let rejects: Vec<serde_json::Value> = rejects
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect();
+        assert_eq!(rejects.len(), 2);
+        assert_eq!(rejects[0]["line"], 4);
+        assert_eq!(rejects[0]["raw"], "3,\u{FFFD}");
+        assert_eq!(rejects[1]["line"], 1);
+        assert_eq!(rejects[1]["raw"], "{\"id\":\"1\",\"n\":\"x\"}");
+        assert!(converter.take_rejects().is_empty());
+    }
+
+    #[test]
+    fn test_float_format() {
+        let format = FloatFormat {
+            max_decimals: Some(2),
+            ..FloatFormat::default()
+        };
+        let input = b"{\"price\":19.990000000000002,\"qty\":3,\"note\":\"0.123456\"}\n";
+        let convert = |output_format: Format| {
+            let config = ConverterConfig::new(Format::Ndjson, output_format).with_float_format(format);
+            let mut converter = converter_from_config(config);
+            let mut output = converter.push(input).unwrap();
+            output.extend(converter.finish().unwrap());
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(convert(Format::Ndjson), "{\"price\":19.99,\"qty\":3,\"note\":\"0.123456\"}\n");
+        assert_eq!(convert(Format::Json), "[{\"price\":19.99,\"qty\":3,\"note\":\"0.123456\"}]");
+        assert_eq!(convert(Format::Csv), "note,price,qty\n0.123456,19.99,3\n");
+        assert!(convert(Format::Xml).contains("<price>19.99</price>"));
+
+        let options: ConverterOptionsInput =
+            serde_json::from_value(serde_json::json!({ "floatFormat": { "style": "fixed" } })).unwrap();
+        assert!(ConverterConfig::default().with_options(options).is_err());
+    }
+
+    #[test]
+    fn test_integer_strings() {
+        let csv_config = CsvConfig {
+            infer_types: true,
+            ..CsvConfig::default()
+        };
+        let config = ConverterConfig::new(Format::Csv, Format::Ndjson)
+            .with_csv_config(csv_config)
+            .with_integer_strings(IntegerStrings {
+                fields: vec!["account".to_string()],
+                ..IntegerStrings::default()
+            });
+        let mut converter = converter_from_config(config);
+        let mut output = converter.push(b"id,account,n\n1234567890123456789,42,7\n").unwrap();
+        output.extend(converter.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":\"1234567890123456789\",\"account\":\"42\",\"n\":7}\n"
+        );
+    }
+
+    #[cfg(feature = "wasm")]
     fn build_converter(
         input_format: &str,
         output_format: &str,
@@ -2321,11 +5790,12 @@ mod integration_tests {
             csv_config,
             xml_config,
             JsValue::NULL,
+            JsValue::NULL,
         )
         .expect("converter should build")
     }
 
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
     #[test]
     fn test_parse_configs_and_deserialize_optional() {
         let csv_config = build_csv_config(Some(";"), Some("'"));
@@ -2342,6 +5812,7 @@ mod integration_tests {
         assert!(none_csv.is_none());
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn test_converter_invalid_format_errors() {
         let result = Converter::with_config(
@@ -2353,6 +5824,7 @@ mod integration_tests {
             JsValue::NULL,
             JsValue::NULL,
             JsValue::NULL,
+            JsValue::NULL,
         );
         assert!(result.is_err());
     }
@@ -2368,6 +5840,7 @@ mod integration_tests {
         Ok(())
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn test_converter_states_csv_and_ndjson() {
         let mut csv_to_ndjson = build_converter(
@@ -2411,6 +5884,7 @@ mod integration_tests {
         assert!(String::from_utf8_lossy(&output).contains("\"a\""));
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn test_converter_states_ndjson_targets() {
         let mut ndjson_to_json = build_converter("ndjson", "json", false, JsValue::NULL, JsValue::NULL);
@@ -2436,6 +5910,7 @@ mod integration_tests {
         assert!(combined.contains("<root>"));
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn test_converter_states_xml_targets() {
         let mut xml_to_ndjson = build_converter("xml", "ndjson", false, JsValue::NULL, JsValue::NULL);
@@ -2461,6 +5936,7 @@ mod integration_tests {
         assert!(String::from_utf8_lossy(&output).contains("Ada"));
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn test_converter_states_json_targets() {
         let mut json_passthrough = build_converter("json", "json", false, JsValue::NULL, JsValue::NULL);
@@ -2492,6 +5968,7 @@ mod integration_tests {
         assert!(combined.contains("<root>"));
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn test_stats_and_finish_errors() {
         let mut converter = build_converter("ndjson", "json", true, JsValue::NULL, JsValue::NULL);
@@ -2504,6 +5981,7 @@ mod integration_tests {
         assert!(error.is_err());
     }
 
+    #[cfg(feature = "wasm")]
     #[test]
     fn test_push_after_finish_errors() {
         let mut converter = build_converter("json", "json", false, JsValue::NULL, JsValue::NULL);
@@ -2519,7 +5997,7 @@ mod integration_tests {
         let format = detect_format(br#"{"a":1}"#);
         assert_eq!(format, Some("json".to_string()));
 
-        #[cfg(target_arch = "wasm32")]
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
         {
             let csv_result = detect_csv_fields(b"a,b\n1,2\n");
             assert!(!csv_result.is_null());
@@ -2530,7 +6008,7 @@ mod integration_tests {
     }
 
     // --- Roundtrip consistency wasm tests (moved from separate module) ---
-    #[cfg(target_arch = "wasm32")]
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
     mod wasm_roundtrip {
         use super::*;
         use wasm_bindgen_test::*;
@@ -2669,7 +6147,7 @@ pub fn init_nodejs_thread_pool(thread_count: usize) -> bool {
     
     match init_thread_pool(thread_count) {
         Ok(_) => {
-            info!("Node.js WASM thread pool initialized with {} threads", thread_count);
+            log::info!("Node.js WASM thread pool initialized with {} threads", thread_count);
             true
         }
         Err(e) => {
@@ -2702,6 +6180,7 @@ pub fn init_nodejs_thread_pool_auto() -> bool {
 }
 
 // Performance and threading information functions
+#[cfg(feature = "wasm")]
 #[wasm_bindgen]
 pub fn get_threading_support_info() -> JsValue {
     let info = serde_json::json!({
@@ -0,0 +1,175 @@
+use crate::error::{ConvertError, Result};
+use serde::{Deserialize, Serialize};
+
+/// Hard caps for converting untrusted input; `None` leaves a dimension unbounded.
+///
+/// Exceeding any limit fails the conversion with
+/// [`ConvertError::LimitExceeded`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ResourceLimits {
+    /// Total bytes pushed into the converter, counted after decompression.
+    pub max_input_bytes: Option<usize>,
+    /// Input records parsed.
+    pub max_records: Option<usize>,
+    /// Bytes in one CSV field, JSON string or XML text/attribute value, as
+    /// written in the input.
+    pub max_field_bytes: Option<usize>,
+    /// Nesting depth of JSON objects and arrays, or of XML elements counted
+    /// from the record element.
+    pub max_depth: Option<usize>,
+}
+
+impl ResourceLimits {
+    pub(crate) fn check_field(&self, bytes: usize) -> Result<()> {
+        match self.max_field_bytes {
+            Some(max) if bytes > max => Err(ConvertError::LimitExceeded(format!(
+                "field of more than {max} bytes (maxFieldBytes)"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_depth(&self, depth: usize) -> Result<()> {
+        match self.max_depth {
+            Some(max) if depth > max => Err(ConvertError::LimitExceeded(format!(
+                "nesting deeper than {max} levels (maxDepth)"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Converter-wide limit bookkeeping: input size, record count, and a
+/// structural scan of JSON/NDJSON input for string length and nesting depth.
+///
+/// CSV fields and XML values are checked by their parsers, which already
+/// split them out. JSON input reaches several pipelines, so it is checked
+/// here once, on the raw bytes.
+#[derive(Debug, Default)]
+pub(crate) struct LimitGuard {
+    limits: ResourceLimits,
+    input_bytes: usize,
+    records: usize,
+    json: JsonScan,
+}
+
+#[derive(Debug, Default)]
+struct JsonScan {
+    depth: usize,
+    in_string: bool,
+    escape: bool,
+    string_bytes: usize,
+}
+
+impl LimitGuard {
+    pub(crate) fn new(limits: ResourceLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn add_input(&mut self, bytes: usize) -> Result<()> {
+        self.input_bytes += bytes;
+        match self.limits.max_input_bytes {
+            Some(max) if self.input_bytes > max => Err(ConvertError::LimitExceeded(format!(
+                "input larger than {max} bytes (maxInputBytes)"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn add_records(&mut self, count: usize) -> Result<()> {
+        self.records += count;
+        match self.limits.max_records {
+            Some(max) if self.records > max => Err(ConvertError::LimitExceeded(format!(
+                "more than {max} records (maxRecords)"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Track string lengths and bracket depth across JSON or NDJSON chunks.
+    ///
+    /// Only structure is followed; malformed input is left for the parsers
+    /// to report.
+    pub(crate) fn scan_json(&mut self, chunk: &[u8]) -> Result<()> {
+        if self.limits.max_field_bytes.is_none() && self.limits.max_depth.is_none() {
+            return Ok(());
+        }
+        let scan = &mut self.json;
+        for &b in chunk {
+            if scan.in_string {
+                if scan.escape {
+                    scan.escape = false;
+                } else if b == b'\\' {
+                    scan.escape = true;
+                } else if b == b'"' {
+                    scan.in_string = false;
+                    continue;
+                }
+                scan.string_bytes += 1;
+                self.limits.check_field(scan.string_bytes)?;
+                continue;
+            }
+            match b {
+                b'"' => {
+                    scan.in_string = true;
+                    scan.string_bytes = 0;
+                }
+                b'{' | b'[' => {
+                    scan.depth += 1;
+                    self.limits.check_depth(scan.depth)?;
+                }
+                b'}' | b']' => scan.depth = scan.depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guard(limits: ResourceLimits) -> LimitGuard {
+        LimitGuard::new(limits)
+    }
+
+    #[test]
+    fn counts_input_and_records_across_calls() {
+        let mut g = guard(ResourceLimits {
+            max_input_bytes: Some(10),
+            max_records: Some(2),
+            ..ResourceLimits::default()
+        });
+        assert!(g.add_input(6).is_ok());
+        assert!(matches!(g.add_input(5), Err(ConvertError::LimitExceeded(_))));
+        assert!(g.add_records(2).is_ok());
+        assert!(matches!(g.add_records(1), Err(ConvertError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn scans_json_split_across_chunks() {
+        let depth = ResourceLimits {
+            max_depth: Some(2),
+            ..ResourceLimits::default()
+        };
+        let mut g = guard(depth);
+        assert!(g.scan_json(b"{\"a\":[1,\"]]]\"").is_ok());
+        assert!(g.scan_json(b"]}\n{\"b\":{}}\n").is_ok());
+        let mut g = guard(depth);
+        assert!(g.scan_json(b"{\"a\":[").is_ok());
+        assert!(g.scan_json(b"{}]}").is_err());
+
+        let mut g = guard(ResourceLimits {
+            max_field_bytes: Some(4),
+            ..ResourceLimits::default()
+        });
+        assert!(g.scan_json(b"{\"ab\\\"").is_ok());
+        assert!(g.scan_json(b"\":\"12").is_ok());
+        assert!(g.scan_json(b"345\"}").is_err());
+    }
+}
@@ -0,0 +1,58 @@
+use js_sys::Function;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::cell::RefCell;
+use wasm_bindgen::JsValue;
+
+thread_local! {
+    /// JS function taking `(level, message, target)`; `None` logs to the console.
+    static LOG_SINK: RefCell<Option<Function>> = const { RefCell::new(None) };
+}
+
+/// `log` backend of the JS bindings. Native users install their own logger
+/// (`env_logger`, `tracing-log`, ...) instead, since the crate only logs
+/// through the `log` macros.
+struct JsLogger;
+
+static LOGGER: JsLogger = JsLogger;
+
+impl Log for JsLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = JsValue::from(record.args().to_string());
+        // Cloned out so the sink may replace itself while being called
+        let sink = LOG_SINK.with(|sink| sink.borrow().clone());
+        match sink {
+            Some(sink) => {
+                let level = JsValue::from(record.level().as_str().to_ascii_lowercase());
+                // A throwing sink must not take the conversion down with it
+                let _ = sink.call3(&JsValue::NULL, &level, &message, &JsValue::from(record.target()));
+            }
+            None => match record.level() {
+                Level::Error => web_sys::console::error_1(&message),
+                Level::Warn => web_sys::console::warn_1(&message),
+                Level::Info => web_sys::console::info_1(&message),
+                Level::Debug | Level::Trace => web_sys::console::debug_1(&message),
+            },
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Route log records up to `level` to JS, unless another logger is installed.
+pub(crate) fn install(level: LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Send log records on this thread to `sink`, or back to the console.
+pub(crate) fn set_sink(sink: Option<Function>) {
+    LOG_SINK.with(|current| *current.borrow_mut() = sink);
+}
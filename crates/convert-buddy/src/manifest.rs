@@ -0,0 +1,172 @@
+use crate::chunking::sha256_hex;
+use crate::detect::DetectionResult;
+use crate::format::{ConverterConfig, Format};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Size and SHA-256 of a byte stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Checksum {
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// Record of a finished conversion, built by `finish()` when `manifest` is
+/// set, for pipelines to archive next to the output.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionManifest {
+    pub input_format: String,
+    pub output_format: String,
+    /// Settings in effect, detected ones included, shaped like the
+    /// converter options.
+    pub config: Value,
+    /// What was detected from the first chunk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detection: Option<DetectionResult>,
+    /// Input records parsed.
+    pub records: u64,
+    /// Records dropped under the `skip` or `collect` record error policy.
+    pub records_skipped: u64,
+    pub warnings: usize,
+    /// Time from the first push to the end of `finish()`.
+    pub duration_ms: f64,
+    /// The bytes pushed, before decompression.
+    pub input: Checksum,
+    /// The bytes returned, after compression.
+    pub output: Checksum,
+}
+
+/// Counts about a conversion the manifest takes from the converter.
+pub(crate) struct ManifestCounts {
+    pub(crate) records: u64,
+    pub(crate) records_skipped: u64,
+    pub(crate) warnings: usize,
+}
+
+#[derive(Debug, Default)]
+struct StreamDigest {
+    bytes: u64,
+    digest: Sha256,
+}
+
+impl StreamDigest {
+    fn update(&mut self, data: &[u8]) {
+        self.bytes += data.len() as u64;
+        self.digest.update(data);
+    }
+
+    fn finish(&mut self) -> Checksum {
+        Checksum {
+            bytes: self.bytes,
+            sha256: sha256_hex(std::mem::take(&mut self.digest)),
+        }
+    }
+}
+
+/// Collects what [`ConversionManifest`] needs while a converter runs.
+#[derive(Debug, Default)]
+pub(crate) struct ManifestRecorder {
+    started_ms: Option<f64>,
+    input: StreamDigest,
+    output: StreamDigest,
+    manifest: Option<ConversionManifest>,
+}
+
+impl ManifestRecorder {
+    pub(crate) fn for_config(config: &ConverterConfig) -> Option<Self> {
+        config.manifest.then(Self::default)
+    }
+
+    /// Take bytes as pushed by the caller.
+    pub(crate) fn input(&mut self, data: &[u8]) {
+        self.started_ms.get_or_insert_with(crate::timing::now_ms);
+        self.input.update(data);
+    }
+
+    /// Take bytes as returned to the caller.
+    pub(crate) fn output(&mut self, data: &[u8]) {
+        self.output.update(data);
+    }
+
+    pub(crate) fn finish(
+        &mut self,
+        config: &ConverterConfig,
+        detection: Option<&DetectionResult>,
+        counts: ManifestCounts,
+    ) {
+        let now = crate::timing::now_ms();
+        self.manifest = Some(ConversionManifest {
+            input_format: config.input_format.to_string_js(),
+            output_format: config.output_format.to_string_js(),
+            config: effective_config(config),
+            detection: detection.cloned(),
+            records: counts.records,
+            records_skipped: counts.records_skipped,
+            warnings: counts.warnings,
+            duration_ms: now - self.started_ms.unwrap_or(now),
+            input: self.input.finish(),
+            output: self.output.finish(),
+        });
+    }
+
+    /// The manifest, once the converter has finished.
+    pub(crate) fn manifest(&self) -> Option<&ConversionManifest> {
+        self.manifest.as_ref()
+    }
+}
+
+/// The parsing settings of the input format, the transform and the options
+/// that change what is written.
+fn effective_config(config: &ConverterConfig) -> Value {
+    let mut settings = Map::new();
+    match (config.input_format, &config.csv_config, &config.xml_config) {
+        (Format::Csv | Format::Tsv, Some(csv), _) => {
+            settings.insert(
+                "csvConfig".to_string(),
+                json!({
+                    "delimiter": char::from(csv.delimiter).to_string(),
+                    "quote": char::from(csv.quote).to_string(),
+                    "hasHeaders": csv.has_headers,
+                    "inferTypes": csv.infer_types,
+                }),
+            );
+        }
+        (Format::Xml, _, Some(xml)) => {
+            settings.insert(
+                "xmlConfig".to_string(),
+                json!({
+                    "recordElement": xml.record_element,
+                    "recordPath": xml.record_path,
+                    "namespaces": xml.namespaces,
+                    "includeAttributes": xml.include_attributes,
+                }),
+            );
+        }
+        (Format::Json, _, _) => {
+            settings.insert("jsonInput".to_string(), json!(config.json_input));
+        }
+        _ => {}
+    }
+    if let Some(plan) = &config.transform {
+        settings.insert("transform".to_string(), json!(plan.to_config()));
+    }
+    settings.insert(
+        "options".to_string(),
+        json!({
+            "inputCompression": config.input_compression,
+            "outputCompression": config.output_compression,
+            "invalidUtf8": config.invalid_utf8,
+            "onRecordError": config.on_record_error,
+            "limits": config.limits,
+            "projection": config.projection,
+            "dedupe": config.dedupe,
+            "sort": config.sort,
+            "maxRecords": config.max_records,
+            "sampleEveryN": config.sample_every_n,
+            "outputColumns": config.output_columns,
+        }),
+    );
+    Value::Object(settings)
+}
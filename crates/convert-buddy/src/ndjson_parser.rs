@@ -1,16 +1,13 @@
-use crate::error::Result;
+use crate::error::{ConvertError, Result};
 use crate::json_parser::JsonParser;
-use crate::buffer_pool::BufferPool;
+use crate::projection::{ColumnMapper, OutputColumn};
+use crate::buffer_pool;
+use crate::record_errors::RecordErrors;
+use crate::transform::record_snippet;
+use crate::warnings::{WarningCode, Warnings};
 use log::debug;
 use memchr::memchr;
-
-#[cfg(feature = "threads")]
-use rayon::prelude::*;
-
-// Thread-local buffer pool for reduced allocations
-thread_local! {
-    static BUFFER_POOL: BufferPool = BufferPool::default();
-}
+use serde::Deserialize;
 
 /// High-performance NDJSON (Newline Delimited JSON) parser
 /// Uses memchr for fast line splitting and minimal allocations
@@ -20,6 +17,13 @@ pub struct NdjsonParser {
     output_buffer: Vec<u8>,
     chunk_target_bytes: usize,
     items_written: usize, // Track number of items written for proper comma placement
+    ordered: bool, // Keep parallel output in input order
+    threads: Option<usize>, // Parallel thread limit; None defers to the pool
+    warnings: Warnings,
+    pretty: Option<JsonPrettifier>, // Indents the JSON array output
+    errors: RecordErrors, // What happens to lines that fail to parse
+    lines: u64, // Lines consumed so far, for the line numbers of failed records
+    columns: Option<ColumnMapper>, // `outputColumns` of the JSON array output
 }
 
 impl NdjsonParser {
@@ -30,9 +34,53 @@ impl NdjsonParser {
             output_buffer: Vec::with_capacity(chunk_target_bytes),
             chunk_target_bytes,
             items_written: 0,
+            ordered: true,
+            threads: None,
+            warnings: Warnings::default(),
+            pretty: None,
+            errors: RecordErrors::default(),
+            lines: 0,
+            columns: None,
         }
     }
 
+    /// Allow parallel batches to be emitted as they finish instead of in input order.
+    pub fn with_ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Cap the threads used by the parallel path.
+    pub fn with_threads(mut self, threads: Option<usize>) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Report skipped blank and invalid lines.
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Skip or collect lines that aren't valid JSON instead of failing on them.
+    pub fn with_record_errors(mut self, errors: RecordErrors) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Indent the output of `to_json_array` when `config.pretty` is set.
+    pub fn with_json_output(mut self, config: JsonOutputConfig) -> Self {
+        self.pretty = config.pretty.then(|| JsonPrettifier::new(config.indent));
+        self
+    }
+
+    /// Write only these fields of each record, renamed and in this order,
+    /// into the output of `to_json_array`.
+    pub fn with_output_columns(mut self, columns: Option<&[OutputColumn]>) -> Self {
+        self.columns = columns.map(ColumnMapper::new);
+        self
+    }
+
     /// Process a chunk of NDJSON data
     /// Returns output bytes when buffer reaches target size
     pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
@@ -62,8 +110,9 @@ impl NdjsonParser {
             let line = &input_data[start..line_end];
 
             // Skip empty lines
-            if !line.is_empty() && !line.iter().all(|&b| b.is_ascii_whitespace()) {
-                self.process_line(line, &mut output)?;
+            self.lines += 1;
+            if self.is_record_line(line) {
+                self.process_record(line, &mut output)?;
             }
 
             start = line_end + 1;
@@ -79,11 +128,13 @@ impl NdjsonParser {
     }
 
     /// Process a chunk of NDJSON data using parallel processing
-    /// This method processes multiple lines in parallel for better performance on large datasets
+    /// This method processes multiple lines in parallel for better performance on large datasets.
+    /// Unless `ordered` is turned off, the output matches `push` byte for byte.
     #[cfg(feature = "threads")]
     pub fn push_parallel(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
-        // For small chunks, use sequential processing
-        if chunk.len() < 32 * 1024 { // 32KB threshold
+        // For small chunks, use sequential processing. Skipped lines are
+        // reported with their number, which only the sequential path tracks.
+        if chunk.len() < 32 * 1024 || !self.errors.aborts() { // 32KB threshold
             return self.push(chunk);
         }
 
@@ -113,7 +164,7 @@ impl NdjsonParser {
             let line = &input_data[start..line_end];
 
             // Skip empty lines
-            if !line.is_empty() && !line.iter().all(|&b| b.is_ascii_whitespace()) {
+            if self.is_record_line(line) {
                 lines.push(line);
             }
 
@@ -124,42 +175,11 @@ impl NdjsonParser {
         let mut output = Vec::with_capacity(estimated_size);
         
         if lines.len() > 1 {
-            // Parallel processing
-            let parallel_results: Result<Vec<Vec<u8>>> = lines
-                .par_iter()
-                .map(|line| {
-                    // Quick validation before full parse
-                    if !self.json_parser.quick_validate(line) {
-                        debug!("Skipping invalid JSON line");
-                        return Ok(Vec::new());
-                    }
-
-                    // For NDJSON, we validate and pass through
-                    #[cfg(feature = "simd")]
-                    {
-                        let mut mutable_line = line.to_vec();
-                        self.json_parser.parse_and_validate(&mut mutable_line)?;
-                    }
-                    
-                    #[cfg(not(feature = "simd"))]
-                    {
-                        self.json_parser.parse_and_validate(line)?;
-                    }
-                    
-                    let mut line_output = Vec::with_capacity(line.len() + 1);
-                    line_output.extend_from_slice(line);
-                    line_output.push(b'\n');
-                    
-                    Ok(line_output)
-                })
-                .collect();
-
-            // Combine parallel results
-            for line_result in parallel_results? {
-                if !line_result.is_empty() {
-                    output.extend_from_slice(&line_result);
-                }
-            }
+            // Parallel processing in contiguous batches; see `with_ordered`
+            let this = &*self;
+            crate::parallel::convert_lines(&lines, self.threads, self.ordered, &mut output, |line, out| {
+                this.process_line(line, out)
+            })?;
         } else if lines.len() == 1 {
             // Single line, process sequentially
             self.process_line(lines[0], &mut output)?;
@@ -174,29 +194,43 @@ impl NdjsonParser {
         Ok(output)
     }
 
+    /// Whether `line` holds a record; blank lines are reported and skipped.
+    fn is_record_line(&self, line: &[u8]) -> bool {
+        let blank = line.iter().all(|&b| b.is_ascii_whitespace());
+        if blank {
+            self.warnings
+                .warn(WarningCode::BlankLineSkipped, || "blank line skipped".to_string());
+        }
+        !blank
+    }
+
+    /// Process a line, applying the record error policy.
+    fn process_record(&self, line: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        if self.errors.aborts() {
+            return self.process_line(line, output);
+        }
+        if !self.json_parser.quick_validate(line) {
+            let error = ConvertError::JsonParse(format!("not JSON: {}", record_snippet(line)));
+            return self.errors.handle(self.lines, line, error);
+        }
+        self.process_line(line, output)
+            .or_else(|error| self.errors.handle(self.lines, line, error))
+    }
+
     /// Process a single JSON line
-    fn process_line(&mut self, line: &[u8], output: &mut Vec<u8>) -> Result<()> {
+    fn process_line(&self, line: &[u8], output: &mut Vec<u8>) -> Result<()> {
         // Quick validation before full parse
         if !self.json_parser.quick_validate(line) {
             debug!("Skipping invalid JSON line");
+            self.warnings.warn(WarningCode::InvalidLineSkipped, || {
+                format!("skipped a line that isn't JSON: {}", record_snippet(line))
+            });
             return Ok(());
         }
 
-        // For NDJSON, we typically want to pass through or transform
-        // For now, we'll validate and pass through
-        #[cfg(feature = "simd")]
-        {
-            let mut mutable_line = line.to_vec();
-            self.json_parser.parse_and_validate(&mut mutable_line)?;
-            output.extend_from_slice(line);
-        }
-        
-        #[cfg(not(feature = "simd"))]
-        {
-            self.json_parser.parse_and_validate(line)?;
-            output.extend_from_slice(line);
-        }
-        
+        // Lines are validated and passed through as they are
+        self.json_parser.parse_and_validate(line)?;
+        output.extend_from_slice(line);
         output.push(b'\n');
         
         Ok(())
@@ -209,8 +243,9 @@ impl NdjsonParser {
         // Process any remaining partial line
         if !self.partial_line.is_empty() {
             let line = std::mem::take(&mut self.partial_line);
+            self.lines += 1;
             if !line.iter().all(|&b| b.is_ascii_whitespace()) {
-                self.process_line(&line, &mut output)?;
+                self.process_record(&line, &mut output)?;
             }
         }
 
@@ -227,12 +262,18 @@ impl NdjsonParser {
         self.partial_line.len()
     }
 
+    /// Drop the line cut off at the end of the last chunk.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn discard_partial(&mut self) {
+        self.partial_line.clear();
+    }
+
     /// Convert NDJSON to JSON array with streaming output
     /// Optimized to minimize allocations and use buffer pooling
     pub fn to_json_array(&mut self, chunk: &[u8], is_first: bool, is_last: bool) -> Result<Vec<u8>> {
         // Use pooled buffer for output
         let output_capacity = if is_first { chunk.len() + 2 } else { chunk.len() + 1 };
-        let mut output = BUFFER_POOL.with(|pool: &BufferPool| pool.acquire_with_capacity(output_capacity));
+        let mut output = buffer_pool::acquire_shared(output_capacity);
 
         if is_first {
             output.push(b'[');
@@ -263,7 +304,7 @@ impl NdjsonParser {
                 self.items_written += 1;
 
                 // Direct copy for valid JSON (streaming, no re-parsing)
-                output.extend_from_slice(line);
+                self.write_item(line, &mut output);
             }
 
             start = line_end + 1;
@@ -285,14 +326,142 @@ impl NdjsonParser {
                         output.push(b',');
                     }
                     self.items_written += 1;
-                    output.extend_from_slice(&line);
+                    self.write_item(&line, &mut output);
                 }
             }
             output.push(b']');
         }
 
+        if let Some(pretty) = &mut self.pretty {
+            let mut indented = Vec::with_capacity(output.len() * 2);
+            pretty.push(&output, &mut indented);
+            if is_last {
+                indented.extend(pretty.finish());
+            }
+            return Ok(indented);
+        }
+
         Ok(output)
     }
+
+    /// Copy a record line into the JSON array, applying `outputColumns` to
+    /// objects.
+    fn write_item(&self, line: &[u8], output: &mut Vec<u8>) {
+        let mapped = self.columns.as_ref().is_some_and(|columns| columns.map(line, output));
+        if !mapped {
+            output.extend_from_slice(line);
+        }
+    }
+}
+
+/// How JSON output is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct JsonOutputConfig {
+    /// Put every value on its own line, indented by nesting depth.
+    pub pretty: bool,
+    /// Spaces per nesting level when `pretty` is set.
+    pub indent: usize,
+}
+
+impl Default for JsonOutputConfig {
+    fn default() -> Self {
+        Self {
+            pretty: false,
+            indent: 2,
+        }
+    }
+}
+
+/// Re-indents a stream of JSON text without parsing it into values, so key
+/// order and number spelling are kept and a value may span any number of
+/// chunks. Whitespace outside strings is dropped and replaced; empty objects
+/// and arrays stay as `{}` and `[]`.
+pub(crate) struct JsonPrettifier {
+    indent: usize,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    /// An object or array was just opened; its first byte decides whether it is empty.
+    opened: bool,
+    written: bool,
+}
+
+impl JsonPrettifier {
+    pub(crate) fn new(indent: usize) -> Self {
+        Self {
+            indent,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            opened: false,
+            written: false,
+        }
+    }
+
+    pub(crate) fn push(&mut self, input: &[u8], output: &mut Vec<u8>) {
+        for &byte in input {
+            if self.in_string {
+                output.push(byte);
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+            self.written = true;
+            if std::mem::take(&mut self.opened) {
+                if byte == b'}' || byte == b']' {
+                    self.depth = self.depth.saturating_sub(1);
+                    output.push(byte);
+                    continue;
+                }
+                self.new_line(output);
+            }
+            match byte {
+                b'{' | b'[' => {
+                    output.push(byte);
+                    self.depth += 1;
+                    self.opened = true;
+                }
+                b'}' | b']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    self.new_line(output);
+                    output.push(byte);
+                }
+                b',' => {
+                    output.push(b',');
+                    self.new_line(output);
+                }
+                b':' => output.extend_from_slice(b": "),
+                b'"' => {
+                    output.push(b'"');
+                    self.in_string = true;
+                }
+                _ => output.push(byte),
+            }
+        }
+    }
+
+    /// End the output with a newline once anything was written.
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        if std::mem::take(&mut self.written) {
+            b"\n".to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn new_line(&self, output: &mut Vec<u8>) {
+        output.push(b'\n');
+        output.resize(output.len() + self.depth * self.indent, b' ');
+    }
 }
 
 impl Default for NdjsonParser {
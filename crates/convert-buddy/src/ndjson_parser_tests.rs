@@ -1,7 +1,7 @@
 #[cfg(test)]
 mod ndjson_parser_tests {
     use wasm_bindgen_test::*;
-    use crate::ndjson_parser::NdjsonParser;
+    use crate::ndjson_parser::{JsonOutputConfig, NdjsonParser};
 
     #[wasm_bindgen_test]
     fn test_ndjson_parsing() {
@@ -37,6 +37,23 @@ mod ndjson_parser_tests {
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_to_json_array_pretty() {
+        let config = JsonOutputConfig {
+            pretty: true,
+            indent: 2,
+        };
+        let mut parser = NdjsonParser::new(1024).with_json_output(config);
+        let mut result = parser.to_json_array(b"{\"s\":\"a,\\\"{b\",\"e\":[]}\n{\"n\"", true, false).unwrap();
+        result.extend(parser.to_json_array(b":[1,{}]}\n", false, false).unwrap());
+        result.extend(parser.to_json_array(&[], false, true).unwrap());
+        let expected = "[\n  {\n    \"s\": \"a,\\\"{b\",\n    \"e\": []\n  },\n  {\n    \"n\": [\n      1,\n      {}\n    ]\n  }\n]\n";
+        assert_eq!(String::from_utf8(result).unwrap(), expected);
+
+        let mut empty = NdjsonParser::new(1024).with_json_output(config);
+        assert_eq!(empty.to_json_array(&[], true, true).unwrap(), b"[]\n");
+    }
+
     #[wasm_bindgen_test]
     fn test_skip_invalid_and_whitespace_lines() {
         let mut parser = NdjsonParser::new(1024);
@@ -68,4 +85,34 @@ mod ndjson_parser_tests {
         
         assert!(!combined.is_empty());
     }
+
+    #[cfg(feature = "threads")]
+    #[test]
+    fn test_parallel_output_matches_sequential() {
+        let mut input = Vec::new();
+        for i in 0..3000 {
+            input.extend_from_slice(format!("{{\"id\":{i},\"tags\":[\"a\",\"b\"]}}\n\n").as_bytes());
+        }
+
+        let mut sequential = NdjsonParser::new(1024);
+        let expected = [sequential.push(&input).unwrap(), sequential.finish().unwrap()].concat();
+
+        for chunk_size in [input.len(), 40_009] {
+            let mut parser = NdjsonParser::new(1024);
+            let mut output = Vec::new();
+            for chunk in input.chunks(chunk_size) {
+                output.extend(parser.push_parallel(chunk).unwrap());
+            }
+            output.extend(parser.finish().unwrap());
+            assert!(output == expected, "parallel output differs at chunk size {chunk_size}");
+        }
+
+        let mut unordered = NdjsonParser::new(1024).with_ordered(false);
+        let output = [unordered.push_parallel(&input).unwrap(), unordered.finish().unwrap()].concat();
+        let mut lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+        let mut expected_lines: Vec<&[u8]> = expected.split(|&b| b == b'\n').collect();
+        lines.sort();
+        expected_lines.sort();
+        assert_eq!(lines, expected_lines);
+    }
 }
@@ -0,0 +1,202 @@
+use serde::Deserialize;
+
+/// ASCII record separator, used by RFC 7464 JSON text sequences.
+const RECORD_SEPARATOR: u8 = 0x1E;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub(crate) fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::Crlf => b"\r\n",
+        }
+    }
+}
+
+/// How NDJSON output records are framed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct NdjsonOutputConfig {
+    pub line_ending: LineEnding,
+    /// Whether the last record is followed by a line ending.
+    pub trailing_newline: bool,
+    /// Prefix every record with an RS (`0x1E`) byte.
+    pub record_separator: bool,
+    /// End every record with a NUL byte instead of `line_ending`, for tools
+    /// reading records like `xargs -0`.
+    pub nul_terminated: bool,
+}
+
+impl Default for NdjsonOutputConfig {
+    fn default() -> Self {
+        Self {
+            line_ending: LineEnding::Lf,
+            trailing_newline: true,
+            record_separator: false,
+            nul_terminated: false,
+        }
+    }
+}
+
+impl NdjsonOutputConfig {
+    /// Bytes ending each record.
+    pub(crate) fn record_end(&self) -> &'static [u8] {
+        if self.nul_terminated {
+            b"\0"
+        } else {
+            self.line_ending.as_bytes()
+        }
+    }
+}
+
+/// Re-frames `\n`-delimited NDJSON output according to an [`NdjsonOutputConfig`].
+///
+/// Blank lines are dropped and `\r\n` input endings are normalized. Without a
+/// trailing newline, each line ending is held back until the next record
+/// starts, so chunked output concatenates to the same bytes.
+#[derive(Debug)]
+pub struct NdjsonWriter {
+    config: NdjsonOutputConfig,
+    at_record_start: bool,
+    pending_newline: bool,
+    /// The last chunk ended in `\r`, which is dropped if a `\n` follows.
+    held_cr: bool,
+}
+
+impl NdjsonWriter {
+    pub fn new(config: NdjsonOutputConfig) -> Self {
+        Self {
+            config,
+            at_record_start: true,
+            pending_newline: false,
+            held_cr: false,
+        }
+    }
+
+    pub fn write(&mut self, ndjson: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(ndjson.len() + ndjson.len() / 16);
+
+        for segment in ndjson.split_inclusive(|&b| b == b'\n') {
+            let (body, terminated) = match segment.strip_suffix(b"\n") {
+                Some(body) => (body.strip_suffix(b"\r").unwrap_or(body), true),
+                // A `\r` at the end of the chunk may be half of a CRLF
+                None => match segment.strip_suffix(b"\r") {
+                    Some(body) => (body, false),
+                    None => (segment, false),
+                },
+            };
+            let held_cr = std::mem::replace(&mut self.held_cr, !terminated && body.len() < segment.len());
+
+            if !body.is_empty() {
+                if self.pending_newline {
+                    output.extend_from_slice(self.config.record_end());
+                    self.pending_newline = false;
+                }
+                if self.at_record_start && self.config.record_separator {
+                    output.push(RECORD_SEPARATOR);
+                }
+                self.at_record_start = false;
+                if held_cr {
+                    output.push(b'\r');
+                }
+                output.extend_from_slice(body);
+            }
+
+            if terminated && !self.at_record_start {
+                self.end_record(&mut output);
+            }
+        }
+
+        output
+    }
+
+    /// Close a final record that arrived without a line ending.
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.held_cr = false;
+        if !self.at_record_start {
+            self.end_record(&mut output);
+        }
+        output
+    }
+
+    fn end_record(&mut self, output: &mut Vec<u8>) {
+        if self.config.trailing_newline {
+            output.extend_from_slice(self.config.record_end());
+        } else {
+            self.pending_newline = true;
+        }
+        self.at_record_start = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(config: NdjsonOutputConfig, chunks: &[&[u8]]) -> Vec<u8> {
+        let mut writer = NdjsonWriter::new(config);
+        let mut output = Vec::new();
+        for chunk in chunks {
+            output.extend(writer.write(chunk));
+        }
+        output.extend(writer.finish());
+        output
+    }
+
+    #[test]
+    fn crlf_and_record_separator() {
+        let config = NdjsonOutputConfig {
+            line_ending: LineEnding::Crlf,
+            record_separator: true,
+            ..NdjsonOutputConfig::default()
+        };
+        let output = frame(config, &[b"{\"a\":1}\n{\"a\"", b":2}\r\n\n{\"a\":3}"]);
+        assert_eq!(
+            output,
+            b"\x1e{\"a\":1}\r\n\x1e{\"a\":2}\r\n\x1e{\"a\":3}\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn crlf_split_across_chunks() {
+        let config = NdjsonOutputConfig {
+            line_ending: LineEnding::Crlf,
+            ..NdjsonOutputConfig::default()
+        };
+        let output = frame(config, &[b"{\"a\":1}\r", b"\n{\"a\":2}\r", b"\n{\"a\":3}\r"]);
+        assert_eq!(output, b"{\"a\":1}\r\n{\"a\":2}\r\n{\"a\":3}\r\n".to_vec());
+
+        let output = frame(NdjsonOutputConfig::default(), &[b"{\"a\":1}\r", b"\n", b"{\"a\":2}\r", b"\r\n"]);
+        assert_eq!(output, b"{\"a\":1}\n{\"a\":2}\n".to_vec());
+    }
+
+    #[test]
+    fn nul_terminated_records() {
+        let config = NdjsonOutputConfig {
+            nul_terminated: true,
+            ..NdjsonOutputConfig::default()
+        };
+        let output = frame(config, &[b"{\"a\":\"x\\ny\"}\n{\"a\"", b":2}\r\n"]);
+        assert_eq!(output, b"{\"a\":\"x\\ny\"}\0{\"a\":2}\0".to_vec());
+    }
+
+    #[test]
+    fn trailing_newline_is_held_back_across_chunks() {
+        let config = NdjsonOutputConfig {
+            trailing_newline: false,
+            ..NdjsonOutputConfig::default()
+        };
+        let mut writer = NdjsonWriter::new(config);
+        assert_eq!(writer.write(b"{\"a\":1}\n"), b"{\"a\":1}".to_vec());
+        assert_eq!(writer.write(b"{\"a\":2}\n"), b"\n{\"a\":2}".to_vec());
+        assert!(writer.finish().is_empty());
+    }
+}
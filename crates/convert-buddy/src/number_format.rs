@@ -0,0 +1,301 @@
+use crate::error::{ConvertError, Result};
+use serde::Deserialize;
+use serde_json::Number;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FloatStyle {
+    /// The fewest digits that read back as the same value, e.g. `0.1`.
+    #[default]
+    Shortest,
+    /// Exactly `max_decimals` digits after the point, e.g. `0.10`.
+    Fixed,
+}
+
+/// How numbers with a fraction or exponent are written. Integers are left
+/// as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FloatFormat {
+    /// Round to at most this many digits after the decimal point.
+    pub max_decimals: Option<u32>,
+    pub style: FloatStyle,
+    /// Use scientific notation (`1.5e-7`) when the decimal exponent is at
+    /// least this large in either direction; otherwise numbers are written
+    /// without an exponent.
+    pub scientific_threshold: Option<u32>,
+}
+
+impl FloatFormat {
+    pub fn validate(&self) -> Result<()> {
+        if self.style == FloatStyle::Fixed && self.max_decimals.is_none() {
+            return Err(ConvertError::InvalidConfig(
+                "floatFormat.style \"fixed\" requires maxDecimals".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn format(&self, value: f64) -> String {
+        let mut value = match self.max_decimals {
+            Some(decimals) => round_to(value, decimals),
+            None => value,
+        };
+        if value == 0.0 {
+            // Drop the sign of values that round to zero
+            value = 0.0;
+        }
+
+        if let Some(threshold) = self.scientific_threshold {
+            let exponent = if value == 0.0 { 0 } else { value.abs().log10().floor() as i32 };
+            if exponent.unsigned_abs() >= threshold && value != 0.0 {
+                return format!("{value:e}");
+            }
+        }
+
+        match (self.style, self.max_decimals) {
+            (FloatStyle::Fixed, Some(decimals)) => format!("{value:.0$}", decimals as usize),
+            _ => format!("{value}"),
+        }
+    }
+
+    /// Text of a JSON number, reformatting it when it isn't an integer.
+    pub(crate) fn number_text(format: Option<&FloatFormat>, number: &Number) -> String {
+        match (format, number.as_f64()) {
+            (Some(format), Some(value)) if number.is_f64() => format.format(value),
+            _ => number.to_string(),
+        }
+    }
+}
+
+fn round_to(value: f64, decimals: u32) -> f64 {
+    // Formatting rounds on the exact binary value, so `1.005` stays `1.00`
+    // just like `toFixed` in JavaScript.
+    format!("{value:.0$}", decimals as usize).parse().unwrap_or(value)
+}
+
+/// Integers written as JSON strings, so JavaScript's `JSON.parse` can't
+/// round them to the nearest double.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct IntegerStrings {
+    /// Keys whose numbers are always written as strings, at any depth.
+    pub fields: Vec<String>,
+    /// Quote integers outside `±(2^53 - 1)` (`Number.MAX_SAFE_INTEGER`).
+    pub unsafe_integers: bool,
+}
+
+impl Default for IntegerStrings {
+    fn default() -> Self {
+        Self {
+            fields: Vec::new(),
+            unsafe_integers: true,
+        }
+    }
+}
+
+/// Largest integer a double holds exactly, `Number.MAX_SAFE_INTEGER`.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Whether the JSON integer token `raw` is outside the safe integer range.
+fn is_unsafe_integer(raw: &[u8]) -> bool {
+    let digits = raw.strip_prefix(b"-").unwrap_or(raw);
+    if !digits.iter().all(u8::is_ascii_digit) {
+        return false;
+    }
+    match std::str::from_utf8(digits).ok().and_then(|text| text.parse::<u64>().ok()) {
+        Some(value) => value > MAX_SAFE_INTEGER,
+        None => true,
+    }
+}
+
+/// Applies a [`FloatFormat`] and [`IntegerStrings`] to the numbers of
+/// streamed JSON or NDJSON text.
+///
+/// String contents are copied as they are. A number cut off at the end of a
+/// chunk is held back until the next one.
+#[derive(Debug)]
+pub struct NumberRewriter {
+    float_format: Option<FloatFormat>,
+    integer_strings: Option<IntegerStrings>,
+    in_string: bool,
+    escaped: bool,
+    number: Vec<u8>,
+    /// Raw contents of the string being read, kept to match `integer_strings.fields`.
+    string: Vec<u8>,
+    /// The last string, until it is known whether it was a key.
+    last_string: Option<Vec<u8>>,
+    key: Option<Vec<u8>>,
+}
+
+impl NumberRewriter {
+    pub fn new(float_format: Option<FloatFormat>, integer_strings: Option<IntegerStrings>) -> Self {
+        Self {
+            float_format,
+            integer_strings,
+            in_string: false,
+            escaped: false,
+            number: Vec::new(),
+            string: Vec::new(),
+            last_string: None,
+            key: None,
+        }
+    }
+
+    pub fn write(&mut self, json: &[u8]) -> Vec<u8> {
+        let tracks_keys = self.integer_strings.as_ref().is_some_and(|s| !s.fields.is_empty());
+        let mut output = Vec::with_capacity(json.len() + 16);
+        for &byte in json {
+            if self.in_string {
+                output.push(byte);
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                    if tracks_keys {
+                        self.last_string = Some(std::mem::take(&mut self.string));
+                    }
+                    continue;
+                }
+                if tracks_keys {
+                    self.string.push(byte);
+                }
+                continue;
+            }
+
+            if !self.number.is_empty() {
+                if matches!(byte, b'0'..=b'9' | b'.' | b'e' | b'E' | b'+' | b'-') {
+                    self.number.push(byte);
+                    continue;
+                }
+                self.flush_number(&mut output);
+            }
+
+            match byte {
+                b'-' | b'0'..=b'9' => self.number.push(byte),
+                b'"' => {
+                    self.in_string = true;
+                    output.push(byte);
+                }
+                b':' => {
+                    self.key = self.last_string.take();
+                    output.push(byte);
+                }
+                b'{' => {
+                    self.key = None;
+                    output.push(byte);
+                }
+                _ => output.push(byte),
+            }
+        }
+        output
+    }
+
+    /// Write a number left at the very end of the input.
+    pub fn finish(&mut self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.flush_number(&mut output);
+        output
+    }
+
+    pub fn buffered_bytes(&self) -> usize {
+        self.number.len()
+    }
+
+    fn flush_number(&mut self, output: &mut Vec<u8>) {
+        if self.number.is_empty() {
+            return;
+        }
+        let raw = std::mem::take(&mut self.number);
+        let is_float = raw.iter().any(|b| matches!(b, b'.' | b'e' | b'E'));
+        let quote = self.integer_strings.as_ref().is_some_and(|strings| {
+            let in_field = self.key.as_ref().is_some_and(|key| {
+                strings.fields.iter().any(|field| field.as_bytes() == key.as_slice())
+            });
+            in_field || (strings.unsafe_integers && !is_float && is_unsafe_integer(&raw))
+        });
+
+        if quote {
+            output.push(b'"');
+        }
+        let value = std::str::from_utf8(&raw).ok().and_then(|text| text.parse::<f64>().ok());
+        match (self.float_format, value) {
+            (Some(format), Some(value)) if is_float && value.is_finite() => {
+                output.extend_from_slice(format.format(value).as_bytes())
+            }
+            _ => output.extend_from_slice(&raw),
+        }
+        if quote {
+            output.push(b'"');
+        }
+        self.number = raw;
+        self.number.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(max_decimals: Option<u32>, style: FloatStyle, scientific_threshold: Option<u32>) -> FloatFormat {
+        FloatFormat {
+            max_decimals,
+            style,
+            scientific_threshold,
+        }
+    }
+
+    #[test]
+    fn formats_floats() {
+        let shortest = FloatFormat::default();
+        assert_eq!(shortest.format(19.990000000000002), "19.990000000000002");
+        assert_eq!(shortest.format(1e21), "1000000000000000000000");
+
+        let two = format(Some(2), FloatStyle::Shortest, None);
+        assert_eq!(two.format(19.990000000000002), "19.99");
+        assert_eq!(two.format(0.5), "0.5");
+        assert_eq!(two.format(-0.001), "0");
+
+        let fixed = format(Some(2), FloatStyle::Fixed, None);
+        assert_eq!(fixed.format(0.5), "0.50");
+        assert_eq!(fixed.format(3.0), "3.00");
+
+        let scientific = format(None, FloatStyle::Shortest, Some(6));
+        assert_eq!(scientific.format(1.5e-7), "1.5e-7");
+        assert_eq!(scientific.format(2.5e6), "2.5e6");
+        assert_eq!(scientific.format(12345.5), "12345.5");
+
+        assert!(format(None, FloatStyle::Fixed, None).validate().is_err());
+    }
+
+    #[test]
+    fn rewrites_numbers_outside_strings() {
+        let mut rewriter = NumberRewriter::new(Some(format(Some(2), FloatStyle::Shortest, None)), None);
+        let mut output = rewriter.write(b"{\"a\":19.990000000000002,\"b\":\"1.23456\",\"c\":[1.005");
+        output.extend(rewriter.write(b"1,12345678901234567890]}\n{\"d\\\"1.111\":-2.5e-3}\n"));
+        output.extend(rewriter.finish());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"a\":19.99,\"b\":\"1.23456\",\"c\":[1.01,12345678901234567890]}\n{\"d\\\"1.111\":0}\n"
+        );
+    }
+
+    #[test]
+    fn quotes_integers() {
+        let strings = IntegerStrings {
+            fields: vec!["id".to_string()],
+            ..IntegerStrings::default()
+        };
+        let mut rewriter = NumberRewriter::new(None, Some(strings));
+        let mut output = rewriter.write(b"{\"id\":7,\"n\":9007199254740991,\"big\":-9007199254740");
+        output.extend(rewriter.write(b"992,\"x\":{\"id\":1.5},\"ids\":[\"id\",3],\"f\":1e300}\n"));
+        output.extend(rewriter.finish());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"id\":\"7\",\"n\":9007199254740991,\"big\":\"-9007199254740992\",\
+             \"x\":{\"id\":\"1.5\"},\"ids\":[\"id\",3],\"f\":1e300}\n"
+        );
+    }
+}
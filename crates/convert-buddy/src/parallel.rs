@@ -0,0 +1,108 @@
+use crate::error::Result;
+use rayon::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Process-wide thread limit set by `setThreadPoolSize`; 0 means no limit.
+static DEFAULT_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn set_default_threads(threads: usize) {
+    DEFAULT_THREADS.store(threads, Ordering::Relaxed);
+}
+
+/// Threads a conversion may keep busy: the converter's own limit, else the
+/// process-wide one, never more than the rayon pool has.
+fn thread_limit(threads: Option<usize>) -> usize {
+    let pool = rayon::current_num_threads();
+    match threads.unwrap_or_else(|| DEFAULT_THREADS.load(Ordering::Relaxed)) {
+        0 => pool,
+        limit => limit.min(pool),
+    }
+}
+
+/// Convert `lines` in contiguous batches across the rayon pool, appending the
+/// results to `output`.
+///
+/// Lines are split into one batch per allowed thread, so at most `threads`
+/// workers are busy at once; a limit of 1 converts on the calling thread.
+/// With `ordered`, batch results are concatenated in input order, so the output
+/// is byte-for-byte what converting the lines one by one would produce.
+/// Otherwise each batch is appended as soon as it completes: records stay
+/// whole, but their order depends on thread scheduling.
+pub(crate) fn convert_lines<F>(
+    lines: &[&[u8]],
+    threads: Option<usize>,
+    ordered: bool,
+    output: &mut Vec<u8>,
+    convert: F,
+) -> Result<()>
+where
+    F: Fn(&[u8], &mut Vec<u8>) -> Result<()> + Sync,
+{
+    let threads = thread_limit(threads);
+    if threads <= 1 {
+        for line in lines {
+            convert(line, output)?;
+        }
+        return Ok(());
+    }
+
+    let batch_size = lines.len().div_ceil(threads).max(1);
+    let convert_batch = |batch: &[&[u8]]| -> Result<Vec<u8>> {
+        let mut batch_output = Vec::new();
+        for line in batch {
+            convert(line, &mut batch_output)?;
+        }
+        Ok(batch_output)
+    };
+
+    if ordered {
+        let parts: Result<Vec<Vec<u8>>> = lines.par_chunks(batch_size).map(convert_batch).collect();
+        for part in parts? {
+            output.extend_from_slice(&part);
+        }
+    } else {
+        let shared = Mutex::new(std::mem::take(output));
+        lines.par_chunks(batch_size).try_for_each(|batch| -> Result<()> {
+            let part = convert_batch(batch)?;
+            shared
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .extend_from_slice(&part);
+            Ok(())
+        })?;
+        *output = shared.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::thread::ThreadId;
+
+    fn threads_used(threads: Option<usize>) -> (Vec<u8>, HashSet<ThreadId>) {
+        let lines: Vec<&[u8]> = vec![b"a".as_slice(); 64];
+        let used = Mutex::new(HashSet::new());
+        let mut output = Vec::new();
+        convert_lines(&lines, threads, true, &mut output, |line, out| {
+            used.lock().unwrap().insert(std::thread::current().id());
+            out.extend_from_slice(line);
+            Ok(())
+        })
+        .unwrap();
+        (output, used.into_inner().unwrap())
+    }
+
+    #[test]
+    fn respects_the_thread_limit() {
+        let (output, used) = threads_used(Some(1));
+        assert_eq!(output, vec![b'a'; 64]);
+        assert_eq!(used, HashSet::from([std::thread::current().id()]));
+
+        let (output, used) = threads_used(Some(2));
+        assert_eq!(output, vec![b'a'; 64]);
+        assert!(used.len() <= 2);
+    }
+}
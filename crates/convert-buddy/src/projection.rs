@@ -0,0 +1,299 @@
+use crate::error::{ConvertError, Result};
+use crate::query::{decode_key, raw_members};
+use memchr::memchr;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Top-level fields kept in each input record, from the `selectFields` or
+/// `dropFields` option.
+///
+/// Applied before `transform`, `dedupe` and `sort`. Kept fields stay in the
+/// order of the record, and fields that aren't in a record are not added.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum FieldProjection {
+    /// Keep only these fields.
+    #[serde(rename = "selectFields")]
+    Select(Vec<String>),
+    /// Keep all fields but these.
+    #[serde(rename = "dropFields")]
+    Drop(Vec<String>),
+}
+
+impl FieldProjection {
+    pub fn validate(&self) -> Result<()> {
+        let (option, fields) = match self {
+            FieldProjection::Select(fields) => ("selectFields", fields),
+            FieldProjection::Drop(fields) => ("dropFields", fields),
+        };
+        if fields.is_empty() || fields.iter().any(String::is_empty) {
+            return Err(ConvertError::InvalidConfig(format!(
+                "{option} must list at least one non-empty field"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Applies a [`FieldProjection`] to NDJSON lines by copying the raw bytes of
+/// the kept members, so values are neither parsed nor re-serialized.
+#[derive(Debug)]
+pub(crate) struct Projector {
+    fields: HashSet<String>,
+    keep_listed: bool,
+}
+
+impl Projector {
+    pub(crate) fn new(projection: &FieldProjection) -> Self {
+        let (fields, keep_listed) = match projection {
+            FieldProjection::Select(fields) => (fields, true),
+            FieldProjection::Drop(fields) => (fields, false),
+        };
+        Self {
+            fields: fields.iter().cloned().collect(),
+            keep_listed,
+        }
+    }
+
+    /// Append the projected `line` to `output`, without a line ending.
+    /// Returns false, leaving `output` as it was, when the line isn't a
+    /// JSON object.
+    pub(crate) fn project(&self, line: &[u8], output: &mut Vec<u8>) -> bool {
+        let start = output.len();
+        output.push(b'{');
+        let scanned = raw_members(line, |raw_key, value| {
+            if self.keeps(raw_key) {
+                if output.len() > start + 1 {
+                    output.push(b',');
+                }
+                output.push(b'"');
+                output.extend_from_slice(raw_key);
+                output.extend_from_slice(b"\":");
+                output.extend_from_slice(value);
+            }
+        });
+        if scanned.is_none() {
+            output.truncate(start);
+            return false;
+        }
+        output.push(b'}');
+        true
+    }
+
+    fn keeps(&self, raw_key: &[u8]) -> bool {
+        let listed = if raw_key.contains(&b'\\') {
+            decode_key(raw_key).is_some_and(|key| self.fields.contains(&key))
+        } else {
+            std::str::from_utf8(raw_key).is_ok_and(|key| self.fields.contains(key))
+        };
+        listed == self.keep_listed
+    }
+}
+
+/// A column of the `outputColumns` option: the top-level field `from` of
+/// each record, written under the name `to`.
+///
+/// Applied by the CSV, XML and JSON writers after everything else, so
+/// `transform`, `dedupe` and `sort` still see the original names. Records
+/// get only these fields, in this order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputColumn {
+    pub from: String,
+    /// Defaults to `from`, to reorder without renaming.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+impl OutputColumn {
+    pub fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: Some(to.into()),
+        }
+    }
+
+    /// The name the column is written under.
+    pub fn name(&self) -> &str {
+        self.to.as_deref().unwrap_or(&self.from)
+    }
+
+    pub fn validate(columns: &[OutputColumn]) -> Result<()> {
+        if columns.is_empty() || columns.iter().any(|column| column.from.is_empty() || column.name().is_empty()) {
+            return Err(ConvertError::InvalidConfig(
+                "outputColumns must list at least one column, with non-empty names".to_string(),
+            ));
+        }
+        let mut names = HashSet::new();
+        if let Some(column) = columns.iter().find(|column| !names.insert(column.name())) {
+            return Err(ConvertError::InvalidConfig(format!(
+                "outputColumns names '{}' more than once",
+                column.name()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Applies `outputColumns` to NDJSON lines by copying the raw bytes of the
+/// values, like [`Projector`].
+#[derive(Debug)]
+pub(crate) struct ColumnMapper {
+    /// Each column's source field and its name as a JSON string.
+    columns: Vec<(String, Vec<u8>)>,
+}
+
+impl ColumnMapper {
+    pub(crate) fn new(columns: &[OutputColumn]) -> Self {
+        let columns = columns
+            .iter()
+            .map(|column| {
+                let name = serde_json::to_vec(column.name()).unwrap_or_default();
+                (column.from.clone(), name)
+            })
+            .collect();
+        Self { columns }
+    }
+
+    /// Append `line` as an object of the columns to `output`, without a line
+    /// ending. Returns false, leaving `output` as it was, when the line isn't
+    /// a JSON object.
+    pub(crate) fn map(&self, line: &[u8], output: &mut Vec<u8>) -> bool {
+        let mut members = Vec::new();
+        if raw_members(line, |raw_key, value| members.push((raw_key, value))).is_none() {
+            return false;
+        }
+        let start = output.len();
+        output.push(b'{');
+        for (from, name) in &self.columns {
+            // The last of repeated keys wins, as when the record is parsed
+            let Some((_, value)) = members.iter().rev().find(|(raw_key, _)| key_is(raw_key, from)) else {
+                continue;
+            };
+            if output.len() > start + 1 {
+                output.push(b',');
+            }
+            output.extend_from_slice(name);
+            output.push(b':');
+            output.extend_from_slice(value);
+        }
+        output.push(b'}');
+        true
+    }
+}
+
+/// Applies a [`ColumnMapper`] to `\n`-delimited NDJSON output, holding back
+/// a line until its end arrives. Lines that aren't objects pass unchanged.
+#[derive(Debug)]
+pub(crate) struct NdjsonColumns {
+    mapper: ColumnMapper,
+    partial: Vec<u8>,
+}
+
+impl NdjsonColumns {
+    pub(crate) fn new(columns: &[OutputColumn]) -> Self {
+        Self {
+            mapper: ColumnMapper::new(columns),
+            partial: Vec::new(),
+        }
+    }
+
+    pub(crate) fn write(&mut self, ndjson: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(ndjson.len());
+        let mut rest = ndjson;
+        while let Some(end) = memchr(b'\n', rest) {
+            if self.partial.is_empty() {
+                self.map_line(&rest[..end], &mut output);
+            } else {
+                self.partial.extend_from_slice(&rest[..end]);
+                let line = std::mem::take(&mut self.partial);
+                self.map_line(&line, &mut output);
+            }
+            output.push(b'\n');
+            rest = &rest[end + 1..];
+        }
+        self.partial.extend_from_slice(rest);
+        output
+    }
+
+    /// Map a last line that arrived without a line ending.
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        let line = std::mem::take(&mut self.partial);
+        let mut output = Vec::new();
+        self.map_line(&line, &mut output);
+        output
+    }
+
+    fn map_line(&self, line: &[u8], output: &mut Vec<u8>) {
+        let body = line.strip_suffix(b"\r").unwrap_or(line);
+        if body.iter().all(u8::is_ascii_whitespace) || !self.mapper.map(body, output) {
+            output.extend_from_slice(line);
+        }
+    }
+}
+
+fn key_is(raw_key: &[u8], key: &str) -> bool {
+    if raw_key.contains(&b'\\') {
+        decode_key(raw_key).is_some_and(|decoded| decoded == key)
+    } else {
+        raw_key == key.as_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(projection: FieldProjection, line: &str) -> Option<String> {
+        let mut output = b"x".to_vec();
+        Projector::new(&projection)
+            .project(line.as_bytes(), &mut output)
+            .then(|| String::from_utf8(output[1..].to_vec()).unwrap())
+    }
+
+    #[test]
+    fn copies_kept_members() {
+        let line = r#" {"id": 7, "name": "Ada, \"A\"", "tags": [1, {"id": 2}], "price": 9.5} "#;
+        let select = FieldProjection::Select(vec!["price".to_string(), "name".to_string(), "sku".to_string()]);
+        assert_eq!(
+            project(select, line).unwrap(),
+            r#"{"name":"Ada, \"A\"","price":9.5}"#
+        );
+        let drop = FieldProjection::Drop(vec!["tags".to_string(), "id".to_string()]);
+        assert_eq!(
+            project(drop.clone(), line).unwrap(),
+            r#"{"name":"Ada, \"A\"","price":9.5}"#
+        );
+        assert_eq!(project(drop.clone(), r#"{"id":1}"#).unwrap(), "{}");
+        assert_eq!(project(drop, "[1]"), None);
+        assert!(FieldProjection::Select(Vec::new()).validate().is_err());
+    }
+
+    #[test]
+    fn maps_output_columns() {
+        let columns = vec![
+            OutputColumn::new("price", "Price (€)"),
+            OutputColumn::new("sku", "SKU"),
+            OutputColumn { from: "id".to_string(), to: None },
+        ];
+        assert!(OutputColumn::validate(&columns).is_ok());
+        let mapper = ColumnMapper::new(&columns);
+
+        let mut output = Vec::new();
+        assert!(mapper.map(br#"{"id": 7, "s\u006bu": "A-1", "price": [1, 2], "id": 8}"#, &mut output));
+        assert_eq!(String::from_utf8(output).unwrap(), r#"{"Price (€)":[1, 2],"SKU":"A-1","id":8}"#);
+        assert!(!mapper.map(b"[1]", &mut Vec::new()));
+
+        let mut lines = NdjsonColumns::new(&columns);
+        let mut output = lines.write(b"{\"sku\":\"A\",\"x\":1}\n[2]\n\n{\"id\":");
+        output.extend(lines.write(b"3,\"price\":4}\r\n{\"price\""));
+        output.extend(lines.write(b":5}"));
+        output.extend(lines.finish());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"SKU\":\"A\"}\n[2]\n\n{\"Price (€)\":4,\"id\":3}\n{\"Price (€)\":5}"
+        );
+
+        let repeated = vec![OutputColumn::new("a", "x"), OutputColumn::new("b", "x")];
+        assert!(OutputColumn::validate(&repeated).is_err());
+        assert!(OutputColumn::validate(&[]).is_err());
+    }
+}
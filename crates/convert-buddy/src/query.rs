@@ -0,0 +1,250 @@
+use crate::json_validate;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Simple per-record predicate: a field compared by equality and/or substring.
+///
+/// With neither `equals` nor `contains` set, the query matches any record
+/// where the field is present and not null. Scalars are compared by their
+/// text form, so `{"equals": 42}` matches both `42` and `"42"` (CSV values
+/// are always strings).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordQuery {
+    pub field: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub equals: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contains: Option<String>,
+}
+
+impl RecordQuery {
+    pub fn matches(&self, record: &Map<String, Value>) -> bool {
+        self.matches_value(record.get(&self.field))
+    }
+
+    /// Evaluate against a raw NDJSON line, decoding only the queried field.
+    /// Returns `None` when the line can't be scanned cheaply (not a plain
+    /// JSON object, or not vouched for by [`json_validate::is_valid`]), so
+    /// callers can fall back to a full parse, which reports malformed lines.
+    pub fn matches_raw(&self, line: &[u8]) -> Option<bool> {
+        if !json_validate::is_valid(line) {
+            return None;
+        }
+        match raw_field(line, &self.field)? {
+            Some(raw) => {
+                let value: Value = serde_json::from_slice(raw).ok()?;
+                Some(self.matches_value(Some(&value)))
+            }
+            None => Some(false),
+        }
+    }
+
+    fn matches_value(&self, value: Option<&Value>) -> bool {
+        let value = match value {
+            Some(Value::Null) | None => return false,
+            Some(value) => value,
+        };
+
+        if let Some(expected) = &self.equals {
+            if value != expected && scalar_text(value) != scalar_text(expected) {
+                return false;
+            }
+        }
+
+        if let Some(needle) = &self.contains {
+            match scalar_text(value) {
+                Some(text) if text.contains(needle.as_str()) => {}
+                _ => return false,
+            }
+        }
+
+        true
+    }
+}
+
+/// Find the raw value bytes of top-level `key` in a JSON object line without
+/// building a `Value`. The outer `None` means the line isn't a well-formed
+/// object; the inner `None` means the key is absent. Like serde_json, the
+/// last occurrence of a duplicated key wins.
+fn raw_field<'a>(line: &'a [u8], key: &str) -> Option<Option<&'a [u8]>> {
+    let mut found = None;
+    raw_members(line, |raw_key, value| {
+        if key_matches(raw_key, key) {
+            found = Some(value);
+        }
+    })?;
+    Some(found)
+}
+
+/// Call `visit` with the raw key (without quotes) and raw value bytes of each
+/// top-level member of a JSON object line, in order. `None` when the line
+/// isn't a well-formed object; members before the fault were still visited.
+pub(crate) fn raw_members<'a>(line: &'a [u8], mut visit: impl FnMut(&'a [u8], &'a [u8])) -> Option<()> {
+    let mut pos = skip_whitespace(line, 0);
+    if line.get(pos) != Some(&b'{') {
+        return None;
+    }
+    pos = skip_whitespace(line, pos + 1);
+    if line.get(pos) == Some(&b'}') {
+        return Some(());
+    }
+
+    loop {
+        if line.get(pos) != Some(&b'"') {
+            return None;
+        }
+        let key_end = string_end(line, pos)?;
+        let raw_key = &line[pos + 1..key_end - 1];
+
+        pos = skip_whitespace(line, key_end);
+        if line.get(pos) != Some(&b':') {
+            return None;
+        }
+        pos = skip_whitespace(line, pos + 1);
+        let value_end = value_end(line, pos)?;
+        visit(raw_key, &line[pos..value_end]);
+
+        pos = skip_whitespace(line, value_end);
+        match line.get(pos)? {
+            b',' => pos = skip_whitespace(line, pos + 1),
+            b'}' => return Some(()),
+            _ => return None,
+        }
+    }
+}
+
+fn skip_whitespace(line: &[u8], mut pos: usize) -> usize {
+    while pos < line.len() && line[pos].is_ascii_whitespace() {
+        pos += 1;
+    }
+    pos
+}
+
+/// Index just past the closing quote of the string starting at `start`.
+fn string_end(line: &[u8], start: usize) -> Option<usize> {
+    let mut pos = start + 1;
+    while pos < line.len() {
+        match line[pos] {
+            b'\\' => pos += 2,
+            b'"' => return Some(pos + 1),
+            _ => pos += 1,
+        }
+    }
+    None
+}
+
+/// Index just past the JSON value starting at `start`.
+fn value_end(line: &[u8], start: usize) -> Option<usize> {
+    match *line.get(start)? {
+        b'"' => string_end(line, start),
+        b'{' | b'[' => {
+            let mut depth = 0usize;
+            let mut pos = start;
+            while pos < line.len() {
+                match line[pos] {
+                    b'"' => {
+                        pos = string_end(line, pos)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+            None
+        }
+        _ => {
+            let mut pos = start;
+            while pos < line.len()
+                && !matches!(line[pos], b',' | b'}' | b']')
+                && !line[pos].is_ascii_whitespace()
+            {
+                pos += 1;
+            }
+            (pos > start).then_some(pos)
+        }
+    }
+}
+
+fn key_matches(raw_key: &[u8], key: &str) -> bool {
+    if !raw_key.contains(&b'\\') {
+        return raw_key == key.as_bytes();
+    }
+    decode_key(raw_key).is_some_and(|decoded| decoded == key)
+}
+
+/// The text of a raw key with escapes.
+pub(crate) fn decode_key(raw_key: &[u8]) -> Option<String> {
+    let mut quoted = Vec::with_capacity(raw_key.len() + 2);
+    quoted.push(b'"');
+    quoted.extend_from_slice(raw_key);
+    quoted.push(b'"');
+    serde_json::from_slice::<String>(&quoted).ok()
+}
+
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Number(num) => Some(num.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(value: Value) -> RecordQuery {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn record(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn equals_compares_scalars_by_text() {
+        let q = query(json!({ "field": "id", "equals": 42 }));
+        assert!(q.matches(&record(json!({ "id": 42 }))));
+        assert!(q.matches(&record(json!({ "id": "42" }))));
+        assert!(!q.matches(&record(json!({ "id": "43" }))));
+        assert!(!q.matches(&record(json!({ "other": 42 }))));
+    }
+
+    #[test]
+    fn raw_matching_agrees_with_parsed_matching() {
+        let q = query(json!({ "field": "k\"ey", "equals": "v" }));
+        let line = br#"{ "nested": {"k\"ey": "no", "arr": [1, "}"]}, "k\"ey" : "v" }"#;
+        assert_eq!(q.matches_raw(line), Some(true));
+
+        let q = query(json!({ "field": "n", "equals": 5 }));
+        assert_eq!(q.matches_raw(br#"{"n":5,"n":6}"#), Some(false));
+        assert_eq!(q.matches_raw(br#"{"m":5}"#), Some(false));
+        assert_eq!(q.matches_raw(br#"{"n":"5"}"#), Some(true));
+        assert_eq!(q.matches_raw(br#"[1,2]"#), None);
+        assert_eq!(q.matches_raw(br#"{"n":5"#), None);
+        // Malformed outside the queried field
+        assert_eq!(q.matches_raw(br#"{"n":5,"m":tru}"#), None);
+        assert_eq!(q.matches_raw(br#"{"m":[1,,2],"n":6}"#), None);
+    }
+
+    #[test]
+    fn contains_and_presence() {
+        let q = query(json!({ "field": "title", "contains": "shoe" }));
+        assert!(q.matches(&record(json!({ "title": "red shoes" }))));
+        assert!(!q.matches(&record(json!({ "title": "hat" }))));
+
+        let q = query(json!({ "field": "title" }));
+        assert!(q.matches(&record(json!({ "title": "" }))));
+        assert!(!q.matches(&record(json!({ "title": null }))));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::{ConvertError, Result};
+use crate::format::ConverterConfig;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// What to do with a CSV row or NDJSON line that can't be converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordErrorPolicy {
+    /// Fail the conversion.
+    #[default]
+    Abort,
+    /// Drop the record and count it in `Stats::records_skipped`.
+    Skip,
+    /// Drop the record and also keep its line number and error for `getErrors()`.
+    Collect,
+}
+
+/// A record dropped under [`RecordErrorPolicy::Collect`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RecordError {
+    /// 1-based input line the record starts on, or for records the transform
+    /// failed on, their 1-based position among the parsed records.
+    pub line: u64,
+    pub message: String,
+}
+
+/// Most errors kept for `getErrors()`; later ones are still counted.
+pub const MAX_COLLECTED_ERRORS: usize = 1000;
+
+/// One line of the rejects stream.
+#[derive(Serialize)]
+struct Reject<'a> {
+    line: u64,
+    error: String,
+    raw: &'a str,
+}
+
+#[derive(Debug, Default)]
+struct Log {
+    skipped: u64,
+    errors: Vec<RecordError>,
+    rejects: Vec<u8>,
+}
+
+/// Shared log of a converter's dropped records.
+///
+/// Like [`Warnings`](crate::Warnings), clones report into the same log, so
+/// each parser of a pipeline holds one.
+#[derive(Debug, Clone, Default)]
+pub struct RecordErrors {
+    policy: RecordErrorPolicy,
+    keep_rejects: bool,
+    log: Arc<Mutex<Log>>,
+}
+
+impl RecordErrors {
+    pub fn new(policy: RecordErrorPolicy) -> Self {
+        Self {
+            policy,
+            keep_rejects: false,
+            log: Arc::default(),
+        }
+    }
+
+    pub fn for_config(config: &ConverterConfig) -> Self {
+        Self::new(config.on_record_error).with_rejects(config.keep_rejects)
+    }
+
+    /// Also keep the raw bytes of dropped records for [`take_rejects`](Self::take_rejects).
+    pub fn with_rejects(mut self, keep: bool) -> Self {
+        self.keep_rejects = keep;
+        self
+    }
+
+    /// Whether bad records fail the conversion.
+    pub fn aborts(&self) -> bool {
+        self.policy == RecordErrorPolicy::Abort
+    }
+
+    /// Apply the policy to the error of the record `raw` on `line`: returned
+    /// as is under `Abort`, logged otherwise. Resource limits always fail.
+    pub fn handle(&self, line: u64, raw: &[u8], error: ConvertError) -> Result<()> {
+        if self.aborts() || matches!(error, ConvertError::LimitExceeded(_)) {
+            return Err(error);
+        }
+        let mut log = self.lock();
+        log.skipped += 1;
+        if self.keep_rejects {
+            let reject = Reject {
+                line,
+                error: error.to_string(),
+                raw: &String::from_utf8_lossy(raw),
+            };
+            serde_json::to_writer(&mut log.rejects, &reject)
+                .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+            log.rejects.push(b'\n');
+        }
+        if self.policy == RecordErrorPolicy::Collect && log.errors.len() < MAX_COLLECTED_ERRORS {
+            log.errors.push(RecordError {
+                line,
+                message: error.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Records dropped so far.
+    pub fn skipped(&self) -> u64 {
+        self.lock().skipped
+    }
+
+    pub fn list(&self) -> Vec<RecordError> {
+        self.lock().errors.clone()
+    }
+
+    /// Dropped records kept since the last call, as NDJSON `{line, error, raw}`
+    /// objects. Empty unless rejects are kept.
+    pub fn take_rejects(&self) -> Vec<u8> {
+        std::mem::take(&mut self.lock().rejects)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Log> {
+        self.log.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_policy() {
+        let error = || ConvertError::JsonParse("bad".to_string());
+        assert!(RecordErrors::new(RecordErrorPolicy::Abort).handle(1, b"x", error()).is_err());
+
+        let skip = RecordErrors::new(RecordErrorPolicy::Skip);
+        skip.handle(2, b"x", error()).unwrap();
+        assert_eq!((skip.skipped(), skip.list().len()), (1, 0));
+        assert!(skip.take_rejects().is_empty());
+
+        let collect = RecordErrors::new(RecordErrorPolicy::Collect);
+        collect.clone().handle(3, b"x", error()).unwrap();
+        assert_eq!(collect.skipped(), 1);
+        assert_eq!(
+            serde_json::to_value(collect.list()).unwrap(),
+            serde_json::json!([{ "line": 3, "message": "JSON parse error: bad" }])
+        );
+
+        let limit = ConvertError::LimitExceeded("field".to_string());
+        assert!(collect.handle(4, b"x", limit).is_err());
+    }
+
+    #[test]
+    fn keeps_rejects() {
+        let errors = RecordErrors::new(RecordErrorPolicy::Skip).with_rejects(true);
+        errors.handle(2, b"a,\"b\"", ConvertError::JsonParse("bad".to_string())).unwrap();
+        errors.handle(5, b"\xff", ConvertError::JsonParse("worse".to_string())).unwrap();
+        assert_eq!(
+            String::from_utf8(errors.take_rejects()).unwrap(),
+            "{\"line\":2,\"error\":\"JSON parse error: bad\",\"raw\":\"a,\\\"b\\\"\"}\n\
+             {\"line\":5,\"error\":\"JSON parse error: worse\",\"raw\":\"\u{FFFD}\"}\n"
+        );
+        assert!(errors.take_rejects().is_empty());
+    }
+}
@@ -0,0 +1,73 @@
+/// Keeps every `every`th record written, up to `max_records` of them, for
+/// the converter's `maxRecords` and `sampleEveryN` options.
+///
+/// Records are counted as the transform stage writes them, after `filter`
+/// and `dedupe` and before `sort`.
+#[derive(Debug, Clone)]
+pub(crate) struct Sampler {
+    max_records: Option<usize>,
+    every: usize,
+    seen: usize,
+    kept: usize,
+}
+
+impl Sampler {
+    /// `None` when neither option is set.
+    pub(crate) fn new(max_records: Option<usize>, every: Option<usize>) -> Option<Self> {
+        if max_records.is_none() && every.is_none() {
+            return None;
+        }
+        Some(Self {
+            max_records,
+            every: every.unwrap_or(1).max(1),
+            seen: 0,
+            kept: 0,
+        })
+    }
+
+    /// Drop the NDJSON lines of `output[start..]` that aren't sampled or
+    /// come after `max_records`. Returns the number kept.
+    pub(crate) fn sample(&mut self, output: &mut Vec<u8>, start: usize) -> usize {
+        let mut read = start;
+        let mut write = start;
+        let mut kept = 0;
+        while read < output.len() {
+            let end = memchr::memchr(b'\n', &output[read..]).map_or(output.len(), |pos| read + pos + 1);
+            if !self.is_done() && self.seen.is_multiple_of(self.every) {
+                output.copy_within(read..end, write);
+                write += end - read;
+                self.kept += 1;
+                kept += 1;
+            }
+            self.seen += 1;
+            read = end;
+        }
+        output.truncate(write);
+        kept
+    }
+
+    /// Whether `max_records` records have been kept.
+    pub(crate) fn is_done(&self) -> bool {
+        self.max_records.is_some_and(|max| self.kept >= max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_every_nth_record_up_to_the_limit() {
+        let mut sampler = Sampler::new(Some(3), Some(2)).unwrap();
+        let mut output = b"x\n1\n2\n3\n".to_vec();
+        assert_eq!(sampler.sample(&mut output, 2), 2);
+        assert_eq!(output, b"x\n1\n3\n");
+
+        let mut output = b"4\n5\n6\n7\n".to_vec();
+        assert_eq!(sampler.sample(&mut output, 0), 1);
+        assert_eq!(output, b"5\n");
+        assert!(sampler.is_done());
+
+        assert!(Sampler::new(None, None).is_none());
+    }
+}
@@ -0,0 +1,314 @@
+use crate::csv_parser::{CsvConfig, CsvParser};
+use crate::error::{ConvertError, Result};
+use crate::format::Format;
+use crate::json_parser::JsonRecordReader;
+use crate::query::RecordQuery;
+#[cfg(feature = "xlsx")]
+use crate::xlsx_parser::{XlsxConfig, XlsxParser};
+#[cfg(feature = "yaml")]
+use crate::yaml_parser::YamlParser;
+use crate::xml_parser::{XmlConfig, XmlParser};
+use memchr::memchr;
+use serde_json::Value;
+
+enum RecordSource {
+    Csv(CsvParser),
+    Xml(XmlParser),
+    Ndjson,
+    Json(JsonRecordReader),
+    /// Workbooks are read once complete; the parser buffers input.
+    #[cfg(feature = "xlsx")]
+    Xlsx(XlsxParser),
+    #[cfg(feature = "yaml")]
+    Yaml(YamlParser),
+    /// Output-only formats, which cannot be read.
+    #[cfg(feature = "parquet")]
+    Unreadable(Format),
+}
+
+/// Streams records out of any input format and keeps the ones matching a
+/// [`RecordQuery`], without producing converted output.
+pub struct RecordFinder {
+    source: RecordSource,
+    query: RecordQuery,
+    limit: Option<usize>,
+    matches: Vec<Value>,
+    /// Matches found so far, including ones already taken.
+    matched: usize,
+    scanned: usize,
+    partial_line: Vec<u8>,
+}
+
+impl RecordFinder {
+    pub fn new(
+        format: Format,
+        query: RecordQuery,
+        csv_config: Option<CsvConfig>,
+        xml_config: Option<XmlConfig>,
+    ) -> Self {
+        // Output is consumed internally, so a small chunk target is enough.
+        let chunk_target_bytes = 64 * 1024;
+        let source = match format {
+            Format::Csv => RecordSource::Csv(CsvParser::new(
+                csv_config.unwrap_or_default(),
+                chunk_target_bytes,
+            )),
+            Format::Tsv => RecordSource::Csv(CsvParser::new(
+                CsvConfig {
+                    delimiter: b'\t',
+                    ..csv_config.unwrap_or_default()
+                },
+                chunk_target_bytes,
+            )),
+            Format::Xml => RecordSource::Xml(XmlParser::new(
+                xml_config.unwrap_or_default(),
+                chunk_target_bytes,
+            )),
+            Format::Ndjson => RecordSource::Ndjson,
+            Format::Json => RecordSource::Json(JsonRecordReader::new()),
+            #[cfg(feature = "parquet")]
+            Format::Parquet => RecordSource::Unreadable(format),
+            #[cfg(feature = "xlsx")]
+            Format::Xlsx => RecordSource::Xlsx(XlsxParser::new(XlsxConfig::default())),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => RecordSource::Yaml(YamlParser::new()),
+        };
+
+        Self {
+            source,
+            query,
+            limit: None,
+            matches: Vec::new(),
+            matched: 0,
+            scanned: 0,
+            partial_line: Vec::new(),
+        }
+    }
+
+    /// Stop collecting after `limit` matches.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Whether the match limit has been reached; further input is ignored.
+    pub fn is_done(&self) -> bool {
+        self.limit.is_some_and(|limit| self.matched >= limit)
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<()> {
+        if self.is_done() {
+            return Ok(());
+        }
+
+        let ndjson = match &mut self.source {
+            RecordSource::Csv(parser) => parser.push_to_ndjson(chunk)?,
+            RecordSource::Xml(parser) => parser.push_to_ndjson(chunk)?,
+            #[cfg(feature = "yaml")]
+            RecordSource::Yaml(parser) => parser.push(chunk)?,
+            RecordSource::Ndjson => chunk.to_vec(),
+            RecordSource::Json(reader) => reader.push(chunk)?,
+            #[cfg(feature = "xlsx")]
+            RecordSource::Xlsx(parser) => {
+                parser.push(chunk);
+                return Ok(());
+            }
+            #[cfg(feature = "parquet")]
+            RecordSource::Unreadable(format) => return Err(unreadable(*format)),
+        };
+        self.scan_ndjson(&ndjson)
+    }
+
+    pub fn finish(&mut self) -> Result<()> {
+        if self.is_done() {
+            return Ok(());
+        }
+
+        match &mut self.source {
+            RecordSource::Csv(parser) => {
+                let ndjson = parser.finish()?;
+                self.scan_ndjson(&ndjson)?;
+            }
+            RecordSource::Xml(parser) => {
+                let ndjson = parser.finish()?;
+                self.scan_ndjson(&ndjson)?;
+            }
+            RecordSource::Ndjson => {}
+            #[cfg(feature = "yaml")]
+            RecordSource::Yaml(parser) => {
+                let ndjson = parser.finish()?;
+                self.scan_ndjson(&ndjson)?;
+            }
+            #[cfg(feature = "xlsx")]
+            RecordSource::Xlsx(parser) => {
+                let ndjson = parser.finish()?;
+                self.scan_ndjson(&ndjson)?;
+            }
+            RecordSource::Json(reader) => {
+                let ndjson = reader.finish()?;
+                self.scan_ndjson(&ndjson)?;
+            }
+            #[cfg(feature = "parquet")]
+            RecordSource::Unreadable(format) => return Err(unreadable(*format)),
+        }
+
+        let line = std::mem::take(&mut self.partial_line);
+        self.scan_line(&line)
+    }
+
+    /// Number of records examined so far.
+    pub fn scanned(&self) -> usize {
+        self.scanned
+    }
+
+    pub fn matches(&self) -> &[Value] {
+        &self.matches
+    }
+
+    /// Take the matches found since the last call. Taken matches still count
+    /// towards the limit.
+    pub fn take_matches(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.matches)
+    }
+
+    fn scan_ndjson(&mut self, ndjson: &[u8]) -> Result<()> {
+        let mut temp_buffer = Vec::new();
+        let input_data: &[u8] = if !self.partial_line.is_empty() {
+            temp_buffer.append(&mut self.partial_line);
+            temp_buffer.extend_from_slice(ndjson);
+            &temp_buffer
+        } else {
+            ndjson
+        };
+
+        let mut start = 0;
+        while let Some(pos) = memchr(b'\n', &input_data[start..]) {
+            let line_end = start + pos;
+            self.scan_line(&input_data[start..line_end])?;
+            start = line_end + 1;
+        }
+
+        if start < input_data.len() {
+            self.partial_line.extend_from_slice(&input_data[start..]);
+        }
+        Ok(())
+    }
+
+    fn scan_line(&mut self, line: &[u8]) -> Result<()> {
+        if self.is_done() || line.iter().all(|b| b.is_ascii_whitespace()) {
+            return Ok(());
+        }
+        let value: Value = serde_json::from_slice(line)
+            .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+        self.check_record(value);
+        Ok(())
+    }
+
+    fn check_record(&mut self, value: Value) {
+        if self.is_done() {
+            return;
+        }
+        self.scanned += 1;
+        if value.as_object().is_some_and(|record| self.query.matches(record)) {
+            self.matched += 1;
+            self.matches.push(value);
+        }
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn unreadable(format: Format) -> ConvertError {
+    ConvertError::Unsupported(format!("{} is only supported as an output format", format.to_string_js()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(value: Value) -> RecordQuery {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn finds_records_across_chunk_boundaries() {
+        let mut finder = RecordFinder::new(
+            Format::Csv,
+            query(json!({ "field": "name", "contains": "shoe" })),
+            None,
+            None,
+        );
+        finder.push(b"id,name\n1,red sh").unwrap();
+        finder.push(b"oes\n2,hat\n3,blue shoe").unwrap();
+        finder.finish().unwrap();
+
+        assert_eq!(finder.scanned(), 3);
+        assert_eq!(
+            finder.matches(),
+            &[
+                json!({ "id": "1", "name": "red shoes" }),
+                json!({ "id": "3", "name": "blue shoe" })
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_limit_and_supports_json_and_xml() {
+        let mut finder = RecordFinder::new(
+            Format::Ndjson,
+            query(json!({ "field": "ok", "equals": true })),
+            None,
+            None,
+        )
+        .with_limit(1);
+        finder.push(b"{\"ok\":true,\"n\":1}\n{\"ok\":true,\"n\":2}\n").unwrap();
+        assert!(finder.is_done());
+        assert_eq!(finder.take_matches(), vec![json!({ "ok": true, "n": 1 })]);
+
+        let mut finder =
+            RecordFinder::new(Format::Json, query(json!({ "field": "id", "equals": 2 })), None, None);
+        finder.push(b"[{\"id\":1},").unwrap();
+        finder.push(b"{\"id\":2},").unwrap();
+        // Found before the document ends
+        assert_eq!(finder.matches(), &[json!({ "id": 2 })]);
+        finder.push(b"{\"id\":3}]").unwrap();
+        finder.finish().unwrap();
+        assert_eq!(finder.matches(), &[json!({ "id": 2 })]);
+
+        let xml_config = XmlConfig {
+            record_element: "item".to_string(),
+            ..XmlConfig::default()
+        };
+        let mut finder = RecordFinder::new(
+            Format::Xml,
+            query(json!({ "field": "sku", "equals": "B" })),
+            None,
+            Some(xml_config),
+        );
+        finder
+            .push(b"<feed><item><sku>A</sku></item><item><sku>B</sku></item></feed>")
+            .unwrap();
+        finder.finish().unwrap();
+        assert_eq!(finder.matches().len(), 1);
+    }
+
+    #[test]
+    fn taken_matches_count_towards_the_limit() {
+        let mut finder = RecordFinder::new(
+            Format::Ndjson,
+            query(json!({ "field": "ok", "equals": true })),
+            None,
+            None,
+        )
+        .with_limit(2);
+        let mut found = Vec::new();
+        for line in [b"{\"ok\":true,\"n\":1}\n", b"{\"ok\":true,\"n\":2}\n", b"{\"ok\":true,\"n\":3}\n"] {
+            finder.push(line).unwrap();
+            found.extend(finder.take_matches());
+        }
+        finder.finish().unwrap();
+        found.extend(finder.take_matches());
+        assert!(finder.is_done());
+        assert_eq!(found, vec![json!({ "ok": true, "n": 1 }), json!({ "ok": true, "n": 2 })]);
+    }
+}
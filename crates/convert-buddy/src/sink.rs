@@ -0,0 +1,44 @@
+use crate::error::{ConvertError, Result};
+use std::io::Write;
+
+/// Receives converted output as it is produced, set with
+/// [`Converter::with_sink`](crate::Converter::with_sink).
+///
+/// Each push, flush and finish writes its output here instead of returning
+/// it, in parts of at most `max_output_bytes` when that is set. Any
+/// [`std::io::Write`], such as a file, a socket or a `Vec<u8>`, is a sink.
+pub trait OutputSink {
+    /// Take the next part of the output. An error fails the call that
+    /// produced it, and the converter with it.
+    fn write_output(&mut self, output: &[u8]) -> Result<()>;
+
+    /// Called once the last output has been written by `finish`.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> OutputSink for W {
+    fn write_output(&mut self, output: &[u8]) -> Result<()> {
+        self.write_all(output).map_err(|e| ConvertError::Io(e.to_string()))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush().map_err(|e| ConvertError::Io(e.to_string()))
+    }
+}
+
+/// Adapter that passes output to a JS callback as a `Uint8Array`.
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) struct JsOutputSink(pub(crate) js_sys::Function);
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+impl OutputSink for JsOutputSink {
+    fn write_output(&mut self, output: &[u8]) -> Result<()> {
+        let chunk = js_sys::Uint8Array::from(output);
+        self.0
+            .call1(&wasm_bindgen::JsValue::NULL, &chunk)
+            .map(|_| ())
+            .map_err(|e| ConvertError::Io(format!("Output callback threw: {:?}", e)))
+    }
+}
@@ -0,0 +1,440 @@
+use crate::error::{ConvertError, Result};
+use flate2::bufread::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+/// Records buffered before a sorted run is spilled, unless configured.
+pub const DEFAULT_SORT_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Estimated bookkeeping of one buffered record beyond its line and key.
+const ENTRY_OVERHEAD_BYTES: usize = 64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Writes records ordered by `keys` at the end of the conversion.
+///
+/// Values of a key compare as null < booleans < numbers < strings < arrays
+/// < objects, numbers by value and strings by their bytes, so CSV fields
+/// sort as text unless typed. Records with equal keys keep their input order.
+/// Keys are read from the records as written, i.e. after `transform`.
+///
+/// Records are buffered until `max_memory_bytes`, then spilled as a sorted
+/// run, and the runs are merged at the end. Runs are kept deflated in memory,
+/// or with `spill_dir` (native only) written to files there.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SortConfig {
+    /// Top-level fields to sort by, most significant first.
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub order: SortOrder,
+    #[serde(default = "default_memory_bytes")]
+    pub max_memory_bytes: usize,
+    /// Also build a sidecar index of the output, taken with
+    /// `Converter::take_index`; only for uncompressed NDJSON output.
+    #[serde(default)]
+    pub index: bool,
+    #[cfg(not(target_arch = "wasm32"))]
+    #[serde(skip)]
+    pub spill_dir: Option<PathBuf>,
+}
+
+fn default_memory_bytes() -> usize {
+    DEFAULT_SORT_MEMORY_BYTES
+}
+
+impl SortConfig {
+    pub fn new(keys: Vec<String>, order: SortOrder) -> Self {
+        Self {
+            keys,
+            order,
+            max_memory_bytes: DEFAULT_SORT_MEMORY_BYTES,
+            index: false,
+            #[cfg(not(target_arch = "wasm32"))]
+            spill_dir: None,
+        }
+    }
+
+    pub fn with_max_memory_bytes(mut self, bytes: usize) -> Self {
+        self.max_memory_bytes = bytes;
+        self
+    }
+
+    pub fn with_index(mut self, index: bool) -> Self {
+        self.index = index;
+        self
+    }
+
+    /// Spill sorted runs to files in `dir` instead of keeping them in memory.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_spill_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = Some(dir.into());
+        self
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.keys.is_empty() || self.keys.iter().any(String::is_empty) {
+            return Err(ConvertError::InvalidConfig(
+                "sort.keys must list at least one non-empty field".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Order of two key values, before `SortOrder` is applied.
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+        }
+    }
+
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => match (a.as_u64(), b.as_u64()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a
+                    .as_f64()
+                    .unwrap_or(0.0)
+                    .total_cmp(&b.as_f64().unwrap_or(0.0)),
+            },
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(_), Value::Array(_)) | (Value::Object(_), Value::Object(_)) => {
+            a.to_string().cmp(&b.to_string())
+        }
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn compare_keys(a: &[Value], b: &[Value], order: SortOrder) -> Ordering {
+    let ordering = a
+        .iter()
+        .zip(b)
+        .map(|(a, b)| compare_values(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal);
+    match order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    key: Vec<Value>,
+    /// The NDJSON line, with its newline.
+    line: Vec<u8>,
+}
+
+impl Entry {
+    fn parse(line: &[u8], keys: &[String]) -> Result<Self> {
+        let value: Value = serde_json::from_slice(line)
+            .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+        let key = keys
+            .iter()
+            .map(|field| value.get(field).cloned().unwrap_or(Value::Null))
+            .collect();
+        let mut line = line.to_vec();
+        line.push(b'\n');
+        Ok(Self { key, line })
+    }
+
+    fn bytes(&self) -> usize {
+        let key: usize = self
+            .key
+            .iter()
+            .map(|value| match value {
+                Value::String(text) => text.len() + 24,
+                _ => 24,
+            })
+            .sum();
+        ENTRY_OVERHEAD_BYTES + key + self.line.len()
+    }
+}
+
+/// A sorted run of NDJSON lines spilled out of the buffer.
+enum Run {
+    Deflated(Vec<u8>),
+    #[cfg(not(target_arch = "wasm32"))]
+    File(SpillFile),
+}
+
+impl Run {
+    fn reader(&self) -> Result<Box<dyn BufRead + '_>> {
+        match self {
+            Run::Deflated(bytes) => Ok(Box::new(BufReader::new(DeflateDecoder::new(&bytes[..])))),
+            #[cfg(not(target_arch = "wasm32"))]
+            Run::File(file) => {
+                let reader = std::fs::File::open(&file.0).map_err(io_error)?;
+                Ok(Box::new(BufReader::new(reader)))
+            }
+        }
+    }
+}
+
+/// A run file, removed when the sort is done with it.
+#[cfg(not(target_arch = "wasm32"))]
+struct SpillFile(PathBuf);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl SpillFile {
+    fn create(dir: &Path) -> Result<(Self, std::fs::File)> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "convert-buddy-sort-{}-{}.ndjson",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = dir.join(name);
+        let file = std::fs::File::create(&path).map_err(|e| {
+            ConvertError::Io(format!("Unable to create {}: {e}", path.display()))
+        })?;
+        Ok((Self(path), file))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn io_error(error: std::io::Error) -> ConvertError {
+    ConvertError::Io(error.to_string())
+}
+
+/// Next line of a run being merged, ordered so that `BinaryHeap` pops the
+/// smallest key, and of equal keys the one from the earliest source.
+struct Head {
+    entry: Entry,
+    source: usize,
+    order: SortOrder,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_keys(&other.entry.key, &self.entry.key, self.order)
+            .then_with(|| other.source.cmp(&self.source))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Head {}
+
+/// Applies a [`SortConfig`] to the NDJSON lines a transform engine writes.
+pub(crate) struct Sorter {
+    config: SortConfig,
+    buffer: Vec<Entry>,
+    buffered_bytes: usize,
+    runs: Vec<Run>,
+}
+
+impl Sorter {
+    pub(crate) fn new(config: SortConfig) -> Self {
+        Self {
+            config,
+            buffer: Vec::new(),
+            buffered_bytes: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Take NDJSON lines to write once all records are in.
+    pub(crate) fn push(&mut self, lines: &[u8]) -> Result<()> {
+        for line in lines.split(|&b| b == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+            let entry = Entry::parse(line, &self.config.keys)?;
+            self.buffered_bytes += entry.bytes();
+            self.buffer.push(entry);
+            if self.buffered_bytes > self.config.max_memory_bytes {
+                self.spill()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write every record taken, sorted. Returns how many there were.
+    pub(crate) fn finish(&mut self, output: &mut Vec<u8>) -> Result<usize> {
+        self.sort_buffer();
+        let buffer = std::mem::take(&mut self.buffer);
+        self.buffered_bytes = 0;
+        let runs = std::mem::take(&mut self.runs);
+        if runs.is_empty() {
+            for entry in &buffer {
+                output.extend_from_slice(&entry.line);
+            }
+            return Ok(buffer.len());
+        }
+
+        let mut readers = runs.iter().map(Run::reader).collect::<Result<Vec<_>>>()?;
+        // The buffer holds the latest records, so it merges as the last source
+        let mut rest = buffer.into_iter();
+        let mut heap = BinaryHeap::with_capacity(readers.len() + 1);
+        for source in 0..=readers.len() {
+            if let Some(entry) = self.next_entry(&mut readers, &mut rest, source)? {
+                heap.push(Head { entry, source, order: self.config.order });
+            }
+        }
+
+        let mut written = 0;
+        while let Some(Head { entry, source, .. }) = heap.pop() {
+            output.extend_from_slice(&entry.line);
+            written += 1;
+            if let Some(entry) = self.next_entry(&mut readers, &mut rest, source)? {
+                heap.push(Head { entry, source, order: self.config.order });
+            }
+        }
+        Ok(written)
+    }
+
+    fn next_entry(
+        &self,
+        readers: &mut [Box<dyn BufRead + '_>],
+        rest: &mut std::vec::IntoIter<Entry>,
+        source: usize,
+    ) -> Result<Option<Entry>> {
+        let Some(reader) = readers.get_mut(source) else {
+            return Ok(rest.next());
+        };
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line).map_err(io_error)? == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        Entry::parse(&line, &self.config.keys).map(Some)
+    }
+
+    fn sort_buffer(&mut self) {
+        let order = self.config.order;
+        // Stable, so records with equal keys keep their input order
+        self.buffer.sort_by(|a, b| compare_keys(&a.key, &b.key, order));
+    }
+
+    fn spill(&mut self) -> Result<()> {
+        self.sort_buffer();
+        let run = self.write_run()?;
+        self.runs.push(run);
+        self.buffer.clear();
+        self.buffered_bytes = 0;
+        Ok(())
+    }
+
+    fn write_run(&self) -> Result<Run> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(dir) = &self.config.spill_dir {
+            let (spill, file) = SpillFile::create(dir)?;
+            let mut writer = std::io::BufWriter::new(file);
+            for entry in &self.buffer {
+                writer.write_all(&entry.line).map_err(io_error)?;
+            }
+            writer.flush().map_err(io_error)?;
+            return Ok(Run::File(spill));
+        }
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+        for entry in &self.buffer {
+            encoder.write_all(&entry.line).map_err(io_error)?;
+        }
+        Ok(Run::Deflated(encoder.finish().map_err(io_error)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sort(config: SortConfig, records: &[Value]) -> Vec<Value> {
+        let mut sorter = Sorter::new(config);
+        for record in records {
+            let mut line = serde_json::to_vec(record).unwrap();
+            line.push(b'\n');
+            sorter.push(&line).unwrap();
+        }
+        let mut output = Vec::new();
+        let count = sorter.finish(&mut output).unwrap();
+        let sorted: Vec<Value> = serde_json::Deserializer::from_slice(&output)
+            .into_iter()
+            .map(|value| value.unwrap())
+            .collect();
+        assert_eq!(sorted.len(), count);
+        sorted
+    }
+
+    #[test]
+    fn sorts_by_keys_keeping_input_order_of_ties() {
+        let records = [
+            json!({"k": "b", "n": 2, "i": 0}),
+            json!({"k": "a", "n": 10, "i": 1}),
+            json!({"n": 1, "i": 2}),
+            json!({"k": "b", "n": 1, "i": 3}),
+            json!({"k": "a", "n": 10, "i": 4}),
+            json!({"k": 5, "i": 5}),
+        ];
+        let order = |sorted: Vec<Value>| -> Vec<u64> {
+            sorted.iter().map(|record| record["i"].as_u64().unwrap()).collect()
+        };
+
+        let keys = vec!["k".to_string(), "n".to_string()];
+        assert_eq!(order(sort(SortConfig::new(keys.clone(), SortOrder::Asc), &records)), [2, 5, 1, 4, 3, 0]);
+        assert_eq!(order(sort(SortConfig::new(keys, SortOrder::Desc), &records)), [0, 3, 1, 4, 5, 2]);
+        assert!(SortConfig::new(Vec::new(), SortOrder::Asc).validate().is_err());
+    }
+
+    #[test]
+    fn merges_spilled_runs() {
+        let records: Vec<Value> = (0..500).map(|i| json!({"k": (i * 7919) % 100, "i": i})).collect();
+        let config = SortConfig::new(vec!["k".to_string()], SortOrder::Asc).with_max_memory_bytes(2000);
+        let sorted = sort(config.clone(), &records);
+
+        let mut expected = records.clone();
+        expected.sort_by_key(|record| record["k"].as_u64().unwrap());
+        assert_eq!(sorted, expected);
+
+        let dir = std::env::temp_dir().join(format!("convert-buddy-sort-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(sort(config.with_spill_dir(&dir), &records), expected);
+        // Run files are removed once merged
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::format::ConverterConfig;
+use memchr::memchr;
+use serde_json::Value;
+
+/// Byte that starts each record of NDJSON output framed with `record_separator`.
+const RECORD_SEPARATOR: u8 = 0x1E;
+
+/// A run of records sharing a key, not yet written to the index.
+#[derive(Debug)]
+struct Entry {
+    /// The key values as a JSON array.
+    key: String,
+    offset: u64,
+    length: u64,
+}
+
+/// Builds the sidecar index of sorted NDJSON output, for `sort.index`.
+///
+/// The index is NDJSON with one `[key, offset, length]` array per distinct
+/// key, in output order: `key` is the array of the sort key values, and
+/// `offset` and `length` give the bytes of the output holding the records
+/// with that key, framing included. As the output is sorted, a reader can
+/// binary search the index and read just those bytes.
+#[derive(Debug)]
+pub(crate) struct SortIndex {
+    keys: Vec<String>,
+    /// Last byte of each record's framing: `\n`, or NUL with `nul_terminated`.
+    record_end: u8,
+    /// Output bytes seen before `line`.
+    offset: u64,
+    line: Vec<u8>,
+    current: Option<Entry>,
+    index: Vec<u8>,
+}
+
+impl SortIndex {
+    /// An index when `config` sorts into uncompressed NDJSON with `index` set.
+    pub(crate) fn for_config(config: &ConverterConfig) -> Option<Self> {
+        let sort = config.sort.as_ref().filter(|sort| sort.index)?;
+        if !config.supports_sort_index() {
+            return None;
+        }
+        let record_end = config.ndjson_output.map_or(b"\n".as_slice(), |framing| framing.record_end());
+        Some(Self {
+            keys: sort.keys.clone(),
+            record_end: record_end[record_end.len() - 1],
+            offset: 0,
+            line: Vec::new(),
+            current: None,
+            index: Vec::new(),
+        })
+    }
+
+    /// Take the next bytes of output, as returned to the caller.
+    pub(crate) fn write(&mut self, output: &[u8]) {
+        let mut rest = output;
+        while let Some(pos) = memchr(self.record_end, rest) {
+            self.line.extend_from_slice(&rest[..=pos]);
+            self.end_line();
+            rest = &rest[pos + 1..];
+        }
+        self.line.extend_from_slice(rest);
+    }
+
+    /// Index the last record, which may lack a line ending.
+    pub(crate) fn finish(&mut self) {
+        if !self.line.is_empty() {
+            self.end_line();
+        }
+        self.flush();
+    }
+
+    /// Index lines written since the last call.
+    pub(crate) fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.index)
+    }
+
+    fn end_line(&mut self) {
+        let start = self.offset;
+        let length = self.line.len() as u64;
+        self.offset += length;
+
+        let body = self.line.strip_prefix(&[RECORD_SEPARATOR]).unwrap_or(&self.line);
+        let body = body.strip_suffix(&[self.record_end]).unwrap_or(body);
+        let body = body.strip_suffix(b"\r").unwrap_or(body);
+        let key = serde_json::from_slice::<Value>(body).ok().and_then(|record| {
+            let values: Vec<&Value> = self
+                .keys
+                .iter()
+                .map(|key| record.get(key).unwrap_or(&Value::Null))
+                .collect();
+            serde_json::to_string(&values).ok()
+        });
+        self.line.clear();
+        let Some(key) = key else {
+            return;
+        };
+
+        match &mut self.current {
+            Some(entry) if entry.key == key => entry.length = start + length - entry.offset,
+            _ => {
+                self.flush();
+                self.current = Some(Entry { key, offset: start, length });
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(entry) = self.current.take() {
+            let line = format!("[{},{},{}]\n", entry.key, entry.offset, entry.length);
+            self.index.extend_from_slice(line.as_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::Format;
+    use crate::ndjson_writer::NdjsonOutputConfig;
+    use crate::sort::{SortConfig, SortOrder};
+
+    #[test]
+    fn indexes_runs_of_equal_keys_across_chunks() {
+        let sort = SortConfig::new(vec!["k".to_string()], SortOrder::Asc).with_index(true);
+        let config = ConverterConfig::new(Format::Ndjson, Format::Ndjson).with_sort_config(sort);
+        let mut index = SortIndex::for_config(&config).unwrap();
+
+        let output = b"{\"k\":\"a\",\"n\":1}\n{\"k\":\"a\",\"n\":2}\n{\"k\":\"b\"}\n{\"n\":3}";
+        index.write(&output[..20]);
+        index.write(&output[20..]);
+        index.finish();
+        assert_eq!(
+            String::from_utf8(index.take()).unwrap(),
+            "[[\"a\"],0,32]\n[[\"b\"],32,10]\n[[null],42,7]\n"
+        );
+        assert_eq!(&output[32..42], b"{\"k\":\"b\"}\n");
+
+        let framing = NdjsonOutputConfig { nul_terminated: true, ..NdjsonOutputConfig::default() };
+        let mut index = SortIndex::for_config(&config.with_ndjson_output(framing)).unwrap();
+        index.write(b"{\"k\":\"a\"}\0{\"k\":\"b\"}\0");
+        index.finish();
+        assert_eq!(String::from_utf8(index.take()).unwrap(), "[[\"a\"],0,10]\n[[\"b\"],10,10]\n");
+
+        let csv = ConverterConfig::new(Format::Ndjson, Format::Csv)
+            .with_sort_config(SortConfig::new(vec!["k".to_string()], SortOrder::Asc).with_index(true));
+        assert!(SortIndex::for_config(&csv).is_none());
+    }
+}
@@ -0,0 +1,23 @@
+//! `tracing` spans around the pipeline, compiled in with the `tracing` feature.
+//!
+//! `stage_span!(LEVEL, "name", field = value, ...)` enters a span until the
+//! end of the enclosing block. Without the feature it expands to a unit
+//! struct and the fields are never evaluated.
+
+#[cfg(feature = "tracing")]
+macro_rules! stage_span {
+    ($level:ident, $name:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        tracing::span!(tracing::Level::$level, $name $(, $field = $value)*).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! stage_span {
+    ($($args:tt)*) => {
+        $crate::spans::NoSpan
+    };
+}
+
+/// What `stage_span!` gives without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
@@ -1,14 +1,33 @@
+use crate::warnings::Warning;
 use std::time::Duration;
+#[cfg(feature = "wasm")]
 use wasm_bindgen::prelude::*;
 
+/// What one [`Converter::push_with_info`](crate::Converter::push_with_info)
+/// call did, where [`Stats`] holds totals since the start.
+#[derive(Debug, Clone, Default)]
+pub struct PushInfo {
+    /// Converted output, as `push` returns it.
+    pub output: Vec<u8>,
+    /// Records the chunk sent to the output, after any transform filter,
+    /// dedupe or sampling.
+    pub records_emitted: u64,
+    /// Input held back after the chunk, waiting for the rest of a record.
+    pub bytes_buffered: usize,
+    /// Warnings the chunk raised, counting only its own occurrences.
+    pub warnings: Vec<Warning>,
+}
+
 /// Performance statistics for the converter
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 #[derive(Debug, Clone, Default)]
 pub struct Stats {
     pub(crate) bytes_in: u64,
     pub(crate) bytes_out: u64,
     pub(crate) chunks_in: u64,
     pub(crate) records_processed: u64,
+    /// Records dropped under the `skip` or `collect` record error policy.
+    pub(crate) records_skipped: u64,
     pub(crate) parse_time_ns: u64,
     pub(crate) transform_time_ns: u64,
     pub(crate) write_time_ns: u64,
@@ -16,54 +35,59 @@ pub struct Stats {
     pub(crate) current_partial_size: usize,
 }
 
-#[wasm_bindgen]
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
 impl Stats {
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn bytes_in(&self) -> f64 {
         self.bytes_in as f64
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn bytes_out(&self) -> f64 {
         self.bytes_out as f64
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn chunks_in(&self) -> f64 {
         self.chunks_in as f64
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn records_processed(&self) -> f64 {
         self.records_processed as f64
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn records_skipped(&self) -> f64 {
+        self.records_skipped as f64
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn parse_time_ms(&self) -> f64 {
         self.parse_time_ns as f64 / 1_000_000.0
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn transform_time_ms(&self) -> f64 {
         self.transform_time_ns as f64 / 1_000_000.0
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn write_time_ms(&self) -> f64 {
         self.write_time_ns as f64 / 1_000_000.0
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn max_buffer_size(&self) -> usize {
         self.max_buffer_size
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn current_partial_size(&self) -> usize {
         self.current_partial_size
     }
 
-    #[wasm_bindgen(getter)]
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
     pub fn throughput_mb_per_sec(&self) -> f64 {
         let total_time_sec = (self.parse_time_ns + self.transform_time_ns + self.write_time_ns) as f64 / 1_000_000_000.0;
         if total_time_sec > 0.0 {
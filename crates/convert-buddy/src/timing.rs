@@ -1,13 +1,13 @@
 use std::time::Duration;
 
 /// Get current time in milliseconds (WASM-compatible)
-#[cfg(target_arch = "wasm32")]
-fn now_ms() -> f64 {
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn now_ms() -> f64 {
     js_sys::Date::now()
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-fn now_ms() -> f64 {
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub(crate) fn now_ms() -> f64 {
     use std::time::SystemTime;
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
@@ -0,0 +1,105 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// Events kept when trace mode is enabled without an explicit capacity.
+pub const DEFAULT_TRACE_CAPACITY: usize = 1024;
+
+/// Something the converter did, as recorded in trace mode.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum TraceEvent {
+    /// The converter switched to a different internal pipeline.
+    StateChange { from: &'static str, to: &'static str },
+    /// Input format settings picked by auto-detection, or why none were.
+    Detection {
+        sample_bytes: usize,
+        decision: String,
+    },
+    Push {
+        state: &'static str,
+        input_bytes: usize,
+        output_bytes: usize,
+        /// Bytes held in parser/engine buffers after the push.
+        buffered_bytes: usize,
+        elapsed_ms: f64,
+    },
+    Finish {
+        state: &'static str,
+        output_bytes: usize,
+        elapsed_ms: f64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceEntry {
+    /// Position in the full event sequence, including entries already evicted.
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: TraceEvent,
+}
+
+/// Fixed-size ring buffer of the most recent [`TraceEvent`]s.
+#[derive(Debug)]
+pub struct TraceLog {
+    entries: VecDeque<TraceEntry>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl TraceLog {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            entries: VecDeque::with_capacity(capacity.min(DEFAULT_TRACE_CAPACITY)),
+            capacity,
+            next_seq: 0,
+        }
+    }
+
+    pub fn record(&mut self, event: TraceEvent) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TraceEntry {
+            seq: self.next_seq,
+            event,
+        });
+        self.next_seq += 1;
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    /// Events recorded but no longer held, because they were evicted or cleared.
+    pub fn dropped(&self) -> u64 {
+        self.next_seq - self.entries.len() as u64
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_the_most_recent_events() {
+        let mut log = TraceLog::new(2);
+        for to in ["A", "B", "C"] {
+            log.record(TraceEvent::StateChange { from: "X", to });
+        }
+
+        let seqs: Vec<u64> = log.entries().map(|entry| entry.seq).collect();
+        assert_eq!(seqs, vec![1, 2]);
+        assert_eq!(log.dropped(), 1);
+
+        let json = serde_json::to_value(log.entries().next().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "seq": 1, "event": "stateChange", "from": "X", "to": "B" })
+        );
+    }
+}
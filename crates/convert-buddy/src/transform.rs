@@ -1,9 +1,30 @@
+use crate::dedupe::{DedupeConfig, Deduper};
 use crate::error::{ConvertError, Result};
+use crate::query::{decode_key, raw_members, RecordQuery};
+use crate::record_errors::RecordErrors;
+use crate::sampling::Sampler;
+use crate::sort::{SortConfig, Sorter};
+use crate::projection::{FieldProjection, Projector};
+use crate::warnings::{WarningCode, Warnings};
+use crate::xml_parser::{XmlConfig, XmlParser};
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig, STANDARD as BASE64_STANDARD};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Months, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat};
 use memchr::memchr;
-use serde::Deserialize;
+use regex::Regex;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Number, Value};
-
-#[derive(Debug, Clone, Copy, Deserialize)]
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TransformMode {
     Replace,
@@ -16,7 +37,7 @@ impl Default for TransformMode {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MissingFieldPolicy {
     Error,
@@ -30,7 +51,7 @@ impl Default for MissingFieldPolicy {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MissingRequiredPolicy {
     Error,
@@ -43,7 +64,7 @@ impl Default for MissingRequiredPolicy {
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CoerceErrorPolicy {
     Error,
@@ -57,7 +78,30 @@ impl Default for CoerceErrorPolicy {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// How compute expressions handle non-finite results such as division by zero.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NanPolicy {
+    #[default]
+    Null,
+    Error,
+    Zero,
+}
+
+/// How `base64_decode()` writes bytes that aren't UTF-8 text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinaryOutput {
+    /// Standard padded base64, whatever variant the input used.
+    #[default]
+    Base64,
+    /// Lowercase hex.
+    Hex,
+    /// Fail the record.
+    Error,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum CoerceSpec {
     String,
@@ -65,9 +109,11 @@ pub enum CoerceSpec {
     F64,
     Bool,
     TimestampMs { format: Option<TimestampFormat> },
+    Json,
+    Stringify,
 }
 
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum TimestampFormat {
     Iso8601,
@@ -90,26 +136,282 @@ pub struct FieldMapInput {
     pub default_value: Option<Value>,
     pub coerce: Option<CoerceSpec>,
     pub compute: Option<String>,
+    pub on_missing_field: Option<MissingFieldPolicy>,
+    pub on_coerce_error: Option<CoerceErrorPolicy>,
+    /// When `false`, the field is only visible to later fields and is not written to output.
+    pub emit: Option<bool>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TransformConfigInput {
     #[serde(default)]
     pub mode: TransformMode,
+    #[serde(default)]
     pub fields: Vec<FieldMapInput>,
     pub on_missing_field: Option<MissingFieldPolicy>,
     pub on_missing_required: Option<MissingRequiredPolicy>,
     pub on_coerce_error: Option<CoerceErrorPolicy>,
+    pub nan_policy: Option<NanPolicy>,
+    pub binary_output: Option<BinaryOutput>,
+    /// Stop the conversion after the first input record matching this query.
+    pub stop_when: Option<RecordQuery>,
+    /// Only records matching this query or expression are transformed and written.
+    pub filter: Option<FilterInput>,
+    /// Dotted path of an array field, e.g. `offers.offer`; each element is
+    /// merged into the rest of the record and transformed as its own record.
+    pub explode: Option<String>,
+}
+
+/// `transform.filter`: a [`RecordQuery`], or a boolean expression such as
+/// `status == 'active' && age >= 18`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum FilterInput {
+    Expression(String),
+    Query(RecordQuery),
+}
+
+/// Compiled `transform.filter`.
+#[derive(Debug, Clone)]
+enum RecordFilter {
+    Query(RecordQuery),
+    Expression(Expr),
+}
+
+impl RecordFilter {
+    fn compile(input: FilterInput) -> Result<Self> {
+        match input {
+            FilterInput::Query(query) => Ok(RecordFilter::Query(query)),
+            FilterInput::Expression(expr) => parse_expression(&expr)
+                .map(RecordFilter::Expression)
+                .map_err(|e| ConvertError::InvalidConfig(format!("Invalid filter expression: {e}"))),
+        }
+    }
+
+    fn matches(&self, record: &Map<String, Value>, ctx: &EvalContext<'_>) -> Result<bool> {
+        match self {
+            RecordFilter::Query(query) => Ok(query.matches(record)),
+            RecordFilter::Expression(expr) => Ok(truthy(&expr.evaluate(record, ctx)?)),
+        }
+    }
+}
+
+/// Names handled by `evaluate_function`; custom functions may not shadow them.
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "concat",
+    "lower",
+    "upper",
+    "trim",
+    "coalesce",
+    "if",
+    "substring",
+    "replace",
+    "split",
+    "pad_left",
+    "pad_right",
+    "len",
+    "regex_extract",
+    "now",
+    "parse_date",
+    "format_date",
+    "date_add",
+    "parse_xml",
+    "parse_csv",
+    "base64_encode",
+    "base64_decode",
+    "url_encode",
+    "url_decode",
+    "url_param",
+    "strip_html",
+    "decode_html_entities",
+    "normalize",
+    "transliterate",
+    "slugify",
+    "geo_split",
+    "haversine",
+    "lag",
+    "lead",
+    "delta",
+];
+
+/// Largest `n` of `lag(field, n)` and `lead(field, n)`, which bounds the
+/// records a [`TransformEngine`] keeps around.
+const MAX_WINDOW_OFFSET: usize = 1000;
+
+/// A custom function callable from compute expressions, e.g. `vat(price, country)`.
+///
+/// Arguments are evaluated before the call; the returned value is used as-is.
+pub trait ExpressionFunction {
+    fn call(&self, args: &[Value]) -> Result<Value>;
+}
+
+impl<F> ExpressionFunction for F
+where
+    F: Fn(&[Value]) -> Result<Value>,
+{
+    fn call(&self, args: &[Value]) -> Result<Value> {
+        self(args)
+    }
+}
+
+/// Named custom functions available to compute expressions.
+#[derive(Clone, Default)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Rc<dyn ExpressionFunction>>,
+}
+
+impl FunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `function` under `name`, replacing any previous custom function
+    /// with that name. Built-in function names are reserved.
+    pub fn register<F>(&mut self, name: impl Into<String>, function: F) -> Result<()>
+    where
+        F: ExpressionFunction + 'static,
+    {
+        let name = name.into();
+        if BUILTIN_FUNCTIONS.contains(&name.as_str()) {
+            return Err(ConvertError::InvalidConfig(format!(
+                "Cannot override built-in function '{name}'"
+            )));
+        }
+        self.functions.insert(name, Rc::new(function));
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    fn get(&self, name: &str) -> Option<&Rc<dyn ExpressionFunction>> {
+        self.functions.get(name)
+    }
+}
+
+impl fmt::Debug for FunctionRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<&String> = self.functions.keys().collect();
+        names.sort();
+        f.debug_struct("FunctionRegistry").field("functions", &names).finish()
+    }
+}
+
+/// Current version of the serialized plan format produced by [`TransformPlan::to_config`].
+///
+/// Version history:
+/// - 1: initial format; arithmetic collapsed non-finite results to `0`.
+/// - 2: adds `nanPolicy`; version 1 plans load with [`NanPolicy::Zero`].
+/// - 3: adds `filterExpression`.
+/// - 4: adds `explode`.
+/// - 5: adds `binaryOutput`.
+/// - 6: adds `emit: false` on fields.
+/// - 7: adds `stopWhen`.
+/// - 8: adds `filter`.
+pub const TRANSFORM_PLAN_VERSION: u32 = 8;
+
+/// Stable, serializable form of a compiled [`TransformPlan`].
+///
+/// Compute expressions are stored as parsed trees, so reloading a plan never
+/// re-runs the expression parser.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformPlanConfig {
+    version: u32,
+    mode: TransformMode,
+    fields: Vec<TransformFieldConfig>,
+    on_missing_required: MissingRequiredPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nan_policy: Option<NanPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    binary_output: Option<BinaryOutput>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stop_when: Option<RecordQuery>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filter: Option<RecordQuery>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filter_expression: Option<Expr>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    explode: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TransformFieldConfig {
+    target_field_name: String,
+    origin_field_name: String,
+    #[serde(default)]
+    required: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_value: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    coerce: Option<CoerceSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compute: Option<Expr>,
+    #[serde(default)]
+    on_missing_field: MissingFieldPolicy,
+    #[serde(default)]
+    on_coerce_error: CoerceErrorPolicy,
+    #[serde(default = "default_emit")]
+    emit: bool,
+}
+
+fn default_emit() -> bool {
+    true
+}
+
+impl TransformPlanConfig {
+    pub fn version(&self) -> u32 {
+        self.version
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TransformPlan {
     mode: TransformMode,
     fields: Vec<TransformField>,
-    on_missing_field: MissingFieldPolicy,
     on_missing_required: MissingRequiredPolicy,
-    on_coerce_error: CoerceErrorPolicy,
+    nan_policy: NanPolicy,
+    binary_output: BinaryOutput,
+    stop_when: Option<RecordQuery>,
+    filter: Option<RecordFilter>,
+    /// `explode` split into its keys.
+    explode: Option<Vec<String>>,
+    /// Neighbouring records read by `lag()`, `lead()` and `delta()`.
+    window: WindowSpan,
+    /// Converter-level `dedupe` and `sort`, applied to the records the plan writes.
+    dedupe: Option<DedupeConfig>,
+    sort: Option<SortConfig>,
+    /// Converter-level `selectFields`/`dropFields`, applied to the records the plan reads.
+    projection: Option<FieldProjection>,
+    /// Converter-level `maxRecords` and `sampleEveryN`, applied to the records the plan writes.
+    max_records: Option<usize>,
+    sample_every: Option<usize>,
+    /// Made by [`passthrough`](Self::passthrough), for those alone.
+    passthrough: bool,
+    functions: FunctionRegistry,
+    warnings: Warnings,
+    errors: RecordErrors,
+    regexes: RegexCache,
+}
+
+/// Patterns compiled by `regex_extract`, kept for the following records.
+#[derive(Debug, Clone, Default)]
+struct RegexCache(RefCell<HashMap<String, Rc<Regex>>>);
+
+impl RegexCache {
+    fn get(&self, pattern: &str) -> Result<Rc<Regex>> {
+        if let Some(regex) = self.0.borrow().get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Rc::new(Regex::new(pattern).map_err(|e| {
+            ConvertError::InvalidConfig(format!("regex_extract(): invalid pattern: {e}"))
+        })?);
+        self.0.borrow_mut().insert(pattern.to_string(), regex.clone());
+        Ok(regex)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -120,15 +422,38 @@ pub struct TransformField {
     default_value: Option<Value>,
     coerce: Option<CoerceSpec>,
     compute: Option<Expr>,
+    on_missing_field: MissingFieldPolicy,
+    on_coerce_error: CoerceErrorPolicy,
+    emit: bool,
+}
+
+/// A plan without fields is only meaningful in augment mode, where it passes
+/// records through (e.g. to evaluate `stopWhen` or `filter` alone).
+fn check_fields_present(mode: TransformMode, fields_empty: bool) -> Result<()> {
+    if fields_empty && !matches!(mode, TransformMode::Augment) {
+        return Err(ConvertError::InvalidConfig(
+            "transform.fields must contain at least one field unless mode is augment".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn explode_path(path: &str) -> Result<Vec<String>> {
+    let keys: Vec<String> = path.split('.').map(str::to_string).collect();
+    if keys.iter().any(String::is_empty) {
+        return Err(ConvertError::InvalidConfig(format!(
+            "Invalid transform.explode path '{path}'"
+        )));
+    }
+    Ok(keys)
 }
 
 impl TransformPlan {
     pub fn compile(input: TransformConfigInput) -> Result<Self> {
-        if input.fields.is_empty() {
-            return Err(ConvertError::InvalidConfig(
-                "transform.fields must contain at least one field".to_string(),
-            ));
-        }
+        check_fields_present(input.mode, input.fields.is_empty())?;
+
+        let on_missing_field = input.on_missing_field.unwrap_or_default();
+        let on_coerce_error = input.on_coerce_error.unwrap_or_default();
 
         let mut fields = Vec::with_capacity(input.fields.len());
         for field in input.fields {
@@ -150,36 +475,362 @@ impl TransformPlan {
                 default_value: field.default_value,
                 coerce: field.coerce,
                 compute,
+                on_missing_field: field.on_missing_field.unwrap_or(on_missing_field),
+                on_coerce_error: field.on_coerce_error.unwrap_or(on_coerce_error),
+                emit: field.emit.unwrap_or(true),
             });
         }
 
-        Ok(Self {
+        Self {
             mode: input.mode,
             fields,
-            on_missing_field: input.on_missing_field.unwrap_or_default(),
             on_missing_required: input.on_missing_required.unwrap_or_default(),
-            on_coerce_error: input.on_coerce_error.unwrap_or_default(),
+            nan_policy: input.nan_policy.unwrap_or_default(),
+            binary_output: input.binary_output.unwrap_or_default(),
+            stop_when: input.stop_when,
+            filter: input.filter.map(RecordFilter::compile).transpose()?,
+            explode: input.explode.as_deref().map(explode_path).transpose()?,
+            window: WindowSpan::default(),
+            dedupe: None,
+            sort: None,
+            projection: None,
+            max_records: None,
+            sample_every: None,
+            passthrough: false,
+            functions: FunctionRegistry::default(),
+            warnings: Warnings::default(),
+            errors: RecordErrors::default(),
+            regexes: RegexCache::default(),
+        }
+        .with_window_span()
+    }
+
+    /// Export the compiled plan in its persisted form.
+    pub fn to_config(&self) -> TransformPlanConfig {
+        TransformPlanConfig {
+            version: TRANSFORM_PLAN_VERSION,
+            mode: self.mode,
+            on_missing_required: self.on_missing_required,
+            nan_policy: Some(self.nan_policy),
+            binary_output: Some(self.binary_output),
+            stop_when: self.stop_when.clone(),
+            filter: match &self.filter {
+                Some(RecordFilter::Query(query)) => Some(query.clone()),
+                _ => None,
+            },
+            filter_expression: match &self.filter {
+                Some(RecordFilter::Expression(expr)) => Some(expr.clone()),
+                _ => None,
+            },
+            explode: self.explode.as_ref().map(|keys| keys.join(".")),
+            fields: self
+                .fields
+                .iter()
+                .map(|field| TransformFieldConfig {
+                    target_field_name: field.target_field_name.clone(),
+                    origin_field_name: field.origin_field_name.clone(),
+                    required: field.required,
+                    default_value: field.default_value.clone(),
+                    coerce: field.coerce.clone(),
+                    compute: field.compute.clone(),
+                    on_missing_field: field.on_missing_field,
+                    on_coerce_error: field.on_coerce_error,
+                    emit: field.emit,
+                })
+                .collect(),
+        }
+    }
+
+    /// Rebuild a plan from a config produced by [`TransformPlan::to_config`].
+    pub fn from_config(config: TransformPlanConfig) -> Result<Self> {
+        if config.version == 0 || config.version > TRANSFORM_PLAN_VERSION {
+            return Err(ConvertError::InvalidConfig(format!(
+                "Unsupported transform plan version {} (supported: 1..={})",
+                config.version, TRANSFORM_PLAN_VERSION
+            )));
+        }
+        check_fields_present(config.mode, config.fields.is_empty())?;
+
+        let nan_policy = config.nan_policy.unwrap_or(if config.version < 2 {
+            NanPolicy::Zero
+        } else {
+            NanPolicy::default()
+        });
+
+        let filter = match (config.filter, config.filter_expression) {
+            (Some(_), Some(_)) => {
+                return Err(ConvertError::InvalidConfig(
+                    "filter and filterExpression can't both be set".to_string(),
+                ))
+            }
+            (Some(query), None) => Some(RecordFilter::Query(query)),
+            (None, expr) => expr.map(RecordFilter::Expression),
+        };
+
+        Self {
+            mode: config.mode,
+            on_missing_required: config.on_missing_required,
+            nan_policy,
+            binary_output: config.binary_output.unwrap_or_default(),
+            stop_when: config.stop_when,
+            filter,
+            explode: config.explode.as_deref().map(explode_path).transpose()?,
+            window: WindowSpan::default(),
+            dedupe: None,
+            sort: None,
+            projection: None,
+            max_records: None,
+            sample_every: None,
+            passthrough: false,
+            fields: config
+                .fields
+                .into_iter()
+                .map(|field| TransformField {
+                    target_field_name: field.target_field_name,
+                    origin_field_name: field.origin_field_name,
+                    required: field.required,
+                    default_value: field.default_value,
+                    coerce: field.coerce,
+                    compute: field.compute,
+                    on_missing_field: field.on_missing_field,
+                    on_coerce_error: field.on_coerce_error,
+                    emit: field.emit,
+                })
+                .collect(),
+            functions: FunctionRegistry::default(),
+            warnings: Warnings::default(),
+            errors: RecordErrors::default(),
+            regexes: RegexCache::default(),
+        }
+        .with_window_span()
+    }
+
+    /// Check the window functions of the plan and record how far they reach.
+    fn with_window_span(mut self) -> Result<Self> {
+        if let Some(RecordFilter::Expression(expr)) = &self.filter {
+            let mut span = WindowSpan::default();
+            expr.window_span(&mut span)?;
+            if span != WindowSpan::default() {
+                return Err(ConvertError::InvalidConfig(
+                    "lag(), lead() and delta() can only be used in compute expressions".to_string(),
+                ));
+            }
+        }
+        let mut span = WindowSpan::default();
+        for expr in self.fields.iter().filter_map(|field| field.compute.as_ref()) {
+            expr.window_span(&mut span)?;
+        }
+        self.window = span;
+        Ok(self)
+    }
+
+    /// A plan that writes records unchanged, for converter options that run
+    /// in the transform stage without a `transform`.
+    pub(crate) fn passthrough() -> Self {
+        let plan = Self::compile(TransformConfigInput {
+            mode: TransformMode::Augment,
+            ..TransformConfigInput::default()
         })
+        .expect("an augment plan without fields is valid");
+        Self {
+            passthrough: true,
+            ..plan
+        }
+    }
+
+    /// Input fields the plan reads, or `None` when every field is needed
+    /// (augment mode copies the whole record).
+    pub fn required_columns(&self) -> Option<Vec<String>> {
+        if matches!(self.mode, TransformMode::Augment) {
+            return None;
+        }
+
+        let mut columns = Vec::new();
+        for field in &self.fields {
+            match &field.compute {
+                Some(expr) => expr.collect_fields(&mut columns),
+                None => columns.push(field.origin_field_name.clone()),
+            }
+        }
+        if let Some(query) = &self.stop_when {
+            columns.push(query.field.clone());
+        }
+        match &self.filter {
+            Some(RecordFilter::Query(query)) => columns.push(query.field.clone()),
+            Some(RecordFilter::Expression(expr)) => expr.collect_fields(&mut columns),
+            None => {}
+        }
+        if let Some(keys) = &self.explode {
+            columns.push(keys[0].clone());
+        }
+        columns.sort();
+        columns.dedup();
+        Some(columns)
     }
 
+    /// The query of a plan that only filters records by a [`RecordQuery`], so
+    /// matching NDJSON lines can be passed through byte-for-byte without a
+    /// full parse.
+    fn filter_only_query(&self) -> Option<&RecordQuery> {
+        if !matches!(self.mode, TransformMode::Augment)
+            || !self.fields.is_empty()
+            || self.stop_when.is_some()
+            || self.explode.is_some()
+            || self.dedupe.is_some()
+        {
+            return None;
+        }
+        match &self.filter {
+            Some(RecordFilter::Query(query)) => Some(query),
+            _ => None,
+        }
+    }
+
+    /// Make the custom functions in `functions` callable from compute expressions.
+    pub fn with_functions(mut self, functions: FunctionRegistry) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    /// Report coercion failures handled by `onCoerceError` to `warnings`.
+    pub fn with_warnings(mut self, warnings: Warnings) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    /// Apply the record error policy of `errors` to records the plan fails on.
+    pub fn with_record_errors(mut self, errors: RecordErrors) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Drop records repeating the key fields of an earlier one, as the
+    /// converter's `dedupe` option. Only applied by [`TransformEngine`].
+    pub fn with_dedupe(mut self, dedupe: Option<DedupeConfig>) -> Self {
+        self.dedupe = dedupe;
+        self
+    }
+
+    /// Write records sorted at the end, as the converter's `sort` option.
+    /// Only applied by [`TransformEngine`].
+    pub fn with_sort(mut self, sort: Option<SortConfig>) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Keep at most `max_records` of the records written, and only every
+    /// `sample_every`th one, as the converter's `maxRecords` and
+    /// `sampleEveryN` options. Only applied by [`TransformEngine`].
+    pub fn with_sampling(mut self, max_records: Option<usize>, sample_every: Option<usize>) -> Self {
+        self.max_records = max_records;
+        self.sample_every = sample_every;
+        self
+    }
+
+    /// Keep only some fields of each input record, as the converter's
+    /// `selectFields`/`dropFields` options. Only applied by [`TransformEngine`].
+    pub fn with_projection(mut self, projection: Option<FieldProjection>) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Transform one record; `None` when it is filtered out or dropped. A
+    /// plan with `explode` fails on records it would split in several; use
+    /// [`apply_all`](Self::apply_all) for those.
     pub fn apply_to_value(&self, value: &Value) -> Result<Option<Value>> {
+        if self.explode.is_some() {
+            let mut records = self.apply_all(value)?;
+            if records.len() > 1 {
+                return Err(ConvertError::InvalidConfig(format!(
+                    "transform.explode splits the record into {} records",
+                    records.len()
+                )));
+            }
+            return Ok(records.pop());
+        }
         let record = value.as_object().ok_or_else(|| {
             ConvertError::InvalidConfig("Transform expects object records".to_string())
         })?;
         self.apply_to_record(record)
     }
 
+    /// The records the plan writes for one input record: none when it is
+    /// filtered out, one per element of the `explode` field otherwise.
+    pub fn apply_all(&self, value: &Value) -> Result<Vec<Value>> {
+        let record = value.as_object().ok_or_else(|| {
+            ConvertError::InvalidConfig("Transform expects object records".to_string())
+        })?;
+        let Some(keys) = &self.explode else {
+            return Ok(self.apply_to_record(record)?.into_iter().collect());
+        };
+        let mut output = Vec::new();
+        for exploded in explode_record(record, keys) {
+            output.extend(self.apply_to_record(&exploded)?);
+        }
+        Ok(output)
+    }
+
     fn apply_to_record(&self, record: &Map<String, Value>) -> Result<Option<Value>> {
+        let ctx = self.eval_context(None);
+        if !self.passes_filter(record, &ctx)? {
+            return Ok(None);
+        }
+        self.map_record(record, &ctx)
+    }
+
+    /// The input records field mapping sees for one input record, after
+    /// `explode` and `filter`; what a [`TransformEngine`] keeps in its window.
+    fn mapped_inputs(&self, value: &Value) -> Result<Vec<Map<String, Value>>> {
+        let record = value.as_object().ok_or_else(|| {
+            ConvertError::InvalidConfig("Transform expects object records".to_string())
+        })?;
+        let records = match &self.explode {
+            Some(keys) => explode_record(record, keys),
+            None => vec![record.clone()],
+        };
+        let ctx = self.eval_context(None);
+        let mut inputs = Vec::with_capacity(records.len());
+        for record in records {
+            if self.passes_filter(&record, &ctx)? {
+                inputs.push(record);
+            }
+        }
+        Ok(inputs)
+    }
+
+    fn eval_context<'a>(&'a self, window: Option<WindowView<'a>>) -> EvalContext<'a> {
+        EvalContext {
+            functions: &self.functions,
+            nan_policy: self.nan_policy,
+            binary_output: self.binary_output,
+            regexes: &self.regexes,
+            window,
+        }
+    }
+
+    fn passes_filter(&self, record: &Map<String, Value>, ctx: &EvalContext<'_>) -> Result<bool> {
+        match &self.filter {
+            Some(filter) => filter.matches(record, ctx),
+            None => Ok(true),
+        }
+    }
+
+    /// Map the fields of a record that passed the filter.
+    fn map_record(&self, record: &Map<String, Value>, ctx: &EvalContext<'_>) -> Result<Option<Value>> {
         let mut output = match self.mode {
             TransformMode::Replace => Map::new(),
             TransformMode::Augment => record.clone(),
         };
 
+        // Non-emitted fields are added to the scope seen by later fields; the
+        // input record is only cloned when the plan actually uses them.
+        let mut scope = Cow::Borrowed(record);
+
         for field in &self.fields {
             let mut value = if let Some(expr) = &field.compute {
-                Some(expr.evaluate(record)? )
+                Some(expr.evaluate(&scope, ctx)?)
             } else {
-                record.get(&field.origin_field_name).cloned()
+                scope.get(&field.origin_field_name).cloned()
             };
 
             if value.as_ref().map(|v| v.is_null()).unwrap_or(true) {
@@ -195,7 +846,7 @@ impl TransformPlan {
                         }
                     }
                 } else {
-                    match self.on_missing_field {
+                    match field.on_missing_field {
                         MissingFieldPolicy::Error => {
                             return Err(ConvertError::InvalidConfig(format!(
                                 "Missing field '{}'",
@@ -218,21 +869,81 @@ impl TransformPlan {
                     Ok(coerced) => {
                         value = coerced;
                     }
-                    Err(err) => match self.on_coerce_error {
+                    Err(err) => match field.on_coerce_error {
                         CoerceErrorPolicy::Error => return Err(err),
                         CoerceErrorPolicy::Null => {
+                            self.warn_coercion(field, &err, "set to null");
                             value = Value::Null;
                         }
-                        CoerceErrorPolicy::DropRecord => return Ok(None),
+                        CoerceErrorPolicy::DropRecord => {
+                            self.warn_coercion(field, &err, "record dropped");
+                            return Ok(None);
+                        }
                     },
                 }
             }
 
-            output.insert(field.target_field_name.clone(), value);
+            if field.emit {
+                output.insert(field.target_field_name.clone(), value);
+            } else {
+                scope.to_mut().insert(field.target_field_name.clone(), value);
+            }
         }
 
         Ok(Some(Value::Object(output)))
     }
+
+    fn warn_coercion(&self, field: &TransformField, err: &ConvertError, outcome: &str) {
+        self.warnings.warn(WarningCode::CoercionFailed, || {
+            format!("field '{}' {outcome}: {err}", field.target_field_name)
+        });
+    }
+}
+
+/// Split `record` on the value at `keys`: one record per array element, with
+/// object elements merged into the rest of the record (their fields win) and
+/// other elements stored under the last key. A single object, as XML input
+/// gives for one child element, counts as one element; a missing, null or
+/// empty value leaves the record as one record without it.
+fn explode_record(record: &Map<String, Value>, keys: &[String]) -> Vec<Map<String, Value>> {
+    let mut parent = record.clone();
+    let elements = match take_path(&mut parent, keys) {
+        Some(Value::Array(items)) => items,
+        Some(Value::Null) | None => Vec::new(),
+        Some(other) => vec![other],
+    };
+    if elements.is_empty() {
+        return vec![parent];
+    }
+    elements
+        .into_iter()
+        .map(|element| {
+            let mut output = parent.clone();
+            match element {
+                Value::Object(fields) => output.extend(fields),
+                other => {
+                    output.insert(keys[keys.len() - 1].clone(), other);
+                }
+            }
+            output
+        })
+        .collect()
+}
+
+/// Remove the value at `keys`, along with the objects it leaves empty.
+fn take_path(record: &mut Map<String, Value>, keys: &[String]) -> Option<Value> {
+    let (first, rest) = keys.split_first()?;
+    if rest.is_empty() {
+        return record.remove(first);
+    }
+    let Value::Object(child) = record.get_mut(first)? else {
+        return None;
+    };
+    let value = take_path(child, rest)?;
+    if child.is_empty() {
+        record.remove(first);
+    }
+    Some(value)
 }
 
 #[derive(Debug)]
@@ -241,20 +952,60 @@ pub struct TransformResult {
     pub records: usize,
 }
 
+/// Maximum number of bytes of the offending record quoted in transform errors.
+const ERROR_SNIPPET_BYTES: usize = 200;
+
 pub struct TransformEngine {
     plan: TransformPlan,
     partial_line: Vec<u8>,
+    record_index: usize,
+    matched: Option<Value>,
+    dedupe: Option<Deduper>,
+    sampler: Option<Sampler>,
+    sort: Option<Sorter>,
+    projector: Option<Projector>,
+    raw_mapper: Option<RawMapper>,
+    window: Option<RecordWindow>,
+}
+
+/// Input records kept for window functions: written ones `lag()` may still
+/// read, then the `pending` ones waiting for the records `lead()` reads.
+#[derive(Debug, Default)]
+struct RecordWindow {
+    records: VecDeque<WindowRecord>,
+    pending: usize,
 }
 
 impl TransformEngine {
     pub fn new(plan: TransformPlan) -> Self {
+        let dedupe = plan
+            .dedupe
+            .clone()
+            .map(|config| Deduper::new(config, plan.warnings.clone()));
+        let sampler = Sampler::new(plan.max_records, plan.sample_every);
+        let sort = plan.sort.clone().map(Sorter::new);
+        let projector = plan.projection.as_ref().map(Projector::new);
+        let raw_mapper = RawMapper::for_plan(&plan);
+        let window = (plan.window != WindowSpan::default()).then(RecordWindow::default);
         Self {
             plan,
             partial_line: Vec::new(),
+            record_index: 0,
+            matched: None,
+            dedupe,
+            sampler,
+            sort,
+            projector,
+            raw_mapper,
+            window,
         }
     }
 
     pub fn push(&mut self, chunk: &[u8]) -> Result<TransformResult> {
+        if self.is_stopped() {
+            return Ok(TransformResult { output: Vec::new(), records: 0 });
+        }
+
         let mut output = Vec::with_capacity(chunk.len() + 64);
         let mut records = 0;
 
@@ -273,14 +1024,14 @@ impl TransformEngine {
             let line = &input_data[start..line_end];
 
             if !line.is_empty() && !line.iter().all(|&b| b.is_ascii_whitespace()) {
-                if let Some(transformed) = self.transform_line(line)? {
-                    output.extend_from_slice(&transformed);
-                    output.push(b'\n');
-                    records += 1;
-                }
+                records += self.transform_record(line, &mut output)?;
             }
 
             start = line_end + 1;
+            if self.is_stopped() {
+                start = input_data.len();
+                break;
+            }
         }
 
         self.partial_line.clear();
@@ -295,16 +1046,26 @@ impl TransformEngine {
         let mut output = Vec::new();
         let mut records = 0;
 
-        if !self.partial_line.is_empty() {
+        if !self.partial_line.is_empty() && !self.is_stopped() {
             let line = std::mem::take(&mut self.partial_line);
             if !line.iter().all(|&b| b.is_ascii_whitespace()) {
-                if let Some(transformed) = self.transform_line(&line)? {
-                    output.extend_from_slice(&transformed);
-                    output.push(b'\n');
-                    records += 1;
-                }
+                records += self.transform_record(&line, &mut output)?;
             }
         }
+        let start = output.len();
+        let mut held = self.drain_window(&mut output, true)?;
+        if let Some(dedupe) = &mut self.dedupe {
+            held += dedupe.finish(&mut output);
+        }
+        records += match &mut self.sampler {
+            Some(sampler) => sampler.sample(&mut output, start),
+            None => held,
+        };
+        if let Some(sort) = &mut self.sort {
+            sort.push(&output)?;
+            output.clear();
+            records = sort.finish(&mut output)?;
+        }
 
         Ok(TransformResult { output, records })
     }
@@ -313,30 +1074,359 @@ impl TransformEngine {
         self.partial_line.len()
     }
 
+    /// Drop the line cut off at the end of the last chunk.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn discard_partial(&mut self) {
+        self.partial_line.clear();
+    }
+
     pub fn plan(&self) -> &TransformPlan {
         &self.plan
     }
 
-    fn transform_line(&self, line: &[u8]) -> Result<Option<Vec<u8>>> {
+    /// Whether a record matching `stopWhen` has been seen, or `maxRecords`
+    /// records written; later input is ignored.
+    pub fn is_stopped(&self) -> bool {
+        self.matched.is_some() || self.reached_max_records()
+    }
+
+    /// Whether `maxRecords` records have been written.
+    pub fn reached_max_records(&self) -> bool {
+        self.sampler.as_ref().is_some_and(Sampler::is_done)
+    }
+
+    /// The input record that matched `stopWhen`, if any.
+    pub fn matched_record(&self) -> Option<&Value> {
+        self.matched.as_ref()
+    }
+
+    /// Transform a line into NDJSON lines appended to `output`, applying the
+    /// record error policy. Returns the number of records written.
+    /// With `sort`, records are taken by the sorter and written by `finish`.
+    fn transform_record(&mut self, line: &[u8], output: &mut Vec<u8>) -> Result<usize> {
+        let start = output.len();
+        let mut written = self.transform_line(line, output).or_else(|error| -> Result<usize> {
+            output.truncate(start);
+            self.plan.errors.handle(self.record_index as u64, line, error)?;
+            Ok(0)
+        })?;
+        if let Some(sampler) = &mut self.sampler {
+            written = sampler.sample(output, start);
+        }
+        match &mut self.sort {
+            Some(sort) => {
+                sort.push(&output[start..])?;
+                output.truncate(start);
+                Ok(0)
+            }
+            None => Ok(written),
+        }
+    }
+
+    fn transform_line(&mut self, line: &[u8], output: &mut Vec<u8>) -> Result<usize> {
+        let index = self.record_index;
+        self.record_index += 1;
+
+        let mut projected = Vec::new();
+        if let Some(projector) = &self.projector {
+            // Lines that aren't objects go on as they are
+            if projector.project(line, &mut projected) {
+                if self.plan.passthrough && self.dedupe.is_none() {
+                    output.extend_from_slice(&projected);
+                    output.push(b'\n');
+                    return Ok(1);
+                }
+            } else {
+                projected.extend_from_slice(line);
+            }
+        }
+        let line = if self.projector.is_some() { &projected[..] } else { line };
+
+        if let Some(filter) = self.plan.filter_only_query() {
+            // Lines the scanner can't handle fall through to the full parse,
+            // which reports the error.
+            if let Some(matched) = filter.matches_raw(line) {
+                if !matched {
+                    return Ok(0);
+                }
+                output.extend_from_slice(line);
+                output.push(b'\n');
+                return Ok(1);
+            }
+        }
+        if let Some(mapper) = &self.raw_mapper {
+            // Records it can't map, such as ones missing a required field,
+            // fall through to the full parse, which reports the error.
+            if let Some(written) = mapper.map_line(line, output) {
+                return Ok(written);
+            }
+        }
+
         let value: Value = serde_json::from_slice(line)
             .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
-        let transformed = self.plan.apply_to_value(&value)?;
-        if let Some(output_value) = transformed {
-            let output = serde_json::to_vec(&output_value)
+        if self.plan.passthrough {
+            // Keep the line's key order and formatting
+            let start = output.len();
+            output.extend_from_slice(line);
+            output.push(b'\n');
+            return self.admit(&value, output, start);
+        }
+        if let (Some(query), Some(record)) = (&self.plan.stop_when, value.as_object()) {
+            if query.matches(record) {
+                self.matched = Some(value.clone());
+            }
+        }
+        if let Some(window) = &mut self.window {
+            let inputs = self.plan.mapped_inputs(&value).map_err(|err| record_error(index, line, err))?;
+            let line: Rc<[u8]> = Rc::from(line);
+            for record in inputs {
+                window.records.push_back(WindowRecord { record, index, line: line.clone() });
+                window.pending += 1;
+            }
+            return self.drain_window(output, false);
+        }
+        let transformed = self.plan.apply_all(&value).map_err(|err| record_error(index, line, err))?;
+        let mut written = 0;
+        for record in &transformed {
+            let start = output.len();
+            serde_json::to_writer(&mut *output, record)
                 .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
-            Ok(Some(output))
-        } else {
-            Ok(None)
+            output.push(b'\n');
+            written += self.admit(record, output, start)?;
+        }
+        Ok(written)
+    }
+
+    /// Map the records in the window whose `lead()` records have arrived, or
+    /// all of them when `finishing`. Their errors are handled here, as they
+    /// belong to earlier input lines.
+    fn drain_window(&mut self, output: &mut Vec<u8>, finishing: bool) -> Result<usize> {
+        let span = self.plan.window;
+        let mut written = 0;
+        while let Some(window) = &mut self.window {
+            if window.pending == 0 || (!finishing && window.pending <= span.ahead) {
+                break;
+            }
+            let position = window.records.len() - window.pending;
+            window.pending -= 1;
+            let view = WindowView { records: &window.records, position };
+            let entry = &window.records[position];
+            let mapped = self.plan.map_record(&entry.record, &self.plan.eval_context(Some(view)));
+            let (index, line) = (entry.index, entry.line.clone());
+            while window.records.len() - window.pending > span.behind {
+                window.records.pop_front();
+            }
+
+            match mapped {
+                Ok(Some(record)) => {
+                    let start = output.len();
+                    serde_json::to_writer(&mut *output, &record)
+                        .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+                    output.push(b'\n');
+                    written += self.admit(&record, output, start)?;
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    let error = record_error(index, &line, err);
+                    self.plan.errors.handle(index as u64 + 1, &line, error)?;
+                }
+            }
+        }
+        Ok(written)
+    }
+
+    /// Pass the record written at `output[start..]` through `dedupe`.
+    fn admit(&mut self, record: &Value, output: &mut Vec<u8>, start: usize) -> Result<usize> {
+        match &mut self.dedupe {
+            Some(dedupe) => dedupe.admit(record, output, start),
+            None => Ok(1),
         }
     }
 }
 
-#[derive(Debug, Clone)]
-enum Expr {
-    Literal(Value),
-    Field(String),
-    Binary {
-        op: BinaryOp,
+/// Field mapping done on the raw bytes of NDJSON lines, for plans that only
+/// rename, select and add fields. Values are copied from the input instead of
+/// being parsed into a [`Value`] and written back, so they keep their own
+/// formatting; keys come out in the same order as from the `Value` path.
+#[derive(Debug)]
+struct RawMapper {
+    augment: bool,
+    fields: Vec<RawField>,
+    /// Indices into `fields` of the fields reading each input key.
+    origins: HashMap<String, Vec<usize>>,
+    /// Output keys in `Map` order, with their escaped form and the fields
+    /// writing them; the last field with a value wins.
+    targets: Vec<(String, Vec<u8>, Vec<usize>)>,
+    filter: Option<RecordQuery>,
+}
+
+#[derive(Debug)]
+enum RawMissing {
+    Default(Vec<u8>),
+    Null,
+    Drop,
+    /// Left to the `Value` path, which reports the error.
+    Fail,
+}
+
+#[derive(Debug)]
+struct RawField {
+    on_missing: RawMissing,
+}
+
+impl RawMapper {
+    /// `None` when the plan does more than copy fields, or when a later
+    /// stage needs the parsed record.
+    fn for_plan(plan: &TransformPlan) -> Option<Self> {
+        let filter = match &plan.filter {
+            Some(RecordFilter::Query(query)) => Some(query.clone()),
+            Some(RecordFilter::Expression(_)) => return None,
+            None => None,
+        };
+        if plan.passthrough
+            || plan.fields.is_empty()
+            || plan.stop_when.is_some()
+            || plan.explode.is_some()
+            || plan.window != WindowSpan::default()
+            || plan.dedupe.is_some()
+            || plan
+                .fields
+                .iter()
+                .any(|field| field.compute.is_some() || field.coerce.is_some() || !field.emit)
+        {
+            return None;
+        }
+
+        let mut fields = Vec::with_capacity(plan.fields.len());
+        let mut origins: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut targets: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, field) in plan.fields.iter().enumerate() {
+            let on_missing = match (&field.default_value, field.required, field.on_missing_field) {
+                (Some(default), _, _) => RawMissing::Default(serde_json::to_vec(default).ok()?),
+                (None, true, _) | (None, false, MissingFieldPolicy::Error) => RawMissing::Fail,
+                (None, false, MissingFieldPolicy::Null) => RawMissing::Null,
+                (None, false, MissingFieldPolicy::Drop) => RawMissing::Drop,
+            };
+            fields.push(RawField { on_missing });
+            origins.entry(field.origin_field_name.clone()).or_default().push(index);
+            targets.entry(&field.target_field_name).or_default().push(index);
+        }
+        let targets = targets
+            .into_iter()
+            .map(|(target, indices)| {
+                let quoted = serde_json::to_vec(target).ok()?;
+                Some((target.to_string(), quoted[1..quoted.len() - 1].to_vec(), indices))
+            })
+            .collect::<Option<_>>()?;
+
+        Some(Self {
+            augment: matches!(plan.mode, TransformMode::Augment),
+            fields,
+            origins,
+            targets,
+            filter,
+        })
+    }
+
+    /// Append the mapped record for `line` to `output` and return how many
+    /// records were written, or `None` to leave the line to the `Value` path.
+    fn map_line(&self, line: &[u8], output: &mut Vec<u8>) -> Option<usize> {
+        if let Some(filter) = &self.filter {
+            if !filter.matches_raw(line)? {
+                return Some(0);
+            }
+        }
+        // Checks the whole line without building a `Value`, so malformed
+        // input fails the same way on both paths.
+        serde_json::from_slice::<IgnoredAny>(line).ok()?;
+
+        let mut inputs: Vec<Option<&[u8]>> = vec![None; self.fields.len()];
+        let mut members = Vec::new();
+        raw_members(line, |raw_key, value| {
+            let key = if raw_key.contains(&b'\\') {
+                decode_key(raw_key).map(Cow::Owned)
+            } else {
+                std::str::from_utf8(raw_key).ok().map(Cow::Borrowed)
+            };
+            let Some(key) = key else {
+                return;
+            };
+            if let Some(indices) = self.origins.get(key.as_ref()) {
+                for &index in indices {
+                    inputs[index] = Some(value);
+                }
+            }
+            if self.augment {
+                members.push((key, raw_key, value));
+            }
+        })?;
+
+        let mut values = Vec::with_capacity(self.fields.len());
+        for (field, input) in self.fields.iter().zip(inputs) {
+            values.push(match input.filter(|value| *value != b"null") {
+                Some(value) => Some(value),
+                None => match &field.on_missing {
+                    RawMissing::Default(value) => Some(value.as_slice()),
+                    RawMissing::Null => Some(b"null".as_slice()),
+                    RawMissing::Drop => None,
+                    RawMissing::Fail => return None,
+                },
+            });
+        }
+        let mapped = self.targets.iter().filter_map(|(target, raw_key, indices)| {
+            let value = indices.iter().rev().find_map(|&index| values[index])?;
+            Some((target.as_str(), raw_key.as_slice(), value))
+        });
+
+        let mut record: BTreeMap<Cow<'_, str>, (&[u8], &[u8])> = BTreeMap::new();
+        for (key, raw_key, value) in members {
+            record.insert(key, (raw_key, value));
+        }
+        for (target, raw_key, value) in mapped {
+            record.insert(Cow::Borrowed(target), (raw_key, value));
+        }
+        output.push(b'{');
+        for (position, (raw_key, value)) in record.values().enumerate() {
+            if position > 0 {
+                output.push(b',');
+            }
+            output.push(b'"');
+            output.extend_from_slice(raw_key);
+            output.extend_from_slice(b"\":");
+            output.extend_from_slice(value);
+        }
+        output.extend_from_slice(b"}\n");
+        Some(1)
+    }
+}
+
+/// The error of the record at 0-based `index`, numbered from 1 as in the
+/// `line` of its [`RecordError`](crate::RecordError).
+fn record_error(index: usize, line: &[u8], err: ConvertError) -> ConvertError {
+    ConvertError::Transform(format!("record {}: {err} (record: {})", index + 1, record_snippet(line)))
+}
+
+/// Render a record for error messages, truncated to `ERROR_SNIPPET_BYTES` on a char boundary.
+pub(crate) fn record_snippet(line: &[u8]) -> String {
+    let text = String::from_utf8_lossy(line);
+    let text = text.trim();
+    if text.len() <= ERROR_SNIPPET_BYTES {
+        return text.to_string();
+    }
+    let mut end = ERROR_SNIPPET_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}...", &text[..end])
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Expr {
+    Literal(Value),
+    Field(String),
+    Binary {
+        op: BinaryOp,
         left: Box<Expr>,
         right: Box<Expr>,
     },
@@ -345,107 +1435,1134 @@ enum Expr {
         args: Vec<Expr>,
     },
     UnaryNeg(Box<Expr>),
+    Not(Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+/// Per-record state shared by every expression evaluated under one plan.
+struct EvalContext<'a> {
+    functions: &'a FunctionRegistry,
+    nan_policy: NanPolicy,
+    binary_output: BinaryOutput,
+    regexes: &'a RegexCache,
+    /// The records around this one, when a [`TransformEngine`] keeps them.
+    window: Option<WindowView<'a>>,
+}
+
+/// How many records before and after the current one window functions read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct WindowSpan {
+    behind: usize,
+    ahead: usize,
+}
+
+/// An input record kept by a [`TransformEngine`] for window functions.
+#[derive(Debug)]
+struct WindowRecord {
+    record: Map<String, Value>,
+    /// Index and line of the input record, for error reports.
+    index: usize,
+    line: Rc<[u8]>,
+}
+
+/// The current record's place among the records a [`TransformEngine`] keeps.
+#[derive(Clone, Copy)]
+struct WindowView<'a> {
+    records: &'a VecDeque<WindowRecord>,
+    position: usize,
+}
+
+impl<'a> WindowView<'a> {
+    fn record(&self, offset: isize) -> Option<&'a Map<String, Value>> {
+        let position = self.position.checked_add_signed(offset)?;
+        self.records.get(position).map(|entry| &entry.record)
+    }
+}
+
+impl EvalContext<'_> {
+    /// Wrap an arithmetic result, applying the NaN policy to non-finite values.
+    fn number(&self, value: f64) -> Result<Value> {
+        if let Some(number) = Number::from_f64(value) {
+            return Ok(Value::Number(number));
+        }
+        match self.nan_policy {
+            NanPolicy::Null => Ok(Value::Null),
+            NanPolicy::Zero => Ok(Value::Number(Number::from(0))),
+            NanPolicy::Error => Err(ConvertError::InvalidConfig(format!(
+                "Expression produced a non-finite number ({value})"
+            ))),
+        }
+    }
+}
+
+impl Expr {
+    /// Widen `span` to the records the window functions in this expression read.
+    fn window_span(&self, span: &mut WindowSpan) -> Result<()> {
+        match self {
+            Expr::Literal(_) | Expr::Field(_) => {}
+            Expr::Binary { left, right, .. } => {
+                left.window_span(span)?;
+                right.window_span(span)?;
+            }
+            Expr::Function { name, args } => {
+                match name.as_str() {
+                    "lag" => span.behind = span.behind.max(window_offset(name, args)?),
+                    "lead" => span.ahead = span.ahead.max(window_offset(name, args)?),
+                    "delta" => {
+                        if args.len() != 1 {
+                            return Err(ConvertError::InvalidConfig(
+                                "delta() expects 1 argument".to_string(),
+                            ));
+                        }
+                        span.behind = span.behind.max(1);
+                    }
+                    _ => {}
+                }
+                for arg in args {
+                    arg.window_span(span)?;
+                }
+            }
+            Expr::UnaryNeg(expr) | Expr::Not(expr) => expr.window_span(span)?,
+        }
+        Ok(())
+    }
+
+    fn collect_fields(&self, out: &mut Vec<String>) {
+        match self {
+            Expr::Literal(_) => {}
+            Expr::Field(name) => out.push(name.clone()),
+            Expr::Binary { left, right, .. } => {
+                left.collect_fields(out);
+                right.collect_fields(out);
+            }
+            Expr::Function { args, .. } => {
+                for arg in args {
+                    arg.collect_fields(out);
+                }
+            }
+            Expr::UnaryNeg(expr) | Expr::Not(expr) => expr.collect_fields(out),
+        }
+    }
+
+    fn evaluate(&self, record: &Map<String, Value>, ctx: &EvalContext<'_>) -> Result<Value> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Field(name) => Ok(record.get(name).cloned().unwrap_or(Value::Null)),
+            Expr::UnaryNeg(expr) => {
+                let value = expr.evaluate(record, ctx)?;
+                if value.is_null() {
+                    return Ok(Value::Null);
+                }
+                let number = to_f64(&value).ok_or_else(|| {
+                    ConvertError::InvalidConfig("Unary '-' expects a numeric value".to_string())
+                })?;
+                ctx.number(-number)
+            }
+            Expr::Not(expr) => Ok(Value::Bool(!truthy(&expr.evaluate(record, ctx)?))),
+            Expr::Binary { op: BinaryOp::And, left, right } => {
+                let result = truthy(&left.evaluate(record, ctx)?) && truthy(&right.evaluate(record, ctx)?);
+                Ok(Value::Bool(result))
+            }
+            Expr::Binary { op: BinaryOp::Or, left, right } => {
+                let result = truthy(&left.evaluate(record, ctx)?) || truthy(&right.evaluate(record, ctx)?);
+                Ok(Value::Bool(result))
+            }
+            Expr::Binary { op, left, right } => {
+                let left_val = left.evaluate(record, ctx)?;
+                let right_val = right.evaluate(record, ctx)?;
+                match op {
+                    BinaryOp::Equal => return Ok(Value::Bool(values_equal(&left_val, &right_val))),
+                    BinaryOp::NotEqual => return Ok(Value::Bool(!values_equal(&left_val, &right_val))),
+                    _ => {}
+                }
+                if left_val.is_null() || right_val.is_null() {
+                    return Ok(Value::Null);
+                }
+                if let Some(ordering) = op.ordering() {
+                    return Ok(match compare_values(&left_val, &right_val) {
+                        Some(order) => Value::Bool(ordering.contains(&order)),
+                        None => Value::Null,
+                    });
+                }
+                let left_num = to_f64(&left_val).ok_or_else(|| {
+                    ConvertError::InvalidConfig("Binary operator expects numeric values".to_string())
+                })?;
+                let right_num = to_f64(&right_val).ok_or_else(|| {
+                    ConvertError::InvalidConfig("Binary operator expects numeric values".to_string())
+                })?;
+                let result = match op {
+                    BinaryOp::Add => left_num + right_num,
+                    BinaryOp::Subtract => left_num - right_num,
+                    BinaryOp::Multiply => left_num * right_num,
+                    BinaryOp::Divide => left_num / right_num,
+                    _ => unreachable!("comparison and logical operators are handled above"),
+                };
+                ctx.number(result)
+            }
+            Expr::Function { name, args } => evaluate_function(name, args, record, ctx),
+        }
+    }
+}
+
+impl BinaryOp {
+    /// Orderings an ordering comparison accepts; `None` for other operators.
+    fn ordering(self) -> Option<&'static [std::cmp::Ordering]> {
+        use std::cmp::Ordering::{Equal, Greater, Less};
+        match self {
+            BinaryOp::Less => Some(&[Less]),
+            BinaryOp::LessEqual => Some(&[Less, Equal]),
+            BinaryOp::Greater => Some(&[Greater]),
+            BinaryOp::GreaterEqual => Some(&[Greater, Equal]),
+            _ => None,
+        }
+    }
+}
+
+/// Truthiness used by `!`, `&&`, `||` and filters: `null`, `false`, `0` and
+/// `""` are false, everything else is true.
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(flag) => *flag,
+        Value::Number(num) => num.as_f64().map(|n| n != 0.0).unwrap_or(true),
+        Value::String(text) => !text.is_empty(),
+        Value::Array(_) | Value::Object(_) => true,
+    }
+}
+
+/// `==` semantics: a number is compared numerically with a numeric string
+/// (CSV values are always strings), other scalars by their text form.
+fn values_equal(left: &Value, right: &Value) -> bool {
+    if left == right {
+        return true;
+    }
+    match (left, right) {
+        (Value::Null, _) | (_, Value::Null) => false,
+        (Value::Number(_), _) | (_, Value::Number(_)) => match (to_f64(left), to_f64(right)) {
+            (Some(l), Some(r)) => l == r,
+            _ => false,
+        },
+        (Value::Bool(flag), Value::String(text)) | (Value::String(text), Value::Bool(flag)) => {
+            text.as_str() == if *flag { "true" } else { "false" }
+        }
+        _ => false,
+    }
+}
+
+/// Order two values: as strings when both are strings, otherwise
+/// numerically. `None` when they can't be ordered, e.g. `"" < 3`.
+fn compare_values(left: &Value, right: &Value) -> Option<std::cmp::Ordering> {
+    if let (Value::String(l), Value::String(r)) = (left, right) {
+        return Some(l.cmp(r));
+    }
+    to_f64(left)?.partial_cmp(&to_f64(right)?)
+}
+
+fn evaluate_function(
+    name: &str,
+    args: &[Expr],
+    record: &Map<String, Value>,
+    ctx: &EvalContext<'_>,
+) -> Result<Value> {
+    match name {
+        "concat" => {
+            let mut output = String::new();
+            for arg in args {
+                let value = arg.evaluate(record, ctx)?;
+                match value {
+                    Value::Null => {}
+                    Value::String(s) => output.push_str(&s),
+                    other => output.push_str(&other.to_string()),
+                }
+            }
+            Ok(Value::String(output))
+        }
+        "lower" => {
+            let value = single_arg(name, args, record, ctx)?;
+            let text = value.as_str().ok_or_else(|| {
+                ConvertError::InvalidConfig("lower() expects a string".to_string())
+            })?;
+            Ok(Value::String(text.to_lowercase()))
+        }
+        "upper" => {
+            let value = single_arg(name, args, record, ctx)?;
+            let text = value.as_str().ok_or_else(|| {
+                ConvertError::InvalidConfig("upper() expects a string".to_string())
+            })?;
+            Ok(Value::String(text.to_uppercase()))
+        }
+        "trim" => {
+            let value = single_arg(name, args, record, ctx)?;
+            let text = value.as_str().ok_or_else(|| {
+                ConvertError::InvalidConfig("trim() expects a string".to_string())
+            })?;
+            Ok(Value::String(text.trim().to_string()))
+        }
+        "if" => {
+            let [condition, then, otherwise] = args else {
+                return Err(ConvertError::InvalidConfig(
+                    "if() expects 3 arguments".to_string(),
+                ));
+            };
+            // Only the chosen branch is evaluated
+            if truthy(&condition.evaluate(record, ctx)?) {
+                then.evaluate(record, ctx)
+            } else {
+                otherwise.evaluate(record, ctx)
+            }
+        }
+        "substring" => {
+            let (text, rest) = text_args(name, args, 2..=3, record, ctx)?;
+            let Some(text) = text else { return Ok(Value::Null) };
+            let start = index_arg(name, "start", &rest[0])?;
+            let chars = text.chars().skip(start);
+            Ok(Value::String(match rest.get(1) {
+                Some(len) => chars.take(index_arg(name, "len", len)?).collect(),
+                None => chars.collect(),
+            }))
+        }
+        "replace" => {
+            let (text, rest) = text_args(name, args, 3..=3, record, ctx)?;
+            let Some(text) = text else { return Ok(Value::Null) };
+            let from = string_arg(name, "from", &rest[0])?;
+            let to = string_arg(name, "to", &rest[1])?;
+            if from.is_empty() {
+                return Ok(Value::String(text));
+            }
+            Ok(Value::String(text.replace(from, to)))
+        }
+        "split" => {
+            let (text, rest) = text_args(name, args, 3..=3, record, ctx)?;
+            let Some(text) = text else { return Ok(Value::Null) };
+            let separator = string_arg(name, "sep", &rest[0])?;
+            if separator.is_empty() {
+                return Err(ConvertError::InvalidConfig(
+                    "split() expects a non-empty separator".to_string(),
+                ));
+            }
+            let index = index_arg(name, "index", &rest[1])?;
+            Ok(text
+                .split(separator)
+                .nth(index)
+                .map_or(Value::Null, |part| Value::String(part.to_string())))
+        }
+        "pad_left" | "pad_right" => {
+            let (text, rest) = text_args(name, args, 2..=3, record, ctx)?;
+            let Some(text) = text else { return Ok(Value::Null) };
+            let width = index_arg(name, "width", &rest[0])?;
+            let fill = match rest.get(1) {
+                Some(fill) => {
+                    let fill = string_arg(name, "fill", fill)?;
+                    let mut chars = fill.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) => ch,
+                        _ => {
+                            return Err(ConvertError::InvalidConfig(format!(
+                                "{name}() expects a single-character fill"
+                            )))
+                        }
+                    }
+                }
+                None => ' ',
+            };
+            let padding: String =
+                std::iter::repeat_n(fill, width.saturating_sub(text.chars().count())).collect();
+            Ok(Value::String(if name == "pad_left" {
+                padding + &text
+            } else {
+                text + &padding
+            }))
+        }
+        "len" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            Ok(text.map_or(Value::Null, |text| Value::Number(Number::from(text.chars().count()))))
+        }
+        "regex_extract" => {
+            let (text, rest) = text_args(name, args, 2..=3, record, ctx)?;
+            let Some(text) = text else { return Ok(Value::Null) };
+            let regex = ctx.regexes.get(string_arg(name, "pattern", &rest[0])?)?;
+            let Some(captures) = regex.captures(&text) else {
+                return Ok(Value::Null);
+            };
+            // The first group when the pattern has one, otherwise the whole match
+            let group = match rest.get(1) {
+                Some(group) => index_arg(name, "group", group)?,
+                None => usize::from(captures.len() > 1),
+            };
+            Ok(captures
+                .get(group)
+                .map_or(Value::Null, |found| Value::String(found.as_str().to_string())))
+        }
+        "now" => {
+            if !args.is_empty() {
+                return Err(ConvertError::InvalidConfig(
+                    "now() expects no arguments".to_string(),
+                ));
+            }
+            Ok(Value::Number(Number::from(crate::timing::now_ms() as i64)))
+        }
+        "parse_date" => {
+            let (text, rest) = text_args(name, args, 2..=2, record, ctx)?;
+            let format = string_arg(name, "format", &rest[0])?;
+            check_date_format(name, format)?;
+            // A value in another format gives null, so coalesce() can try several
+            Ok(text
+                .and_then(|text| parse_date(text.trim(), format))
+                .map_or(Value::Null, |ms| Value::Number(Number::from(ms))))
+        }
+        "format_date" => {
+            let [timestamp, format] = args else {
+                return Err(ConvertError::InvalidConfig(
+                    "format_date() expects 2 arguments".to_string(),
+                ));
+            };
+            let format = format.evaluate(record, ctx)?;
+            let format = string_arg(name, "format", &format)?;
+            check_date_format(name, format)?;
+            let Some(ms) = timestamp_arg(name, &timestamp.evaluate(record, ctx)?)? else {
+                return Ok(Value::Null);
+            };
+            Ok(Value::String(format_date(name, ms, format)?))
+        }
+        "date_add" => {
+            let [timestamp, amount, unit] = args else {
+                return Err(ConvertError::InvalidConfig(
+                    "date_add() expects 3 arguments".to_string(),
+                ));
+            };
+            let Some(ms) = timestamp_arg(name, &timestamp.evaluate(record, ctx)?)? else {
+                return Ok(Value::Null);
+            };
+            let amount = amount.evaluate(record, ctx)?;
+            if amount.is_null() {
+                return Ok(Value::Null);
+            }
+            let amount = to_f64(&amount)
+                .filter(|n| n.fract() == 0.0 && n.abs() <= i64::MAX as f64)
+                .map(|n| n as i64)
+                .ok_or_else(|| {
+                    ConvertError::InvalidConfig(
+                        "date_add() expects 'amount' to be an integer".to_string(),
+                    )
+                })?;
+            let unit = unit.evaluate(record, ctx)?;
+            let ms = date_add(ms, amount, string_arg(name, "unit", &unit)?)?;
+            Ok(Value::Number(Number::from(ms)))
+        }
+        "parse_xml" => {
+            let (text, rest) = text_args(name, args, 2..=2, record, ctx)?;
+            let record_element = string_arg(name, "recordElement", &rest[0])?;
+            if record_element.is_empty() {
+                return Err(ConvertError::InvalidConfig(
+                    "parse_xml() expects a non-empty recordElement".to_string(),
+                ));
+            }
+            let Some(text) = text else { return Ok(Value::Null) };
+            parse_xml(&text, record_element)
+        }
+        "parse_csv" => {
+            let (text, rest) = text_args(name, args, 1..=2, record, ctx)?;
+            let delimiter = match rest.first() {
+                Some(delimiter) => match string_arg(name, "delimiter", delimiter)?.as_bytes() {
+                    [byte] => *byte,
+                    _ => {
+                        return Err(ConvertError::InvalidConfig(
+                            "parse_csv() expects a single-byte delimiter".to_string(),
+                        ))
+                    }
+                },
+                None => b',',
+            };
+            let Some(text) = text else { return Ok(Value::Null) };
+            parse_csv(&text, delimiter)
+        }
+        "base64_encode" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            Ok(text.map_or(Value::Null, |text| Value::String(BASE64_STANDARD.encode(text))))
+        }
+        "base64_decode" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            let Some(text) = text else { return Ok(Value::Null) };
+            let bytes = base64_decode(&text)?;
+            match String::from_utf8(bytes) {
+                Ok(text) => Ok(Value::String(text)),
+                Err(err) => match ctx.binary_output {
+                    BinaryOutput::Base64 => Ok(Value::String(BASE64_STANDARD.encode(err.as_bytes()))),
+                    BinaryOutput::Hex => Ok(Value::String(hex(err.as_bytes()))),
+                    BinaryOutput::Error => Err(ConvertError::InvalidConfig(
+                        "base64_decode() got binary data, not UTF-8 text".to_string(),
+                    )),
+                },
+            }
+        }
+        "url_encode" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            Ok(text.map_or(Value::Null, |text| Value::String(url_encode(&text))))
+        }
+        "url_decode" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            Ok(text.map_or(Value::Null, |text| Value::String(url_decode(&text))))
+        }
+        "url_param" => {
+            let (url, rest) = text_args(name, args, 2..=2, record, ctx)?;
+            let param = string_arg(name, "name", &rest[0])?;
+            Ok(url
+                .and_then(|url| url_param(&url, param))
+                .map_or(Value::Null, Value::String))
+        }
+        "strip_html" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            Ok(text.map_or(Value::Null, |text| Value::String(strip_html(&text))))
+        }
+        "decode_html_entities" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            Ok(text.map_or(Value::Null, |text| Value::String(decode_html_entities(&text))))
+        }
+        "normalize" => {
+            let (text, rest) = text_args(name, args, 1..=2, record, ctx)?;
+            let form = match rest.first() {
+                Some(form) => string_arg(name, "form", form)?,
+                None => "NFC",
+            };
+            let normalize: fn(&str) -> String = match form.to_ascii_uppercase().as_str() {
+                "NFC" => |text| text.nfc().collect(),
+                "NFD" => |text| text.nfd().collect(),
+                "NFKC" => |text| text.nfkc().collect(),
+                "NFKD" => |text| text.nfkd().collect(),
+                _ => {
+                    return Err(ConvertError::InvalidConfig(format!(
+                        "normalize() expects form 'NFC', 'NFD', 'NFKC' or 'NFKD', got '{form}'"
+                    )))
+                }
+            };
+            Ok(text.map_or(Value::Null, |text| Value::String(normalize(&text))))
+        }
+        "transliterate" => {
+            let (text, _) = text_args(name, args, 1..=1, record, ctx)?;
+            Ok(text.map_or(Value::Null, |text| Value::String(deunicode::deunicode(&text))))
+        }
+        "slugify" => {
+            let (text, rest) = text_args(name, args, 1..=2, record, ctx)?;
+            let separator = match rest.first() {
+                Some(separator) => string_arg(name, "sep", separator)?,
+                None => "-",
+            };
+            Ok(text.map_or(Value::Null, |text| Value::String(slugify(&text, separator))))
+        }
+        "geo_split" => {
+            let (text, rest) = text_args(name, args, 1..=2, record, ctx)?;
+            let part = rest.first().map(|part| string_arg(name, "part", part)).transpose()?;
+            if part.is_some_and(|part| part != "lat" && part != "lon") {
+                return Err(ConvertError::InvalidConfig(
+                    "geo_split() expects part 'lat' or 'lon'".to_string(),
+                ));
+            }
+            // Text that isn't a coordinate pair gives null, like parse_date()
+            let Some((lat, lon)) = text.as_deref().and_then(parse_coordinates) else {
+                return Ok(Value::Null);
+            };
+            match part {
+                Some("lat") => ctx.number(lat),
+                Some(_) => ctx.number(lon),
+                None => {
+                    let mut point = Map::new();
+                    point.insert("lat".to_string(), ctx.number(lat)?);
+                    point.insert("lon".to_string(), ctx.number(lon)?);
+                    Ok(Value::Object(point))
+                }
+            }
+        }
+        "haversine" => {
+            if !(4..=5).contains(&args.len()) {
+                return Err(ConvertError::InvalidConfig(
+                    "haversine() expects 4 to 5 arguments".to_string(),
+                ));
+            }
+            let unit = match args.get(4) {
+                Some(unit) => string_arg(name, "unit", &unit.evaluate(record, ctx)?)?.to_string(),
+                None => "km".to_string(),
+            };
+            let unit_km = match unit.as_str() {
+                "km" => 1.0,
+                "m" => 0.001,
+                "mi" => 1.609344,
+                _ => {
+                    return Err(ConvertError::InvalidConfig(format!(
+                        "haversine() expects unit 'km', 'm' or 'mi', got '{unit}'"
+                    )))
+                }
+            };
+            let mut coordinates = [0.0; 4];
+            for (index, arg) in args[..4].iter().enumerate() {
+                let limit = if index % 2 == 0 { 90.0 } else { 180.0 };
+                match to_f64(&arg.evaluate(record, ctx)?).filter(|n| n.abs() <= limit) {
+                    Some(value) => coordinates[index] = value,
+                    None => return Ok(Value::Null),
+                }
+            }
+            let [lat1, lon1, lat2, lon2] = coordinates;
+            ctx.number(haversine_km(lat1, lon1, lat2, lon2) / unit_km)
+        }
+        "lag" | "lead" => {
+            let offset = window_offset(name, args)? as isize;
+            let offset = if name == "lag" { -offset } else { offset };
+            // Outside a TransformEngine no other records are known
+            match ctx.window.and_then(|window| window.record(offset)) {
+                Some(other) => args[0].evaluate(other, &EvalContext { window: None, ..*ctx }),
+                None => Ok(Value::Null),
+            }
+        }
+        "delta" => {
+            let [arg] = args else {
+                return Err(ConvertError::InvalidConfig(
+                    "delta() expects 1 argument".to_string(),
+                ));
+            };
+            let Some(previous) = ctx.window.and_then(|window| window.record(-1)) else {
+                return Ok(Value::Null);
+            };
+            let previous = arg.evaluate(previous, &EvalContext { window: None, ..*ctx })?;
+            match (to_f64(&arg.evaluate(record, ctx)?), to_f64(&previous)) {
+                (Some(current), Some(previous)) => ctx.number(current - previous),
+                _ => Ok(Value::Null),
+            }
+        }
+        "coalesce" => {
+            for arg in args {
+                let value = arg.evaluate(record, ctx)?;
+                if !value.is_null() {
+                    return Ok(value);
+                }
+            }
+            Ok(Value::Null)
+        }
+        _ => {
+            let function = ctx.functions.get(name).ok_or_else(|| {
+                ConvertError::InvalidConfig(format!("Unknown function '{name}'"))
+            })?;
+            let values = args
+                .iter()
+                .map(|arg| arg.evaluate(record, ctx))
+                .collect::<Result<Vec<_>>>()?;
+            function.call(&values)
+        }
+    }
+}
+
+fn single_arg(
+    name: &str,
+    args: &[Expr],
+    record: &Map<String, Value>,
+    ctx: &EvalContext<'_>,
+) -> Result<Value> {
+    if args.len() != 1 {
+        return Err(ConvertError::InvalidConfig(format!(
+            "{name}() expects 1 argument"
+        )));
+    }
+    args[0].evaluate(record, ctx)
+}
+
+/// Evaluate the arguments of a string function whose first argument is the
+/// text: `None` for a null text, numbers and booleans by their JSON form.
+fn text_args(
+    name: &str,
+    args: &[Expr],
+    count: std::ops::RangeInclusive<usize>,
+    record: &Map<String, Value>,
+    ctx: &EvalContext<'_>,
+) -> Result<(Option<String>, Vec<Value>)> {
+    if !count.contains(&args.len()) {
+        let expected = match (count.start(), count.end()) {
+            (1, 1) => "1 argument".to_string(),
+            (min, max) if min == max => format!("{min} arguments"),
+            (min, max) => format!("{min} to {max} arguments"),
+        };
+        return Err(ConvertError::InvalidConfig(format!(
+            "{name}() expects {expected}"
+        )));
+    }
+    let text = match args[0].evaluate(record, ctx)? {
+        Value::Null => None,
+        Value::String(text) => Some(text),
+        Value::Number(num) => Some(num.to_string()),
+        Value::Bool(flag) => Some(flag.to_string()),
+        _ => {
+            return Err(ConvertError::InvalidConfig(format!(
+                "{name}() expects a string"
+            )))
+        }
+    };
+    let rest = args[1..]
+        .iter()
+        .map(|arg| arg.evaluate(record, ctx))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((text, rest))
+}
+
+/// The `n` of `lag(field, n)` or `lead(field, n)`, 1 when left out. It must
+/// be a number literal, so the records to keep are known up front.
+fn window_offset(name: &str, args: &[Expr]) -> Result<usize> {
+    match args {
+        [_] => Ok(1),
+        [_, Expr::Literal(n)] => {
+            let n = index_arg(name, "n", n)?;
+            if n == 0 || n > MAX_WINDOW_OFFSET {
+                return Err(ConvertError::InvalidConfig(format!(
+                    "{name}() expects 'n' to be between 1 and {MAX_WINDOW_OFFSET}"
+                )));
+            }
+            Ok(n)
+        }
+        [_, _] => Err(ConvertError::InvalidConfig(format!(
+            "{name}() expects 'n' to be a number literal"
+        ))),
+        _ => Err(ConvertError::InvalidConfig(format!(
+            "{name}() expects 1 to 2 arguments"
+        ))),
+    }
+}
+
+fn string_arg<'v>(name: &str, param: &str, value: &'v Value) -> Result<&'v str> {
+    value.as_str().ok_or_else(|| {
+        ConvertError::InvalidConfig(format!("{name}() expects '{param}' to be a string"))
+    })
+}
+
+fn index_arg(name: &str, param: &str, value: &Value) -> Result<usize> {
+    to_f64(value)
+        .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+        .map(|n| n as usize)
+        .ok_or_else(|| {
+            ConvertError::InvalidConfig(format!(
+                "{name}() expects '{param}' to be a non-negative integer"
+            ))
+        })
+}
+
+/// The `record_element` records of an XML document, read as XML input is.
+fn parse_xml(text: &str, record_element: &str) -> Result<Value> {
+    check_well_formed(text)?;
+    let config = XmlConfig {
+        record_element: record_element.to_string(),
+        ..XmlConfig::default()
+    };
+    let mut parser = XmlParser::new(config, text.len());
+    let mut ndjson = parser.push_to_ndjson(text.as_bytes())?;
+    ndjson.extend(parser.finish()?);
+    ndjson
+        .split(|&b| b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).map_err(|e| ConvertError::JsonParse(e.to_string())))
+        .collect::<Result<Vec<Value>>>()
+        .map(Value::Array)
+}
+
+/// Fail on mismatched or unclosed tags, which the record parser would skip
+/// as an incomplete record.
+fn check_well_formed(text: &str) -> Result<()> {
+    let mut reader = quick_xml::Reader::from_str(text);
+    let mut depth = 0usize;
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Start(_)) => depth += 1,
+            Ok(quick_xml::events::Event::End(_)) => depth = depth.saturating_sub(1),
+            Ok(quick_xml::events::Event::Eof) if depth == 0 => return Ok(()),
+            Ok(quick_xml::events::Event::Eof) => {
+                return Err(ConvertError::XmlParse("parse_xml(): unclosed element".to_string()))
+            }
+            Ok(_) => {}
+            Err(e) => return Err(ConvertError::XmlParse(format!("parse_xml(): {e}"))),
+        }
+    }
+}
+
+/// The values of a delimiter-packed cell such as `S;M;L`, with CSV quoting,
+/// as an array of strings. Lines after the first add their values too.
+fn parse_csv(text: &str, delimiter: u8) -> Result<Value> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(text.as_bytes());
+    let mut values = Vec::new();
+    for row in reader.records() {
+        let row = row.map_err(|e| ConvertError::CsvParse(e.to_string()))?;
+        values.extend(row.iter().map(|value| Value::String(value.to_string())));
+    }
+    Ok(Value::Array(values))
+}
+
+/// Decode standard or URL-safe base64, padded or not, ignoring whitespace.
+fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    let compact: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    let alphabet = if compact.iter().any(|b| matches!(b, b'-' | b'_')) {
+        &base64::alphabet::URL_SAFE
+    } else {
+        &base64::alphabet::STANDARD
+    };
+    let config = GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+    GeneralPurpose::new(alphabet, config)
+        .decode(&compact)
+        .map_err(|e| ConvertError::InvalidConfig(format!("base64_decode(): {e}")))
+}
+
+/// Percent-encode everything but RFC 3986 unreserved characters, so the
+/// result is safe in any part of a URL.
+fn url_encode(text: &str) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push('%');
+            encoded.push(DIGITS[usize::from(byte >> 4)] as char);
+            encoded.push(DIGITS[usize::from(byte & 0xf)] as char);
+        }
+    }
+    encoded
+}
+
+/// Decode `%XX` escapes and, as in query strings, `+` as a space. Malformed
+/// escapes are kept as they are and invalid UTF-8 becomes U+FFFD.
+fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let escape = bytes
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = escape {
+                    decoded.push(byte);
+                    i += 3;
+                    continue;
+                }
+                decoded.push(b'%');
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
 }
 
-#[derive(Debug, Clone, Copy)]
-enum BinaryOp {
-    Add,
-    Subtract,
-    Multiply,
-    Divide,
+/// The decoded value of the first `name` parameter in the query string of
+/// `url`; empty for a bare `?name`.
+fn url_param(url: &str, name: &str) -> Option<String> {
+    let url = url.split('#').next().unwrap_or_default();
+    let (_, query) = url.split_once('?')?;
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (url_decode(key) == name).then(|| url_decode(value))
+    })
 }
 
-impl Expr {
-    fn evaluate(&self, record: &Map<String, Value>) -> Result<Value> {
-        match self {
-            Expr::Literal(value) => Ok(value.clone()),
-            Expr::Field(name) => Ok(record.get(name).cloned().unwrap_or(Value::Null)),
-            Expr::UnaryNeg(expr) => {
-                let value = expr.evaluate(record)?;
-                let number = to_f64(&value).ok_or_else(|| {
-                    ConvertError::InvalidConfig("Unary '-' expects a numeric value".to_string())
-                })?;
-                Ok(Value::Number(Number::from_f64(-number).unwrap_or_else(|| Number::from(0))))
-            }
-            Expr::Binary { op, left, right } => {
-                let left_val = left.evaluate(record)?;
-                let right_val = right.evaluate(record)?;
-                let left_num = to_f64(&left_val).ok_or_else(|| {
-                    ConvertError::InvalidConfig("Binary operator expects numeric values".to_string())
-                })?;
-                let right_num = to_f64(&right_val).ok_or_else(|| {
-                    ConvertError::InvalidConfig("Binary operator expects numeric values".to_string())
-                })?;
-                let result = match op {
-                    BinaryOp::Add => left_num + right_num,
-                    BinaryOp::Subtract => left_num - right_num,
-                    BinaryOp::Multiply => left_num * right_num,
-                    BinaryOp::Divide => left_num / right_num,
-                };
-                Ok(Value::Number(Number::from_f64(result).unwrap_or_else(|| Number::from(0))))
+/// `text` transliterated to ASCII and lowercased, with each run of other
+/// characters replaced by one `separator`, e.g. `Crème Brûlée 2L` to
+/// `creme-brulee-2l`.
+fn slugify(text: &str, separator: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    let mut pending_separator = false;
+    for ch in deunicode::deunicode(text).chars() {
+        if ch.is_ascii_alphanumeric() {
+            if pending_separator && !slug.is_empty() {
+                slug.push_str(separator);
             }
-            Expr::Function { name, args } => evaluate_function(name, args, record),
+            pending_separator = false;
+            slug.push(ch.to_ascii_lowercase());
+        } else {
+            pending_separator = true;
         }
     }
+    slug
 }
 
-fn evaluate_function(name: &str, args: &[Expr], record: &Map<String, Value>) -> Result<Value> {
-    match name {
-        "concat" => {
-            let mut output = String::new();
-            for arg in args {
-                let value = arg.evaluate(record)?;
-                match value {
-                    Value::Null => {}
-                    Value::String(s) => output.push_str(&s),
-                    other => output.push_str(&other.to_string()),
-                }
-            }
-            Ok(Value::String(output))
+/// Elements that start a new line or cell when rendered; their tags become
+/// a space so the words on either side stay apart.
+const HTML_BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "br", "dd", "div", "dl", "dt", "footer", "h1",
+    "h2", "h3", "h4", "h5", "h6", "header", "hr", "li", "ol", "p", "pre", "section", "table",
+    "tbody", "td", "tfoot", "th", "thead", "tr", "ul",
+];
+
+/// Remove tags, comments and `<script>`/`<style>` content from `text`, then
+/// collapse whitespace. Entities are left for `decode_html_entities`. A `<`
+/// that doesn't start a tag, as in `a < b`, is kept.
+fn strip_html(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('<') {
+        stripped.push_str(&rest[..pos]);
+        let tag = &rest[pos..];
+        if let Some(comment) = tag.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
         }
-        "lower" => {
-            let value = single_arg(name, args, record)?;
-            let text = value.as_str().ok_or_else(|| {
-                ConvertError::InvalidConfig("lower() expects a string".to_string())
-            })?;
-            Ok(Value::String(text.to_lowercase()))
+        let starts_tag = tag[1..]
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_alphabetic() || matches!(ch, '/' | '!' | '?'));
+        let Some(end) = starts_tag.then(|| html_tag_end(tag)).flatten() else {
+            stripped.push('<');
+            rest = &tag[1..];
+            continue;
+        };
+        let element: String = tag[1..end]
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|ch| ch.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        rest = &tag[end + 1..];
+        if HTML_BLOCK_ELEMENTS.contains(&element.as_str()) {
+            stripped.push(' ');
         }
-        "upper" => {
-            let value = single_arg(name, args, record)?;
-            let text = value.as_str().ok_or_else(|| {
-                ConvertError::InvalidConfig("upper() expects a string".to_string())
-            })?;
-            Ok(Value::String(text.to_uppercase()))
+        if matches!(element.as_str(), "script" | "style") && !tag.starts_with("</") {
+            // Skip to the closing tag, whatever the content looks like
+            let close = format!("</{element}");
+            rest = match rest.to_ascii_lowercase().find(&close) {
+                Some(start) => rest[start..].find('>').map_or("", |end| &rest[start + end + 1..]),
+                None => "",
+            };
         }
-        "trim" => {
-            let value = single_arg(name, args, record)?;
-            let text = value.as_str().ok_or_else(|| {
-                ConvertError::InvalidConfig("trim() expects a string".to_string())
-            })?;
-            Ok(Value::String(text.trim().to_string()))
+    }
+    stripped.push_str(rest);
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Position of the `>` closing the tag at the start of `tag`, skipping
+/// quoted attribute values.
+fn html_tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, ch) in tag.char_indices().skip(1) {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
         }
-        "coalesce" => {
-            for arg in args {
-                let value = arg.evaluate(record)?;
-                if !value.is_null() {
-                    return Ok(value);
+    }
+    None
+}
+
+/// Named entities decoded besides numeric ones: XML's, Latin-1 and common
+/// typography.
+const HTML_ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'), ("lt", '<'), ("gt", '>'), ("quot", '"'), ("apos", '\''),
+    ("nbsp", '\u{a0}'), ("iexcl", '¡'), ("cent", '¢'), ("pound", '£'), ("curren", '¤'),
+    ("yen", '¥'), ("brvbar", '¦'), ("sect", '§'), ("uml", '¨'), ("copy", '©'), ("ordf", 'ª'),
+    ("laquo", '«'), ("not", '¬'), ("shy", '\u{ad}'), ("reg", '®'), ("macr", '¯'), ("deg", '°'),
+    ("plusmn", '±'), ("sup2", '²'), ("sup3", '³'), ("acute", '´'), ("micro", 'µ'), ("para", '¶'),
+    ("middot", '·'), ("cedil", '¸'), ("sup1", '¹'), ("ordm", 'º'), ("raquo", '»'),
+    ("frac14", '¼'), ("frac12", '½'), ("frac34", '¾'), ("iquest", '¿'), ("Agrave", 'À'),
+    ("Aacute", 'Á'), ("Acirc", 'Â'), ("Atilde", 'Ã'), ("Auml", 'Ä'), ("Aring", 'Å'),
+    ("AElig", 'Æ'), ("Ccedil", 'Ç'), ("Egrave", 'È'), ("Eacute", 'É'), ("Ecirc", 'Ê'),
+    ("Euml", 'Ë'), ("Igrave", 'Ì'), ("Iacute", 'Í'), ("Icirc", 'Î'), ("Iuml", 'Ï'), ("ETH", 'Ð'),
+    ("Ntilde", 'Ñ'), ("Ograve", 'Ò'), ("Oacute", 'Ó'), ("Ocirc", 'Ô'), ("Otilde", 'Õ'),
+    ("Ouml", 'Ö'), ("times", '×'), ("Oslash", 'Ø'), ("Ugrave", 'Ù'), ("Uacute", 'Ú'),
+    ("Ucirc", 'Û'), ("Uuml", 'Ü'), ("Yacute", 'Ý'), ("THORN", 'Þ'), ("szlig", 'ß'),
+    ("agrave", 'à'), ("aacute", 'á'), ("acirc", 'â'), ("atilde", 'ã'), ("auml", 'ä'),
+    ("aring", 'å'), ("aelig", 'æ'), ("ccedil", 'ç'), ("egrave", 'è'), ("eacute", 'é'),
+    ("ecirc", 'ê'), ("euml", 'ë'), ("igrave", 'ì'), ("iacute", 'í'), ("icirc", 'î'), ("iuml", 'ï'),
+    ("eth", 'ð'), ("ntilde", 'ñ'), ("ograve", 'ò'), ("oacute", 'ó'), ("ocirc", 'ô'),
+    ("otilde", 'õ'), ("ouml", 'ö'), ("divide", '÷'), ("oslash", 'ø'), ("ugrave", 'ù'),
+    ("uacute", 'ú'), ("ucirc", 'û'), ("uuml", 'ü'), ("yacute", 'ý'), ("thorn", 'þ'), ("yuml", 'ÿ'),
+    ("OElig", 'Œ'), ("oelig", 'œ'), ("Scaron", 'Š'), ("scaron", 'š'), ("Yuml", 'Ÿ'),
+    ("ndash", '–'), ("mdash", '—'), ("lsquo", '‘'), ("rsquo", '’'), ("sbquo", '‚'),
+    ("ldquo", '“'), ("rdquo", '”'), ("bdquo", '„'), ("dagger", '†'), ("Dagger", '‡'),
+    ("bull", '•'), ("hellip", '…'), ("permil", '‰'), ("prime", '′'), ("lsaquo", '‹'),
+    ("rsaquo", '›'), ("euro", '€'), ("trade", '™'), ("larr", '←'), ("rarr", '→'),
+    ("minus", '−'), ("le", '≤'), ("ge", '≥'), ("ne", '≠'), ("infin", '∞'),
+];
+
+/// Decode named (see `HTML_ENTITIES`) and numeric character references.
+/// Unknown or malformed ones are kept as they are.
+fn decode_html_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find('&') {
+        decoded.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]).and_then(|name| {
+            let ch = match name.strip_prefix('#') {
+                Some(number) => {
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => number.parse().ok(),
+                    };
+                    code.and_then(char::from_u32)
                 }
+                None => HTML_ENTITIES.iter().find(|(entity, _)| *entity == name).map(|(_, ch)| *ch),
+            }?;
+            Some((ch, name.len() + 2))
+        });
+        match entity {
+            Some((ch, len)) => {
+                decoded.push(ch);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
             }
-            Ok(Value::Null)
         }
-        _ => Err(ConvertError::InvalidConfig(format!(
-            "Unknown function '{name}'"
-        ))),
     }
+    decoded.push_str(rest);
+    decoded
 }
 
-fn single_arg(name: &str, args: &[Expr], record: &Map<String, Value>) -> Result<Value> {
-    if args.len() != 1 {
+/// Mean radius of the Earth used by `haversine()`.
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+/// A `lat,lon` pair such as `48.85,2.35`, also separated by `;` or
+/// whitespace. `None` unless both parse and lie in range.
+fn parse_coordinates(text: &str) -> Option<(f64, f64)> {
+    let text = text.trim();
+    let (lat, lon) = text
+        .split_once([',', ';'])
+        .or_else(|| text.split_once(char::is_whitespace))?;
+    let lat: f64 = lat.trim().parse().ok()?;
+    let lon: f64 = lon.trim().parse().ok()?;
+    (lat.abs() <= 90.0 && lon.abs() <= 180.0).then_some((lat, lon))
+}
+
+/// Great-circle distance in kilometres between two points given in degrees.
+fn haversine_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    let mut text = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        text.push(DIGITS[usize::from(byte >> 4)] as char);
+        text.push(DIGITS[usize::from(byte & 0xf)] as char);
+    }
+    text
+}
+
+/// Epoch milliseconds from a number or numeric string; `None` for null.
+fn timestamp_arg(name: &str, value: &Value) -> Result<Option<i64>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    to_f64(value)
+        .filter(|n| n.is_finite())
+        .map(|n| Some(n.floor() as i64))
+        .ok_or_else(|| {
+            ConvertError::InvalidConfig(format!(
+                "{name}() expects a timestamp in milliseconds"
+            ))
+        })
+}
+
+/// Reject strftime formats chrono can't use; formatting with one panics.
+fn check_date_format(name: &str, format: &str) -> Result<()> {
+    if format == "iso8601" || format == "rfc2822" {
+        return Ok(());
+    }
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
         return Err(ConvertError::InvalidConfig(format!(
-            "{name}() expects 1 argument"
+            "{name}() got an invalid date format '{format}'"
         )));
     }
-    args[0].evaluate(record)
+    Ok(())
+}
+
+/// Parse `text` with a strftime format, `"iso8601"` or `"rfc2822"`. Dates and
+/// times without an offset are taken as UTC; a date alone as its midnight.
+fn parse_date(text: &str, format: &str) -> Option<i64> {
+    let parsed = match format {
+        "iso8601" => DateTime::parse_from_rfc3339(text).ok(),
+        "rfc2822" => DateTime::parse_from_rfc2822(text).ok(),
+        _ => DateTime::parse_from_str(text, format).ok(),
+    };
+    if let Some(parsed) = parsed {
+        return Some(parsed.timestamp_millis());
+    }
+    if let Ok(parsed) = NaiveDateTime::parse_from_str(text, format) {
+        return Some(parsed.and_utc().timestamp_millis());
+    }
+    NaiveDate::parse_from_str(text, format)
+        .ok()
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc().timestamp_millis())
+}
+
+/// Format epoch milliseconds in UTC; `"iso8601"` gives e.g. `2024-03-01T12:00:00.000Z`.
+fn format_date(name: &str, ms: i64, format: &str) -> Result<String> {
+    let date = DateTime::from_timestamp_millis(ms).ok_or_else(|| {
+        ConvertError::InvalidConfig(format!("{name}() got an out of range timestamp {ms}"))
+    })?;
+    Ok(match format {
+        "iso8601" => date.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "rfc2822" => date.to_rfc2822(),
+        _ => date.format(format).to_string(),
+    })
+}
+
+/// Add `amount` units to epoch milliseconds. Months and years follow the
+/// calendar, clamping the day, so Jan 31 plus a month is the end of February.
+fn date_add(ms: i64, amount: i64, unit: &str) -> Result<i64> {
+    let out_of_range =
+        || ConvertError::InvalidConfig("date_add() result is out of range".to_string());
+    let unit_ms: i64 = match unit {
+        "millisecond" | "milliseconds" | "ms" => 1,
+        "second" | "seconds" => 1_000,
+        "minute" | "minutes" => 60_000,
+        "hour" | "hours" => 3_600_000,
+        "day" | "days" => 86_400_000,
+        "week" | "weeks" => 604_800_000,
+        "month" | "months" | "year" | "years" => {
+            let months = if unit.starts_with("year") {
+                amount.checked_mul(12).ok_or_else(out_of_range)?
+            } else {
+                amount
+            };
+            let date = DateTime::from_timestamp_millis(ms).ok_or_else(out_of_range)?;
+            let step = Months::new(u32::try_from(months.unsigned_abs()).map_err(|_| out_of_range())?);
+            let date = if months < 0 {
+                date.checked_sub_months(step)
+            } else {
+                date.checked_add_months(step)
+            };
+            return date.map(|date| date.timestamp_millis()).ok_or_else(out_of_range);
+        }
+        _ => {
+            return Err(ConvertError::InvalidConfig(format!(
+                "date_add() got unknown unit '{unit}' (expected ms, second, minute, hour, day, week, month or year)"
+            )))
+        }
+    };
+    amount
+        .checked_mul(unit_ms)
+        .and_then(|delta| ms.checked_add(delta))
+        .ok_or_else(out_of_range)
 }
 
 fn to_f64(value: &Value) -> Option<f64> {
@@ -478,8 +2595,9 @@ fn coerce_value(value: &Value, spec: &CoerceSpec) -> Result<Value> {
         }
         CoerceSpec::F64 => {
             let number = to_f64(value)
+                .and_then(Number::from_f64)
                 .ok_or_else(|| ConvertError::InvalidConfig("Unable to coerce to f64".to_string()))?;
-            Ok(Value::Number(Number::from_f64(number).unwrap_or_else(|| Number::from(0))))
+            Ok(Value::Number(number))
         }
         CoerceSpec::Bool => {
             let bool_value = match value {
@@ -518,6 +2636,15 @@ fn coerce_value(value: &Value, spec: &CoerceSpec) -> Result<Value> {
                 }
             }
         }
+        CoerceSpec::Json => match value {
+            Value::String(text) => serde_json::from_str(text)
+                .map_err(|e| ConvertError::InvalidConfig(format!("Unable to parse embedded JSON: {e}"))),
+            other => Ok(other.clone()),
+        },
+        CoerceSpec::Stringify => match value {
+            Value::Null => Ok(Value::Null),
+            other => Ok(Value::String(other.to_string())),
+        },
     }
 }
 
@@ -544,6 +2671,15 @@ enum Token {
     Minus,
     Star,
     Slash,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    AndAnd,
+    OrOr,
+    Bang,
 }
 
 fn parse_expression(input: &str) -> std::result::Result<Expr, String> {
@@ -604,8 +2740,41 @@ impl<'a> Lexer<'a> {
                     tokens.push(Token::Slash);
                     self.advance();
                 }
-                '"' => {
-                    tokens.push(Token::StringLiteral(self.read_string()?));
+                '=' => {
+                    self.advance();
+                    if !self.advance_if('=') {
+                        return Err("Expected '==' (use '==' to compare)".to_string());
+                    }
+                    tokens.push(Token::EqualEqual);
+                }
+                '!' => {
+                    self.advance();
+                    tokens.push(if self.advance_if('=') { Token::BangEqual } else { Token::Bang });
+                }
+                '<' => {
+                    self.advance();
+                    tokens.push(if self.advance_if('=') { Token::LessEqual } else { Token::Less });
+                }
+                '>' => {
+                    self.advance();
+                    tokens.push(if self.advance_if('=') { Token::GreaterEqual } else { Token::Greater });
+                }
+                '&' => {
+                    self.advance();
+                    if !self.advance_if('&') {
+                        return Err("Expected '&&'".to_string());
+                    }
+                    tokens.push(Token::AndAnd);
+                }
+                '|' => {
+                    self.advance();
+                    if !self.advance_if('|') {
+                        return Err("Expected '||'".to_string());
+                    }
+                    tokens.push(Token::OrOr);
+                }
+                '"' | '\'' => {
+                    tokens.push(Token::StringLiteral(self.read_string(ch)?));
                 }
                 '0'..='9' => {
                     tokens.push(Token::Number(self.read_number()?));
@@ -632,12 +2801,22 @@ impl<'a> Lexer<'a> {
         self.current = self.chars.next();
     }
 
-    fn read_string(&mut self) -> std::result::Result<String, String> {
+    /// Advance past the current character if it is `expected`.
+    fn advance_if(&mut self, expected: char) -> bool {
+        if self.current == Some(expected) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn read_string(&mut self, quote: char) -> std::result::Result<String, String> {
         self.advance();
         let mut out = String::new();
         while let Some(ch) = self.current {
             match ch {
-                '"' => {
+                ch if ch == quote => {
                     self.advance();
                     return Ok(out);
                 }
@@ -706,7 +2885,56 @@ impl Parser {
     }
 
     fn parse_expression(&mut self) -> std::result::Result<Expr, String> {
-        self.parse_add_sub()
+        self.parse_or()
+    }
+
+    fn parse_or(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while let Some(Token::OrOr) = self.peek() {
+            self.advance();
+            let right = self.parse_and()?;
+            expr = Expr::Binary {
+                op: BinaryOp::Or,
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> std::result::Result<Expr, String> {
+        let mut expr = self.parse_comparison()?;
+        while let Some(Token::AndAnd) = self.peek() {
+            self.advance();
+            let right = self.parse_comparison()?;
+            expr = Expr::Binary {
+                op: BinaryOp::And,
+                left: Box::new(expr),
+                right: Box::new(right),
+            };
+        }
+        Ok(expr)
+    }
+
+    /// Comparisons don't chain: `a < b < c` is rejected.
+    fn parse_comparison(&mut self) -> std::result::Result<Expr, String> {
+        let expr = self.parse_add_sub()?;
+        let op = match self.peek() {
+            Some(Token::EqualEqual) => BinaryOp::Equal,
+            Some(Token::BangEqual) => BinaryOp::NotEqual,
+            Some(Token::Less) => BinaryOp::Less,
+            Some(Token::LessEqual) => BinaryOp::LessEqual,
+            Some(Token::Greater) => BinaryOp::Greater,
+            Some(Token::GreaterEqual) => BinaryOp::GreaterEqual,
+            _ => return Ok(expr),
+        };
+        self.advance();
+        let right = self.parse_add_sub()?;
+        Ok(Expr::Binary {
+            op,
+            left: Box::new(expr),
+            right: Box::new(right),
+        })
     }
 
     fn parse_add_sub(&mut self) -> std::result::Result<Expr, String> {
@@ -753,6 +2981,11 @@ impl Parser {
             let expr = self.parse_unary()?;
             return Ok(Expr::UnaryNeg(Box::new(expr)));
         }
+        if let Some(Token::Bang) = self.peek() {
+            self.advance();
+            let expr = self.parse_unary()?;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
         self.parse_primary()
     }
 
@@ -820,3 +3053,753 @@ impl Parser {
         self.position += 1;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn plan(config: Value) -> TransformPlan {
+        let input: TransformConfigInput = serde_json::from_value(config).unwrap();
+        TransformPlan::compile(input).unwrap()
+    }
+
+    #[test]
+    fn coerce_json_parses_embedded_payloads() {
+        let plan = plan(json!({
+            "fields": [{ "targetFieldName": "payload", "coerce": { "type": "json" } }]
+        }));
+
+        let output = plan
+            .apply_to_value(&json!({ "payload": "{\"a\":[1,2]}" }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, json!({ "payload": { "a": [1, 2] } }));
+
+        let output = plan
+            .apply_to_value(&json!({ "payload": { "already": true } }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, json!({ "payload": { "already": true } }));
+
+        assert!(plan.apply_to_value(&json!({ "payload": "{not json" })).is_err());
+    }
+
+    #[test]
+    fn coerce_stringify_serializes_values() {
+        let plan = plan(json!({
+            "fields": [{ "targetFieldName": "payload", "coerce": { "type": "stringify" } }],
+            "onMissingField": "null"
+        }));
+
+        let output = plan
+            .apply_to_value(&json!({ "payload": { "a": [1, 2] } }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, json!({ "payload": "{\"a\":[1,2]}" }));
+
+        let output = plan.apply_to_value(&json!({})).unwrap().unwrap();
+        assert_eq!(output, json!({ "payload": null }));
+    }
+
+    #[test]
+    fn per_field_policies_override_global_policies() {
+        let plan = plan(json!({
+            "fields": [
+                { "targetFieldName": "id", "coerce": { "type": "i64" } },
+                { "targetFieldName": "score", "coerce": { "type": "i64" }, "onCoerceError": "null" },
+                { "targetFieldName": "note", "onMissingField": "drop" }
+            ],
+            "onMissingField": "error",
+            "onCoerceError": "error"
+        }));
+
+        let output = plan
+            .apply_to_value(&json!({ "id": "7", "score": "n/a" }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, json!({ "id": 7, "score": null }));
+
+        assert!(plan.apply_to_value(&json!({ "id": "x", "score": "1" })).is_err());
+    }
+
+    #[test]
+    fn plan_config_roundtrips_through_json() {
+        let original = plan(json!({
+            "mode": "augment",
+            "fields": [
+                { "targetFieldName": "total", "compute": "price * (qty + 1)", "coerce": { "type": "f64" } },
+                { "targetFieldName": "name", "originFieldName": "full_name", "onMissingField": "null" }
+            ]
+        }));
+
+        let saved = serde_json::to_string(&original.to_config()).unwrap();
+        let config: TransformPlanConfig = serde_json::from_str(&saved).unwrap();
+        assert_eq!(config.version(), TRANSFORM_PLAN_VERSION);
+        let reloaded = TransformPlan::from_config(config).unwrap();
+
+        let record = json!({ "price": 2.5, "qty": 3 });
+        assert_eq!(
+            original.apply_to_value(&record).unwrap(),
+            reloaded.apply_to_value(&record).unwrap()
+        );
+        assert_eq!(
+            reloaded.apply_to_value(&record).unwrap().unwrap()["total"],
+            json!(10.0)
+        );
+    }
+
+    #[test]
+    fn plan_config_rejects_future_versions() {
+        let mut config = serde_json::to_value(
+            plan(json!({ "fields": [{ "targetFieldName": "id" }] })).to_config(),
+        )
+        .unwrap();
+        config["version"] = json!(TRANSFORM_PLAN_VERSION + 1);
+        let config: TransformPlanConfig = serde_json::from_value(config).unwrap();
+        assert!(TransformPlan::from_config(config).is_err());
+    }
+
+    #[test]
+    fn custom_functions_are_callable_from_expressions() {
+        let mut functions = FunctionRegistry::new();
+        functions
+            .register("vat", |args: &[Value]| {
+                let price = args.first().and_then(Value::as_f64).unwrap_or(0.0);
+                let rate = match args.get(1).and_then(Value::as_str) {
+                    Some("DE") => 0.19,
+                    _ => 0.2,
+                };
+                Ok(json!(price * rate))
+            })
+            .unwrap();
+        assert!(functions.register("concat", |_: &[Value]| Ok(Value::Null)).is_err());
+
+        let plan = plan(json!({
+            "fields": [{ "targetFieldName": "vat", "compute": "vat(price, country)" }]
+        }))
+        .with_functions(functions);
+
+        let output = plan
+            .apply_to_value(&json!({ "price": 100, "country": "DE" }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, json!({ "vat": 19.0 }));
+    }
+
+    #[test]
+    fn non_emitted_fields_feed_later_fields_only() {
+        let plan = plan(json!({
+            "fields": [
+                { "targetFieldName": "subtotal", "compute": "price * qty", "emit": false },
+                { "targetFieldName": "total", "compute": "subtotal + shipping" },
+                { "targetFieldName": "subtotal_copy", "originFieldName": "subtotal" }
+            ]
+        }));
+
+        let output = plan
+            .apply_to_value(&json!({ "price": 2, "qty": 3, "shipping": 1 }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(output, json!({ "total": 7.0, "subtotal_copy": 6.0 }));
+
+        let reloaded = TransformPlan::from_config(plan.to_config()).unwrap();
+        let output = reloaded
+            .apply_to_value(&json!({ "price": 2, "qty": 3, "shipping": 1 }))
+            .unwrap()
+            .unwrap();
+        assert!(output.get("subtotal").is_none());
+    }
+
+    #[test]
+    fn arithmetic_propagates_null_and_applies_nan_policy() {
+        let record = json!({ "a": 1, "zero": 0, "missing": null });
+        let compute = |expr: &str, nan_policy: Option<&str>| {
+            let mut config = json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            });
+            if let Some(policy) = nan_policy {
+                config["nanPolicy"] = json!(policy);
+            }
+            plan(config).apply_to_value(&record).map(|v| v.unwrap()["x"].clone())
+        };
+
+        assert_eq!(compute("a + missing", None).unwrap(), Value::Null);
+        assert_eq!(compute("-missing * 2", None).unwrap(), Value::Null);
+        assert_eq!(compute("a / zero", None).unwrap(), Value::Null);
+        assert_eq!(compute("a / zero", Some("zero")).unwrap(), json!(0));
+        assert!(compute("a / zero", Some("error")).is_err());
+        assert_eq!(compute("a / 2", Some("error")).unwrap(), json!(0.5));
+    }
+
+    #[test]
+    fn version_one_plans_keep_zero_nan_semantics() {
+        let mut config = serde_json::to_value(
+            plan(json!({ "fields": [{ "targetFieldName": "x", "compute": "a / 0" }] })).to_config(),
+        )
+        .unwrap();
+        config["version"] = json!(1);
+        config.as_object_mut().unwrap().remove("nanPolicy");
+
+        let config: TransformPlanConfig = serde_json::from_value(config).unwrap();
+        let plan = TransformPlan::from_config(config).unwrap();
+        let output = plan.apply_to_value(&json!({ "a": 1 })).unwrap().unwrap();
+        assert_eq!(output, json!({ "x": 0 }));
+    }
+
+    #[test]
+    fn stop_when_halts_after_matching_record() {
+        let mut engine = TransformEngine::new(plan(json!({
+            "mode": "augment",
+            "stopWhen": { "field": "id", "equals": 2 }
+        })));
+
+        let result = engine.push(b"{\"id\":1}\n{\"id\":\"2\"}\n{\"id\":3}\n{\"id\"").unwrap();
+        assert_eq!(result.output, b"{\"id\":1}\n{\"id\":\"2\"}\n");
+        assert!(engine.is_stopped());
+        assert_eq!(engine.matched_record(), Some(&json!({ "id": "2" })));
+
+        assert!(engine.push(b":4}\n").unwrap().output.is_empty());
+        assert!(engine.finish().unwrap().output.is_empty());
+    }
+
+    #[test]
+    fn filter_only_plans_pass_matching_lines_through_verbatim() {
+        let mut engine = TransformEngine::new(plan(json!({
+            "mode": "augment",
+            "filter": { "field": "status", "equals": "ok" }
+        })));
+
+        let input = b"{ \"status\" : \"ok\", \"n\": 1.50 }\n{\"status\":\"bad\"}\n{\"n\":2}\n";
+        let result = engine.push(input).unwrap();
+        assert_eq!(result.output, b"{ \"status\" : \"ok\", \"n\": 1.50 }\n");
+        assert_eq!(result.records, 1);
+
+        assert!(engine.push(b"{\"status\":\n").is_err());
+        assert!(engine.push(b"{\"status\":\"ok\",\"n\":tru}\n").is_err());
+    }
+
+    #[test]
+    fn copy_only_plans_map_raw_bytes_like_the_value_path() {
+        let input = concat!(
+            "{\"id\": 1, \"name\": \"Ada\", \"tags\": [1, 2], \"zone\": null, \"t\\u00e9\": true}\n",
+            "{\"name\":\"Bob\",\"id\":2,\"id\":3}\n",
+        );
+        for mode in ["replace", "augment"] {
+            let config = json!({
+                "mode": mode,
+                "fields": [
+                    { "targetFieldName": "who", "originFieldName": "name" },
+                    { "targetFieldName": "id" },
+                    { "targetFieldName": "zone", "defaultValue": "eu" },
+                    { "targetFieldName": "tags", "onMissingField": "drop" },
+                    { "targetFieldName": "té", "onMissingField": "null" }
+                ],
+                "filter": { "field": "id", "gt": 0 }
+            });
+            let mut engine = TransformEngine::new(plan(config.clone()));
+            assert!(engine.raw_mapper.is_some());
+            let result = engine.push(input.as_bytes()).unwrap();
+
+            let plan = plan(config);
+            let expected: Vec<String> = input
+                .lines()
+                .map(|line| {
+                    let record = plan.apply_to_value(&serde_json::from_str(line).unwrap()).unwrap();
+                    serde_json::to_string(&record.unwrap()).unwrap()
+                })
+                .collect();
+            let output = String::from_utf8(result.output).unwrap();
+            let output: Vec<&str> = output.lines().collect();
+            assert_eq!(output[0], expected[0].replace("[1,2]", "[1, 2]"), "{mode}");
+            assert_eq!(output[1], expected[1], "{mode}");
+        }
+
+        let mut engine = TransformEngine::new(plan(json!({
+            "fields": [{ "targetFieldName": "id", "required": true }]
+        })));
+        let error = engine.push(b"{\"name\":\"Ada\"}\n").unwrap_err();
+        assert!(error.to_string().contains("Missing required field 'id'"));
+        assert!(TransformEngine::new(plan(json!({
+            "fields": [{ "targetFieldName": "id", "coerce": { "type": "i64" } }]
+        })))
+        .raw_mapper
+        .is_none());
+    }
+
+    #[test]
+    fn window_functions_read_neighbouring_records() {
+        let mut engine = TransformEngine::new(plan(json!({
+            "fields": [
+                { "targetFieldName": "t" },
+                { "targetFieldName": "delta", "compute": "delta(bytes)" },
+                { "targetFieldName": "prev", "compute": "lag(t, 2)" },
+                { "targetFieldName": "next", "compute": "lead(t)" }
+            ],
+            "filter": "host == 'a'",
+            "onMissingField": "null"
+        })));
+
+        // Records of other hosts are not neighbours
+        let result = engine.push(b"{\"t\":1,\"host\":\"a\",\"bytes\":10}\n{\"t\":2,\"host\":\"b\",\"bytes\":99}\n").unwrap();
+        assert!(result.output.is_empty());
+        let result = engine.push(b"{\"t\":3,\"host\":\"a\",\"bytes\":15}\n{\"t\":4,\"host\":\"a\",\"bytes\":\"n/a\"}").unwrap();
+        assert_eq!(result.output, b"{\"delta\":null,\"next\":3,\"prev\":null,\"t\":1}\n");
+        let result = engine.finish().unwrap();
+        assert_eq!(result.records, 2);
+        assert_eq!(
+            String::from_utf8(result.output).unwrap(),
+            "{\"delta\":5.0,\"next\":4,\"prev\":null,\"t\":3}\n{\"delta\":null,\"next\":null,\"prev\":1,\"t\":4}\n"
+        );
+
+        // A single record has no neighbours
+        let compute = |expr: &str| plan(json!({
+            "fields": [{ "targetFieldName": "x", "compute": expr }],
+            "onMissingField": "null"
+        }));
+        assert_eq!(compute("lag(t)").apply_to_value(&json!({ "t": 1 })).unwrap(), Some(json!({ "x": null })));
+
+        for invalid in ["lag(t, n)", "lag(t, 0)", "lead(t, 0)", "lead(t, 1001)", "delta(t, 1)", "lag()"] {
+            let config = json!({ "fields": [{ "targetFieldName": "x", "compute": invalid }] });
+            assert!(TransformPlan::compile(serde_json::from_value(config).unwrap()).is_err(), "{invalid}");
+        }
+        let config = json!({ "mode": "augment", "filter": "delta(t) > 0" });
+        assert!(TransformPlan::compile(serde_json::from_value(config).unwrap()).is_err());
+    }
+
+    #[test]
+    fn explode_writes_a_record_per_element() {
+        let mut engine = TransformEngine::new(plan(json!({
+            "mode": "augment",
+            "explode": "offers.offer",
+            "fields": [{ "targetFieldName": "price", "coerce": { "type": "f64" } }],
+            "filter": "price > 1"
+        })));
+
+        let input = concat!(
+            "{\"id\":\"1\",\"offers\":{\"offer\":[{\"price\":\"1\"},{\"price\":\"2\"},{\"price\":\"3\"}]}}\n",
+            "{\"id\":\"2\",\"offers\":{\"@count\":\"1\",\"offer\":{\"price\":\"4\",\"id\":\"o4\"}}}\n",
+            "{\"id\":\"3\",\"price\":\"5\"}\n"
+        );
+        let result = engine.push(input.as_bytes()).unwrap();
+        assert_eq!(
+            String::from_utf8(result.output).unwrap(),
+            concat!(
+                "{\"id\":\"1\",\"price\":2.0}\n",
+                "{\"id\":\"1\",\"price\":3.0}\n",
+                "{\"id\":\"o4\",\"offers\":{\"@count\":\"1\"},\"price\":4.0}\n",
+                "{\"id\":\"3\",\"price\":5.0}\n"
+            )
+        );
+        assert_eq!(result.records, 4);
+
+        let tags = plan(json!({ "mode": "augment", "explode": "tags" }));
+        assert_eq!(
+            tags.apply_all(&json!({ "id": 1, "tags": ["a", "b"] })).unwrap(),
+            vec![json!({ "id": 1, "tags": "a" }), json!({ "id": 1, "tags": "b" })]
+        );
+        assert!(tags.apply_to_value(&json!({ "tags": ["a", "b"] })).is_err());
+        assert_eq!(
+            tags.apply_to_value(&json!({ "tags": [] })).unwrap(),
+            Some(json!({}))
+        );
+
+        let reloaded = TransformPlan::from_config(tags.to_config()).unwrap();
+        assert_eq!(reloaded.apply_all(&json!({ "tags": [1, 2] })).unwrap().len(), 2);
+
+        let input: TransformConfigInput =
+            serde_json::from_value(json!({ "mode": "augment", "explode": "offers..offer" })).unwrap();
+        assert!(TransformPlan::compile(input).is_err());
+    }
+
+    #[test]
+    fn filter_applies_before_field_mapping() {
+        let plan = plan(json!({
+            "fields": [{ "targetFieldName": "id", "required": true }],
+            "filter": { "field": "kind", "equals": "a" }
+        }));
+        assert_eq!(plan.apply_to_value(&json!({ "kind": "b" })).unwrap(), None);
+        assert_eq!(
+            plan.apply_to_value(&json!({ "kind": "a", "id": 1 })).unwrap(),
+            Some(json!({ "id": 1 }))
+        );
+    }
+
+    #[test]
+    fn filter_expressions_compare_and_combine() {
+        let keeps = |filter: &str, record: Value| {
+            plan(json!({ "mode": "augment", "filter": filter }))
+                .apply_to_value(&record)
+                .unwrap()
+                .is_some()
+        };
+
+        assert!(keeps("status == 'active'", json!({ "status": "active" })));
+        assert!(!keeps("status == 'active'", json!({ "status": "idle" })));
+        assert!(keeps("age >= 18 && !banned", json!({ "age": "36", "banned": false })));
+        assert!(!keeps("age >= 18 && !banned", json!({ "age": "12", "banned": false })));
+        assert!(keeps("age > 65 || vip == true", json!({ "age": 30, "vip": "true" })));
+        assert!(keeps("id != 3", json!({ "id": "4" })));
+        assert!(!keeps("id != 3", json!({ "id": "3.0" })));
+        assert!(keeps("name < \"b\"", json!({ "name": "ada" })));
+        assert!(keeps("missing == null", json!({})));
+        // Values that can't be ordered compare as null, which drops the record
+        assert!(!keeps("age > 18", json!({ "age": "" })));
+        assert!(!keeps("age > 18", json!({})));
+    }
+
+    #[test]
+    fn if_picks_a_branch_by_condition() {
+        let compute = |expr: &str, record: Value| {
+            plan(json!({ "fields": [{ "targetFieldName": "x", "compute": expr }] }))
+                .apply_to_value(&record)
+                .map(|v| v.unwrap()["x"].clone())
+        };
+
+        let tier = "if(price > 100, \"premium\", \"standard\")";
+        assert_eq!(compute(tier, json!({ "price": "150" })).unwrap(), json!("premium"));
+        assert_eq!(compute(tier, json!({ "price": 20 })).unwrap(), json!("standard"));
+        assert_eq!(compute(tier, json!({})).unwrap(), json!("standard"));
+        assert_eq!(
+            compute("if(qty == 0 || !qty, \"n/a\", total / qty)", json!({ "total": 9, "qty": 0 })).unwrap(),
+            json!("n/a")
+        );
+        assert!(compute("if(a, 1)", json!({ "a": 1 })).is_err());
+    }
+
+    #[test]
+    fn string_functions() {
+        let record = json!({
+            "sku": "AB-1234-xl",
+            "name": "Grüße",
+            "id": 42,
+            "ref": "order #9917 shipped",
+            "none": null
+        });
+        let compute = |expr: &str| {
+            plan(json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            }))
+                .apply_to_value(&record)
+                .map(|v| v.unwrap()["x"].clone())
+        };
+
+        assert_eq!(compute("substring(sku, 3, 4)").unwrap(), json!("1234"));
+        assert_eq!(compute("substring(name, 3)").unwrap(), json!("ße"));
+        assert_eq!(compute("substring(sku, 20, 2)").unwrap(), json!(""));
+        assert_eq!(compute("replace(sku, \"-\", \"\")").unwrap(), json!("AB1234xl"));
+        assert_eq!(compute("split(sku, \"-\", 2)").unwrap(), json!("xl"));
+        assert_eq!(compute("split(sku, \"-\", 3)").unwrap(), Value::Null);
+        assert_eq!(compute("pad_left(id, 6, \"0\")").unwrap(), json!("000042"));
+        assert_eq!(compute("concat(pad_right(name, 7), \"|\")").unwrap(), json!("Grüße  |"));
+        assert_eq!(compute("len(name)").unwrap(), json!(5));
+        assert_eq!(compute("regex_extract(ref, \"#(\\\\d+)\")").unwrap(), json!("9917"));
+        assert_eq!(compute("regex_extract(ref, \"[a-z]+$\")").unwrap(), json!("shipped"));
+        assert_eq!(compute("regex_extract(sku, \"(\\\\d)(\\\\d)\", 2)").unwrap(), json!("2"));
+        assert_eq!(compute("regex_extract(sku, \"z+\")").unwrap(), Value::Null);
+        assert_eq!(compute("len(none)").unwrap(), Value::Null);
+
+        assert!(compute("substring(sku, -1, 2)").is_err());
+        assert!(compute("pad_left(sku, 4, \"ab\")").is_err());
+        assert!(compute("regex_extract(sku, \"(\")").is_err());
+        assert!(compute("len(sku, 1)").is_err());
+    }
+
+    #[test]
+    fn date_functions() {
+        let record = json!({
+            "us": "03/01/2024",
+            "eu": "01.03.2024 12:00",
+            "iso": "2024-03-01T13:00:00+01:00",
+            "ts": 1709294400000_i64,
+            "jan": 1706659200000_i64
+        });
+        let compute = |expr: &str| {
+            plan(json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            }))
+            .apply_to_value(&record)
+            .map(|v| v.unwrap()["x"].clone())
+        };
+
+        assert_eq!(compute("parse_date(us, '%m/%d/%Y')").unwrap(), json!(1709251200000_i64));
+        assert_eq!(compute("parse_date(eu, '%d.%m.%Y %H:%M')").unwrap(), json!(1709294400000_i64));
+        assert_eq!(compute("parse_date(iso, 'iso8601')").unwrap(), json!(1709294400000_i64));
+        assert_eq!(compute("parse_date(us, '%Y-%m-%d')").unwrap(), Value::Null);
+        assert_eq!(
+            compute("format_date(coalesce(parse_date(eu, '%m/%d/%Y'), parse_date(eu, '%d.%m.%Y %H:%M')), 'iso8601')")
+                .unwrap(),
+            json!("2024-03-01T12:00:00.000Z")
+        );
+        assert_eq!(compute("format_date(ts, '%Y-%m-%d %H:%M')").unwrap(), json!("2024-03-01 12:00"));
+        assert_eq!(compute("format_date(missing, 'iso8601')").unwrap(), Value::Null);
+
+        assert_eq!(compute("date_add(ts, -90, 'minutes')").unwrap(), json!(1709289000000_i64));
+        assert_eq!(
+            compute("format_date(date_add(jan, 1, 'month'), '%Y-%m-%d')").unwrap(),
+            json!("2024-02-29")
+        );
+        assert_eq!(
+            compute("format_date(date_add(ts, -1, 'year'), '%Y-%m-%d')").unwrap(),
+            json!("2023-03-01")
+        );
+        assert!(compute("now()").unwrap().as_i64().unwrap() > 1709294400000);
+
+        assert!(compute("format_date(ts, '%Q')").is_err());
+        assert!(compute("date_add(ts, 1, 'fortnight')").is_err());
+        assert!(compute("date_add(ts, 1.5, 'day')").is_err());
+        assert!(compute("now(1)").is_err());
+    }
+
+    #[test]
+    fn parse_xml_expands_payloads() {
+        let compute = |expr: &str, record: Value| {
+            plan(json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            }))
+            .apply_to_value(&record)
+            .map(|v| v.unwrap()["x"].clone())
+        };
+
+        let message = json!({
+            "body": "<batch><order id=\"7\"><sku>A1</sku><qty>2</qty></order><order id=\"8\"><sku>B2</sku></order></batch>"
+        });
+        assert_eq!(
+            compute("parse_xml(body, 'order')", message.clone()).unwrap(),
+            json!([
+                { "@id": "7", "sku": "A1", "qty": "2" },
+                { "@id": "8", "sku": "B2" }
+            ])
+        );
+        assert_eq!(compute("parse_xml(body, 'invoice')", message).unwrap(), json!([]));
+        let text = json!({ "body": "<r><o>y</o><o a=\"1\">x</o></r>" });
+        assert_eq!(
+            compute("parse_xml(body, 'o')", text).unwrap(),
+            json!([{ "#text": "y" }, { "@a": "1", "#text": "x" }])
+        );
+        assert!(compute("parse_xml(body, 'o')", json!({ "body": "<r><o>x</r>" })).is_err());
+        assert!(compute("parse_xml(body, 'o')", json!({ "body": "<r><o>x</o>" })).is_err());
+        assert_eq!(compute("parse_xml(body, 'order')", json!({})).unwrap(), Value::Null);
+        assert!(compute("parse_xml(body, 'order')", json!({ "body": "<order><sku>A1</order>" })).is_err());
+        assert!(compute("parse_xml(body, '')", json!({ "body": "<order/>" })).is_err());
+    }
+
+    #[test]
+    fn parse_csv_splits_packed_cells() {
+        let compute = |expr: &str, record: Value| {
+            plan(json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            }))
+            .apply_to_value(&record)
+            .map(|v| v.unwrap()["x"].clone())
+        };
+
+        let record = json!({ "sizes": "S;M;L", "tags": "red,\"navy, dark\",", "n": 7 });
+        assert_eq!(compute("parse_csv(sizes, ';')", record.clone()).unwrap(), json!(["S", "M", "L"]));
+        assert_eq!(compute("parse_csv(tags)", record.clone()).unwrap(), json!(["red", "navy, dark", ""]));
+        assert_eq!(compute("parse_csv(n)", record.clone()).unwrap(), json!(["7"]));
+        assert_eq!(compute("parse_csv(sizes, ';')", json!({ "sizes": "" })).unwrap(), json!([]));
+        assert_eq!(compute("parse_csv(sizes, ';')", json!({})).unwrap(), Value::Null);
+        assert!(compute("parse_csv(sizes, ';;')", record).is_err());
+    }
+
+    #[test]
+    fn base64_functions() {
+        let compute = |expr: &str, record: Value, binary_output: Option<&str>| {
+            let mut config = json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            });
+            if let Some(policy) = binary_output {
+                config["binaryOutput"] = json!(policy);
+            }
+            plan(config).apply_to_value(&record).map(|v| v.unwrap()["x"].clone())
+        };
+
+        let record = json!({ "text": "héllo?", "url": "aMOpbGxvPw", "blob": "__8A\n" });
+        assert_eq!(compute("base64_encode(text)", record.clone(), None).unwrap(), json!("aMOpbGxvPw=="));
+        assert_eq!(compute("base64_decode('aMOpbGxvPw==')", record.clone(), None).unwrap(), json!("héllo?"));
+        assert_eq!(compute("base64_decode(url)", record.clone(), None).unwrap(), json!("héllo?"));
+        assert_eq!(compute("base64_decode(blob)", record.clone(), None).unwrap(), json!("//8A"));
+        assert_eq!(compute("base64_decode(blob)", record.clone(), Some("hex")).unwrap(), json!("ffff00"));
+        assert!(compute("base64_decode(blob)", record.clone(), Some("error")).is_err());
+        assert!(compute("base64_decode(text)", record.clone(), None).is_err());
+        assert_eq!(compute("base64_encode(missing)", record, None).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn url_functions() {
+        let compute = |expr: &str, record: Value| {
+            let config = json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            });
+            plan(config).apply_to_value(&record).map(|v| v.unwrap()["x"].clone())
+        };
+
+        let record = json!({
+            "link": "https://shop.example/p/1?utm_source=news%20letter&id=7&utm_medium=e+mail&flag#utm_source=x",
+            "name": "Café & Bar/2"
+        });
+        assert_eq!(compute("url_encode(name)", record.clone()).unwrap(), json!("Caf%C3%A9%20%26%20Bar%2F2"));
+        assert_eq!(compute("url_decode(url_encode(name))", record.clone()).unwrap(), json!("Café & Bar/2"));
+        assert_eq!(compute("url_decode('100%+%zz')", record.clone()).unwrap(), json!("100% %zz"));
+        assert_eq!(compute("url_param(link, 'utm_source')", record.clone()).unwrap(), json!("news letter"));
+        assert_eq!(compute("url_param(link, 'utm_medium')", record.clone()).unwrap(), json!("e mail"));
+        assert_eq!(compute("url_param(link, 'flag')", record.clone()).unwrap(), json!(""));
+        assert_eq!(compute("url_param(link, 'gclid')", record.clone()).unwrap(), Value::Null);
+        assert_eq!(compute("url_param(missing, 'id')", record).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn html_functions() {
+        let compute = |expr: &str, record: Value| {
+            let config = json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            });
+            plan(config).apply_to_value(&record).map(|v| v.unwrap()["x"].clone())
+        };
+
+        let record = json!({
+            "description": "<ul><li>Warm &amp; dry</li><li class=\"x>y\">Size&nbsp;M</li></ul>\n<!-- <b>draft</b> --><p>1 < 2<br/>caf&eacute; &#8364;5 &#x2013; &bogus; & more</p><script>if (a<b) x()</script>",
+        });
+        assert_eq!(
+            compute("strip_html(description)", record.clone()).unwrap(),
+            json!("Warm &amp; dry Size&nbsp;M 1 < 2 caf&eacute; &#8364;5 &#x2013; &bogus; & more")
+        );
+        assert_eq!(
+            compute("decode_html_entities(strip_html(description))", record.clone()).unwrap(),
+            json!("Warm & dry Size\u{a0}M 1 < 2 café €5 – &bogus; & more")
+        );
+        assert_eq!(compute("decode_html_entities('&lt;ul&gt;')", record.clone()).unwrap(), json!("<ul>"));
+        assert_eq!(compute("strip_html('<ul>A warm jacket</ul>')", record.clone()).unwrap(), json!("A warm jacket"));
+        assert_eq!(compute("strip_html(missing)", record).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn unicode_functions() {
+        let compute = |expr: &str, record: Value| {
+            let config = json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            });
+            plan(config).apply_to_value(&record).map(|v| v.unwrap()["x"].clone())
+        };
+
+        // "é" precomposed, and as "e" plus a combining accent
+        let record = json!({ "composed": "Caf\u{e9}", "decomposed": "Cafe\u{301}", "name": "  Crème Brûlée — 2L / Größe ½ " });
+        assert_eq!(compute("normalize(decomposed)", record.clone()).unwrap(), json!("Caf\u{e9}"));
+        assert_eq!(compute("normalize(composed, 'nfd')", record.clone()).unwrap(), json!("Cafe\u{301}"));
+        assert_eq!(compute("normalize('ﬁ ½', 'NFKC')", record.clone()).unwrap(), json!("fi 1⁄2"));
+        assert!(compute("normalize(composed, 'NFX')", record.clone()).is_err());
+        assert_eq!(compute("transliterate(name)", record.clone()).unwrap(), json!("  Creme Brulee -- 2L / Grosse 1/2 "));
+        assert_eq!(compute("slugify(name)", record.clone()).unwrap(), json!("creme-brulee-2l-grosse-1-2"));
+        assert_eq!(compute("slugify(decomposed, '_')", record.clone()).unwrap(), json!("cafe"));
+        assert_eq!(compute("slugify(missing)", record).unwrap(), Value::Null);
+    }
+
+    #[test]
+    fn geo_functions() {
+        let compute = |expr: &str, record: Value| {
+            let config = json!({
+                "fields": [{ "targetFieldName": "x", "compute": expr }],
+                "onMissingField": "null"
+            });
+            plan(config).apply_to_value(&record).map(|v| v.unwrap()["x"].clone())
+        };
+
+        let record = json!({ "loc": " 48.8566, 2.3522 ", "bad": "48.85", "lat": "51.5074", "lon": -0.1278 });
+        assert_eq!(compute("geo_split(loc)", record.clone()).unwrap(), json!({ "lat": 48.8566, "lon": 2.3522 }));
+        assert_eq!(compute("geo_split('-33.9 151.2', 'lon')", record.clone()).unwrap(), json!(151.2));
+        assert_eq!(compute("geo_split(bad)", record.clone()).unwrap(), Value::Null);
+        assert_eq!(compute("geo_split('91,0')", record.clone()).unwrap(), Value::Null);
+        assert!(compute("geo_split(loc, 'x')", record.clone()).is_err());
+
+        // Paris to London is about 344 km
+        let paris_london = "haversine(geo_split(loc, 'lat'), geo_split(loc, 'lon'), lat, lon)";
+        let km = compute(paris_london, record.clone()).unwrap().as_f64().unwrap();
+        assert!((km - 343.6).abs() < 0.5, "{km}");
+        let mi = compute("haversine(48.8566, 2.3522, lat, lon, 'mi')", record.clone()).unwrap();
+        assert!((mi.as_f64().unwrap() - km / 1.609344).abs() < 1e-9);
+        assert_eq!(compute("haversine(0, 0, 0, 0)", record.clone()).unwrap(), json!(0.0));
+        assert_eq!(compute("haversine(0, 0, 'n/a', 0)", record.clone()).unwrap(), Value::Null);
+        assert!(compute("haversine(0, 0, 0, 0, 'ft')", record.clone()).is_err());
+        assert!(compute("haversine(0, 0, 0)", record).is_err());
+    }
+
+    #[test]
+    fn filter_expressions_survive_plan_round_trip() {
+        let config = plan(json!({ "mode": "augment", "filter": "n * 2 > 5" })).to_config();
+        let serialized = serde_json::to_value(&config).unwrap();
+        assert!(serialized.get("filter").is_none());
+        assert!(serialized.get("filterExpression").is_some());
+
+        let plan = TransformPlan::from_config(serde_json::from_value(serialized).unwrap()).unwrap();
+        assert_eq!(plan.apply_to_value(&json!({ "n": 2 })).unwrap(), None);
+        assert!(plan.apply_to_value(&json!({ "n": 3 })).unwrap().is_some());
+    }
+
+    #[test]
+    fn invalid_filter_expressions_are_rejected() {
+        for filter in ["status = 'a'", "a < b < c", "a & b", "(a == 1"] {
+            let input: TransformConfigInput =
+                serde_json::from_value(json!({ "mode": "augment", "filter": filter })).unwrap();
+            assert!(TransformPlan::compile(input).is_err(), "{filter}");
+        }
+    }
+
+    #[test]
+    fn empty_fields_require_augment_mode() {
+        let input: TransformConfigInput = serde_json::from_value(json!({ "fields": [] })).unwrap();
+        assert!(TransformPlan::compile(input).is_err());
+    }
+
+    #[test]
+    fn required_columns_cover_origins_and_expression_fields() {
+        let replace = plan(json!({
+            "fields": [
+                { "targetFieldName": "name", "originFieldName": "full_name" },
+                { "targetFieldName": "total", "compute": "price * coalesce(qty, 1)" }
+            ],
+            "stopWhen": { "field": "id" }
+        }));
+        assert_eq!(
+            replace.required_columns(),
+            Some(vec![
+                "full_name".to_string(),
+                "id".to_string(),
+                "price".to_string(),
+                "qty".to_string()
+            ])
+        );
+
+        let augment = plan(json!({ "mode": "augment", "fields": [{ "targetFieldName": "id" }] }));
+        assert_eq!(augment.required_columns(), None);
+    }
+
+    #[test]
+    fn engine_errors_include_record_index_and_snippet() {
+        let mut engine = TransformEngine::new(plan(json!({
+            "fields": [{ "targetFieldName": "id", "required": true }]
+        })));
+
+        let input = format!(
+            "{{\"id\":1}}\n{{\"id\":2}}\n{{\"name\":\"{}\"}}\n",
+            "x".repeat(500)
+        );
+        let err = engine.push(input.as_bytes()).unwrap_err();
+        let message = err.to_string();
+        assert!(matches!(err, ConvertError::Transform(_)));
+        assert!(message.contains("record 3:"), "{message}");
+        assert!(message.contains("Missing required field 'id'"), "{message}");
+        assert!(message.contains("{\"name\":\"xxx"), "{message}");
+        assert!(message.ends_with("...)"), "{message}");
+        assert!(message.len() < 400, "{message}");
+    }
+}
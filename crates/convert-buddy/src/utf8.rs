@@ -0,0 +1,215 @@
+use crate::format::{ConverterConfig, Format};
+use crate::warnings::{WarningCode, Warnings};
+use memchr::memrchr;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// What to do with input bytes that aren't valid UTF-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidUtf8Policy {
+    /// Fail the conversion.
+    #[default]
+    Error,
+    /// Replace each invalid sequence with U+FFFD.
+    Replace,
+    /// Drop the CSV row or NDJSON line holding invalid bytes. JSON and XML
+    /// records can't be told apart in raw bytes, so they still fail.
+    Skip,
+}
+
+/// Applies [`InvalidUtf8Policy`] to input on its way to the parsers.
+///
+/// Replacing works on any text format. Skipping is done here for NDJSON,
+/// where a record is a line; CSV rows can span lines, so the CSV parser
+/// skips them itself.
+pub(crate) enum Utf8Filter {
+    Passthrough,
+    /// Holds an incomplete sequence from the end of the last chunk.
+    Replace(Vec<u8>),
+    /// Holds the unfinished last line.
+    SkipLines(Vec<u8>),
+}
+
+const REPLACEMENT: &[u8] = "\u{FFFD}".as_bytes();
+
+impl Utf8Filter {
+    pub(crate) fn new(policy: InvalidUtf8Policy, input_format: Format) -> Self {
+        match (policy, input_format) {
+            // Binary input; the parser reads the text parts itself
+            #[cfg(feature = "xlsx")]
+            (_, Format::Xlsx) => Utf8Filter::Passthrough,
+            (InvalidUtf8Policy::Replace, _) => Utf8Filter::Replace(Vec::new()),
+            (InvalidUtf8Policy::Skip, Format::Ndjson) => Utf8Filter::SkipLines(Vec::new()),
+            _ => Utf8Filter::Passthrough,
+        }
+    }
+
+    /// The filter for a converter. While the input format is still to be
+    /// detected, input passes through and the sample is filtered afterwards.
+    pub(crate) fn for_config(config: &ConverterConfig) -> Self {
+        if config.auto_detect.input_format {
+            return Utf8Filter::Passthrough;
+        }
+        Self::new(config.invalid_utf8, config.input_format)
+    }
+
+    pub(crate) fn push<'a>(&mut self, chunk: &'a [u8], warnings: &Warnings) -> Cow<'a, [u8]> {
+        match self {
+            Utf8Filter::Passthrough => Cow::Borrowed(chunk),
+            Utf8Filter::Replace(pending) => {
+                if pending.is_empty() && std::str::from_utf8(chunk).is_ok() {
+                    return Cow::Borrowed(chunk);
+                }
+                let data = [std::mem::take(pending).as_slice(), chunk].concat();
+                let (output, incomplete) = replace_invalid(&data, warnings);
+                *pending = incomplete.to_vec();
+                Cow::Owned(output)
+            }
+            Utf8Filter::SkipLines(partial) => {
+                let data = [std::mem::take(partial).as_slice(), chunk].concat();
+                let complete = memrchr(b'\n', &data).map_or(0, |pos| pos + 1);
+                *partial = data[complete..].to_vec();
+                let lines = &data[..complete];
+                if std::str::from_utf8(lines).is_ok() {
+                    return Cow::Owned(lines.to_vec());
+                }
+                let mut output = Vec::with_capacity(lines.len());
+                for line in lines.split_inclusive(|&b| b == b'\n') {
+                    if keep_line(line, warnings) {
+                        output.extend_from_slice(line);
+                    }
+                }
+                Cow::Owned(output)
+            }
+        }
+    }
+
+    /// Release held-back input at the end of the stream.
+    pub(crate) fn finish(&mut self, warnings: &Warnings) -> Vec<u8> {
+        match self {
+            Utf8Filter::Passthrough => Vec::new(),
+            Utf8Filter::Replace(pending) => {
+                // A sequence cut off by the end of input
+                if pending.is_empty() {
+                    return Vec::new();
+                }
+                pending.clear();
+                warn_replaced(warnings);
+                REPLACEMENT.to_vec()
+            }
+            Utf8Filter::SkipLines(partial) => {
+                let line = std::mem::take(partial);
+                if keep_line(&line, warnings) {
+                    line
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    pub(crate) fn buffered_bytes(&self) -> usize {
+        match self {
+            Utf8Filter::Passthrough => 0,
+            Utf8Filter::Replace(held) | Utf8Filter::SkipLines(held) => held.len(),
+        }
+    }
+
+    /// Drop the held bytes, for input that starts over.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn discard_partial(&mut self) {
+        if let Utf8Filter::Replace(held) | Utf8Filter::SkipLines(held) = self {
+            held.clear();
+        }
+    }
+}
+
+/// Replace invalid sequences, returning the output and any incomplete
+/// sequence at the end that may be completed by the next chunk.
+fn replace_invalid<'a>(mut data: &'a [u8], warnings: &Warnings) -> (Vec<u8>, &'a [u8]) {
+    let mut output = Vec::with_capacity(data.len() + REPLACEMENT.len());
+    loop {
+        match std::str::from_utf8(data) {
+            Ok(_) => {
+                output.extend_from_slice(data);
+                return (output, &[]);
+            }
+            Err(err) => {
+                let (valid, rest) = data.split_at(err.valid_up_to());
+                output.extend_from_slice(valid);
+                let Some(invalid) = err.error_len() else {
+                    return (output, rest);
+                };
+                output.extend_from_slice(REPLACEMENT);
+                warn_replaced(warnings);
+                data = &rest[invalid..];
+            }
+        }
+    }
+}
+
+fn warn_replaced(warnings: &Warnings) {
+    warnings.warn(WarningCode::InvalidUtf8Replaced, || {
+        "invalid UTF-8 replaced with U+FFFD".to_string()
+    });
+}
+
+/// Whether a line is valid UTF-8; invalid lines are reported.
+pub(crate) fn keep_line(line: &[u8], warnings: &Warnings) -> bool {
+    if std::str::from_utf8(line).is_ok() {
+        return true;
+    }
+    warnings.warn(WarningCode::InvalidUtf8Skipped, || {
+        "skipped a record with invalid UTF-8".to_string()
+    });
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_in_chunks(
+        mut filter: Utf8Filter,
+        data: &[u8],
+        size: usize,
+        warnings: &Warnings,
+    ) -> Vec<u8> {
+        let mut output = Vec::new();
+        for chunk in data.chunks(size) {
+            output.extend_from_slice(&filter.push(chunk, warnings));
+        }
+        output.extend(filter.finish(warnings));
+        output
+    }
+
+    #[test]
+    fn replaces_invalid_sequences_split_across_chunks() {
+        // Latin-1 "café", then a valid "é" and a truncated sequence at the end
+        let data = b"caf\xe9,\xc3\xa9\n\xe2\x82";
+        for size in [1, 2, 5, 64] {
+            let warnings = Warnings::default();
+            let filter = Utf8Filter::new(InvalidUtf8Policy::Replace, Format::Csv);
+            let output = filter_in_chunks(filter, data, size, &warnings);
+            assert_eq!(String::from_utf8(output).unwrap(), "caf\u{FFFD},é\n\u{FFFD}");
+            assert_eq!(warnings.list()[0].count, 2);
+        }
+    }
+
+    #[test]
+    fn skips_invalid_ndjson_lines() {
+        let data = b"{\"a\":\"ok\"}\n{\"a\":\"caf\xe9\"}\n{\"a\":\"\xc3\xa9\"}";
+        for size in [1, 3, 64] {
+            let warnings = Warnings::default();
+            let filter = Utf8Filter::new(InvalidUtf8Policy::Skip, Format::Ndjson);
+            let output = filter_in_chunks(filter, data, size, &warnings);
+            assert_eq!(String::from_utf8(output).unwrap(), "{\"a\":\"ok\"}\n{\"a\":\"é\"}");
+            assert_eq!(warnings.list()[0].code, WarningCode::InvalidUtf8Skipped);
+        }
+        assert!(matches!(
+            Utf8Filter::new(InvalidUtf8Policy::Skip, Format::Csv),
+            Utf8Filter::Passthrough
+        ));
+    }
+}
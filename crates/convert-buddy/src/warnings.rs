@@ -0,0 +1,120 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// Non-fatal problems a conversion works around instead of failing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WarningCode {
+    /// Blank lines skipped in CSV or NDJSON input.
+    BlankLineSkipped,
+    /// NDJSON lines that aren't valid JSON, skipped.
+    InvalidLineSkipped,
+    /// CSV rows shorter than the header; the missing columns are left out.
+    MissingFields,
+    /// CSV rows longer than the header; the extra values are keyed `field_N`.
+    ExtraFields,
+    /// Keys missing from the CSV output header, which comes from the first record.
+    FieldDropped,
+    /// Records that aren't JSON objects and so can't become CSV rows.
+    RecordSkipped,
+    /// Flattened keys that collided under the last-wins policy.
+    FlattenCollision,
+    /// Values a transform couldn't coerce, replaced by null or dropped with their record.
+    CoercionFailed,
+    /// Auto-detection failed and default settings were used.
+    DetectionFallback,
+    /// The start of the input doesn't fit the CSV delimiter or XML record
+    /// element; see `DetectionCheck`.
+    DetectionSuspect,
+    /// Invalid UTF-8 sequences replaced with U+FFFD.
+    InvalidUtf8Replaced,
+    /// CSV rows or NDJSON lines skipped for holding invalid UTF-8.
+    InvalidUtf8Skipped,
+    /// Records dropped by `dedupe` for repeating an earlier record's key.
+    DuplicateDropped,
+}
+
+/// One kind of warning: the first occurrence's message and how often it happened.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Warning {
+    pub code: WarningCode,
+    pub message: String,
+    pub count: u64,
+}
+
+/// Shared collector for a converter's warnings, one entry per code.
+///
+/// Clones report into the same list, so each parser and writer of a pipeline
+/// holds one; it is thread-safe for the parallel paths.
+#[derive(Debug, Clone, Default)]
+pub struct Warnings(Arc<Mutex<Vec<Warning>>>);
+
+impl Warnings {
+    /// Record an occurrence of `code`. `message` is only built for the first one.
+    pub fn warn(&self, code: WarningCode, message: impl FnOnce() -> String) {
+        let mut warnings = self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match warnings.iter_mut().find(|warning| warning.code == code) {
+            Some(warning) => warning.count += 1,
+            None => warnings.push(Warning {
+                code,
+                message: message(),
+                count: 1,
+            }),
+        }
+    }
+
+    pub fn list(&self) -> Vec<Warning> {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// Warnings raised since `before`, an earlier [`list`](Self::list), each
+    /// counting only its new occurrences.
+    pub fn since(&self, before: &[Warning]) -> Vec<Warning> {
+        self.list()
+            .into_iter()
+            .filter_map(|mut warning| {
+                let seen = before
+                    .iter()
+                    .find(|earlier| earlier.code == warning.code)
+                    .map_or(0, |earlier| earlier.count);
+                warning.count = warning.count.checked_sub(seen).filter(|&count| count > 0)?;
+                Some(warning)
+            })
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_occurrences_by_code() {
+        let warnings = Warnings::default();
+        let shared = warnings.clone();
+        warnings.warn(WarningCode::BlankLineSkipped, || "first".to_string());
+        shared.warn(WarningCode::BlankLineSkipped, || unreachable!());
+        shared.warn(WarningCode::MissingFields, || "short row".to_string());
+
+        let list = warnings.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!((list[0].message.as_str(), list[0].count), ("first", 2));
+        assert_eq!(
+            serde_json::to_value(&list[1]).unwrap(),
+            serde_json::json!({ "code": "missingFields", "message": "short row", "count": 1 })
+        );
+
+        let before = warnings.list();
+        shared.warn(WarningCode::MissingFields, || unreachable!());
+        let since = warnings.since(&before);
+        assert_eq!(since.len(), 1);
+        assert_eq!((since[0].code, since[0].count), (WarningCode::MissingFields, 1));
+
+        warnings.clear();
+        assert!(shared.list().is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{wasm_config_from_object, Converter};
+use js_sys::Uint8Array;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{QueuingStrategy, TransformStream, TransformStreamDefaultController, Transformer};
+
+/// A `TransformStream` of bytes converted by a new converter, for
+/// `readable.pipeThrough(stream).pipeTo(writable)`.
+///
+/// `config` takes `inputFormat` ("auto" when left out), `outputFormat`,
+/// `chunkTargetBytes`, `profile`, `csvConfig`, `xmlConfig`, `transform` and
+/// `options` as `Converter.withConfig` does. Written chunks are pushed in
+/// slices of at most `chunkTargetBytes`, and output is enqueued in chunks of
+/// at most `options.maxOutputBytes`, which defaults to `chunkTargetBytes`.
+/// Both sides count their queues in bytes, with a high water mark of
+/// `chunkTargetBytes`, so a slow reader holds back writes once about one
+/// chunk of output is waiting rather than after a number of chunks of any
+/// size.
+#[wasm_bindgen(js_name = createTransformStream)]
+pub fn create_transform_stream(config: JsValue) -> Result<TransformStream, JsValue> {
+    let mut converter_config = wasm_config_from_object(&config, "createTransformStream")?;
+    let chunk_target_bytes = converter_config.chunk_target_bytes;
+    converter_config.max_output_bytes.get_or_insert(chunk_target_bytes);
+    let converter = Rc::new(RefCell::new(Converter::from_config(converter_config)));
+
+    let pushing = Rc::clone(&converter);
+    let transform = Closure::<dyn FnMut(JsValue, TransformStreamDefaultController) -> Result<(), JsValue>>::new(
+        move |chunk: JsValue, controller: TransformStreamDefaultController| {
+            let chunk: Uint8Array = chunk
+                .dyn_into()
+                .map_err(|_| JsValue::from_str("createTransformStream only accepts Uint8Array chunks"))?;
+            let mut converter = pushing.borrow_mut();
+            let len = chunk.length() as usize;
+            let mut start = 0;
+            while start < len {
+                let end = (start + chunk_target_bytes).min(len);
+                let slice = chunk.subarray(start as u32, end as u32).to_vec();
+                let output = converter.push(&slice)?;
+                enqueue(&mut converter, output, &controller)?;
+                start = end;
+            }
+            Ok(())
+        },
+    );
+    let flush = Closure::<dyn FnMut(TransformStreamDefaultController) -> Result<(), JsValue>>::new(
+        move |controller: TransformStreamDefaultController| {
+            let mut converter = converter.borrow_mut();
+            let output = converter.finish()?;
+            enqueue(&mut converter, output, &controller)
+        },
+    );
+
+    let transformer = Transformer::new();
+    transformer.set_transform(transform.into_js_value().unchecked_ref());
+    transformer.set_flush(flush.into_js_value().unchecked_ref());
+    TransformStream::new_with_transformer_and_writable_strategy_and_readable_strategy(
+        &transformer,
+        &byte_strategy(chunk_target_bytes),
+        &byte_strategy(chunk_target_bytes),
+    )
+}
+
+/// Enqueue `output` and whatever `maxOutputBytes` held back of it.
+fn enqueue(
+    converter: &mut Converter,
+    output: Vec<u8>,
+    controller: &TransformStreamDefaultController,
+) -> Result<(), JsValue> {
+    if !output.is_empty() {
+        controller.enqueue_with_chunk(&Uint8Array::from(output.as_slice()))?;
+    }
+    while converter.pending_output_bytes() > 0 {
+        controller.enqueue_with_chunk(&Uint8Array::from(converter.drain().as_slice()))?;
+    }
+    Ok(())
+}
+
+/// A queuing strategy that counts chunks by their length in bytes.
+fn byte_strategy(high_water_mark: usize) -> QueuingStrategy {
+    let size = Closure::<dyn Fn(Uint8Array) -> f64>::new(|chunk: Uint8Array| f64::from(chunk.byte_length()));
+    let strategy = QueuingStrategy::new();
+    strategy.set_high_water_mark(high_water_mark as f64);
+    strategy.set_size(size.into_js_value().unchecked_ref());
+    strategy
+}
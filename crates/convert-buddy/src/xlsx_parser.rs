@@ -0,0 +1,479 @@
+use crate::error::{ConvertError, Result};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+/// Excel input settings.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct XlsxConfig {
+    /// Worksheet to read, by name; the first sheet of the workbook when unset.
+    pub sheet: Option<String>,
+    /// Use the first row as column names; otherwise columns are `field_N`.
+    pub has_headers: bool,
+}
+
+impl Default for XlsxConfig {
+    fn default() -> Self {
+        Self {
+            sheet: None,
+            has_headers: true,
+        }
+    }
+}
+
+/// Reads the records of one worksheet of an Excel (.xlsx) workbook as NDJSON.
+///
+/// An .xlsx file is a zip archive with its directory at the end, so pushed
+/// bytes are buffered and the sheet is read by `finish`. Rows are keyed by
+/// the header row, or `field_N` like the CSV parser. Text is written as
+/// strings, numbers as numbers and booleans as `true`/`false`; empty cells
+/// under a header are `null`. Cell formats aren't applied, so dates come out
+/// as Excel serial numbers.
+pub struct XlsxParser {
+    config: XlsxConfig,
+    buffer: Vec<u8>,
+}
+
+/// A cell value before it is turned into JSON.
+enum CellType {
+    SharedString,
+    Text,
+    Bool,
+    Number,
+}
+
+impl XlsxParser {
+    pub fn new(config: XlsxConfig) -> Self {
+        Self {
+            config,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+    }
+
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Drop the workbook bytes pushed so far.
+    pub fn discard_partial(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Read the workbook and return the sheet's rows as NDJSON.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        let data = std::mem::take(&mut self.buffer);
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut archive = ZipArchive::new(Cursor::new(data)).map_err(xlsx_error)?;
+
+        let sheet_path = self.sheet_path(&mut archive)?;
+        let shared_strings = match read_part(&mut archive, "xl/sharedStrings.xml")? {
+            Some(xml) => parse_shared_strings(&xml)?,
+            None => Vec::new(),
+        };
+        let sheet = read_part(&mut archive, &sheet_path)?
+            .ok_or_else(|| ConvertError::XlsxParse(format!("missing worksheet part '{sheet_path}'")))?;
+        self.rows_to_ndjson(&sheet, &shared_strings)
+    }
+
+    /// Path of the configured sheet's part, found through the workbook and its relationships.
+    fn sheet_path(&self, archive: &mut ZipArchive<Cursor<Vec<u8>>>) -> Result<String> {
+        let workbook = read_part(archive, "xl/workbook.xml")?
+            .ok_or_else(|| ConvertError::XlsxParse("missing xl/workbook.xml; not an .xlsx file?".to_string()))?;
+
+        // (name, relationship id) of each sheet, in workbook order
+        let mut sheets = Vec::new();
+        let mut reader = Reader::from_reader(workbook.as_slice());
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf).map_err(xml_error)? {
+                Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"sheet" => {
+                    let mut name = String::new();
+                    let mut rel_id = String::new();
+                    for attr in e.attributes().flatten() {
+                        let value = attr.unescape_value().map_err(xml_error)?.into_owned();
+                        match (attr.key.prefix().is_some(), attr.key.local_name().as_ref()) {
+                            (false, b"name") => name = value,
+                            (true, b"id") => rel_id = value,
+                            _ => {}
+                        }
+                    }
+                    sheets.push((name, rel_id));
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        let rel_id = match &self.config.sheet {
+            Some(wanted) => sheets
+                .iter()
+                .find(|(name, _)| name == wanted)
+                .map(|(_, id)| id.clone())
+                .ok_or_else(|| ConvertError::InvalidConfig(format!("Worksheet '{wanted}' not found")))?,
+            None => sheets
+                .first()
+                .map(|(_, id)| id.clone())
+                .ok_or_else(|| ConvertError::XlsxParse("workbook has no sheets".to_string()))?,
+        };
+
+        let rels = read_part(archive, "xl/_rels/workbook.xml.rels")?.unwrap_or_default();
+        let mut reader = Reader::from_reader(rels.as_slice());
+        loop {
+            match reader.read_event_into(&mut buf).map_err(xml_error)? {
+                Event::Start(e) | Event::Empty(e)
+                    if e.local_name().as_ref() == b"Relationship"
+                        && attribute(&e, b"Id")?.as_deref() == Some(rel_id.as_str()) =>
+                {
+                    if let Some(target) = attribute(&e, b"Target")? {
+                        // Targets are relative to xl/ unless absolute within the package
+                        return Ok(match target.strip_prefix('/') {
+                            Some(absolute) => absolute.to_string(),
+                            None => format!("xl/{target}"),
+                        });
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        Err(ConvertError::XlsxParse(format!("no worksheet part for relationship '{rel_id}'")))
+    }
+
+    fn rows_to_ndjson(&self, sheet: &[u8], shared_strings: &[String]) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let mut headers: Option<Vec<String>> = None;
+
+        let mut row: Vec<(usize, Value)> = Vec::new();
+        let mut next_column = 0;
+        let mut cell_column = 0;
+        let mut cell_type = CellType::Number;
+        let mut cell_text = String::new();
+        let mut in_value = false;
+
+        let mut reader = Reader::from_reader(sheet);
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf).map_err(xml_error)? {
+                Event::Start(e) => match e.local_name().as_ref() {
+                    b"row" => {
+                        row.clear();
+                        next_column = 0;
+                    }
+                    b"c" => {
+                        (cell_column, cell_type) = cell_start(&e, next_column)?;
+                        next_column = cell_column + 1;
+                        cell_text.clear();
+                    }
+                    // <v> holds the value; <t> the text of an inline string
+                    b"v" | b"t" => in_value = true,
+                    _ => {}
+                },
+                Event::Empty(e) if e.local_name().as_ref() == b"c" => {
+                    let (column, _) = cell_start(&e, next_column)?;
+                    next_column = column + 1;
+                }
+                Event::Text(e) if in_value => {
+                    cell_text.push_str(&e.unescape().map_err(xml_error)?);
+                }
+                Event::CData(e) if in_value => {
+                    cell_text.push_str(&String::from_utf8_lossy(&e));
+                }
+                Event::End(e) => match e.local_name().as_ref() {
+                    b"v" | b"t" => in_value = false,
+                    b"c" => {
+                        if let Some(value) = cell_value(&cell_type, &cell_text, shared_strings)? {
+                            row.push((cell_column, value));
+                        }
+                    }
+                    b"row" if !row.is_empty() => match &headers {
+                        None if self.config.has_headers => {
+                            headers = Some(header_names(&row));
+                        }
+                        _ => {
+                            write_record(&row, headers.as_deref(), &mut output)?;
+                        }
+                    },
+                    _ => {}
+                },
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+        Ok(output)
+    }
+}
+
+fn read_part(archive: &mut ZipArchive<Cursor<Vec<u8>>>, path: &str) -> Result<Option<Vec<u8>>> {
+    let mut file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(xlsx_error(err)),
+    };
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)
+        .map_err(|e| ConvertError::XlsxParse(format!("reading '{path}': {e}")))?;
+    Ok(Some(data))
+}
+
+/// Strings referenced by index from `t="s"` cells. Rich text runs are joined;
+/// phonetic hints (`<rPh>`) are left out.
+fn parse_shared_strings(xml: &[u8]) -> Result<Vec<String>> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    let mut in_phonetic = false;
+
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf).map_err(xml_error)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"si" => current.clear(),
+                b"t" => in_text = true,
+                b"rPh" => in_phonetic = true,
+                _ => {}
+            },
+            Event::Empty(e) if e.local_name().as_ref() == b"si" => strings.push(String::new()),
+            Event::Text(e) if in_text && !in_phonetic => {
+                current.push_str(&e.unescape().map_err(xml_error)?);
+            }
+            Event::CData(e) if in_text && !in_phonetic => {
+                current.push_str(&String::from_utf8_lossy(&e));
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"si" => strings.push(std::mem::take(&mut current)),
+                b"t" => in_text = false,
+                b"rPh" => in_phonetic = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(strings)
+}
+
+/// Column index and value type of a `<c>` element. Cells without an `r`
+/// reference follow the previous one.
+fn cell_start(e: &BytesStart, next_column: usize) -> Result<(usize, CellType)> {
+    let column = match attribute(e, b"r")? {
+        Some(reference) => column_index(&reference).unwrap_or(next_column),
+        None => next_column,
+    };
+    let cell_type = match attribute(e, b"t")?.as_deref() {
+        Some("s") => CellType::SharedString,
+        Some("inlineStr" | "str" | "e") => CellType::Text,
+        Some("b") => CellType::Bool,
+        _ => CellType::Number,
+    };
+    Ok((column, cell_type))
+}
+
+/// Zero-based column of a cell reference such as `AB12`.
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<u8> = reference
+        .bytes()
+        .take_while(u8::is_ascii_alphabetic)
+        .map(|b| b.to_ascii_uppercase())
+        .collect();
+    if letters.is_empty() {
+        return None;
+    }
+    let number = letters
+        .iter()
+        .fold(0usize, |acc, &b| acc * 26 + usize::from(b - b'A' + 1));
+    Some(number - 1)
+}
+
+fn cell_value(cell_type: &CellType, text: &str, shared_strings: &[String]) -> Result<Option<Value>> {
+    if text.is_empty() && !matches!(cell_type, CellType::Text) {
+        return Ok(None);
+    }
+    let value = match cell_type {
+        CellType::SharedString => {
+            let index: usize = text
+                .trim()
+                .parse()
+                .map_err(|_| ConvertError::XlsxParse(format!("invalid shared string index '{text}'")))?;
+            let string = shared_strings
+                .get(index)
+                .ok_or_else(|| ConvertError::XlsxParse(format!("shared string {index} out of range")))?;
+            Value::String(string.clone())
+        }
+        CellType::Text => Value::String(text.to_string()),
+        CellType::Bool => Value::Bool(text.trim() == "1"),
+        CellType::Number => number_value(text.trim()),
+    };
+    Ok(Some(value))
+}
+
+/// Whole numbers are written without a fraction; anything unparseable stays text.
+fn number_value(text: &str) -> Value {
+    match text.parse::<f64>() {
+        Ok(n) if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 => Value::from(n as i64),
+        Ok(n) => Number::from_f64(n).map_or_else(|| Value::String(text.to_string()), Value::Number),
+        Err(_) => Value::String(text.to_string()),
+    }
+}
+
+/// Column names from the header row; blank header cells are named `field_N`.
+fn header_names(row: &[(usize, Value)]) -> Vec<String> {
+    let width = row.iter().map(|(column, _)| column + 1).max().unwrap_or(0);
+    let mut names: Vec<String> = (0..width).map(|i| format!("field_{i}")).collect();
+    for (column, value) in row {
+        let name = match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        if !name.is_empty() {
+            names[*column] = name;
+        }
+    }
+    names
+}
+
+fn write_record(row: &[(usize, Value)], headers: Option<&[String]>, output: &mut Vec<u8>) -> Result<()> {
+    let width = headers.map_or(0, <[String]>::len);
+    let mut record = Map::new();
+    for (i, name) in headers.unwrap_or_default().iter().enumerate() {
+        let value = row.iter().find(|(column, _)| *column == i).map(|(_, v)| v.clone());
+        record.insert(name.clone(), value.unwrap_or(Value::Null));
+    }
+    // Cells past the header, or every cell without one
+    for (column, value) in row.iter().filter(|(column, _)| *column >= width) {
+        record.insert(format!("field_{column}"), value.clone());
+    }
+    serde_json::to_writer(&mut *output, &Value::Object(record))
+        .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+    output.push(b'\n');
+    Ok(())
+}
+
+fn attribute(e: &BytesStart, name: &[u8]) -> Result<Option<String>> {
+    for attr in e.attributes().flatten() {
+        if attr.key.as_ref() == name {
+            return Ok(Some(attr.unescape_value().map_err(xml_error)?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+fn xlsx_error(error: zip::result::ZipError) -> ConvertError {
+    ConvertError::XlsxParse(error.to_string())
+}
+
+fn xml_error(error: quick_xml::Error) -> ConvertError {
+    ConvertError::XlsxParse(error.to_string())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    /// A minimal workbook with the given sheets (name, sheet XML rows).
+    pub(crate) fn workbook(sheets: &[(&str, &str)], shared_strings: &[&str]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        let mut entries = String::new();
+        let mut rels = String::new();
+        for (i, (name, rows)) in sheets.iter().enumerate() {
+            let n = i + 1;
+            entries.push_str(&format!(r#"<sheet name="{name}" sheetId="{n}" r:id="rId{n}"/>"#));
+            rels.push_str(&format!(
+                r#"<Relationship Id="rId{n}" Type="worksheet" Target="worksheets/sheet{n}.xml"/>"#
+            ));
+            zip.start_file(format!("xl/worksheets/sheet{n}.xml"), options).unwrap();
+            write!(zip, "<worksheet><sheetData>{rows}</sheetData></worksheet>").unwrap();
+        }
+        zip.start_file("xl/workbook.xml", options).unwrap();
+        write!(
+            zip,
+            r#"<workbook xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>{entries}</sheets></workbook>"#
+        )
+        .unwrap();
+        zip.start_file("xl/_rels/workbook.xml.rels", options).unwrap();
+        write!(zip, "<Relationships>{rels}</Relationships>").unwrap();
+        zip.start_file("xl/sharedStrings.xml", options).unwrap();
+        write!(zip, "<sst>").unwrap();
+        for s in shared_strings {
+            write!(zip, "<si><t>{s}</t></si>").unwrap();
+        }
+        write!(zip, "</sst>").unwrap();
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn parse(config: XlsxConfig, data: &[u8]) -> Result<String> {
+        let mut parser = XlsxParser::new(config);
+        for chunk in data.chunks(100) {
+            parser.push(chunk);
+        }
+        parser.finish().map(|ndjson| String::from_utf8(ndjson).unwrap())
+    }
+
+    #[test]
+    fn reads_typed_cells_under_the_header() {
+        let rows = r#"<row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c><c r="C1" t="inlineStr"><is><t>ok</t></is></c></row>
+            <row r="2"><c r="A2" t="s"><v>2</v></c><c r="B2"><v>42</v></c><c r="C2" t="b"><v>1</v></c></row>
+            <row r="3"><c r="B3"><v>0.5</v></c><c r="E3" t="str"><v>x</v></c></row>"#;
+        let data = workbook(&[("Data", rows)], &["name", "score", "Ada &amp; co"]);
+
+        assert_eq!(
+            parse(XlsxConfig::default(), &data).unwrap(),
+            "{\"name\":\"Ada & co\",\"ok\":true,\"score\":42}\n\
+             {\"field_4\":\"x\",\"name\":null,\"ok\":null,\"score\":0.5}\n"
+        );
+    }
+
+    #[test]
+    fn selects_sheets_by_name() {
+        let data = workbook(
+            &[
+                ("First", r#"<row><c><v>1</v></c></row>"#),
+                ("Second", r#"<row><c><v>2</v></c><c><v>3</v></c></row>"#),
+            ],
+            &[],
+        );
+        let headerless = |sheet: Option<&str>| XlsxConfig {
+            sheet: sheet.map(str::to_string),
+            has_headers: false,
+        };
+
+        assert_eq!(parse(headerless(None), &data).unwrap(), "{\"field_0\":1}\n");
+        assert_eq!(
+            parse(headerless(Some("Second")), &data).unwrap(),
+            "{\"field_0\":2,\"field_1\":3}\n"
+        );
+        assert!(matches!(
+            parse(headerless(Some("Missing")), &data),
+            Err(ConvertError::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            parse(XlsxConfig::default(), b"not a zip archive"),
+            Err(ConvertError::XlsxParse(_))
+        ));
+    }
+
+    #[test]
+    fn column_references() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("Z9"), Some(25));
+        assert_eq!(column_index("AB12"), Some(27));
+        assert_eq!(column_index("12"), None);
+    }
+}
@@ -1,9 +1,13 @@
 use crate::error::{ConvertError, Result};
+use crate::number_format::FloatFormat;
+use crate::projection::OutputColumn;
+use crate::limits::ResourceLimits;
 use quick_xml::events::Event;
 use quick_xml::Reader;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io::Write as IoWrite;
 use bumpalo::Bump;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, PartialEq)]
 enum JsonValue {
@@ -15,8 +19,21 @@ enum JsonValue {
 /// XML parser configuration
 #[derive(Debug, Clone)]
 pub struct XmlConfig {
-    /// The XML element name that represents a record (e.g., "row", "item")
+    /// The XML element name that represents a record (e.g., "row", "item"),
+    /// or a path such as `channel/item` to only take elements directly
+    /// inside the given parents
     pub record_element: String,
+    /// Further record elements or paths converted in the same pass, e.g.
+    /// `bundle` next to `product`
+    pub record_elements: Vec<String>,
+    /// Path of the record element from the document root, such as
+    /// `/rss/channel/item`. When set, it replaces `record_element`, and
+    /// elements of the same name at any other place are not records
+    pub record_path: Option<String>,
+    /// Add an `_element` field holding the record element's name to each record
+    pub tag_element: bool,
+    /// How namespace prefixes in element and attribute names become keys
+    pub namespaces: NamespacePolicy,
     /// Whether to trim text content
     pub trim_text: bool,
     /// Whether to include attributes in output
@@ -25,10 +42,25 @@ pub struct XmlConfig {
     pub expand_entities: bool,
 }
 
+impl XmlConfig {
+    /// Name of the main record element, without the parents of a path.
+    pub(crate) fn record_element_name(&self) -> &str {
+        self.main_record_element().rsplit('/').next().unwrap_or_default()
+    }
+
+    fn main_record_element(&self) -> &str {
+        self.record_path.as_deref().unwrap_or(&self.record_element)
+    }
+}
+
 impl Default for XmlConfig {
     fn default() -> Self {
         Self {
             record_element: "row".to_string(),
+            record_elements: Vec::new(),
+            record_path: None,
+            tag_element: false,
+            namespaces: NamespacePolicy::Keep,
             trim_text: true,
             include_attributes: true,
             expand_entities: false,
@@ -36,31 +68,236 @@ impl Default for XmlConfig {
     }
 }
 
+/// How [`XmlParser`] names keys for namespaced elements and attributes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NamespacePolicy {
+    /// Keys as written, such as `g:price`; `xmlns` declarations are kept as
+    /// `@xmlns:g` attributes.
+    #[default]
+    Keep,
+    /// Local names only, such as `price`, without `xmlns` declarations.
+    Strip,
+    /// The namespace URI in braces before the local name, such as
+    /// `{http://base.google.com/ns/1.0}price`, without `xmlns` declarations.
+    /// Prefixes declared outside the record, e.g. on the document root,
+    /// are resolved too.
+    Expand,
+}
+
+/// Namespace of the `xml:` prefix, which needs no declaration.
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+fn is_namespace_declaration(attribute: &str) -> bool {
+    attribute == "xmlns" || attribute.starts_with("xmlns:")
+}
+
+/// An element open around the current position of an [`XmlParser`].
+#[derive(Debug, Clone, PartialEq)]
+struct OpenElement {
+    name: String,
+    /// Namespaces it declares, as `(prefix, uri)`; the prefix of a default
+    /// namespace is empty.
+    namespaces: Vec<(String, String)>,
+}
+
+/// The `xmlns` and `xmlns:prefix` attributes in the text of a start tag.
+fn namespace_declarations(tag: &str) -> Vec<(String, String)> {
+    let mut declarations = Vec::new();
+    let mut rest = tag;
+    while let Some(pos) = rest.find("xmlns") {
+        let preceded_by_space = rest[..pos].ends_with(|c: char| c.is_ascii_whitespace());
+        rest = &rest[pos + "xmlns".len()..];
+        if !preceded_by_space {
+            continue;
+        }
+        let Some((name, value)) = rest.split_once('=') else {
+            break;
+        };
+        let prefix = match name.trim_end().strip_prefix(':') {
+            Some(prefix) => prefix,
+            None if name.trim().is_empty() => "",
+            None => continue,
+        };
+        let value = value.trim_start();
+        let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            continue;
+        };
+        let Some(end) = value[1..].find(quote) else {
+            break;
+        };
+        declarations.push((prefix.to_string(), value[1..end + 1].to_string()));
+        rest = &value[end + 2..];
+    }
+    declarations
+}
+
+/// A record element to extract, from `XmlConfig::record_element(s)`.
+#[derive(Debug, Clone)]
+struct RecordMatcher {
+    /// Elements expected directly above the record, outermost first.
+    parents: Vec<String>,
+    /// Whether `parents` start at the document root, for paths given as
+    /// `/root/.../name`.
+    rooted: bool,
+    start_tag: String,
+    end_tag: String,
+}
+
+impl RecordMatcher {
+    fn new(path: &str) -> Self {
+        let mut parents: Vec<String> = path
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+        let name = parents.pop().unwrap_or_default();
+        Self {
+            parents,
+            rooted: path.starts_with('/'),
+            start_tag: format!("<{name}"),
+            end_tag: format!("</{name}>"),
+        }
+    }
+
+    /// Position of the next start tag of this element in `content` from `from`.
+    fn find_start(&self, content: &str, mut from: usize) -> Option<usize> {
+        loop {
+            let start = from + content[from..].find(&self.start_tag)?;
+            // Don't match "<character" inside "<characters>"
+            let after_tag = content.as_bytes().get(start + self.start_tag.len());
+            if after_tag.is_some_and(|b| *b == b'>' || *b == b'/' || b.is_ascii_whitespace()) {
+                return Some(start);
+            }
+            from = start + 1;
+        }
+    }
+
+    /// End of the element starting at `start`, past its end tag. Elements of
+    /// the same name nested inside it are skipped.
+    fn find_end(&self, content: &str, start: usize) -> Option<usize> {
+        let mut from = start + self.start_tag.len();
+        let mut depth = 0;
+        loop {
+            let end = from + content[from..].find(&self.end_tag)?;
+            let mut nested_from = from;
+            while let Some(nested) = self.find_start(&content[..end], nested_from) {
+                let tag_end = nested + content[nested..end].find('>').unwrap_or(end - nested);
+                if !content[..tag_end].ends_with('/') {
+                    depth += 1;
+                }
+                nested_from = nested + 1;
+            }
+            from = end + self.end_tag.len();
+            if depth == 0 {
+                return Some(from);
+            }
+            depth -= 1;
+        }
+    }
+
+    fn needs_parents(&self) -> bool {
+        self.rooted || !self.parents.is_empty()
+    }
+
+    fn matches_parents(&self, open_elements: &[OpenElement]) -> bool {
+        let names = open_elements.iter().map(|open| &open.name);
+        if self.rooted {
+            names.eq(&self.parents)
+        } else {
+            open_elements.len() >= self.parents.len()
+                && names.skip(open_elements.len() - self.parents.len()).eq(&self.parents)
+        }
+    }
+}
+
+/// Follow the start and end tags in `xml`, which lies between records, to
+/// keep `open_elements` up to date. Returns the length of `xml` read, which
+/// stops short at a comment, CDATA section or tag left open at its end.
+fn track_open_elements(open_elements: &mut Vec<OpenElement>, xml: &str) -> usize {
+    let mut rest = xml;
+    while let Some(pos) = rest.find('<') {
+        let tag = &rest[pos..];
+        let close = if tag.starts_with("<!--") {
+            "-->"
+        } else if tag.starts_with("<![CDATA[") {
+            "]]>"
+        } else {
+            ">"
+        };
+        let Some(end) = tag.find(close) else {
+            return xml.len() - tag.len();
+        };
+        rest = &tag[end + close.len()..];
+        if tag.starts_with("<!") || tag.starts_with("<?") {
+            continue;
+        }
+
+        let inner = &tag[1..end];
+        let name = |text: &str| {
+            let end = text.find(|c: char| c.is_ascii_whitespace() || c == '/').unwrap_or(text.len());
+            text[..end].to_string()
+        };
+        if let Some(closing) = inner.strip_prefix('/') {
+            let closing = name(closing);
+            if let Some(index) = open_elements.iter().rposition(|open| open.name == closing) {
+                open_elements.truncate(index);
+            }
+        } else if !inner.ends_with('/') {
+            open_elements.push(OpenElement {
+                name: name(inner),
+                namespaces: namespace_declarations(inner),
+            });
+        }
+    }
+    xml.len()
+}
+
 /// High-performance streaming XML parser using SAX-like event model
 /// Converts XML to NDJSON by extracting record elements
 /// Uses SIMD-optimized quick-xml and arena allocator for performance
 pub struct XmlParser {
     config: XmlConfig,
+    matchers: Vec<RecordMatcher>,
+    /// Elements open around the start of `partial_buffer`, when a record
+    /// element is given as a path or namespaces are expanded.
+    open_elements: Vec<OpenElement>,
     partial_buffer: Vec<u8>,
     output_buffer: Vec<u8>,
     chunk_target_bytes: usize,
     record_count: usize,
     // Arena allocator for temporary allocations during parsing
     arena: Bump,
+    /// Depth and value size caps; the converter enforces the other limits
+    limits: ResourceLimits,
 }
 
 impl XmlParser {
     pub fn new(config: XmlConfig, chunk_target_bytes: usize) -> Self {
+        let matchers = std::iter::once(config.main_record_element())
+            .chain(config.record_elements.iter().map(String::as_str))
+            .map(RecordMatcher::new)
+            .collect();
         Self {
             config,
+            matchers,
+            open_elements: Vec::new(),
             partial_buffer: Vec::new(),
             output_buffer: Vec::with_capacity(chunk_target_bytes),
             chunk_target_bytes,
             record_count: 0,
             arena: Bump::with_capacity(64 * 1024), // 64KB arena for temp allocations
+            limits: ResourceLimits::default(),
         }
     }
 
+    /// Reject records nested deeper than `limits.max_depth` or holding text or
+    /// attribute values larger than `limits.max_field_bytes`.
+    pub fn with_limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Process XML chunk and convert to NDJSON
     pub fn push_to_ndjson(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
         // Append chunk to partial buffer
@@ -100,79 +337,92 @@ impl XmlParser {
         
         // Find all complete record elements using string matching
         // This approach is more reliable for streaming than using quick-xml on partial buffers
-        let record_tag_start = format!("<{}", self.config.record_element);
-        let record_tag_end = format!("</{}>", self.config.record_element);
-        
+        let track_parents = self.matchers.iter().any(RecordMatcher::needs_parents)
+            || self.config.namespaces == NamespacePolicy::Expand;
+        let mut open_elements = self.open_elements.clone();
+        let mut open_at_processed = open_elements.clone();
+        let mut tracked_up_to = 0;
+        // Next start tag of each matcher; `Some(None)` once there is none left
+        let mut next_starts: Vec<Option<Option<usize>>> = vec![None; self.matchers.len()];
+
         let mut processed_up_to = 0;
         let mut search_start = 0;
-        
-        loop {
-            // Find the next record start
-            if let Some(record_start) = content[search_start..].find(&record_tag_start) {
-                let record_start_abs = search_start + record_start;
 
-                // Ensure the found match is a true tag start and not a substring
-                // e.g. don't match "<character" inside "<characters>". Check the
-                // byte immediately after the tag name is whitespace, '>' or '/'.
-                let after_tag_pos = record_start_abs + record_tag_start.len();
-                let is_tag_boundary = content.as_bytes().get(after_tag_pos).map_or(false, |b| {
-                    *b == b'>' || *b == b'/' || b.is_ascii_whitespace()
-                });
-
-                if !is_tag_boundary {
-                    // Continue searching after this position
+        loop {
+            // Find the next record start of any record element
+            let mut next = None;
+            for (index, matcher) in self.matchers.iter().enumerate() {
+                let cached = next_starts[index].filter(|start| start.is_none_or(|start| start >= search_start));
+                let start = cached.unwrap_or_else(|| matcher.find_start(content, search_start));
+                next_starts[index] = Some(start);
+                if let Some(start) = start {
+                    if next.is_none_or(|(first, _)| start < first) {
+                        next = Some((start, index));
+                    }
+                }
+            }
+            let Some((record_start_abs, index)) = next else {
+                break;
+            };
+            let matcher = &self.matchers[index];
+
+            if track_parents {
+                let between = &content[tracked_up_to..record_start_abs];
+                tracked_up_to += track_open_elements(&mut open_elements, between);
+                // Inside a comment, or not directly inside the parents; its
+                // tags are tracked with what follows
+                if tracked_up_to < record_start_abs || !matcher.matches_parents(&open_elements) {
                     search_start = record_start_abs + 1;
                     continue;
                 }
+            }
 
-                // Find the matching end tag for this record
-                let search_from = record_start_abs + record_tag_start.len();
-                if let Some(record_end) = content[search_from..].find(&record_tag_end) {
-                    let record_end_abs = search_from + record_end + record_tag_end.len();
-                    
-                    // Extract the complete record element
-                    let record_xml = &content[record_start_abs..record_end_abs];
+            // Find the matching end tag for this record
+            let Some(record_end_abs) = matcher.find_end(content, record_start_abs) else {
+                // Incomplete record - stop processing and keep this data for next chunk
+                break;
+            };
 
-                    // Test-only debug: log the extracted fragment and positions
-                    if cfg!(test) {
-                        let snippet = if record_xml.len() > 200 {
-                            &record_xml[..200]
-                        } else {
-                            record_xml
-                        };
-                        println!("[xml_parser debug] record_start_abs={} record_end_abs={} fragment='{}'", record_start_abs, record_end_abs, snippet);
-                    }
+            // Extract the complete record element
+            let record_xml = &content[record_start_abs..record_end_abs];
 
-                    // Parse this single complete record using quick-xml
-                    let parsed_record = self.parse_single_record(record_xml)?;
-                    if !parsed_record.is_empty() {
-                        output.extend_from_slice(&parsed_record);
-                        output.push(b'\n');
-                        self.record_count += 1;
-                    }
-                    
-                    processed_up_to = record_end_abs;
-                    search_start = record_end_abs;
+            // Test-only debug: log the extracted fragment and positions
+            if cfg!(test) {
+                let snippet = if record_xml.len() > 200 {
+                    &record_xml[..200]
                 } else {
-                    // Incomplete record - stop processing and keep this data for next chunk
-                    break;
-                }
-            } else {
-                // No more record starts found
-                break;
+                    record_xml
+                };
+                println!("[xml_parser debug] record_start_abs={} record_end_abs={} fragment='{}'", record_start_abs, record_end_abs, snippet);
+            }
+
+            // Parse this single complete record using quick-xml
+            let parsed_record = self.parse_single_record(record_xml, &open_elements)?;
+            if !parsed_record.is_empty() {
+                output.extend_from_slice(&parsed_record);
+                output.push(b'\n');
+                self.record_count += 1;
+            }
+
+            processed_up_to = record_end_abs;
+            search_start = record_end_abs;
+            tracked_up_to = record_end_abs;
+            if track_parents {
+                open_at_processed.clone_from(&open_elements);
             }
         }
-        
+
         // Remove the data we've successfully processed
         if processed_up_to > 0 {
             self.partial_buffer.drain(0..processed_up_to);
+            self.open_elements = open_at_processed;
         }
 
         Ok(())
     }
-    
+
     /// Parse a single complete record element using quick-xml
-    fn parse_single_record(&self, record_xml: &str) -> Result<Vec<u8>> {
+    fn parse_single_record(&self, record_xml: &str, open_elements: &[OpenElement]) -> Result<Vec<u8>> {
         let mut reader = Reader::from_str(record_xml);
         reader.config_mut().trim_text(self.config.trim_text);
         reader.config_mut().expand_empty_elements = true;
@@ -181,22 +431,52 @@ impl XmlParser {
         let mut element_stack: Vec<(String, HashMap<String, JsonValue>)> = Vec::new();
         let mut current_text = String::new();
         let mut root_found = false;
+        // Whether the record element holds elements, and not just text
+        let mut root_has_children = false;
+        // Namespaces in scope, and how many there were outside each open element
+        let mut namespaces: Vec<(String, String)> = open_elements
+            .iter()
+            .flat_map(|open| open.namespaces.iter().cloned())
+            .collect();
+        let mut namespace_marks: Vec<usize> = Vec::new();
         
         loop {
             match reader.read_event_into(&mut buf) {
                 Ok(Event::Start(e)) => {
-                    let name = std::str::from_utf8(e.name().as_ref())?.to_string();
+                    namespace_marks.push(namespaces.len());
+                    if self.config.namespaces == NamespacePolicy::Expand {
+                        for attr in e.attributes().flatten() {
+                            let key = std::str::from_utf8(attr.key.as_ref())?;
+                            if is_namespace_declaration(key) {
+                                let prefix = key.strip_prefix("xmlns:").unwrap_or_default();
+                                let uri = std::str::from_utf8(&attr.value)?;
+                                namespaces.push((prefix.to_string(), uri.to_string()));
+                            }
+                        }
+                    }
+                    let name = self.key_for(std::str::from_utf8(e.name().as_ref())?, &namespaces, false);
+                    self.limits.check_depth(element_stack.len() + 1)?;
                     
                     if !root_found {
                         // This should be our record element
                         root_found = true;
                         let mut root = HashMap::new();
+                        if self.config.tag_element {
+                            root.insert("_element".to_string(), JsonValue::String(name.clone()));
+                        }
                         
                         // Include attributes if configured
                         if self.config.include_attributes {
                             for attr in e.attributes() {
                                 if let Ok(attr) = attr {
-                                    let key = format!("@{}", std::str::from_utf8(attr.key.as_ref())?);
+                                    self.limits.check_field(attr.value.len())?;
+                                    let key = std::str::from_utf8(attr.key.as_ref())?;
+                                    if is_namespace_declaration(key)
+                                        && self.config.namespaces != NamespacePolicy::Keep
+                                    {
+                                        continue;
+                                    }
+                                    let key = format!("@{}", self.key_for(key, &namespaces, true));
                                     let value = std::str::from_utf8(&attr.value)?.to_string();
                                     root.insert(key, JsonValue::String(value));
                                 }
@@ -206,16 +486,22 @@ impl XmlParser {
                         element_stack.push((name, root));
                     } else {
                         // Child element
+                        root_has_children |= element_stack.len() == 1;
                         element_stack.push((name, HashMap::new()));
                         current_text.clear();
                     }
                 }
                 Ok(Event::End(e)) => {
-                    let name = std::str::from_utf8(e.name().as_ref())?.to_string();
+                    let name = self.key_for(std::str::from_utf8(e.name().as_ref())?, &namespaces, false);
+                    namespaces.truncate(namespace_marks.pop().unwrap_or_default());
                     
-                    if element_stack.len() == 1 && name == self.config.record_element {
+                    if element_stack.len() == 1 && name == element_stack[0].0 {
                         // End of root record element
-                        if let Some((_, root_obj)) = element_stack.pop() {
+                        if let Some((_, mut root_obj)) = element_stack.pop() {
+                            // The text of a record without elements, as XmlWriter writes it
+                            if !root_has_children && !current_text.is_empty() {
+                                root_obj.insert("#text".to_string(), JsonValue::String(current_text));
+                            }
                             let mut output = Vec::new();
                             self.json_value_to_output(&JsonValue::Object(root_obj), &mut output)?;
                             return Ok(output);
@@ -240,6 +526,7 @@ impl XmlParser {
                     }
                 }
                 Ok(Event::Text(e)) => {
+                    self.limits.check_field(e.len())?;
                     let text = e
                         .unescape()
                         .map_err(|e| ConvertError::XmlParse(e.to_string()))?;
@@ -262,6 +549,35 @@ impl XmlParser {
         Err(ConvertError::XmlParse("Failed to parse XML record".to_string()))
     }
 
+    /// Key for an element or attribute `name` under the namespace policy,
+    /// with `namespaces` in scope. Unprefixed attributes have no namespace.
+    fn key_for(&self, name: &str, namespaces: &[(String, String)], attribute: bool) -> String {
+        let (prefix, local) = name.split_once(':').unwrap_or(("", name));
+        match self.config.namespaces {
+            NamespacePolicy::Keep => name.to_string(),
+            NamespacePolicy::Strip => local.to_string(),
+            NamespacePolicy::Expand if attribute && prefix.is_empty() => local.to_string(),
+            NamespacePolicy::Expand => {
+                let uri = if prefix == "xml" {
+                    Some(XML_NAMESPACE)
+                } else {
+                    namespaces
+                        .iter()
+                        .rev()
+                        .find(|(declared, _)| declared == prefix)
+                        .map(|(_, uri)| uri.as_str())
+                };
+                match uri {
+                    Some("") => local.to_string(),
+                    Some(uri) => format!("{{{uri}}}{local}"),
+                    // An undeclared prefix is kept as written
+                    None if prefix.is_empty() => local.to_string(),
+                    None => name.to_string(),
+                }
+            }
+        }
+    }
+
     /// Convert common backslash escape sequences (e.g. `\"` -> `\"`) into
     /// their unescaped character equivalents. This helps when test data or
     /// upstream producers include C-style backslash escaping inside element
@@ -389,6 +705,12 @@ impl XmlParser {
         self.partial_buffer.len()
     }
 
+    /// Drop the unparsed input, so the next chunk is read as a new document.
+    pub fn discard_partial(&mut self) {
+        self.partial_buffer.clear();
+        self.open_elements.clear();
+    }
+
     pub fn record_count(&self) -> usize {
         self.record_count
     }
@@ -437,25 +759,83 @@ impl XmlParser {
     }
 }
 
+/// XML writer output settings
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct XmlOutputConfig {
+    pub root_element: String,
+    pub record_element: String,
+    /// Spaces per nesting level; every element still starts a new line.
+    pub indent: usize,
+    /// Start the document with `<?xml version="1.0" encoding="UTF-8"?>`.
+    pub declaration: bool,
+    /// Write `@`-prefixed keys as attributes of the record element.
+    pub attributes: bool,
+    /// Namespaces declared on the root element, by prefix; an empty prefix
+    /// declares the default namespace. Keys like `g:price` are written as
+    /// they are, and `{uri}price` keys, as read with
+    /// [`NamespacePolicy::Expand`], get the prefix declared for `uri`.
+    pub namespaces: BTreeMap<String, String>,
+    /// Element for each array item, nested in an element named by the key:
+    /// `{"tags":["a","b"]}` gives `<tags><item>a</item><item>b</item></tags>`.
+    /// Without it, each item repeats the key's element, `<tags>a</tags><tags>b</tags>`,
+    /// as [`XmlParser`] reads repeated elements back into an array.
+    pub item_element: Option<String>,
+}
+
+impl Default for XmlOutputConfig {
+    fn default() -> Self {
+        Self {
+            root_element: "root".to_string(),
+            record_element: "record".to_string(),
+            indent: 2,
+            declaration: false,
+            attributes: false,
+            namespaces: BTreeMap::new(),
+            item_element: None,
+        }
+    }
+}
+
 /// XML writer that converts JSON objects to XML format
 pub struct XmlWriter {
-    root_element: String,
-    record_element: String,
+    config: XmlOutputConfig,
     header_written: bool,
+    float_format: Option<FloatFormat>,
+    output_columns: Option<Vec<OutputColumn>>,
 }
 
 impl XmlWriter {
     pub fn new() -> Self {
         Self {
-            root_element: "root".to_string(),
-            record_element: "record".to_string(),
+            config: XmlOutputConfig::default(),
             header_written: false,
+            float_format: None,
+            output_columns: None,
         }
     }
 
+    pub fn with_config(mut self, config: XmlOutputConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Write non-integer numbers with `format` instead of as they come.
+    pub fn with_float_format(mut self, format: Option<FloatFormat>) -> Self {
+        self.float_format = format;
+        self
+    }
+
+    /// Write only these fields of each record, renamed, as the record's
+    /// children in this order.
+    pub fn with_output_columns(mut self, columns: Option<Vec<OutputColumn>>) -> Self {
+        self.output_columns = columns;
+        self
+    }
+
     pub fn with_elements(mut self, root: String, record: String) -> Self {
-        self.root_element = root;
-        self.record_element = record;
+        self.config.root_element = root;
+        self.config.record_element = record;
         self
     }
 
@@ -465,39 +845,31 @@ impl XmlWriter {
 
         // Write header on first call
         if !self.header_written {
-            write!(output, "<{}>\n", self.root_element).ok();
+            if self.config.declaration {
+                output.extend_from_slice(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+            }
+            write!(output, "<{}", self.config.root_element).ok();
+            for (prefix, uri) in &self.config.namespaces {
+                let attribute = if prefix.is_empty() { "xmlns".to_string() } else { format!("xmlns:{prefix}") };
+                write!(output, " {}=\"{}\"", escape_xml(&attribute), escape_xml(uri)).ok();
+            }
+            output.extend_from_slice(b">\n");
             self.header_written = true;
         }
 
         // Parse the JSON to extract fields
         if let Ok(value) = serde_json::from_str::<serde_json::Value>(json_line) {
             if let Some(obj) = value.as_object() {
-                write!(output, "  <{}>\n", self.record_element).ok();
-                
-                for (key, val) in obj {
-                    let xml_key = key.to_string();
-                    let xml_value = match val {
-                        serde_json::Value::String(s) => s.clone(),
-                        serde_json::Value::Number(n) => n.to_string(),
-                        serde_json::Value::Bool(b) => b.to_string(),
-                        serde_json::Value::Null => String::new(),
-                        _ => serde_json::to_string(val).unwrap_or_default(),
-                    };
-                    
-                    // Escape XML special characters
-                    let escaped = xml_key.replace("&", "&amp;")
-                        .replace("<", "&lt;")
-                        .replace(">", "&gt;")
-                        .replace("\"", "&quot;");
-                    let escaped_value = xml_value.replace("&", "&amp;")
-                        .replace("<", "&lt;")
-                        .replace(">", "&gt;")
-                        .replace("\"", "&quot;");
-                    
-                    write!(output, "    <{}>{}</{}>\n", escaped, escaped_value, escaped).ok();
+                let record = &self.config.record_element;
+                match &self.output_columns {
+                    Some(columns) => {
+                        let fields = columns
+                            .iter()
+                            .filter_map(|column| Some((column.name(), obj.get(&column.from)?)));
+                        self.write_object(&mut output, record, fields, 1);
+                    }
+                    None => self.write_object(&mut output, record, fields(obj), 1),
                 }
-                
-                write!(output, "  </{}>\n", self.record_element).ok();
             }
         }
 
@@ -508,8 +880,124 @@ impl XmlWriter {
     pub fn finish(&self) -> Result<Vec<u8>> {
         let mut output = Vec::new();
         if self.header_written {
-            write!(output, "</{}>\n", self.root_element).ok();
+            write!(output, "</{}>\n", self.config.root_element).ok();
         }
         Ok(output)
     }
+}
+
+impl XmlWriter {
+    /// Element or attribute name for `key`, with a `{uri}` in front replaced
+    /// by the prefix declared for it.
+    fn name(&self, key: &str) -> String {
+        let Some((uri, local)) = key.strip_prefix('{').and_then(|rest| rest.split_once('}')) else {
+            return key.to_string();
+        };
+        match self.config.namespaces.iter().find(|(_, declared)| declared.as_str() == uri) {
+            Some((prefix, _)) if prefix.is_empty() => local.to_string(),
+            Some((prefix, _)) => format!("{prefix}:{local}"),
+            None => key.to_string(),
+        }
+    }
+
+    /// Write `value` as elements named `key`, `depth` levels in: objects as
+    /// child elements and arrays as one element per item.
+    fn write_element(&self, output: &mut Vec<u8>, key: &str, value: &serde_json::Value, depth: usize) {
+        let pad = " ".repeat(self.config.indent * depth);
+        let name = escape_xml(&self.name(key));
+        match value {
+            serde_json::Value::Array(items) => match &self.config.item_element {
+                Some(_) if items.is_empty() => {
+                    writeln!(output, "{pad}<{name}></{name}>").ok();
+                }
+                Some(item) => {
+                    writeln!(output, "{pad}<{name}>").ok();
+                    for value in items {
+                        self.write_element(output, item, value, depth + 1);
+                    }
+                    writeln!(output, "{pad}</{name}>").ok();
+                }
+                // Nested arrays are flattened into the same run of elements
+                None => {
+                    for value in items {
+                        self.write_element(output, key, value, depth);
+                    }
+                }
+            },
+            serde_json::Value::Object(fields) if fields.is_empty() => {
+                writeln!(output, "{pad}<{name}></{name}>").ok();
+            }
+            serde_json::Value::Object(object) => self.write_object(output, &name, fields(object), depth),
+            _ => {
+                let text = escape_xml(&self.text(value));
+                writeln!(output, "{pad}<{name}>{text}</{name}>").ok();
+            }
+        }
+    }
+
+    /// Write the element `name` for an object, `depth` levels in. With
+    /// `attributes` set, its `@` keys become attributes and `#text` its text.
+    fn write_object<'a>(
+        &self,
+        output: &mut Vec<u8>,
+        name: &str,
+        fields: impl IntoIterator<Item = (&'a str, &'a serde_json::Value)>,
+        depth: usize,
+    ) {
+        let pad = " ".repeat(self.config.indent * depth);
+        write!(output, "{pad}<{name}").ok();
+        let mut text = None;
+        let mut children = Vec::new();
+        for (key, value) in fields {
+            if !self.config.attributes {
+                children.push((key, value));
+            } else if let Some(attribute) = key.strip_prefix('@') {
+                let value = escape_xml(&self.text(value));
+                write!(output, " {}=\"{}\"", escape_xml(&self.name(attribute)), value).ok();
+            } else if key == "#text" {
+                text = Some(escape_xml(&self.text(value)));
+            } else {
+                children.push((key, value));
+            }
+        }
+        output.push(b'>');
+        if let Some(text) = &text {
+            output.extend_from_slice(text.as_bytes());
+        }
+        // Text alone stays on the element's line, as for a plain field
+        if text.is_some() && children.is_empty() {
+            writeln!(output, "</{name}>").ok();
+            return;
+        }
+        output.push(b'\n');
+        for (key, value) in children {
+            self.write_element(output, key, value, depth + 1);
+        }
+        writeln!(output, "{pad}</{name}>").ok();
+    }
+
+    /// Text of a JSON value; arrays and objects are written as JSON.
+    fn text(&self, value: &serde_json::Value) -> String {
+        match value {
+            serde_json::Value::String(s) => s.clone(),
+            serde_json::Value::Number(n) => FloatFormat::number_text(self.float_format.as_ref(), n),
+            serde_json::Value::Bool(b) => b.to_string(),
+            serde_json::Value::Null => String::new(),
+            _ => serde_json::to_string(value).unwrap_or_default(),
+        }
+    }
+}
+
+/// The members of `object` as [`XmlWriter::write_object`] takes them.
+fn fields(
+    object: &serde_json::Map<String, serde_json::Value>,
+) -> impl Iterator<Item = (&str, &serde_json::Value)> {
+    object.iter().map(|(key, value)| (key.as_str(), value))
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
\ No newline at end of file
@@ -1,7 +1,8 @@
 #[cfg(test)]
 mod xml_parser_tests {
     use wasm_bindgen_test::*;
-    use crate::xml_parser::{XmlParser, XmlConfig, XmlWriter};
+    use crate::xml_parser::{NamespacePolicy, XmlParser, XmlConfig, XmlOutputConfig, XmlWriter};
+    use crate::OutputColumn;
 
     #[wasm_bindgen_test]
     fn test_simple_xml() {
@@ -33,6 +34,97 @@ mod xml_parser_tests {
         assert!(result.contains(&b'{'));
     }
 
+    #[test]
+    fn test_depth_and_value_limits() {
+        let config = XmlConfig {
+            record_element: "row".to_string(),
+            ..Default::default()
+        };
+        let limits = crate::ResourceLimits {
+            max_depth: Some(2),
+            max_field_bytes: Some(8),
+            ..Default::default()
+        };
+        let mut parser = XmlParser::new(config.clone(), 1024).with_limits(limits);
+        assert!(parser.push_to_ndjson(b"<rows><row><name>Ada</name></row>").is_ok());
+        let err = parser.push_to_ndjson(b"<row><a><b>deep</b></a></row>").unwrap_err();
+        assert!(matches!(err, crate::ConvertError::LimitExceeded(_)));
+
+        let mut parser = XmlParser::new(config, 1024).with_limits(limits);
+        let err = parser.push_to_ndjson(b"<row><name>Bartholomew</name></row>").unwrap_err();
+        assert!(matches!(err, crate::ConvertError::LimitExceeded(_)));
+    }
+
+    #[test]
+    fn test_several_record_elements_in_one_pass() {
+        let config = XmlConfig {
+            record_element: "product".to_string(),
+            record_elements: vec!["bundle".to_string()],
+            tag_element: true,
+            ..Default::default()
+        };
+        let mut parser = XmlParser::new(config, 1024);
+        let mut output = parser
+            .push_to_ndjson(b"<feed><product><sku>A</sku></product><bundle><sku>B")
+            .unwrap();
+        output.extend(parser.push_to_ndjson(b"</sku></bundle><product><sku>C</sku></product></feed>").unwrap());
+        output.extend(parser.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\"_element\":\"product\",\"sku\":\"A\"}\n\
+             {\"_element\":\"bundle\",\"sku\":\"B\"}\n\
+             {\"_element\":\"product\",\"sku\":\"C\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_record_element_path_skips_other_parents() {
+        let config = XmlConfig {
+            record_element: "channel/item".to_string(),
+            ..Default::default()
+        };
+        let input: &[u8] = b"<?xml version=\"1.0\"?><rss><channel><title>T</title>\
+            <image><item>logo</item></image>\
+            <item><id>1</id></item><!-- <item> --><related><item>x</item></related>\
+            <item><id>2</id></item></channel></rss>";
+        // Any split keeps track of the parents
+        for split in [1, 30, 60, 90, input.len() - 20] {
+            let mut parser = XmlParser::new(config.clone(), 1024);
+            let mut output = parser.push_to_ndjson(&input[..split]).unwrap();
+            output.extend(parser.push_to_ndjson(&input[split..]).unwrap());
+            output.extend(parser.finish().unwrap());
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                "{\"id\":\"1\"}\n{\"id\":\"2\"}\n",
+                "split at {split}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_record_path_matches_from_the_root_only() {
+        let config = XmlConfig {
+            record_element: "ignored".to_string(),
+            record_path: Some("/Envelope/Body/Response/item".to_string()),
+            ..Default::default()
+        };
+        let input: &[u8] = b"<Envelope><Header><item>h</item></Header><Body><Response>\
+            <item><id>1</id><lines><item>a</item><item/><item>b</item></lines></item>\
+            <Response><item><id>x</id></item></Response>\
+            <item><id>2</id></item></Response></Body></Envelope>";
+        for split in [1, 50, 100, 150, input.len() - 10] {
+            let mut parser = XmlParser::new(config.clone(), 1024);
+            let mut output = parser.push_to_ndjson(&input[..split]).unwrap();
+            output.extend(parser.push_to_ndjson(&input[split..]).unwrap());
+            output.extend(parser.finish().unwrap());
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                "{\"id\":\"1\",\"lines\":{\"item\":[\"a\",\"b\"]}}\n{\"id\":\"2\"}\n",
+                "split at {split}"
+            );
+        }
+    }
+
     #[wasm_bindgen_test]
     fn test_xml_repeated_elements_as_array() {
         let config = XmlConfig {
@@ -190,6 +282,191 @@ mod xml_parser_tests {
         assert!(output_str.contains("&quot;yes&quot;"));
     }
 
+    #[test]
+    fn xml_writer_applies_output_config() {
+        let mut writer = XmlWriter::new().with_config(XmlOutputConfig {
+            root_element: "people".to_string(),
+            record_element: "person".to_string(),
+            indent: 0,
+            declaration: true,
+            attributes: true,
+            ..XmlOutputConfig::default()
+        });
+        let mut output = writer
+            .process_json_line(r#"{"@id":"7","@note":"a \"b\"","name":"Ada"}"#)
+            .unwrap();
+        output.extend(writer.finish().unwrap());
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<people>\n<person id=\"7\" note=\"a &quot;b&quot;\">\n<name>Ada</name>\n</person>\n</people>\n"
+        );
+    }
+
+    #[test]
+    fn test_namespace_policies() {
+        let input: &[u8] = b"<rss xmlns:g=\"http://base.google.com/ns/1.0\"><channel>\
+            <item xmlns:x=\"urn:x\" x:id=\"7\"><title>Mug</title><g:price>9.50 EUR</g:price></item>\
+            </channel></rss>";
+        let convert = |namespaces| {
+            let config = XmlConfig {
+                record_element: "item".to_string(),
+                namespaces,
+                ..Default::default()
+            };
+            // Split inside the root's declaration
+            let mut parser = XmlParser::new(config, 1024);
+            let mut output = parser.push_to_ndjson(&input[..20]).unwrap();
+            output.extend(parser.push_to_ndjson(&input[20..]).unwrap());
+            output.extend(parser.finish().unwrap());
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(
+            convert(NamespacePolicy::Keep),
+            "{\"@x:id\":\"7\",\"@xmlns:x\":\"urn:x\",\"g:price\":\"9.50 EUR\",\"title\":\"Mug\"}\n"
+        );
+        assert_eq!(
+            convert(NamespacePolicy::Strip),
+            "{\"@id\":\"7\",\"price\":\"9.50 EUR\",\"title\":\"Mug\"}\n"
+        );
+        assert_eq!(
+            convert(NamespacePolicy::Expand),
+            "{\"@{urn:x}id\":\"7\",\"title\":\"Mug\",\"{http://base.google.com/ns/1.0}price\":\"9.50 EUR\"}\n"
+        );
+    }
+
+    #[test]
+    fn xml_writer_declares_namespaces_on_the_root() {
+        let mut namespaces = std::collections::BTreeMap::new();
+        namespaces.insert("g".to_string(), "http://base.google.com/ns/1.0".to_string());
+        let mut writer = XmlWriter::new().with_config(XmlOutputConfig {
+            root_element: "channel".to_string(),
+            record_element: "item".to_string(),
+            indent: 0,
+            namespaces,
+            ..XmlOutputConfig::default()
+        });
+        let mut output = writer
+            .process_json_line(r#"{"g:id":"1","{http://base.google.com/ns/1.0}price":"9.50 EUR"}"#)
+            .unwrap();
+        output.extend(writer.finish().unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<channel xmlns:g=\"http://base.google.com/ns/1.0\">\n<item>\n<g:id>1</g:id>\n<g:price>9.50 EUR</g:price>\n</item>\n</channel>\n"
+        );
+    }
+
+    #[test]
+    fn xml_writer_nests_objects_and_arrays() {
+        let line = r#"{"dims":{"h":2,"w":{}},"id":"1","tags":["a",["b"]],"none":[]}"#;
+        let write = |item_element: Option<&str>| {
+            let mut writer = XmlWriter::new().with_config(XmlOutputConfig {
+                indent: 1,
+                item_element: item_element.map(str::to_string),
+                ..XmlOutputConfig::default()
+            });
+            let mut output = writer.process_json_line(line).unwrap();
+            output.extend(writer.finish().unwrap());
+            String::from_utf8(output).unwrap()
+        };
+
+        let repeated = write(None);
+        assert_eq!(
+            repeated,
+            "<root>\n <record>\n  <dims>\n   <h>2</h>\n   <w></w>\n  </dims>\n  <id>1</id>\n  \
+             <tags>a</tags>\n  <tags>b</tags>\n </record>\n</root>\n"
+        );
+        assert_eq!(
+            write(Some("item")),
+            "<root>\n <record>\n  <dims>\n   <h>2</h>\n   <w></w>\n  </dims>\n  <id>1</id>\n  \
+             <none></none>\n  <tags>\n   <item>a</item>\n   <item>\n    <item>b</item>\n   </item>\n  </tags>\n \
+             </record>\n</root>\n"
+        );
+
+        // Repeated elements read back as the array they were written from
+        let mut parser = XmlParser::new(
+            XmlConfig {
+                record_element: "record".to_string(),
+                ..Default::default()
+            },
+            1024,
+        );
+        let mut output = parser.push_to_ndjson(repeated.as_bytes()).unwrap();
+        output.extend(parser.finish().unwrap());
+        let record: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(record["tags"], serde_json::json!(["a", "b"]));
+        assert_eq!(record["dims"]["h"], "2");
+    }
+
+    #[test]
+    fn xml_writer_writes_attributes_and_text_of_nested_elements() {
+        let mut writer = XmlWriter::new().with_config(XmlOutputConfig {
+            root_element: "items".to_string(),
+            record_element: "item".to_string(),
+            indent: 0,
+            attributes: true,
+            ..XmlOutputConfig::default()
+        });
+        let mut output = writer
+            .process_json_line(r##"{"@id":"7","price":{"@currency":"EUR","#text":9.5},"size":{"#text":"L"}}"##)
+            .unwrap();
+        output.extend(writer.process_json_line(r##"{"@id":"8","#text":"sold out"}"##).unwrap());
+        output.extend(writer.finish().unwrap());
+        let xml = String::from_utf8(output).unwrap();
+        assert_eq!(
+            xml,
+            "<items>\n<item id=\"7\">\n<price currency=\"EUR\">9.5</price>\n<size>L</size>\n</item>\n\
+             <item id=\"8\">sold out</item>\n</items>\n"
+        );
+
+        // Record attributes survive XML -> NDJSON -> XML
+        let input = "<items>\n<item id=\"7\">\n<name>Mug</name>\n</item>\n</items>\n";
+        let mut parser = XmlParser::new(
+            XmlConfig {
+                record_element: "item".to_string(),
+                ..Default::default()
+            },
+            1024,
+        );
+        let mut ndjson = parser.push_to_ndjson(input.as_bytes()).unwrap();
+        ndjson.extend(parser.finish().unwrap());
+        let mut writer = XmlWriter::new().with_config(XmlOutputConfig {
+            root_element: "items".to_string(),
+            record_element: "item".to_string(),
+            indent: 0,
+            attributes: true,
+            ..XmlOutputConfig::default()
+        });
+        let mut output = writer.process_json_line(std::str::from_utf8(&ndjson).unwrap().trim_end()).unwrap();
+        output.extend(writer.finish().unwrap());
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+    }
+
+    #[test]
+    fn xml_writer_writes_output_columns_in_order() {
+        let columns = vec![
+            OutputColumn::new("name", "Name"),
+            OutputColumn::new("id", "@ID"),
+            OutputColumn::new("missing", "Missing"),
+            OutputColumn::new("a", "A"),
+        ];
+        let mut writer = XmlWriter::new()
+            .with_config(XmlOutputConfig {
+                indent: 0,
+                attributes: true,
+                ..XmlOutputConfig::default()
+            })
+            .with_output_columns(Some(columns));
+        let output = writer
+            .process_json_line(r#"{"a":{"b":1},"id":7,"name":"Ada","skip":true}"#)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "<root>\n<record ID=\"7\">\n<Name>Ada</Name>\n<A>\n<b>1</b>\n</A>\n</record>\n"
+        );
+    }
+
     #[wasm_bindgen_test]
     fn xml_writer_finish_without_header_is_empty() {
         let writer = XmlWriter::new();
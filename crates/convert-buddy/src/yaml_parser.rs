@@ -0,0 +1,256 @@
+use crate::error::{ConvertError, Result};
+use serde::Deserialize;
+use serde_json::{Map, Number, Value};
+use serde_yaml::Value as YamlValue;
+
+/// Reads YAML records as NDJSON.
+///
+/// A document whose top level is a block sequence (`- ` items at column 0) is
+/// streamed: each item is parsed and written as soon as the next one starts.
+/// Any other document is buffered and read at the end; its records are the
+/// items of a top-level sequence, or the document itself. Several documents
+/// separated by `---` are read one after another.
+///
+/// Items are parsed on their own, so an alias can only refer to an anchor in
+/// the same record. Non-string mapping keys are written as their YAML text.
+pub struct YamlParser {
+    partial_line: Vec<u8>,
+    mode: Mode,
+    /// Lines of the item or document being collected.
+    text: String,
+    started: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Before the first content line of a document.
+    Start,
+    /// Streaming the items of a top-level block sequence.
+    Sequence,
+    /// Buffering a document that isn't a block sequence.
+    Document,
+}
+
+impl YamlParser {
+    pub fn new() -> Self {
+        Self {
+            partial_line: Vec::new(),
+            mode: Mode::Start,
+            text: String::new(),
+            started: false,
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        let mut data = std::mem::take(&mut self.partial_line);
+        data.extend_from_slice(chunk);
+        let complete = memchr::memrchr(b'\n', &data).map_or(0, |pos| pos + 1);
+        self.partial_line = data[complete..].to_vec();
+
+        let mut output = Vec::new();
+        for line in std::str::from_utf8(&data[..complete])?.split_inclusive('\n') {
+            self.process_line(line, &mut output)?;
+        }
+        Ok(output)
+    }
+
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let line = std::mem::take(&mut self.partial_line);
+        if !line.is_empty() {
+            self.process_line(std::str::from_utf8(&line)?, &mut output)?;
+        }
+        self.end_document(&mut output)?;
+        Ok(output)
+    }
+
+    pub fn buffered_bytes(&self) -> usize {
+        self.partial_line.len() + self.text.len()
+    }
+
+    /// Drop the unparsed input, so the next chunk is read as a new stream.
+    pub fn discard_partial(&mut self) {
+        *self = Self::new();
+    }
+
+    fn process_line(&mut self, mut line: &str, output: &mut Vec<u8>) -> Result<()> {
+        if !self.started {
+            self.started = true;
+            line = line.strip_prefix('\u{FEFF}').unwrap_or(line);
+        }
+        let content = line.trim_end_matches(['\n', '\r']);
+
+        if content == "..." || content == "---" || content.starts_with("--- ") {
+            self.end_document(output)?;
+            // Content after the marker belongs to the new document
+            match content.strip_prefix("--- ") {
+                Some(rest) if !rest.trim().is_empty() => {
+                    self.mode = Mode::Document;
+                    self.text.push_str(rest);
+                    self.text.push('\n');
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        match self.mode {
+            Mode::Start => {
+                let trimmed = content.trim_start();
+                if trimmed.is_empty() || trimmed.starts_with('#') || content.starts_with('%') {
+                    return Ok(());
+                }
+                self.mode = if is_item_start(content) {
+                    Mode::Sequence
+                } else {
+                    Mode::Document
+                };
+                self.text.push_str(line);
+            }
+            Mode::Sequence => {
+                if is_item_start(content) {
+                    self.flush_item(output)?;
+                }
+                self.text.push_str(line);
+            }
+            Mode::Document => self.text.push_str(line),
+        }
+        Ok(())
+    }
+
+    /// Write the item collected so far, a one-item block sequence.
+    fn flush_item(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        let text = std::mem::take(&mut self.text);
+        let items: Vec<YamlValue> = serde_yaml::from_str(&text).map_err(yaml_error)?;
+        for item in items {
+            write_record(item, output)?;
+        }
+        Ok(())
+    }
+
+    fn end_document(&mut self, output: &mut Vec<u8>) -> Result<()> {
+        match std::mem::replace(&mut self.mode, Mode::Start) {
+            Mode::Start => {}
+            Mode::Sequence => self.flush_item(output)?,
+            Mode::Document => {
+                let text = std::mem::take(&mut self.text);
+                for document in serde_yaml::Deserializer::from_str(&text) {
+                    match YamlValue::deserialize(document).map_err(yaml_error)? {
+                        YamlValue::Null => {}
+                        YamlValue::Sequence(items) => {
+                            for item in items {
+                                write_record(item, output)?;
+                            }
+                        }
+                        record => write_record(record, output)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for YamlParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A `- ` item of a block sequence at column 0.
+fn is_item_start(line: &str) -> bool {
+    line == "-" || line.starts_with("- ") || line.starts_with("-\t")
+}
+
+fn write_record(record: YamlValue, output: &mut Vec<u8>) -> Result<()> {
+    serde_json::to_writer(&mut *output, &to_json(record)?)
+        .map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+    output.push(b'\n');
+    Ok(())
+}
+
+fn to_json(value: YamlValue) -> Result<Value> {
+    Ok(match value {
+        YamlValue::Null => Value::Null,
+        YamlValue::Bool(b) => Value::Bool(b),
+        YamlValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Value::from(i)
+            } else if let Some(u) = n.as_u64() {
+                Value::from(u)
+            } else {
+                // .nan and .inf have no JSON form
+                n.as_f64().and_then(Number::from_f64).map_or(Value::Null, Value::Number)
+            }
+        }
+        YamlValue::String(s) => Value::String(s),
+        YamlValue::Sequence(items) => {
+            Value::Array(items.into_iter().map(to_json).collect::<Result<_>>()?)
+        }
+        YamlValue::Mapping(mapping) => {
+            let mut object = Map::new();
+            for (key, value) in mapping {
+                let key = match key {
+                    YamlValue::String(s) => s,
+                    other => serde_yaml::to_string(&other)
+                        .map_err(yaml_error)?
+                        .trim_end()
+                        .to_string(),
+                };
+                object.insert(key, to_json(value)?);
+            }
+            Value::Object(object)
+        }
+        YamlValue::Tagged(tagged) => to_json(tagged.value)?,
+    })
+}
+
+fn yaml_error(error: serde_yaml::Error) -> ConvertError {
+    ConvertError::YamlParse(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_in_chunks(input: &str, size: usize) -> String {
+        let mut parser = YamlParser::new();
+        let mut output = Vec::new();
+        for chunk in input.as_bytes().chunks(size) {
+            output.extend(parser.push(chunk).unwrap());
+        }
+        output.extend(parser.finish().unwrap());
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn streams_block_sequence_items() {
+        let input = "# people\n---\n- name: Ada\n  langs: [en, fr]\n\n- name: \"Grace\"\n  age: 85\n  1: one\n";
+        let expected = "{\"langs\":[\"en\",\"fr\"],\"name\":\"Ada\"}\n{\"1\":\"one\",\"age\":85,\"name\":\"Grace\"}\n";
+        for size in [1, 7, 1024] {
+            assert_eq!(parse_in_chunks(input, size), expected);
+        }
+
+        // The first item is written once the second one starts
+        let mut parser = YamlParser::new();
+        assert_eq!(parser.push(b"- a: 1\n").unwrap(), b"");
+        assert_eq!(parser.push(b"- a: 2\n").unwrap(), b"{\"a\":1}\n");
+        assert_eq!(parser.finish().unwrap(), b"{\"a\":2}\n");
+    }
+
+    #[test]
+    fn reads_other_documents_whole() {
+        let input = "--- [{a: 1}, {a: 2}]\n---\nb: true\nc: ~\n...\n";
+        assert_eq!(
+            parse_in_chunks(input, 5),
+            "{\"a\":1}\n{\"a\":2}\n{\"b\":true,\"c\":null}\n"
+        );
+    }
+
+    #[test]
+    fn reports_invalid_items() {
+        let mut parser = YamlParser::new();
+        parser.push(b"- a: [1\n").unwrap();
+        assert!(matches!(parser.push(b"- a: 2\n"), Err(ConvertError::YamlParse(_))));
+    }
+}
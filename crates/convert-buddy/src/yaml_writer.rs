@@ -0,0 +1,70 @@
+use crate::error::{ConvertError, Result};
+use serde_json::Value;
+
+/// Writes NDJSON records as a YAML block sequence, one `- ` item per record.
+///
+/// With no records at all, `finish` writes an empty flow sequence (`[]`) so
+/// the output is still a valid YAML document.
+pub struct YamlWriter {
+    records: usize,
+}
+
+impl YamlWriter {
+    pub fn new() -> Self {
+        Self { records: 0 }
+    }
+
+    pub fn process_ndjson(&mut self, ndjson: &[u8]) -> Result<Vec<u8>> {
+        let mut output = Vec::new();
+        for line in std::str::from_utf8(ndjson)?.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: Value =
+                serde_json::from_str(line).map_err(|e| ConvertError::JsonParse(e.to_string()))?;
+            let item = serde_yaml::to_string(&[record])
+                .map_err(|e| ConvertError::Io(format!("YAML write error: {e}")))?;
+            output.extend_from_slice(item.as_bytes());
+            self.records += 1;
+        }
+        Ok(output)
+    }
+
+    pub fn finish(&mut self) -> Vec<u8> {
+        if self.records == 0 {
+            b"[]\n".to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+impl Default for YamlWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yaml_parser::YamlParser;
+
+    #[test]
+    fn writes_records_as_sequence_items() {
+        let mut writer = YamlWriter::new();
+        let ndjson = b"{\"name\":\"Ada\",\"note\":\"true\",\"tags\":[\"x\"]}\n{\"name\":null}\n";
+        let mut output = writer.process_ndjson(ndjson).unwrap();
+        output.extend(writer.finish());
+        let yaml = String::from_utf8(output).unwrap();
+        assert_eq!(yaml, "- name: Ada\n  note: 'true'\n  tags:\n  - x\n- name: null\n");
+
+        // Reading it back gives the same records
+        let mut parser = YamlParser::new();
+        let mut records = parser.push(yaml.as_bytes()).unwrap();
+        records.extend(parser.finish().unwrap());
+        assert_eq!(records, ndjson);
+
+        assert_eq!(YamlWriter::new().finish(), b"[]\n");
+    }
+}
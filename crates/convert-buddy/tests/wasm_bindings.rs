@@ -0,0 +1,117 @@
+//! Smoke tests of the JS-facing bindings: configs built as JS objects,
+//! detection results read back as JS objects, and streaming through
+//! `push`/`finish`. They only build for wasm32:
+//!
+//! wasm-pack test --node
+//! WASM_BINDGEN_USE_BROWSER=1 wasm-pack test --headless --chrome
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+use convert_buddy::{convert, detect_csv_fields, detect_format, detect_structure, Converter};
+use js_sys::{Array, Reflect, JSON};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::*;
+
+fn js(json: &str) -> JsValue {
+    JSON::parse(json).expect("valid JSON")
+}
+
+fn field(object: &JsValue, name: &str) -> JsValue {
+    Reflect::get(object, &JsValue::from(name)).expect("an object")
+}
+
+fn with_config(input: &str, output: &str, csv: &str, transform: &str, options: &str) -> Result<Converter, JsValue> {
+    Converter::with_config(
+        false,
+        input,
+        output,
+        1024,
+        true,
+        js(csv),
+        JsValue::UNDEFINED,
+        js(transform),
+        js(options),
+    )
+}
+
+#[wasm_bindgen_test]
+fn with_config_reads_js_configs() {
+    let mut converter = with_config(
+        "csv",
+        "ndjson",
+        r#"{"delimiter": ";", "hasHeaders": true}"#,
+        r#"{"mode": "replace", "fields": [{"targetFieldName": "id", "originFieldName": "ID"}]}"#,
+        r#"{"maxOutputBytes": 16}"#,
+    )
+    .expect("valid config");
+
+    let mut output = converter.push(b"ID;name\n1;Ann\n2;").expect("push");
+    while converter.pending_output_bytes() > 0 {
+        output.extend(converter.drain());
+    }
+    output.extend(converter.push(b"Bo\n").expect("push"));
+    while !converter.is_finished() {
+        output.extend(converter.finish().expect("finish"));
+    }
+    assert_eq!(String::from_utf8(output).unwrap(), "{\"id\":\"1\"}\n{\"id\":\"2\"}\n");
+    assert_eq!(converter.get_stats().records_processed(), 2.0);
+    assert!(converter.push(b"3;Cy\n").is_err());
+}
+
+#[wasm_bindgen_test]
+fn invalid_configs_reject_with_a_message() {
+    let error = with_config("csv", "parquet?", "null", "null", "null").err().expect("an error");
+    assert!(error.as_string().unwrap_or_default().contains("Invalid output format"));
+
+    let error = with_config("csv", "json", "null", "null", r#"{"maxOutputBytes": 0}"#)
+        .err()
+        .expect("an error");
+    assert!(error.as_string().unwrap_or_default().contains("maxOutputBytes"));
+}
+
+#[wasm_bindgen_test]
+fn auto_detection_streams_across_chunks() {
+    let mut converter = with_config("auto", "json", "null", "null", "null").expect("valid config");
+    let mut output = Vec::new();
+    for chunk in [&b"{\"a\":1}\n{\"a\""[..], b":2}\n", b"{\"a\":3}\n"] {
+        output.extend(converter.push(chunk).expect("push"));
+    }
+    output.extend(converter.finish().expect("finish"));
+    let records = JSON::parse(std::str::from_utf8(&output).unwrap()).expect("JSON output");
+    assert_eq!(Array::from(&records).length(), 3);
+}
+
+#[wasm_bindgen_test]
+fn detection_functions_return_js_objects() {
+    let sample = b"name\tage\nAnn\t30\nBo\t41\n";
+    assert_eq!(detect_format(sample).as_deref(), Some("csv"));
+
+    let csv = detect_csv_fields(sample);
+    assert_eq!(field(&csv, "delimiter").as_string().as_deref(), Some("\t"));
+    let fields = Array::from(&field(&csv, "fields"));
+    assert_eq!(fields.to_vec(), vec![JsValue::from("name"), JsValue::from("age")]);
+
+    let structure = detect_structure(b"<rows><row><id>1</id></row><row><id>2</id></row></rows>", None);
+    assert_eq!(field(&structure, "format").as_string().as_deref(), Some("xml"));
+    assert_eq!(field(&structure, "recordElement").as_string().as_deref(), Some("row"));
+
+    assert!(detect_structure(b"", None).is_null());
+}
+
+#[wasm_bindgen_test]
+fn one_shot_convert_takes_a_config_object() {
+    let output = convert(b"a,b\n1,2\n", js(r#"{"outputFormat": "ndjson"}"#)).expect("convert");
+    assert_eq!(output, b"{\"a\":\"1\",\"b\":\"2\"}\n");
+
+    assert!(convert(b"a,b\n", js("{}")).is_err());
+}
+
+#[wasm_bindgen_test]
+fn push_with_info_returns_an_object() {
+    let mut converter = with_config("csv", "ndjson", "null", "null", "null").expect("valid config");
+    let info = converter.push_with_info_js(b"a,b\n1\n2,").expect("push");
+    assert_eq!(js_sys::Uint8Array::from(field(&info, "output")).to_vec(), b"{\"a\":\"1\"}\n");
+    assert_eq!(field(&info, "recordsEmitted").as_f64(), Some(1.0));
+    assert_eq!(field(&info, "bytesBuffered").as_f64(), Some(2.0));
+    let warnings = Array::from(&field(&info, "warnings"));
+    assert_eq!(field(&warnings.get(0), "code").as_string().as_deref(), Some("missingFields"));
+}
@@ -13,6 +13,12 @@ const debug = process.env.CONVERT_BUDDY_DEBUG === "1";
 const enableSimd = process.env.CONVERT_BUDDY_SIMD !== "0"; // Enabled by default
 // Only enable threads when explicitly requested (set CONVERT_BUDDY_THREADS=1)
 const enableThreads = process.env.CONVERT_BUDDY_THREADS === "1";
+// Parquet output adds noticeably to the binary size (set CONVERT_BUDDY_PARQUET=1)
+const enableParquet = process.env.CONVERT_BUDDY_PARQUET === "1";
+// Excel (.xlsx) input adds a zip reader (set CONVERT_BUDDY_XLSX=1)
+const enableXlsx = process.env.CONVERT_BUDDY_XLSX === "1";
+// YAML input and output add a YAML parser (set CONVERT_BUDDY_YAML=1)
+const enableYaml = process.env.CONVERT_BUDDY_YAML === "1";
  // "web" | "nodejs"
 
 
@@ -20,6 +26,9 @@ const enableThreads = process.env.CONVERT_BUDDY_THREADS === "1";
 const features = [];
 if (debug) features.push("debug-logs");
 if (enableSimd) features.push("simd");
+if (enableParquet) features.push("parquet");
+if (enableXlsx) features.push("xlsx");
+if (enableYaml) features.push("yaml");
 if (enableThreads) {
   // Use threads-web for both targets for now (until wasm-bindgen-rayon issues are resolved)
   features.push("threads-web"); // Custom JS threading for both Node.js and browsers
@@ -194,12 +194,18 @@ export async function convertFile(
       if (output.length > 0) {
         chunks.push(output);
       }
+      while (buddy.pendingOutputBytes() > 0) {
+        chunks.push(buddy.drain());
+      }
     }
     
     const final = buddy.finish();
     if (final.length > 0) {
       chunks.push(final);
     }
+    while (buddy.pendingOutputBytes() > 0) {
+      chunks.push(buddy.drain());
+    }
     
     // Combine all chunks
     const totalLength = chunks.reduce((sum, chunk) => sum + chunk.length, 0);
@@ -321,6 +327,9 @@ export async function convertFileStream(
             if (final.length > 0) {
               controller.enqueue(final);
             }
+            while (buddy.pendingOutputBytes() > 0) {
+              controller.enqueue(buddy.drain());
+            }
             controller.close();
             break;
           }
@@ -334,6 +343,9 @@ export async function convertFileStream(
           if (output.length > 0) {
             controller.enqueue(output);
           }
+          while (buddy.pendingOutputBytes() > 0) {
+            controller.enqueue(buddy.drain());
+          }
         }
       } catch (error) {
         controller.error(error);
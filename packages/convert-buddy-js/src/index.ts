@@ -1,4 +1,9 @@
-export type Format = "csv" | "ndjson" | "json" | "xml";
+export type Format = "csv" | "tsv" | "ndjson" | "json" | "xml";
+// "parquet" needs a WASM build with the `parquet` feature (CONVERT_BUDDY_PARQUET=1)
+// "yaml" needs a WASM build with the `yaml` feature (CONVERT_BUDDY_YAML=1)
+export type OutputFormat = Format | "parquet" | "yaml";
+// "xlsx" needs a WASM build with the `xlsx` feature (CONVERT_BUDDY_XLSX=1)
+export type InputFormat = Format | "xlsx" | "yaml";
 export type DetectInput =
   | Uint8Array
   | ArrayBuffer
@@ -9,6 +14,8 @@ export type DetectInput =
 export type CsvDetection = {
   delimiter: string;
   fields: string[];
+  quote?: string;
+  hasHeaders?: boolean;
 };
 
 export type XmlDetection = {
@@ -31,31 +38,72 @@ export type StructureDetection = {
   recordElement?: string;  // For XML
 };
 
+export type HeaderField = {
+  name: string;
+  index: number;
+  // Byte range of the field in the sample, quotes included
+  start: number;
+  end: number;
+};
+
+export type CsvHeader = {
+  delimiter: string;
+  fields: HeaderField[];
+};
+
+export type HeaderOptions = {
+  // Detected from the header row when left out
+  delimiter?: string;
+  quote?: string;
+  // With false, columns are named field_0, field_1, ...
+  hasHeaders?: boolean;
+};
+
+export type RecordEstimate = {
+  format: Format;
+  records: number;
+  // Complete records in the sample, which the estimate is based on
+  sampleRecords: number;
+  averageRecordBytes: number;
+  // True when the sample was the whole input, so records is a count
+  exact: boolean;
+};
+
 export type DetectOptions = {
   maxBytes?: number;
   debug?: boolean;
 };
 
+export type TransformStreamConfig = Pick<
+  ConvertBuddyOptions,
+  "inputFormat" | "outputFormat" | "chunkTargetBytes" | "profile" | "csvConfig" | "xmlConfig" | "transform" | "options"
+> & { outputFormat: OutputFormat };
+
 export type ProgressCallback = (stats: Stats) => void;
 
+export type OutputCallback = (chunk: Uint8Array) => void;
+
 export type ConvertBuddyOptions = {
   debug?: boolean;
   profile?: boolean;
-  inputFormat?: Format | "auto";
-  outputFormat?: Format;
+  inputFormat?: InputFormat | "auto";
+  outputFormat?: OutputFormat;
   chunkTargetBytes?: number;
   parallelism?: number; // Node only - number of worker threads
   maxMemoryMB?: number; // Memory limit for conversions (future use)
   csvConfig?: CsvConfig;
   xmlConfig?: XmlConfig;
   transform?: TransformConfig;
+  options?: ConverterOptions;
   onProgress?: ProgressCallback;
   progressIntervalBytes?: number; // Trigger progress callback every N bytes (default: 1MB)
+  // Receives output as it is produced; push() and finish() then return empty arrays
+  onOutput?: OutputCallback;
 };
 
 export type ConvertOptions = {
-  inputFormat?: Format | "auto";
-  outputFormat: Format;
+  inputFormat?: InputFormat | "auto";
+  outputFormat: OutputFormat;
   csvConfig?: CsvConfig;
   xmlConfig?: XmlConfig;
   transform?: TransformConfig;
@@ -67,15 +115,247 @@ export type CsvConfig = {
   quote?: string;
   hasHeaders?: boolean;
   trimWhitespace?: boolean;
+  inferTypes?: boolean; // numbers, booleans and null (empty fields) as JSON types (default false)
+  columnTypes?: Record<string, CsvColumnType>; // per-column override of inferTypes
+  columns?: CsvColumn[]; // schema of the leading columns, by position
+  unflatten?: boolean; // nest dotted keys like "a.b" and "a.0" back into objects and arrays (default false)
+  skipRows?: number; // lines ignored before the header, like a report title (default 0)
+  headerRow?: number; // index of the header among the lines left after skipRows (default 0)
+  maxRows?: number; // data rows to convert; the rest of the input is ignored
+  commentChar?: string; // skip lines starting with this character, like "#"
 };
 
+export type CsvColumnType = "string" | "infer" | "integer" | "number" | "boolean" | "date" | "datetime" | "json";
+
+export type CsvColumn = {
+  name: string; // output key, replacing the header
+  type?: CsvColumnType;
+  nullable?: boolean; // empty fields fail the record when false (default true)
+  format?: string; // chrono format of date/datetime values, e.g. "%d/%m/%Y"
+  empty?: "null" | "omit"; // what empty fields become instead of the type's default
+  path?: string[]; // keys of the nested value a CSV writer flattened into this column, e.g. ["tags", "0"]
+};
+
+// Column types noted by csvOutput.typeSidecar, to spread into the csvConfig that reads the CSV back
+export type CsvSchema = { columns: CsvColumn[]; unflatten: boolean };
+
 export type XmlConfig = {
-  recordElement?: string;
+  recordElement?: string | string[]; // element name or path like "channel/item"; a list converts several in one pass
+  recordPath?: string; // path from the document root, like "/rss/channel/item"; replaces recordElement
+  tagElement?: boolean; // add an `_element` field with the record element's name (default false)
+  namespaces?: "keep" | "strip" | "expand"; // keys like "g:price", "price" or "{uri}price" (default "keep")
   trimText?: boolean;
   includeAttributes?: boolean;
   expandEntities?: boolean;
 };
 
+export type ConverterOptions = {
+  flattenCollision?: "error" | "suffix" | "lastWins";
+  ndjsonOutput?: NdjsonOutputOptions;
+  csvOutput?: CsvOutputOptions; // used when outputFormat is "csv" or "tsv"
+  xmlOutput?: XmlOutputOptions; // used when outputFormat is "xml"
+  jsonInput?: JsonInputOptions; // used when inputFormat is "json"
+  jsonOutput?: JsonOutputOptions; // used when outputFormat is "json"
+  floatFormat?: FloatFormatOptions; // JSON, NDJSON, CSV and XML output
+  integerStrings?: IntegerStringsOptions; // JSON and NDJSON output
+  maxOutputBytes?: number; // cap per push()/finish() result; fetch the rest with drain()
+  minOutputBytes?: number; // push() returns output once about this many bytes are ready
+  trace?: boolean; // record internal events for getTrace()
+  traceCapacity?: number; // events kept in the trace ring buffer (default 1024)
+  ordered?: boolean; // keep multi-threaded output in input order (default true)
+  threads?: number; // most threads this converter may use (default: setThreadPoolSize, else all)
+  limits?: ResourceLimits; // hard caps for untrusted input
+  inputCompression?: "none" | "gzip" | "zlib" | "deflate"; // default: gzip/zlib recognised by header
+  outputCompression?: "none" | "gzip" | "zlib" | "deflate"; // compress the returned bytes (default "none")
+  invalidUtf8?: "error" | "replace" | "skip"; // input that isn't valid UTF-8 (default "error")
+  onRecordError?: "abort" | "skip" | "collect"; // CSV rows / NDJSON lines / transformed records that fail (default "abort")
+  keepRejects?: boolean; // keep the raw bytes of dropped records for takeRejects() (default false)
+  selectFields?: string[]; // keep only these top-level fields of each input record
+  dropFields?: string[]; // remove these top-level fields from each input record
+  dedupe?: DedupeOptions; // drop records repeating an earlier record's key
+  sort?: SortOptions; // write records sorted by key fields at finish()
+  maxRecords?: number; // stop cleanly after writing this many records
+  sampleEveryN?: number; // write only every nth record, starting with the first
+  outputColumns?: OutputColumn[]; // fields written, renamed and in this order
+  chunking?: ChunkingOptions; // cut the output into content-defined chunks for takeChunks()
+  manifest?: boolean; // build a ConversionManifest at finish() for getManifest() (default false)
+  detectionCheck?: DetectionCheckOptions; // sanity check of the CSV delimiter / XML record element
+  parquet?: ParquetOptions; // used when outputFormat is "parquet"
+  xlsx?: XlsxOptions; // used when inputFormat is "xlsx"
+};
+
+// `to` defaults to `from`; for CSV output, `from` can name a flattened column like "address.city"
+export type OutputColumn = { from: string; to?: string };
+
+// Keys are read from the records as written, i.e. after `transform`.
+export type DedupeOptions = {
+  fields: string[]; // top-level fields that together identify a record
+  keep?: "first" | "last"; // "last" holds records back until finish() (default "first")
+  maxMemoryBytes?: number; // cap on remembered keys and held-back records
+  approximate?: boolean; // remember key hashes, and forget the oldest keys past maxMemoryBytes instead of failing
+};
+
+// Values compare as null < booleans < numbers < strings < arrays < objects;
+// records with equal keys keep their input order.
+export type ChunkingOptions = {
+  minBytes?: number; // smallest chunk but the last (default 16 KiB)
+  avgBytes?: number; // typical distance between cut points past minBytes (default 64 KiB)
+  maxBytes?: number; // largest chunk (default 256 KiB)
+  known?: string[]; // sha256 of chunks the consumer already has, e.g. from the last manifest
+};
+
+export type SortOptions = {
+  keys: string[]; // fields to sort by, most significant first
+  order?: "asc" | "desc"; // default "asc"
+  maxMemoryBytes?: number; // records buffered before a sorted run is spilled (default 64 MiB)
+  index?: boolean; // build a key -> byte range index of the output for takeIndex(); NDJSON output only (default false)
+};
+
+// Exceeding a limit fails the conversion with "Resource limit exceeded: ...".
+export type ResourceLimits = {
+  maxInputBytes?: number; // total bytes pushed, after decompression
+  maxRecords?: number; // input records parsed
+  maxFieldBytes?: number; // one CSV field, JSON string or XML text/attribute value
+  maxDepth?: number; // JSON object/array nesting, or XML elements below the record element
+};
+
+export type ParquetOptions = {
+  rowGroupSize?: number; // rows per row group (default 10000)
+  compression?: "none" | "snappy" | "gzip"; // default "snappy"
+};
+
+export type XlsxOptions = {
+  sheet?: string; // worksheet name (default: the first sheet)
+  hasHeaders?: boolean; // first row holds column names (default true)
+};
+
+export type TraceEntry = { seq: number } & (
+  | { event: "stateChange"; from: string; to: string }
+  | { event: "detection"; sampleBytes: number; decision: string }
+  | {
+      event: "push";
+      state: string;
+      inputBytes: number;
+      outputBytes: number;
+      bufferedBytes: number;
+      elapsedMs: number;
+    }
+  | { event: "finish"; state: string; outputBytes: number; elapsedMs: number }
+);
+
+export type WarningCode =
+  | "blankLineSkipped"
+  | "invalidLineSkipped"
+  | "missingFields"
+  | "extraFields"
+  | "fieldDropped"
+  | "recordSkipped"
+  | "flattenCollision"
+  | "coercionFailed"
+  | "detectionFallback"
+  | "detectionSuspect"
+  | "invalidUtf8Replaced"
+  | "invalidUtf8Skipped"
+  | "duplicateDropped";
+
+// One entry per code: the first occurrence's message and how often it happened
+export type Warning = { code: WarningCode; message: string; count: number };
+
+// What one pushWithInfo() call did; warnings count only the chunk's own occurrences
+export type PushInfo = {
+  output: Uint8Array;
+  recordsEmitted: number; // records converted from the chunk
+  bytesBuffered: number; // input held back waiting for the rest of a record
+  warnings: Warning[];
+};
+
+export type RecordError = { line: number; message: string };
+
+// Only settings that were detected are present; configured ones are left out
+export type DetectionResult = {
+  inputFormat?: InputFormat;
+  delimiter?: string;
+  quote?: string;
+  hasHeaders?: boolean;
+  recordElement?: string;
+  fields: string[]; // first CSV row, or XML element names
+};
+
+export type Checksum = { bytes: number; sha256: string };
+
+export type ConversionManifest = {
+  inputFormat: string;
+  outputFormat: string;
+  config: Record<string, unknown>; // csvConfig/xmlConfig, transform and options in effect, detected settings included
+  detection?: DetectionResult;
+  records: number; // input records parsed
+  recordsSkipped: number; // dropped under onRecordError "skip" or "collect"
+  warnings: number;
+  durationMs: number; // first push() to the end of finish()
+  input: Checksum; // bytes as pushed, before decompression
+  output: Checksum; // bytes as returned, after compression
+};
+
+export type NdjsonOutputOptions = {
+  lineEnding?: "lf" | "crlf"; // default "lf"
+  trailingNewline?: boolean; // default true
+  recordSeparator?: boolean; // prefix each record with RS (0x1E), as in RFC 7464
+  nulTerminated?: boolean; // end each record with NUL (0x00) instead of lineEnding
+};
+
+export type CsvOutputOptions = {
+  delimiter?: string; // one ASCII character (default ","; always a tab for "tsv")
+  quote?: string; // one ASCII character (default '"')
+  quoteStyle?: "minimal" | "always"; // default "minimal"
+  lineEnding?: "lf" | "crlf"; // default "lf"
+  includeHeader?: boolean; // default true
+  columns?: string[]; // column order; other keys are dropped
+  typeSidecar?: boolean; // note each column's JSON types for getCsvSchema() (default false)
+};
+
+export type DetectionCheckOptions = {
+  enabled?: boolean; // default true
+  afterBytes?: number; // input to look at before judging (default 1 MiB)
+  redetect?: boolean; // hold input until judged and re-detect when wrong (default false)
+};
+
+export type XmlOutputOptions = {
+  rootElement?: string; // default "root"
+  recordElement?: string; // default "record"
+  indent?: number; // spaces per level (default 2)
+  declaration?: boolean; // start with <?xml ...?> (default false)
+  attributes?: boolean; // write "@"-prefixed keys as attributes and "#text" as element text (default false)
+  namespaces?: Record<string, string>; // prefix -> URI, declared on the root element; "" is the default namespace
+  itemElement?: string; // wrap array items in <key><itemElement>...</itemElement></key> (default: repeat <key> per item)
+};
+
+export type JsonInputOptions = {
+  recordsPath?: string; // dot-separated keys to the record array, like "data.items" or "$.data.items"
+};
+
+export type JsonOutputOptions = {
+  pretty?: boolean; // one value per line, indented by depth (default false)
+  indent?: number; // spaces per level when pretty (default 2)
+};
+
+export type FloatFormatOptions = {
+  maxDecimals?: number; // round to at most this many digits after the point
+  style?: "shortest" | "fixed"; // "fixed" pads to maxDecimals digits (default "shortest")
+  scientificThreshold?: number; // use 1.5e-7 notation from this decimal exponent on (default never)
+};
+
+export type IntegerStringsOptions = {
+  fields?: string[]; // keys whose numbers are always written as strings, at any depth
+  unsafeIntegers?: boolean; // quote integers beyond Number.MAX_SAFE_INTEGER (default true)
+};
+
+export type ExpressionFunction = (...args: any[]) => unknown;
+
+export type LogLevel = "error" | "warn" | "info" | "debug" | "trace";
+
+// Receives the converter's log records; `target` is the Rust module logging.
+export type LogSink = (level: LogLevel, message: string, target: string) => void;
+
 export type TransformMode = "replace" | "augment";
 
 export type Coerce =
@@ -83,7 +363,9 @@ export type Coerce =
   | { type: "i64" }
   | { type: "f64" }
   | { type: "bool" }
-  | { type: "timestamp_ms"; format?: "iso8601" | "unix_ms" | "unix_s" };
+  | { type: "timestamp_ms"; format?: "iso8601" | "unix_ms" | "unix_s" }
+  | { type: "json" }
+  | { type: "stringify" };
 
 export type FieldMap = {
   targetFieldName: string;
@@ -92,14 +374,35 @@ export type FieldMap = {
   defaultValue?: string | number | boolean | null;
   coerce?: Coerce;
   compute?: string;
+  onMissingField?: "error" | "null" | "drop";
+  onCoerceError?: "error" | "null" | "dropRecord";
+  emit?: boolean;
 };
 
 export type TransformConfig = {
   mode?: TransformMode;
-  fields: FieldMap[];
+  fields?: FieldMap[]; // may be omitted in "augment" mode
   onMissingField?: "error" | "null" | "drop";
   onMissingRequired?: "error" | "abort";
   onCoerceError?: "error" | "null" | "dropRecord";
+  nanPolicy?: "null" | "error" | "zero";
+  binaryOutput?: "base64" | "hex" | "error"; // how base64_decode() writes bytes that aren't UTF-8
+  stopWhen?: RecordQuery;
+  filter?: RecordQuery | string; // query or expression; only matching records are transformed and written
+  explode?: string; // dotted path of an array field; one record per element, merged with the parent
+};
+
+export type RecordQuery = {
+  field: string;
+  equals?: string | number | boolean;
+  contains?: string;
+};
+
+export type DemuxConfig = {
+  field: string;
+  routes: Record<string, TransformConfig | null>;
+  onUnmatched?: "keep" | "drop" | "error";
+  unmatchedRoute?: string;
 };
 
 export type Stats = {
@@ -107,6 +410,7 @@ export type Stats = {
   bytesOut: number;
   chunksIn: number;
   recordsProcessed: number;
+  recordsSkipped: number;
   parseTimeMs: number;
   transformTimeMs: number;
   writeTimeMs: number;
@@ -117,7 +421,7 @@ export type Stats = {
 
 type WasmModule = {
   default?: unknown;
-  init: (debugEnabled: boolean) => void;
+  init: (debugEnabled: boolean, logSink?: LogSink) => void;
   Converter: new (debug: boolean) => {
     push: (chunk: Uint8Array) => Uint8Array;
     finish: () => Uint8Array;
@@ -129,7 +433,14 @@ type WasmModule = {
   detectJsonFields?: (sample: Uint8Array) => JsonDetection | null | undefined;
   detectNdjsonFields?: (sample: Uint8Array) => NdjsonDetection | null | undefined;
   detectStructure?: (sample: Uint8Array, formatHint?: string) => StructureDetection | null | undefined;
+  extractHeader?: (sample: Uint8Array, config?: HeaderOptions) => CsvHeader | null | undefined;
+  estimateRecords?: (sample: Uint8Array, totalBytes: number, formatHint?: string) => RecordEstimate | null | undefined;
+  createTransformStream?: (config: TransformStreamConfig) => TransformStream<Uint8Array, Uint8Array>;
+  convert?: (input: Uint8Array, config: TransformStreamConfig) => Uint8Array;
   getSimdEnabled?: () => boolean;
+  registerExpressionFunction?: (name: string, fn: ExpressionFunction) => void;
+  setThreadPoolSize?: (threads: number) => boolean;
+  setLogSink?: (sink: LogSink | null) => void;
   __wbg_set_wasm?: (wasm: unknown) => void;
 };
 
@@ -279,6 +590,7 @@ export class ConvertBuddy {
   private lastProgressBytes: number = 0;
   private globalConfig: ConvertBuddyOptions;
   private initialized: boolean = false;
+  private asyncPush: Promise<unknown> = Promise.resolve();
   public simd: boolean;
 
   /**
@@ -415,13 +727,13 @@ export class ConvertBuddy {
     }
 
     const buddy = await ConvertBuddy.create(actualOpts);
-    const output = buddy.push(input);
-    const final = buddy.finish();
+    const outputs = [buddy.push(input)];
+    drainPending(buddy, outputs);
+    outputs.push(buddy.finish());
+    drainPending(buddy, outputs);
 
     // Combine outputs
-    const result = new Uint8Array(output.length + final.length);
-    result.set(output, 0);
-    result.set(final, output.length);
+    const result = concatChunks(outputs, outputs.reduce((sum, arr) => sum + arr.length, 0));
 
     if (opts.profile) {
       const stats = buddy.stats();
@@ -606,20 +918,18 @@ export class ConvertBuddy {
       const chunkOpts = { ...opts, parallelism: 1 }; // Disable recursion
       
       // For CSV with headers, only the first chunk should process headers
-      if (opts.inputFormat === "csv" && index > 0) {
+      if ((opts.inputFormat === "csv" || opts.inputFormat === "tsv") && index > 0) {
         chunkOpts.csvConfig = { ...chunkOpts.csvConfig, hasHeaders: false };
       }
       
       const chunkBuddy = await ConvertBuddy.create(chunkOpts);
-      const output = chunkBuddy.push(chunk);
-      const final = chunkBuddy.finish();
+      const outputs = [chunkBuddy.push(chunk)];
+      drainPending(chunkBuddy, outputs);
+      outputs.push(chunkBuddy.finish());
+      drainPending(chunkBuddy, outputs);
       
       // Combine chunk output
-      const result = new Uint8Array(output.length + final.length);
-      result.set(output, 0);
-      result.set(final, output.length);
-      
-      return result;
+      return concatChunks(outputs, outputs.reduce((sum, arr) => sum + arr.length, 0));
     });
 
     const chunkResults = await Promise.all(chunkPromises);
@@ -727,12 +1037,18 @@ export class ConvertBuddy {
         if (output.length > 0) {
           outputs.push(output);
         }
+        drainPending(buddy, outputs);
+        if (buddy.isStopped()) {
+          await reader.cancel();
+          break;
+        }
       }
       
       const final = buddy.finish();
       if (final.length > 0) {
         outputs.push(final);
       }
+      drainPending(buddy, outputs);
       
       // Combine all outputs
       const totalLength = outputs.reduce((sum, arr) => sum + arr.length, 0);
@@ -814,6 +1130,7 @@ export class ConvertBuddy {
       if (output.length > 0) {
         outputs.push(output);
       }
+      drainPending(buddy, outputs);
     }
     
     // Continue with the rest of the stream
@@ -828,12 +1145,18 @@ export class ConvertBuddy {
         if (output.length > 0) {
           outputs.push(output);
         }
+        drainPending(buddy, outputs);
+        if (buddy.isStopped()) {
+          await reader.cancel();
+          break;
+        }
       }
       
       const final = buddy.finish();
       if (final.length > 0) {
         outputs.push(final);
       }
+      drainPending(buddy, outputs);
       
       // Combine all outputs
       const totalLength = outputs.reduce((sum, arr) => sum + arr.length, 0);
@@ -868,16 +1191,9 @@ export class ConvertBuddy {
 
     const wasmModule = await loadWasmModule();
 
-    // Handle auto-detection
-    let inputFormat = opts.inputFormat;
-    let csvConfig = opts.csvConfig;
-
-    // We can't auto-detect without data, so we'll defer this to push()
-    // For now, just validate the format if it's not "auto"
-    if (inputFormat === "auto") {
-      // Auto-detection will be handled on first push()
-      inputFormat = undefined;
-    }
+    // "auto" is resolved by the converter from the first chunk pushed
+    const inputFormat = opts.inputFormat;
+    const csvConfig = opts.csvConfig;
 
     // Optimize chunk size for better WASM performance
     // Larger chunks reduce boundary crossing overhead
@@ -897,16 +1213,17 @@ export class ConvertBuddy {
           profile, // Enable stats tracking when profile is enabled
           csvConfig || null,
           opts.xmlConfig || null,
-          opts.transform || null
+          opts.transform || null,
+          opts.options || null
         );
       } catch (err: any) {
         // Enhance error message for common issues
         const errorMsg = typeof err === 'string' ? err : err?.message || String(err);
         if (errorMsg.includes('Invalid output format')) {
-          const validFormats = ['csv', 'json', 'ndjson', 'xml'];
+          const validFormats = ['csv', 'tsv', 'json', 'ndjson', 'xml', 'parquet', 'yaml'];
           throw new Error(`Invalid outputFormat: "${opts.outputFormat}". Must be one of: ${validFormats.join(', ')}`);
         } else if (errorMsg.includes('Invalid input format')) {
-          const validFormats = ['csv', 'json', 'ndjson', 'xml', 'auto'];
+          const validFormats = ['csv', 'tsv', 'json', 'ndjson', 'xml', 'xlsx', 'yaml', 'auto'];
           throw new Error(`Invalid inputFormat: "${inputFormat}". Must be one of: ${validFormats.join(', ')}`);
         }
         throw new Error(`Configuration error: ${errorMsg}`);
@@ -915,6 +1232,13 @@ export class ConvertBuddy {
       converter = new wasmModule.Converter(debug);
     }
 
+    if (opts.onOutput) {
+      if (!converter.setOutputCallback) {
+        throw new Error("onOutput is not supported by this WASM build");
+      }
+      converter.setOutputCallback(opts.onOutput);
+    }
+
     // Check if SIMD is enabled
     const simdEnabled = (wasmModule as any).getSimdEnabled?.() ?? false;
 
@@ -953,6 +1277,10 @@ export class ConvertBuddy {
     return output;
   }
 
+  /**
+   * End the input and return the remaining output. With `maxOutputBytes`
+   * set, call again until `isFinished()` to get the rest in parts.
+   */
   finish(): Uint8Array {
     if (this.aborted) {
       throw new Error("Conversion has been aborted");
@@ -969,6 +1297,247 @@ export class ConvertBuddy {
     return output;
   }
 
+  /**
+   * Like `push()`, but converts the chunk in slices and yields to the event
+   * loop whenever the slices so far have taken `budgetMs` (8 by default), so
+   * a conversion on the browser main thread doesn't hold up rendering or
+   * input. Slices are resized after each one to fit the budget. Resolves to
+   * the output of the whole chunk. Calls run one after another; don't call
+   * `push()` while one is pending.
+   */
+  pushAsync(chunk: Uint8Array, opts: { budgetMs?: number } = {}): Promise<Uint8Array> {
+    const run = this.asyncPush.then(() => this.pushInSlices(chunk, opts.budgetMs ?? 8));
+    this.asyncPush = run.catch(() => undefined);
+    return run;
+  }
+
+  private async pushInSlices(chunk: Uint8Array, budgetMs: number): Promise<Uint8Array> {
+    const outputs: Uint8Array[] = [];
+    let total = 0;
+    let sliceBytes = ASYNC_SLICE_BYTES;
+    let offset = 0;
+    let turnStart = performance.now();
+    while (offset < chunk.length) {
+      const end = Math.min(offset + sliceBytes, chunk.length);
+      const sliceStart = performance.now();
+      const output = this.push(chunk.subarray(offset, end));
+      const now = performance.now();
+      if (output.length > 0) {
+        outputs.push(output);
+        total += output.length;
+      }
+      if (now > sliceStart) {
+        const fitted = Math.floor(((end - offset) * budgetMs) / (now - sliceStart));
+        sliceBytes = Math.min(Math.max(fitted, MIN_ASYNC_SLICE_BYTES), MAX_ASYNC_SLICE_BYTES);
+      }
+      offset = end;
+      if (offset < chunk.length && now - turnStart >= budgetMs) {
+        await yieldToEventLoop();
+        turnStart = performance.now();
+      }
+    }
+    return outputs.length === 1 ? outputs[0] : concatChunks(outputs, total);
+  }
+
+  /**
+   * Like `push()`, but copies the output into `output` instead of returning
+   * a new array, so one buffer can be reused for every chunk. Returns the
+   * number of bytes written; output that doesn't fit is fetched with
+   * `drainInto()` while `pendingOutputBytes()` is above 0.
+   */
+  pushInto(chunk: Uint8Array, output: Uint8Array): number {
+    if (this.aborted) {
+      throw new Error("Conversion has been aborted");
+    }
+
+    if (this.paused) {
+      throw new Error("Conversion is paused. Call resume() before pushing more data.");
+    }
+
+    if (this.debug) console.log("[convert-buddy-js] pushInto", chunk.byteLength);
+    const written = this.converter.pushInto(chunk, output);
+
+    if (this.onProgress) {
+      const stats = this.stats();
+      if (stats.bytesIn - this.lastProgressBytes >= this.progressIntervalBytes) {
+        this.onProgress(stats);
+        this.lastProgressBytes = stats.bytesIn;
+      }
+    }
+
+    return written;
+  }
+
+  /**
+   * Like `push()`, but also returns the records converted, the input held
+   * back and the warnings raised by this chunk alone, where `getStats()` and
+   * `getWarnings()` give totals since the start.
+   */
+  pushWithInfo(chunk: Uint8Array): PushInfo {
+    if (this.aborted) {
+      throw new Error("Conversion has been aborted");
+    }
+
+    if (this.paused) {
+      throw new Error("Conversion is paused. Call resume() before pushing more data.");
+    }
+
+    if (this.debug) console.log("[convert-buddy-js] pushWithInfo", chunk.byteLength);
+    const info: PushInfo = this.converter.pushWithInfo(chunk);
+
+    if (this.onProgress) {
+      const stats = this.stats();
+      if (stats.bytesIn - this.lastProgressBytes >= this.progressIntervalBytes) {
+        this.onProgress(stats);
+        this.lastProgressBytes = stats.bytesIn;
+      }
+    }
+
+    return info;
+  }
+
+  /** Like `finish()`, but copies the output into `output` like `pushInto()`. */
+  finishInto(output: Uint8Array): number {
+    if (this.aborted) {
+      throw new Error("Conversion has been aborted");
+    }
+
+    if (this.debug) console.log("[convert-buddy-js] finishInto");
+    const written = this.converter.finishInto(output);
+
+    if (this.onProgress) {
+      this.onProgress(this.stats());
+    }
+
+    return written;
+  }
+
+  /**
+   * Most recent internal events (oldest first) when `options.trace` is set:
+   * state changes, detection decisions, and per-push sizes and timings.
+   */
+  getTrace(): TraceEntry[] {
+    return this.converter?.getTrace?.() ?? [];
+  }
+
+  clearTrace(): void {
+    this.converter?.clearTrace?.();
+  }
+
+  /**
+   * Problems the conversion worked around instead of failing: skipped blank
+   * or invalid lines, ragged CSV rows, dropped keys, failed coercions and
+   * auto-detection fallbacks.
+   */
+  getWarnings(): Warning[] {
+    return this.converter?.getWarnings?.() ?? [];
+  }
+
+  /**
+   * The JSON types written into each CSV column with
+   * `csvOutput.typeSidecar` set, or null. Spread it into the `csvConfig` of
+   * the conversion that reads the CSV back.
+   */
+  getCsvSchema(): CsvSchema | null {
+    return this.converter?.getCsvSchema?.() ?? null;
+  }
+
+  /**
+   * Records dropped under `onRecordError: "collect"`, with the line each
+   * starts on. At most 1000 are kept.
+   */
+  getErrors(): RecordError[] {
+    return this.converter?.getErrors?.() ?? [];
+  }
+
+  /**
+   * Records dropped under `onRecordError` since the last call, as NDJSON
+   * `{ line, error, raw }` objects. Empty unless `keepRejects` is set.
+   */
+  takeRejects(): Uint8Array {
+    return this.converter?.takeRejects?.() ?? new Uint8Array(0);
+  }
+
+  /**
+   * Index entries of the sorted output since the last call, as NDJSON
+   * `[key, offset, length]` arrays. Empty unless `sort.index` is set.
+   */
+  takeIndex(): Uint8Array {
+    return this.converter?.takeIndex?.() ?? new Uint8Array(0);
+  }
+
+  /**
+   * Chunk manifest of the output since the last call, as NDJSON
+   * `{ offset, length, sha256, known }` objects. Empty unless `chunking` is set.
+   */
+  takeChunks(): Uint8Array {
+    return this.converter?.takeChunks?.() ?? new Uint8Array(0);
+  }
+
+  clearWarnings(): void {
+    this.converter?.clearWarnings?.();
+  }
+
+  /**
+   * Settings auto-detected from the start of the input, or `null` until
+   * detection has run (after 256 bytes or at `finish()`).
+   */
+  getDetectionResult(): DetectionResult | null {
+    return this.converter?.getDetectionResult?.() ?? null;
+  }
+
+  /**
+   * Record of the finished conversion, or `null` before `finish()` or
+   * without `options.manifest`.
+   */
+  getManifest(): ConversionManifest | null {
+    return this.converter?.getManifest?.() ?? null;
+  }
+
+  /**
+   * Next part of the output held back by `options.maxOutputBytes` (at most
+   * that many bytes). Call until `pendingOutputBytes()` is 0.
+   */
+  drain(): Uint8Array {
+    return this.converter.drain();
+  }
+
+  /**
+   * Copy output waiting for `drain()` into `output`, returning the number of
+   * bytes written.
+   */
+  drainInto(output: Uint8Array): number {
+    return this.converter.drainInto(output);
+  }
+
+  /** Bytes of converted output waiting to be returned by `drain()`. */
+  pendingOutputBytes(): number {
+    return this.converter?.pendingOutputBytes?.() ?? 0;
+  }
+
+  /**
+   * Whether `finish()` has returned the last output. Calling `push()`,
+   * `flush()` or `finish()` again throws "Converter already finished".
+   * `push()` and `flush()` throw as soon as `finish()` has been called.
+   */
+  isFinished(): boolean {
+    return !!this.converter?.isFinished?.();
+  }
+
+  /**
+   * Whether a record matched `transform.stopWhen` or `maxRecords` records
+   * were written. Further pushes are ignored, so stop reading input and call
+   * `finish()`.
+   */
+  isStopped(): boolean {
+    return !!this.converter?.isStopped?.();
+  }
+
+  /** The input record that matched `transform.stopWhen`, or null. */
+  getMatch(): Record<string, unknown> | null {
+    return this.converter?.getMatch?.() ?? null;
+  }
+
   stats(): Stats {
     if (!this.converter || typeof this.converter.getStats !== 'function') {
       // Converter not initialized yet
@@ -977,6 +1546,7 @@ export class ConvertBuddy {
         bytesOut: 0,
         chunksIn: 0,
         recordsProcessed: 0,
+        recordsSkipped: 0,
         parseTimeMs: 0,
         transformTimeMs: 0,
         writeTimeMs: 0,
@@ -996,6 +1566,7 @@ export class ConvertBuddy {
         bytesOut: wasmStats.bytes_out,
         chunksIn: wasmStats.chunks_in,
         recordsProcessed: wasmStats.records_processed,
+        recordsSkipped: wasmStats.records_skipped,
         parseTimeMs: wasmStats.parse_time_ms,
         transformTimeMs: wasmStats.transform_time_ms,
         writeTimeMs: wasmStats.write_time_ms,
@@ -1020,6 +1591,7 @@ export class ConvertBuddy {
         bytesOut: 0,
         chunksIn: 0,
         recordsProcessed: 0,
+        recordsSkipped: 0,
         parseTimeMs: 0,
         transformTimeMs: 0,
         writeTimeMs: 0,
@@ -1112,6 +1684,32 @@ async function readSample(
   return concatChunks(chunks, total);
 }
 
+const ASYNC_SLICE_BYTES = 64 * 1024;
+const MIN_ASYNC_SLICE_BYTES = 4 * 1024;
+const MAX_ASYNC_SLICE_BYTES = 4 * 1024 * 1024;
+
+/** Let rendering and input handlers run, without setTimeout's minimum delay. */
+function yieldToEventLoop(): Promise<void> {
+  if (typeof MessageChannel === "undefined") {
+    return new Promise((resolve) => setTimeout(resolve, 0));
+  }
+  return new Promise((resolve) => {
+    const channel = new MessageChannel();
+    channel.port1.onmessage = () => {
+      channel.port1.close();
+      resolve();
+    };
+    channel.port2.postMessage(null);
+  });
+}
+
+/** Collect output held back by `options.maxOutputBytes`. */
+function drainPending(buddy: ConvertBuddy, outputs: Uint8Array[]): void {
+  while (buddy.pendingOutputBytes() > 0) {
+    outputs.push(buddy.drain());
+  }
+}
+
 function concatChunks(chunks: Uint8Array[], total: number): Uint8Array {
   const result = new Uint8Array(total);
   let offset = 0;
@@ -1140,11 +1738,11 @@ async function loadDetectionWasm(debug: boolean): Promise<WasmModule> {
 export async function detectFormat(
   input: DetectInput,
   opts: DetectOptions = {}
-): Promise<Format | "unknown"> {
+): Promise<InputFormat | "unknown"> {
   const wasmModule = await loadDetectionWasm(!!opts.debug);
   const sample = await readSample(input, opts.maxBytes);
   const format = wasmModule.detectFormat?.(sample);
-  return (format as Format) ?? "unknown";
+  return (format as InputFormat) ?? "unknown";
 }
 
 export async function detectStructure(
@@ -1158,6 +1756,38 @@ export async function detectStructure(
   return result ?? null;
 }
 
+// Read only the header row of CSV or TSV input, e.g. to list the columns of a
+// very large file without running full detection.
+export async function extractHeader(
+  input: DetectInput,
+  config: HeaderOptions = {},
+  opts: DetectOptions = {}
+): Promise<CsvHeader | null> {
+  const wasmModule = await loadDetectionWasm(!!opts.debug);
+  const sample = await readSample(input, opts.maxBytes);
+  if (!wasmModule.extractHeader) {
+    throw new Error("extractHeader is not supported by this WASM build");
+  }
+  return wasmModule.extractHeader(sample, config) ?? null;
+}
+
+// Extrapolate the record count of an input of totalBytes from its first
+// bytes, e.g. to show the size of a large file before converting it.
+// Returns null for compressed input.
+export async function estimateRecords(
+  sample: DetectInput,
+  totalBytes: number,
+  formatHint?: Format,
+  opts: DetectOptions = {}
+): Promise<RecordEstimate | null> {
+  const wasmModule = await loadDetectionWasm(!!opts.debug);
+  const bytes = await readSample(sample, opts.maxBytes);
+  if (!wasmModule.estimateRecords) {
+    throw new Error("estimateRecords is not supported by this WASM build");
+  }
+  return wasmModule.estimateRecords(bytes, totalBytes, formatHint) ?? null;
+}
+
 // Backward compatibility functions - these now use the unified detectStructure internally
 export async function detectCsvFieldsAndDelimiter(
   input: DetectInput,
@@ -1187,6 +1817,80 @@ export async function detectXmlElements(
   return null;
 }
 
+// Register a custom function callable from transform `compute` expressions.
+// Only converters created after registration can call it.
+export async function registerExpressionFunction(
+  name: string,
+  fn: ExpressionFunction,
+  opts: { debug?: boolean } = {}
+): Promise<void> {
+  const wasmModule = await loadDetectionWasm(!!opts.debug);
+  if (!wasmModule.registerExpressionFunction) {
+    throw new Error("registerExpressionFunction is not supported by this WASM build");
+  }
+  wasmModule.registerExpressionFunction(name, fn);
+}
+
+// Limit how many threads converters may use, e.g. to keep an interactive app
+// responsive during background conversions. Converters with their own
+// `threads` option ignore it; 0 removes the limit. Returns false when this
+// WASM build is single-threaded, in which case the limit has no effect.
+export async function setThreadPoolSize(
+  threads: number,
+  opts: { debug?: boolean } = {}
+): Promise<boolean> {
+  if (!Number.isInteger(threads) || threads < 0) {
+    throw new Error(`Invalid thread count: ${threads}. Must be a non-negative integer`);
+  }
+  const wasmModule = await loadDetectionWasm(!!opts.debug);
+  return wasmModule.setThreadPoolSize?.(threads) ?? false;
+}
+
+// Send the converter's log records to `sink` instead of the console, e.g. to
+// forward them to your own logger. Pass null to go back to the console.
+export async function setLogSink(
+  sink: LogSink | null,
+  opts: { debug?: boolean } = {}
+): Promise<void> {
+  const wasmModule = await loadDetectionWasm(!!opts.debug);
+  if (!wasmModule.setLogSink) {
+    throw new Error("setLogSink is not supported by this WASM build");
+  }
+  wasmModule.setLogSink(sink);
+}
+
+// Split a mixed NDJSON stream into one NDJSON output per value of `config.field`,
+// applying each route's transform (or passing records through when it is null).
+export async function demuxNdjson(
+  input: Uint8Array | string,
+  config: DemuxConfig,
+  opts: { debug?: boolean } = {}
+): Promise<Record<string, Uint8Array>> {
+  const wasmModule = await loadDetectionWasm(!!opts.debug);
+  const Demuxer = (wasmModule as any).Demuxer;
+  if (!Demuxer) {
+    throw new Error("Demuxer is not supported by this WASM build");
+  }
+
+  const bytes = typeof input === "string" ? new TextEncoder().encode(input) : input;
+  const demuxer = new Demuxer(config);
+  const parts: Record<string, Uint8Array>[] = [demuxer.push(bytes), demuxer.finish()];
+
+  const chunks: Record<string, Uint8Array[]> = {};
+  for (const part of parts) {
+    for (const [route, chunk] of Object.entries(part)) {
+      (chunks[route] ??= []).push(chunk);
+    }
+  }
+
+  const outputs: Record<string, Uint8Array> = {};
+  for (const [route, routeChunks] of Object.entries(chunks)) {
+    const total = routeChunks.reduce((sum, chunk) => sum + chunk.length, 0);
+    outputs[route] = concatChunks(routeChunks, total);
+  }
+  return outputs;
+}
+
 // Helper to auto-detect format and CSV/XML configuration from sample data
 export async function autoDetectConfig(
   input: DetectInput,
@@ -1208,7 +1912,8 @@ export async function autoDetectConfig(
     if (csvDetection) {
       result.csvConfig = {
         delimiter: csvDetection.delimiter,
-        hasHeaders: csvDetection.fields.length > 0,
+        quote: csvDetection.quote,
+        hasHeaders: csvDetection.hasHeaders ?? csvDetection.fields.length > 0,
       };
     }
   } else if (format === "xml") {
@@ -1223,6 +1928,111 @@ export async function autoDetectConfig(
   return result;
 }
 
+export type FindRecordsOptions = {
+  inputFormat?: Format | "auto";
+  csvConfig?: CsvConfig;
+  xmlConfig?: XmlConfig;
+  limit?: number; // Stop scanning after this many matches
+  debug?: boolean;
+};
+
+async function* iterateChunks(input: DetectInput): AsyncGenerator<Uint8Array> {
+  if (typeof input === "string") {
+    yield new TextEncoder().encode(input);
+  } else if (input instanceof Uint8Array) {
+    yield input;
+  } else if (input instanceof ArrayBuffer) {
+    yield new Uint8Array(input);
+  } else if (isReadableStream(input)) {
+    const reader = input.getReader();
+    try {
+      while (true) {
+        const { value, done } = await reader.read();
+        if (done || !value) break;
+        yield value;
+      }
+    } finally {
+      await reader.cancel();
+    }
+  } else {
+    for await (const chunk of input as AsyncIterable<Uint8Array>) {
+      yield chunk instanceof Uint8Array ? chunk : new Uint8Array(chunk);
+    }
+  }
+}
+
+// Stream records out of any supported format and return those matching `query`,
+// without converting the whole input.
+export async function findRecords(
+  input: DetectInput,
+  query: RecordQuery,
+  opts: FindRecordsOptions = {}
+): Promise<Record<string, unknown>[]> {
+  const wasmModule = await loadDetectionWasm(!!opts.debug);
+  const RecordFinder = (wasmModule as any).RecordFinder;
+  if (!RecordFinder) {
+    throw new Error("findRecords is not supported by this WASM build");
+  }
+
+  let finder: any = null;
+  for await (const chunk of iterateChunks(input)) {
+    if (!finder) {
+      let { inputFormat, csvConfig, xmlConfig } = opts;
+      if (!inputFormat || inputFormat === "auto") {
+        const detected = await autoDetectConfig(chunk, { debug: opts.debug });
+        if (detected.format === "unknown") {
+          throw new Error("Could not detect input format; pass opts.inputFormat");
+        }
+        inputFormat = detected.format;
+        csvConfig = csvConfig ?? detected.csvConfig;
+        xmlConfig = xmlConfig ?? detected.xmlConfig;
+      }
+      finder = new RecordFinder(inputFormat, query, csvConfig ?? null, xmlConfig ?? null, opts.limit);
+    }
+    if (finder.push(chunk)) break;
+  }
+
+  return finder ? finder.finish() : [];
+}
+
+export type GroupPart = {
+  key: string | null; // value of the group field; null when it is missing
+  data: Uint8Array;
+  complete: boolean; // true on the last part of a group
+};
+
+// Convert input that is grouped (e.g. sorted) by the `groupBy` field, yielding
+// each group's output as it is produced. Every group is a complete document in
+// the output format, such as its own JSON array or CSV with its own header.
+// A group ends where the value changes, so only one group is held at a time.
+export async function* convertGroups(
+  input: DetectInput,
+  groupBy: string,
+  opts: ConvertBuddyOptions & { outputFormat: OutputFormat }
+): AsyncGenerator<GroupPart> {
+  await initWasm(!!opts.debug);
+  const wasmModule = await loadWasmModule();
+  const GroupBatcher = (wasmModule as any).GroupBatcher;
+  if (!GroupBatcher) {
+    throw new Error("convertGroups is not supported by this WASM build");
+  }
+
+  const batcher = new GroupBatcher(
+    groupBy,
+    opts.inputFormat ?? "auto",
+    opts.outputFormat,
+    opts.chunkTargetBytes || (512 * 1024),
+    opts.csvConfig || null,
+    opts.xmlConfig || null,
+    opts.transform || null,
+    opts.options || null
+  );
+  for await (const chunk of iterateChunks(input)) {
+    yield* batcher.push(chunk) as GroupPart[];
+  }
+  yield* batcher.finish() as GroupPart[];
+}
+
 // Web Streams TransformStream adapter
 export class ConvertBuddyTransformStream extends TransformStream<Uint8Array, Uint8Array> {
   constructor(opts: ConvertBuddyOptions = {}) {
@@ -1242,6 +2052,9 @@ export class ConvertBuddyTransformStream extends TransformStream<Uint8Array, Uin
         if (output.length > 0) {
           controller.enqueue(output);
         }
+        while (buddy.pendingOutputBytes() > 0) {
+          controller.enqueue(buddy.drain());
+        }
       },
 
       flush(controller) {
@@ -1253,6 +2066,9 @@ export class ConvertBuddyTransformStream extends TransformStream<Uint8Array, Uin
         if (output.length > 0) {
           controller.enqueue(output);
         }
+        while (buddy.pendingOutputBytes() > 0) {
+          controller.enqueue(buddy.drain());
+        }
 
         if (opts.profile) {
           const stats = buddy.stats();
@@ -1263,6 +2079,37 @@ export class ConvertBuddyTransformStream extends TransformStream<Uint8Array, Uin
   }
 }
 
+// A TransformStream run entirely by the WASM converter, for
+// readable.pipeThrough(stream).pipeTo(writable). Chunks are pushed and
+// enqueued in pieces of at most chunkTargetBytes, and both queues are
+// measured in bytes so a slow reader holds back writes.
+export async function createTransformStream(
+  config: TransformStreamConfig
+): Promise<TransformStream<Uint8Array, Uint8Array>> {
+  await initWasm(false);
+  const wasmModule = await loadWasmModule();
+  if (!wasmModule.createTransformStream) {
+    throw new Error("createTransformStream is not supported by this WASM build");
+  }
+  return wasmModule.createTransformStream(config);
+}
+
+// Convert a whole input in a single WASM call, which detects the input
+// format and settings, converts and finishes. Takes the same config as
+// createTransformStream.
+export async function convertBytes(
+  input: Uint8Array | string,
+  config: TransformStreamConfig
+): Promise<Uint8Array> {
+  await initWasm(false);
+  const wasmModule = await loadWasmModule();
+  if (!wasmModule.convert) {
+    throw new Error("convertBytes is not supported by this WASM build");
+  }
+  const bytes = typeof input === "string" ? new TextEncoder().encode(input) : input;
+  return wasmModule.convert(bytes, config);
+}
+
 // Utility: Convert entire buffer/string
 export async function convert(
   input: Uint8Array | string,
@@ -1271,7 +2118,7 @@ export async function convert(
   try {
     // Validate outputFormat early
     if (opts.outputFormat) {
-      const validFormats = ['csv', 'json', 'ndjson', 'xml'];
+      const validFormats = ['csv', 'tsv', 'json', 'ndjson', 'xml', 'parquet', 'yaml'];
       if (!validFormats.includes(opts.outputFormat)) {
         throw new Error(`Invalid outputFormat: "${opts.outputFormat}". Must be one of: ${validFormats.join(', ')}`);
       }
@@ -1279,7 +2126,7 @@ export async function convert(
 
     // Validate inputFormat early (if specified)
     if (opts.inputFormat && opts.inputFormat !== 'auto') {
-      const validFormats = ['csv', 'json', 'ndjson', 'xml'];
+      const validFormats = ['csv', 'tsv', 'json', 'ndjson', 'xml', 'xlsx', 'yaml'];
       if (!validFormats.includes(opts.inputFormat)) {
         throw new Error(`Invalid inputFormat: "${opts.inputFormat}". Must be one of: ${validFormats.join(', ')}, or "auto"`);
       }
@@ -1319,13 +2166,13 @@ export async function convert(
 
     const buddy = await ConvertBuddy.create(actualOpts);
 
-    const output = buddy.push(inputBytes);
-    const final = buddy.finish();
+    const outputs = [buddy.push(inputBytes)];
+    drainPending(buddy, outputs);
+    outputs.push(buddy.finish());
+    drainPending(buddy, outputs);
 
     // Combine outputs
-    const result = new Uint8Array(output.length + final.length);
-    result.set(output, 0);
-    result.set(final, output.length);
+    const result = concatChunks(outputs, outputs.reduce((sum, arr) => sum + arr.length, 0));
 
     if (opts.profile) {
       const stats = buddy.stats();
@@ -1379,19 +2226,21 @@ export async function convertAny(
 ): Promise<Uint8Array> {
   // Validate outputFormat is provided
   if (!opts.outputFormat) {
-    throw new Error('outputFormat is required. Must be one of: csv, json, ndjson, xml');
+    throw new Error('outputFormat is required. Must be one of: csv, tsv, json, ndjson, xml');
   }
 
   // Validate outputFormat value
-  const validFormats = ['csv', 'json', 'ndjson', 'xml'];
-  if (!validFormats.includes(opts.outputFormat)) {
-    throw new Error(`Invalid outputFormat: "${opts.outputFormat}". Must be one of: ${validFormats.join(', ')}`);
+  const validFormats = ['csv', 'tsv', 'json', 'ndjson', 'xml'];
+  const validOutputFormats = [...validFormats, 'parquet', 'yaml'];
+  const validInputFormats = [...validFormats, 'xlsx', 'yaml'];
+  if (!validOutputFormats.includes(opts.outputFormat)) {
+    throw new Error(`Invalid outputFormat: "${opts.outputFormat}". Must be one of: ${validOutputFormats.join(', ')}`);
   }
 
   // Validate inputFormat if specified
   if (opts.inputFormat && opts.inputFormat !== 'auto') {
-    if (!validFormats.includes(opts.inputFormat)) {
-      throw new Error(`Invalid inputFormat: "${opts.inputFormat}". Must be one of: ${validFormats.join(', ')}, or "auto"`);
+    if (!validInputFormats.includes(opts.inputFormat)) {
+      throw new Error(`Invalid inputFormat: "${opts.inputFormat}". Must be one of: ${validInputFormats.join(', ')}, or "auto"`);
     }
   }
 
@@ -1425,7 +2274,7 @@ export async function convertAnyToString(
  * @example
  * const mimeType = getMimeType("json"); // "application/json"
  */
-export function getMimeType(format: Format): string {
+export function getMimeType(format: OutputFormat): string {
   switch (format) {
     case "json":
       return "application/json";
@@ -1433,8 +2282,14 @@ export function getMimeType(format: Format): string {
       return "application/x-ndjson";
     case "csv":
       return "text/csv";
+    case "tsv":
+      return "text/tab-separated-values";
     case "xml":
       return "application/xml";
+    case "parquet":
+      return "application/vnd.apache.parquet";
+    case "yaml":
+      return "application/yaml";
   }
 }
 
@@ -1444,7 +2299,7 @@ export function getMimeType(format: Format): string {
  * @example
  * const ext = getExtension("json"); // "json"
  */
-export function getExtension(format: Format): string {
+export function getExtension(format: OutputFormat): string {
   return format;
 }
 
@@ -1461,7 +2316,7 @@ export function getExtension(format: Format): string {
  */
 export function getSuggestedFilename(
   originalName: string,
-  outputFormat: Format,
+  outputFormat: OutputFormat,
   includeTimestamp = false
 ): string {
   const baseName = originalName.replace(/\.[^/.]+$/, "");
@@ -1481,7 +2336,7 @@ export function getSuggestedFilename(
  * const types = getFileTypeConfig("json");
  * const handle = await showSaveFilePicker({ types });
  */
-export function getFileTypeConfig(format: Format): Array<{
+export function getFileTypeConfig(format: OutputFormat): Array<{
   description: string;
   accept: Record<string, string[]>;
 }> {
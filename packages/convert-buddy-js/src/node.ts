@@ -100,6 +100,9 @@ export async function createNodeTransform(
             if (output.length > 0) {
               this.push(Buffer.from(output));
             }
+            while (buddy!.pendingOutputBytes() > 0) {
+              this.push(Buffer.from(buddy!.drain()));
+            }
             callback();
           } catch (e) {
             callback(e);
@@ -125,6 +128,9 @@ export async function createNodeTransform(
               if (output.length > 0) {
                 this.push(Buffer.from(output));
               }
+              while (buddy.pendingOutputBytes() > 0) {
+                this.push(Buffer.from(buddy.drain()));
+              }
 
               if (opts.profile) {
                 const stats = buddy.stats();